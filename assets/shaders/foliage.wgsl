@@ -0,0 +1,119 @@
+// Same imports as <https://github.com/bevyengine/bevy/blob/main/crates/bevy_pbr/src/render/pbr.wgsl>
+#import bevy_pbr::mesh_view_bindings
+#import bevy_pbr::pbr_bindings
+#import bevy_pbr::mesh_bindings
+#import bevy_pbr::mesh_functions
+
+#import bevy_pbr::utils
+#import bevy_pbr::clustered_forward
+#import bevy_pbr::lighting
+#import bevy_pbr::shadows
+#import bevy_pbr::pbr_functions
+
+struct Wind {
+    direction: vec2<f32>,
+    strength: f32,
+    frequency: f32,
+    time: f32,
+    _wasm_padding1: f32,
+    _wasm_padding2: f32,
+    _wasm_padding3: f32,
+}
+
+@group(1) @binding(0)
+var texture: texture_2d<f32>;
+@group(1) @binding(1)
+var texture_sampler: sampler;
+@group(1) @binding(2)
+var<uniform> wind: Wind;
+
+struct Vertex {
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) uv: vec2<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    #import bevy_pbr::mesh_vertex_output
+}
+
+/// Horizontal offset of a vertex at the given height above the mesh's origin
+fn get_sway(world_position: vec3<f32>, height: f32) -> vec3<f32> {
+    // Plants further downwind sway later, so that the wind seems to travel across the foliage
+    let phase = dot(world_position.xz, wind.direction) * 0.5;
+    let wave = sin(wind.time * wind.frequency * 2. * PI - phase);
+    // The base stays in place while the tip bends the most
+    let bend = height * height;
+    // Leaning with the wind and swaying back and forth around that
+    let amount = wind.strength * bend * (0.6 + 0.4 * wave);
+    return vec3(wind.direction.x, 0., wind.direction.y) * amount;
+}
+
+@vertex
+fn vertex(vertex: Vertex) -> VertexOutput {
+    var out: VertexOutput;
+    var world_position = mesh_position_local_to_world(mesh.model, vec4<f32>(vertex.position, 1.0));
+    let height = max(vertex.position.y, 0.);
+    world_position = world_position + vec4(get_sway(world_position.xyz, height), 0.);
+    out.world_position = world_position;
+    out.clip_position = mesh_position_world_to_clip(world_position);
+    out.world_normal = mesh_normal_local_to_world(vertex.normal);
+    out.uv = vertex.uv;
+    return out;
+}
+
+struct FragmentInput {
+    @builtin(front_facing) is_front: bool,
+    @builtin(position) frag_coord: vec4<f32>,
+    #import bevy_pbr::mesh_vertex_output
+}
+
+/// Adapted from <https://github.com/bevyengine/bevy/blob/main/crates/bevy_pbr/src/render/pbr.wgsl#L30>
+fn get_pbr_output(in: FragmentInput) -> vec4<f32> {
+    var material = standard_material_new();
+    material.perceptual_roughness = 1.0;
+    material.flags = material.flags | STANDARD_MATERIAL_FLAGS_DOUBLE_SIDED_BIT;
+
+    var pbr_input = pbr_input_new();
+    pbr_input.frag_coord = in.frag_coord;
+    pbr_input.world_position = in.world_position;
+    pbr_input.material = material;
+    pbr_input.world_normal = prepare_world_normal(
+        in.world_normal,
+        (material.flags & STANDARD_MATERIAL_FLAGS_DOUBLE_SIDED_BIT) != 0u,
+        in.is_front,
+    );
+
+    pbr_input.is_orthographic = view.projection[3].w == 1.0;
+
+    pbr_input.N = apply_normal_mapping(
+        material.flags,
+        pbr_input.world_normal,
+        in.uv,
+    );
+    pbr_input.V = calculate_view(in.world_position, pbr_input.is_orthographic);
+
+    var output_color = pbr(pbr_input);
+
+    output_color = tone_mapping(output_color);
+    var output_rgb = output_color.rgb;
+    output_rgb = pow(output_rgb, vec3<f32>(1.0 / 2.2));
+    output_rgb = output_rgb + screen_space_dither(in.frag_coord.xy);
+    // This conversion back to linear space is required because our output texture format is
+    // SRGB; the GPU will assume our output is linear and will apply an SRGB conversion.
+    output_rgb = pow(output_rgb, vec3<f32>(2.2));
+    output_color = vec4(output_rgb, output_color.a);
+
+    return output_color;
+}
+
+@fragment
+fn fragment(in: FragmentInput) -> @location(0) vec4<f32> {
+    let color = textureSample(texture, texture_sampler, in.uv);
+    // Cut out the gaps between leaves
+    if (color.a < 0.5) {
+        discard;
+    }
+    return color * get_pbr_output(in);
+}
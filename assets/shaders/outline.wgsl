@@ -0,0 +1,60 @@
+// Same imports as <https://github.com/bevyengine/bevy/blob/main/crates/bevy_pbr/src/render/mesh.wgsl>
+#import bevy_pbr::mesh_view_bindings
+#import bevy_pbr::mesh_bindings
+#import bevy_pbr::mesh_functions
+
+struct Outline {
+    color: vec4<f32>,
+    thickness: f32,
+    pulse_amount: f32,
+    pulse_frequency: f32,
+    time: f32,
+}
+
+@group(1) @binding(0)
+var<uniform> outline: Outline;
+
+struct Vertex {
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+#ifdef SKINNED
+    @location(5) joint_indices: vec4<u32>,
+    @location(6) joint_weights: vec4<f32>,
+#endif
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+}
+
+/// Renders the back faces of the mesh pushed outwards along their normals, so that they peek out around the mesh itself.
+/// The offset is done in screen space, so that the outline is equally thick at every distance.
+@vertex
+fn vertex(vertex: Vertex) -> VertexOutput {
+#ifdef SKINNED
+    let model = skin_model(vertex.joint_indices, vertex.joint_weights);
+    let world_normal = skin_normals(model, vertex.normal);
+#else
+    let model = mesh.model;
+    let world_normal = mesh_normal_local_to_world(vertex.normal);
+#endif
+    let world_position = mesh_position_local_to_world(model, vec4<f32>(vertex.position, 1.0));
+    let clip_position = mesh_position_world_to_clip(world_position);
+    let clip_normal = (view.view_proj * vec4(world_normal, 0.)).xy;
+
+    var out: VertexOutput;
+    out.clip_position = clip_position;
+    // Normals pointing straight at the camera have no direction on screen
+    if (length(clip_normal) > 0.0001) {
+        let offset = normalize(clip_normal) * outline.thickness * 2. / view.viewport.zw * clip_position.w;
+        out.clip_position = vec4(clip_position.xy + offset, clip_position.zw);
+    }
+    return out;
+}
+
+@fragment
+fn fragment() -> @location(0) vec4<f32> {
+    let pulse = 0.5 + 0.5 * sin(outline.time * outline.pulse_frequency * 2. * 3.14159265);
+    let brightness = 1. - outline.pulse_amount * pulse;
+    return vec4(outline.color.rgb * brightness, outline.color.a);
+}
@@ -7,6 +7,10 @@
 var texture: texture_2d<f32>;
 @group(1) @binding(1)
 var texture_sampler: sampler;
+@group(1) @binding(2)
+var<uniform> zenith_color: vec4<f32>;
+@group(1) @binding(3)
+var<uniform> horizon_color: vec4<f32>;
 
 struct FragmentInput {
     @builtin(front_facing) is_front: bool,
@@ -34,5 +38,7 @@ fn get_texture_sample(uv: vec2<f32>) -> vec4<f32> {
 fn fragment(in: FragmentInput) -> @location(0) vec4<f32> {
     var n = normalize(in.world_normal);
     let uv = dir_to_equirectangular(n);
-    return get_texture_sample(uv);
+    // Below the horizon is tinted like the horizon, so that there is no seam
+    let tint = mix(horizon_color, zenith_color, clamp(n.y, 0., 1.));
+    return get_texture_sample(uv) * vec4(tint.rgb, 1.);
 }
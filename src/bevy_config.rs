@@ -1,23 +1,35 @@
+use crate::file_system_interaction::user_settings::{read_user_settings, Graphics, UserSettings};
 use crate::util::log_error::log_errors;
 use anyhow::{Context, Result};
 use bevy::prelude::*;
+#[cfg(feature = "native")]
+use bevy::utils::Instant;
 use bevy::window::{PresentMode, WindowId};
 use bevy::winit::WinitWindows;
 use std::io::Cursor;
+#[cfg(feature = "native")]
+use std::time::Duration;
 use winit::window::Icon;
 
 /// Overrides the default Bevy plugins and configures things like the screen settings.
+/// The window is created with the graphics settings of the [`UserSettings`], which are applied again whenever they change.
 pub struct BevyConfigPlugin;
 
 impl Plugin for BevyConfigPlugin {
     fn build(&self, app: &mut App) {
+        let settings = read_user_settings();
+        let graphics = &settings.graphics;
+        // The backend's scale factor is not known before the window exists, so the size is corrected in `apply_graphics_settings`
+        let scale_factor = graphics.scale_factor_override.unwrap_or(1.);
         let default_plugins = DefaultPlugins.set(WindowPlugin {
             window: WindowDescriptor {
-                width: 800.,
-                height: 600.,
+                width: (graphics.resolution.x as f64 / scale_factor) as f32,
+                height: (graphics.resolution.y as f64 / scale_factor) as f32,
                 title: "Foxtrot".to_string(),
                 canvas: Some("#bevy".to_owned()),
-                present_mode: PresentMode::AutoVsync,
+                present_mode: get_present_mode(graphics),
+                mode: graphics.display_mode.window_mode(),
+                scale_factor_override: graphics.scale_factor_override,
                 ..default()
             },
             ..default()
@@ -30,10 +42,122 @@ impl Plugin for BevyConfigPlugin {
         app.insert_resource(Msaa { samples: 4 })
             .insert_resource(ClearColor(Color::rgb(0.4, 0.4, 0.4)))
             .add_plugins(default_plugins)
-            .add_startup_system(set_window_icon.pipe(log_errors));
+            .insert_resource(settings)
+            .init_resource::<AvailableResolutions>()
+            .add_startup_system(set_window_icon.pipe(log_errors))
+            .add_startup_system(read_available_resolutions.pipe(log_errors))
+            .add_system(apply_graphics_settings.pipe(log_errors));
+        // Browsers schedule the frames themselves
+        #[cfg(feature = "native")]
+        app.add_system_to_stage(CoreStage::Last, limit_frame_rate);
     }
 }
 
+/// Used when the platform does not tell us the resolutions of the monitor
+const FALLBACK_RESOLUTIONS: [(u32, u32); 8] = [
+    (3840, 2160),
+    (2560, 1440),
+    (1920, 1080),
+    (1600, 900),
+    (1366, 768),
+    (1280, 720),
+    (1024, 768),
+    (800, 600),
+];
+
+/// Resolutions the current monitor supports in physical pixels, from largest to smallest
+#[derive(Debug, Clone, PartialEq, Eq, Resource)]
+pub struct AvailableResolutions(pub Vec<UVec2>);
+
+impl Default for AvailableResolutions {
+    fn default() -> Self {
+        Self(
+            FALLBACK_RESOLUTIONS
+                .iter()
+                .map(|(width, height)| UVec2::new(*width, *height))
+                .collect(),
+        )
+    }
+}
+
+fn read_available_resolutions(
+    windows: NonSend<WinitWindows>,
+    mut available_resolutions: ResMut<AvailableResolutions>,
+) -> Result<()> {
+    let primary = windows
+        .get_window(WindowId::primary())
+        .context("Failed to get primary window")?;
+    let monitor = match primary.current_monitor() {
+        Some(monitor) => monitor,
+        None => return Ok(()),
+    };
+    let mut resolutions: Vec<_> = monitor
+        .video_modes()
+        .map(|video_mode| {
+            let size = video_mode.size();
+            UVec2::new(size.width, size.height)
+        })
+        .collect();
+    // Every resolution is listed once per refresh rate and bit depth
+    resolutions.sort_unstable_by_key(|resolution| std::cmp::Reverse((resolution.x, resolution.y)));
+    resolutions.dedup();
+    if !resolutions.is_empty() {
+        available_resolutions.0 = resolutions;
+    }
+    Ok(())
+}
+
+fn get_present_mode(graphics: &Graphics) -> PresentMode {
+    if graphics.vsync {
+        PresentMode::AutoVsync
+    } else {
+        PresentMode::AutoNoVsync
+    }
+}
+
+fn apply_graphics_settings(
+    settings: Res<UserSettings>,
+    mut windows: ResMut<Windows>,
+) -> Result<()> {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("apply_graphics_settings").entered();
+    if !settings.is_changed() {
+        return Ok(());
+    }
+    let graphics = &settings.graphics;
+    let window = windows
+        .get_primary_mut()
+        .context("Failed to get primary window")?;
+    window.set_mode(graphics.display_mode.window_mode());
+    window.set_present_mode(get_present_mode(graphics));
+    window.set_scale_factor_override(graphics.scale_factor_override);
+    // The window's size is logical, the setting's is physical
+    let scale_factor = graphics
+        .scale_factor_override
+        .unwrap_or_else(|| window.backend_scale_factor());
+    window.set_resolution(
+        (graphics.resolution.x as f64 / scale_factor) as f32,
+        (graphics.resolution.y as f64 / scale_factor) as f32,
+    );
+    Ok(())
+}
+
+/// Sleeps for the rest of the frame when it was faster than the frame rate cap allows
+#[cfg(feature = "native")]
+fn limit_frame_rate(settings: Res<UserSettings>, mut last_frame_end: Local<Option<Instant>>) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("limit_frame_rate").entered();
+    let frame_rate_cap = settings.graphics.frame_rate_cap.filter(|cap| *cap > 0);
+    if let (Some(frame_rate_cap), Some(last_frame_end)) = (frame_rate_cap, *last_frame_end) {
+        let frame_time = Duration::from_secs_f64(1. / frame_rate_cap as f64);
+        let elapsed = last_frame_end.elapsed();
+        if elapsed < frame_time {
+            std::thread::sleep(frame_time - elapsed);
+        }
+    }
+    *last_frame_end = Some(Instant::now());
+}
+
 // Sets the icon on Windows and X11
 fn set_window_icon(windows: NonSend<WinitWindows>) -> Result<()> {
     let primary = windows
@@ -1,11 +1,25 @@
+use crate::dev::console::ConsolePlugin;
+use crate::dev::debug_overlays::DebugOverlayPlugin;
 use crate::dev::dev_editor::DevEditorPlugin;
+use crate::dev::editor_history::EditorHistoryPlugin;
+use crate::dev::editor_saving::EditorSavePlugin;
+use crate::dev::level_reload::LevelReloadPlugin;
+use crate::dev::performance_overlay::PerformanceOverlayPlugin;
+use crate::dev::transform_gizmo::TransformGizmoPlugin;
 use bevy::diagnostic::{FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin};
 use bevy::prelude::*;
 use bevy_editor_pls::prelude::*;
 use bevy_prototype_debug_lines::DebugLinesPlugin;
 use bevy_rapier3d::prelude::*;
 
+pub mod console;
+pub mod debug_overlays;
 pub mod dev_editor;
+pub mod editor_history;
+pub mod editor_saving;
+pub mod level_reload;
+pub mod performance_overlay;
+pub mod transform_gizmo;
 
 /// Plugin with debugging utility intended for use during development only.
 /// Don't include this in a release build.
@@ -19,6 +33,13 @@ impl Plugin for DevPlugin {
                 .add_plugin(FrameTimeDiagnosticsPlugin::default())
                 .add_plugin(DebugLinesPlugin::default())
                 .add_plugin(DevEditorPlugin)
+                .add_plugin(EditorHistoryPlugin)
+                .add_plugin(EditorSavePlugin)
+                .add_plugin(TransformGizmoPlugin)
+                .add_plugin(LevelReloadPlugin)
+                .add_plugin(ConsolePlugin)
+                .add_plugin(DebugOverlayPlugin)
+                .add_plugin(PerformanceOverlayPlugin)
                 .add_plugin(LogDiagnosticsPlugin::filtered(vec![]))
                 .add_plugin(RapierDebugRenderPlugin {
                     enabled: false,
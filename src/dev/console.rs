@@ -0,0 +1,337 @@
+use crate::dev::console::commands::{
+    CameraCommand, FlagCommand, GiveCommand, ReloadLevelCommand, SetCommand, SpawnCommand,
+    TeleportCommand,
+};
+use crate::player_control::actions::ActionsFrozen;
+use anyhow::{bail, Result};
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use bevy_egui::{egui, EguiContext};
+
+mod commands;
+
+/// Quake-style console toggled with the key left of 1. While it is open, typing goes to the console instead of the player.
+/// Commands implement [`ConsoleCommand`] and are registered from any plugin with [`AddConsoleCommand::add_console_command`].
+/// Up and down recall earlier input, tab completes command names, and `help` lists all commands.
+pub struct ConsolePlugin;
+
+impl Plugin for ConsolePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ConsoleState>()
+            .init_resource::<ConsoleCommands>()
+            .add_console_command(SpawnCommand)
+            .add_console_command(TeleportCommand)
+            .add_console_command(SetCommand)
+            .add_console_command(GiveCommand)
+            .add_console_command(FlagCommand)
+            .add_console_command(CameraCommand)
+            .add_console_command(ReloadLevelCommand)
+            .add_system(toggle_console)
+            .add_system(show_console.after(toggle_console))
+            .add_system(run_console_commands.after(show_console));
+    }
+}
+
+const TOGGLE_KEY: KeyCode = KeyCode::Grave;
+/// Number of lines kept in the scrollback
+const MAX_SCROLLBACK: usize = 500;
+
+/// A command that can be typed into the console
+pub trait ConsoleCommand: Send + Sync + 'static {
+    /// Word that invokes the command
+    fn name(&self) -> &'static str;
+    /// Arguments as shown by `help`, e.g. "<x> <y> <z>"
+    fn usage(&self) -> &'static str;
+    /// Runs the command with the words typed after its name and returns the text to print
+    fn run(&self, args: &[&str], world: &mut World) -> Result<String>;
+}
+
+pub trait AddConsoleCommand {
+    fn add_console_command(&mut self, command: impl ConsoleCommand) -> &mut Self;
+}
+
+impl AddConsoleCommand for App {
+    fn add_console_command(&mut self, command: impl ConsoleCommand) -> &mut Self {
+        self.init_resource::<ConsoleCommands>();
+        self.world
+            .resource_mut::<ConsoleCommands>()
+            .0
+            .insert(command.name(), Box::new(command));
+        self
+    }
+}
+
+/// All commands known to the console by name
+#[derive(Resource, Default)]
+pub struct ConsoleCommands(HashMap<&'static str, Box<dyn ConsoleCommand>>);
+
+impl ConsoleCommands {
+    fn names(&self) -> Vec<&'static str> {
+        let mut names: Vec<_> = self.0.keys().copied().chain(BUILTINS).collect();
+        names.sort_unstable();
+        names
+    }
+
+    fn run(&self, line: &str, world: &mut World) -> Result<String> {
+        let words: Vec<_> = line.split_whitespace().collect();
+        let (name, args) = match words.split_first() {
+            Some((name, args)) => (*name, args),
+            None => return Ok(String::new()),
+        };
+        match (name, self.0.get(name)) {
+            ("help", _) => Ok(self
+                .names()
+                .into_iter()
+                .map(|name| match self.0.get(name) {
+                    Some(command) => format!("{name} {}", command.usage()),
+                    None => name.to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join("\n")),
+            (_, Some(command)) => command.run(args, world),
+            _ => bail!("Unknown command \"{name}\". Type \"help\" for a list of commands"),
+        }
+    }
+}
+
+/// Commands handled by the console itself
+const BUILTINS: [&str; 2] = ["help", "clear"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ConsoleLine {
+    Input(String),
+    Output(String),
+    Error(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Resource, Default)]
+pub struct ConsoleState {
+    pub open: bool,
+    input: String,
+    scrollback: Vec<ConsoleLine>,
+    /// Earlier input, oldest first
+    history: Vec<String>,
+    /// Position in the history while recalling it with the arrow keys
+    history_index: Option<usize>,
+    /// Input waiting to be run, which needs exclusive access to the world
+    submitted: Vec<String>,
+    focus_input: bool,
+}
+
+impl ConsoleState {
+    fn print(&mut self, line: ConsoleLine) {
+        self.scrollback.push(line);
+        let excess = self.scrollback.len().saturating_sub(MAX_SCROLLBACK);
+        self.scrollback.drain(..excess);
+    }
+
+    fn recall(&mut self, older: bool) {
+        if self.history.is_empty() {
+            return;
+        }
+        let index = match (self.history_index, older) {
+            (None, true) => Some(self.history.len() - 1),
+            (None, false) => None,
+            (Some(index), true) => Some(index.saturating_sub(1)),
+            (Some(index), false) if index + 1 < self.history.len() => Some(index + 1),
+            (Some(_), false) => None,
+        };
+        self.history_index = index;
+        self.input = index
+            .map(|index| self.history[index].clone())
+            .unwrap_or_default();
+    }
+}
+
+fn toggle_console(
+    keyboard: Res<Input<KeyCode>>,
+    mut console: ResMut<ConsoleState>,
+    mut actions_frozen: ResMut<ActionsFrozen>,
+) {
+    if !keyboard.just_pressed(TOGGLE_KEY) {
+        return;
+    }
+    console.open = !console.open;
+    if console.open {
+        actions_frozen.freeze();
+        console.focus_input = true;
+    } else {
+        actions_frozen.unfreeze();
+    }
+}
+
+fn show_console(
+    mut egui_context: ResMut<EguiContext>,
+    mut console: ResMut<ConsoleState>,
+    commands: Res<ConsoleCommands>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("show_console").entered();
+    if !console.open {
+        return;
+    }
+    let console = &mut *console;
+    egui::TopBottomPanel::top("console")
+        .resizable(true)
+        .default_height(250.)
+        .show(egui_context.ctx_mut(), |ui| {
+            egui::ScrollArea::vertical()
+                .auto_shrink([false; 2])
+                .stick_to_bottom(true)
+                .max_height(ui.available_height() - 30.)
+                .show(ui, |ui| {
+                    for line in console.scrollback.iter() {
+                        let text = match line {
+                            ConsoleLine::Input(text) => egui::RichText::new(format!("> {text}"))
+                                .color(ui.visuals().weak_text_color()),
+                            ConsoleLine::Output(text) => egui::RichText::new(text),
+                            ConsoleLine::Error(text) => {
+                                egui::RichText::new(text).color(ui.visuals().error_fg_color)
+                            }
+                        };
+                        ui.label(text.monospace());
+                    }
+                });
+
+            // Consumed before the text field sees them, so that they don't move the cursor or the focus
+            let (older, newer, complete) = {
+                let mut input = ui.ctx().input_mut();
+                (
+                    input.consume_key(egui::Modifiers::NONE, egui::Key::ArrowUp),
+                    input.consume_key(egui::Modifiers::NONE, egui::Key::ArrowDown),
+                    input.consume_key(egui::Modifiers::NONE, egui::Key::Tab),
+                )
+            };
+            if older || newer {
+                console.recall(older);
+            }
+            if complete {
+                complete_input(console, &commands.names());
+            }
+
+            let response = ui.add(
+                egui::TextEdit::singleline(&mut console.input)
+                    .font(egui::TextStyle::Monospace)
+                    .desired_width(f32::INFINITY)
+                    .lock_focus(true),
+            );
+            // The key that opened the console is typed into the field as well
+            console.input.retain(|character| character != '`');
+            if console.focus_input {
+                response.request_focus();
+                console.focus_input = false;
+            }
+            if response.lost_focus() && ui.input().key_pressed(egui::Key::Enter) {
+                let line = std::mem::take(&mut console.input);
+                let line = line.trim();
+                if !line.is_empty() {
+                    if console.history.last().map(String::as_str) != Some(line) {
+                        console.history.push(line.to_string());
+                    }
+                    console.submitted.push(line.to_string());
+                }
+                console.history_index = None;
+                response.request_focus();
+            }
+        });
+}
+
+fn complete_input(console: &mut ConsoleState, names: &[&'static str]) {
+    // Only the command name is completed
+    if console.input.contains(char::is_whitespace) {
+        return;
+    }
+    let candidates: Vec<_> = names
+        .iter()
+        .filter(|name| name.starts_with(console.input.as_str()))
+        .collect();
+    match candidates.as_slice() {
+        [] => {}
+        [name] => console.input = format!("{name} "),
+        [first, rest @ ..] => {
+            let common_prefix = rest.iter().fold(first.to_string(), |prefix, name| {
+                prefix
+                    .chars()
+                    .zip(name.chars())
+                    .take_while(|(a, b)| a == b)
+                    .map(|(a, _)| a)
+                    .collect()
+            });
+            console.input = common_prefix;
+            let candidates = candidates
+                .iter()
+                .map(|name| name.to_string())
+                .collect::<Vec<_>>()
+                .join("  ");
+            console.print(ConsoleLine::Output(candidates));
+        }
+    }
+}
+
+fn run_console_commands(world: &mut World) {
+    let submitted = std::mem::take(&mut world.resource_mut::<ConsoleState>().submitted);
+    if submitted.is_empty() {
+        return;
+    }
+    world.resource_scope(|world, commands: Mut<ConsoleCommands>| {
+        for line in submitted {
+            if line == "clear" {
+                world.resource_mut::<ConsoleState>().scrollback.clear();
+                continue;
+            }
+            let result = commands.run(&line, world);
+            let mut console = world.resource_mut::<ConsoleState>();
+            console.print(ConsoleLine::Input(line));
+            match result {
+                Ok(output) if output.is_empty() => {}
+                Ok(output) => console.print(ConsoleLine::Output(output)),
+                Err(error) => console.print(ConsoleLine::Error(format!("{error:#}"))),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn completes_command_names() {
+        let names = ["camera", "clear", "flag", "give"];
+        let mut console = ConsoleState {
+            input: "g".to_string(),
+            ..default()
+        };
+        complete_input(&mut console, &names);
+        assert_eq!(console.input, "give ");
+
+        console.input = "c".to_string();
+        complete_input(&mut console, &names);
+        assert_eq!(console.input, "c");
+        assert_eq!(
+            console.scrollback.last(),
+            Some(&ConsoleLine::Output("camera  clear".to_string()))
+        );
+
+        console.input = "give ap".to_string();
+        complete_input(&mut console, &names);
+        assert_eq!(console.input, "give ap");
+    }
+
+    #[test]
+    fn recalls_history() {
+        let mut console = ConsoleState {
+            history: vec!["first".to_string(), "second".to_string()],
+            ..default()
+        };
+        console.recall(true);
+        assert_eq!(console.input, "second");
+        console.recall(true);
+        console.recall(true);
+        assert_eq!(console.input, "first");
+        console.recall(false);
+        assert_eq!(console.input, "second");
+        console.recall(false);
+        assert_eq!(console.input, "");
+    }
+}
@@ -0,0 +1,303 @@
+use crate::dev::console::ConsoleCommand;
+use crate::dev::level_reload::LevelReloadRequest;
+use crate::file_system_interaction::asset_loading::ConfigAssets;
+use crate::file_system_interaction::config::GameConfig;
+use crate::level_instantiation::spawning::{GameObject, SpawnRequest};
+use crate::player_control::camera::{IngameCamera, IngameCameraKind, ThirdPersonCamera};
+use crate::player_control::player_embodiment::Player;
+use crate::world_interaction::condition::{
+    ActiveConditions, ConditionAddEvent, ConditionId, ConditionRemoveEvent,
+};
+use crate::world_interaction::inventory::{Inventory, Item};
+use anyhow::{anyhow, bail, Context, Result};
+use bevy::prelude::*;
+use bevy::reflect::serde::TypedReflectDeserializer;
+use bevy::reflect::GetPath;
+use bevy_rapier3d::prelude::*;
+use serde::de::DeserializeSeed;
+use strum::IntoEnumIterator;
+
+/// Spawns an object at the given position, or in front of the player
+pub struct SpawnCommand;
+
+impl ConsoleCommand for SpawnCommand {
+    fn name(&self) -> &'static str {
+        "spawn"
+    }
+
+    fn usage(&self) -> &'static str {
+        "<object> [x y z]"
+    }
+
+    fn run(&self, args: &[&str], world: &mut World) -> Result<String> {
+        let (object, position) = match args {
+            [object] => (*object, None),
+            [object, x, y, z] => (*object, Some(parse_position([*x, *y, *z])?)),
+            _ => bail!("Usage: spawn {}", self.usage()),
+        };
+        let object = parse_game_object(object)?;
+        let position = match position {
+            Some(position) => position,
+            None => {
+                let player = get_player_transform(world)?;
+                player.translation + player.forward() * 2.
+            }
+        };
+        world.send_event(SpawnRequest {
+            object,
+            transform: Transform::from_translation(position),
+            ..default()
+        });
+        Ok(format!("Spawned {object:?} at {position}"))
+    }
+}
+
+/// Teleports the player
+pub struct TeleportCommand;
+
+impl ConsoleCommand for TeleportCommand {
+    fn name(&self) -> &'static str {
+        "tp"
+    }
+
+    fn usage(&self) -> &'static str {
+        "<x> <y> <z>"
+    }
+
+    fn run(&self, args: &[&str], world: &mut World) -> Result<String> {
+        let position = match args {
+            [x, y, z] => parse_position([*x, *y, *z])?,
+            _ => bail!("Usage: tp {}", self.usage()),
+        };
+        let mut player_query =
+            world.query_filtered::<(&mut Transform, &mut Velocity), With<Player>>();
+        let (mut transform, mut velocity) = player_query
+            .iter_mut(world)
+            .next()
+            .context("There is no player to teleport")?;
+        transform.translation = position;
+        *velocity = default();
+        Ok(format!("Teleported the player to {position}"))
+    }
+}
+
+/// Sets a value of the game config, e.g. `set camera.mouse_sensitivity_x 2.0`.
+/// The value is written in RON, so strings need quotes.
+pub struct SetCommand;
+
+impl ConsoleCommand for SetCommand {
+    fn name(&self) -> &'static str {
+        "set"
+    }
+
+    fn usage(&self) -> &'static str {
+        "<config.path> <value>"
+    }
+
+    fn run(&self, args: &[&str], world: &mut World) -> Result<String> {
+        let (path, value) = match args {
+            [path, value @ ..] if !value.is_empty() => (*path, value.join(" ")),
+            _ => bail!("Usage: set {}", self.usage()),
+        };
+        let handle = world
+            .get_resource::<ConfigAssets>()
+            .context("The config is not loaded yet")?
+            .game
+            .clone();
+        let type_registry = world.resource::<AppTypeRegistry>().clone();
+        let type_registry = type_registry.read();
+        let mut configs = world.resource_mut::<Assets<GameConfig>>();
+        let config = configs
+            .get_mut(&handle)
+            .context("Failed to get game config from handle")?;
+        let field = config
+            .reflect_path_mut(path)
+            .map_err(|error| anyhow!("Invalid config path \"{path}\": {error}"))?;
+        let registration = type_registry
+            .get_with_name(field.type_name())
+            .with_context(|| format!("Cannot set values of type {}", field.type_name()))?;
+        let mut deserializer = ron::Deserializer::from_str(&value)?;
+        let new_value = TypedReflectDeserializer::new(registration, &type_registry)
+            .deserialize(&mut deserializer)
+            .with_context(|| format!("\"{value}\" is not a valid {}", field.type_name()))?;
+        field.apply(&*new_value);
+        Ok(format!("Set {path} to {value}"))
+    }
+}
+
+/// Puts items into the inventory
+pub struct GiveCommand;
+
+impl ConsoleCommand for GiveCommand {
+    fn name(&self) -> &'static str {
+        "give"
+    }
+
+    fn usage(&self) -> &'static str {
+        "<item> [count]"
+    }
+
+    fn run(&self, args: &[&str], world: &mut World) -> Result<String> {
+        let (id, count) = match args {
+            [id] => (*id, 1),
+            [id, count] => (*id, count.parse().context("The count must be a number")?),
+            _ => bail!("Usage: give {}", self.usage()),
+        };
+        let mut inventory = world.resource_mut::<Inventory>();
+        // Items spawned in the level carry more information than the id, so reuse it if possible
+        let item = inventory
+            .0
+            .get(id)
+            .map(|held| held.item.clone())
+            .unwrap_or_else(|| Item {
+                id: id.to_string(),
+                name: id.to_string(),
+                ..default()
+            });
+        inventory.add(item, count);
+        Ok(format!(
+            "Gave {count} {id}, now holding {}",
+            inventory.count(id)
+        ))
+    }
+}
+
+/// Sets or clears a flag of the [`ActiveConditions`]
+pub struct FlagCommand;
+
+impl ConsoleCommand for FlagCommand {
+    fn name(&self) -> &'static str {
+        "flag"
+    }
+
+    fn usage(&self) -> &'static str {
+        "<name> <on|off>"
+    }
+
+    fn run(&self, args: &[&str], world: &mut World) -> Result<String> {
+        let (name, on) = match args {
+            [name, "on"] => (*name, true),
+            [name, "off"] => (*name, false),
+            _ => bail!("Usage: flag {}", self.usage()),
+        };
+        let id = ConditionId(name.to_string());
+        let was_on = world.resource::<ActiveConditions>().0.contains(&id);
+        if on {
+            world.send_event(ConditionAddEvent(id));
+        } else {
+            world.send_event(ConditionRemoveEvent(id));
+        }
+        let state = |on| if on { "on" } else { "off" };
+        Ok(format!(
+            "Turned flag {name} {}, it was {}",
+            state(on),
+            state(was_on)
+        ))
+    }
+}
+
+/// Switches the kind of the ingame camera
+pub struct CameraCommand;
+
+impl ConsoleCommand for CameraCommand {
+    fn name(&self) -> &'static str {
+        "camera"
+    }
+
+    fn usage(&self) -> &'static str {
+        "<first|third|fixed>"
+    }
+
+    fn run(&self, args: &[&str], world: &mut World) -> Result<String> {
+        let (kind, description) = match args {
+            ["first"] => ("first", "first person"),
+            ["third"] => ("third", "third person"),
+            ["fixed"] => ("fixed", "fixed angle"),
+            _ => bail!("Usage: camera {}", self.usage()),
+        };
+        let mut camera_query = world.query::<&mut IngameCamera>();
+        let mut camera = camera_query
+            .iter_mut(world)
+            .next()
+            .context("There is no ingame camera")?;
+        // The kinds can all be converted from and into a third person camera
+        let third_person = match &camera.kind {
+            IngameCameraKind::ThirdPerson(third_person) => third_person.clone(),
+            IngameCameraKind::FirstPerson(first_person) => ThirdPersonCamera::from(first_person),
+            IngameCameraKind::FixedAngle(fixed_angle) => ThirdPersonCamera::from(fixed_angle),
+        };
+        camera.kind = match kind {
+            "first" => IngameCameraKind::FirstPerson((&third_person).into()),
+            "fixed" => IngameCameraKind::FixedAngle((&third_person).into()),
+            _ => IngameCameraKind::ThirdPerson(third_person),
+        };
+        Ok(format!("Switched to the {description} camera"))
+    }
+}
+
+/// Respawns the objects of the current level, see [`LevelReloadRequest`]
+pub struct ReloadLevelCommand;
+
+impl ConsoleCommand for ReloadLevelCommand {
+    fn name(&self) -> &'static str {
+        "reload_level"
+    }
+
+    fn usage(&self) -> &'static str {
+        ""
+    }
+
+    fn run(&self, args: &[&str], world: &mut World) -> Result<String> {
+        if !args.is_empty() {
+            bail!("reload_level takes no arguments");
+        }
+        world.send_event(LevelReloadRequest);
+        Ok("Reloading the current level".to_string())
+    }
+}
+
+fn parse_position(coordinates: [&str; 3]) -> Result<Vec3> {
+    let [x, y, z] = coordinates.map(|coordinate| {
+        coordinate
+            .parse::<f32>()
+            .with_context(|| format!("\"{coordinate}\" is not a number"))
+    });
+    Ok(Vec3::new(x?, y?, z?))
+}
+
+/// Finds an object by its name, ignoring case and underscores, e.g. "moving_platform"
+fn parse_game_object(name: &str) -> Result<GameObject> {
+    let normalize = |name: &str| name.replace('_', "").to_lowercase();
+    GameObject::iter()
+        .find(|object| normalize(&format!("{object:?}")) == normalize(name))
+        .with_context(|| {
+            let objects: Vec<_> = GameObject::iter()
+                .map(|object| format!("{object:?}"))
+                .collect();
+            format!("Unknown object \"{name}\". Known objects: {objects:?}")
+        })
+}
+
+fn get_player_transform(world: &mut World) -> Result<Transform> {
+    let mut player_query = world.query_filtered::<&Transform, With<Player>>();
+    player_query
+        .iter(world)
+        .next()
+        .copied()
+        .context("There is no player, so a position is required")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parses_game_objects_loosely() {
+        assert_eq!(
+            parse_game_object("moving_platform").unwrap(),
+            GameObject::MovingPlatform
+        );
+        assert_eq!(parse_game_object("NPC").unwrap(), GameObject::Npc);
+        assert!(parse_game_object("dragon").is_err());
+    }
+}
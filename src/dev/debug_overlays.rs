@@ -0,0 +1,281 @@
+use crate::dev::dev_editor::{DevEditorState, DevEditorWindow};
+use crate::movement::general_movement::probe_ground;
+use crate::player_control::camera::{IngameCamera, IngameCameraKind, LineOfSightCorrection};
+use crate::player_control::player_embodiment::Player;
+use crate::util::log_error::log_errors;
+use crate::world_interaction::trigger::TriggerState;
+use crate::GameState;
+use anyhow::{Context, Result};
+use bevy::prelude::*;
+use bevy_editor_pls::Editor;
+use bevy_egui::EguiContext;
+use bevy_prototype_debug_lines::DebugLines;
+use bevy_rapier3d::prelude::*;
+use std::f32::consts::TAU;
+
+/// Debug drawings of the physics and the camera that help with movement problems.
+/// Each overlay is toggled with a function key or in the dev window, see [`OVERLAY_KEYS`].
+pub struct DebugOverlayPlugin;
+
+impl Plugin for DebugOverlayPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(
+            SystemSet::on_update(GameState::Playing)
+                .with_system(toggle_overlays.pipe(log_errors))
+                .with_system(draw_contacts.pipe(log_errors))
+                .with_system(draw_ground_probes.pipe(log_errors))
+                .with_system(draw_line_of_sight.pipe(log_errors))
+                .with_system(draw_sensors.pipe(log_errors)),
+        );
+    }
+}
+
+/// F5 and F9 are taken by quick saving and loading
+pub const OVERLAY_KEYS: [(KeyCode, Overlay); 7] = [
+    (KeyCode::F1, Overlay::Colliders),
+    (KeyCode::F2, Overlay::Contacts),
+    (KeyCode::F3, Overlay::GroundProbes),
+    (KeyCode::F4, Overlay::LineOfSight),
+    (KeyCode::F6, Overlay::NavigationPaths),
+    (KeyCode::F7, Overlay::Navmeshes),
+    (KeyCode::F8, Overlay::Sensors),
+];
+
+const CONTACT_COLOR: Color = Color::ORANGE;
+const SENSOR_COLOR: Color = Color::CYAN;
+const HIT_COLOR: Color = Color::RED;
+const MISS_COLOR: Color = Color::GREEN;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Overlay {
+    Colliders,
+    Contacts,
+    GroundProbes,
+    LineOfSight,
+    NavigationPaths,
+    Navmeshes,
+    Sensors,
+}
+
+impl Overlay {
+    pub fn name(self) -> &'static str {
+        match self {
+            Overlay::Colliders => "Colliders",
+            Overlay::Contacts => "Contact points",
+            Overlay::GroundProbes => "Player ground probe",
+            Overlay::LineOfSight => "Camera line of sight",
+            Overlay::NavigationPaths => "Navigation paths",
+            Overlay::Navmeshes => "Navmeshes",
+            Overlay::Sensors => "Triggers and sensors",
+        }
+    }
+
+    pub fn is_enabled(self, state: &DevEditorState) -> bool {
+        match self {
+            Overlay::Colliders => state.collider_render_enabled,
+            Overlay::Contacts => state.contact_render_enabled,
+            Overlay::GroundProbes => state.ground_probe_render_enabled,
+            Overlay::LineOfSight => state.line_of_sight_render_enabled,
+            Overlay::NavigationPaths => state.navigation_path_render_enabled,
+            Overlay::Navmeshes => state.navmesh_render_enabled,
+            Overlay::Sensors => state.trigger_render_enabled,
+        }
+    }
+
+    pub fn enabled_mut(self, state: &mut DevEditorState) -> &mut bool {
+        match self {
+            Overlay::Colliders => &mut state.collider_render_enabled,
+            Overlay::Contacts => &mut state.contact_render_enabled,
+            Overlay::GroundProbes => &mut state.ground_probe_render_enabled,
+            Overlay::LineOfSight => &mut state.line_of_sight_render_enabled,
+            Overlay::NavigationPaths => &mut state.navigation_path_render_enabled,
+            Overlay::Navmeshes => &mut state.navmesh_render_enabled,
+            Overlay::Sensors => &mut state.trigger_render_enabled,
+        }
+    }
+}
+
+fn toggle_overlays(
+    keyboard: Res<Input<KeyCode>>,
+    mut editor: ResMut<Editor>,
+    mut egui_context: ResMut<EguiContext>,
+) -> Result<()> {
+    if egui_context.ctx_mut().wants_keyboard_input() {
+        return Ok(());
+    }
+    let state = editor
+        .window_state_mut::<DevEditorWindow>()
+        .context("Failed to read dev window state")?;
+    for (key, overlay) in OVERLAY_KEYS {
+        if keyboard.just_pressed(key) {
+            let enabled = overlay.enabled_mut(state);
+            *enabled = !*enabled;
+            info!(
+                "{} overlay {}",
+                overlay.name(),
+                if *enabled { "on" } else { "off" }
+            );
+        }
+    }
+    Ok(())
+}
+
+fn is_enabled(editor: &Editor, overlay: Overlay) -> Result<bool> {
+    let state = editor
+        .window_state::<DevEditorWindow>()
+        .context("Failed to read dev window state")?;
+    Ok(overlay.is_enabled(state))
+}
+
+fn draw_contacts(
+    editor: Res<Editor>,
+    rapier_context: Res<RapierContext>,
+    mut lines: ResMut<DebugLines>,
+) -> Result<()> {
+    if !is_enabled(&editor, Overlay::Contacts)? {
+        return Ok(());
+    }
+    for pair in rapier_context.contact_pairs() {
+        if !pair.has_any_active_contacts() {
+            continue;
+        }
+        for manifold in pair.manifolds() {
+            let normal = manifold.normal();
+            for contact in manifold.solver_contacts() {
+                let point = contact.point();
+                draw_cross(&mut lines, point, 0.05, CONTACT_COLOR);
+                lines.line_colored(point, point + normal * 0.3, 0.0, CONTACT_COLOR);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Draws the ray and the sphere cast down by [`probe_ground`], red where they hit the ground
+fn draw_ground_probes(
+    editor: Res<Editor>,
+    rapier_context: Res<RapierContext>,
+    player_query: Query<(Entity, &Transform, &Collider), With<Player>>,
+    mut lines: ResMut<DebugLines>,
+) -> Result<()> {
+    if !is_enabled(&editor, Overlay::GroundProbes)? {
+        return Ok(());
+    }
+    for (entity, transform, collider) in player_query.iter() {
+        let probe = probe_ground(&rapier_context, entity, transform, collider);
+        let start = transform.translation;
+        let down = transform.down();
+        match probe.ray_hit {
+            Some((_ground, intersection)) => {
+                lines.line_colored(start, intersection.point, 0.0, HIT_COLOR);
+                draw_cross(&mut lines, intersection.point, 0.1, HIT_COLOR);
+                lines.line_colored(
+                    intersection.point,
+                    intersection.point + intersection.normal * 0.5,
+                    0.0,
+                    HIT_COLOR,
+                );
+            }
+            None => lines.line_colored(start, start + down * probe.ray_length, 0.0, MISS_COLOR),
+        }
+        // The sphere is only cast when the ray hit something
+        if probe.ray_hit.is_none() {
+            continue;
+        }
+        let (distance, color) = match probe.sphere_hit {
+            Some((_ground, toi)) => (toi.toi, HIT_COLOR),
+            None => (probe.sphere_distance, MISS_COLOR),
+        };
+        let center = start + down * distance;
+        draw_sphere(&mut lines, center, probe.sphere_radius, color);
+        if let Some((_ground, toi)) = probe.sphere_hit {
+            // This normal is used instead of the ray's when it is more upright
+            let contact = center - toi.normal1 * probe.sphere_radius;
+            lines.line_colored(contact, contact + toi.normal1 * 0.5, 0.0, Color::YELLOW);
+        }
+    }
+    Ok(())
+}
+
+/// Draws the line of sight of the third person camera from its target. It is orange while the camera is pulled closer
+/// because something is in the way and green while it moves back out, the hit point is marked red.
+fn draw_line_of_sight(
+    editor: Res<Editor>,
+    camera_query: Query<&IngameCamera>,
+    mut lines: ResMut<DebugLines>,
+) -> Result<()> {
+    if !is_enabled(&editor, Overlay::LineOfSight)? {
+        return Ok(());
+    }
+    for camera in camera_query.iter() {
+        let camera = match &camera.kind {
+            IngameCameraKind::ThirdPerson(camera) => camera,
+            _ => continue,
+        };
+        let result = match camera.line_of_sight {
+            Some(result) => result,
+            None => continue,
+        };
+        let color = match result.correction {
+            LineOfSightCorrection::Closer => Color::ORANGE,
+            LineOfSightCorrection::Further => Color::GREEN,
+        };
+        lines.line_colored(result.origin, result.location, 0.0, color);
+        draw_cross(&mut lines, result.location, 0.1, color);
+        if let Some(hit) = result.hit {
+            lines.line_colored(result.location, hit, 0.0, Color::GRAY);
+            draw_cross(&mut lines, hit, 0.1, HIT_COLOR);
+        }
+    }
+    Ok(())
+}
+
+/// Draws the bounds of all sensors except triggers, which are drawn by the dev editor with their state
+fn draw_sensors(
+    editor: Res<Editor>,
+    sensor_query: Query<(&Collider, &GlobalTransform), (With<Sensor>, Without<TriggerState>)>,
+    mut lines: ResMut<DebugLines>,
+) -> Result<()> {
+    if !is_enabled(&editor, Overlay::Sensors)? {
+        return Ok(());
+    }
+    for (collider, transform) in sensor_query.iter() {
+        let aabb = collider.raw.compute_local_aabb();
+        let mins = Vec3::new(aabb.mins.x, aabb.mins.y, aabb.mins.z);
+        let maxs = Vec3::new(aabb.maxs.x, aabb.maxs.y, aabb.maxs.z);
+        let corners: Vec<_> = (0..8)
+            .map(|i| {
+                let corner =
+                    Vec3::select(BVec3::new(i & 1 != 0, i & 2 != 0, i & 4 != 0), maxs, mins);
+                transform.transform_point(corner)
+            })
+            .collect();
+        for i in 0..8 {
+            for axis in [1, 2, 4] {
+                if i & axis == 0 {
+                    lines.line_colored(corners[i], corners[i | axis], 0.0, SENSOR_COLOR);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn draw_cross(lines: &mut DebugLines, point: Vec3, size: f32, color: Color) {
+    for axis in [Vec3::X, Vec3::Y, Vec3::Z] {
+        lines.line_colored(point - axis * size, point + axis * size, 0.0, color);
+    }
+}
+
+fn draw_sphere(lines: &mut DebugLines, center: Vec3, radius: f32, color: Color) {
+    const SEGMENTS: usize = 24;
+    for (u, v) in [(Vec3::X, Vec3::Y), (Vec3::Y, Vec3::Z), (Vec3::Z, Vec3::X)] {
+        let point = |segment: usize| {
+            let angle = segment as f32 / SEGMENTS as f32 * TAU;
+            center + (u * angle.cos() + v * angle.sin()) * radius
+        };
+        for segment in 0..SEGMENTS {
+            lines.line_colored(point(segment), point(segment + 1), 0.0, color);
+        }
+    }
+}
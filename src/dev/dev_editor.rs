@@ -1,8 +1,18 @@
-use crate::file_system_interaction::game_state_serialization::{GameLoadRequest, GameSaveRequest};
+use crate::dev::debug_overlays::OVERLAY_KEYS;
+use crate::dev::editor_history::{EditorCommand, EditorHistory, EditorHistoryRequest};
+use crate::dev::editor_saving::EditorSaveRequest;
+use crate::dev::level_reload::LevelReloadRequest;
+use crate::dev::transform_gizmo::{GizmoMode, GizmoSpace, TransformGizmo};
+use crate::file_system_interaction::game_state_serialization::{
+    GameLoadRequest, GameSaveRequest, SaveSlot,
+};
 use crate::file_system_interaction::level_serialization::{WorldLoadRequest, WorldSaveRequest};
+use crate::level_instantiation::spawning::spawn::NextRuntimeSpawnId;
 use crate::level_instantiation::spawning::{DelayedSpawnEvent, GameObject, SpawnEvent};
 use crate::player_control::camera::ForceCursorGrabMode;
 use crate::util::log_error::log_errors;
+use crate::world_interaction::time_of_day::TimeOfDay;
+use crate::world_interaction::trigger::{FiredTriggers, TriggerState};
 use crate::GameState;
 use anyhow::{Context, Result};
 use bevy::prelude::*;
@@ -27,6 +37,7 @@ impl Plugin for DevEditorPlugin {
                 SystemSet::on_update(GameState::Playing)
                     .with_system(handle_debug_render.pipe(log_errors))
                     .with_system(handle_navmesh_render.pipe(log_errors))
+                    .with_system(handle_trigger_render.pipe(log_errors))
                     .with_system(set_cursor_grab_mode),
             );
     }
@@ -49,11 +60,30 @@ impl EditorWindow for DevEditorWindow {
 
         state.open = true;
         ui.heading("Debug Rendering");
-        ui.checkbox(&mut state.collider_render_enabled, "Colliders");
-        ui.checkbox(&mut state.navmesh_render_enabled, "Navmeshes");
+        for (key, overlay) in OVERLAY_KEYS {
+            ui.checkbox(
+                overlay.enabled_mut(state),
+                format!("{} ({key:?})", overlay.name()),
+            );
+        }
+        ui.separator();
+
+        ui.heading("Time of Day");
+        if let Some(mut time_of_day) = world.get_resource_mut::<TimeOfDay>() {
+            ui.add(egui::Slider::new(&mut time_of_day.hour, 0.0..=24.0).suffix(" h"));
+            ui.checkbox(&mut time_of_day.frozen, "Frozen");
+        }
         ui.separator();
 
         ui.heading("Scene Control");
+        let changed_objects = world.resource::<EditorHistory>().changed_objects().len();
+        if ui
+            .button(format!("Save {changed_objects} changed objects to level"))
+            .on_hover_text("Ctrl+S. Writes the objects changed in the editor into the file of the current level")
+            .clicked()
+        {
+            world.send_event(EditorSaveRequest);
+        }
         ui.horizontal(|ui| {
             ui.label("Level name: ");
             ui.text_edit_singleline(&mut state.level_name);
@@ -61,7 +91,11 @@ impl EditorWindow for DevEditorWindow {
 
         ui.add_enabled_ui(!state.level_name.is_empty(), |ui| {
             ui.horizontal(|ui| {
-                if ui.button("Save").clicked() {
+                if ui
+                    .button("Save")
+                    .on_hover_text("Saves all objects to a new level file with this name")
+                    .clicked()
+                {
                     world.send_event(WorldSaveRequest {
                         filename: state.level_name.clone(),
                     })
@@ -69,6 +103,7 @@ impl EditorWindow for DevEditorWindow {
                 if ui.button("Load").clicked() {
                     world.send_event(WorldLoadRequest {
                         filename: state.level_name.clone(),
+                        ..default()
                     });
                     // Make sure the player is spawned after the level
                     world.send_event(DelayedSpawnEvent {
@@ -76,35 +111,84 @@ impl EditorWindow for DevEditorWindow {
                         event: SpawnEvent {
                             object: GameObject::Player,
                             transform: Transform::from_translation((0., 1.5, 0.).into()),
+                            ..default()
                         },
                     });
                 }
             });
         });
+        if ui
+            .button("Reload current level")
+            .on_hover_text("Respawns the level objects, keeping the player and runtime spawns")
+            .clicked()
+        {
+            world.send_event(LevelReloadRequest);
+        }
         ui.horizontal(|ui| {
             ui.label("Save name: ");
             ui.text_edit_singleline(&mut state.save_name);
         });
 
         ui.horizontal(|ui| {
-            let filename = (!state.save_name.is_empty()).then(|| state.save_name.clone());
+            let slot = SaveSlot::from_name(&state.save_name);
             if ui.button("Save").clicked() {
-                world.send_event(GameSaveRequest {
-                    filename: filename.clone(),
-                })
+                world.send_event(GameSaveRequest { slot: slot.clone() })
             }
             if ui.button("Load").clicked() {
-                world.send_event(GameLoadRequest { filename });
+                world.send_event(GameLoadRequest { slot });
+            }
+        });
+
+        ui.add_space(10.);
+        ui.heading("Transform");
+        show_transform_controls(world, ui);
+
+        ui.add_space(10.);
+        ui.heading("History");
+        let (undo_len, redo_len) = {
+            let history = world.resource::<EditorHistory>();
+            (history.undo_len(), history.redo_len())
+        };
+        ui.horizontal(|ui| {
+            if ui
+                .add_enabled(
+                    undo_len > 0,
+                    egui::Button::new(format!("Undo ({undo_len})")),
+                )
+                .on_hover_text("Ctrl+Z")
+                .clicked()
+            {
+                world.send_event(EditorHistoryRequest::Undo);
+            }
+            if ui
+                .add_enabled(
+                    redo_len > 0,
+                    egui::Button::new(format!("Redo ({redo_len})")),
+                )
+                .on_hover_text("Ctrl+Shift+Z or Ctrl+Y")
+                .clicked()
+            {
+                world.send_event(EditorHistoryRequest::Redo);
             }
         });
+        ui.horizontal(|ui| {
+            ui.label("Undo steps: ");
+            ui.add(egui::DragValue::new(&mut state.undo_depth).clamp_range(1..=1000));
+        });
 
         ui.add_space(10.);
         ui.label("Spawning");
         if ui.button("Spawn").clicked() {
-            world.send_event(SpawnEvent {
+            // Spawned with a known id instead of through a `SpawnRequest` so that the spawn can be undone
+            let event = SpawnEvent {
                 object: state.spawn_item,
+                id: Some(world.resource_mut::<NextRuntimeSpawnId>().next()),
                 ..default()
-            });
+            };
+            world.send_event(event.clone());
+            world
+                .resource_mut::<EditorHistory>()
+                .push(EditorCommand::Spawn(event), state.undo_depth);
         }
 
         ui.add_space(3.);
@@ -121,6 +205,96 @@ impl EditorWindow for DevEditorWindow {
     }
 }
 
+/// Gizmo settings and numeric fields for precise values of the object the [`TransformGizmo`] is shown on
+fn show_transform_controls(world: &mut World, ui: &mut egui::Ui) {
+    let target = {
+        let mut gizmo = world.resource_mut::<TransformGizmo>();
+        ui.horizontal(|ui| {
+            ui.radio_value(&mut gizmo.mode, GizmoMode::Translate, "Move (W)");
+            ui.radio_value(&mut gizmo.mode, GizmoMode::Rotate, "Rotate (E)");
+            ui.radio_value(&mut gizmo.mode, GizmoMode::Scale, "Scale (R)");
+        });
+        let mut local = gizmo.space == GizmoSpace::Local;
+        if ui.checkbox(&mut local, "Local axes").changed() {
+            gizmo.space = if local {
+                GizmoSpace::Local
+            } else {
+                GizmoSpace::World
+            };
+        }
+        ui.label("Snapping with Ctrl held:");
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::DragValue::new(&mut gizmo.translation_snap)
+                    .speed(0.05)
+                    .clamp_range(0.01..=100.)
+                    .suffix(" m"),
+            );
+            ui.add(
+                egui::DragValue::new(&mut gizmo.rotation_snap)
+                    .clamp_range(1.0..=180.)
+                    .suffix("°"),
+            );
+            ui.add(
+                egui::DragValue::new(&mut gizmo.scale_snap)
+                    .speed(0.01)
+                    .clamp_range(0.01..=10.)
+                    .prefix("×"),
+            );
+        });
+        gizmo.target
+    };
+
+    let original = match target.and_then(|target| world.get::<Transform>(target)) {
+        Some(transform) => *transform,
+        None => {
+            ui.label("Select an object to edit its transform");
+            return;
+        }
+    };
+    let mut transform = original;
+    egui::Grid::new("transform").show(ui, |ui| {
+        ui.label("Position");
+        for value in transform.translation.as_mut() {
+            ui.add(egui::DragValue::new(value).speed(0.05));
+        }
+        ui.end_row();
+
+        ui.label("Rotation");
+        let (y, x, z) = transform.rotation.to_euler(EulerRot::YXZ);
+        let mut degrees = [x, y, z].map(f32::to_degrees);
+        let mut rotated = false;
+        for value in degrees.iter_mut() {
+            rotated |= ui
+                .add(egui::DragValue::new(value).speed(1.).suffix("°"))
+                .changed();
+        }
+        if rotated {
+            let [x, y, z] = degrees.map(f32::to_radians);
+            transform.rotation = Quat::from_euler(EulerRot::YXZ, y, x, z);
+        }
+        ui.end_row();
+
+        ui.label("Scale");
+        for value in transform.scale.as_mut() {
+            ui.add(
+                egui::DragValue::new(value)
+                    .speed(0.01)
+                    .clamp_range(0.01..=f32::MAX),
+            );
+        }
+        ui.end_row();
+    });
+    // Only written on change so that physics is not disturbed
+    if transform != original {
+        if let Some(mut target_transform) =
+            target.and_then(|target| world.get_mut::<Transform>(target))
+        {
+            *target_transform = transform;
+        }
+    }
+}
+
 #[derive(Debug, Clone, Eq, PartialEq, Resource, Reflect, Serialize, Deserialize)]
 #[reflect(Resource, Serialize, Deserialize)]
 pub struct DevEditorState {
@@ -130,6 +304,13 @@ pub struct DevEditorState {
     pub spawn_item: GameObject,
     pub collider_render_enabled: bool,
     pub navmesh_render_enabled: bool,
+    pub trigger_render_enabled: bool,
+    pub contact_render_enabled: bool,
+    pub ground_probe_render_enabled: bool,
+    pub line_of_sight_render_enabled: bool,
+    pub navigation_path_render_enabled: bool,
+    /// Number of changes that can be undone
+    pub undo_depth: usize,
 }
 
 impl Default for DevEditorState {
@@ -140,6 +321,12 @@ impl Default for DevEditorState {
             spawn_item: default(),
             collider_render_enabled: false,
             navmesh_render_enabled: false,
+            trigger_render_enabled: false,
+            contact_render_enabled: false,
+            ground_probe_render_enabled: false,
+            line_of_sight_render_enabled: false,
+            navigation_path_render_enabled: false,
+            undo_depth: 100,
             open: false,
         }
     }
@@ -211,3 +398,45 @@ fn handle_navmesh_render(
     }
     Ok(())
 }
+
+/// Draws the bounds of every trigger: green while armed, yellow while occupied and grey once a one-shot trigger fired
+fn handle_trigger_render(
+    state: Res<Editor>,
+    trigger_query: Query<(&TriggerState, &GlobalTransform)>,
+    fired_triggers: Res<FiredTriggers>,
+    mut lines: ResMut<DebugLines>,
+) -> Result<()> {
+    if !state
+        .window_state::<DevEditorWindow>()
+        .context("Failed to read dev window state")?
+        .trigger_render_enabled
+    {
+        return Ok(());
+    }
+
+    for (trigger_state, transform) in trigger_query.iter() {
+        let color = if trigger_state.is_occupied() {
+            Color::YELLOW
+        } else if trigger_state.has_fired(&fired_triggers) {
+            Color::GRAY
+        } else {
+            Color::GREEN
+        };
+        // Triggers are boxes with half extents of 1 m, scaled by their transform
+        let corners: Vec<_> = [-1., 1.]
+            .into_iter()
+            .flat_map(|x| [-1., 1.].into_iter().map(move |y| (x, y)))
+            .flat_map(|(x, y)| [-1., 1.].into_iter().map(move |z| Vec3::new(x, y, z)))
+            .map(|corner| transform.transform_point(corner))
+            .collect();
+        for (i, a) in corners.iter().enumerate() {
+            for (j, b) in corners.iter().enumerate().skip(i + 1) {
+                // Corners that differ in exactly one axis share an edge
+                if (i ^ j).count_ones() == 1 {
+                    lines.line_colored(*a, *b, 0.0, color);
+                }
+            }
+        }
+    }
+    Ok(())
+}
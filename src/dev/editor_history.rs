@@ -0,0 +1,481 @@
+use crate::dev::dev_editor::DevEditorWindow;
+use crate::dev::level_reload::LevelReloadRequest;
+use crate::dev::transform_gizmo::TransformGizmo;
+use crate::file_system_interaction::game_state_serialization::GameLoadRequest;
+use crate::file_system_interaction::level_serialization::WorldLoadRequest;
+use crate::level_instantiation::spawning::spawn::DespawnedObjects;
+use crate::level_instantiation::spawning::{DespawnRequest, SpawnEvent, SpawnId, SpawnTracker};
+use crate::util::log_error::log_errors;
+use crate::GameState;
+use anyhow::{Context, Result};
+use bevy::prelude::*;
+use bevy::utils::{HashMap, HashSet};
+use bevy_editor_pls::default_windows::hierarchy::HierarchyWindow;
+use bevy_editor_pls::Editor;
+use bevy_egui::EguiContext;
+use std::collections::VecDeque;
+
+/// Records the changes made to spawned objects while the editor is open so that they can be undone with Ctrl+Z
+/// and redone with Ctrl+Shift+Z or Ctrl+Y. Objects are referred to by their [`SpawnId`], so a deleted object that is
+/// recreated by an undo is still the same object for the commands recorded before.
+/// Changes are observed on the objects' [`SpawnTracker`] and [`Transform`], so they are recorded no matter which editor
+/// window made them. Moving an object only counts while it is selected or held by the [`TransformGizmo`],
+/// everything else moves by itself.
+/// The history is cleared whenever a level or a save is loaded.
+pub struct EditorHistoryPlugin;
+
+impl Plugin for EditorHistoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<EditorHistory>()
+            .add_event::<EditorHistoryRequest>()
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(clear_history_on_load)
+                    .with_system(read_history_input)
+                    .with_system(
+                        record_editor_changes
+                            .pipe(log_errors)
+                            .after(clear_history_on_load),
+                    )
+                    .with_system(
+                        apply_history_requests
+                            .pipe(log_errors)
+                            .after(record_editor_changes)
+                            .after(read_history_input),
+                    ),
+            );
+    }
+}
+
+/// Seconds a dragged object has to rest with the mouse released before the drag is recorded
+const DRAG_SETTLE_SECONDS: f32 = 0.3;
+/// Frames after loading during which despawned objects are not recorded as deleted
+const SETTLE_FRAMES: u32 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EditorHistoryRequest {
+    Undo,
+    Redo,
+}
+
+/// A reversible change to the spawned objects
+#[derive(Debug, Clone, PartialEq)]
+pub enum EditorCommand {
+    /// The object was spawned, holds everything needed to spawn it again
+    Spawn(SpawnEvent),
+    /// The object was deleted, holds everything needed to spawn it again
+    Delete(SpawnEvent),
+    /// Objects were moved, rotated or scaled in a single drag
+    Transform(Vec<MovedObject>),
+    /// Properties of the object were edited. It is spawned anew to apply them.
+    Edit {
+        before: SpawnEvent,
+        after: SpawnEvent,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MovedObject {
+    pub id: SpawnId,
+    pub before: Transform,
+    pub after: Transform,
+}
+
+impl EditorCommand {
+    fn ids(&self) -> Vec<SpawnId> {
+        match self {
+            EditorCommand::Spawn(event) | EditorCommand::Delete(event) => {
+                event.id.into_iter().collect()
+            }
+            EditorCommand::Transform(objects) => objects.iter().map(|object| object.id).collect(),
+            EditorCommand::Edit { after, .. } => after.id.into_iter().collect(),
+        }
+    }
+
+    /// The command that reverts this one
+    fn inverse(&self) -> Self {
+        match self {
+            EditorCommand::Spawn(event) => EditorCommand::Delete(event.clone()),
+            EditorCommand::Delete(event) => EditorCommand::Spawn(event.clone()),
+            EditorCommand::Transform(objects) => EditorCommand::Transform(
+                objects
+                    .iter()
+                    .map(|object| MovedObject {
+                        id: object.id,
+                        before: object.after,
+                        after: object.before,
+                    })
+                    .collect(),
+            ),
+            EditorCommand::Edit { before, after } => EditorCommand::Edit {
+                before: after.clone(),
+                after: before.clone(),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Resource, Default)]
+pub struct EditorHistory {
+    /// Oldest first
+    undo_stack: VecDeque<EditorCommand>,
+    redo_stack: Vec<EditorCommand>,
+    /// The objects as of the last frame, used to find out what changed
+    known_objects: HashMap<SpawnId, SpawnEvent>,
+    /// Transforms from before the current drag of the objects being dragged
+    drag_start: HashMap<SpawnId, Transform>,
+    /// Seconds since a dragged object last moved
+    drag_idle: f32,
+    /// Entities despawned by an undo or redo that are still around until the commands are applied
+    despawning: HashSet<Entity>,
+    /// Objects touched by any command since the level was loaded
+    changed: HashSet<SpawnId>,
+    settle_frames: u32,
+}
+
+impl EditorHistory {
+    /// Records a command that was just executed. Anything undone before cannot be redone anymore.
+    pub fn push(&mut self, command: EditorCommand, depth: usize) {
+        self.changed.extend(command.ids());
+        self.redo_stack.clear();
+        self.undo_stack.push_back(command);
+        self.truncate(depth);
+    }
+
+    pub fn undo_len(&self) -> usize {
+        self.undo_stack.len()
+    }
+
+    pub fn redo_len(&self) -> usize {
+        self.redo_stack.len()
+    }
+
+    /// Objects the editor changed since the level was loaded, including those whose changes were undone
+    pub fn changed_objects(&self) -> &HashSet<SpawnId> {
+        &self.changed
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self {
+            settle_frames: SETTLE_FRAMES,
+            ..default()
+        };
+    }
+
+    /// Returns the command to execute in order to undo the last one
+    fn take_undo(&mut self) -> Option<EditorCommand> {
+        let command = self.undo_stack.pop_back()?;
+        self.changed.extend(command.ids());
+        let inverse = command.inverse();
+        self.redo_stack.push(command);
+        Some(inverse)
+    }
+
+    /// Returns the command to execute in order to redo the last undone one
+    fn take_redo(&mut self, depth: usize) -> Option<EditorCommand> {
+        let command = self.redo_stack.pop()?;
+        self.changed.extend(command.ids());
+        self.undo_stack.push_back(command.clone());
+        self.truncate(depth);
+        Some(command)
+    }
+
+    fn truncate(&mut self, depth: usize) {
+        let excess = self.undo_stack.len().saturating_sub(depth);
+        self.undo_stack.drain(..excess);
+    }
+
+    /// Records the drag in progress, if any, as a single command
+    fn finish_drag(&mut self, depth: usize) {
+        let moved_objects: Vec<_> = self
+            .drag_start
+            .drain()
+            .filter_map(|(id, before)| {
+                let after = self.known_objects.get(&id)?.transform;
+                (after != before).then_some(MovedObject { id, before, after })
+            })
+            .collect();
+        if !moved_objects.is_empty() {
+            self.push(EditorCommand::Transform(moved_objects), depth);
+        }
+    }
+}
+
+fn clear_history_on_load(
+    mut world_load_requests: EventReader<WorldLoadRequest>,
+    mut level_reload_requests: EventReader<LevelReloadRequest>,
+    mut game_load_requests: EventReader<GameLoadRequest>,
+    mut history: ResMut<EditorHistory>,
+) {
+    // Not short-circuited so that all readers are drained
+    let world_loaded = world_load_requests.iter().count() > 0;
+    let level_reloaded = level_reload_requests.iter().count() > 0;
+    let game_loaded = game_load_requests.iter().count() > 0;
+    if world_loaded || level_reloaded || game_loaded {
+        history.clear();
+    }
+}
+
+fn read_history_input(
+    keyboard: Res<Input<KeyCode>>,
+    editor: Res<Editor>,
+    mut egui_context: ResMut<EguiContext>,
+    mut requests: EventWriter<EditorHistoryRequest>,
+) {
+    // Text fields have their own undo
+    if !editor.active() || egui_context.ctx_mut().wants_keyboard_input() {
+        return;
+    }
+    let control = keyboard.any_pressed([KeyCode::LControl, KeyCode::RControl]);
+    let shift = keyboard.any_pressed([KeyCode::LShift, KeyCode::RShift]);
+    if !control {
+        return;
+    }
+    if keyboard.just_pressed(KeyCode::Z) {
+        requests.send(if shift {
+            EditorHistoryRequest::Redo
+        } else {
+            EditorHistoryRequest::Undo
+        });
+    } else if keyboard.just_pressed(KeyCode::Y) {
+        requests.send(EditorHistoryRequest::Redo);
+    }
+}
+
+fn record_editor_changes(
+    time: Res<Time>,
+    mouse: Res<Input<MouseButton>>,
+    editor: Res<Editor>,
+    gizmo: Res<TransformGizmo>,
+    mut history: ResMut<EditorHistory>,
+    objects: Query<(Entity, &SpawnId, &SpawnTracker, &Transform)>,
+) -> Result<()> {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("record_editor_changes").entered();
+    let depth = editor
+        .window_state::<DevEditorWindow>()
+        .context("Failed to read dev window state")?
+        .undo_depth;
+    let selected = &editor
+        .window_state::<HierarchyWindow>()
+        .context("Failed to read hierarchy window state")?
+        .selected;
+    let history = &mut *history;
+    history
+        .despawning
+        .retain(|entity| objects.get(*entity).is_ok());
+    let recording = editor.active() && history.settle_frames == 0;
+    history.settle_frames = history.settle_frames.saturating_sub(1);
+
+    let mut present = HashSet::new();
+    let mut moved = false;
+    for (entity, id, spawn_tracker, transform) in objects.iter() {
+        if history.despawning.contains(&entity) {
+            continue;
+        }
+        present.insert(*id);
+        let current = spawn_tracker.to_spawn_event(Some(*id), *transform);
+        let previous = match history.known_objects.insert(*id, current.clone()) {
+            Some(previous) if recording => previous,
+            // Objects spawned by anything but the editor's spawn button are not recorded
+            _ => continue,
+        };
+        if SpawnTracker::from(previous.clone()) != *spawn_tracker {
+            history.push(
+                EditorCommand::Edit {
+                    before: previous,
+                    after: current,
+                },
+                depth,
+            );
+        } else if previous.transform != *transform
+            && (selected.contains(entity) || gizmo.target == Some(entity))
+        {
+            history.drag_start.entry(*id).or_insert(previous.transform);
+            moved = true;
+        }
+    }
+
+    let deleted: Vec<_> = history
+        .known_objects
+        .keys()
+        .filter(|id| !present.contains(*id))
+        .copied()
+        .collect();
+    for id in deleted {
+        history.drag_start.remove(&id);
+        if let Some(event) = history.known_objects.remove(&id) {
+            if recording {
+                history.push(EditorCommand::Delete(event), depth);
+            }
+        }
+    }
+
+    if !recording {
+        history.drag_start.clear();
+    }
+    if moved {
+        history.drag_idle = 0.;
+    } else {
+        history.drag_idle += time.delta_seconds();
+    }
+    if !mouse.pressed(MouseButton::Left) && history.drag_idle >= DRAG_SETTLE_SECONDS {
+        history.finish_drag(depth);
+    }
+    Ok(())
+}
+
+fn apply_history_requests(
+    mut commands: Commands,
+    mut requests: EventReader<EditorHistoryRequest>,
+    editor: Res<Editor>,
+    mut history: ResMut<EditorHistory>,
+    mut objects: Query<(Entity, &SpawnId, &mut Transform)>,
+    mut spawn_events: EventWriter<SpawnEvent>,
+    mut despawn_requests: EventWriter<DespawnRequest>,
+    mut despawned_objects: ResMut<DespawnedObjects>,
+) -> Result<()> {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("apply_history_requests").entered();
+    let depth = editor
+        .window_state::<DevEditorWindow>()
+        .context("Failed to read dev window state")?
+        .undo_depth;
+    let history = &mut *history;
+    for request in requests.iter() {
+        // A drag that has not settled yet is still undone as a whole
+        history.finish_drag(depth);
+        let command = match request {
+            EditorHistoryRequest::Undo => history.take_undo(),
+            EditorHistoryRequest::Redo => history.take_redo(depth),
+        };
+        let command = match command {
+            Some(command) => command,
+            None => continue,
+        };
+        match command {
+            EditorCommand::Spawn(event) => {
+                despawned_objects.0.retain(|id| Some(*id) != event.id);
+                spawn_events.send(event);
+            }
+            EditorCommand::Delete(event) => match find_entity(&objects, history, event.id) {
+                Some(entity) => {
+                    despawn_requests.send(DespawnRequest::Entity(entity));
+                    history.despawning.insert(entity);
+                }
+                None => warn!("Failed to delete {:?}: It no longer exists", event.id),
+            },
+            EditorCommand::Transform(moved_objects) => {
+                for moved_object in moved_objects {
+                    let entity = find_entity(&objects, history, Some(moved_object.id));
+                    match entity.and_then(|entity| objects.get_mut(entity).ok()) {
+                        Some((_entity, _id, mut transform)) => *transform = moved_object.after,
+                        None => warn!("Failed to move {:?}: It no longer exists", moved_object.id),
+                    }
+                    if let Some(known) = history.known_objects.get_mut(&moved_object.id) {
+                        known.transform = moved_object.after;
+                    }
+                }
+            }
+            EditorCommand::Edit { after, .. } => match find_entity(&objects, history, after.id) {
+                Some(entity) => {
+                    // Not a despawn request, as the object stays in the level
+                    commands.entity(entity).despawn_recursive();
+                    history.despawning.insert(entity);
+                    spawn_events.send(after);
+                }
+                None => warn!("Failed to edit {:?}: It no longer exists", after.id),
+            },
+        }
+    }
+    // Objects appearing or vanishing because of the commands are not recorded as new changes
+    let present: HashSet<_> = objects
+        .iter()
+        .filter(|(entity, ..)| !history.despawning.contains(entity))
+        .map(|(_entity, id, _transform)| *id)
+        .collect();
+    history.known_objects.retain(|id, _| present.contains(id));
+    Ok(())
+}
+
+fn find_entity(
+    objects: &Query<(Entity, &SpawnId, &mut Transform)>,
+    history: &EditorHistory,
+    id: Option<SpawnId>,
+) -> Option<Entity> {
+    objects
+        .iter()
+        .find(|(entity, spawn_id, _transform)| {
+            Some(**spawn_id) == id && !history.despawning.contains(entity)
+        })
+        .map(|(entity, ..)| entity)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn spawn(index: usize) -> EditorCommand {
+        EditorCommand::Spawn(SpawnEvent {
+            id: Some(SpawnId::Level(index)),
+            ..default()
+        })
+    }
+
+    #[test]
+    fn undo_and_redo_walk_the_stack() {
+        let mut history = EditorHistory::default();
+        history.push(spawn(0), 10);
+        history.push(spawn(1), 10);
+
+        assert_eq!(history.take_undo(), Some(spawn(1).inverse()));
+        assert_eq!(history.take_redo(10), Some(spawn(1)));
+        assert_eq!(history.take_redo(10), None);
+
+        history.take_undo();
+        history.push(spawn(2), 10);
+        assert_eq!(history.redo_len(), 0);
+        assert_eq!(history.undo_len(), 2);
+    }
+
+    #[test]
+    fn oldest_commands_are_dropped_beyond_depth() {
+        let mut history = EditorHistory::default();
+        for index in 0..5 {
+            history.push(spawn(index), 3);
+        }
+        assert_eq!(history.undo_len(), 3);
+        assert_eq!(history.take_undo(), Some(spawn(4).inverse()));
+        assert_eq!(history.take_undo(), Some(spawn(3).inverse()));
+        assert_eq!(history.take_undo(), Some(spawn(2).inverse()));
+        assert_eq!(history.take_undo(), None);
+    }
+
+    #[test]
+    fn drag_is_recorded_once() {
+        let mut history = EditorHistory::default();
+        let id = SpawnId::Runtime(0);
+        let start = Transform::from_xyz(1., 0., 0.);
+        history.drag_start.insert(id, start);
+        history.known_objects.insert(
+            id,
+            SpawnEvent {
+                transform: Transform::from_xyz(3., 0., 0.),
+                id: Some(id),
+                ..default()
+            },
+        );
+        history.finish_drag(10);
+        history.finish_drag(10);
+
+        assert_eq!(history.undo_len(), 1);
+        assert_eq!(
+            history.take_undo(),
+            Some(EditorCommand::Transform(vec![MovedObject {
+                id,
+                before: Transform::from_xyz(3., 0., 0.),
+                after: start,
+            }]))
+        );
+    }
+}
@@ -0,0 +1,108 @@
+use crate::dev::editor_history::EditorHistory;
+use crate::file_system_interaction::asset_loading::LevelAssets;
+use crate::file_system_interaction::level_serialization::{
+    get_level_path, merge_level_edits, write_level_file, CurrentLevel, SerializedLevel,
+};
+use crate::level_instantiation::spawning::{SpawnId, SpawnTracker};
+use crate::util::log_error::log_errors;
+use crate::GameState;
+use anyhow::{Context, Result};
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use bevy_editor_pls::Editor;
+use bevy_egui::EguiContext;
+
+/// Writes the objects changed in the editor into the file of the current level, so that they are there after a restart.
+/// Saving is triggered with Ctrl+S while the editor is open or with an [`EditorSaveRequest`].
+/// Only the objects recorded in the [`EditorHistory`] are taken from the running game, the rest of the file stays as it is.
+/// Afterwards the level is reloaded from the new file, which turns placed objects into regular level objects.
+pub struct EditorSavePlugin;
+
+impl Plugin for EditorSavePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<EditorSaveRequest>().add_system_set(
+            SystemSet::on_update(GameState::Playing)
+                .with_system(read_save_input)
+                .with_system(save_edited_level.pipe(log_errors).after(read_save_input)),
+        );
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct EditorSaveRequest;
+
+fn read_save_input(
+    keyboard: Res<Input<KeyCode>>,
+    editor: Res<Editor>,
+    mut egui_context: ResMut<EguiContext>,
+    mut requests: EventWriter<EditorSaveRequest>,
+) {
+    if !editor.active() || egui_context.ctx_mut().wants_keyboard_input() {
+        return;
+    }
+    let control = keyboard.any_pressed([KeyCode::LControl, KeyCode::RControl]);
+    if control && keyboard.just_pressed(KeyCode::S) {
+        requests.send(EditorSaveRequest);
+    }
+}
+
+fn save_edited_level(
+    mut commands: Commands,
+    mut requests: EventReader<EditorSaveRequest>,
+    current_level: Option<Res<CurrentLevel>>,
+    level_handles: Res<LevelAssets>,
+    levels: Res<Assets<SerializedLevel>>,
+    history: Res<EditorHistory>,
+    objects: Query<(Entity, &SpawnTracker, &SpawnId, &Transform)>,
+    asset_server: Res<AssetServer>,
+) -> Result<()> {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("save_edited_level").entered();
+    if requests.iter().count() == 0 {
+        return Ok(());
+    }
+    let current_level = match current_level {
+        Some(current_level) => current_level,
+        None => {
+            warn!("Failed to save the level: No level is loaded");
+            return Ok(());
+        }
+    };
+    let path = get_level_path(&current_level.scene)?;
+    let original = level_handles
+        .levels
+        .get(&path)
+        .and_then(|handle| levels.get(handle))
+        .with_context(|| format!("Failed to get the loaded level {path}"))?;
+    let now: HashMap<_, _> = objects
+        .iter()
+        .map(|(_entity, spawn_tracker, id, transform)| {
+            (*id, spawn_tracker.to_spawn_event(Some(*id), *transform))
+        })
+        .collect();
+    let level = merge_level_edits(original, history.changed_objects(), &now);
+    let file = match write_level_file(&current_level.scene, &level) {
+        Ok(file) => file,
+        Err(error) => {
+            error!(
+                "Failed to save the level \"{}\": {error:#}",
+                current_level.scene
+            );
+            return Ok(());
+        }
+    };
+    info!(
+        "Saved {} objects to {}",
+        level.0.len(),
+        file.to_string_lossy()
+    );
+
+    // The placed objects come back as level objects when the level is reloaded from the new file
+    for (entity, _spawn_tracker, id, _transform) in objects.iter() {
+        if id.is_runtime() && history.changed_objects().contains(id) {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+    asset_server.reload_asset(path.as_str());
+    Ok(())
+}
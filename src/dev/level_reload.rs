@@ -0,0 +1,112 @@
+use crate::file_system_interaction::asset_loading::{LevelAssets, SceneAssets};
+use crate::file_system_interaction::level_serialization::{
+    get_level_path, spawn_level_objects, CurrentLevel, LoadWorldLabel, SerializedLevel,
+};
+use crate::level_instantiation::spawning::spawn::{DespawnedObjects, LevelOwned};
+use crate::level_instantiation::spawning::SpawnEvent;
+use crate::player_control::camera::{IngameCamera, RestoredCamera};
+use bevy::prelude::*;
+
+/// Reloads the current level while the game is running when its glTF scene or its level file changes on disk.
+/// Only the [`LevelOwned`] objects are despawned and spawned again, so the player stays where they are
+/// and runtime spawns, flags and fired triggers survive the reload. The colliders are rebuilt with the new scene.
+/// Send a [`LevelReloadRequest`] to reload the level by hand, e.g. when the file watcher misses a change.
+pub struct LevelReloadPlugin;
+
+impl Plugin for LevelReloadPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<LevelReloadRequest>()
+            .add_system(watch_level_assets)
+            .add_system_to_stage(CoreStage::PostUpdate, reload_level.after(LoadWorldLabel));
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LevelReloadRequest;
+
+fn watch_level_assets(
+    mut scene_events: EventReader<AssetEvent<Scene>>,
+    mut level_events: EventReader<AssetEvent<SerializedLevel>>,
+    scene_handles: Option<Res<SceneAssets>>,
+    level_handles: Option<Res<LevelAssets>>,
+    current_level: Option<Res<CurrentLevel>>,
+    mut reload_requests: EventWriter<LevelReloadRequest>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("watch_level_assets").entered();
+    let scene_changed = scene_events
+        .iter()
+        .any(|event| match (event, &scene_handles) {
+            (AssetEvent::Modified { handle }, Some(scene_handles)) => {
+                *handle == scene_handles.level
+            }
+            _ => false,
+        });
+    let current_level_handle = current_level
+        .as_ref()
+        .and_then(|current_level| get_level_path(&current_level.scene).ok())
+        .zip(level_handles.as_ref())
+        .and_then(|(path, level_handles)| level_handles.levels.get(&path).cloned());
+    let level_changed = level_events
+        .iter()
+        .any(|event| match (event, &current_level_handle) {
+            (AssetEvent::Modified { handle }, Some(current_level_handle)) => {
+                handle == current_level_handle
+            }
+            _ => false,
+        });
+    if current_level.is_some() && (scene_changed || level_changed) {
+        info!("Level changed on disk, reloading it");
+        reload_requests.send(LevelReloadRequest);
+    }
+}
+
+fn reload_level(
+    mut commands: Commands,
+    mut reload_requests: EventReader<LevelReloadRequest>,
+    level_owned_query: Query<Entity, With<LevelOwned>>,
+    camera_query: Query<&IngameCamera>,
+    mut spawn_requests: EventWriter<SpawnEvent>,
+    levels: Res<Assets<SerializedLevel>>,
+    level_handles: Option<Res<LevelAssets>>,
+    current_level: Option<Res<CurrentLevel>>,
+    despawned_objects: Res<DespawnedObjects>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("reload_level").entered();
+    if reload_requests.iter().count() == 0 {
+        return;
+    }
+    let current_level = match current_level {
+        Some(current_level) => current_level,
+        None => {
+            warn!("Failed to reload level: No level is loaded");
+            return;
+        }
+    };
+    let level = get_level_path(&current_level.scene)
+        .ok()
+        .zip(level_handles)
+        .and_then(|(path, level_handles)| level_handles.levels.get(&path).cloned())
+        .and_then(|handle| levels.get(&handle));
+    let level = match level {
+        Some(level) => level,
+        None => {
+            error!(
+                "Failed to reload level \"{}\": No such level",
+                current_level.scene
+            );
+            return;
+        }
+    };
+
+    for entity in &level_owned_query {
+        commands.entity(entity).despawn_recursive();
+    }
+    // The camera is part of the level, so keep looking where it was looking
+    if let Some(camera) = camera_query.iter().next() {
+        commands.insert_resource(RestoredCamera(camera.kind.clone()));
+    }
+    spawn_level_objects(level, &despawned_objects.0, &mut spawn_requests);
+    info!("Successfully reloaded level \"{}\"", current_level.scene);
+}
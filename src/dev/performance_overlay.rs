@@ -0,0 +1,525 @@
+use bevy::diagnostic::{Diagnostics, FrameTimeDiagnosticsPlugin};
+use bevy::ecs::entity::Entities;
+use bevy::ecs::schedule::ShouldRun;
+use bevy::prelude::*;
+use bevy::utils::Instant;
+use bevy_egui::{egui, EguiContext};
+use chrono::prelude::Local;
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Shows the frame rate, a graph of recent frame times and how long each stage of the schedule took.
+/// Toggled with [`TOGGLE_KEY`]. While hidden, nothing is measured and nothing is drawn.
+/// Timings of individual systems are not available at runtime, build with the `tracing` feature for a Chrome trace of them.
+pub struct PerformanceOverlayPlugin;
+
+impl Plugin for PerformanceOverlayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PerformanceOverlay>()
+            .init_resource::<StageTimings>()
+            .add_stage_before(CoreStage::First, TimingMark::First, marker_stage::<0>())
+            .add_stage_before(
+                CoreStage::PreUpdate,
+                TimingMark::PreUpdate,
+                marker_stage::<1>(),
+            )
+            .add_stage_before(CoreStage::Update, TimingMark::Update, marker_stage::<2>())
+            // Rapier runs its stages between the update and the post update
+            .add_stage_after(CoreStage::Update, TimingMark::Physics, marker_stage::<3>())
+            .add_stage_before(
+                CoreStage::PostUpdate,
+                TimingMark::PostUpdate,
+                marker_stage::<4>(),
+            )
+            .add_stage_before(CoreStage::Last, TimingMark::Last, marker_stage::<5>())
+            .add_stage_after(CoreStage::Last, TimingMark::Render, marker_stage::<6>())
+            .add_system(toggle_performance_overlay)
+            .add_system(
+                record_frame
+                    .with_run_criteria(overlay_visible)
+                    .after(toggle_performance_overlay),
+            )
+            .add_system(
+                show_performance_overlay
+                    .with_run_criteria(overlay_visible)
+                    .after(record_frame),
+            );
+    }
+}
+
+pub const TOGGLE_KEY: KeyCode = KeyCode::F10;
+/// Number of frames kept for the graph, the percentiles and the CSV export
+const HISTORY_LENGTH: usize = 600;
+const GRAPH_HEIGHT: f32 = 120.;
+/// Frame times above these are drawn yellow and red respectively
+const WARNING_FRAME_TIME_MS: f32 = 1000. / 60.;
+const CRITICAL_FRAME_TIME_MS: f32 = 1000. / 30.;
+const CSV_DIRECTORY: &str = "performance";
+
+/// Parts of a frame that are timed, in the order they run.
+/// The render stage is everything after the main schedule until the next frame starts, including waiting for vsync.
+const STAGE_NAMES: [&str; STAGE_COUNT] = [
+    "First",
+    "PreUpdate",
+    "Update",
+    "Physics",
+    "PostUpdate",
+    "Last",
+    "Render and present",
+];
+const STAGE_COUNT: usize = 7;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, StageLabel)]
+enum TimingMark {
+    First,
+    PreUpdate,
+    Update,
+    Physics,
+    PostUpdate,
+    Last,
+    Render,
+}
+
+/// Stage that notes the time at which the part of the frame with index `STAGE` starts
+fn marker_stage<const STAGE: usize>() -> SystemStage {
+    SystemStage::single_threaded().with_system(mark::<STAGE>.with_run_criteria(overlay_visible))
+}
+
+fn mark<const STAGE: usize>(mut timings: ResMut<StageTimings>) {
+    timings.mark(STAGE, Instant::now());
+}
+
+#[derive(Debug, Clone, Resource)]
+pub struct PerformanceOverlay {
+    pub visible: bool,
+    /// How many of the most recent frames are written to the CSV file
+    pub dump_frames: usize,
+    history: FrameHistory,
+    /// Reused for sorting frame times so that the percentiles don't allocate every frame
+    scratch: Vec<f32>,
+}
+
+impl Default for PerformanceOverlay {
+    fn default() -> Self {
+        Self {
+            visible: false,
+            dump_frames: HISTORY_LENGTH,
+            history: FrameHistory::new(HISTORY_LENGTH),
+            scratch: Vec::with_capacity(HISTORY_LENGTH),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameMeasurement {
+    pub frame: u64,
+    pub frame_time_ms: f32,
+    pub entity_count: u32,
+    /// Duration of each of the [`STAGE_NAMES`] in milliseconds, if the whole frame was measured
+    pub stages_ms: Option<[f32; STAGE_COUNT]>,
+}
+
+/// Ring buffer of the most recent frames, allocated once
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrameHistory {
+    frames: Vec<FrameMeasurement>,
+    capacity: usize,
+    /// Index that is overwritten next once the buffer is full
+    next: usize,
+}
+
+impl FrameHistory {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            frames: Vec::with_capacity(capacity),
+            capacity,
+            next: 0,
+        }
+    }
+
+    pub fn push(&mut self, frame: FrameMeasurement) {
+        if self.frames.len() < self.capacity {
+            self.frames.push(frame);
+        } else {
+            self.frames[self.next] = frame;
+        }
+        self.next = (self.next + 1) % self.capacity;
+    }
+
+    pub fn clear(&mut self) {
+        self.frames.clear();
+        self.next = 0;
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Frames from oldest to newest
+    pub fn iter(&self) -> impl Iterator<Item = &FrameMeasurement> {
+        // While the buffer is not full, `next` is its length and the first slice is empty
+        self.frames[self.next..]
+            .iter()
+            .chain(self.frames[..self.next].iter())
+    }
+
+    pub fn latest(&self) -> Option<&FrameMeasurement> {
+        self.iter().last()
+    }
+}
+
+/// Times at which the parts of the current frame started, see [`STAGE_NAMES`]
+#[derive(Debug, Clone, PartialEq, Resource, Default)]
+struct StageTimings {
+    marks: [Option<Instant>; STAGE_COUNT],
+    /// Stage durations of the last completely measured frame
+    last: Option<[f32; STAGE_COUNT]>,
+}
+
+impl StageTimings {
+    fn mark(&mut self, stage: usize, now: Instant) {
+        if stage == 0 {
+            // The first mark of a frame ends the render stage of the previous one
+            let mut durations = [0.; STAGE_COUNT];
+            let mut complete = true;
+            for (index, duration) in durations.iter_mut().enumerate() {
+                let end = match self.marks.get(index + 1) {
+                    Some(end) => *end,
+                    None => Some(now),
+                };
+                match (self.marks[index], end) {
+                    (Some(start), Some(end)) => {
+                        *duration = end.duration_since(start).as_secs_f32() * 1000.
+                    }
+                    _ => complete = false,
+                }
+            }
+            self.last = complete.then_some(durations);
+            self.marks = [None; STAGE_COUNT];
+        }
+        self.marks[stage] = Some(now);
+    }
+}
+
+fn overlay_visible(overlay: Res<PerformanceOverlay>) -> ShouldRun {
+    if overlay.visible {
+        ShouldRun::Yes
+    } else {
+        ShouldRun::No
+    }
+}
+
+fn toggle_performance_overlay(
+    keyboard: Res<Input<KeyCode>>,
+    mut egui_context: ResMut<EguiContext>,
+    mut overlay: ResMut<PerformanceOverlay>,
+    mut timings: ResMut<StageTimings>,
+) {
+    if egui_context.ctx_mut().wants_keyboard_input() || !keyboard.just_pressed(TOGGLE_KEY) {
+        return;
+    }
+    overlay.visible = !overlay.visible;
+    // Frames from before the overlay was hidden would leave a gap in the graph
+    overlay.history.clear();
+    *timings = default();
+}
+
+fn record_frame(
+    time: Res<Time>,
+    entities: &Entities,
+    timings: Res<StageTimings>,
+    mut overlay: ResMut<PerformanceOverlay>,
+) {
+    let frame = overlay
+        .history
+        .latest()
+        .map(|latest| latest.frame + 1)
+        .unwrap_or_default();
+    overlay.history.push(FrameMeasurement {
+        frame,
+        frame_time_ms: time.delta_seconds() * 1000.,
+        entity_count: entities.len(),
+        stages_ms: timings.last,
+    });
+}
+
+fn show_performance_overlay(
+    mut egui_context: ResMut<EguiContext>,
+    diagnostics: Res<Diagnostics>,
+    mut overlay: ResMut<PerformanceOverlay>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("show_performance_overlay").entered();
+    let overlay = &mut *overlay;
+    let fps = diagnostics
+        .get(FrameTimeDiagnosticsPlugin::FPS)
+        .and_then(|fps| fps.smoothed());
+    let [p50, p95, p99] = percentiles(&overlay.history, &mut overlay.scratch, [50., 95., 99.]);
+    let entity_count = overlay
+        .history
+        .latest()
+        .map(|frame| frame.entity_count)
+        .unwrap_or_default();
+
+    let mut dump = false;
+    egui::Window::new("Performance")
+        .anchor(egui::Align2::RIGHT_TOP, [-10., 10.])
+        .resizable(false)
+        .show(egui_context.ctx_mut(), |ui| {
+            match fps {
+                Some(fps) => ui.label(format!("FPS: {fps:.0}")),
+                None => ui.label("FPS: -"),
+            };
+            ui.label(format!(
+                "Frame time p50 {p50:.2} ms, p95 {p95:.2} ms, p99 {p99:.2} ms"
+            ));
+            ui.label(format!("Entities: {entity_count}"));
+            draw_frame_graph(ui, &overlay.history);
+
+            ui.separator();
+            show_stage_breakdown(ui, &overlay.history);
+            ui.weak("Build with the tracing feature for timings of single systems");
+
+            ui.separator();
+            ui.horizontal(|ui| {
+                ui.label("Frames");
+                ui.add(
+                    egui::DragValue::new(&mut overlay.dump_frames).clamp_range(1..=HISTORY_LENGTH),
+                );
+                dump = ui.button("Save as CSV").clicked();
+            });
+        });
+
+    if dump {
+        match write_csv(&overlay.history, overlay.dump_frames) {
+            Ok(path) => info!("Saved frame measurements to {}", path.to_string_lossy()),
+            Err(error) => error!("Failed to save frame measurements: {error:#}"),
+        }
+    }
+}
+
+/// Bars of the frame times, newest on the right, with a gridline every few milliseconds
+fn draw_frame_graph(ui: &mut egui::Ui, history: &FrameHistory) {
+    let width = ui.available_width().max(HISTORY_LENGTH as f32 / 2.);
+    let (response, painter) =
+        ui.allocate_painter(egui::vec2(width, GRAPH_HEIGHT), egui::Sense::hover());
+    let rect = response.rect;
+    painter.rect_filled(rect, 0., egui::Color32::from_black_alpha(160));
+
+    let slowest = history
+        .iter()
+        .map(|frame| frame.frame_time_ms)
+        .fold(0., f32::max);
+    let ceiling = graph_ceiling(slowest);
+    let height_of = |ms: f32| rect.bottom() - ms.min(ceiling) / ceiling * rect.height();
+
+    let grid_step = if ceiling > 50. { 10. } else { 5. };
+    let mut ms = grid_step;
+    while ms < ceiling {
+        let y = height_of(ms);
+        painter.line_segment(
+            [egui::pos2(rect.left(), y), egui::pos2(rect.right(), y)],
+            egui::Stroke::new(1., egui::Color32::from_gray(70)),
+        );
+        painter.text(
+            egui::pos2(rect.left() + 2., y),
+            egui::Align2::LEFT_BOTTOM,
+            format!("{ms} ms"),
+            egui::FontId::monospace(9.),
+            egui::Color32::from_gray(150),
+        );
+        ms += grid_step;
+    }
+
+    let bar_width = rect.width() / HISTORY_LENGTH as f32;
+    // Right-aligned so that a history that is not full yet grows in from the right
+    let first_x = rect.right() - history.len() as f32 * bar_width;
+    for (index, frame) in history.iter().enumerate() {
+        let color = if frame.frame_time_ms > CRITICAL_FRAME_TIME_MS {
+            egui::Color32::from_rgb(230, 80, 80)
+        } else if frame.frame_time_ms > WARNING_FRAME_TIME_MS {
+            egui::Color32::from_rgb(230, 200, 80)
+        } else {
+            egui::Color32::from_rgb(100, 200, 100)
+        };
+        let x = first_x + (index as f32 + 0.5) * bar_width;
+        painter.line_segment(
+            [
+                egui::pos2(x, rect.bottom()),
+                egui::pos2(x, height_of(frame.frame_time_ms)),
+            ],
+            egui::Stroke::new(bar_width, color),
+        );
+    }
+}
+
+/// Average and maximum duration of each stage over the history, most expensive first
+fn show_stage_breakdown(ui: &mut egui::Ui, history: &FrameHistory) {
+    let mut totals = [0.; STAGE_COUNT];
+    let mut maxima = [0.; STAGE_COUNT];
+    let mut measured_frames = 0;
+    for stages in history.iter().filter_map(|frame| frame.stages_ms) {
+        measured_frames += 1;
+        for (index, ms) in stages.into_iter().enumerate() {
+            totals[index] += ms;
+            maxima[index] = f32::max(maxima[index], ms);
+        }
+    }
+    if measured_frames == 0 {
+        ui.label("No stage timings yet");
+        return;
+    }
+    let mut order: [usize; STAGE_COUNT] = std::array::from_fn(|index| index);
+    order.sort_by(|a, b| totals[*b].total_cmp(&totals[*a]));
+    egui::Grid::new("stage_timings")
+        .striped(true)
+        .show(ui, |ui| {
+            ui.strong("Stage");
+            ui.strong("Average");
+            ui.strong("Max");
+            ui.end_row();
+            for index in order {
+                ui.label(STAGE_NAMES[index]);
+                ui.monospace(format!("{:6.2} ms", totals[index] / measured_frames as f32));
+                ui.monospace(format!("{:6.2} ms", maxima[index]));
+                ui.end_row();
+            }
+        });
+}
+
+/// Rounds the slowest frame time up to a multiple of 10 ms, so that the graph doesn't rescale on every frame
+fn graph_ceiling(slowest_ms: f32) -> f32 {
+    ((slowest_ms / 10.).ceil() * 10.).max(20.)
+}
+
+/// Nearest-rank percentiles of the frame times in the history
+fn percentiles<const N: usize>(
+    history: &FrameHistory,
+    scratch: &mut Vec<f32>,
+    percents: [f32; N],
+) -> [f32; N] {
+    scratch.clear();
+    scratch.extend(history.iter().map(|frame| frame.frame_time_ms));
+    scratch.sort_unstable_by(f32::total_cmp);
+    percents.map(|percent| {
+        if scratch.is_empty() {
+            return 0.;
+        }
+        let rank = (percent / 100. * scratch.len() as f32).ceil() as usize;
+        scratch[rank.clamp(1, scratch.len()) - 1]
+    })
+}
+
+fn write_csv(history: &FrameHistory, frames: usize) -> anyhow::Result<PathBuf> {
+    let csv = to_csv(history, frames);
+    fs::create_dir_all(CSV_DIRECTORY)?;
+    let path = Path::new(CSV_DIRECTORY).join(format!(
+        "frames_{}.csv",
+        Local::now().format("%Y-%m-%d_%H-%M-%S")
+    ));
+    fs::write(&path, csv)?;
+    Ok(path)
+}
+
+/// The last `frames` frames of the history, one per line. Stage columns are empty for frames without stage timings.
+fn to_csv(history: &FrameHistory, frames: usize) -> String {
+    let mut csv = String::from("frame,frame_time_ms,entities");
+    for name in STAGE_NAMES {
+        let _ = write!(csv, ",{}_ms", name.to_lowercase().replace(' ', "_"));
+    }
+    csv.push('\n');
+    for frame in history.iter().skip(history.len().saturating_sub(frames)) {
+        let _ = write!(
+            csv,
+            "{},{:.3},{}",
+            frame.frame, frame.frame_time_ms, frame.entity_count
+        );
+        for index in 0..STAGE_COUNT {
+            match frame.stages_ms {
+                Some(stages) => {
+                    let _ = write!(csv, ",{:.3}", stages[index]);
+                }
+                None => csv.push(','),
+            }
+        }
+        csv.push('\n');
+    }
+    csv
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    fn frame(frame: u64, frame_time_ms: f32) -> FrameMeasurement {
+        FrameMeasurement {
+            frame,
+            frame_time_ms,
+            entity_count: 0,
+            stages_ms: None,
+        }
+    }
+
+    #[test]
+    fn history_overwrites_oldest_frames() {
+        let mut history = FrameHistory::new(3);
+        for index in 0..5 {
+            history.push(frame(index, index as f32));
+        }
+        let frames: Vec<_> = history.iter().map(|frame| frame.frame).collect();
+        assert_eq!(frames, vec![2, 3, 4]);
+    }
+
+    #[test]
+    fn percentiles_use_nearest_rank() {
+        let mut history = FrameHistory::new(100);
+        for index in 0..100 {
+            history.push(frame(index, (index + 1) as f32));
+        }
+        let mut scratch = Vec::new();
+        assert_eq!(
+            percentiles(&history, &mut scratch, [50., 95., 99., 100.]),
+            [50., 95., 99., 100.]
+        );
+        assert_eq!(
+            percentiles(&FrameHistory::new(1), &mut scratch, [50.]),
+            [0.]
+        );
+    }
+
+    #[test]
+    fn stage_timings_need_a_whole_frame() {
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+        let mut timings = StageTimings::default();
+        // Starts measuring in the middle of a frame
+        timings.mark(3, at(0));
+        timings.mark(0, at(1));
+        assert_eq!(timings.last, None);
+        for stage in 1..STAGE_COUNT {
+            timings.mark(stage, at(1 + stage as u64));
+        }
+        timings.mark(0, at(20));
+        assert_eq!(timings.last, Some([1., 1., 1., 1., 1., 1., 13.]));
+    }
+
+    #[test]
+    fn csv_contains_the_last_frames() {
+        let mut history = FrameHistory::new(4);
+        for index in 0..4 {
+            history.push(frame(index, 10.));
+        }
+        let csv = to_csv(&history, 2);
+        let lines: Vec<_> = csv.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("frame,frame_time_ms,entities,first_ms"));
+        assert!(lines[0].ends_with("render_and_present_ms"));
+        assert_eq!(lines[1], "2,10.000,0,,,,,,,");
+    }
+}
@@ -0,0 +1,556 @@
+use crate::level_instantiation::spawning::SpawnTracker;
+use crate::util::log_error::log_errors;
+use crate::GameState;
+use anyhow::{Context, Result};
+use bevy::math::Ray;
+use bevy::prelude::*;
+use bevy_editor_pls::default_windows::hierarchy::HierarchyWindow;
+use bevy_editor_pls::Editor;
+use bevy_egui::EguiContext;
+use bevy_prototype_debug_lines::DebugLines;
+use std::f32::consts::TAU;
+
+/// Handles drawn on the selected object while the editor is open that move, rotate or scale it when dragged with the mouse.
+/// W, E and R switch between [`GizmoMode::Translate`], [`GizmoMode::Rotate`] and [`GizmoMode::Scale`].
+/// Holding Ctrl while dragging snaps to the increments of the [`TransformGizmo`].
+///
+/// The gizmo always manipulates the spawned object the selection belongs to, so that the change ends up in the saved level.
+/// Rapier scales colliders by their global transform, so scaled objects collide according to their new size.
+pub struct TransformGizmoPlugin;
+
+impl Plugin for TransformGizmoPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<GizmoMode>()
+            .register_type::<GizmoSpace>()
+            .init_resource::<TransformGizmo>()
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(select_gizmo_mode)
+                    .with_system(update_gizmo_target.pipe(log_errors))
+                    .with_system(
+                        drag_gizmo
+                            .pipe(log_errors)
+                            .after(update_gizmo_target)
+                            .after(select_gizmo_mode),
+                    )
+                    .with_system(draw_gizmo.after(drag_gizmo)),
+            );
+    }
+}
+
+/// Distance in pixels within which the cursor grabs a handle
+const PICK_RADIUS: f32 = 10.;
+/// Fraction of the distance to the camera that the handles are long, so that they have the same size on screen
+const SCREEN_SIZE: f32 = 0.15;
+/// Pixels the cursor moves to the right to double the size of an object with the uniform scale handle
+const UNIFORM_SCALE_PIXELS: f32 = 200.;
+const RING_SEGMENTS: usize = 48;
+const MIN_SCALE: f32 = 0.01;
+const AXIS_COLORS: [Color; 3] = [Color::RED, Color::GREEN, Color::BLUE];
+const ACTIVE_COLOR: Color = Color::YELLOW;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Reflect, FromReflect)]
+pub enum GizmoMode {
+    #[default]
+    Translate,
+    Rotate,
+    Scale,
+}
+
+/// Whether the handles follow the world axes or the object's own. Scaling always happens along the object's own axes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Reflect, FromReflect)]
+pub enum GizmoSpace {
+    #[default]
+    World,
+    Local,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum GizmoHandle {
+    /// Moves along, rotates around or scales along the axis with this index
+    Axis(usize),
+    /// Moves within the plane whose normal is the axis with this index
+    Plane(usize),
+    UniformScale,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct GizmoDrag {
+    handle: GizmoHandle,
+    start_transform: Transform,
+    axes: [Vec3; 3],
+    start_cursor: Vec2,
+    /// Where the cursor ray first hit the handle's axis or plane, relative to the object
+    start_point: Vec3,
+}
+
+#[derive(Debug, Clone, PartialEq, Resource)]
+pub struct TransformGizmo {
+    pub mode: GizmoMode,
+    pub space: GizmoSpace,
+    /// Grid size in m of snapped moves
+    pub translation_snap: f32,
+    /// Increment in degrees of snapped rotations
+    pub rotation_snap: f32,
+    /// Increment of snapped scales
+    pub scale_snap: f32,
+    /// Spawned object the gizmo is shown on
+    pub target: Option<Entity>,
+    hovered: Option<GizmoHandle>,
+    drag: Option<GizmoDrag>,
+}
+
+impl Default for TransformGizmo {
+    fn default() -> Self {
+        Self {
+            mode: default(),
+            space: default(),
+            translation_snap: 0.5,
+            rotation_snap: 15.,
+            scale_snap: 0.1,
+            target: None,
+            hovered: None,
+            drag: None,
+        }
+    }
+}
+
+impl TransformGizmo {
+    pub fn is_dragging(&self) -> bool {
+        self.drag.is_some()
+    }
+
+    fn axes(&self, transform: &Transform) -> [Vec3; 3] {
+        match (self.mode, self.space) {
+            (GizmoMode::Scale, _) | (_, GizmoSpace::Local) => [
+                transform.rotation * Vec3::X,
+                transform.rotation * Vec3::Y,
+                transform.rotation * Vec3::Z,
+            ],
+            _ => [Vec3::X, Vec3::Y, Vec3::Z],
+        }
+    }
+
+    fn handles(&self) -> Vec<GizmoHandle> {
+        let axes = (0..3).map(GizmoHandle::Axis);
+        match self.mode {
+            GizmoMode::Translate => axes.chain((0..3).map(GizmoHandle::Plane)).collect(),
+            GizmoMode::Rotate => axes.collect(),
+            GizmoMode::Scale => axes.chain([GizmoHandle::UniformScale]).collect(),
+        }
+    }
+
+    /// The lines a handle is drawn with
+    fn handle_lines(
+        &self,
+        handle: GizmoHandle,
+        center: Vec3,
+        axes: [Vec3; 3],
+        size: f32,
+    ) -> Vec<(Vec3, Vec3)> {
+        match (self.mode, handle) {
+            (GizmoMode::Rotate, GizmoHandle::Axis(axis)) => {
+                let (u, v) = (axes[(axis + 1) % 3], axes[(axis + 2) % 3]);
+                let point = |segment: usize| {
+                    let angle = segment as f32 / RING_SEGMENTS as f32 * TAU;
+                    center + (u * angle.cos() + v * angle.sin()) * size
+                };
+                (0..RING_SEGMENTS)
+                    .map(|segment| (point(segment), point(segment + 1)))
+                    .collect()
+            }
+            (GizmoMode::Scale, GizmoHandle::Axis(axis)) => {
+                let end = center + axes[axis] * size;
+                let mut lines = vec![(center, end)];
+                lines.extend(box_lines(end, axes, size * 0.05));
+                lines
+            }
+            (_, GizmoHandle::Axis(axis)) => {
+                let end = center + axes[axis] * size;
+                let (u, v) = (axes[(axis + 1) % 3], axes[(axis + 2) % 3]);
+                let tip = size * 0.1;
+                let arrow_base = end - axes[axis] * tip;
+                vec![
+                    (center, end),
+                    (end, arrow_base + u * tip * 0.4),
+                    (end, arrow_base - u * tip * 0.4),
+                    (end, arrow_base + v * tip * 0.4),
+                    (end, arrow_base - v * tip * 0.4),
+                ]
+            }
+            (_, GizmoHandle::Plane(axis)) => {
+                let (u, v) = (axes[(axis + 1) % 3], axes[(axis + 2) % 3]);
+                let corners = [(0.2, 0.2), (0.4, 0.2), (0.4, 0.4), (0.2, 0.4)]
+                    .map(|(a, b)| center + (u * a + v * b) * size);
+                (0..4).map(|i| (corners[i], corners[(i + 1) % 4])).collect()
+            }
+            (_, GizmoHandle::UniformScale) => box_lines(center, axes, size * 0.08),
+        }
+    }
+}
+
+fn box_lines(center: Vec3, axes: [Vec3; 3], half_size: f32) -> Vec<(Vec3, Vec3)> {
+    let corner = |i: usize| {
+        center
+            + (0..3)
+                .map(|axis| {
+                    let sign = if i & (1 << axis) == 0 { -1. } else { 1. };
+                    axes[axis] * sign * half_size
+                })
+                .sum::<Vec3>()
+    };
+    (0..8)
+        .flat_map(|i| (0..3).map(move |axis| (i, i | (1 << axis))))
+        .filter(|(i, j)| i != j)
+        .map(|(i, j)| (corner(i), corner(j)))
+        .collect()
+}
+
+fn select_gizmo_mode(
+    keyboard: Res<Input<KeyCode>>,
+    mouse: Res<Input<MouseButton>>,
+    editor: Res<Editor>,
+    mut egui_context: ResMut<EguiContext>,
+    mut gizmo: ResMut<TransformGizmo>,
+) {
+    // The editor's fly camera moves with WASD while the right mouse button is held
+    if !editor.active()
+        || gizmo.is_dragging()
+        || mouse.pressed(MouseButton::Right)
+        || egui_context.ctx_mut().wants_keyboard_input()
+    {
+        return;
+    }
+    if keyboard.just_pressed(KeyCode::W) {
+        gizmo.mode = GizmoMode::Translate;
+    } else if keyboard.just_pressed(KeyCode::E) {
+        gizmo.mode = GizmoMode::Rotate;
+    } else if keyboard.just_pressed(KeyCode::R) {
+        gizmo.mode = GizmoMode::Scale;
+    }
+}
+
+fn update_gizmo_target(
+    editor: Res<Editor>,
+    mut gizmo: ResMut<TransformGizmo>,
+    spawned_query: Query<(), With<SpawnTracker>>,
+    parent_query: Query<&Parent>,
+) -> Result<()> {
+    let selected = editor
+        .window_state::<HierarchyWindow>()
+        .context("Failed to read hierarchy window state")?
+        .selected
+        .iter()
+        .next();
+    // Selecting a part of a spawned object, e.g. a mesh of its scene, selects the whole object
+    let target = selected.and_then(|selected| {
+        std::iter::successors(Some(selected), |entity| {
+            parent_query.get(*entity).ok().map(|parent| parent.get())
+        })
+        .find(|entity| spawned_query.contains(*entity))
+    });
+    let target = target.filter(|_| editor.active());
+    if gizmo.target != target {
+        gizmo.target = target;
+        gizmo.drag = None;
+    }
+    Ok(())
+}
+
+fn drag_gizmo(
+    mouse: Res<Input<MouseButton>>,
+    keyboard: Res<Input<KeyCode>>,
+    windows: Res<Windows>,
+    mut egui_context: ResMut<EguiContext>,
+    mut gizmo: ResMut<TransformGizmo>,
+    mut transforms: Query<&mut Transform, With<SpawnTracker>>,
+    camera_query: Query<(&Camera, &GlobalTransform)>,
+) -> Result<()> {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("drag_gizmo").entered();
+    let target = match gizmo.target {
+        Some(target) => target,
+        None => return Ok(()),
+    };
+    let cursor = windows
+        .get_primary()
+        .context("Failed to get primary window")?
+        .cursor_position();
+    let camera = get_active_camera(&camera_query);
+    let (cursor, (camera, camera_transform)) = match cursor.zip(camera) {
+        Some(cursor_and_camera) => cursor_and_camera,
+        None => {
+            gizmo.hovered = None;
+            return Ok(());
+        }
+    };
+    let ray = match camera.viewport_to_world(camera_transform, cursor) {
+        Some(ray) => ray,
+        None => return Ok(()),
+    };
+    let mut transform = match transforms.get_mut(target) {
+        Ok(transform) => transform,
+        Err(_) => return Ok(()),
+    };
+
+    if !mouse.pressed(MouseButton::Left) {
+        gizmo.drag = None;
+    }
+    let drag = match gizmo.drag {
+        Some(drag) => drag,
+        None => {
+            let center = transform.translation;
+            let axes = gizmo.axes(&transform);
+            let size = center.distance(camera_transform.translation()) * SCREEN_SIZE;
+            let project = |point: Vec3| camera.world_to_viewport(camera_transform, point);
+            let hovered = gizmo
+                .handles()
+                .into_iter()
+                .filter_map(|handle| {
+                    let distance = gizmo
+                        .handle_lines(handle, center, axes, size)
+                        .into_iter()
+                        .filter_map(|(a, b)| {
+                            Some(distance_to_segment(cursor, project(a)?, project(b)?))
+                        })
+                        .reduce(f32::min)?;
+                    (distance <= PICK_RADIUS).then_some((handle, distance))
+                })
+                .min_by(|(_, a), (_, b)| a.total_cmp(b))
+                .map(|(handle, _distance)| handle);
+            gizmo.hovered = hovered;
+            let over_ui = egui_context.ctx_mut().is_pointer_over_area();
+            if let Some(handle) = gizmo.hovered.filter(|_| !over_ui) {
+                if mouse.just_pressed(MouseButton::Left) {
+                    let start_point = hit_handle(handle, gizmo.mode, &ray, center, axes)
+                        .map(|point| point - center)
+                        .unwrap_or_default();
+                    gizmo.drag = Some(GizmoDrag {
+                        handle,
+                        start_transform: *transform,
+                        axes,
+                        start_cursor: cursor,
+                        start_point,
+                    });
+                }
+            }
+            return Ok(());
+        }
+    };
+
+    let snap = keyboard.any_pressed([KeyCode::LControl, KeyCode::RControl]);
+    let start = drag.start_transform;
+    let center = start.translation;
+    let point = match hit_handle(drag.handle, gizmo.mode, &ray, center, drag.axes) {
+        Some(point) => point - center,
+        None if drag.handle == GizmoHandle::UniformScale => Vec3::ZERO,
+        // The ray runs parallel to the handle, keep the last result
+        None => return Ok(()),
+    };
+    let new_transform = match (gizmo.mode, drag.handle) {
+        (GizmoMode::Translate, GizmoHandle::Axis(axis)) => {
+            let distance = (point - drag.start_point).dot(drag.axes[axis]);
+            let distance = snap_to(distance, gizmo.translation_snap, snap);
+            start.with_translation(center + drag.axes[axis] * distance)
+        }
+        (GizmoMode::Translate, GizmoHandle::Plane(axis)) => {
+            let offset = point - drag.start_point;
+            let translation = [(axis + 1) % 3, (axis + 2) % 3]
+                .into_iter()
+                .map(|other| {
+                    let distance =
+                        snap_to(offset.dot(drag.axes[other]), gizmo.translation_snap, snap);
+                    drag.axes[other] * distance
+                })
+                .sum::<Vec3>();
+            start.with_translation(center + translation)
+        }
+        (GizmoMode::Rotate, GizmoHandle::Axis(axis)) => {
+            let angle = signed_angle(drag.start_point, point, drag.axes[axis]);
+            let angle = snap_to(angle.to_degrees(), gizmo.rotation_snap, snap).to_radians();
+            start.with_rotation(Quat::from_axis_angle(drag.axes[axis], angle) * start.rotation)
+        }
+        (GizmoMode::Scale, GizmoHandle::Axis(axis)) => {
+            let start_distance = drag.start_point.dot(drag.axes[axis]);
+            if start_distance.abs() < f32::EPSILON {
+                return Ok(());
+            }
+            let factor = point.dot(drag.axes[axis]) / start_distance;
+            let mut scale = start.scale;
+            scale[axis] = snap_scale(start.scale[axis] * factor, gizmo.scale_snap, snap);
+            start.with_scale(scale)
+        }
+        (GizmoMode::Scale, GizmoHandle::UniformScale) => {
+            let factor = 1. + (cursor.x - drag.start_cursor.x) / UNIFORM_SCALE_PIXELS;
+            let scale = (start.scale * factor.max(0.))
+                .to_array()
+                .map(|scale| snap_scale(scale, gizmo.scale_snap, snap));
+            start.with_scale(Vec3::from(scale))
+        }
+        _ => return Ok(()),
+    };
+    // Only written on change so that physics is not disturbed while holding still
+    if *transform != new_transform {
+        *transform = new_transform;
+    }
+    Ok(())
+}
+
+fn draw_gizmo(
+    gizmo: Res<TransformGizmo>,
+    transforms: Query<&Transform, With<SpawnTracker>>,
+    camera_query: Query<(&Camera, &GlobalTransform)>,
+    mut lines: ResMut<DebugLines>,
+) {
+    let transform = match gizmo.target.and_then(|target| transforms.get(target).ok()) {
+        Some(transform) => transform,
+        None => return,
+    };
+    let (_camera, camera_transform) = match get_active_camera(&camera_query) {
+        Some(camera) => camera,
+        None => return,
+    };
+    // While dragging, the handles stay where the drag started
+    let (center, axes) = match gizmo.drag {
+        Some(drag) => (transform.translation, drag.axes),
+        None => (transform.translation, gizmo.axes(transform)),
+    };
+    let size = center.distance(camera_transform.translation()) * SCREEN_SIZE;
+    let active = gizmo.drag.map(|drag| drag.handle).or(gizmo.hovered);
+    for handle in gizmo.handles() {
+        let color = match handle {
+            _ if Some(handle) == active => ACTIVE_COLOR,
+            GizmoHandle::Axis(axis) | GizmoHandle::Plane(axis) => AXIS_COLORS[axis],
+            GizmoHandle::UniformScale => Color::WHITE,
+        };
+        for (a, b) in gizmo.handle_lines(handle, center, axes, size) {
+            lines.line_colored(a, b, 0.0, color);
+        }
+    }
+}
+
+/// The camera that renders the scene right now, which is the editor's camera while the editor is open
+fn get_active_camera<'a>(
+    camera_query: &'a Query<(&Camera, &GlobalTransform)>,
+) -> Option<(&'a Camera, &'a GlobalTransform)> {
+    camera_query
+        .iter()
+        .filter(|(camera, _transform)| camera.is_active)
+        .max_by_key(|(camera, _transform)| camera.priority)
+}
+
+/// Where the ray hits the line or plane a handle is dragged along
+fn hit_handle(
+    handle: GizmoHandle,
+    mode: GizmoMode,
+    ray: &Ray,
+    center: Vec3,
+    axes: [Vec3; 3],
+) -> Option<Vec3> {
+    match (mode, handle) {
+        (GizmoMode::Rotate, GizmoHandle::Axis(axis)) | (_, GizmoHandle::Plane(axis)) => {
+            intersect_plane(ray, center, axes[axis])
+        }
+        (_, GizmoHandle::Axis(axis)) => {
+            let distance = closest_distance_on_line(ray, center, axes[axis])?;
+            Some(center + axes[axis] * distance)
+        }
+        (_, GizmoHandle::UniformScale) => None,
+    }
+}
+
+/// Distance along the line through `point` in `direction` of the point that comes closest to the ray
+fn closest_distance_on_line(ray: &Ray, point: Vec3, direction: Vec3) -> Option<f32> {
+    let offset = ray.origin - point;
+    let alignment = direction.dot(ray.direction);
+    let denominator = 1. - alignment * alignment;
+    if denominator.abs() < 1e-4 {
+        return None;
+    }
+    Some((direction.dot(offset) - alignment * ray.direction.dot(offset)) / denominator)
+}
+
+fn intersect_plane(ray: &Ray, point: Vec3, normal: Vec3) -> Option<Vec3> {
+    let denominator = normal.dot(ray.direction);
+    if denominator.abs() < 1e-4 {
+        return None;
+    }
+    let distance = normal.dot(point - ray.origin) / denominator;
+    (distance >= 0.).then_some(ray.origin + ray.direction * distance)
+}
+
+/// Angle in radians from `from` to `to` when looking against `axis`, counterclockwise being positive
+fn signed_angle(from: Vec3, to: Vec3, axis: Vec3) -> f32 {
+    from.cross(to).dot(axis).atan2(from.dot(to))
+}
+
+fn distance_to_segment(point: Vec2, a: Vec2, b: Vec2) -> f32 {
+    let segment = b - a;
+    let length_squared = segment.length_squared();
+    let t = if length_squared > 0. {
+        ((point - a).dot(segment) / length_squared).clamp(0., 1.)
+    } else {
+        0.
+    };
+    point.distance(a + segment * t)
+}
+
+fn snap_to(value: f32, increment: f32, snap: bool) -> f32 {
+    if snap && increment > 0. {
+        (value / increment).round() * increment
+    } else {
+        value
+    }
+}
+
+fn snap_scale(scale: f32, increment: f32, snap: bool) -> f32 {
+    // Zero scale makes colliders degenerate
+    let minimum = if snap && increment > 0. {
+        increment
+    } else {
+        MIN_SCALE
+    };
+    snap_to(scale, increment, snap).max(minimum)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn finds_closest_point_on_axis() {
+        let ray = Ray {
+            origin: Vec3::new(2., 5., 0.),
+            direction: Vec3::NEG_Y,
+        };
+        let distance = closest_distance_on_line(&ray, Vec3::ZERO, Vec3::X).unwrap();
+        assert!((distance - 2.).abs() < 1e-5);
+        assert_eq!(closest_distance_on_line(&ray, Vec3::ZERO, Vec3::Y), None);
+    }
+
+    #[test]
+    fn intersects_planes_in_front_only() {
+        let ray = Ray {
+            origin: Vec3::new(1., 5., 2.),
+            direction: Vec3::NEG_Y,
+        };
+        let hit = intersect_plane(&ray, Vec3::ZERO, Vec3::Y).unwrap();
+        assert!(hit.distance(Vec3::new(1., 0., 2.)) < 1e-5);
+        assert_eq!(intersect_plane(&ray, Vec3::Y * 10., Vec3::Y), None);
+    }
+
+    #[test]
+    fn measures_signed_angles() {
+        let angle = signed_angle(Vec3::X, Vec3::NEG_Z, Vec3::Y);
+        assert!((angle - TAU / 4.).abs() < 1e-5);
+        assert!((signed_angle(Vec3::NEG_Z, Vec3::X, Vec3::Y) + TAU / 4.).abs() < 1e-5);
+    }
+
+    #[test]
+    fn snaps_only_when_asked() {
+        assert_eq!(snap_to(1.3, 0.5, true), 1.5);
+        assert_eq!(snap_to(1.3, 0.5, false), 1.3);
+        assert_eq!(snap_scale(0.02, 0.1, true), 0.1);
+        assert_eq!(snap_scale(-1., 0.1, false), MIN_SCALE);
+    }
+}
@@ -1,29 +1,48 @@
 pub mod asset_loading;
 pub mod audio;
+pub mod autosave;
 pub mod config;
 pub mod game_state_serialization;
 pub mod level_serialization;
+pub mod localization;
+pub mod particle_definition;
+pub mod quicksave;
+pub mod storage;
+pub mod thumbnail;
+pub mod user_settings;
 
 use bevy::prelude::*;
 
 use crate::file_system_interaction::asset_loading::LoadingPlugin;
 use crate::file_system_interaction::audio::InternalAudioPlugin;
+use crate::file_system_interaction::autosave::AutosavePlugin;
 use crate::file_system_interaction::game_state_serialization::GameStateSerializationPlugin;
 use crate::file_system_interaction::level_serialization::LevelSerializationPlugin;
+use crate::file_system_interaction::localization::LocalizationPlugin;
+use crate::file_system_interaction::quicksave::QuicksavePlugin;
+use crate::file_system_interaction::thumbnail::ThumbnailPlugin;
 
 /// Handles loading and saving of levels and save states to disk.
 /// Split into the following sub-plugins:
 /// - [`LoadingPlugin`] handles loading of assets.
 /// - [`GameStateSerializationPlugin`] handles saving and loading of game states.
+/// - [`AutosavePlugin`] handles saving the game in the background at checkpoints.
+/// - [`QuicksavePlugin`] handles quick saving and quick loading with a single key.
+/// - [`ThumbnailPlugin`] handles capturing a screenshot for every save.
 /// - [`LevelSerializationPlugin`] handles saving and loading of levels.
 /// - [`InternalAudioPlugin`]: Handles audio initialization
+/// - [`LocalizationPlugin`]: Handles translated strings
 pub struct FileSystemInteractionPlugin;
 
 impl Plugin for FileSystemInteractionPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugin(LoadingPlugin)
             .add_plugin(GameStateSerializationPlugin)
+            .add_plugin(AutosavePlugin)
+            .add_plugin(QuicksavePlugin)
+            .add_plugin(ThumbnailPlugin)
             .add_plugin(LevelSerializationPlugin)
-            .add_plugin(InternalAudioPlugin);
+            .add_plugin(InternalAudioPlugin)
+            .add_plugin(LocalizationPlugin);
     }
 }
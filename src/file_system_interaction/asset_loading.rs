@@ -1,36 +1,62 @@
 use crate::file_system_interaction::config::GameConfig;
-use crate::file_system_interaction::level_serialization::SerializedLevel;
-use crate::world_interaction::dialog::Dialog;
+use crate::file_system_interaction::level_serialization::{LevelManifest, SerializedLevel};
+use crate::file_system_interaction::localization::LocaleStrings;
+use crate::file_system_interaction::particle_definition::ParticleEffectDefinition;
+use crate::world_interaction::dialog::{Dialog, DialogLoader};
 use crate::GameState;
+use anyhow::Result;
+use bevy::asset::{AssetLoader, LoadContext, LoadState, LoadedAsset};
+use bevy::gltf::Gltf;
 use bevy::prelude::*;
-use bevy::utils::HashMap;
+use bevy::reflect::TypeUuid;
+use bevy::utils::{BoxedFuture, HashMap};
 use bevy_asset_loader::prelude::*;
 use bevy_common_assets::ron::RonAssetPlugin;
 use bevy_common_assets::toml::TomlAssetPlugin;
-use bevy_egui::egui::ProgressBar;
-use bevy_egui::{egui, EguiContext};
+use bevy_kira_audio::prelude::*;
 use bevy_kira_audio::AudioSource;
-use iyes_progress::{ProgressCounter, ProgressPlugin};
+use iyes_progress::prelude::*;
 
 pub struct LoadingPlugin;
 
 impl Plugin for LoadingPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugin(RonAssetPlugin::<SerializedLevel>::new(&["lvl.ron"]))
-            .add_plugin(RonAssetPlugin::<Dialog>::new(&["dlg.ron"]))
+            .add_plugin(RonAssetPlugin::<LevelManifest>::new(&["manifest.ron"]))
+            .add_asset::<Dialog>()
+            .init_asset_loader::<DialogLoader>()
+            .add_plugin(RonAssetPlugin::<LocaleStrings>::new(&["locale.ron"]))
+            .add_plugin(RonAssetPlugin::<ParticleEffectDefinition>::new(&[
+                "particle.ron",
+            ]))
+            .add_asset::<EguiFont>()
+            .init_asset_loader::<EguiFontLoader>()
             .add_plugin(TomlAssetPlugin::<GameConfig>::new(&["game.toml"]))
             .add_plugin(ProgressPlugin::new(GameState::Loading).continue_to(GameState::Menu))
             .add_loading_state(
                 LoadingState::new(GameState::Loading)
+                    .on_failure_continue_to(GameState::LoadingFailed)
                     .with_collection::<AudioAssets>()
                     .with_collection::<SceneAssets>()
                     .with_collection::<AnimationAssets>()
                     .with_collection::<LevelAssets>()
                     .with_collection::<DialogAssets>()
+                    .with_collection::<LocaleAssets>()
                     .with_collection::<TextureAssets>()
+                    .with_collection::<ParticleEffects>()
                     .with_collection::<ConfigAssets>(),
             )
-            .add_system_set(SystemSet::on_update(GameState::Loading).with_system(show_progress));
+            .add_audio_channel::<WarmUpChannel>()
+            .add_system_set(
+                SystemSet::on_enter(GameState::Loading).with_system(start_audio_preload),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Loading)
+                    .with_system(track_audio_preload.track_progress()),
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::Loading).with_system(finish_audio_preload),
+            );
     }
 }
 
@@ -41,6 +67,128 @@ impl Plugin for LoadingPlugin {
 pub struct AudioAssets {
     #[asset(path = "audio/walking.ogg")]
     pub walking: Handle<AudioSource>,
+    /// Footstep clips by path. See `audio/footsteps/README.md` for the naming scheme.
+    /// Filled in from the [`PreloadedAudio`] when loading is done.
+    pub footsteps: HashMap<String, Handle<AudioSource>>,
+    /// Music tracks by path. Tracks are identified by their file name without extension.
+    /// Filled in from the [`PreloadedAudio`] when loading is done.
+    pub music: HashMap<String, Handle<AudioSource>>,
+    /// Dialog voice lines by path. Filled in from the [`PreloadedAudio`] when loading is done.
+    pub voices: HashMap<String, Handle<AudioSource>>,
+}
+
+/// Folders whose sounds are loaded and warmed up during [`GameState::Loading`] in addition to the [`AudioAssets`].
+#[cfg(feature = "native")]
+const PRELOADED_AUDIO_FOLDERS: [&str; 3] = ["audio/footsteps", "audio/music", "audio/voices"];
+
+/// Wasm cannot load folders, so the preloaded sounds have to be listed explicitly.
+#[cfg(feature = "wasm")]
+const PRELOADED_AUDIO_FILES: [&str; 0] = [];
+
+/// Optional sounds that are loaded during [`GameState::Loading`] and moved into the [`AudioAssets`] afterwards.
+/// Unlike the asset collections, a file that fails to load is skipped with a warning instead of blocking the loading state.
+/// Every loaded sound is played once at zero volume so that its first real playback does not hitch.
+#[derive(Debug, Clone, Resource, Default)]
+pub struct PreloadedAudio {
+    pending: Vec<HandleUntyped>,
+    loaded: HashMap<String, Handle<AudioSource>>,
+    total: u32,
+}
+
+impl PreloadedAudio {
+    pub fn is_done(&self) -> bool {
+        self.pending.is_empty()
+    }
+}
+
+/// Plays the sounds that are warmed up during loading. Stopped as soon as loading is done.
+#[derive(Debug, Clone, Copy, Resource, Default)]
+struct WarmUpChannel;
+
+fn start_audio_preload(mut commands: Commands, asset_server: Res<AssetServer>) {
+    #[cfg(feature = "native")]
+    let pending: Vec<_> = PRELOADED_AUDIO_FOLDERS
+        .iter()
+        .filter_map(|folder| match asset_server.load_folder(folder) {
+            Ok(handles) => Some(handles),
+            Err(error) => {
+                warn!("Failed to preload audio folder \"{folder}\": {error}");
+                None
+            }
+        })
+        .flatten()
+        .collect();
+    #[cfg(feature = "wasm")]
+    let pending: Vec<_> = PRELOADED_AUDIO_FILES
+        .iter()
+        .map(|path| asset_server.load_untyped(*path))
+        .collect();
+    commands.insert_resource(PreloadedAudio {
+        total: pending.len() as u32,
+        pending,
+        loaded: default(),
+    });
+}
+
+fn track_audio_preload(
+    preloaded_audio: Option<ResMut<PreloadedAudio>>,
+    asset_server: Res<AssetServer>,
+    warm_up: Res<AudioChannel<WarmUpChannel>>,
+) -> Progress {
+    let mut preloaded_audio = match preloaded_audio {
+        Some(preloaded_audio) => preloaded_audio,
+        None => return Progress { done: 0, total: 1 },
+    };
+    let PreloadedAudio {
+        pending, loaded, ..
+    } = &mut *preloaded_audio;
+    pending.retain(|handle| {
+        let path = asset_server
+            .get_handle_path(handle)
+            .map(|path| path.path().to_string_lossy().replace('\\', "/"))
+            .unwrap_or_default();
+        match asset_server.get_load_state(handle) {
+            LoadState::Loaded => {
+                let handle = handle.clone().typed::<AudioSource>();
+                warm_up.play(handle.clone()).with_volume(0.);
+                loaded.insert(path, handle);
+                false
+            }
+            LoadState::Failed => {
+                warn!("Failed to load sound \"{path}\", skipping it");
+                false
+            }
+            _ => true,
+        }
+    });
+    Progress {
+        done: preloaded_audio.total - preloaded_audio.pending.len() as u32,
+        total: preloaded_audio.total,
+    }
+}
+
+fn finish_audio_preload(
+    mut commands: Commands,
+    preloaded_audio: Option<Res<PreloadedAudio>>,
+    audio_assets: Option<ResMut<AudioAssets>>,
+    warm_up: Res<AudioChannel<WarmUpChannel>>,
+) {
+    warm_up.stop();
+    let (preloaded_audio, mut audio_assets) = match (preloaded_audio, audio_assets) {
+        (Some(preloaded_audio), Some(audio_assets)) => (preloaded_audio, audio_assets),
+        _ => return,
+    };
+    for (path, handle) in preloaded_audio.loaded.iter() {
+        let map = if path.starts_with("audio/footsteps/") {
+            &mut audio_assets.footsteps
+        } else if path.starts_with("audio/music/") {
+            &mut audio_assets.music
+        } else {
+            &mut audio_assets.voices
+        };
+        map.insert(path.clone(), handle.clone());
+    }
+    commands.remove_resource::<PreloadedAudio>();
 }
 
 #[derive(AssetCollection, Resource)]
@@ -49,6 +197,9 @@ pub struct SceneAssets {
     pub character: Handle<Scene>,
     #[asset(path = "scenes/old_town.glb#Scene0")]
     pub level: Handle<Scene>,
+    /// The whole glTF file of the level, for the named animations of its [`AnimatedProp`](crate::world_interaction::animated_prop::AnimatedProp)s
+    #[asset(path = "scenes/old_town.glb")]
+    pub level_gltf: Handle<Gltf>,
 }
 
 #[derive(AssetCollection, Resource)]
@@ -81,6 +232,49 @@ pub struct DialogAssets {
     pub dialogs: HashMap<String, Handle<Dialog>>,
 }
 
+#[derive(AssetCollection, Resource)]
+pub struct LocaleAssets {
+    #[cfg_attr(feature = "native", asset(path = "locales", collection(typed, mapped)))]
+    #[cfg_attr(
+        feature = "wasm",
+        asset(
+            paths(
+                "locales/en/ui.locale.ron",
+                "locales/en/dialogs.locale.ron",
+                "locales/de/ui.locale.ron"
+            ),
+            collection(typed, mapped)
+        )
+    )]
+    pub locales: HashMap<String, Handle<LocaleStrings>>,
+}
+
+/// Raw font file for egui, used for languages whose script the default font does not cover.
+/// Loaded from files ending in `.egui.ttf` or `.egui.otf` so that it does not clash with Bevy's own font loader.
+#[derive(Debug, Clone, PartialEq, Eq, TypeUuid)]
+#[uuid = "3c0f5b8e-2a4d-4c1b-8e5f-7d9a6b1c4e20"]
+pub struct EguiFont(pub Vec<u8>);
+
+#[derive(Debug, Clone, Copy, Default)]
+struct EguiFontLoader;
+
+impl AssetLoader for EguiFontLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<()>> {
+        Box::pin(async move {
+            load_context.set_default_asset(LoadedAsset::new(EguiFont(bytes.to_vec())));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["egui.ttf", "egui.otf"]
+    }
+}
+
 #[derive(AssetCollection, Resource)]
 pub struct TextureAssets {
     #[asset(path = "textures/stone_alley_2.jpg")]
@@ -89,48 +283,47 @@ pub struct TextureAssets {
     pub sky: Handle<Image>,
 }
 
+/// Particle effects by path, see [`ParticleEffects::get`] for looking them up by name
 #[derive(AssetCollection, Resource)]
-pub struct ConfigAssets {
-    #[asset(path = "config/config.game.toml")]
-    pub game: Handle<GameConfig>,
+pub struct ParticleEffects {
+    #[cfg_attr(
+        feature = "native",
+        asset(path = "particles", collection(typed, mapped))
+    )]
+    #[cfg_attr(
+        feature = "wasm",
+        asset(
+            paths(
+                "particles/sprint.particle.ron",
+                "particles/slide.particle.ron",
+                "particles/landing.particle.ron",
+                "particles/hard_landing.particle.ron"
+            ),
+            collection(typed, mapped)
+        )
+    )]
+    pub effects: HashMap<String, Handle<ParticleEffectDefinition>>,
 }
 
-fn show_progress(
-    progress: Option<Res<ProgressCounter>>,
-    mut egui_context: ResMut<EguiContext>,
-    mut last_done: Local<u32>,
-    audio_assets: Option<Res<AudioAssets>>,
-    scene_assets: Option<Res<SceneAssets>>,
-    animation_assets: Option<Res<AnimationAssets>>,
-    level_assets: Option<Res<LevelAssets>>,
-    dialog_assets: Option<Res<DialogAssets>>,
-    texture_assets: Option<Res<TextureAssets>>,
-    config_assets: Option<Res<ConfigAssets>>,
-) {
-    if let Some(progress) = progress.map(|counter| counter.progress()) {
-        if progress.done > *last_done {
-            *last_done = progress.done;
-        }
+impl ParticleEffects {
+    /// Handle of the effect defined in `particles/<name>.particle.ron`
+    pub fn get(&self, name: &str) -> Option<&Handle<ParticleEffectDefinition>> {
+        self.effects.get(&format!("particles/{name}.particle.ron"))
+    }
 
-        egui::CentralPanel::default().show(egui_context.ctx_mut(), |ui| {
-            ui.vertical_centered(|ui| {
-                ui.add_space(100.0);
-                ui.heading("Loading");
-                ui.label("Loading assets...");
-                ui.add(
-                    ProgressBar::new(progress.done as f32 / progress.total as f32).animate(true),
-                );
-                ui.add_space(100.0);
-                ui.add_enabled_ui(false, |ui| {
-                    ui.checkbox(&mut audio_assets.is_some(), "Audio");
-                    ui.checkbox(&mut scene_assets.is_some(), "Scenes");
-                    ui.checkbox(&mut animation_assets.is_some(), "Animations");
-                    ui.checkbox(&mut level_assets.is_some(), "Levels");
-                    ui.checkbox(&mut dialog_assets.is_some(), "Dialogs");
-                    ui.checkbox(&mut texture_assets.is_some(), "Textures");
-                    ui.checkbox(&mut config_assets.is_some(), "Config");
-                });
-            });
-        });
+    /// Names of all known effects, e.g. for error messages
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.effects.keys().filter_map(|path| {
+            path.strip_prefix("particles/")?
+                .strip_suffix(".particle.ron")
+        })
     }
 }
+
+#[derive(AssetCollection, Resource)]
+pub struct ConfigAssets {
+    #[asset(path = "config/config.game.toml")]
+    pub game: Handle<GameConfig>,
+    #[asset(path = "config/levels.manifest.ron")]
+    pub levels: Handle<LevelManifest>,
+}
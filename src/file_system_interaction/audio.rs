@@ -1,16 +1,58 @@
-use crate::file_system_interaction::asset_loading::AudioAssets;
+use crate::file_system_interaction::asset_loading::{AudioAssets, ConfigAssets};
+use crate::file_system_interaction::audio::music::{MusicPlugin, SecondaryMusicChannel};
+use crate::file_system_interaction::config::GameConfig;
+use crate::movement::footsteps::{Footstep, SurfaceType};
+use crate::player_control::camera::IngameCamera;
+use crate::player_control::player_embodiment::PlayerLanded;
+use crate::util::log_error::log_errors;
+use crate::world_interaction::dialog::{CurrentDialog, DialogId, PageId};
 use crate::GameState;
+use anyhow::{Context, Result};
 use bevy::prelude::*;
+use bevy::utils::HashMap;
+use bevy::window::WindowFocused;
 use bevy_kira_audio::prelude::{Audio, *};
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::path::Path;
 
-/// Handles initialization of all sounds.
+mod music;
+
+/// Handles initialization and playback of all sounds.
+/// Sounds played through an [`AudioEmitter`] are spatialized relative to the ingame camera,
+/// i.e. they are attenuated with distance and panned to the side they are coming from.
+/// Sounds played directly on a channel, like music or the player's own walking sound, are not spatialized.
+/// Music is played on the [`MusicChannel`], all other new sounds on the [`SfxChannel`].
 pub struct InternalAudioPlugin;
 
 // This plugin is responsible to control the game audio
 impl Plugin for InternalAudioPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugin(AudioPlugin)
-            .add_system_set(SystemSet::on_exit(GameState::Loading).with_system(init_audio));
+            .add_audio_channel::<MusicChannel>()
+            .add_audio_channel::<SfxChannel>()
+            .add_plugin(MusicPlugin)
+            .register_type::<AudioEmitter>()
+            .init_resource::<AudioVolumes>()
+            .add_system(update_volumes)
+            .add_system(apply_volumes.after(update_volumes))
+            .add_system_set(SystemSet::on_exit(GameState::Loading).with_system(init_audio))
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(play_footsteps.pipe(log_errors))
+                    .with_system(play_landing_thud.pipe(log_errors))
+                    .with_system(play_dialog_voices.pipe(log_errors)),
+            )
+            // Keeps following the volumes while the pause menu turns them down
+            .add_system_set(
+                SystemSet::on_in_stack_update(GameState::Playing)
+                    .with_system(update_audio_emitters.after(play_dialog_voices)),
+            )
+            .add_system_set(
+                SystemSet::on_enter(GameState::Paused).with_system(pause_sounds.pipe(log_errors)),
+            )
+            .add_system_set(SystemSet::on_exit(GameState::Paused).with_system(resume_sounds))
+            .add_system_set(SystemSet::on_exit(GameState::Playing).with_system(stop_sounds));
     }
 }
 
@@ -19,12 +61,479 @@ pub struct AudioHandles {
     pub walking: Handle<AudioInstance>,
 }
 
-fn init_audio(mut commands: Commands, audio_assets: Res<AudioAssets>, audio: Res<Audio>) {
+/// Channel for background music. Its volume is set on the channel as a whole.
+/// See [`MusicPlugin`] for how tracks are chosen.
+#[derive(Debug, Clone, Copy, Resource, Default)]
+pub struct MusicChannel;
+
+/// Channel for all sounds that are not music.
+/// Since these are played with individual volumes, e.g. for spatialization, the SFX volume is
+/// multiplied into every sound by hand instead of being set on the channel. See [`AudioVolumes`].
+#[derive(Debug, Clone, Copy, Resource, Default)]
+pub struct SfxChannel;
+
+/// The volumes currently in effect, i.e. the configured ones multiplied by the master volume
+/// and muted while the window is unfocused if so configured.
+#[derive(Debug, Clone, Copy, PartialEq, Resource)]
+pub struct AudioVolumes {
+    pub music: f64,
+    pub sfx: f64,
+}
+
+impl Default for AudioVolumes {
+    fn default() -> Self {
+        Self { music: 1., sfx: 1. }
+    }
+}
+
+const WALKING_VOLUME: f64 = 0.8;
+
+/// The looped walking sound keeps the paused main channel to itself and is resumed and paused by hand.
+/// It follows the SFX volume like every other effect.
+fn init_audio(mut commands: Commands, audio_assets: Option<Res<AudioAssets>>, audio: Res<Audio>) {
+    // Missing when loading failed
+    let audio_assets = match audio_assets {
+        Some(audio_assets) => audio_assets,
+        None => return,
+    };
     audio.pause();
     let handle = audio
         .play(audio_assets.walking.clone())
         .looped()
-        .with_volume(0.8)
+        .with_volume(WALKING_VOLUME)
         .handle();
     commands.insert_resource(AudioHandles { walking: handle });
 }
+
+fn update_volumes(
+    config: Res<Assets<GameConfig>>,
+    mut config_asset_events: EventReader<AssetEvent<GameConfig>>,
+    mut focus_events: EventReader<WindowFocused>,
+    mut audio_config: Local<Option<crate::file_system_interaction::config::Audio>>,
+    mut unfocused: Local<bool>,
+    mut volumes: ResMut<AudioVolumes>,
+    state: Res<State<GameState>>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("update_volumes").entered();
+    for event in config_asset_events.iter() {
+        match event {
+            AssetEvent::Created { handle } | AssetEvent::Modified { handle } => {
+                *audio_config = config.get(handle).map(|config| config.audio.clone());
+            }
+            AssetEvent::Removed { .. } => {}
+        }
+    }
+    for event in focus_events.iter() {
+        *unfocused = !event.focused;
+    }
+    let audio_config = match audio_config.as_ref() {
+        Some(audio_config) => audio_config,
+        None => return,
+    };
+
+    let new_volumes = if *unfocused && audio_config.mute_when_unfocused {
+        AudioVolumes { music: 0., sfx: 0. }
+    } else {
+        let mut master = clamp_volume("master_volume", audio_config.master_volume);
+        if state.current() == &GameState::Paused && !audio_config.pause_in_menu {
+            master *= clamp_volume("menu_volume", audio_config.menu_volume);
+        }
+        AudioVolumes {
+            music: master * clamp_volume("music_volume", audio_config.music_volume),
+            sfx: master * clamp_volume("sfx_volume", audio_config.sfx_volume),
+        }
+    };
+    // Avoid triggering change detection every frame
+    if *volumes != new_volumes {
+        *volumes = new_volumes;
+    }
+}
+
+fn clamp_volume(name: &str, volume: f64) -> f64 {
+    if !(0. ..=1.).contains(&volume) {
+        warn!("Config value audio.{name} = {volume} is outside of 0..1, clamping it");
+    }
+    volume.clamp(0., 1.)
+}
+
+fn pause_sounds(
+    sfx: Res<AudioChannel<SfxChannel>>,
+    music: Res<AudioChannel<MusicChannel>>,
+    secondary_music: Res<AudioChannel<SecondaryMusicChannel>>,
+    audio_handles: Option<Res<AudioHandles>>,
+    mut audio_instances: ResMut<Assets<AudioInstance>>,
+    config_handles: Res<ConfigAssets>,
+    config: Res<Assets<GameConfig>>,
+) -> Result<()> {
+    let config = config
+        .get(&config_handles.game)
+        .context("Failed to get game config from handle")?;
+    if !config.audio.pause_in_menu {
+        return Ok(());
+    }
+    sfx.pause();
+    music.pause();
+    secondary_music.pause();
+    // The walking sound is resumed by the player's movement
+    if let Some(walking) =
+        audio_handles.and_then(|handles| audio_instances.get_mut(&handles.walking))
+    {
+        walking.pause(default());
+    }
+    Ok(())
+}
+
+/// Resuming sounds that are not paused does nothing, so this does not need to know whether they were paused
+fn resume_sounds(
+    sfx: Res<AudioChannel<SfxChannel>>,
+    music: Res<AudioChannel<MusicChannel>>,
+    secondary_music: Res<AudioChannel<SecondaryMusicChannel>>,
+) {
+    sfx.resume();
+    music.resume();
+    secondary_music.resume();
+}
+
+/// Silences the game when quitting to the main menu
+fn stop_sounds(
+    sfx: Res<AudioChannel<SfxChannel>>,
+    audio_handles: Option<Res<AudioHandles>>,
+    mut audio_instances: ResMut<Assets<AudioInstance>>,
+) {
+    sfx.stop();
+    if let Some(walking) =
+        audio_handles.and_then(|handles| audio_instances.get_mut(&handles.walking))
+    {
+        walking.pause(default());
+    }
+}
+
+fn apply_volumes(
+    volumes: Res<AudioVolumes>,
+    audio_handles: Option<Res<AudioHandles>>,
+    mut audio_instances: ResMut<Assets<AudioInstance>>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("apply_volumes").entered();
+    let audio_handles_added = audio_handles
+        .as_ref()
+        .map(|handles| handles.is_added())
+        .unwrap_or_default();
+    if !volumes.is_changed() && !audio_handles_added {
+        return;
+    }
+    // The music volume is applied by the music plugin as part of crossfading
+    if let Some(walking) =
+        audio_handles.and_then(|handles| audio_instances.get_mut(&handles.walking))
+    {
+        walking.set_volume(WALKING_VOLUME * volumes.sfx, AudioTween::default());
+    }
+}
+
+/// A source of spatialized sounds in the world, e.g. an NPC or a fountain.
+/// Its sounds are paused while the listener is further away than [`AudioEmitter::max_distance`].
+#[derive(Debug, Clone, PartialEq, Component, Reflect)]
+#[reflect(Component)]
+pub struct AudioEmitter {
+    /// Distance in m at which the emitter's sounds become inaudible
+    pub max_distance: f32,
+    #[reflect(ignore)]
+    pub sounds: Vec<EmittedSound>,
+}
+
+impl Default for AudioEmitter {
+    fn default() -> Self {
+        Self {
+            max_distance: 30.,
+            sounds: default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EmittedSound {
+    pub instance: Handle<AudioInstance>,
+    /// Volume of the sound when heard right at the emitter
+    pub volume: f64,
+}
+
+impl AudioEmitter {
+    /// Plays a sound at the emitter's position. The spatialization is applied starting with the next update.
+    pub fn play(
+        &mut self,
+        sfx: &AudioChannel<SfxChannel>,
+        source: Handle<AudioSource>,
+        volume: f64,
+    ) -> Handle<AudioInstance> {
+        let instance = sfx.play(source).with_volume(0.).handle();
+        self.sounds.push(EmittedSound {
+            instance: instance.clone(),
+            volume,
+        });
+        instance
+    }
+}
+
+fn update_audio_emitters(
+    camera_query: Query<&GlobalTransform, With<IngameCamera>>,
+    mut emitter_query: Query<(&GlobalTransform, &mut AudioEmitter)>,
+    mut audio_instances: ResMut<Assets<AudioInstance>>,
+    volumes: Res<AudioVolumes>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("update_audio_emitters").entered();
+    let listener = match camera_query.iter().next() {
+        Some(listener) => listener,
+        None => return,
+    };
+    for (emitter_transform, mut emitter) in &mut emitter_query {
+        let (spatial_volume, panning) = get_spatial_volume_and_panning(
+            listener,
+            emitter_transform.translation(),
+            emitter.max_distance,
+        );
+        emitter.sounds.retain(|sound| {
+            let instance = match audio_instances.get_mut(&sound.instance) {
+                Some(instance) => instance,
+                // Still waiting for the backend to start playing the sound
+                None => return true,
+            };
+            match instance.state() {
+                PlaybackState::Stopped => false,
+                PlaybackState::Paused { .. } if spatial_volume > 0. => {
+                    instance.resume(AudioTween::default());
+                    true
+                }
+                PlaybackState::Playing { .. } if spatial_volume <= 0. => {
+                    // Out of earshot, so free up the voice until we come back
+                    instance.pause(AudioTween::default());
+                    true
+                }
+                _ => {
+                    instance.set_volume(
+                        sound.volume * volumes.sfx * spatial_volume,
+                        AudioTween::default(),
+                    );
+                    instance.set_panning(panning, AudioTween::default());
+                    true
+                }
+            }
+        });
+    }
+}
+
+fn play_dialog_voices(
+    current_dialog: Option<Res<CurrentDialog>>,
+    mut last_page: Local<Option<(DialogId, PageId)>>,
+    mut playing_voice: Local<Option<Handle<AudioInstance>>>,
+    mut audio_instances: ResMut<Assets<AudioInstance>>,
+    mut emitter_query: Query<&mut AudioEmitter>,
+    asset_server: Res<AssetServer>,
+    audio_assets: Res<AudioAssets>,
+    sfx: Res<AudioChannel<SfxChannel>>,
+    volumes: Res<AudioVolumes>,
+    config_handles: Res<ConfigAssets>,
+    config: Res<Assets<GameConfig>>,
+) -> Result<()> {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("play_dialog_voices").entered();
+    let page_key = current_dialog.as_ref().map(|current_dialog| {
+        (
+            current_dialog.id.clone(),
+            current_dialog.current_page.clone(),
+        )
+    });
+    if *last_page == page_key {
+        return Ok(());
+    }
+    *last_page = page_key;
+    // The line was skipped or the dialog closed
+    if let Some(instance) = playing_voice
+        .take()
+        .and_then(|handle| audio_instances.get_mut(&handle))
+    {
+        instance.stop(AudioTween::default());
+    }
+    let current_dialog = match current_dialog {
+        Some(current_dialog) => current_dialog,
+        None => return Ok(()),
+    };
+
+    let voice = match current_dialog.fetch_current_page()?.voice {
+        Some(voice) => voice,
+        None => return Ok(()),
+    };
+    let config = config
+        .get(&config_handles.game)
+        .context("Failed to get game config from handle")?;
+    let volume = config.audio.voice_volume;
+    let source = audio_assets
+        .voices
+        .get(&voice)
+        .cloned()
+        .unwrap_or_else(|| asset_server.load(voice.as_str()));
+    let instance = match emitter_query.get_mut(current_dialog.source) {
+        Ok(mut emitter) => emitter.play(&sfx, source, volume),
+        Err(_) => sfx.play(source).with_volume(volume * volumes.sfx).handle(),
+    };
+    *playing_voice = Some(instance);
+    Ok(())
+}
+
+/// Volume multiplier and panning of a sound played at `position` as heard by the `listener`.
+/// A panning of 0 is fully left, 1 is fully right.
+pub fn get_spatial_volume_and_panning(
+    listener: &GlobalTransform,
+    position: Vec3,
+    max_distance: f32,
+) -> (f64, f64) {
+    let to_sound = position - listener.translation();
+    let distance = to_sound.length();
+    let volume = (1. - distance / max_distance).clamp(0., 1.);
+    let sideways = to_sound.normalize_or_zero().dot(listener.right());
+    let panning = 0.5 + 0.5 * sideways;
+    (volume as f64, panning as f64)
+}
+
+fn play_footsteps(
+    mut footstep_events: EventReader<Footstep>,
+    camera_query: Query<&GlobalTransform, With<IngameCamera>>,
+    sfx: Res<AudioChannel<SfxChannel>>,
+    volumes: Res<AudioVolumes>,
+    audio_assets: Res<AudioAssets>,
+    config_handles: Res<ConfigAssets>,
+    config: Res<Assets<GameConfig>>,
+) -> Result<()> {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("play_footsteps").entered();
+    let listener = match camera_query.iter().next() {
+        Some(listener) => listener,
+        None => {
+            footstep_events.clear();
+            return Ok(());
+        }
+    };
+    let config = config
+        .get(&config_handles.game)
+        .context("Failed to get game config from handle")?;
+    let audio_config = &config.audio;
+    let mut rng = rand::thread_rng();
+    for footstep in footstep_events.iter() {
+        let is_landing = footstep.landing_speed.is_some();
+        let (clip, is_landing_clip) =
+            match choose_footstep_clip(&audio_assets.footsteps, footstep.surface, is_landing) {
+                Some(clip) => clip,
+                None => continue,
+            };
+        let (spatial_volume, panning) = get_spatial_volume_and_panning(
+            listener,
+            footstep.position,
+            audio_config.max_audible_distance,
+        );
+        if spatial_volume <= 0. {
+            continue;
+        }
+
+        let volume_variation = audio_config.footstep_volume_variation;
+        let pitch_variation = audio_config.footstep_pitch_variation;
+        let mut volume = volumes.sfx
+            * audio_config.footstep_volume
+            * spatial_volume
+            * rng.gen_range(1. - volume_variation..=1. + volume_variation);
+        let mut playback_rate = rng.gen_range(1. - pitch_variation..=1. + pitch_variation);
+        if let Some(landing_speed) = footstep.landing_speed {
+            let impact = (landing_speed / audio_config.heavy_landing_speed).min(1.) as f64;
+            volume *= 1. + impact;
+            if !is_landing_clip {
+                // Make a regular step sound heavier
+                playback_rate *= 1. - 0.25 * impact;
+            }
+        }
+        sfx.play(clip)
+            .with_volume(volume)
+            .with_playback_rate(playback_rate)
+            .with_panning(panning);
+    }
+    Ok(())
+}
+
+fn play_landing_thud(
+    mut player_landed_events: EventReader<PlayerLanded>,
+    sfx: Res<AudioChannel<SfxChannel>>,
+    volumes: Res<AudioVolumes>,
+    audio_assets: Res<AudioAssets>,
+    config_handles: Res<ConfigAssets>,
+    config: Res<Assets<GameConfig>>,
+) -> Result<()> {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("play_landing_thud").entered();
+    let impact_speed = match player_landed_events
+        .iter()
+        .map(|event| event.impact_speed)
+        .reduce(f32::max)
+    {
+        Some(impact_speed) => impact_speed,
+        None => return Ok(()),
+    };
+    let config = config
+        .get(&config_handles.game)
+        .context("Failed to get game config from handle")?;
+    let audio_config = &config.audio;
+    let clip = match choose_clip(&audio_assets.footsteps, "thud_", |_file_name| true) {
+        Some(clip) => clip,
+        None => return Ok(()),
+    };
+    let impact = (impact_speed / audio_config.heavy_landing_speed).min(1.) as f64;
+    let volume = volumes.sfx * audio_config.landing_thud_volume * impact;
+    if volume > 0. {
+        sfx.play(clip).with_volume(volume);
+    }
+    Ok(())
+}
+
+/// Picks a random clip for the surface, falling back to regular steps if there are no landing clips
+/// and to the default clips if there are no clips for the surface.
+/// Also returns whether the clip is a dedicated landing sound.
+fn choose_footstep_clip(
+    clips: &HashMap<String, Handle<AudioSource>>,
+    surface: SurfaceType,
+    is_landing: bool,
+) -> Option<(Handle<AudioSource>, bool)> {
+    let mut candidates = Vec::new();
+    for surface in [surface, SurfaceType::Unknown] {
+        if is_landing {
+            candidates.push((format!("{}_landing_", surface.name()), true));
+        }
+        candidates.push((format!("{}_", surface.name()), false));
+    }
+    candidates
+        .into_iter()
+        .find_map(|(prefix, is_landing_clip)| {
+            choose_clip(clips, &prefix, |file_name| {
+                is_landing_clip || !file_name.contains("_landing_")
+            })
+            .map(|clip| (clip, is_landing_clip))
+        })
+}
+
+/// Picks a random clip whose file name starts with `prefix` and passes the `filter`
+fn choose_clip(
+    clips: &HashMap<String, Handle<AudioSource>>,
+    prefix: &str,
+    filter: impl Fn(&str) -> bool,
+) -> Option<Handle<AudioSource>> {
+    let pool: Vec<_> = clips
+        .iter()
+        .filter(|(path, _handle)| {
+            let file_name = Path::new(path)
+                .file_name()
+                .and_then(|name| name.to_str())
+                .unwrap_or_default();
+            file_name.starts_with(prefix) && filter(file_name)
+        })
+        .map(|(_path, handle)| handle)
+        .collect();
+    pool.choose(&mut rand::thread_rng())
+        .map(|&handle| handle.clone())
+}
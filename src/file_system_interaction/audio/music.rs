@@ -0,0 +1,280 @@
+use crate::file_system_interaction::asset_loading::{AudioAssets, ConfigAssets};
+use crate::file_system_interaction::audio::{AudioVolumes, MusicChannel};
+use crate::file_system_interaction::config::GameConfig;
+use crate::player_control::player_embodiment::Player;
+use crate::util::log_error::log_errors;
+use crate::util::trait_extension::MeshExt;
+use crate::GameState;
+use anyhow::{Context, Result};
+use bevy::prelude::*;
+use bevy_kira_audio::prelude::{AudioSource, *};
+use bevy_rapier3d::prelude::*;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::LazyLock;
+
+/// Plays music depending on the area the player is in.
+/// Music zones are loaded from any entity whose name contains `"[music:<track>]"`, where `<track>` is the file name
+/// of a track in `audio/music` without its extension. They become sensors shaped like the bounding box of their mesh.
+/// Outside of all zones, the configured default track is played. Tracks are crossfaded using the [`MusicChannel`] and the [`SecondaryMusicChannel`].
+pub struct MusicPlugin;
+
+impl Plugin for MusicPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<MusicZone>()
+            .add_audio_channel::<SecondaryMusicChannel>()
+            .init_resource::<CurrentMusicZone>()
+            .init_resource::<MusicState>()
+            // Keeps following the volumes while the pause menu turns them down
+            .add_system_set(
+                SystemSet::on_in_stack_update(GameState::Playing)
+                    .with_system(read_music_zones.pipe(log_errors))
+                    .with_system(update_current_music_zone)
+                    .with_system(play_music.pipe(log_errors).after(update_current_music_zone)),
+            )
+            .add_system_set(SystemSet::on_exit(GameState::Playing).with_system(stop_music));
+    }
+}
+
+/// Second channel for music, so that one track can fade out while the next one fades in on the [`MusicChannel`] or vice versa.
+#[derive(Debug, Clone, Copy, Resource, Default)]
+pub struct SecondaryMusicChannel;
+
+#[derive(Debug, Clone, PartialEq, Eq, Component, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Component, Serialize, Deserialize)]
+pub struct MusicZone {
+    /// Identifier of the track, i.e. its file name in `audio/music` without the extension
+    pub track: String,
+}
+
+/// The music zone the player is currently in
+#[derive(Debug, Clone, PartialEq, Eq, Resource, Default)]
+pub struct CurrentMusicZone {
+    pub entity: Option<Entity>,
+    pub track: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Resource, Default)]
+struct MusicState {
+    /// Index into `slots` of the slot that is fading in or playing. The other one is fading out or silent.
+    active: usize,
+    /// One slot per music channel. The first one plays on the [`MusicChannel`], the second on the [`SecondaryMusicChannel`].
+    slots: [MusicSlot; 2],
+    /// Last requested track that does not exist, so that we only warn about it once
+    missing_track: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+struct MusicSlot {
+    track: Option<String>,
+    /// Current volume of the slot before applying the music volume
+    volume: f64,
+}
+
+static MUSIC_ZONE_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\[music:\s*([^\]]+?)\s*\]").expect("Failed to compile music zone regex")
+});
+
+fn read_music_zones(
+    mut commands: Commands,
+    added_name: Query<(Entity, &Name), Added<Name>>,
+    children: Query<&Children>,
+    meshes: Res<Assets<Mesh>>,
+    mesh_handles: Query<&Handle<Mesh>>,
+) -> Result<()> {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("read_music_zones").entered();
+    for (entity, name) in &added_name {
+        let track = match MUSIC_ZONE_REGEX.captures(&name.to_lowercase()) {
+            Some(captures) => captures[1].to_string(),
+            None => continue,
+        };
+        for (zone_entity, zone_mesh) in
+            Mesh::search_in_children(entity, &children, &meshes, &mesh_handles)
+        {
+            let aabb = zone_mesh
+                .compute_aabb()
+                .context("Failed to compute bounding box of music zone mesh")?;
+            let center = Vec3::from(aabb.center);
+            let half_extents = Vec3::from(aabb.half_extents);
+            commands.entity(zone_entity).insert((
+                MusicZone {
+                    track: track.clone(),
+                },
+                Collider::compound(vec![(
+                    center,
+                    Quat::IDENTITY,
+                    Collider::cuboid(half_extents.x, half_extents.y, half_extents.z),
+                )]),
+                Sensor,
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn update_current_music_zone(
+    player_query: Query<Entity, With<Player>>,
+    zone_query: Query<&MusicZone>,
+    rapier_context: Res<RapierContext>,
+    mut current_zone: ResMut<CurrentMusicZone>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("update_current_music_zone").entered();
+    let player = match player_query.iter().next() {
+        Some(player) => player,
+        None => return,
+    };
+    let zones: Vec<_> = rapier_context
+        .intersections_with(player)
+        .filter(|(_, _, intersecting)| *intersecting)
+        .map(|(a, b, _)| if a == player { b } else { a })
+        .filter(|other| zone_query.contains(*other))
+        .collect();
+    // When zones overlap, stay in the one we entered first until we leave it
+    let still_in_current_zone = current_zone
+        .entity
+        .map(|entity| zones.contains(&entity))
+        .unwrap_or_default();
+    if still_in_current_zone {
+        return;
+    }
+    let new_zone = CurrentMusicZone {
+        entity: zones.first().copied(),
+        track: zones
+            .first()
+            .and_then(|entity| zone_query.get(*entity).ok())
+            .map(|zone| zone.track.clone()),
+    };
+    if *current_zone != new_zone {
+        *current_zone = new_zone;
+    }
+}
+
+fn play_music(
+    time: Res<Time>,
+    current_zone: Res<CurrentMusicZone>,
+    mut music_state: ResMut<MusicState>,
+    volumes: Res<AudioVolumes>,
+    primary_channel: Res<AudioChannel<MusicChannel>>,
+    secondary_channel: Res<AudioChannel<SecondaryMusicChannel>>,
+    audio_assets: Res<AudioAssets>,
+    config_handles: Res<ConfigAssets>,
+    config: Res<Assets<GameConfig>>,
+) -> Result<()> {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("play_music").entered();
+    let config = config
+        .get(&config_handles.game)
+        .context("Failed to get game config from handle")?;
+    let desired_track = current_zone
+        .track
+        .clone()
+        .unwrap_or_else(|| config.audio.default_music_track.clone());
+    let state = music_state.bypass_change_detection();
+
+    let active = state.active;
+    let inactive = 1 - active;
+    if state.slots[active].track.as_ref() != Some(&desired_track) {
+        if state.slots[inactive].track.as_ref() == Some(&desired_track) {
+            // We came back before the previous track faded out completely, so fade it back in from where it is
+            state.active = inactive;
+        } else if state.missing_track.as_ref() != Some(&desired_track) {
+            state.active = inactive;
+            match get_track(&audio_assets, &desired_track) {
+                Some(source) => {
+                    // Replaces whatever was still fading out in the other slot
+                    let slot = &mut state.slots[inactive];
+                    slot.track = Some(desired_track);
+                    slot.volume = 0.;
+                    match inactive {
+                        0 => play_track(&primary_channel, source),
+                        _ => play_track(&secondary_channel, source),
+                    }
+                }
+                None => {
+                    warn!("Music track \"{desired_track}\" not found in audio/music");
+                    // Fade the current track out to silence
+                    stop_slot(state, inactive, &primary_channel, &secondary_channel);
+                    state.missing_track = Some(desired_track);
+                }
+            }
+        }
+    }
+
+    let fade_speed = if config.audio.crossfade_seconds > 0. {
+        time.delta_seconds_f64() / config.audio.crossfade_seconds
+    } else {
+        f64::INFINITY
+    };
+    for index in 0..state.slots.len() {
+        let is_active = index == state.active;
+        let slot = &mut state.slots[index];
+        let target = if is_active && slot.track.is_some() {
+            1.
+        } else {
+            0.
+        };
+        let previous_volume = slot.volume;
+        slot.volume = if slot.volume < target {
+            (slot.volume + fade_speed).min(target)
+        } else {
+            (slot.volume - fade_speed).max(target)
+        };
+        if !is_active && slot.volume <= 0. && slot.track.is_some() {
+            stop_slot(state, index, &primary_channel, &secondary_channel);
+            continue;
+        }
+        if slot.volume != previous_volume || volumes.is_changed() {
+            let volume = slot.volume * volumes.music;
+            match index {
+                0 => primary_channel.set_volume(volume),
+                _ => secondary_channel.set_volume(volume),
+            };
+        }
+    }
+    Ok(())
+}
+
+/// Stops the music when quitting to the main menu, so that the next game starts with the track of its own area
+fn stop_music(
+    mut current_zone: ResMut<CurrentMusicZone>,
+    mut music_state: ResMut<MusicState>,
+    primary_channel: Res<AudioChannel<MusicChannel>>,
+    secondary_channel: Res<AudioChannel<SecondaryMusicChannel>>,
+) {
+    primary_channel.stop();
+    secondary_channel.stop();
+    *current_zone = default();
+    *music_state = default();
+}
+
+fn get_track(audio_assets: &AudioAssets, track: &str) -> Option<Handle<AudioSource>> {
+    audio_assets.music.iter().find_map(|(path, handle)| {
+        let file_stem = Path::new(path).file_stem()?.to_str()?;
+        (file_stem.to_lowercase() == track).then(|| handle.clone())
+    })
+}
+
+fn play_track<T: Resource>(channel: &AudioChannel<T>, source: Handle<AudioSource>) {
+    channel.stop();
+    // Start silent, the volume is faded in by hand
+    channel.set_volume(0.);
+    channel.play(source).looped();
+}
+
+fn stop_slot(
+    state: &mut MusicState,
+    index: usize,
+    primary_channel: &AudioChannel<MusicChannel>,
+    secondary_channel: &AudioChannel<SecondaryMusicChannel>,
+) {
+    let slot = &mut state.slots[index];
+    slot.track = None;
+    slot.volume = 0.;
+    match index {
+        0 => primary_channel.stop(),
+        _ => secondary_channel.stop(),
+    };
+}
@@ -0,0 +1,174 @@
+use crate::file_system_interaction::asset_loading::ConfigAssets;
+use crate::file_system_interaction::config::GameConfig;
+use crate::file_system_interaction::game_state_serialization::{
+    write_rotated_slot, SaveSlot, SaveSnapshot,
+};
+use crate::file_system_interaction::localization::Localization;
+use crate::file_system_interaction::thumbnail::ThumbnailRequest;
+use crate::level_instantiation::level_transition::LevelTransition;
+use crate::movement::general_movement::Grounded;
+use crate::player_control::player_embodiment::Player;
+use crate::util::log_error::log_errors;
+use crate::world_interaction::checkpoint::Respawning;
+use crate::world_interaction::dialog::CurrentDialog;
+use crate::world_interaction::health::Dying;
+use crate::GameState;
+use anyhow::{Context, Result};
+use bevy::prelude::*;
+use bevy::tasks::{IoTaskPool, Task};
+use bevy_egui::{egui, EguiContext};
+use futures_lite::future;
+
+/// Writes autosaves into a rotating set of [`SaveSlot::Autosave`] slots, keeping `save.autosave_history` of them.
+/// Autosaves are requested through [`AutosaveRequest`], e.g. when the player reaches a checkpoint, arrives in a level
+/// or ends a dialog marked with [`Dialog::autosave`](crate::world_interaction::dialog::Dialog::autosave).
+/// The game state is serialized on the main thread, while the file is written on the [`IoTaskPool`].
+/// Requests are held back while the player is in the air, in a dialog, dying, respawning or moving to another level,
+/// as well as while the previous autosave is still being written.
+pub struct AutosavePlugin;
+
+impl Plugin for AutosavePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<AutosaveRequest>()
+            .init_resource::<Autosaver>()
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(autosave_after_dialogs)
+                    .with_system(finish_autosaves.pipe(log_errors))
+                    .with_system(
+                        start_autosaves
+                            .pipe(log_errors)
+                            .after(autosave_after_dialogs)
+                            .after(finish_autosaves),
+                    )
+                    .with_system(show_saving_indicator),
+            );
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct AutosaveRequest;
+
+#[derive(Debug, Resource, Default)]
+pub struct Autosaver {
+    task: Option<Task<Result<()>>>,
+    /// Whether an autosave was requested but not started yet
+    queued: bool,
+    /// Time in seconds the saving indicator is still shown for
+    indicator_time: f32,
+}
+
+impl Autosaver {
+    pub fn is_saving(&self) -> bool {
+        self.task.is_some()
+    }
+}
+
+fn autosave_after_dialogs(
+    current_dialog: Option<Res<CurrentDialog>>,
+    mut was_autosave_dialog: Local<bool>,
+    mut autosave_requests: EventWriter<AutosaveRequest>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("autosave_after_dialogs").entered();
+    match current_dialog {
+        Some(current_dialog) => *was_autosave_dialog = current_dialog.dialog.autosave,
+        None => {
+            if *was_autosave_dialog {
+                *was_autosave_dialog = false;
+                autosave_requests.send(AutosaveRequest);
+            }
+        }
+    }
+}
+
+fn start_autosaves(
+    mut autosave_requests: EventReader<AutosaveRequest>,
+    mut autosaver: ResMut<Autosaver>,
+    snapshot: SaveSnapshot,
+    player_query: Query<&Grounded, With<Player>>,
+    current_dialog: Option<Res<CurrentDialog>>,
+    dying: Option<Res<Dying>>,
+    respawning: Option<Res<Respawning>>,
+    level_transition: Option<Res<LevelTransition>>,
+    config_handles: Res<ConfigAssets>,
+    config: Res<Assets<GameConfig>>,
+) -> Result<()> {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("start_autosaves").entered();
+    if autosave_requests.iter().count() > 0 {
+        autosaver.queued = true;
+    }
+    let is_airborne = player_query.iter().any(|grounded| !grounded.0);
+    if !autosaver.queued
+        || autosaver.is_saving()
+        || is_airborne
+        || current_dialog.is_some()
+        || dying.is_some()
+        || respawning.is_some()
+        || level_transition.is_some()
+    {
+        return Ok(());
+    }
+    let config = config
+        .get(&config_handles.game)
+        .context("Failed to get game config from handle")?;
+    let serialized = match snapshot
+        .serialize(config.save.format)
+        .context("Failed to create autosave")?
+    {
+        Some(serialized) => serialized,
+        None => return Ok(()),
+    };
+    let history = config.save.autosave_history;
+    autosaver.queued = false;
+    autosaver.indicator_time = config.save.saving_indicator_duration;
+    autosaver.task = Some(
+        IoTaskPool::get()
+            .spawn(async move { write_rotated_slot(&serialized, history, SaveSlot::Autosave) }),
+    );
+    Ok(())
+}
+
+fn finish_autosaves(
+    mut autosaver: ResMut<Autosaver>,
+    mut thumbnail_requests: EventWriter<ThumbnailRequest>,
+) -> Result<()> {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("finish_autosaves").entered();
+    let task = match autosaver.task.as_mut() {
+        Some(task) => task,
+        None => return Ok(()),
+    };
+    let result = match future::block_on(future::poll_once(task)) {
+        Some(result) => result,
+        None => return Ok(()),
+    };
+    autosaver.task = None;
+    result.context("Failed to write autosave")?;
+    info!("Successfully autosaved game");
+    thumbnail_requests.send(ThumbnailRequest {
+        slot: SaveSlot::Autosave(0),
+    });
+    Ok(())
+}
+
+fn show_saving_indicator(
+    time: Res<Time>,
+    mut autosaver: ResMut<Autosaver>,
+    localization: Res<Localization>,
+    mut egui_context: ResMut<EguiContext>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("show_saving_indicator").entered();
+    autosaver.indicator_time = (autosaver.indicator_time - time.delta_seconds()).max(0.);
+    if !autosaver.is_saving() && autosaver.indicator_time <= 0. {
+        return;
+    }
+    egui::Area::new("saving_indicator")
+        .anchor(egui::Align2::RIGHT_BOTTOM, egui::Vec2::new(-20., -20.))
+        .interactable(false)
+        .show(egui_context.ctx_mut(), |ui| {
+            ui.label(localization.get("save.saving"));
+        });
+}
@@ -1,5 +1,6 @@
 use bevy::prelude::*;
 use bevy::reflect::TypeUuid;
+use bevy::utils::HashMap;
 use serde::{Deserialize, Serialize};
 use std::f32::consts::TAU;
 
@@ -10,6 +11,25 @@ use std::f32::consts::TAU;
 #[uuid = "93a7c64b-4d6e-4420-b8c1-dfca481d9387"]
 pub struct GameConfig {
     pub camera: Camera,
+    pub player: Player,
+    pub npc: Npc,
+    pub animation: Animation,
+    pub audio: Audio,
+    pub particles: Particles,
+    pub navigation: Navigation,
+    pub activity: Activity,
+    pub language: Language,
+    pub dialog: Dialog,
+    pub interaction: Interaction,
+    pub world: World,
+    pub save: Save,
+    pub quality: Quality,
+    pub day_night: DayNight,
+    pub wind: WindBaseline,
+    pub speedrun: Speedrun,
+    pub rumble: Rumble,
+    pub accessibility: Accessibility,
+    pub streaming: Streaming,
 }
 
 #[derive(Debug, Clone, PartialEq, Reflect, FromReflect, Serialize, Deserialize)]
@@ -20,6 +40,26 @@ pub struct Camera {
     pub third_person: ThirdPerson,
     pub mouse_sensitivity_x: f32,
     pub mouse_sensitivity_y: f32,
+    /// Vertical field of view in degrees. Players can override it in the settings menu.
+    pub fov_degrees: f32,
+    /// Whether the field of view widens while the player moves fast. Players can override it in the settings menu.
+    pub dynamic_fov: bool,
+    /// Degrees the field of view widens by at `dynamic_fov_max_speed`
+    pub dynamic_fov_max_boost: f32,
+    /// Horizontal speed in m/s at which the field of view is widest
+    pub dynamic_fov_max_speed: f32,
+    pub dynamic_fov_smoothing: f32,
+    /// How far in m the camera dips down per m/s of landing speed
+    pub landing_dip_per_speed: f32,
+    pub max_landing_dip: f32,
+    pub landing_dip_duration: f32,
+    /// The camera snaps to its target instead of smoothly following it when the target moves further than this in m in one frame
+    pub teleport_snap_distance: f32,
+    /// Whether the camera advances in fixed steps with the rendered transform interpolated between them,
+    /// see [`FixedStepCamera`](crate::player_control::camera::FixedStepCamera). Off updates it once per frame.
+    pub fixed_timestep: bool,
+    /// Camera steps per second when `fixed_timestep` is on
+    pub fixed_timestep_rate: f32,
 }
 
 impl Default for Camera {
@@ -30,6 +70,17 @@ impl Default for Camera {
             third_person: ThirdPerson::default(),
             mouse_sensitivity_x: 8e-4,
             mouse_sensitivity_y: 5e-4,
+            fov_degrees: 45.0,
+            dynamic_fov: true,
+            dynamic_fov_max_boost: 15.0,
+            dynamic_fov_max_speed: 12.0,
+            dynamic_fov_smoothing: 4.0,
+            landing_dip_per_speed: 0.02,
+            max_landing_dip: 0.3,
+            landing_dip_duration: 0.25,
+            teleport_snap_distance: 10.0,
+            fixed_timestep: false,
+            fixed_timestep_rate: 60.0,
         }
     }
 }
@@ -63,6 +114,11 @@ pub struct FirstPerson {
     pub rotation_smoothing: f32,
     pub most_acute_from_above: f32,
     pub most_acute_from_below: f32,
+    /// How strongly a look target, e.g. a dialog partner, holds the view, from 0 to 1.
+    /// The player's camera controls are scaled by the rest, so 1 locks the view onto the target.
+    pub look_target_weight: f32,
+    /// Radians per second at which the view turns towards a look target at a `look_target_weight` of 1
+    pub look_target_max_angular_speed: f32,
 }
 
 impl Default for FirstPerson {
@@ -72,6 +128,8 @@ impl Default for FirstPerson {
             rotation_smoothing: 45.0,
             most_acute_from_above: TAU / 10.,
             most_acute_from_below: TAU / 7.,
+            look_target_weight: 0.8,
+            look_target_max_angular_speed: TAU / 2.,
         }
     }
 }
@@ -88,6 +146,14 @@ pub struct ThirdPerson {
     pub max_distance: f32,
     pub zoom_speed: f32,
     pub min_distance_to_objects: f32,
+    pub min_distance_to_objects_underwater: f32,
+    pub climbing_target_smoothing: f32,
+    /// How quickly the camera turns to keep a secondary target, e.g. the current speaker in a dialog, in frame
+    pub alignment_smoothing: f32,
+    /// Height in m above the player's feet that the camera orbits around, e.g. to center the chest instead of the feet
+    pub target_height_offset: f32,
+    /// Distance in m by which the player is shifted down the screen, so that more of what is above them is visible
+    pub frame_offset_y: f32,
 }
 
 impl Default for ThirdPerson {
@@ -102,6 +168,772 @@ impl Default for ThirdPerson {
             max_distance: 10.0,
             zoom_speed: 0.7,
             min_distance_to_objects: 5e-1,
+            min_distance_to_objects_underwater: 1e-1,
+            climbing_target_smoothing: 10.0,
+            alignment_smoothing: 6.0,
+            target_height_offset: 0.0,
+            frame_offset_y: 0.0,
+        }
+    }
+}
+
+/// How NPCs turn their heads towards what they look at, see [`LookAtTarget`](crate::world_interaction::look_at::LookAtTarget)
+#[derive(Debug, Clone, PartialEq, Reflect, FromReflect, Serialize, Deserialize)]
+#[reflect(Serialize, Deserialize)]
+pub struct Npc {
+    /// Idle NPCs look at the player while they are closer than this in m
+    pub glance_distance: f32,
+    /// Name of the head bone in the NPC's rig
+    pub head_bone: String,
+    /// Name of the bone that turns the upper body when the head alone cannot turn far enough
+    pub upper_body_bone: String,
+    /// Maximum angle in radians the head turns sideways relative to the body
+    pub max_head_yaw: f32,
+    /// Maximum angle in radians the head tilts up or down
+    pub max_head_pitch: f32,
+    /// Maximum angle in radians the upper body turns sideways to help the head
+    pub max_upper_body_yaw: f32,
+    /// How quickly the head turns towards a new target and back when there is none
+    pub look_at_smoothing: f32,
+}
+
+impl Default for Npc {
+    fn default() -> Self {
+        Self {
+            glance_distance: 4.0,
+            head_bone: "b_Head_05".to_string(),
+            upper_body_bone: "b_Spine02_03".to_string(),
+            max_head_yaw: 1.2,
+            max_head_pitch: 0.5,
+            max_upper_body_yaw: 0.4,
+            look_at_smoothing: 8.0,
+        }
+    }
+}
+
+/// How characters switch between their animations, see [`AnimationState`](crate::movement::animation::AnimationState)
+#[derive(Debug, Clone, PartialEq, Reflect, FromReflect, Serialize, Deserialize)]
+#[reflect(Serialize, Deserialize)]
+pub struct Animation {
+    /// Grounded characters walk above this horizontal speed in m/s
+    pub walk_speed: f32,
+    /// Grounded characters run above this horizontal speed in m/s
+    pub run_speed: f32,
+    /// A character only switches to a faster state when it is this much in m/s above the threshold and back when it is this much below it,
+    /// so that a speed hovering around a threshold does not flicker between two states
+    pub speed_threshold_margin: f32,
+    /// Horizontal speed in m/s at which the walk clip plays at its normal speed. It plays faster or slower with the actual speed.
+    pub walk_clip_speed: f32,
+    /// Horizontal speed in m/s at which the run clip plays at its normal speed. It plays faster or slower with the actual speed.
+    pub run_clip_speed: f32,
+    /// Slowest factor the walk and run clips are played at
+    pub min_playback_speed: f32,
+    /// Fastest factor the walk and run clips are played at
+    pub max_playback_speed: f32,
+    /// Time in seconds the jump start is shown before the character counts as airborne
+    pub jump_start_duration: f32,
+    /// Time in seconds the landing is shown before the character walks or idles again
+    pub land_duration: f32,
+    /// Time in seconds over which the previous pose fades into the next animation
+    pub default_blend_time: f32,
+    /// Fade times in seconds for specific transitions, keyed like `"walk->run"`. Transitions that are not listed take [`Animation::default_blend_time`].
+    pub blend_times: HashMap<String, f32>,
+}
+
+impl Default for Animation {
+    fn default() -> Self {
+        Self {
+            walk_speed: 0.3,
+            run_speed: 4.0,
+            speed_threshold_margin: 0.2,
+            walk_clip_speed: 1.5,
+            run_clip_speed: 5.0,
+            min_playback_speed: 0.5,
+            max_playback_speed: 2.0,
+            jump_start_duration: 0.15,
+            land_duration: 0.25,
+            default_blend_time: 0.2,
+            blend_times: default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Reflect, FromReflect, Serialize, Deserialize)]
+#[reflect(Serialize, Deserialize)]
+pub struct Player {
+    pub extra_jumps: usize,
+    pub air_jump_height: f32,
+    pub coyote_time: f32,
+    pub wall_slide_speed: f32,
+    pub wall_jump_speed: f32,
+    pub wall_jump_angle: f32,
+    pub wall_jump_input_lock: f32,
+    pub climb_speed: f32,
+    pub max_walkable_slope_degrees: f32,
+    pub slope_slide_acceleration: f32,
+    pub controller: CharacterControllerBackend,
+    pub autostep_max_height: f32,
+    pub autostep_min_width: f32,
+    pub snap_to_ground_distance: f32,
+    pub dash_speed: f32,
+    pub dash_duration: f32,
+    pub dash_cooldown: f32,
+    pub dash_gravity_scale: f32,
+    pub grapple_range: f32,
+    pub grapple_pull_strength: f32,
+    pub grapple_max_swing_speed: f32,
+    /// Landings slower than this in m/s give no feedback
+    pub soft_landing_threshold: f32,
+    /// Landings faster than this in m/s slow the player down
+    pub hard_landing_threshold: f32,
+    /// Factor by which the horizontal speed is multiplied on a hard landing
+    pub hard_landing_speed_factor: f32,
+    pub max_health: f32,
+    /// Time in seconds the camera orbits the player's body after they died, before the screen fades out
+    pub death_camera_duration: f32,
+    /// Speed in radians per second at which the camera orbits the body
+    pub death_camera_orbit_speed: f32,
+}
+
+/// How the player's movement is simulated
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Reflect, FromReflect, Serialize, Deserialize, Default,
+)]
+#[reflect(Serialize, Deserialize)]
+pub enum CharacterControllerBackend {
+    /// A dynamic rigid body moved by forces
+    #[default]
+    Dynamic,
+    /// Rapier's kinematic character controller, which additionally uses the autostep and snap to ground settings
+    Kinematic,
+}
+
+impl Default for Player {
+    fn default() -> Self {
+        Self {
+            extra_jumps: 0,
+            air_jump_height: 0.4,
+            coyote_time: 0.1,
+            wall_slide_speed: 1.0,
+            wall_jump_speed: 5.0,
+            wall_jump_angle: TAU / 8.,
+            wall_jump_input_lock: 0.3,
+            climb_speed: 2.0,
+            max_walkable_slope_degrees: 45.0,
+            slope_slide_acceleration: 6.0,
+            controller: default(),
+            autostep_max_height: 0.3,
+            autostep_min_width: 0.2,
+            snap_to_ground_distance: 0.2,
+            dash_speed: 15.0,
+            dash_duration: 0.2,
+            dash_cooldown: 0.8,
+            dash_gravity_scale: 0.2,
+            grapple_range: 30.0,
+            grapple_pull_strength: 25.0,
+            grapple_max_swing_speed: 20.0,
+            soft_landing_threshold: 4.0,
+            hard_landing_threshold: 12.0,
+            hard_landing_speed_factor: 0.3,
+            max_health: 100.0,
+            death_camera_duration: 2.5,
+            death_camera_orbit_speed: 0.6,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Reflect, FromReflect, Serialize, Deserialize)]
+#[reflect(Serialize, Deserialize)]
+pub struct Audio {
+    pub master_volume: f64,
+    pub music_volume: f64,
+    pub sfx_volume: f64,
+    /// Whether to silence all sounds while the game window is not focused
+    pub mute_when_unfocused: bool,
+    /// Whether to pause all sounds while the pause menu is open. Otherwise, they are turned down to `menu_volume`.
+    pub pause_in_menu: bool,
+    /// Volume multiplier for all sounds while the pause menu is open and `pause_in_menu` is off
+    pub menu_volume: f64,
+    pub footstep_volume: f64,
+    pub footstep_volume_variation: f64,
+    pub footstep_pitch_variation: f64,
+    /// Landing speed in m/s at which the landing sound reaches its full volume
+    pub heavy_landing_speed: f32,
+    /// Distance in m at which spatial sounds become inaudible
+    pub max_audible_distance: f32,
+    /// Volume of the thud played when the player lands. The actual volume scales with the landing speed.
+    pub landing_thud_volume: f64,
+    pub voice_volume: f64,
+    /// Time in seconds it takes to fade from one music track to the next
+    pub crossfade_seconds: f64,
+    /// Track that plays outside of all music zones
+    pub default_music_track: String,
+}
+
+impl Default for Audio {
+    fn default() -> Self {
+        Self {
+            master_volume: 1.0,
+            music_volume: 0.8,
+            sfx_volume: 1.0,
+            mute_when_unfocused: true,
+            pause_in_menu: false,
+            menu_volume: 0.3,
+            footstep_volume: 0.6,
+            footstep_volume_variation: 0.15,
+            footstep_pitch_variation: 0.1,
+            heavy_landing_speed: 10.0,
+            max_audible_distance: 30.0,
+            landing_thud_volume: 1.0,
+            voice_volume: 1.0,
+            crossfade_seconds: 2.0,
+            default_music_track: "ambient".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Reflect, FromReflect, Serialize, Deserialize)]
+#[reflect(Serialize, Deserialize)]
+pub struct Particles {
+    /// Whether a dust trail follows the player while sprinting on the ground
+    pub sprint_dust: bool,
+    /// Horizontal speed in m/s above which the sprinting dust trail is emitted
+    pub sprint_dust_min_speed: f32,
+    /// Number of dust particles emitted per second per m/s of horizontal speed while sprinting
+    pub sprint_dust_per_speed: f32,
+    /// Whether dust is kicked up while sliding down slopes too steep to walk on
+    pub slide_dust: bool,
+    /// Number of dust particles in every burst while sliding
+    pub slide_dust_count: f32,
+    /// Seconds between two bursts while sliding
+    pub slide_dust_interval: f32,
+    /// Whether dust is kicked up when landing after a fall
+    pub landing_dust: bool,
+    /// Number of dust particles spawned per m/s of landing speed
+    pub landing_dust_per_speed: f32,
+    pub max_landing_dust: f32,
+    /// Factor by which the landing dust is multiplied on a hard landing, see [`Player::hard_landing_threshold`]
+    pub hard_landing_dust_factor: f32,
+    /// Tint of the dust by the surface it is kicked up from
+    pub dust_colors: DustColors,
+}
+
+impl Default for Particles {
+    fn default() -> Self {
+        Self {
+            sprint_dust: true,
+            sprint_dust_min_speed: 7.0,
+            sprint_dust_per_speed: 1.5,
+            slide_dust: true,
+            slide_dust_count: 8.0,
+            slide_dust_interval: 0.25,
+            landing_dust: true,
+            landing_dust_per_speed: 3.0,
+            max_landing_dust: 40.0,
+            hard_landing_dust_factor: 2.0,
+            dust_colors: default(),
+        }
+    }
+}
+
+/// RGB tints multiplied onto the colors of the dust effects
+#[derive(Debug, Clone, PartialEq, Reflect, FromReflect, Serialize, Deserialize)]
+#[reflect(Serialize, Deserialize)]
+pub struct DustColors {
+    /// Used for surfaces without a [`SurfaceType`](crate::movement::footsteps::SurfaceType)
+    pub default: [f32; 3],
+    pub dirt: [f32; 3],
+    pub grass: [f32; 3],
+    pub stone: [f32; 3],
+    pub wood: [f32; 3],
+}
+
+impl Default for DustColors {
+    fn default() -> Self {
+        Self {
+            default: [0.8, 0.75, 0.7],
+            dirt: [0.6, 0.45, 0.3],
+            grass: [0.55, 0.5, 0.35],
+            stone: [0.6, 0.6, 0.6],
+            wood: [0.65, 0.55, 0.45],
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Reflect, FromReflect, Serialize, Deserialize)]
+#[reflect(Serialize, Deserialize)]
+pub struct Navigation {
+    /// Distance in m the target has to move before the path to it is planned again
+    pub replan_distance: f32,
+    /// Time in seconds after which a path is planned again even if the target did not move
+    pub replan_interval: f32,
+    /// Distance in m at which a waypoint counts as reached
+    pub waypoint_reach_distance: f32,
+    /// Time in seconds without getting closer to the next waypoint after which the path counts as blocked
+    pub stuck_time: f32,
+    /// A patrolling [`crate::movement::navigation::Follower`] follows the player while they are closer than this in m
+    pub follow_radius: f32,
+    /// Distance in m at which a patrolling NPC counts as having arrived at a waypoint
+    pub patrol_stop_distance: f32,
+    /// Characters closer to each other than this in m push each other away
+    pub separation_radius: f32,
+    /// How strongly the push away from other characters is weighted against following the path
+    pub separation_strength: f32,
+    /// How fast in 1/s the push away from other characters follows changes of their positions
+    pub avoidance_smoothing: f32,
+    /// Radius in m of the ring around the player on which followers stop
+    pub follow_stop_distance: f32,
+}
+
+impl Default for Navigation {
+    fn default() -> Self {
+        Self {
+            replan_distance: 1.0,
+            replan_interval: 1.0,
+            waypoint_reach_distance: 0.4,
+            stuck_time: 1.0,
+            follow_radius: 8.0,
+            patrol_stop_distance: 0.5,
+            separation_radius: 1.2,
+            separation_strength: 1.0,
+            avoidance_smoothing: 8.0,
+            follow_stop_distance: 3.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Reflect, FromReflect, Serialize, Deserialize)]
+#[reflect(Serialize, Deserialize)]
+pub struct Activity {
+    /// Time in seconds between two evaluations of the characters' activity levels
+    pub update_interval: f32,
+    /// Characters farther away from the camera than this in m have a reduced activity
+    pub reduced_distance: f32,
+    /// Characters farther away from the camera than this in m are dormant
+    pub dormant_distance: f32,
+    /// Distance in m a character has to be past a threshold before its activity is lowered
+    pub hysteresis: f32,
+    /// Time in seconds between two path plannings of characters with a reduced activity
+    pub reduced_navigation_interval: f32,
+}
+
+impl Default for Activity {
+    fn default() -> Self {
+        Self {
+            update_interval: 0.25,
+            reduced_distance: 30.0,
+            dormant_distance: 60.0,
+            hysteresis: 3.0,
+            reduced_navigation_interval: 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Reflect, FromReflect, Serialize, Deserialize)]
+#[reflect(Serialize, Deserialize)]
+pub struct Language {
+    /// Language used at startup, i.e. the name of a folder in `assets/locales`
+    pub current: String,
+    /// Fonts used instead of the default font for languages whose script it does not cover, by language.
+    /// Paths are relative to `assets` and have to end in `.egui.ttf` or `.egui.otf`.
+    pub fonts: HashMap<String, String>,
+}
+
+impl Default for Language {
+    fn default() -> Self {
+        Self {
+            current: "en".to_string(),
+            fonts: default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Reflect, FromReflect, Serialize, Deserialize)]
+#[reflect(Serialize, Deserialize)]
+pub struct Dialog {
+    /// Whether the player can still pan the camera while a dialog is open
+    pub allow_camera_pan: bool,
+    /// Height in m above a speaker's origin that the camera frames during a dialog
+    pub speaker_head_offset: f32,
+    /// How fast text is revealed at a talking speed of 1
+    pub letters_per_second: f32,
+    /// Whether pages without choices can be fast-forwarded
+    pub allow_skipping: bool,
+    /// Time in seconds the confirm action has to be held to fast-forward
+    pub fast_forward_hold_time: f32,
+    /// Time in seconds a page is shown while fast-forwarding
+    pub fast_forward_page_time: f32,
+}
+
+impl Default for Dialog {
+    fn default() -> Self {
+        Self {
+            allow_camera_pan: false,
+            speaker_head_offset: 0.4,
+            letters_per_second: 60.0,
+            allow_skipping: true,
+            fast_forward_hold_time: 0.5,
+            fast_forward_page_time: 0.1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Reflect, FromReflect, Serialize, Deserialize)]
+#[reflect(Serialize, Deserialize)]
+pub struct Interaction {
+    /// How far in m the player can reach when interactions are detected with a ray
+    pub max_distance: f32,
+    /// Whether the third person camera detects interactions with a ray along the player's facing
+    /// instead of with the sensor around the player. The first person camera always uses a ray from the crosshair.
+    pub third_person_raycast: bool,
+    /// How much closer in m another interactable has to be before it replaces the selected one
+    pub selection_margin: f32,
+    /// Height in m above an interactable's origin at which its prompt is shown,
+    /// unless it has its own [`PromptOffset`](crate::world_interaction::interactions_ui::PromptOffset)
+    pub prompt_offset: f32,
+    /// Distance in m before [`Interaction::max_distance`] at which the prompt starts fading out
+    pub prompt_fade_distance: f32,
+    /// Opacity the prompt fades to at [`Interaction::max_distance`] and beyond, so that it stays readable while it can be used
+    pub prompt_min_opacity: f32,
+    /// Whether a prompt whose interactable is off-screen or behind the camera sticks to the edge of the screen with an arrow
+    /// pointing towards it instead of being hidden
+    pub prompt_clamp_to_screen: bool,
+    /// Distance in points from the edge of the screen at which a clamped prompt is shown
+    pub prompt_screen_margin: f32,
+    /// Color in sRGB of the outline around the interactable whose prompt is shown
+    pub outline_color: [f32; 3],
+    /// Width in pixels of the outline
+    pub outline_thickness: f32,
+    /// Fraction by which the outline's brightness pulses, so that it stands out against busy backgrounds. 0 turns pulsing off.
+    pub outline_pulse: f32,
+    /// Pulses per second of the outline
+    pub outline_pulse_frequency: f32,
+}
+
+impl Default for Interaction {
+    fn default() -> Self {
+        Self {
+            max_distance: 3.0,
+            third_person_raycast: false,
+            selection_margin: 0.5,
+            prompt_offset: 0.8,
+            prompt_fade_distance: 1.0,
+            prompt_min_opacity: 0.25,
+            prompt_clamp_to_screen: false,
+            prompt_screen_margin: 40.0,
+            outline_color: [1.0, 0.85, 0.4],
+            outline_thickness: 3.0,
+            outline_pulse: 0.3,
+            outline_pulse_frequency: 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Reflect, FromReflect, Serialize, Deserialize)]
+#[reflect(Serialize, Deserialize)]
+pub struct World {
+    /// Time in seconds the screen takes to fade to black or back, e.g. when moving to another level
+    pub fade_duration: f32,
+    /// The player respawns at the last checkpoint when falling below this height in m
+    pub kill_y: f32,
+}
+
+impl Default for World {
+    fn default() -> Self {
+        Self {
+            fade_duration: 0.4,
+            kill_y: -50.,
+        }
+    }
+}
+
+/// How a level is spawned over several frames, see [`SpawnQueue`](crate::level_instantiation::spawning::spawn::SpawnQueue)
+#[derive(Debug, Clone, PartialEq, Reflect, FromReflect, Serialize, Deserialize)]
+#[reflect(Serialize, Deserialize)]
+pub struct Streaming {
+    /// Time in ms spent on spawning objects per frame. At least one object is spawned per frame regardless.
+    pub frame_budget_ms: f32,
+    /// Objects within this distance in m of the player's spawn point have to be spawned before the loading screen goes away
+    pub playable_radius: f32,
+    /// Colliders of meshes with more vertices than this are built on the async compute task pool
+    pub async_collider_vertex_count: u32,
+    /// Distance in m below a newly spawned player that is searched for ground before letting it fall
+    pub max_ground_distance: f32,
+    /// Time in seconds after which a newly spawned player is let go even when no ground was found
+    pub max_ground_wait: f32,
+}
+
+impl Default for Streaming {
+    fn default() -> Self {
+        Self {
+            frame_budget_ms: 4.,
+            playable_radius: 40.,
+            async_collider_vertex_count: 2000,
+            max_ground_distance: 5.,
+            max_ground_wait: 5.,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Reflect, FromReflect, Serialize, Deserialize)]
+#[reflect(Serialize, Deserialize)]
+pub struct Save {
+    /// How many autosaves are kept before the oldest one is overwritten
+    pub autosave_history: u32,
+    /// Minimum time in seconds the saving indicator is shown for, so that it doesn't just flicker
+    pub saving_indicator_duration: f32,
+    /// Format new saves are written in. Saves in either format can always be loaded.
+    pub format: SaveFormat,
+}
+
+impl Default for Save {
+    fn default() -> Self {
+        Self {
+            autosave_history: 3,
+            saving_indicator_duration: 1.0,
+            format: SaveFormat::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Reflect, FromReflect, Serialize, Deserialize)]
+#[reflect(Serialize, Deserialize)]
+pub enum SaveFormat {
+    /// Human readable and easy to edit
+    Ron,
+    /// Compressed MessagePack, smaller and faster to load
+    Binary,
+}
+
+impl Default for SaveFormat {
+    fn default() -> Self {
+        Self::Ron
+    }
+}
+
+/// The default graphics quality. Players can override it in the settings menu, see [`UserSettings`](crate::file_system_interaction::user_settings::UserSettings).
+#[derive(Debug, Clone, PartialEq, Reflect, FromReflect, Serialize, Deserialize)]
+#[reflect(Serialize, Deserialize)]
+pub struct Quality {
+    pub shadows: bool,
+    /// Width and height in texels of the shadow maps. Must be a power of two.
+    pub shadow_map_size: u32,
+    /// Samples per pixel for anti-aliasing. Only 1, i.e. off, and 4 are supported on every platform.
+    pub msaa_samples: u32,
+    /// Brightness of the ambient light, which the [`DayNight::keyframes`] scale depending on the time of day
+    pub ambient_brightness: f32,
+    /// Distance in m up to which the camera renders
+    pub draw_distance: f32,
+}
+
+impl Default for Quality {
+    fn default() -> Self {
+        Self {
+            shadows: true,
+            shadow_map_size: 2048,
+            msaa_samples: 4,
+            ambient_brightness: 0.3,
+            draw_distance: 1000.,
+        }
+    }
+}
+
+/// How the [`TimeOfDay`](crate::world_interaction::time_of_day::TimeOfDay) passes and how it lights the level.
+/// The sun rises at 6:00 and sets at 18:00. While it is below the horizon, the moon on the opposite side of the sky lights the level instead.
+#[derive(Debug, Clone, PartialEq, Reflect, FromReflect, Serialize, Deserialize)]
+#[reflect(Serialize, Deserialize)]
+pub struct DayNight {
+    /// Hour at which a new game starts
+    pub start_hour: f32,
+    /// In-game hours that pass per second of play
+    pub hours_per_second: f32,
+    /// Whether the time stands still at `start_hour`
+    pub frozen: bool,
+    /// Angle in degrees of the sun above the horizon at noon. Must be below 90.
+    pub max_sun_elevation: f32,
+    /// Angle in degrees by which the path of the sun is turned around the vertical axis
+    pub sun_azimuth: f32,
+    /// Elevation in degrees below which the sun and moon fade out, so that switching between them is not noticeable
+    pub horizon_fade: f32,
+    /// Lighting at certain hours, which is interpolated in between. The last keyframe blends into the first one over midnight.
+    pub keyframes: Vec<LightingKeyframe>,
+}
+
+impl Default for DayNight {
+    fn default() -> Self {
+        Self {
+            start_hour: 10.,
+            hours_per_second: 0.01,
+            frozen: false,
+            max_sun_elevation: 60.,
+            sun_azimuth: 0.,
+            horizon_fade: 10.,
+            keyframes: vec![
+                LightingKeyframe {
+                    hour: 0.,
+                    light_color: [0.6, 0.7, 1.0],
+                    light_illuminance: 5_000.,
+                    ambient_color: [0.4, 0.5, 0.8],
+                    ambient_brightness: 0.3,
+                    sky_zenith_color: [0.05, 0.06, 0.15],
+                    sky_horizon_color: [0.1, 0.12, 0.25],
+                },
+                LightingKeyframe {
+                    hour: 6.,
+                    light_color: [1.0, 0.6, 0.4],
+                    light_illuminance: 30_000.,
+                    ambient_color: [1.0, 0.8, 0.7],
+                    ambient_brightness: 0.6,
+                    sky_zenith_color: [0.6, 0.6, 0.8],
+                    sky_horizon_color: [1.0, 0.6, 0.4],
+                },
+                LightingKeyframe {
+                    hour: 12.,
+                    light_color: [1.0, 1.0, 1.0],
+                    light_illuminance: 100_000.,
+                    ambient_color: [1.0, 1.0, 1.0],
+                    ambient_brightness: 1.0,
+                    sky_zenith_color: [1.0, 1.0, 1.0],
+                    sky_horizon_color: [1.0, 1.0, 1.0],
+                },
+                LightingKeyframe {
+                    hour: 18.,
+                    light_color: [1.0, 0.5, 0.3],
+                    light_illuminance: 30_000.,
+                    ambient_color: [1.0, 0.7, 0.6],
+                    ambient_brightness: 0.6,
+                    sky_zenith_color: [0.5, 0.5, 0.7],
+                    sky_horizon_color: [1.0, 0.5, 0.3],
+                },
+            ],
+        }
+    }
+}
+
+/// Colors are in sRGB
+#[derive(Debug, Clone, PartialEq, Reflect, FromReflect, Serialize, Deserialize, Default)]
+#[reflect(Serialize, Deserialize)]
+pub struct LightingKeyframe {
+    /// Hour in [0, 24) at which the lighting looks exactly like this
+    pub hour: f32,
+    /// Color of the sun or moon light
+    pub light_color: [f32; 3],
+    /// Illuminance in lux of the sun or moon light when high in the sky
+    pub light_illuminance: f32,
+    pub ambient_color: [f32; 3],
+    /// Multiplied with the [`Quality::ambient_brightness`] chosen by the player
+    pub ambient_brightness: f32,
+    /// Multiplied with the sky texture straight above
+    pub sky_zenith_color: [f32; 3],
+    /// Multiplied with the sky texture at the horizon
+    pub sky_horizon_color: [f32; 3],
+}
+
+/// The wind that blows when there are no gusts. It varies randomly around these values.
+#[derive(Debug, Clone, PartialEq, Reflect, FromReflect, Serialize, Deserialize)]
+#[reflect(Serialize, Deserialize)]
+pub struct WindBaseline {
+    /// Angle in degrees around the vertical axis of the direction the wind blows towards, 0 being along the x axis
+    pub direction: f32,
+    /// How far in m the tip of a plant 1 m tall is pushed
+    pub strength: f32,
+    /// Sways per second
+    pub frequency: f32,
+    /// Fraction of the strength by which it varies
+    pub strength_variation: f32,
+    /// Angle in degrees by which the direction varies
+    pub direction_variation: f32,
+    /// How many times per second the variation changes course
+    pub variation_speed: f32,
+}
+
+impl Default for WindBaseline {
+    fn default() -> Self {
+        Self {
+            direction: 30.,
+            strength: 0.1,
+            frequency: 0.5,
+            strength_variation: 0.5,
+            direction_variation: 20.,
+            variation_speed: 0.2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Reflect, FromReflect, Serialize, Deserialize)]
+#[reflect(Serialize, Deserialize)]
+pub struct Speedrun {
+    /// Whether the speedrun timer is shown when the game starts. F11 toggles it either way.
+    pub show_timer: bool,
+    /// Whether loading a save continues the run stored in it. Otherwise loading invalidates the current run.
+    pub restore_from_saves: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Reflect, FromReflect, Serialize, Deserialize)]
+#[reflect(Serialize, Deserialize)]
+pub struct Rumble {
+    /// Multiplies the strength of every rumble. 0 turns rumble off.
+    pub intensity: f32,
+    /// Whether hard landings rumble, see [`Player::hard_landing_threshold`]
+    pub landings: bool,
+    /// Strength of the rumble per m/s of landing speed
+    pub landing_strength_per_speed: f32,
+    pub landing_duration: f32,
+    /// Whether taking damage rumbles
+    pub damage: bool,
+    pub damage_strength: f32,
+    pub damage_duration: f32,
+    /// Whether a new dialog page ticks
+    pub dialog: bool,
+    pub dialog_tick_strength: f32,
+    pub dialog_tick_duration: f32,
+}
+
+/// The default accessibility options. Players can override them in the settings menu, see [`UserSettings`](crate::file_system_interaction::user_settings::UserSettings).
+/// The options in effect are kept in [`AccessibilityOptions`](crate::player_control::accessibility::AccessibilityOptions).
+#[derive(Debug, Clone, PartialEq, Reflect, FromReflect, Serialize, Deserialize)]
+#[reflect(Serialize, Deserialize)]
+pub struct Accessibility {
+    /// Turns off camera movement the player does not control, like landing dips and the dynamic field of view
+    pub reduce_camera_motion: bool,
+    /// Pressing a hold action like sprinting once turns it on until it is pressed again
+    pub hold_to_toggle: bool,
+    /// Multiplies every camera smoothing constant. Above 1 the camera follows more stiffly, below 1 more softly.
+    pub camera_smoothing_multiplier: f32,
+}
+
+impl Default for Accessibility {
+    fn default() -> Self {
+        Self {
+            reduce_camera_motion: false,
+            hold_to_toggle: false,
+            camera_smoothing_multiplier: 1.0,
+        }
+    }
+}
+
+impl Default for Rumble {
+    fn default() -> Self {
+        Self {
+            intensity: 1.0,
+            landings: true,
+            landing_strength_per_speed: 0.04,
+            landing_duration: 0.2,
+            damage: true,
+            damage_strength: 0.8,
+            damage_duration: 0.3,
+            dialog: false,
+            dialog_tick_strength: 0.15,
+            dialog_tick_duration: 0.05,
+        }
+    }
+}
+
+impl Default for Speedrun {
+    fn default() -> Self {
+        Self {
+            show_timer: false,
+            restore_from_saves: false,
         }
     }
 }
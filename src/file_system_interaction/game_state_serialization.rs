@@ -1,25 +1,62 @@
-use crate::file_system_interaction::level_serialization::{CurrentLevel, WorldLoadRequest};
-use crate::level_instantiation::spawning::{DelayedSpawnEvent, GameObject, SpawnEvent};
+use crate::file_system_interaction::asset_loading::ConfigAssets;
+use crate::file_system_interaction::config::{GameConfig, SaveFormat};
+use crate::file_system_interaction::level_serialization::{
+    CurrentLevel, LoadWorldLabel, WorldLoadRequest,
+};
+use crate::file_system_interaction::storage::{get_data_dir, storage};
+use crate::file_system_interaction::thumbnail::ThumbnailRequest;
+use crate::level_instantiation::level_transition::LevelTransition;
+use crate::level_instantiation::spawning::spawn::{
+    get_runtime_spawns, DelayedSpawnEvents, DespawnedObjects, NextRuntimeSpawnId,
+};
+use crate::level_instantiation::spawning::{
+    DelayedSpawnEvent, GameObject, PatrolRoute, SpawnEvent, SpawnId, SpawnTracker,
+};
+use crate::movement::patrol::{PatrolProgress, PendingPatrolProgress};
+use crate::player_control::camera::{
+    CameraForceSnap, IngameCamera, IngameCameraKind, RestoredCamera,
+};
 use crate::player_control::player_embodiment::Player;
 use crate::util::log_error::log_errors;
+use crate::world_interaction::animated_prop::{PendingPropProgress, PropProgress, PropState};
+use crate::world_interaction::checkpoint::LastCheckpoint;
 use crate::world_interaction::condition::ActiveConditions;
 use crate::world_interaction::dialog::{CurrentDialog, DialogEvent};
+use crate::world_interaction::health::{Dying, Health, RestoredHealth};
+use crate::world_interaction::inventory::Inventory;
+use crate::world_interaction::speedrun::{RestoredSpeedrun, SpeedrunTimer};
+use crate::world_interaction::time_of_day::TimeOfDay;
+use crate::world_interaction::trigger::FiredTriggers;
 use crate::GameState;
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
+use bevy::ecs::system::SystemParam;
 use bevy::prelude::*;
+use bevy::utils::HashMap;
 use chrono::prelude::Local;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
 use glob::glob;
-use serde::{Deserialize, Serialize};
-use std::borrow::Cow;
+use serde::de::{DeserializeOwned, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt::{Display, Formatter};
 use std::fs;
+use std::io::{BufRead, Read, Write};
 use std::path::{Path, PathBuf};
 
+/// Saves and loads the game state in save slots.
+/// Every slot is a directory in the platform's data directory holding a single save file.
+/// On the web, where there is no data directory, the slots are kept in the browser's storage instead, see [`storage`].
+/// The first line of a save file is its [`SaveMetadata`], so that the slots can be listed without parsing the whole save.
 pub struct GameStateSerializationPlugin;
 
 impl Plugin for GameStateSerializationPlugin {
     fn build(&self, app: &mut App) {
         app.add_event::<GameSaveRequest>()
             .add_event::<GameLoadRequest>()
+            .register_type::<PlayTime>()
+            .init_resource::<PlayTime>()
+            .add_startup_system(migrate_legacy_saves.pipe(log_errors))
             .add_system_set(
                 SystemSet::on_in_stack_update(GameState::Playing)
                     .with_system(
@@ -32,6 +69,11 @@ impl Plugin for GameStateSerializationPlugin {
                             .pipe(log_errors)
                             .after(HandleLoadRequestsLabel),
                     ),
+            )
+            .add_system_set(SystemSet::on_update(GameState::Playing).with_system(track_play_time))
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                restore_game_state.after(LoadWorldLabel),
             );
     }
 }
@@ -39,24 +81,785 @@ impl Plugin for GameStateSerializationPlugin {
 #[derive(SystemLabel)]
 pub struct HandleLoadRequestsLabel;
 
+/// Version of the save file format written by this build. Saves with a higher version cannot be read,
+/// saves with a lower version are migrated when they are loaded.
+pub const SAVE_FORMAT_VERSION: u32 = 3;
+const SAVE_FILE_NAME: &str = "save.sav.ron";
+const THUMBNAIL_FILE_NAME: &str = "thumbnail.png";
+const GAME_VERSION: &str = env!("CARGO_PKG_VERSION");
+/// First line of a binary save. RON saves start with their header instead.
+const BINARY_SAVE_MAGIC: &[u8] = b"FOXTROT BINARY SAVE\n";
+
 #[derive(Debug, Clone, Eq, PartialEq, Resource, Serialize, Deserialize, Default)]
 pub struct GameSaveRequest {
-    pub filename: Option<String>,
+    pub slot: SaveSlot,
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Resource, Serialize, Deserialize, Default)]
 pub struct GameLoadRequest {
-    pub filename: Option<String>,
+    pub slot: SaveSlot,
+}
+
+/// A place to store a save in. Each slot is a directory in [`get_saves_dir`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum SaveSlot {
+    Index(u32),
+    Named(String),
+    /// Written by the game itself, see [`AutosavePlugin`](crate::file_system_interaction::autosave::AutosavePlugin).
+    /// The newest autosave has index 0.
+    Autosave(u32),
+    /// Written by [`PlayerAction::QuickSave`](crate::player_control::actions::PlayerAction::QuickSave).
+    /// The newest quicksave has index 0.
+    Quicksave(u32),
+}
+
+impl Default for SaveSlot {
+    fn default() -> Self {
+        Self::Index(0)
+    }
+}
+
+impl Display for SaveSlot {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SaveSlot::Index(index) => write!(f, "{index}"),
+            SaveSlot::Named(name) => write!(f, "{name}"),
+            SaveSlot::Autosave(index) => write!(f, "autosave {index}"),
+            SaveSlot::Quicksave(index) => write!(f, "quicksave {index}"),
+        }
+    }
+}
+
+impl SaveSlot {
+    /// The slot with the given name. An empty name refers to the first slot.
+    /// Names of rotated slots are changed, so that their saves are never overwritten by hand.
+    pub fn from_name(name: &str) -> Self {
+        // Sanitizing can leave nothing of the name, e.g. of "..."
+        let name = sanitize_slot_name(name);
+        if name.is_empty() {
+            return default();
+        }
+        match Self::from_dir_name(&name) {
+            slot if slot.is_rotated() => Self::Named(format!("{}_", name.replace('.', "_"))),
+            slot => slot,
+        }
+    }
+
+    fn dir_name(&self) -> String {
+        match self {
+            SaveSlot::Index(index) => format!("slot_{index}"),
+            SaveSlot::Named(name) => sanitize_slot_name(name),
+            SaveSlot::Autosave(index) => format!("autosave.{index}"),
+            SaveSlot::Quicksave(0) => "quicksave".to_string(),
+            SaveSlot::Quicksave(index) => format!("quicksave.{index}"),
+        }
+    }
+
+    fn from_dir_name(dir_name: &str) -> Self {
+        let parse_index = |prefix: &str| {
+            dir_name
+                .strip_prefix(prefix)
+                .and_then(|index| index.parse().ok())
+        };
+        if let Some(index) = parse_index("slot_") {
+            Self::Index(index)
+        } else if let Some(index) = parse_index("autosave.") {
+            Self::Autosave(index)
+        } else if dir_name == "quicksave" {
+            Self::Quicksave(0)
+        } else if let Some(index) = parse_index("quicksave.") {
+            Self::Quicksave(index)
+        } else {
+            Self::Named(dir_name.to_string())
+        }
+    }
+
+    /// Whether the game rotates the saves in this slot by itself
+    pub fn is_rotated(&self) -> bool {
+        matches!(self, SaveSlot::Autosave(_) | SaveSlot::Quicksave(_))
+    }
+
+    /// Fails for a slot without a directory name, which would refer to the directory of all slots
+    pub(crate) fn get_dir(&self) -> Result<PathBuf> {
+        let dir_name = self.dir_name();
+        if dir_name.is_empty() {
+            bail!("Save slot {self:?} has no valid directory name");
+        }
+        Ok(get_saves_dir().join(dir_name))
+    }
+
+    pub(crate) fn get_path(&self) -> Result<PathBuf> {
+        Ok(self.get_dir()?.join(SAVE_FILE_NAME))
+    }
+
+    /// See [`ThumbnailPlugin`](crate::file_system_interaction::thumbnail::ThumbnailPlugin)
+    pub(crate) fn get_thumbnail_path(&self) -> Result<PathBuf> {
+        Ok(self.get_dir()?.join(THUMBNAIL_FILE_NAME))
+    }
+}
+
+fn sanitize_slot_name(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_alphanumeric() || matches!(c, '-' | '_' | ' ' | '.') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>()
+        .trim_matches(|c: char| c == '.' || c.is_whitespace())
+        .to_string()
+}
+
+/// Directory in which the save slots are stored. Relative on platforms without a data directory, e.g. the web.
+pub fn get_saves_dir() -> PathBuf {
+    get_data_dir().join("saves")
+}
+
+/// Summary of a save, written in front of it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
+pub struct SaveMetadata {
+    pub format_version: u32,
+    /// Version of the game that wrote the save
+    #[serde(default)]
+    pub game_version: String,
+    /// Unix timestamp in seconds of when the save was written
+    pub timestamp: i64,
+    /// Time in seconds spent playing
+    pub play_time: f32,
+    pub level: String,
+    pub player_position: Vec3,
+}
+
+/// A save slot as shown in the save menu
+#[derive(Debug, Clone, PartialEq)]
+pub struct SaveSlotInfo {
+    pub slot: SaveSlot,
+    /// The reason the slot cannot be loaded if its save is corrupt or from a version of the game it cannot be migrated from
+    pub metadata: Result<SaveMetadata, String>,
+}
+
+/// All save slots, newest first. Unreadable slots come last.
+pub fn list_save_slots() -> Vec<SaveSlotInfo> {
+    let storage = storage();
+    let slot_dirs = match storage.list(&get_saves_dir()) {
+        Ok(slot_dirs) => slot_dirs,
+        Err(_) => return vec![],
+    };
+    let mut slots: Vec<_> = slot_dirs
+        .into_iter()
+        .filter(|dir| storage.is_file(&dir.join(SAVE_FILE_NAME)))
+        .filter_map(|dir| {
+            let path = dir.join(SAVE_FILE_NAME);
+            let dir_name = dir.file_name()?.to_string_lossy().to_string();
+            Some(SaveSlotInfo {
+                slot: SaveSlot::from_dir_name(&dir_name),
+                metadata: read_save_metadata(&path).map_err(|e| format!("{e:#}")),
+            })
+        })
+        .collect();
+    slots.sort_by_key(|info| {
+        std::cmp::Reverse(
+            info.metadata
+                .as_ref()
+                .map(|metadata| metadata.timestamp)
+                .unwrap_or(i64::MIN),
+        )
+    });
+    slots
+}
+
+pub fn delete_save_slot(slot: &SaveSlot) -> Result<()> {
+    let dir = slot.get_dir()?;
+    storage()
+        .remove(&dir)
+        .with_context(|| format!("Failed to delete save slot at {}", dir.to_string_lossy()))?;
+    info!("Deleted save slot {slot}");
+    Ok(())
+}
+
+fn read_save_metadata(path: &Path) -> Result<SaveMetadata> {
+    let serialized = storage().read(path).context("Failed to open save file")?;
+    read_save_header(&serialized[..])
+}
+
+/// Reads only the header of a save, so that binary saves are not decompressed
+fn read_save_header(mut reader: impl BufRead) -> Result<SaveMetadata> {
+    let mut header = String::new();
+    reader
+        .read_line(&mut header)
+        .context("Failed to read save header")?;
+    if header.as_bytes() == BINARY_SAVE_MAGIC {
+        header.clear();
+        reader
+            .read_line(&mut header)
+            .context("Failed to read save header")?;
+    }
+    parse_save_metadata(&header)
+}
+
+fn parse_save_metadata(header: &str) -> Result<SaveMetadata> {
+    let metadata: SaveMetadata =
+        ron::from_str(header.trim()).context("Failed to deserialize save header")?;
+    if metadata.format_version > SAVE_FORMAT_VERSION {
+        bail!(
+            "The save was made by a newer version of the game ({}, save format {}, supported up to {})",
+            metadata.game_version,
+            metadata.format_version,
+            SAVE_FORMAT_VERSION
+        );
+    }
+    if !can_migrate(metadata.format_version) {
+        bail!(
+            "The save was made by a version of the game that is too old to be loaded (save format {})",
+            metadata.format_version
+        );
+    }
+    Ok(metadata)
+}
+
+/// Writes the header as a line of RON in front of the body.
+/// Binary saves start with [`BINARY_SAVE_MAGIC`] and have a compressed MessagePack body.
+fn write_save(
+    metadata: &SaveMetadata,
+    save_model: &SaveModel,
+    format: SaveFormat,
+) -> Result<Vec<u8>> {
+    let header = ron::to_string(metadata).context("Failed to serialize save header")?;
+    let mut serialized = vec![];
+    match format {
+        SaveFormat::Ron => {
+            let body = ron::to_string(save_model).context("Failed to serialize save")?;
+            serialized.extend_from_slice(format!("{header}\n{body}").as_bytes());
+        }
+        SaveFormat::Binary => {
+            let body = rmp_serde::to_vec_named(save_model).context("Failed to serialize save")?;
+            serialized.extend_from_slice(BINARY_SAVE_MAGIC);
+            serialized.extend_from_slice(format!("{header}\n").as_bytes());
+            let mut encoder = DeflateEncoder::new(serialized, Compression::fast());
+            encoder
+                .write_all(&body)
+                .context("Failed to compress save")?;
+            serialized = encoder.finish().context("Failed to compress save")?;
+        }
+    }
+    Ok(serialized)
+}
+
+/// Reads a save in either format
+fn read_save(serialized: &[u8]) -> Result<(SaveMetadata, SaveModel)> {
+    let (format, serialized) = match serialized.strip_prefix(BINARY_SAVE_MAGIC) {
+        Some(serialized) => (SaveFormat::Binary, serialized),
+        None => (SaveFormat::Ron, serialized),
+    };
+    let header_end = serialized
+        .iter()
+        .position(|byte| *byte == b'\n')
+        .context("Failed to find save header")?;
+    let header =
+        std::str::from_utf8(&serialized[..header_end]).context("Failed to read save header")?;
+    let metadata = parse_save_metadata(header)?;
+    let body = &serialized[header_end + 1..];
+    let save_model = match format {
+        SaveFormat::Ron => {
+            let body = std::str::from_utf8(body).context("Failed to read save")?;
+            deserialize_save_body(body, metadata.format_version)?
+        }
+        SaveFormat::Binary => {
+            let mut decoder = DeflateDecoder::new(body);
+            let mut body = vec![];
+            decoder
+                .read_to_end(&mut body)
+                .context("Failed to decompress save")?;
+            deserialize_binary_save_body(&body, metadata.format_version)?
+        }
+    };
+    Ok((metadata, save_model))
+}
+
+/// Deserializes the body of a save, migrating it first if it has an older format version
+fn deserialize_save_body(body: &str, format_version: u32) -> Result<SaveModel> {
+    if format_version == SAVE_FORMAT_VERSION {
+        return ron::from_str(body).context("Failed to deserialize save");
+    }
+    let mut document = SaveDocument::parse(body).context("Failed to parse save for migration")?;
+    migrate_save(&mut document, format_version)?;
+    document
+        .deserialize()
+        .context("Failed to deserialize migrated save")
+}
+
+/// Like [`deserialize_save_body`], but for the decompressed MessagePack body of a binary save
+fn deserialize_binary_save_body(body: &[u8], format_version: u32) -> Result<SaveModel> {
+    if format_version == SAVE_FORMAT_VERSION {
+        return rmp_serde::from_slice(body).context("Failed to deserialize save");
+    }
+    let mut document =
+        SaveDocument::from_message_pack(body).context("Failed to parse save for migration")?;
+    migrate_save(&mut document, format_version)?;
+    document
+        .deserialize()
+        .context("Failed to deserialize migrated save")
+}
+
+fn migrate_save(document: &mut SaveDocument, format_version: u32) -> Result<()> {
+    for version in format_version..SAVE_FORMAT_VERSION {
+        let migration = SAVE_MIGRATIONS
+            .iter()
+            .find(|migration| migration.from_version == version)
+            .with_context(|| format!("No migration from save format {version}"))?;
+        (migration.migrate)(document)
+            .with_context(|| format!("Failed to migrate save from save format {version}"))?;
+    }
+    Ok(())
+}
+
+fn can_migrate(format_version: u32) -> bool {
+    (format_version..SAVE_FORMAT_VERSION).all(|version| {
+        SAVE_MIGRATIONS
+            .iter()
+            .any(|migration| migration.from_version == version)
+    })
+}
+
+/// Upgrades the body of a save from `from_version` to the next format version.
+/// Every change to the shape of a saved type bumps [`SAVE_FORMAT_VERSION`] and adds a migration to [`SAVE_MIGRATIONS`].
+struct SaveMigration {
+    from_version: u32,
+    migrate: fn(&mut SaveDocument) -> Result<()>,
+}
+
+const SAVE_MIGRATIONS: &[SaveMigration] = &[
+    SaveMigration {
+        from_version: 1,
+        migrate: migrate_level_name,
+    },
+    SaveMigration {
+        from_version: 2,
+        migrate: migrate_camera,
+    },
+];
+
+/// The level used to be called the scene
+fn migrate_level_name(document: &mut SaveDocument) -> Result<()> {
+    document.rename_field("scene", "level")
+}
+
+/// The camera was not saved, so it starts out as it is placed in the level
+fn migrate_camera(document: &mut SaveDocument) -> Result<()> {
+    document.add_field("camera", &None::<IngameCameraKind>)
+}
+
+/// The top-level fields of a serialized save, each kept as raw RON or as a generic MessagePack value for binary saves.
+/// Lets migrations change saves that no longer deserialize into the current types.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SaveDocument {
+    fields: Vec<(String, FieldValue)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum FieldValue {
+    Ron(String),
+    MessagePack(MessagePackValue),
+}
+
+impl SaveDocument {
+    /// Splits a RON struct into its fields without interpreting their values
+    pub fn parse(ron: &str) -> Result<Self> {
+        let ron = ron.trim();
+        // Skip the struct name, if any
+        let body = ron
+            .find('(')
+            .filter(|start| {
+                ron[..*start]
+                    .chars()
+                    .all(|c| c.is_alphanumeric() || c == '_')
+            })
+            .map(|start| &ron[start + 1..])
+            .and_then(|body| body.strip_suffix(')'))
+            .context("Save is not a RON struct")?;
+
+        let mut fields = vec![];
+        for field in split_top_level(body)? {
+            let field = field.trim();
+            if field.is_empty() {
+                continue;
+            }
+            let (name, value) = field
+                .split_once(':')
+                .with_context(|| format!("Failed to find the name of field {field}"))?;
+            fields.push((
+                name.trim().to_string(),
+                FieldValue::Ron(value.trim().to_string()),
+            ));
+        }
+        Ok(Self { fields })
+    }
+
+    /// Splits the MessagePack map of a binary save into its fields
+    pub fn from_message_pack(body: &[u8]) -> Result<Self> {
+        let entries = match rmp_serde::from_slice(body).context("Failed to decode save")? {
+            MessagePackValue::Map(entries) => entries,
+            _ => bail!("Save is not a MessagePack map"),
+        };
+        let fields = entries
+            .into_iter()
+            .map(|(name, value)| match name {
+                MessagePackValue::String(name) => Ok((name, FieldValue::MessagePack(value))),
+                name => bail!("Save has a field that is not named by a string: {name:?}"),
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { fields })
+    }
+
+    pub fn to_ron(&self) -> Result<String> {
+        let fields = self
+            .fields
+            .iter()
+            .map(|(name, value)| match value {
+                FieldValue::Ron(value) => Ok(format!("{name}:{value}")),
+                FieldValue::MessagePack(_) => {
+                    bail!("Failed to convert field {name} of a binary save to RON")
+                }
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(format!("({})", fields.join(",")))
+    }
+
+    /// Whether the document was read from a binary save
+    fn is_message_pack(&self) -> bool {
+        self.fields
+            .iter()
+            .any(|(_, value)| matches!(value, FieldValue::MessagePack(_)))
+    }
+
+    fn deserialize<T: DeserializeOwned>(&self) -> Result<T> {
+        if !self.is_message_pack() {
+            return ron::from_str(&self.to_ron()?).context("Failed to deserialize save");
+        }
+        let entries = self
+            .fields
+            .iter()
+            .map(|(name, value)| match value {
+                FieldValue::MessagePack(value) => {
+                    Ok((MessagePackValue::String(name.clone()), value.clone()))
+                }
+                FieldValue::Ron(_) => {
+                    bail!("Failed to convert field {name} of a RON save to MessagePack")
+                }
+            })
+            .collect::<Result<_>>()?;
+        let body = rmp_serde::to_vec_named(&MessagePackValue::Map(entries))
+            .context("Failed to encode save")?;
+        rmp_serde::from_slice(&body).context("Failed to deserialize save")
+    }
+
+    pub fn has_field(&self, name: &str) -> bool {
+        self.fields.iter().any(|(field, _)| field == name)
+    }
+
+    pub fn rename_field(&mut self, from: &str, to: &str) -> Result<()> {
+        if self.has_field(to) {
+            bail!("Failed to rename field {from} to {to}: {to} already exists");
+        }
+        for (name, _) in self.fields.iter_mut().filter(|(name, _)| name == from) {
+            *name = to.to_string();
+        }
+        Ok(())
+    }
+
+    /// Adds the field unless it already exists, in the same encoding as the other fields
+    pub fn add_field<T: Serialize>(&mut self, name: &str, value: &T) -> Result<()> {
+        if !self.has_field(name) {
+            let error = || format!("Failed to serialize new field {name}");
+            let value = if self.is_message_pack() {
+                let encoded = rmp_serde::to_vec_named(value).with_context(error)?;
+                FieldValue::MessagePack(rmp_serde::from_slice(&encoded).with_context(error)?)
+            } else {
+                FieldValue::Ron(ron::to_string(value).with_context(error)?)
+            };
+            self.fields.push((name.to_string(), value));
+        }
+        Ok(())
+    }
+
+    pub fn remove_field(&mut self, name: &str) {
+        self.fields.retain(|(field, _)| field != name);
+    }
+}
+
+/// Any MessagePack value, kept as it was decoded so that it encodes to the same MessagePack again
+#[derive(Debug, Clone, PartialEq)]
+enum MessagePackValue {
+    Nil,
+    Bool(bool),
+    Unsigned(u64),
+    Signed(i64),
+    F32(f32),
+    F64(f64),
+    String(String),
+    Binary(Vec<u8>),
+    Array(Vec<MessagePackValue>),
+    Map(Vec<(MessagePackValue, MessagePackValue)>),
+}
+
+impl Serialize for MessagePackValue {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            MessagePackValue::Nil => serializer.serialize_unit(),
+            MessagePackValue::Bool(value) => serializer.serialize_bool(*value),
+            MessagePackValue::Unsigned(value) => serializer.serialize_u64(*value),
+            MessagePackValue::Signed(value) => serializer.serialize_i64(*value),
+            MessagePackValue::F32(value) => serializer.serialize_f32(*value),
+            MessagePackValue::F64(value) => serializer.serialize_f64(*value),
+            MessagePackValue::String(value) => serializer.serialize_str(value),
+            MessagePackValue::Binary(value) => serializer.serialize_bytes(value),
+            MessagePackValue::Array(values) => serializer.collect_seq(values),
+            MessagePackValue::Map(entries) => {
+                serializer.collect_map(entries.iter().map(|(key, value)| (key, value)))
+            }
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for MessagePackValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(MessagePackValueVisitor)
+    }
+}
+
+struct MessagePackValueVisitor;
+
+impl<'de> Visitor<'de> for MessagePackValueVisitor {
+    type Value = MessagePackValue;
+
+    fn expecting(&self, formatter: &mut Formatter) -> std::fmt::Result {
+        formatter.write_str("any MessagePack value")
+    }
+
+    fn visit_unit<E>(self) -> Result<Self::Value, E> {
+        Ok(MessagePackValue::Nil)
+    }
+
+    fn visit_none<E>(self) -> Result<Self::Value, E> {
+        Ok(MessagePackValue::Nil)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        MessagePackValue::deserialize(deserializer)
+    }
+
+    fn visit_bool<E>(self, value: bool) -> Result<Self::Value, E> {
+        Ok(MessagePackValue::Bool(value))
+    }
+
+    fn visit_u64<E>(self, value: u64) -> Result<Self::Value, E> {
+        Ok(MessagePackValue::Unsigned(value))
+    }
+
+    fn visit_i64<E>(self, value: i64) -> Result<Self::Value, E> {
+        Ok(MessagePackValue::Signed(value))
+    }
+
+    fn visit_f32<E>(self, value: f32) -> Result<Self::Value, E> {
+        Ok(MessagePackValue::F32(value))
+    }
+
+    fn visit_f64<E>(self, value: f64) -> Result<Self::Value, E> {
+        Ok(MessagePackValue::F64(value))
+    }
+
+    fn visit_str<E>(self, value: &str) -> Result<Self::Value, E> {
+        Ok(MessagePackValue::String(value.to_string()))
+    }
+
+    fn visit_bytes<E>(self, value: &[u8]) -> Result<Self::Value, E> {
+        Ok(MessagePackValue::Binary(value.to_vec()))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut values = vec![];
+        while let Some(value) = seq.next_element()? {
+            values.push(value);
+        }
+        Ok(MessagePackValue::Array(values))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut entries = vec![];
+        while let Some(entry) = map.next_entry()? {
+            entries.push(entry);
+        }
+        Ok(MessagePackValue::Map(entries))
+    }
+}
+
+/// Splits RON at the commas that are not nested in brackets or strings
+fn split_top_level(ron: &str) -> Result<Vec<&str>> {
+    let mut parts = vec![];
+    let mut depth = 0_usize;
+    let mut in_string = false;
+    let mut escaped = false;
+    let mut start = 0;
+    for (index, c) in ron.char_indices() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => in_string = true,
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => {
+                depth = depth
+                    .checked_sub(1)
+                    .context("Unbalanced brackets in save")?;
+            }
+            ',' if depth == 0 => {
+                parts.push(&ron[start..index]);
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    if in_string || depth != 0 {
+        bail!("Unbalanced brackets or quotes in save");
+    }
+    parts.push(&ron[start..]);
+    Ok(parts)
+}
+
+/// Time in seconds spent playing the current game, excluding pauses
+#[derive(Debug, Clone, Copy, PartialEq, Resource, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Resource, Serialize, Deserialize)]
+pub struct PlayTime(pub f32);
+
+fn track_play_time(time: Res<Time>, mut play_time: ResMut<PlayTime>) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("track_play_time").entered();
+    play_time.0 += time.delta_seconds();
 }
 
 #[derive(Debug, Clone, PartialEq, Resource, Serialize, Deserialize, Default)]
 struct SaveModel {
-    scene: String,
+    level: String,
     #[serde(default, skip_serializing_if = "ActiveConditions::is_empty")]
     conditions: ActiveConditions,
+    #[serde(default, skip_serializing_if = "Inventory::is_empty")]
+    inventory: Inventory,
+    /// One-shot triggers that already fired
+    #[serde(default, skip_serializing_if = "FiredTriggers::is_empty")]
+    fired_triggers: FiredTriggers,
+    /// Where the player respawns after falling off the map
+    #[serde(default)]
+    last_checkpoint: LastCheckpoint,
+    /// Missing in saves from before the day/night cycle, which keep the time the level starts at
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    time_of_day: Option<TimeOfDay>,
     player_transform: Transform,
+    /// Missing in saves from before the player could be hurt, who start with full health
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    player_health: Option<Health>,
+    /// Missing in saves from before speedruns were timed, which invalidate the current run
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    speedrun: Option<SpeedrunTimer>,
+    /// Kind, position and zoom of the camera. Its config is not saved.
+    camera: Option<IngameCameraKind>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     dialog_event: Option<DialogEvent>,
+    /// Progress of every NPC patrol, keyed by route name
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    patrols: HashMap<String, PatrolProgress>,
+    /// How far every animated prop is open, keyed by prop
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    props: HashMap<String, PropProgress>,
+    /// Objects spawned during gameplay
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    spawned: Vec<SpawnEvent>,
+    /// Level objects despawned during gameplay
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    despawned: Vec<SpawnId>,
+}
+
+/// State from a loaded save that is applied after the world was loaded, as loading the world resets it
+#[derive(Debug, Clone, PartialEq, Resource, Default)]
+struct PendingGameState {
+    conditions: ActiveConditions,
+    inventory: Inventory,
+    fired_triggers: FiredTriggers,
+    last_checkpoint: LastCheckpoint,
+    play_time: PlayTime,
+    time_of_day: Option<TimeOfDay>,
+    player_health: Option<Health>,
+    speedrun: Option<SpeedrunTimer>,
+    camera: Option<IngameCameraKind>,
+    dialog_event: Option<DialogEvent>,
+}
+
+/// Moves the newest save from before save slots existed into the first slot
+fn migrate_legacy_saves() -> Result<()> {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("migrate_legacy_saves").entered();
+    let slot = SaveSlot::default();
+    let slot_path = slot.get_path()?;
+    if storage().is_file(&slot_path) {
+        return Ok(());
+    }
+    // Legacy saves were always written next to the executable, so they are read from the file system directly
+    let mut legacy_saves: Vec<_> = glob("./saves/*.sav.ron")
+        .context("Failed to read glob pattern")?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.is_file())
+        .collect();
+    legacy_saves.sort_by_cached_key(|path| {
+        path.metadata()
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    });
+    let legacy_path = match legacy_saves.last() {
+        Some(path) => path,
+        None => return Ok(()),
+    };
+    let serialized = fs::read_to_string(legacy_path).context("Failed to read legacy save")?;
+    // Saves from before save slots existed have the shape of the first format version
+    let save_model = deserialize_save_body(&serialized, 1).with_context(|| {
+        format!(
+            "Failed to migrate legacy save at {}",
+            legacy_path.to_string_lossy()
+        )
+    })?;
+    let timestamp = legacy_path
+        .metadata()
+        .and_then(|metadata| metadata.modified())
+        .map(|modified| chrono::DateTime::<Local>::from(modified).timestamp())
+        .unwrap_or_else(|_| Local::now().timestamp());
+    let metadata = SaveMetadata {
+        format_version: SAVE_FORMAT_VERSION,
+        game_version: GAME_VERSION.to_string(),
+        timestamp,
+        play_time: 0.,
+        level: save_model.level.clone(),
+        player_position: save_model.player_transform.translation,
+    };
+    storage()
+        .write(
+            &slot_path,
+            &write_save(&metadata, &save_model, SaveFormat::Ron)?,
+        )
+        .context("Failed to write migrated save")?;
+    // Keep the old file around, but make sure it is not migrated again after the slot was deleted
+    fs::rename(legacy_path, legacy_path.with_extension("ron.migrated"))
+        .context("Failed to rename legacy save")?;
+    info!(
+        "Migrated legacy save {} into save slot {slot}",
+        legacy_path.to_string_lossy()
+    );
+    Ok(())
 }
 
 fn handle_load_requests(
@@ -64,63 +867,58 @@ fn handle_load_requests(
     mut load_events: EventReader<GameLoadRequest>,
     mut loader: EventWriter<WorldLoadRequest>,
     mut spawner: EventWriter<DelayedSpawnEvent>,
-    mut dialog_event_writer: EventWriter<DialogEvent>,
+    mut delayed_spawn_events: ResMut<DelayedSpawnEvents>,
 ) -> Result<()> {
     for load in load_events.iter() {
-        let path = match load
-            .filename
-            .as_ref()
-            .map(|filename| anyhow::Ok(Some(get_save_path(filename.clone()))))
-            .unwrap_or_else(|| {
-                let mut saves: Vec<_> = glob("./saves/*.sav.ron")
-                    .context("Failed to read glob pattern")?
-                    .filter_map(|entry| entry.ok())
-                    .filter(|entry| entry.is_file())
-                    .collect();
-                saves.sort_by_cached_key(|f| {
-                    f.metadata()
-                        .expect("Failed to read file metadata")
-                        .modified()
-                        .expect("Failed to read file modified time")
-                });
-                Ok(saves.last().map(|entry| entry.to_owned()))
-            })? {
-            Some(path) => path,
-            None => {
-                error!("Failed to load save: No filename provided and no saves found on disk");
-                continue;
-            }
-        };
-        let serialized = match fs::read_to_string(&path) {
-            Ok(serialized) => {
-                info!("Successfully read save at {}", path.to_string_lossy());
-                serialized
+        let (metadata, save_model) = match read_slot(&load.slot) {
+            Ok(save) => {
+                info!("Successfully read save slot {}", load.slot);
+                save
             }
             Err(e) => {
-                error!(
-                    "Failed to read save {:?} at {:?}: {}",
-                    &load.filename, path, e
-                );
-                continue;
-            }
-        };
-        let save_model: SaveModel = match ron::from_str(&serialized) {
-            Ok(save_model) => save_model,
-            Err(e) => {
-                error!(
-                    "Failed to deserialize save {:?} at {:?}: {}",
-                    &load.filename, path, e
-                );
+                error!("Failed to load save slot {}: {:#}", load.slot, e);
                 continue;
             }
         };
+        // Objects that were about to be spawned belong to the game that is replaced
+        delayed_spawn_events.clear();
         loader.send(WorldLoadRequest {
-            filename: save_model.scene,
+            filename: save_model.level,
+            despawned: save_model.despawned,
+            ..default()
         });
-        if let Some(dialog_event) = save_model.dialog_event {
-            dialog_event_writer.send(dialog_event);
+        commands.insert_resource(PendingGameState {
+            conditions: save_model.conditions,
+            inventory: save_model.inventory,
+            fired_triggers: save_model.fired_triggers,
+            last_checkpoint: save_model.last_checkpoint,
+            play_time: PlayTime(metadata.play_time),
+            time_of_day: save_model.time_of_day,
+            player_health: save_model.player_health,
+            speedrun: save_model.speedrun,
+            camera: save_model.camera,
+            dialog_event: save_model.dialog_event,
+        });
+        commands.insert_resource(PendingPatrolProgress(save_model.patrols));
+        commands.insert_resource(PendingPropProgress(save_model.props));
+
+        let next_runtime_id = save_model
+            .spawned
+            .iter()
+            .filter_map(|event| match event.id {
+                Some(SpawnId::Runtime(id)) => Some(id + 1),
+                _ => None,
+            })
+            .max()
+            .unwrap_or_default();
+        commands.insert_resource(NextRuntimeSpawnId(next_runtime_id));
+        for event in save_model.spawned {
+            // Spawn after the level was loaded, otherwise loading it despawns the objects again
+            spawner.send(DelayedSpawnEvent {
+                tick_delay: 2,
+                event,
+            });
         }
-        commands.insert_resource(save_model.conditions);
 
         spawner.send(DelayedSpawnEvent {
             tick_delay: 2,
@@ -129,66 +927,542 @@ fn handle_load_requests(
                 transform: Transform {
                     ..save_model.player_transform
                 },
+                ..default()
             },
         });
     }
     Ok(())
 }
 
+fn restore_game_state(
+    mut commands: Commands,
+    pending: Option<Res<PendingGameState>>,
+    mut dialog_event_writer: EventWriter<DialogEvent>,
+    mut snap_events: EventWriter<CameraForceSnap>,
+) {
+    let pending = match pending {
+        Some(pending) => pending,
+        None => return,
+    };
+    commands.insert_resource(pending.conditions.clone());
+    commands.insert_resource(pending.inventory.clone());
+    commands.insert_resource(pending.fired_triggers.clone());
+    commands.insert_resource(pending.last_checkpoint.clone());
+    commands.insert_resource(pending.play_time);
+    if let Some(time_of_day) = pending.time_of_day {
+        commands.insert_resource(time_of_day);
+    }
+    if let Some(health) = pending.player_health {
+        commands.insert_resource(RestoredHealth(health));
+    }
+    commands.insert_resource(RestoredSpeedrun(pending.speedrun.clone()));
+    if let Some(camera) = pending.camera.clone() {
+        commands.insert_resource(RestoredCamera(camera));
+    }
+    // The player is about to appear wherever they were when saving
+    snap_events.send(CameraForceSnap);
+    if let Some(dialog_event) = pending.dialog_event.clone() {
+        dialog_event_writer.send(dialog_event);
+    }
+    commands.remove_resource::<PendingGameState>();
+}
+
+/// Everything that goes into a save
+#[derive(SystemParam)]
+pub(crate) struct SaveSnapshot<'w, 's> {
+    conditions: Res<'w, ActiveConditions>,
+    inventory: Res<'w, Inventory>,
+    fired_triggers: Res<'w, FiredTriggers>,
+    last_checkpoint: Res<'w, LastCheckpoint>,
+    play_time: Res<'w, PlayTime>,
+    time_of_day: Res<'w, TimeOfDay>,
+    speedrun: Res<'w, SpeedrunTimer>,
+    dialog: Option<Res<'w, CurrentDialog>>,
+    player_query: Query<'w, 's, (&'static GlobalTransform, Option<&'static Health>), With<Player>>,
+    camera_query: Query<'w, 's, &'static IngameCamera>,
+    patrol_query: Query<'w, 's, (&'static PatrolRoute, &'static PatrolProgress)>,
+    prop_query: Query<'w, 's, &'static PropState>,
+    spawn_query: Query<
+        'w,
+        's,
+        (
+            &'static SpawnTracker,
+            &'static SpawnId,
+            &'static Transform,
+            Option<&'static PropState>,
+        ),
+    >,
+    despawned_objects: Res<'w, DespawnedObjects>,
+    current_level: Option<Res<'w, CurrentLevel>>,
+}
+
+impl SaveSnapshot<'_, '_> {
+    /// The current game as written to a save file, or `None` while there is no level or player to save
+    pub(crate) fn serialize(&self, format: SaveFormat) -> Result<Option<Vec<u8>>> {
+        let current_level = match &self.current_level {
+            Some(level) => level,
+            None => return Ok(None),
+        };
+        let (player_transform, player_health) = match self.player_query.iter().next() {
+            Some((transform, health)) => (transform.compute_transform(), health.copied()),
+            None => return Ok(None),
+        };
+        let dialog_event = self.dialog.as_ref().map(|dialog| DialogEvent {
+            dialog: dialog.id.clone(),
+            source: dialog.source,
+            page: Some(dialog.current_page.clone()),
+        });
+        let save_model = SaveModel {
+            level: current_level.scene.clone(),
+            conditions: self.conditions.clone(),
+            inventory: self.inventory.clone(),
+            fired_triggers: self.fired_triggers.clone(),
+            last_checkpoint: self.last_checkpoint.clone(),
+            time_of_day: Some(*self.time_of_day),
+            dialog_event,
+            player_transform,
+            player_health,
+            speedrun: Some(self.speedrun.clone()),
+            camera: self
+                .camera_query
+                .iter()
+                .next()
+                .map(|camera| camera.kind.clone()),
+            patrols: self
+                .patrol_query
+                .iter()
+                .map(|(route, progress)| (route.name.clone(), progress.clone()))
+                .collect(),
+            props: self
+                .prop_query
+                .iter()
+                .filter_map(|state| Some((state.key.clone()?, state.progress.clone())))
+                .collect(),
+            spawned: get_runtime_spawns(&self.spawn_query),
+            despawned: self.despawned_objects.0.clone(),
+        };
+        let metadata = SaveMetadata {
+            format_version: SAVE_FORMAT_VERSION,
+            game_version: GAME_VERSION.to_string(),
+            timestamp: Local::now().timestamp(),
+            play_time: self.play_time.0,
+            level: current_level.scene.clone(),
+            player_position: player_transform.translation,
+        };
+        write_save(&metadata, &save_model, format).map(Some)
+    }
+}
+
+/// Writes a serialized save into the slot, creating the slot if needed
+pub(crate) fn write_slot(slot: &SaveSlot, serialized: &[u8]) -> Result<()> {
+    let storage = storage();
+    storage
+        .write(&slot.get_path()?, serialized)
+        .with_context(|| format!("Failed to write save slot {slot}"))?;
+    // The thumbnail of an overwritten save is replaced once the new one is captured
+    let thumbnail = slot.get_thumbnail_path()?;
+    if storage.is_file(&thumbnail) {
+        storage
+            .remove(&thumbnail)
+            .context("Failed to remove outdated thumbnail")?;
+    }
+    Ok(())
+}
+
+/// Moves every save of a rotated set of slots one slot back, dropping the oldest, and writes the new one into the first slot
+pub(crate) fn write_rotated_slot(
+    serialized: &[u8],
+    history: u32,
+    slot: impl Fn(u32) -> SaveSlot,
+) -> Result<()> {
+    let storage = storage();
+    let history = history.max(1);
+    let oldest = slot(history - 1).get_dir()?;
+    if storage.exists(&oldest) {
+        storage
+            .remove(&oldest)
+            .context("Failed to remove oldest save")?;
+    }
+    for index in (0..history - 1).rev() {
+        let dir = slot(index).get_dir()?;
+        if storage.exists(&dir) {
+            storage
+                .rename(&dir, &slot(index + 1).get_dir()?)
+                .context("Failed to rotate saves")?;
+        }
+    }
+    write_slot(&slot(0), serialized)
+}
+
+/// Reads and deserializes the whole save in the slot
+fn read_slot(slot: &SaveSlot) -> Result<(SaveMetadata, SaveModel)> {
+    let path = slot.get_path()?;
+    let serialized = storage()
+        .read(&path)
+        .with_context(|| format!("Failed to read save at {}", path.to_string_lossy()))?;
+    read_save(&serialized)
+}
+
+/// Makes sure the save in the slot can be loaded
+pub(crate) fn verify_slot(slot: &SaveSlot) -> Result<()> {
+    read_slot(slot).map(|_| ())
+}
+
 fn handle_save_requests(
     mut save_events: EventReader<GameSaveRequest>,
-    conditions: Res<ActiveConditions>,
-    dialog: Option<Res<CurrentDialog>>,
-    player_query: Query<&GlobalTransform, With<Player>>,
-    current_level: Option<Res<CurrentLevel>>,
+    snapshot: SaveSnapshot,
+    level_transition: Option<Res<LevelTransition>>,
+    dying: Option<Res<Dying>>,
+    mut thumbnail_requests: EventWriter<ThumbnailRequest>,
+    config_handles: Res<ConfigAssets>,
+    config: Res<Assets<GameConfig>>,
 ) -> Result<()> {
-    let dialog = if let Some(ref dialog) = dialog {
-        let dialog: CurrentDialog = dialog.as_ref().clone();
-        Some(dialog)
+    let blocker = if level_transition.is_some() {
+        Some("The player is moving to another level")
+    } else if dying.is_some() {
+        Some("The player is dead")
     } else {
         None
     };
-    let current_level = match current_level {
-        Some(level) => level,
-        None => return Ok(()),
-    };
+    if let Some(blocker) = blocker {
+        for save in save_events.iter() {
+            error!("Failed to save game in slot {}: {blocker}", save.slot);
+        }
+        return Ok(());
+    }
+    let config = config
+        .get(&config_handles.game)
+        .context("Failed to get game config from handle")?;
     for save in save_events.iter() {
-        for player in &player_query {
-            let dialog_event = dialog.clone().map(|dialog| DialogEvent {
-                dialog: dialog.id,
-                source: dialog.source,
-                page: Some(dialog.current_page),
-            });
-            let save_model = SaveModel {
-                scene: current_level.scene.clone(),
-                conditions: conditions.clone(),
-                dialog_event,
-                player_transform: player.compute_transform(),
-            };
-            let serialized = match ron::to_string(&save_model) {
-                Ok(string) => string,
-                Err(e) => {
-                    error!("Failed to save world: {}", e);
-                    continue;
-                }
-            };
-            let filename = save
-                .filename
-                .clone()
-                .unwrap_or_else(|| Local::now().to_rfc2822().replace(':', "-"));
-            let path = get_save_path(filename.clone());
-            let dir = path.parent().context("Failed to get save directory")?;
-            fs::create_dir_all(dir).context("Failed to create save directory")?;
-            fs::write(&path, serialized)
-                .unwrap_or_else(|e| error!("Failed to write save {filename}: {e}"));
-
-            info!("Successfully saved game at {}", path.to_string_lossy());
+        let serialized = match snapshot.serialize(config.save.format) {
+            Ok(Some(serialized)) => serialized,
+            Ok(None) => continue,
+            Err(e) => {
+                error!("Failed to save world: {e:#}");
+                continue;
+            }
+        };
+        match write_slot(&save.slot, &serialized) {
+            Ok(()) => {
+                info!("Successfully saved game in slot {}", save.slot);
+                thumbnail_requests.send(ThumbnailRequest {
+                    slot: save.slot.clone(),
+                });
+            }
+            Err(e) => error!("{e:#}"),
         }
     }
     Ok(())
 }
 
-fn get_save_path(filename: impl Into<Cow<'static, str>>) -> PathBuf {
-    let filename = filename.into().to_string();
-    Path::new("saves").join(filename).with_extension("sav.ron")
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::player_control::camera::ThirdPersonCamera;
+    use crate::util::headless::{build_headless_app, HeadlessApp};
+    use crate::world_interaction::condition::ConditionId;
+    use crate::world_interaction::dialog::{DialogId, PageId};
+    use crate::world_interaction::speedrun::{RunState, Split, SplitKind};
+
+    #[test]
+    fn slot_survives_directory_name() {
+        for slot in [
+            SaveSlot::Index(3),
+            SaveSlot::Named("before the bridge".to_string()),
+            SaveSlot::Autosave(2),
+            SaveSlot::Quicksave(0),
+            SaveSlot::Quicksave(1),
+        ] {
+            assert_eq!(SaveSlot::from_dir_name(&slot.dir_name()), slot);
+        }
+        assert_eq!(SaveSlot::from_name(""), SaveSlot::Index(0));
+        assert_eq!(SaveSlot::from_name("..."), SaveSlot::Index(0));
+        assert_eq!(SaveSlot::from_name(" . . "), SaveSlot::Index(0));
+        assert!(SaveSlot::Named("...".to_string()).get_dir().is_err());
+        assert!(delete_save_slot(&SaveSlot::Named(String::new())).is_err());
+        assert_eq!(
+            SaveSlot::from_name("../evil/slot"),
+            SaveSlot::Named("_evil_slot".to_string())
+        );
+        assert_eq!(
+            SaveSlot::from_name("autosave.0"),
+            SaveSlot::Named("autosave_0_".to_string())
+        );
+        assert_eq!(
+            SaveSlot::from_name("quicksave"),
+            SaveSlot::Named("quicksave_".to_string())
+        );
+    }
+
+    #[test]
+    fn reads_header_and_save() {
+        let metadata = SaveMetadata {
+            format_version: SAVE_FORMAT_VERSION,
+            game_version: GAME_VERSION.to_string(),
+            timestamp: 1_700_000_000,
+            play_time: 42.,
+            level: "old_town".to_string(),
+            player_position: Vec3::new(1., 2., 3.),
+        };
+        let save_model = SaveModel {
+            level: "old_town".to_string(),
+            ..default()
+        };
+        let serialized = write_save(&metadata, &save_model, SaveFormat::Ron).unwrap();
+        assert_eq!(read_save_header(&serialized[..]).unwrap(), metadata);
+        assert_eq!(read_save(&serialized).unwrap(), (metadata, save_model));
+    }
+
+    #[test]
+    fn rejects_saves_from_newer_versions() {
+        let metadata = SaveMetadata {
+            format_version: SAVE_FORMAT_VERSION + 1,
+            ..default()
+        };
+        for format in [SaveFormat::Ron, SaveFormat::Binary] {
+            let serialized = write_save(&metadata, &default(), format).unwrap();
+            assert!(read_save(&serialized).is_err());
+            assert!(read_save_header(&serialized[..]).is_err());
+        }
+        assert!(read_save(b"not a save").is_err());
+    }
+
+    #[test]
+    fn binary_saves_match_ron_saves() {
+        let metadata = SaveMetadata {
+            format_version: SAVE_FORMAT_VERSION,
+            game_version: GAME_VERSION.to_string(),
+            timestamp: 1_700_000_000,
+            play_time: 3600.5,
+            level: "old_town".to_string(),
+            player_position: Vec3::new(1., 2., 3.),
+        };
+        let player_transform =
+            Transform::from_xyz(1., 2., 3.).with_rotation(Quat::from_rotation_y(1.));
+        let save_model = SaveModel {
+            level: "old_town".to_string(),
+            conditions: ActiveConditions(
+                [ConditionId("talked_to_villager".to_string())]
+                    .into_iter()
+                    .collect(),
+            ),
+            fired_triggers: FiredTriggers(["old_town/gate".to_string()].into_iter().collect()),
+            last_checkpoint: LastCheckpoint {
+                level: "old_town".to_string(),
+                transform: player_transform,
+            },
+            time_of_day: Some(TimeOfDay {
+                hour: 21.5,
+                frozen: true,
+            }),
+            player_transform,
+            player_health: Some(Health {
+                current: 35.,
+                max: 100.,
+            }),
+            speedrun: Some(SpeedrunTimer {
+                level: "old_town".to_string(),
+                state: RunState::Running,
+                ticks: 4_200,
+                splits: vec![Split {
+                    kind: SplitKind::LevelTransition("old_town".to_string()),
+                    ticks: 1_234,
+                }],
+            }),
+            camera: Some(IngameCameraKind::ThirdPerson(ThirdPersonCamera {
+                distance: 7.5,
+                secondary_target: Some(Vec3::ONE),
+                ..default()
+            })),
+            dialog_event: Some(DialogEvent {
+                dialog: DialogId("follower".to_string()),
+                source: Entity::from_raw(42),
+                page: Some(PageId("greeting".to_string())),
+            }),
+            patrols: [(
+                "market".to_string(),
+                PatrolProgress {
+                    next_waypoint: 2,
+                    backwards: true,
+                    ..default()
+                },
+            )]
+            .into_iter()
+            .collect(),
+            props: [(
+                "gate".to_string(),
+                PropProgress {
+                    openness: 0.5,
+                    opening: true,
+                    ..default()
+                },
+            )]
+            .into_iter()
+            .collect(),
+            spawned: vec![SpawnEvent {
+                object: GameObject::Player,
+                transform: player_transform,
+                id: Some(SpawnId::Runtime(7)),
+                ..default()
+            }],
+            despawned: vec![SpawnId::Level(3)],
+            ..default()
+        };
+
+        let ron = write_save(&metadata, &save_model, SaveFormat::Ron).unwrap();
+        let binary = write_save(&metadata, &save_model, SaveFormat::Binary).unwrap();
+        assert!(binary.starts_with(BINARY_SAVE_MAGIC));
+        let from_ron = read_save(&ron).unwrap();
+        let from_binary = read_save(&binary).unwrap();
+        assert_eq!(from_binary, from_ron);
+        assert_eq!(from_binary, (metadata.clone(), save_model));
+        // The slot list only reads the header
+        assert_eq!(read_save_header(&binary[..]).unwrap(), metadata);
+    }
+
+    #[test]
+    fn migrates_old_saves() {
+        let (metadata, save_model) = read_save(include_bytes!(
+            "../../tests/fixtures/saves/format_1.sav.ron"
+        ))
+        .unwrap();
+        assert_eq!(metadata.format_version, 1);
+        assert_eq!(save_model.level, "old_town");
+        assert!(save_model
+            .conditions
+            .0
+            .contains(&ConditionId("talked_to_villager".to_string())));
+        assert_eq!(
+            save_model.player_transform.translation,
+            Vec3::new(1., 2., 3.)
+        );
+        assert_eq!(save_model.despawned, vec![SpawnId::Level(3)]);
+        assert_eq!(save_model.camera, None);
+    }
+
+    #[test]
+    fn migrates_old_binary_saves() {
+        let (metadata, save_model) = read_save(include_bytes!(
+            "../../tests/fixtures/saves/format_2_binary.sav.ron"
+        ))
+        .unwrap();
+        assert_eq!(metadata.format_version, 2);
+        assert_eq!(save_model.level, "old_town");
+        assert!(save_model
+            .conditions
+            .0
+            .contains(&ConditionId("talked_to_villager".to_string())));
+        assert_eq!(
+            save_model.player_transform.translation,
+            Vec3::new(1., 2., 3.)
+        );
+        assert_eq!(save_model.camera, None);
+    }
+
+    #[test]
+    fn migrations_keep_binary_fields_intact() {
+        let save_model = SaveModel {
+            level: "old_town".to_string(),
+            player_transform: Transform::from_xyz(1., 2., 3.),
+            despawned: vec![SpawnId::Level(3)],
+            ..default()
+        };
+        let body = rmp_serde::to_vec_named(&save_model).unwrap();
+        let mut document = SaveDocument::from_message_pack(&body).unwrap();
+        document.remove_field("camera");
+        document
+            .add_field("camera", &None::<IngameCameraKind>)
+            .unwrap();
+        assert!(document.to_ron().is_err());
+        assert_eq!(document.deserialize::<SaveModel>().unwrap(), save_model);
+    }
+
+    #[test]
+    fn migrates_legacy_saves() {
+        let save_model =
+            deserialize_save_body(include_str!("../../tests/fixtures/saves/legacy.sav.ron"), 1)
+                .unwrap();
+        assert_eq!(save_model.level, "old_town");
+        assert_eq!(save_model.conditions.0.len(), 1);
+    }
+
+    #[test]
+    fn rejects_saves_without_migrations() {
+        let serialized = include_bytes!("../../tests/fixtures/saves/format_0.sav.ron");
+        assert!(read_save(serialized).is_err());
+        assert!(read_save_header(&serialized[..]).is_err());
+    }
+
+    #[test]
+    fn edits_save_document() {
+        let mut document =
+            SaveDocument::parse(r#"(name:"a, (b)",nested:(x:[1,2]),removed:1,)"#).unwrap();
+        document.rename_field("name", "title").unwrap();
+        document.remove_field("removed");
+        document.add_field("added", &Some(3)).unwrap();
+        document.add_field("nested", &0).unwrap();
+        assert!(document.rename_field("title", "nested").is_err());
+        assert_eq!(
+            document.to_ron().unwrap(),
+            r#"(title:"a, (b)",nested:(x:[1,2]),added:Some(3))"#
+        );
+        assert!(SaveDocument::parse("(unbalanced:(1)").is_err());
+    }
+
+    #[test]
+    fn saves_camera_without_its_config() {
+        let mut camera = ThirdPersonCamera {
+            distance: 12.,
+            ..default()
+        };
+        camera.config.camera.mouse_sensitivity_x = 1.;
+        let save_model = SaveModel {
+            camera: Some(IngameCameraKind::ThirdPerson(camera)),
+            ..default()
+        };
+        let metadata = SaveMetadata {
+            format_version: SAVE_FORMAT_VERSION,
+            ..default()
+        };
+        let serialized = write_save(&metadata, &save_model, SaveFormat::Ron).unwrap();
+        assert!(!String::from_utf8(serialized.clone())
+            .unwrap()
+            .contains("mouse_sensitivity"));
+        let (_, save_model) = read_save(&serialized).unwrap();
+        match save_model.camera {
+            Some(IngameCameraKind::ThirdPerson(camera)) => {
+                assert_eq!(camera.distance, 12.);
+                assert_eq!(camera.config, GameConfig::default());
+            }
+            camera => panic!("Unexpected camera {camera:?}"),
+        }
+    }
+
+    #[test]
+    fn saving_and_loading_restores_player_position() {
+        let mut app = build_headless_app();
+        let (player, _camera) = app.spawn_player_on_floor();
+        app.set_movement(Vec2::Y);
+        app.step(60);
+        app.set_movement(Vec2::ZERO);
+        app.step(60);
+        let saved = app.translation(player);
+
+        // Tests share an in-memory storage, see `storage`
+        let slot = SaveSlot::Named("headless test".to_string());
+        app.world.send_event(GameSaveRequest { slot: slot.clone() });
+        app.step(1);
+        // Loading a save normally replaces the level and with it the player
+        app.world.entity_mut(player).despawn_recursive();
+        app.world.send_event(GameLoadRequest { slot: slot.clone() });
+        app.step(4);
+        let loaded = app.player().map(|player| app.translation(player));
+        delete_save_slot(&slot).unwrap();
+
+        let loaded = loaded.expect("player was not spawned from the save");
+        assert!(
+            loaded.distance(saved) < 0.05,
+            "player was saved at {saved} but loaded at {loaded}"
+        );
+    }
 }
@@ -1,16 +1,22 @@
 use crate::file_system_interaction::asset_loading::LevelAssets;
+use crate::level_instantiation::spawning::spawn::{DespawnedObjects, SpawnQueue};
 use crate::level_instantiation::spawning::{
-    GameObject, SpawnEvent, SpawnRequestedLabel, SpawnTracker,
+    GameObject, SpawnEvent, SpawnId, SpawnRequestedLabel, SpawnTracker,
 };
+use crate::player_control::actions::ActionsFrozen;
 use crate::util::log_error::log_errors;
 use crate::world_interaction::condition::ActiveConditions;
 use crate::world_interaction::dialog::CurrentDialog;
+use crate::world_interaction::health::Dying;
 use crate::world_interaction::interactions_ui::InteractionOpportunities;
+use crate::world_interaction::trigger::FiredTriggers;
 use anyhow::{Context, Result};
 use bevy::prelude::*;
 use bevy::reflect::TypeUuid;
+use bevy::utils::{HashMap, HashSet};
 use serde::{Deserialize, Serialize};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
 use std::{fs, iter};
 
 pub struct LevelSerializationPlugin;
@@ -20,9 +26,16 @@ impl Plugin for LevelSerializationPlugin {
         app.add_event::<WorldSaveRequest>()
             .add_event::<WorldLoadRequest>()
             .add_system(save_world.pipe(log_errors).after(SpawnRequestedLabel))
-            .add_system_to_stage(CoreStage::PostUpdate, load_world.pipe(log_errors));
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                load_world.pipe(log_errors).label(LoadWorldLabel),
+            );
     }
 }
+
+#[derive(SystemLabel)]
+pub struct LoadWorldLabel;
+
 #[derive(Debug, Clone, Eq, PartialEq, Reflect, Serialize, Deserialize, Default)]
 #[reflect(Serialize, Deserialize)]
 pub struct WorldSaveRequest {
@@ -33,6 +46,12 @@ pub struct WorldSaveRequest {
 #[reflect(Serialize, Deserialize)]
 pub struct WorldLoadRequest {
     pub filename: String,
+    /// Level objects that are not spawned, e.g. because they were despawned before the game was saved
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub despawned: Vec<SpawnId>,
+    /// Keeps the flags and fired triggers of the previous level, e.g. when walking through a level portal
+    #[serde(default)]
+    pub keep_progress: bool,
 }
 
 #[derive(Debug, Clone, PartialEq, Resource, Reflect, Serialize, Deserialize, Default)]
@@ -97,6 +116,10 @@ fn load_world(
     mut spawn_requests: EventWriter<SpawnEvent>,
     levels: Res<Assets<SerializedLevel>>,
     level_handles: Option<Res<LevelAssets>>,
+    current_dialog: Option<Res<CurrentDialog>>,
+    dying: Option<Res<Dying>>,
+    mut actions_frozen: ResMut<ActionsFrozen>,
+    mut spawn_queue: ResMut<SpawnQueue>,
 ) -> Result<()> {
     let level_handles = match level_handles {
         Some(level_handles) => level_handles,
@@ -104,18 +127,10 @@ fn load_world(
             return Ok(());
         }
     };
+    let mut has_dialog = current_dialog.is_some();
+    let mut is_dying = dying.is_some();
     for load in load_requests.iter() {
-        let path = Path::new("levels")
-            .join(load.filename.clone())
-            .with_extension("lvl.ron")
-            .to_str()
-            .with_context(|| {
-                format!(
-                    "Failed to convert path to string for filename: {}",
-                    load.filename
-                )
-            })?
-            .to_string();
+        let path = get_level_path(&load.filename)?;
         let handle = match level_handles.levels.get(&path) {
             Some(handle) => handle,
             None => {
@@ -127,45 +142,308 @@ fn load_world(
                 continue;
             }
         };
-        let spawn_events = &levels
+        let level = levels
             .get(handle)
-            .context("Failed to get level from handle in level assets")?
-            .0;
+            .context("Failed to get level from handle in level assets")?;
         for entity in &current_spawn_query {
             commands
                 .get_entity(entity)
                 .context("Failed to get entity while loading")?
                 .despawn_recursive();
         }
-        for event in spawn_events {
-            spawn_requests.send(event.clone());
-        }
+        spawn_queue.start_level();
+        spawn_level_objects(level, &load.despawned, &mut spawn_requests);
+        commands.insert_resource(DespawnedObjects(load.despawned.clone()));
         commands.insert_resource(CurrentLevel {
             scene: load.filename.clone(),
         });
         commands.insert_resource(InteractionOpportunities::default());
-        commands.insert_resource(ActiveConditions::default());
-        commands.remove_resource::<CurrentDialog>();
+        if !load.keep_progress {
+            commands.insert_resource(ActiveConditions::default());
+            commands.insert_resource(FiredTriggers::default());
+        }
+        // The dialog froze the actions when it started
+        if has_dialog {
+            commands.remove_resource::<CurrentDialog>();
+            actions_frozen.unfreeze();
+            has_dialog = false;
+        }
+        // Dying froze the actions as well, and the player is replaced by the loaded one
+        if is_dying {
+            commands.remove_resource::<Dying>();
+            actions_frozen.unfreeze();
+            is_dying = false;
+        }
 
         info!("Successfully loaded scene \"{}\"", load.filename,)
     }
     Ok(())
 }
 
+/// Spawns the objects of the level, except for the `despawned` ones
+pub(crate) fn spawn_level_objects(
+    level: &SerializedLevel,
+    despawned: &[SpawnId],
+    spawn_requests: &mut EventWriter<SpawnEvent>,
+) {
+    for (index, event) in level.0.iter().enumerate() {
+        let id = SpawnId::Level(index);
+        if despawned.contains(&id) {
+            continue;
+        }
+        spawn_requests.send(SpawnEvent {
+            id: Some(id),
+            ..event.clone()
+        });
+    }
+}
+
+/// Path of a level file relative to the assets directory, as used as key in [`LevelAssets::levels`]
+pub fn get_level_path(filename: &str) -> Result<String> {
+    Ok(Path::new("levels")
+        .join(filename)
+        .with_extension("lvl.ron")
+        .to_str()
+        .with_context(|| format!("Failed to convert path to string for filename: {filename}"))?
+        .to_string())
+}
+
+/// Writes the level to `assets/levels/<filename>.lvl.ron`, replacing the file if it exists.
+/// An existing file that cannot be read as a level is never overwritten. It is moved to a timestamped backup next to it instead,
+/// so that hand edits in progress are not lost.
+pub fn write_level_file(filename: &str, level: &SerializedLevel) -> Result<PathBuf> {
+    let path = Path::new("assets").join(get_level_path(filename)?);
+    if let Ok(existing) = fs::read_to_string(&path) {
+        if let Err(error) = ron::from_str::<SerializedLevel>(&existing) {
+            let timestamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .context("Failed to get the current time")?
+                .as_secs();
+            let backup = path.with_extension(format!("ron.{timestamp}.bak"));
+            fs::rename(&path, &backup).with_context(|| {
+                format!(
+                    "Failed to back up unreadable level file {}",
+                    path.to_string_lossy()
+                )
+            })?;
+            warn!(
+                "Level file {} could not be read ({error}), moved it to {}",
+                path.to_string_lossy(),
+                backup.to_string_lossy()
+            );
+        }
+    }
+    let serialized_level = serialize_level(level)?;
+    let dir = path.parent().context("Failed to get level directory")?;
+    fs::create_dir_all(dir).context("Failed to create level directory")?;
+    // Written next to the file first so that a failed write does not leave half a level behind
+    let temporary_path = path.with_extension("ron.tmp");
+    fs::write(&temporary_path, serialized_level).context("Failed to write level file")?;
+    fs::rename(&temporary_path, &path).context("Failed to replace level file")?;
+    Ok(path)
+}
+
+/// Applies changes made while playing to the objects of a level file.
+/// Level objects that were not `changed` are kept as they are in the file, even if they moved or were despawned since,
+/// e.g. NPCs walking around or items picked up. Changed objects are stored as they are `now`, or removed if they no longer exist.
+/// Changed runtime spawns are appended in the order of their ids, so that saving twice produces the same file.
+pub fn merge_level_edits(
+    original: &SerializedLevel,
+    changed: &HashSet<SpawnId>,
+    now: &HashMap<SpawnId, SpawnEvent>,
+) -> SerializedLevel {
+    let level_objects = original.0.iter().enumerate().filter_map(|(index, event)| {
+        let id = SpawnId::Level(index);
+        if changed.contains(&id) {
+            now.get(&id).cloned()
+        } else {
+            Some(event.clone())
+        }
+    });
+    let mut runtime_ids: Vec<_> = changed
+        .iter()
+        .filter_map(|id| match id {
+            SpawnId::Runtime(index) => Some(*index),
+            SpawnId::Level(_) => None,
+        })
+        .collect();
+    runtime_ids.sort_unstable();
+    let runtime_objects = runtime_ids
+        .into_iter()
+        .filter_map(|index| now.get(&SpawnId::Runtime(index)).cloned());
+    SerializedLevel(
+        level_objects
+            .chain(runtime_objects)
+            // Ids are assigned from the position in the file when loading
+            .map(|event| SpawnEvent { id: None, ..event })
+            .collect(),
+    )
+}
+
+pub fn serialize_level(level: &SerializedLevel) -> Result<String> {
+    ron::ser::to_string_pretty(level, default()).context("Failed to serialize level")
+}
+
 fn serialize_world(spawn_query: &Query<(&SpawnTracker, Option<&Transform>)>) -> Result<String> {
     let objects: Vec<_> = spawn_query
         .iter()
         .filter(|(spawn_tracker, _)| !matches!(spawn_tracker.object, GameObject::Player))
-        .map(|(spawn_tracker, transform)| SpawnEvent {
-            object: spawn_tracker.object,
-            transform: transform.map(Clone::clone).unwrap_or_default(),
+        // The whole transform is stored, so rotation and scale set in the editor are kept
+        .map(|(spawn_tracker, transform)| {
+            spawn_tracker.to_spawn_event(None, transform.copied().unwrap_or_default())
         })
         .collect();
-    let serialized_level = SerializedLevel(objects);
-    ron::ser::to_string_pretty(&serialized_level, default()).context("Failed to serialize world")
+    serialize_level(&SerializedLevel(objects))
 }
 
 #[derive(Debug, Clone, PartialEq, Reflect, Serialize, Deserialize, TypeUuid)]
 #[uuid = "eb7cc7bc-5a97-41ed-b0c3-0d4e2137b73b"]
 #[reflect(Serialize, Deserialize)]
 pub struct SerializedLevel(pub Vec<SpawnEvent>);
+
+/// The levels that can be started from the main menu, read from `config/levels.manifest.ron`.
+/// The first level is started when the player just presses "Play".
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TypeUuid)]
+#[uuid = "5d0c4a43-7b8e-4f2a-9c61-2e8f1b3a6d94"]
+pub struct LevelManifest {
+    pub levels: Vec<LevelInfo>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LevelInfo {
+    /// Stable identifier of the level, used by [`SelectedLevel`]
+    pub id: String,
+    /// Shown in the level selection
+    pub name: String,
+    /// Name of the level file without extension, e.g. "old_town", as used by [`WorldLoadRequest::filename`]
+    pub level: String,
+    /// Where the player is spawned when the level is started from the menu
+    pub spawn_point: Vec3,
+}
+
+impl LevelManifest {
+    pub fn get(&self, id: &str) -> Option<&LevelInfo> {
+        self.levels.iter().find(|level| level.id == id)
+    }
+}
+
+impl LevelInfo {
+    /// Describes why the level cannot be started, e.g. because its level file is missing
+    pub fn get_error(&self, level_handles: &LevelAssets) -> Option<String> {
+        match get_level_path(&self.level) {
+            Ok(path) if level_handles.levels.contains_key(&path) => None,
+            Ok(path) => Some(format!("The level file \"{path}\" does not exist")),
+            Err(e) => Some(format!("{e:#}")),
+        }
+    }
+}
+
+/// Id of the [`LevelInfo`] to start when entering [`GameState::Playing`](crate::GameState::Playing) without loading a save.
+/// Falls back to the first level of the [`LevelManifest`] when missing.
+#[derive(Debug, Clone, PartialEq, Eq, Resource)]
+pub struct SelectedLevel(pub String);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::level_instantiation::spawning::DespawnRequest;
+    use crate::util::headless::build_headless_app;
+
+    #[test]
+    fn edited_level_survives_save_and_load() {
+        let mut app = build_headless_app();
+        let original = SerializedLevel(
+            (0..3)
+                .map(|x| SpawnEvent {
+                    object: GameObject::Box,
+                    transform: Transform::from_xyz(x as f32, 0., 0.),
+                    ..default()
+                })
+                .collect(),
+        );
+        for (index, event) in original.0.iter().enumerate() {
+            app.world.send_event(SpawnEvent {
+                id: Some(SpawnId::Level(index)),
+                ..event.clone()
+            });
+        }
+        app.update();
+
+        // Place a new box, move the second one and delete the first one
+        app.world.send_event(SpawnEvent {
+            object: GameObject::Box,
+            transform: Transform::from_xyz(0., 5., 0.),
+            id: Some(SpawnId::Runtime(0)),
+            ..default()
+        });
+        let moved = Transform::from_xyz(1., 0., 3.).with_scale(Vec3::splat(2.));
+        *get_transform_mut(&mut app, SpawnId::Level(1)) = moved;
+        let first = find_entity(&mut app, SpawnId::Level(0)).unwrap();
+        app.world.send_event(DespawnRequest::Entity(first));
+        app.update();
+
+        let changed = [SpawnId::Level(0), SpawnId::Level(1), SpawnId::Runtime(0)]
+            .into_iter()
+            .collect();
+        let now = app
+            .world
+            .query::<(&SpawnTracker, &SpawnId, &Transform)>()
+            .iter(&app.world)
+            .map(|(spawn_tracker, id, transform)| {
+                (*id, spawn_tracker.to_spawn_event(Some(*id), *transform))
+            })
+            .collect();
+        let saved = merge_level_edits(&original, &changed, &now);
+        let serialized = serialize_level(&saved).unwrap();
+        assert_eq!(
+            serialize_level(&merge_level_edits(&original, &changed, &now)).unwrap(),
+            serialized,
+            "saving is not stable"
+        );
+
+        let entities: Vec<_> = app
+            .world
+            .query_filtered::<Entity, With<SpawnTracker>>()
+            .iter(&app.world)
+            .collect();
+        for entity in entities {
+            app.world.despawn(entity);
+        }
+        let loaded: SerializedLevel = ron::from_str(&serialized).unwrap();
+        for (index, event) in loaded.0.iter().enumerate() {
+            app.world.send_event(SpawnEvent {
+                id: Some(SpawnId::Level(index)),
+                ..event.clone()
+            });
+        }
+        app.update();
+
+        let mut translations: Vec<_> = app
+            .world
+            .query::<(&SpawnTracker, &Transform)>()
+            .iter(&app.world)
+            .map(|(spawn_tracker, transform)| {
+                assert_eq!(spawn_tracker.object, GameObject::Box);
+                (transform.translation.to_array(), transform.scale.x)
+            })
+            .collect();
+        translations.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(
+            translations,
+            vec![([0., 5., 0.], 1.), ([1., 0., 3.], 2.), ([2., 0., 0.], 1.),]
+        );
+    }
+
+    fn find_entity(app: &mut App, id: SpawnId) -> Option<Entity> {
+        app.world
+            .query::<(Entity, &SpawnId)>()
+            .iter(&app.world)
+            .find(|(_entity, spawn_id)| **spawn_id == id)
+            .map(|(entity, _id)| entity)
+    }
+
+    fn get_transform_mut(app: &mut App, id: SpawnId) -> Mut<Transform> {
+        let entity = find_entity(app, id).unwrap();
+        app.world.get_mut::<Transform>(entity).unwrap()
+    }
+}
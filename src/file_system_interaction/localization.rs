@@ -0,0 +1,305 @@
+use crate::file_system_interaction::asset_loading::{ConfigAssets, EguiFont, LocaleAssets};
+use crate::file_system_interaction::config::GameConfig;
+use crate::util::log_error::log_errors;
+use crate::GameState;
+use anyhow::{bail, Context, Result};
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use bevy::utils::{HashMap, HashSet};
+use bevy_egui::{egui, EguiContext};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::Mutex;
+
+/// Provides the strings shown to the player in their language through the [`Localization`] resource.
+/// Strings are read from the `*.locale.ron` files in `assets/locales/<language>/`, which map keys to text.
+/// Keys missing in the current language fall back to English, and keys missing there are shown as they are.
+/// The language can be changed at runtime. Since all UI is redrawn every frame, visible text updates immediately.
+/// Languages can use their own font, see [`config::Language`](crate::file_system_interaction::config::Language).
+pub struct LocalizationPlugin;
+
+impl Plugin for LocalizationPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(
+            SystemSet::on_exit(GameState::Loading).with_system(init_localization.pipe(log_errors)),
+        )
+        .add_system(apply_language_font.pipe(log_errors));
+    }
+}
+
+/// Language used when a key is missing in the current language
+pub const FALLBACK_LANGUAGE: &str = "en";
+
+/// Contents of a `*.locale.ron` file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TypeUuid, Default)]
+#[uuid = "a8e4b8c5-5b52-4c4e-9f0a-1d5f3c6c2e71"]
+pub struct LocaleStrings(pub HashMap<String, String>);
+
+#[derive(Debug, Resource, Default)]
+pub struct Localization {
+    language: String,
+    /// Strings by key, by language
+    locales: HashMap<String, HashMap<String, String>>,
+    /// Keys that were already reported as missing, so that we only warn about them once
+    logged_missing_keys: Mutex<HashSet<String>>,
+}
+
+impl Localization {
+    pub fn language(&self) -> &str {
+        &self.language
+    }
+
+    /// All languages with at least one locale file, sorted by name
+    pub fn languages(&self) -> Vec<&str> {
+        let mut languages: Vec<_> = self.locales.keys().map(String::as_str).collect();
+        languages.sort_unstable();
+        languages
+    }
+
+    pub fn set_language(&mut self, language: impl Into<String>) -> Result<()> {
+        let language = language.into();
+        if !self.locales.contains_key(&language) {
+            bail!(
+                "Failed to set language to \"{language}\": No such language. Available languages: {:?}",
+                self.languages()
+            );
+        }
+        self.language = language;
+        self.logged_missing_keys
+            .lock()
+            .expect("Failed to lock missing localization keys")
+            .clear();
+        Ok(())
+    }
+
+    /// Text for `key` in the current language
+    pub fn get<'a>(&'a self, key: &'a str) -> &'a str {
+        if let Some(text) = self.get_in(&self.language, key) {
+            return text;
+        }
+        let fallback = self.get_in(FALLBACK_LANGUAGE, key);
+        let is_first_miss = self
+            .logged_missing_keys
+            .lock()
+            .expect("Failed to lock missing localization keys")
+            .insert(key.to_string());
+        if is_first_miss {
+            if fallback.is_some() {
+                warn!(
+                    "Localization key \"{key}\" is missing in language \"{}\", falling back to \"{FALLBACK_LANGUAGE}\"",
+                    self.language
+                );
+            } else {
+                warn!("Localization key \"{key}\" is missing in all languages");
+            }
+        }
+        fallback.unwrap_or(key)
+    }
+
+    /// Text for `key` in the given language, without any fallback
+    pub fn get_in(&self, language: &str, key: &str) -> Option<&str> {
+        self.locales
+            .get(language)
+            .and_then(|strings| strings.get(key))
+            .map(String::as_str)
+    }
+}
+
+fn init_localization(
+    mut commands: Commands,
+    locale_assets: Option<Res<LocaleAssets>>,
+    locale_strings: Res<Assets<LocaleStrings>>,
+    config_handles: Option<Res<ConfigAssets>>,
+    config: Res<Assets<GameConfig>>,
+) -> Result<()> {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("init_localization").entered();
+    // Missing when loading failed
+    let (locale_assets, config_handles) = match (locale_assets, config_handles) {
+        (Some(locale_assets), Some(config_handles)) => (locale_assets, config_handles),
+        _ => return Ok(()),
+    };
+    let config = config
+        .get(&config_handles.game)
+        .context("Failed to get game config from handle")?;
+    let mut locales: HashMap<String, HashMap<String, String>> = default();
+    for (path, handle) in locale_assets.locales.iter() {
+        let language = get_language_of_locale_file(path)
+            .with_context(|| format!("Failed to get language of locale file {path}"))?;
+        let strings = locale_strings
+            .get(handle)
+            .with_context(|| format!("Failed to get locale file {path} from handle"))?;
+        let language_strings = locales.entry(language).or_default();
+        for (key, text) in strings.0.iter() {
+            if language_strings.insert(key.clone(), text.clone()).is_some() {
+                warn!(
+                    "Localization key \"{key}\" is defined more than once, using the one in {path}"
+                );
+            }
+        }
+    }
+    let mut localization = Localization {
+        language: FALLBACK_LANGUAGE.to_string(),
+        locales,
+        logged_missing_keys: default(),
+    };
+    if let Err(e) = localization.set_language(config.language.current.clone()) {
+        error!("{e:?}");
+    }
+    commands.insert_resource(localization);
+    Ok(())
+}
+
+/// Gets e.g. `"en"` from `"locales/en/ui.locale.ron"`
+fn get_language_of_locale_file(path: &str) -> Option<String> {
+    let path = Path::new(path);
+    let folder = path.parent()?;
+    if folder.parent()?.file_name()? != "locales" {
+        return None;
+    }
+    Some(folder.file_name()?.to_str()?.to_string())
+}
+
+fn apply_language_font(
+    localization: Option<Res<Localization>>,
+    mut pending_font: Local<Option<Handle<EguiFont>>>,
+    fonts: Res<Assets<EguiFont>>,
+    asset_server: Res<AssetServer>,
+    mut egui_context: ResMut<EguiContext>,
+    config_handles: Option<Res<ConfigAssets>>,
+    config: Res<Assets<GameConfig>>,
+) -> Result<()> {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("apply_language_font").entered();
+    let (localization, config_handles) = match (localization, config_handles) {
+        (Some(localization), Some(config_handles)) => (localization, config_handles),
+        _ => return Ok(()),
+    };
+    if localization.is_changed() {
+        let config = config
+            .get(&config_handles.game)
+            .context("Failed to get game config from handle")?;
+        match config.language.fonts.get(localization.language()) {
+            Some(path) => *pending_font = Some(asset_server.load(path.as_str())),
+            None => {
+                *pending_font = None;
+                egui_context
+                    .ctx_mut()
+                    .set_fonts(egui::FontDefinitions::default());
+            }
+        }
+    }
+    let font = match pending_font.as_ref().and_then(|handle| fonts.get(handle)) {
+        Some(font) => font,
+        None => return Ok(()),
+    };
+    let mut font_definitions = egui::FontDefinitions::default();
+    const FONT_NAME: &str = "language";
+    font_definitions.font_data.insert(
+        FONT_NAME.to_string(),
+        egui::FontData::from_owned(font.0.clone()),
+    );
+    for family in [egui::FontFamily::Proportional, egui::FontFamily::Monospace] {
+        font_definitions
+            .families
+            .entry(family)
+            .or_default()
+            .insert(0, FONT_NAME.to_string());
+    }
+    egui_context.ctx_mut().set_fonts(font_definitions);
+    *pending_font = None;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::world_interaction::dialog::{Dialog, NextPage};
+    use glob::glob;
+    use std::fs;
+
+    fn read_locale(language: &str) -> HashMap<String, String> {
+        let pattern = format!(
+            "{}/assets/locales/{language}/*.locale.ron",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        glob(&pattern)
+            .unwrap()
+            .flat_map(|path| {
+                let path = path.unwrap();
+                let strings: LocaleStrings = ron::from_str(&fs::read_to_string(&path).unwrap())
+                    .unwrap_or_else(|e| panic!("Failed to parse {path:?}: {e}"));
+                strings.0
+            })
+            .collect()
+    }
+
+    #[test]
+    fn bundled_dialogs_are_localized_in_english() {
+        let english = read_locale(FALLBACK_LANGUAGE);
+        let pattern = format!("{}/assets/dialogs/*.dlg.ron", env!("CARGO_MANIFEST_DIR"));
+        for path in glob(&pattern).unwrap() {
+            let path = path.unwrap();
+            let dialog: Dialog = ron::from_str(&fs::read_to_string(&path).unwrap())
+                .unwrap_or_else(|e| panic!("Failed to parse {path:?}: {e}"));
+            for page in dialog.pages.values() {
+                assert!(
+                    english.contains_key(&page.text),
+                    "Key \"{}\" used in {path:?} is missing in the English locale",
+                    page.text
+                );
+                if let NextPage::Choice(choices) = &page.next_page {
+                    for choice in choices.values() {
+                        assert!(
+                            english.contains_key(&choice.text),
+                            "Key \"{}\" used in {path:?} is missing in the English locale",
+                            choice.text
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn falls_back_to_english() {
+        let mut localization = Localization {
+            language: FALLBACK_LANGUAGE.to_string(),
+            locales: [
+                (
+                    "en".to_string(),
+                    [
+                        ("menu.play".to_string(), "Play".to_string()),
+                        ("menu.quit".to_string(), "Quit".to_string()),
+                    ]
+                    .into_iter()
+                    .collect(),
+                ),
+                (
+                    "de".to_string(),
+                    [("menu.play".to_string(), "Spielen".to_string())]
+                        .into_iter()
+                        .collect(),
+                ),
+            ]
+            .into_iter()
+            .collect(),
+            logged_missing_keys: default(),
+        };
+        localization.set_language("de").unwrap();
+        assert_eq!(localization.get("menu.play"), "Spielen");
+        assert_eq!(localization.get("menu.quit"), "Quit");
+        assert_eq!(localization.get("menu.unknown"), "menu.unknown");
+        assert!(localization.set_language("fr").is_err());
+        assert_eq!(localization.language(), "de");
+    }
+
+    #[test]
+    fn reads_language_from_path() {
+        assert_eq!(
+            get_language_of_locale_file("locales/en/ui.locale.ron"),
+            Some("en".to_string())
+        );
+        assert_eq!(get_language_of_locale_file("levels/old_town.lvl.ron"), None);
+    }
+}
@@ -0,0 +1,103 @@
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use serde::{Deserialize, Serialize};
+
+/// Emitter parameters of a particle effect, loaded from `assets/particles/<name>.particle.ron`.
+/// The particle system turns these into the actual effects when they are spawned,
+/// so that editing a file changes every instance spawned afterwards.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TypeUuid)]
+#[uuid = "8d4f3a2e-61b7-4c0e-9f5a-2b7e1c9d3a64"]
+pub struct ParticleEffectDefinition {
+    /// Maximum number of particles alive at the same time
+    pub capacity: u32,
+    pub spawn: ParticleSpawn,
+    /// Whether the effect starts emitting as soon as it is spawned.
+    /// Effects that are turned on and off by code, like the sprinting dust, start inactive.
+    #[serde(default = "default_true")]
+    pub active: bool,
+    /// Seconds every particle lives
+    pub lifetime: f32,
+    /// Where particles appear and in which direction they move
+    pub shape: ParticleShape,
+    /// Initial speed in m/s along the direction given by the [`ParticleShape`]
+    pub speed: ParticleValue,
+    /// Constant acceleration in m/s² applied to every particle
+    #[serde(default)]
+    pub acceleration: Vec3,
+    /// Linear drag slowing the particles down over time
+    #[serde(default)]
+    pub drag: f32,
+    /// RGBA keys of the color over the normalized lifetime, HDR values above 1 are allowed
+    pub color_over_lifetime: Vec<(f32, Vec4)>,
+    /// Keys of the billboard size in m over the normalized lifetime
+    pub size_over_lifetime: Vec<(f32, f32)>,
+    /// Path of the particle texture relative to `assets`. Particles are plain quads without one.
+    #[serde(default)]
+    pub texture: Option<String>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// How many particles are emitted and when
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ParticleSpawn {
+    /// Continuously emits this many particles per second
+    Rate(ParticleValue),
+    /// Emits this many particles once, right when spawned
+    Once(ParticleValue),
+    /// Emits `count` particles every `period` seconds
+    Burst {
+        count: ParticleValue,
+        period: ParticleValue,
+    },
+}
+
+impl ParticleSpawn {
+    /// Whether the effect stops emitting by itself
+    pub fn is_finite(&self) -> bool {
+        matches!(self, ParticleSpawn::Once(_))
+    }
+}
+
+/// Area from which particles are emitted. Particles move away from its center.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ParticleShape {
+    /// A horizontal circle or disc of the given radius in m
+    Circle { radius: f32, filled: bool },
+    /// A sphere or ball of the given radius in m
+    Sphere { radius: f32, filled: bool },
+}
+
+/// A number that is either fixed or randomly picked per particle or burst
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ParticleValue {
+    Constant(f32),
+    /// Uniformly distributed in [min, max]
+    Random(f32, f32),
+}
+
+impl Default for ParticleValue {
+    fn default() -> Self {
+        Self::Constant(0.)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn shipped_effects_parse() {
+        for source in [
+            include_str!("../../assets/particles/sprint.particle.ron"),
+            include_str!("../../assets/particles/slide.particle.ron"),
+            include_str!("../../assets/particles/landing.particle.ron"),
+            include_str!("../../assets/particles/hard_landing.particle.ron"),
+        ] {
+            let definition: ParticleEffectDefinition = ron::from_str(source).unwrap();
+            assert!(definition.lifetime > 0.);
+        }
+    }
+}
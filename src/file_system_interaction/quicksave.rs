@@ -0,0 +1,117 @@
+use crate::file_system_interaction::asset_loading::ConfigAssets;
+use crate::file_system_interaction::config::GameConfig;
+use crate::file_system_interaction::game_state_serialization::{
+    verify_slot, write_rotated_slot, GameLoadRequest, SaveSlot, SaveSnapshot,
+};
+use crate::file_system_interaction::localization::Localization;
+use crate::file_system_interaction::storage::storage;
+use crate::file_system_interaction::thumbnail::ThumbnailRequest;
+use crate::level_instantiation::level_transition::LevelTransition;
+use crate::player_control::actions::PlayerAction;
+use crate::toast::Toast;
+use crate::util::log_error::log_errors;
+use crate::world_interaction::checkpoint::Respawning;
+use crate::world_interaction::health::Dying;
+use crate::GameState;
+use anyhow::{Context, Result};
+use bevy::prelude::*;
+use leafwing_input_manager::prelude::ActionState;
+
+/// Saves the game with [`PlayerAction::QuickSave`] and loads it again with [`PlayerAction::QuickLoad`], without any menu.
+/// The last [`QUICKSAVE_HISTORY`] quicksaves are kept in [`SaveSlot::Quicksave`] slots, so that a bad one does not destroy the player's progress.
+/// Both are unavailable while the player is dying, respawning or moving to another level.
+pub struct QuicksavePlugin;
+
+impl Plugin for QuicksavePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(
+            SystemSet::on_update(GameState::Playing)
+                .with_system(handle_quick_actions.pipe(log_errors)),
+        );
+    }
+}
+
+pub const QUICKSAVE_HISTORY: u32 = 3;
+
+fn handle_quick_actions(
+    actions: Query<&ActionState<PlayerAction>>,
+    snapshot: SaveSnapshot,
+    level_transition: Option<Res<LevelTransition>>,
+    respawning: Option<Res<Respawning>>,
+    dying: Option<Res<Dying>>,
+    mut load_requests: EventWriter<GameLoadRequest>,
+    mut toasts: EventWriter<Toast>,
+    mut thumbnail_requests: EventWriter<ThumbnailRequest>,
+    localization: Res<Localization>,
+    config_handles: Res<ConfigAssets>,
+    config: Res<Assets<GameConfig>>,
+) -> Result<()> {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("handle_quick_actions").entered();
+    // The player actions only exist while a level is loaded
+    let actions = match actions.iter().next() {
+        Some(actions) => actions,
+        None => return Ok(()),
+    };
+    let wants_to_save = actions.just_pressed(PlayerAction::QuickSave);
+    let wants_to_load = actions.just_pressed(PlayerAction::QuickLoad);
+    if !wants_to_save && !wants_to_load {
+        return Ok(());
+    }
+    if level_transition.is_some() || respawning.is_some() || dying.is_some() {
+        return Ok(());
+    }
+    let mut toast = |key: &str| {
+        toasts.send(Toast {
+            text: localization.get(key).to_string(),
+        })
+    };
+
+    if wants_to_save {
+        let config = config
+            .get(&config_handles.game)
+            .context("Failed to get game config from handle")?;
+        let result = snapshot
+            .serialize(config.save.format)
+            .and_then(|serialized| {
+                let serialized = serialized.context("There is no game to save")?;
+                write_rotated_slot(&serialized, QUICKSAVE_HISTORY, SaveSlot::Quicksave)
+            });
+        match result {
+            Ok(()) => {
+                info!("Successfully quick saved game");
+                thumbnail_requests.send(ThumbnailRequest {
+                    slot: SaveSlot::Quicksave(0),
+                });
+                toast("toast.quick_saved");
+            }
+            Err(e) => {
+                error!("Failed to quick save game: {e:#}");
+                toast("toast.quick_save_failed");
+            }
+        }
+    } else {
+        let slot = (0..QUICKSAVE_HISTORY)
+            .map(SaveSlot::Quicksave)
+            .filter(|slot| {
+                slot.get_path()
+                    .map(|path| storage().is_file(&path))
+                    .unwrap_or_default()
+            })
+            .find(|slot| match verify_slot(slot) {
+                Ok(()) => true,
+                Err(e) => {
+                    warn!("Skipping unreadable quicksave {slot}: {e:#}");
+                    false
+                }
+            });
+        match slot {
+            Some(slot) => {
+                load_requests.send(GameLoadRequest { slot });
+                toast("toast.quick_loaded");
+            }
+            None => toast("toast.no_quicksave"),
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,427 @@
+use anyhow::{bail, Context, Result};
+#[cfg(test)]
+use std::collections::BTreeMap;
+use std::path::{Component, Path, PathBuf};
+#[cfg(test)]
+use std::sync::Mutex;
+
+/// Where the game keeps the files it writes while running, i.e. saves, their thumbnails and the user settings.
+/// On native these are regular files. The web has no file system, so there they are kept in the browser's local storage
+/// by a [`KeyValueStorage`]. Use [`storage`] to get the one for the current platform.
+/// Directories are created as needed when writing a file and removed together with everything in them.
+pub trait Storage: Send + Sync {
+    fn read(&self, path: &Path) -> Result<Vec<u8>>;
+    /// Creates or overwrites the file, including missing parent directories
+    fn write(&self, path: &Path, contents: &[u8]) -> Result<()>;
+    /// Whether there is a file or a directory at the path
+    fn exists(&self, path: &Path) -> bool;
+    fn is_file(&self, path: &Path) -> bool;
+    /// Removes a file or a directory with everything in it
+    fn remove(&self, path: &Path) -> Result<()>;
+    /// Moves a file or a directory with everything in it, replacing what is at `to`
+    fn rename(&self, from: &Path, to: &Path) -> Result<()>;
+    /// The files and directories directly inside `dir`, none if it does not exist
+    fn list(&self, dir: &Path) -> Result<Vec<PathBuf>>;
+}
+
+/// Tests get a [`MemoryStore`] shared by the whole process instead, so that they never touch the player's files.
+pub fn storage() -> &'static dyn Storage {
+    #[cfg(test)]
+    {
+        static TEST_STORAGE: KeyValueStorage<MemoryStore> =
+            KeyValueStorage(MemoryStore(Mutex::new(BTreeMap::new())));
+        &TEST_STORAGE
+    }
+    #[cfg(all(not(test), not(feature = "wasm")))]
+    {
+        &NativeStorage
+    }
+    #[cfg(all(not(test), feature = "wasm"))]
+    {
+        static LOCAL_STORAGE: KeyValueStorage<web::LocalStorage> =
+            KeyValueStorage(web::LocalStorage);
+        &LOCAL_STORAGE
+    }
+}
+
+/// Directory in which the game keeps its data, e.g. saves.
+/// Relative where there is no data directory, e.g. on the web, and in tests, whose [`storage`] only takes relative paths.
+pub fn get_data_dir() -> PathBuf {
+    if cfg!(test) {
+        return PathBuf::new();
+    }
+    dirs::data_dir()
+        .map(|dir| dir.join("Foxtrot"))
+        .unwrap_or_default()
+}
+
+/// Regular files through [`std::fs`]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NativeStorage;
+
+impl Storage for NativeStorage {
+    fn read(&self, path: &Path) -> Result<Vec<u8>> {
+        std::fs::read(path).with_context(|| format!("Failed to read {}", path.to_string_lossy()))
+    }
+
+    fn write(&self, path: &Path, contents: &[u8]) -> Result<()> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create {}", dir.to_string_lossy()))?;
+        }
+        std::fs::write(path, contents)
+            .with_context(|| format!("Failed to write {}", path.to_string_lossy()))
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        path.exists()
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        path.is_file()
+    }
+
+    fn remove(&self, path: &Path) -> Result<()> {
+        if path.is_dir() {
+            std::fs::remove_dir_all(path)
+        } else {
+            std::fs::remove_file(path)
+        }
+        .with_context(|| format!("Failed to remove {}", path.to_string_lossy()))
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        if to.exists() {
+            self.remove(to)?;
+        }
+        std::fs::rename(from, to).with_context(|| {
+            format!(
+                "Failed to move {} to {}",
+                from.to_string_lossy(),
+                to.to_string_lossy()
+            )
+        })
+    }
+
+    fn list(&self, dir: &Path) -> Result<Vec<PathBuf>> {
+        let entries = match std::fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to list {}", dir.to_string_lossy()))
+            }
+        };
+        entries
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<_, _>>()
+            .with_context(|| format!("Failed to list {}", dir.to_string_lossy()))
+    }
+}
+
+/// A store of strings by key, such as the browser's local storage
+pub trait KeyValueStore {
+    fn get(&self, key: &str) -> Result<Option<String>>;
+    fn set(&self, key: &str, value: &str) -> Result<()>;
+    fn remove(&self, key: &str) -> Result<()>;
+    fn keys(&self) -> Result<Vec<String>>;
+}
+
+/// Keeps everything in memory, for tests
+#[cfg(test)]
+#[derive(Debug, Default)]
+pub struct MemoryStore(Mutex<BTreeMap<String, String>>);
+
+#[cfg(test)]
+impl KeyValueStore for MemoryStore {
+    fn get(&self, key: &str) -> Result<Option<String>> {
+        Ok(self.0.lock().unwrap().get(key).cloned())
+    }
+
+    fn set(&self, key: &str, value: &str) -> Result<()> {
+        self.0
+            .lock()
+            .unwrap()
+            .insert(key.to_string(), value.to_string());
+        Ok(())
+    }
+
+    fn remove(&self, key: &str) -> Result<()> {
+        self.0.lock().unwrap().remove(key);
+        Ok(())
+    }
+
+    fn keys(&self) -> Result<Vec<String>> {
+        Ok(self.0.lock().unwrap().keys().cloned().collect())
+    }
+}
+
+/// Emulates files on top of a [`KeyValueStore`]. Every file is stored under its path with `/` as separator
+/// and its contents encoded as base64. Directories are not stored at all, they exist as long as there are files in them.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct KeyValueStorage<T>(pub T);
+
+/// Keeps the keys of the game apart from those of other pages on the same domain
+const KEY_PREFIX: &str = "foxtrot/";
+
+impl<T: KeyValueStore> KeyValueStorage<T> {
+    /// Keys of the file at the path or of all files in the directory at the path
+    fn keys_at(&self, path: &Path) -> Result<Vec<String>> {
+        let key = to_key(path)?;
+        let dir_prefix = format!("{key}/");
+        Ok(self
+            .0
+            .keys()?
+            .into_iter()
+            .filter(|existing| *existing == key || existing.starts_with(&dir_prefix))
+            .collect())
+    }
+}
+
+impl<T: KeyValueStore + Send + Sync> Storage for KeyValueStorage<T> {
+    fn read(&self, path: &Path) -> Result<Vec<u8>> {
+        let value = self
+            .0
+            .get(&to_key(path)?)?
+            .with_context(|| format!("Failed to read {}: No such file", path.to_string_lossy()))?;
+        base64::decode(value)
+            .with_context(|| format!("Failed to decode {}", path.to_string_lossy()))
+    }
+
+    fn write(&self, path: &Path, contents: &[u8]) -> Result<()> {
+        self.0
+            .set(&to_key(path)?, &base64::encode(contents))
+            .with_context(|| format!("Failed to write {}", path.to_string_lossy()))
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.keys_at(path)
+            .map(|keys| !keys.is_empty())
+            .unwrap_or_default()
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        to_key(path)
+            .and_then(|key| self.0.get(&key))
+            .map(|value| value.is_some())
+            .unwrap_or_default()
+    }
+
+    fn remove(&self, path: &Path) -> Result<()> {
+        let keys = self.keys_at(path)?;
+        if keys.is_empty() {
+            bail!("Failed to remove {}: No such file", path.to_string_lossy());
+        }
+        for key in keys {
+            self.0.remove(&key)?;
+        }
+        Ok(())
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        let from_key = to_key(from)?;
+        let new_key_prefix = to_key(to)?;
+        let keys = self.keys_at(from)?;
+        if keys.is_empty() {
+            bail!("Failed to move {}: No such file", from.to_string_lossy());
+        }
+        if from_key == new_key_prefix {
+            return Ok(());
+        }
+        if self.exists(to) {
+            self.remove(to)?;
+        }
+        for key in keys {
+            let value = self
+                .0
+                .get(&key)?
+                .with_context(|| format!("Failed to move {key}: It disappeared"))?;
+            let new_key = format!("{new_key_prefix}{}", &key[from_key.len()..]);
+            self.0.set(&new_key, &value)?;
+            self.0.remove(&key)?;
+        }
+        Ok(())
+    }
+
+    fn list(&self, dir: &Path) -> Result<Vec<PathBuf>> {
+        let dir_prefix = format!("{}/", to_key(dir)?);
+        let mut names: Vec<_> = self
+            .0
+            .keys()?
+            .iter()
+            .filter_map(|key| key.strip_prefix(&dir_prefix))
+            .filter_map(|rest| rest.split('/').next())
+            .map(str::to_string)
+            .collect();
+        names.sort();
+        names.dedup();
+        Ok(names.into_iter().map(|name| dir.join(name)).collect())
+    }
+}
+
+fn to_key(path: &Path) -> Result<String> {
+    let mut key = KEY_PREFIX.to_string();
+    for component in path.components() {
+        match component {
+            Component::Normal(name) => {
+                if !key.ends_with('/') {
+                    key.push('/');
+                }
+                key.push_str(&name.to_string_lossy());
+            }
+            Component::CurDir => {}
+            _ => bail!(
+                "Failed to access {}: Only relative paths without \"..\" are supported",
+                path.to_string_lossy()
+            ),
+        }
+    }
+    if key == KEY_PREFIX {
+        bail!("Failed to access an empty path");
+    }
+    Ok(key)
+}
+
+#[cfg(feature = "wasm")]
+mod web {
+    use super::KeyValueStore;
+    use anyhow::{anyhow, Context, Result};
+
+    /// The browser's `window.localStorage`, which keeps its contents across visits of the page
+    #[derive(Debug, Clone, Copy, Default)]
+    pub struct LocalStorage;
+
+    impl LocalStorage {
+        fn get_storage(&self) -> Result<web_sys::Storage> {
+            web_sys::window()
+                .context("Failed to get browser window")?
+                .local_storage()
+                .map_err(|e| anyhow!("Failed to access local storage: {e:?}"))?
+                .context("The browser does not provide local storage")
+        }
+    }
+
+    impl KeyValueStore for LocalStorage {
+        fn get(&self, key: &str) -> Result<Option<String>> {
+            self.get_storage()?
+                .get_item(key)
+                .map_err(|e| anyhow!("Failed to read {key} from local storage: {e:?}"))
+        }
+
+        fn set(&self, key: &str, value: &str) -> Result<()> {
+            // Fails when the quota of the page is used up
+            self.get_storage()?
+                .set_item(key, value)
+                .map_err(|e| anyhow!("Failed to write {key} to local storage: {e:?}"))
+        }
+
+        fn remove(&self, key: &str) -> Result<()> {
+            self.get_storage()?
+                .remove_item(key)
+                .map_err(|e| anyhow!("Failed to remove {key} from local storage: {e:?}"))
+        }
+
+        fn keys(&self) -> Result<Vec<String>> {
+            let storage = self.get_storage()?;
+            let length = storage
+                .length()
+                .map_err(|e| anyhow!("Failed to list local storage: {e:?}"))?;
+            (0..length)
+                .filter_map(|index| storage.key(index).transpose())
+                .map(|key| key.map_err(|e| anyhow!("Failed to list local storage: {e:?}")))
+                .collect()
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Exercises the behavior every [`Storage`] has to share inside of `root`, which must not exist yet
+    fn check_storage(storage: &dyn Storage, root: &Path) {
+        let slot = root.join("slot_0");
+        let save = slot.join("save.sav");
+        let thumbnail = slot.join("thumbnail.png");
+        assert!(!storage.exists(root));
+        assert_eq!(storage.list(root).unwrap(), Vec::<PathBuf>::new());
+        assert!(storage.read(&save).is_err());
+
+        let binary = [0, 159, 146, 150, 255, b'\n'];
+        storage.write(&save, &binary).unwrap();
+        storage.write(&thumbnail, b"png").unwrap();
+        assert_eq!(storage.read(&save).unwrap(), binary);
+        assert!(storage.exists(&slot));
+        assert!(!storage.is_file(&slot));
+        assert!(storage.is_file(&save));
+        assert_eq!(storage.list(root).unwrap(), vec![slot.clone()]);
+        let mut files = storage.list(&slot).unwrap();
+        files.sort();
+        assert_eq!(files, vec![save.clone(), thumbnail.clone()]);
+
+        storage.write(&save, b"overwritten").unwrap();
+        assert_eq!(storage.read(&save).unwrap(), b"overwritten");
+
+        let other_slot = root.join("slot_1");
+        storage.write(&other_slot.join("save.sav"), b"old").unwrap();
+        storage.rename(&slot, &other_slot).unwrap();
+        assert!(!storage.exists(&slot));
+        assert_eq!(
+            storage.read(&other_slot.join("save.sav")).unwrap(),
+            b"overwritten"
+        );
+        assert!(storage.is_file(&other_slot.join("thumbnail.png")));
+
+        storage.remove(&other_slot.join("thumbnail.png")).unwrap();
+        assert!(!storage.is_file(&other_slot.join("thumbnail.png")));
+        storage.remove(root).unwrap();
+        assert!(!storage.exists(&other_slot));
+        assert!(storage.remove(root).is_err());
+        assert!(storage.rename(&slot, &other_slot).is_err());
+    }
+
+    #[test]
+    fn key_value_storage_behaves_like_files() {
+        let storage = KeyValueStorage(MemoryStore::default());
+        check_storage(&storage, Path::new("saves"));
+    }
+
+    #[test]
+    fn native_storage_behaves_like_files() {
+        let root =
+            std::env::temp_dir().join(format!("foxtrot storage test {}", std::process::id()));
+        check_storage(&NativeStorage, &root);
+    }
+
+    #[test]
+    fn tests_do_not_use_native_storage() {
+        let path = Path::new("tests_do_not_use_native_storage/file");
+        storage().write(path, b"test").unwrap();
+        assert!(!path.exists());
+        assert_eq!(storage().read(path).unwrap(), b"test");
+        storage().remove(path).unwrap();
+        assert!(get_data_dir().is_relative());
+    }
+
+    #[test]
+    fn key_value_storage_keeps_directories_apart() {
+        let storage = KeyValueStorage(MemoryStore::default());
+        storage
+            .write(Path::new("saves/slot_1/save.sav"), b"1")
+            .unwrap();
+        storage
+            .write(Path::new("saves/slot_10/save.sav"), b"10")
+            .unwrap();
+        storage.remove(Path::new("saves/slot_1")).unwrap();
+        assert_eq!(
+            storage.read(Path::new("./saves/slot_10/save.sav")).unwrap(),
+            b"10"
+        );
+        assert!(storage.write(Path::new("../outside"), b"").is_err());
+        assert!(storage
+            .0
+            .keys()
+            .unwrap()
+            .iter()
+            .all(|key| key.starts_with(KEY_PREFIX)));
+    }
+}
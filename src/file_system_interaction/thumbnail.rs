@@ -0,0 +1,414 @@
+use crate::file_system_interaction::game_state_serialization::SaveSlot;
+use crate::file_system_interaction::storage::storage;
+use crate::player_control::camera::IngameCamera;
+use anyhow::{bail, Context, Result};
+use bevy::prelude::*;
+use bevy::render::camera::RenderTarget;
+use bevy::render::extract_resource::{ExtractResource, ExtractResourcePlugin};
+use bevy::render::render_asset::RenderAssets;
+use bevy::render::render_resource::{
+    BufferDescriptor, BufferUsages, CommandEncoderDescriptor, Extent3d, ImageCopyBuffer,
+    ImageDataLayout, Maintain, MapMode, TextureDescriptor, TextureDimension, TextureFormat,
+    TextureUsages,
+};
+use bevy::render::renderer::{RenderDevice, RenderQueue};
+use bevy::render::texture::GpuImage;
+use bevy::render::{RenderApp, RenderStage};
+use bevy::tasks::{AsyncComputeTaskPool, Task};
+use futures_lite::future;
+use image::imageops::FilterType;
+use image::{ImageOutputFormat, RgbaImage};
+use std::io::Cursor;
+use std::num::NonZeroU32;
+use std::sync::{mpsc, Arc, Mutex};
+
+/// Stores a small screenshot next to every save, which the save menu shows for each slot.
+/// Send a [`ThumbnailRequest`] after a save was written to capture one.
+/// The scene is rendered by a temporary camera into an image, so UI like the pause menu is never part of the thumbnail.
+/// Thumbnails are copied back from the GPU, downscaled and written on a background task.
+/// Failing to capture one is only logged, as it is not worth losing a save over.
+pub struct ThumbnailPlugin;
+
+impl Plugin for ThumbnailPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ThumbnailRequest>()
+            .init_resource::<ThumbnailCapture>()
+            .init_resource::<ThumbnailReadback>()
+            .add_plugin(ExtractResourcePlugin::<ThumbnailReadback>::default())
+            .add_system(start_thumbnail_captures)
+            .add_system(finish_thumbnail_captures.after(start_thumbnail_captures));
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app.add_system_to_stage(RenderStage::Cleanup, read_back_thumbnail);
+        }
+    }
+}
+
+/// Size the scene is rendered at for a thumbnail
+const CAPTURE_WIDTH: u32 = 640;
+const CAPTURE_HEIGHT: u32 = 360;
+/// Size of the stored thumbnail
+pub const THUMBNAIL_WIDTH: u32 = 320;
+pub const THUMBNAIL_HEIGHT: u32 = 180;
+/// Frames the capture camera renders before its image is read back
+const CAPTURE_DELAY_FRAMES: u32 = 2;
+/// Frames after which a capture that was never read back is given up on
+const CAPTURE_TIMEOUT_FRAMES: u32 = 30;
+/// Rows copied from a texture into a buffer must be aligned to this many bytes
+const COPY_BYTES_PER_ROW_ALIGNMENT: u32 = 256;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ThumbnailRequest {
+    /// Slot holding the save the thumbnail belongs to. The save must already be written.
+    pub slot: SaveSlot,
+}
+
+#[derive(Debug, Resource, Default)]
+struct ThumbnailCapture {
+    /// Slots waiting for the thumbnail currently being captured.
+    /// Requests arriving during a capture share its thumbnail.
+    slots: Vec<SaveSlot>,
+    camera: Option<Entity>,
+    image: Option<Handle<Image>>,
+    frames: u32,
+    writes: Vec<Task<Result<()>>>,
+}
+
+/// Shared between the main world and the render world
+#[derive(Debug, Resource, Clone, Default)]
+struct ThumbnailReadback {
+    /// Image to copy back from the GPU, set once the capture camera rendered into it
+    image: Option<Handle<Image>>,
+    pixels: Arc<Mutex<Option<Result<ThumbnailPixels>>>>,
+}
+
+impl ExtractResource for ThumbnailReadback {
+    type Source = Self;
+
+    fn extract_resource(source: &Self::Source) -> Self {
+        source.clone()
+    }
+}
+
+#[derive(Debug, Clone)]
+struct ThumbnailPixels {
+    width: u32,
+    height: u32,
+    /// Tightly packed rows of four bytes per pixel
+    data: Vec<u8>,
+    is_bgra: bool,
+}
+
+fn start_thumbnail_captures(
+    mut commands: Commands,
+    mut thumbnail_requests: EventReader<ThumbnailRequest>,
+    mut capture: ResMut<ThumbnailCapture>,
+    mut images: ResMut<Assets<Image>>,
+    camera_query: Query<(&GlobalTransform, &Projection), With<IngameCamera>>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("start_thumbnail_captures").entered();
+    capture.slots.extend(
+        thumbnail_requests
+            .iter()
+            .map(|request| request.slot.clone()),
+    );
+    if capture.slots.is_empty() || capture.camera.is_some() {
+        return;
+    }
+    let (transform, projection) = match camera_query.iter().next() {
+        Some(camera) => camera,
+        None => {
+            warn!("Failed to capture save thumbnail: There is no camera");
+            capture.slots.clear();
+            return;
+        }
+    };
+
+    let size = Extent3d {
+        width: CAPTURE_WIDTH,
+        height: CAPTURE_HEIGHT,
+        ..default()
+    };
+    let mut image = Image {
+        texture_descriptor: TextureDescriptor {
+            label: Some("thumbnail_capture"),
+            size,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::Bgra8UnormSrgb,
+            mip_level_count: 1,
+            sample_count: 1,
+            usage: TextureUsages::TEXTURE_BINDING
+                | TextureUsages::COPY_SRC
+                | TextureUsages::COPY_DST
+                | TextureUsages::RENDER_ATTACHMENT,
+        },
+        ..default()
+    };
+    image.resize(size);
+    let image = images.add(image);
+    let camera = commands
+        .spawn((
+            Camera3dBundle {
+                camera: Camera {
+                    target: RenderTarget::Image(image.clone()),
+                    ..default()
+                },
+                projection: projection.clone(),
+                transform: transform.compute_transform(),
+                ..default()
+            },
+            Name::new("Thumbnail Camera"),
+        ))
+        .id();
+    capture.camera = Some(camera);
+    capture.image = Some(image);
+    capture.frames = 0;
+}
+
+fn finish_thumbnail_captures(
+    mut commands: Commands,
+    mut capture: ResMut<ThumbnailCapture>,
+    mut readback: ResMut<ThumbnailReadback>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("finish_thumbnail_captures").entered();
+    capture
+        .writes
+        .retain_mut(|task| match future::block_on(future::poll_once(task)) {
+            Some(Ok(())) => false,
+            Some(Err(e)) => {
+                warn!("Failed to write save thumbnail: {e:#}");
+                false
+            }
+            None => true,
+        });
+    let camera = match capture.camera {
+        Some(camera) => camera,
+        None => return,
+    };
+
+    let pixels = readback
+        .pixels
+        .lock()
+        .ok()
+        .and_then(|mut pixels| pixels.take());
+    let pixels = match pixels {
+        Some(pixels) => pixels,
+        None => {
+            capture.frames += 1;
+            if capture.frames == CAPTURE_DELAY_FRAMES {
+                readback.image = capture.image.clone();
+                return;
+            } else if capture.frames <= CAPTURE_TIMEOUT_FRAMES {
+                return;
+            }
+            Err(anyhow::anyhow!(
+                "The image was not read back within {CAPTURE_TIMEOUT_FRAMES} frames"
+            ))
+        }
+    };
+
+    commands.entity(camera).despawn_recursive();
+    capture.camera = None;
+    capture.image = None;
+    readback.image = None;
+    let slots = std::mem::take(&mut capture.slots);
+    match pixels {
+        Ok(pixels) => {
+            let task = AsyncComputeTaskPool::get().spawn(async move {
+                let thumbnail = encode_thumbnail(pixels)?;
+                for slot in slots {
+                    // The save might have been deleted in the meantime
+                    if storage().exists(&slot.get_dir()?) {
+                        storage()
+                            .write(&slot.get_thumbnail_path()?, &thumbnail)
+                            .with_context(|| format!("Failed to write thumbnail of slot {slot}"))?;
+                    }
+                }
+                Ok(())
+            });
+            capture.writes.push(task);
+        }
+        Err(e) => warn!("Failed to capture save thumbnail: {e:#}"),
+    }
+}
+
+/// Downscales the captured pixels to the thumbnail size and encodes them as PNG
+fn encode_thumbnail(pixels: ThumbnailPixels) -> Result<Vec<u8>> {
+    let mut data = pixels.data;
+    for pixel in data.chunks_exact_mut(4) {
+        if pixels.is_bgra {
+            pixel.swap(0, 2);
+        }
+        pixel[3] = u8::MAX;
+    }
+    let image = RgbaImage::from_raw(pixels.width, pixels.height, data)
+        .context("Captured thumbnail has the wrong number of pixels")?;
+    let thumbnail = image::imageops::resize(
+        &image,
+        THUMBNAIL_WIDTH,
+        THUMBNAIL_HEIGHT,
+        FilterType::Triangle,
+    );
+    let mut encoded = Vec::new();
+    thumbnail
+        .write_to(&mut Cursor::new(&mut encoded), ImageOutputFormat::Png)
+        .context("Failed to encode thumbnail")?;
+    Ok(encoded)
+}
+
+/// Reads the thumbnail of the save in the slot, if it has one
+pub fn read_thumbnail(slot: &SaveSlot) -> Result<Option<RgbaImage>> {
+    let path = slot.get_thumbnail_path()?;
+    if !storage().is_file(&path) {
+        return Ok(None);
+    }
+    let bytes = storage().read(&path).context("Failed to read thumbnail")?;
+    let image = image::load_from_memory_with_format(&bytes, image::ImageFormat::Png)
+        .context("Failed to decode thumbnail")?;
+    Ok(Some(image.into_rgba8()))
+}
+
+fn read_back_thumbnail(
+    readback: Res<ThumbnailReadback>,
+    gpu_images: Res<RenderAssets<Image>>,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("read_back_thumbnail").entered();
+    let image = match readback.image.as_ref() {
+        Some(image) => image,
+        None => return,
+    };
+    let gpu_image = match gpu_images.get(image) {
+        Some(gpu_image) => gpu_image,
+        None => return,
+    };
+    let mut pixels = match readback.pixels.lock() {
+        Ok(pixels) => pixels,
+        Err(_) => return,
+    };
+    if pixels.is_none() {
+        *pixels = Some(copy_to_cpu(gpu_image, &render_device, &render_queue));
+    }
+}
+
+/// Copies the image from the GPU, blocking until the copy is done
+fn copy_to_cpu(
+    gpu_image: &GpuImage,
+    render_device: &RenderDevice,
+    render_queue: &RenderQueue,
+) -> Result<ThumbnailPixels> {
+    let is_bgra = match gpu_image.texture_format {
+        TextureFormat::Bgra8UnormSrgb | TextureFormat::Bgra8Unorm => true,
+        TextureFormat::Rgba8UnormSrgb | TextureFormat::Rgba8Unorm => false,
+        format => bail!("Cannot read back a thumbnail with texture format {format:?}"),
+    };
+    let width = gpu_image.size.x as u32;
+    let height = gpu_image.size.y as u32;
+    let padded_bytes_per_row = get_padded_bytes_per_row(width);
+    let buffer = render_device.create_buffer(&BufferDescriptor {
+        label: Some("thumbnail_readback"),
+        size: (padded_bytes_per_row * height) as u64,
+        usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+    let mut encoder = render_device.create_command_encoder(&CommandEncoderDescriptor {
+        label: Some("thumbnail_readback"),
+    });
+    encoder.copy_texture_to_buffer(
+        gpu_image.texture.as_image_copy(),
+        ImageCopyBuffer {
+            buffer: &buffer,
+            layout: ImageDataLayout {
+                offset: 0,
+                bytes_per_row: NonZeroU32::new(padded_bytes_per_row),
+                rows_per_image: None,
+            },
+        },
+        Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+    );
+    render_queue.submit([encoder.finish()]);
+
+    let slice = buffer.slice(..);
+    let (sender, receiver) = mpsc::channel();
+    slice.map_async(MapMode::Read, move |result| {
+        let _ = sender.send(result);
+    });
+    render_device.wgpu_device().poll(Maintain::Wait);
+    receiver
+        .recv()
+        .context("Thumbnail readback buffer was never mapped")?
+        .context("Failed to map thumbnail readback buffer")?;
+    let data = remove_row_padding(&slice.get_mapped_range(), width, padded_bytes_per_row);
+    buffer.unmap();
+    Ok(ThumbnailPixels {
+        width,
+        height,
+        data,
+        is_bgra,
+    })
+}
+
+fn get_padded_bytes_per_row(width: u32) -> u32 {
+    let bytes_per_row = width * 4;
+    let remainder = bytes_per_row % COPY_BYTES_PER_ROW_ALIGNMENT;
+    if remainder == 0 {
+        bytes_per_row
+    } else {
+        bytes_per_row + COPY_BYTES_PER_ROW_ALIGNMENT - remainder
+    }
+}
+
+fn remove_row_padding(data: &[u8], width: u32, padded_bytes_per_row: u32) -> Vec<u8> {
+    let bytes_per_row = (width * 4) as usize;
+    data.chunks(padded_bytes_per_row as usize)
+        .flat_map(|row| &row[..bytes_per_row])
+        .copied()
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn pads_rows_to_copy_alignment() {
+        assert_eq!(get_padded_bytes_per_row(64), 256);
+        assert_eq!(get_padded_bytes_per_row(65), 512);
+        assert_eq!(get_padded_bytes_per_row(CAPTURE_WIDTH), CAPTURE_WIDTH * 4);
+    }
+
+    #[test]
+    fn removes_row_padding() {
+        let width = 65;
+        let padded_bytes_per_row = get_padded_bytes_per_row(width);
+        let mut data = vec![];
+        for row in 0..2 {
+            data.extend(std::iter::repeat(row + 1).take((width * 4) as usize));
+            data.extend(std::iter::repeat(0).take((padded_bytes_per_row - width * 4) as usize));
+        }
+        let pixels = remove_row_padding(&data, width, padded_bytes_per_row);
+        assert_eq!(pixels.len(), (width * 4 * 2) as usize);
+        assert!(pixels.iter().all(|&byte| byte != 0));
+    }
+
+    #[test]
+    fn encodes_thumbnail_at_thumbnail_size() {
+        let pixels = ThumbnailPixels {
+            width: CAPTURE_WIDTH,
+            height: CAPTURE_HEIGHT,
+            data: vec![128; (CAPTURE_WIDTH * CAPTURE_HEIGHT * 4) as usize],
+            is_bgra: true,
+        };
+        let encoded = encode_thumbnail(pixels).unwrap();
+        let image = image::load_from_memory(&encoded).unwrap();
+        assert_eq!(image.width(), THUMBNAIL_WIDTH);
+        assert_eq!(image.height(), THUMBNAIL_HEIGHT);
+    }
+}
@@ -0,0 +1,208 @@
+use crate::file_system_interaction::config::{Accessibility, Quality};
+use crate::file_system_interaction::storage::storage;
+use anyhow::{Context, Result};
+use bevy::prelude::*;
+use bevy::window::WindowMode;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// Settings chosen by the player, as opposed to the [`GameConfig`](crate::file_system_interaction::config::GameConfig) that ships with the game.
+/// They are stored in `settings.toml` in the platform's config directory, or in the browser's storage on the web,
+/// and read once at startup, before the window is created.
+/// Missing entries fall back to their defaults, so older settings files keep working when new settings are added.
+#[derive(Debug, Clone, PartialEq, Resource, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct UserSettings {
+    pub graphics: Graphics,
+    /// Overrides the [`GameConfig::accessibility`](crate::file_system_interaction::config::GameConfig::accessibility) once the player changed it
+    pub accessibility: Option<Accessibility>,
+}
+
+impl UserSettings {
+    /// The accessibility options in effect, which are the configured `default` unless the player chose their own
+    pub fn accessibility_or<'a>(&'a self, default: &'a Accessibility) -> &'a Accessibility {
+        self.accessibility.as_ref().unwrap_or(default)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Graphics {
+    pub display_mode: DisplayMode,
+    /// Size of the window in physical pixels when windowed, or the resolution of the monitor in exclusive fullscreen
+    pub resolution: UVec2,
+    pub vsync: bool,
+    /// Maximum frames per second, or no limit when missing
+    pub frame_rate_cap: Option<u32>,
+    /// Overrides the scale factor of the monitor, e.g. to make the UI larger on high-DPI displays
+    pub scale_factor_override: Option<f64>,
+    /// Overrides the [`GameConfig::quality`](crate::file_system_interaction::config::GameConfig::quality) once the player changed it
+    pub quality: Option<Quality>,
+    /// Overrides `camera.fov_degrees` of the game config once the player changed it
+    pub fov_degrees: Option<f32>,
+    /// Overrides `camera.dynamic_fov` of the game config once the player changed it
+    pub dynamic_fov: Option<bool>,
+}
+
+impl Default for Graphics {
+    fn default() -> Self {
+        Self {
+            display_mode: default(),
+            resolution: UVec2::new(800, 600),
+            vsync: true,
+            frame_rate_cap: None,
+            scale_factor_override: None,
+            quality: None,
+            fov_degrees: None,
+            dynamic_fov: None,
+        }
+    }
+}
+
+impl Graphics {
+    /// The quality in effect, which is the configured `default` unless the player chose their own
+    pub fn quality_or<'a>(&'a self, default: &'a Quality) -> &'a Quality {
+        self.quality.as_ref().unwrap_or(default)
+    }
+
+    /// The base field of view in effect, which is the configured `default` unless the player chose their own
+    pub fn fov_degrees_or(&self, default: f32) -> f32 {
+        self.fov_degrees.unwrap_or(default)
+    }
+
+    /// Whether the field of view widens at high speeds, which is the configured `default` unless the player chose otherwise
+    pub fn dynamic_fov_or(&self, default: bool) -> bool {
+        self.dynamic_fov.unwrap_or(default)
+    }
+
+    /// Whether going from `self` to `other` changes what the monitor shows, which might leave the player with a black screen
+    pub fn changes_display(&self, other: &Graphics) -> bool {
+        self.display_mode != other.display_mode || self.resolution != other.resolution
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum DisplayMode {
+    #[default]
+    Windowed,
+    /// A window without decorations covering the whole monitor
+    Borderless,
+    /// Switches the monitor to the configured resolution
+    Exclusive,
+}
+
+impl DisplayMode {
+    pub const ALL: [DisplayMode; 3] = [
+        DisplayMode::Windowed,
+        DisplayMode::Borderless,
+        DisplayMode::Exclusive,
+    ];
+
+    pub fn window_mode(self) -> WindowMode {
+        match self {
+            DisplayMode::Windowed => WindowMode::Windowed,
+            DisplayMode::Borderless => WindowMode::BorderlessFullscreen,
+            DisplayMode::Exclusive => WindowMode::SizedFullscreen,
+        }
+    }
+
+    /// Localization key of the mode's name
+    pub fn localization_key(self) -> &'static str {
+        match self {
+            DisplayMode::Windowed => "settings.display_mode.windowed",
+            DisplayMode::Borderless => "settings.display_mode.borderless",
+            DisplayMode::Exclusive => "settings.display_mode.exclusive",
+        }
+    }
+}
+
+/// `None` on native platforms without a config directory. The web and tests have none either, but there the path is only used
+/// as a key in a key-value storage, see [`storage`].
+pub fn get_user_settings_path() -> Option<PathBuf> {
+    if cfg!(feature = "wasm") || cfg!(test) {
+        return Some(PathBuf::from("settings.toml"));
+    }
+    dirs::config_dir().map(|dir| dir.join("Foxtrot").join("settings.toml"))
+}
+
+/// Falls back to the default settings when there are none yet or they cannot be read
+pub fn read_user_settings() -> UserSettings {
+    let path = match get_user_settings_path() {
+        Some(path) => path,
+        None => return default(),
+    };
+    let storage = storage();
+    if !storage.is_file(&path) {
+        return default();
+    }
+    let settings = storage
+        .read(&path)
+        .context("Failed to read settings file")
+        .and_then(|bytes| String::from_utf8(bytes).context("Settings file is not UTF-8"))
+        .and_then(|text| toml::from_str(&text).context("Failed to parse settings file"));
+    match settings {
+        Ok(settings) => settings,
+        Err(e) => {
+            warn!(
+                "Using the default settings instead of {}: {e:?}",
+                path.to_string_lossy()
+            );
+            default()
+        }
+    }
+}
+
+/// Does nothing on platforms without a config directory, where the settings only last until the game is closed
+pub fn write_user_settings(settings: &UserSettings) -> Result<()> {
+    let path = match get_user_settings_path() {
+        Some(path) => path,
+        None => return Ok(()),
+    };
+    let text = toml::to_string_pretty(settings).context("Failed to serialize settings")?;
+    storage()
+        .write(&path, text.as_bytes())
+        .with_context(|| format!("Failed to write settings to {}", path.to_string_lossy()))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn settings_survive_round_trip() {
+        let settings = UserSettings {
+            graphics: Graphics {
+                display_mode: DisplayMode::Exclusive,
+                resolution: UVec2::new(1920, 1080),
+                vsync: false,
+                frame_rate_cap: Some(144),
+                scale_factor_override: Some(1.5),
+                quality: Some(Quality {
+                    shadows: false,
+                    ..default()
+                }),
+                fov_degrees: Some(70.),
+                dynamic_fov: Some(false),
+            },
+            accessibility: Some(Accessibility {
+                hold_to_toggle: true,
+                ..default()
+            }),
+        };
+        let text = toml::to_string_pretty(&settings).unwrap();
+        assert_eq!(toml::from_str::<UserSettings>(&text).unwrap(), settings);
+    }
+
+    #[test]
+    fn missing_settings_fall_back_to_defaults() {
+        let settings: UserSettings = toml::from_str("[graphics]\nvsync = false\n").unwrap();
+        assert_eq!(
+            settings.graphics,
+            Graphics {
+                vsync: false,
+                ..default()
+            }
+        );
+    }
+}
@@ -0,0 +1,138 @@
+use crate::file_system_interaction::asset_loading::ConfigAssets;
+use crate::file_system_interaction::config::{GameConfig, Quality};
+use crate::file_system_interaction::user_settings::UserSettings;
+use crate::player_control::camera::IngameCamera;
+use bevy::pbr::{DirectionalLightShadowMap, PointLightShadowMap};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Applies the graphics [`Quality`] in effect, i.e. the one chosen in the settings menu or else the one of the [`GameConfig`].
+/// The ambient brightness is applied by the [`TimeOfDayPlugin`](crate::world_interaction::time_of_day::TimeOfDayPlugin), which varies it over the day.
+/// Changes take effect immediately, without restarting the game.
+pub struct GraphicsQualityPlugin;
+
+impl Plugin for GraphicsQualityPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<QualityLight>()
+            .add_system(apply_quality);
+    }
+}
+
+/// Marks lights whose shadows are turned on and off by [`Quality::shadows`]
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Component, Reflect, Serialize, Deserialize, Default,
+)]
+#[reflect(Component, Serialize, Deserialize)]
+pub struct QualityLight;
+
+const SUPPORTED_MSAA_SAMPLES: [u32; 2] = [1, 4];
+const FALLBACK_MSAA_SAMPLES: u32 = 4;
+const SHADOW_MAP_SIZES: std::ops::RangeInclusive<u32> = 256..=8192;
+const FALLBACK_SHADOW_MAP_SIZE: u32 = 2048;
+
+fn apply_quality(
+    settings: Res<UserSettings>,
+    config_handles: Option<Res<ConfigAssets>>,
+    config: Res<Assets<GameConfig>>,
+    mut msaa: ResMut<Msaa>,
+    mut directional_shadow_map: ResMut<DirectionalLightShadowMap>,
+    mut point_shadow_map: ResMut<PointLightShadowMap>,
+    mut directional_lights: Query<&mut DirectionalLight, With<QualityLight>>,
+    mut point_lights: Query<&mut PointLight, With<QualityLight>>,
+    mut projections: Query<&mut Projection, With<IngameCamera>>,
+    added_lights: Query<(), Added<QualityLight>>,
+    added_cameras: Query<(), Added<IngameCamera>>,
+    mut applied_quality: Local<Option<Quality>>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("apply_quality").entered();
+    // The config is only available after loading
+    let config = match config_handles.and_then(|handles| config.get(&handles.game)) {
+        Some(config) => config,
+        None => return,
+    };
+    let quality = settings.graphics.quality_or(&config.quality);
+    let quality_changed = applied_quality.as_ref() != Some(quality);
+    if !quality_changed && added_lights.is_empty() && added_cameras.is_empty() {
+        return;
+    }
+
+    if quality_changed {
+        let samples = get_msaa_samples(quality);
+        if msaa.samples != samples {
+            msaa.samples = samples;
+        }
+        let shadow_map_size = get_shadow_map_size(quality) as usize;
+        if directional_shadow_map.size != shadow_map_size {
+            directional_shadow_map.size = shadow_map_size;
+        }
+        if point_shadow_map.size != shadow_map_size {
+            point_shadow_map.size = shadow_map_size;
+        }
+    }
+    for mut light in directional_lights.iter_mut() {
+        light.shadows_enabled = quality.shadows;
+    }
+    for mut light in point_lights.iter_mut() {
+        light.shadows_enabled = quality.shadows;
+    }
+    for mut projection in projections.iter_mut() {
+        if let Projection::Perspective(ref mut perspective) = *projection {
+            perspective.far = quality.draw_distance;
+        }
+    }
+    *applied_quality = Some(quality.clone());
+}
+
+fn get_msaa_samples(quality: &Quality) -> u32 {
+    if SUPPORTED_MSAA_SAMPLES.contains(&quality.msaa_samples) {
+        quality.msaa_samples
+    } else {
+        warn!(
+            "Unsupported number of MSAA samples {}, using {FALLBACK_MSAA_SAMPLES} instead. Supported are {SUPPORTED_MSAA_SAMPLES:?}",
+            quality.msaa_samples
+        );
+        FALLBACK_MSAA_SAMPLES
+    }
+}
+
+fn get_shadow_map_size(quality: &Quality) -> u32 {
+    let size = quality.shadow_map_size;
+    if size.is_power_of_two() && SHADOW_MAP_SIZES.contains(&size) {
+        size
+    } else {
+        warn!(
+            "Invalid shadow map size {size}, using {FALLBACK_SHADOW_MAP_SIZE} instead. It must be a power of two between {} and {}",
+            SHADOW_MAP_SIZES.start(),
+            SHADOW_MAP_SIZES.end()
+        );
+        FALLBACK_SHADOW_MAP_SIZE
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn invalid_quality_falls_back() {
+        let quality = Quality {
+            msaa_samples: 3,
+            shadow_map_size: 3000,
+            ..default()
+        };
+        assert_eq!(get_msaa_samples(&quality), FALLBACK_MSAA_SAMPLES);
+        assert_eq!(get_shadow_map_size(&quality), FALLBACK_SHADOW_MAP_SIZE);
+    }
+
+    #[test]
+    fn valid_quality_is_kept() {
+        let quality = Quality {
+            msaa_samples: 1,
+            shadow_map_size: 512,
+            ..default()
+        };
+        assert_eq!(get_msaa_samples(&quality), 1);
+        assert_eq!(get_shadow_map_size(&quality), 512);
+    }
+}
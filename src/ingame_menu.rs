@@ -1,55 +1,208 @@
+use crate::bevy_config::AvailableResolutions;
+use crate::file_system_interaction::asset_loading::ConfigAssets;
+use crate::file_system_interaction::config::GameConfig;
+use crate::file_system_interaction::game_state_serialization::{GameLoadRequest, GameSaveRequest};
+use crate::file_system_interaction::localization::Localization;
+use crate::file_system_interaction::user_settings::UserSettings;
 use crate::player_control::actions::{ActionsFrozen, UiAction};
+use crate::save_menu::{SaveMenu, SaveMenuAction};
+use crate::settings_menu::{SettingsChangeRequest, SettingsMenu};
+use crate::util::log_error::log_errors;
 use crate::GameState;
+use anyhow::{Context, Result};
 use bevy::prelude::*;
 use bevy_egui::{egui, EguiContext};
+use bevy_rapier3d::prelude::*;
 use leafwing_input_manager::prelude::ActionState;
 
 /// Handles the pause menu accessed while playing the game via ESC.
+/// Pausing pushes [`GameState::Paused`] on top of [`GameState::Playing`], which stops every system that only runs while playing.
+/// An open dialog is paused with them and continues where it was when the game is resumed.
+/// On top of that, time and physics are stopped and the player's actions are frozen while the menu is open.
+/// Quitting to the main menu leaves [`GameState::Playing`], which despawns the level and the player.
 pub struct IngameMenuPlugin;
 
 impl Plugin for IngameMenuPlugin {
     fn build(&self, app: &mut App) {
-        {
-            app.add_system_set(SystemSet::on_update(GameState::Playing).with_system(handle_pause));
+        app.add_system_set(SystemSet::on_update(GameState::Playing).with_system(open_pause_menu))
+            .add_system_set(SystemSet::on_enter(GameState::Paused).with_system(pause_game))
+            .add_system_set(
+                SystemSet::on_update(GameState::Paused)
+                    .with_system(show_pause_menu.pipe(log_errors)),
+            )
+            .add_system_set(SystemSet::on_exit(GameState::Paused).with_system(resume_game));
+    }
+}
+
+/// Screen of the pause menu that is currently shown
+#[derive(Debug, Clone, Default)]
+enum PauseMenuScreen {
+    #[default]
+    Main,
+    Settings(SettingsMenu),
+    Save(SaveMenu),
+    Load(SaveMenu),
+}
+
+fn open_pause_menu(
+    mut actions: Query<&mut ActionState<UiAction>>,
+    mut egui_context: ResMut<EguiContext>,
+    mut state: ResMut<State<GameState>>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("open_pause_menu").entered();
+    if !take_toggle_press(&mut actions, &mut egui_context) {
+        return;
+    }
+    if let Err(e) = state.push(GameState::Paused) {
+        warn!("Failed to open the pause menu: {e:?}");
+    }
+}
+
+/// Whether ESC was just pressed. The press is consumed, so that the state we change to in the same frame does not see it again.
+fn take_toggle_press(
+    actions: &mut Query<&mut ActionState<UiAction>>,
+    egui_context: &mut EguiContext,
+) -> bool {
+    // ESC also leaves text fields, e.g. in the dev editor
+    if egui_context.ctx_mut().wants_keyboard_input() {
+        return false;
+    }
+    let mut toggled = false;
+    for mut action in actions.iter_mut() {
+        if action.just_pressed(UiAction::TogglePause) {
+            action.consume(UiAction::TogglePause);
+            toggled = true;
         }
     }
+    toggled
 }
 
-fn handle_pause(
+fn pause_game(
     mut time: ResMut<Time>,
-    actions: Query<&ActionState<UiAction>>,
     mut actions_frozen: ResMut<ActionsFrozen>,
-    mut egui_context: ResMut<EguiContext>,
-    mut paused: Local<bool>,
+    mut rapier_configuration: ResMut<RapierConfiguration>,
 ) {
-    for action in actions.iter() {
-        let toggled = action.just_pressed(UiAction::TogglePause);
-        if *paused {
-            if toggled {
-                *paused = false;
-                time.unpause();
-                actions_frozen.unfreeze();
-            } else {
-                egui::CentralPanel::default()
-                    .frame(egui::Frame {
-                        fill: egui::Color32::from_black_alpha(240),
-                        ..default()
-                    })
-                    .show(egui_context.ctx_mut(), |ui| {
-                        ui.vertical_centered_justified(|ui| {
-                            ui.visuals_mut().override_text_color =
-                                Some(egui::Color32::from_gray(240));
-                            ui.add_space(100.0);
-                            ui.heading("Game Paused");
-                            ui.separator();
-                            ui.label("Press ESC to resume");
-                        });
-                    });
-            }
-        } else if toggled {
-            *paused = true;
-            time.pause();
-            actions_frozen.freeze();
+    time.pause();
+    actions_frozen.freeze();
+    rapier_configuration.physics_pipeline_active = false;
+}
+
+fn resume_game(
+    mut time: ResMut<Time>,
+    mut actions_frozen: ResMut<ActionsFrozen>,
+    mut rapier_configuration: ResMut<RapierConfiguration>,
+) {
+    time.unpause();
+    actions_frozen.unfreeze();
+    rapier_configuration.physics_pipeline_active = true;
+}
+
+fn show_pause_menu(
+    mut actions: Query<&mut ActionState<UiAction>>,
+    mut state: ResMut<State<GameState>>,
+    mut egui_context: ResMut<EguiContext>,
+    mut localization: ResMut<Localization>,
+    mut save_requests: EventWriter<GameSaveRequest>,
+    mut load_requests: EventWriter<GameLoadRequest>,
+    mut settings_requests: EventWriter<SettingsChangeRequest>,
+    settings: Res<UserSettings>,
+    resolutions: Res<AvailableResolutions>,
+    config_handles: Res<ConfigAssets>,
+    config: Res<Assets<GameConfig>>,
+    mut screen: Local<PauseMenuScreen>,
+) -> Result<()> {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("show_pause_menu").entered();
+    let config = config
+        .get(&config_handles.game)
+        .context("Failed to get game config from handle")?;
+    let toggled = take_toggle_press(&mut actions, &mut egui_context);
+    let mut resume = false;
+    let mut quit = false;
+    let mut next_screen = None;
+    let mut save_menu_action = None;
+    let mut new_settings = None;
+    egui::CentralPanel::default()
+        .frame(egui::Frame {
+            fill: egui::Color32::from_black_alpha(240),
+            ..default()
+        })
+        .show(egui_context.ctx_mut(), |ui| {
+            ui.vertical_centered_justified(|ui| {
+                ui.visuals_mut().override_text_color = Some(egui::Color32::from_gray(240));
+                ui.add_space(100.0);
+                ui.heading(localization.get("pause.title"));
+                ui.separator();
+                ui.label(localization.get("pause.resume_hint"));
+                ui.add_space(50.0);
+                match &mut *screen {
+                    PauseMenuScreen::Main => {
+                        resume = ui.button(localization.get("pause.resume")).clicked();
+                        if ui.button(localization.get("pause.settings")).clicked() {
+                            next_screen = Some(PauseMenuScreen::Settings(default()));
+                        }
+                        if ui.button(localization.get("pause.save")).clicked() {
+                            next_screen = Some(PauseMenuScreen::Save(default()));
+                        }
+                        if ui.button(localization.get("pause.load")).clicked() {
+                            next_screen = Some(PauseMenuScreen::Load(default()));
+                        }
+                        quit = ui.button(localization.get("pause.quit_to_menu")).clicked();
+                    }
+                    PauseMenuScreen::Settings(settings_menu) => {
+                        new_settings = settings_menu.show(
+                            ui,
+                            &mut localization,
+                            &settings,
+                            config,
+                            &resolutions,
+                        );
+                    }
+                    PauseMenuScreen::Save(save_menu) => {
+                        save_menu_action = save_menu.show(ui, &localization, true);
+                    }
+                    PauseMenuScreen::Load(save_menu) => {
+                        save_menu_action = save_menu.show(ui, &localization, false);
+                    }
+                }
+                if !matches!(*screen, PauseMenuScreen::Main) {
+                    ui.add_space(30.0);
+                    if ui.button(localization.get("menu.back")).clicked() {
+                        next_screen = Some(PauseMenuScreen::Main);
+                    }
+                }
+            });
+        });
+
+    if toggled {
+        // ESC leaves the current screen first
+        match *screen {
+            PauseMenuScreen::Main => resume = true,
+            _ => next_screen = Some(PauseMenuScreen::Main),
+        }
+    }
+    match save_menu_action {
+        Some(SaveMenuAction::Save(slot)) => save_requests.send(GameSaveRequest { slot }),
+        Some(SaveMenuAction::Load(slot)) => {
+            load_requests.send(GameLoadRequest { slot });
+            resume = true;
         }
+        None => {}
+    }
+    if let Some(new_settings) = new_settings {
+        settings_requests.send(SettingsChangeRequest(new_settings));
+    }
+
+    if quit {
+        *screen = default();
+        // Replaces the whole state stack, so the game is left as well
+        state.replace(GameState::Menu)?;
+    } else if resume {
+        *screen = default();
+        state.pop()?;
+    } else if let Some(next_screen) = next_screen {
+        *screen = next_screen;
     }
+    Ok(())
 }
@@ -0,0 +1,116 @@
+use crate::file_system_interaction::localization::Localization;
+use crate::player_control::actions::{ActionsFrozen, UiAction};
+use crate::world_interaction::inventory::{Inventory, ItemDropEvent};
+use crate::GameState;
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use bevy_egui::{egui, EguiContext};
+use leafwing_input_manager::prelude::ActionState;
+
+/// Shows the items the player carries in a grid, toggled via I. Clicking an item drops one of it.
+pub struct InventoryMenuPlugin;
+
+impl Plugin for InventoryMenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(
+            SystemSet::on_update(GameState::Playing).with_system(handle_inventory_menu),
+        );
+    }
+}
+
+const COLUMNS: usize = 5;
+const SLOT_SIZE: f32 = 64.;
+
+fn handle_inventory_menu(
+    actions: Query<&ActionState<UiAction>>,
+    inventory: Res<Inventory>,
+    localization: Res<Localization>,
+    asset_server: Res<AssetServer>,
+    time: Res<Time>,
+    mut drop_events: EventWriter<ItemDropEvent>,
+    mut actions_frozen: ResMut<ActionsFrozen>,
+    mut egui_context: ResMut<EguiContext>,
+    mut icons: Local<HashMap<String, (Handle<Image>, egui::TextureId)>>,
+    mut is_open: Local<bool>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("handle_inventory_menu").entered();
+    // The open inventory holds a freeze, so it was reset from elsewhere, e.g. by quitting to the main menu
+    if *is_open && !actions_frozen.is_frozen() {
+        *is_open = false;
+    }
+    for action in actions.iter() {
+        if action.just_pressed(UiAction::ToggleInventory) {
+            if *is_open {
+                *is_open = false;
+                actions_frozen.unfreeze();
+            } else if !actions_frozen.is_frozen() && !time.is_paused() {
+                // Only opened during free gameplay, so that it does not get in the way of dialogs or the pause menu
+                *is_open = true;
+                actions_frozen.freeze();
+            }
+        }
+    }
+    if !*is_open {
+        return;
+    }
+
+    let slots = inventory.slots();
+    let mut texture_ids = HashMap::new();
+    for (item, _count) in slots.iter() {
+        if let Some(path) = &item.icon {
+            let (_handle, texture_id) = icons.entry(path.clone()).or_insert_with(|| {
+                let handle: Handle<Image> = asset_server.load(path.as_str());
+                let texture_id = egui_context.add_image(handle.clone_weak());
+                (handle, texture_id)
+            });
+            texture_ids.insert(path.clone(), *texture_id);
+        }
+    }
+
+    egui::Window::new(localization.get("inventory.title"))
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+        .show(egui_context.ctx_mut(), |ui| {
+            if slots.is_empty() {
+                ui.label(localization.get("inventory.empty"));
+                return;
+            }
+            egui::Grid::new("inventory_grid")
+                .spacing(egui::Vec2::splat(4.))
+                .show(ui, |ui| {
+                    for (index, (item, count)) in slots.iter().enumerate() {
+                        let name = localization.get(&item.name);
+                        let texture_id = item.icon.as_ref().and_then(|path| texture_ids.get(path));
+                        let response = match texture_id {
+                            Some(texture_id) => ui.add(egui::ImageButton::new(
+                                *texture_id,
+                                egui::Vec2::splat(SLOT_SIZE),
+                            )),
+                            None => ui.add_sized(
+                                egui::Vec2::splat(SLOT_SIZE),
+                                egui::Button::new(name).wrap(true),
+                            ),
+                        };
+                        if *count > 1 {
+                            ui.painter().text(
+                                response.rect.right_bottom() - egui::Vec2::splat(4.),
+                                egui::Align2::RIGHT_BOTTOM,
+                                count.to_string(),
+                                egui::FontId::proportional(14.),
+                                ui.visuals().strong_text_color(),
+                            );
+                        }
+                        if response.on_hover_text(name).clicked() {
+                            drop_events.send(ItemDropEvent(item.id.clone()));
+                        }
+                        if (index + 1) % COLUMNS == 0 {
+                            ui.end_row();
+                        }
+                    }
+                });
+            ui.separator();
+            ui.label(localization.get("inventory.drop_hint"));
+        });
+}
@@ -1,8 +1,10 @@
 pub mod grass;
+pub mod level_transition;
 pub mod map;
 pub mod spawning;
 
 use crate::level_instantiation::grass::GrassPlugin;
+use crate::level_instantiation::level_transition::LevelTransitionPlugin;
 use crate::level_instantiation::map::MapPlugin;
 use crate::level_instantiation::spawning::SpawningPlugin;
 use bevy::prelude::*;
@@ -11,12 +13,14 @@ use bevy::prelude::*;
 /// - [`MapPlugin`] handles loading of level files and orchestrates the spawning of the objects therein.
 /// - [`SpawningPlugin`] handles the spawning of objects in general.
 /// - [`GrassPlugin`] handles the spawning of grass on top of marked meshes.
+/// - [`LevelTransitionPlugin`] handles moving the player between levels through portals.
 pub struct LevelInstantiationPlugin;
 
 impl Plugin for LevelInstantiationPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugin(MapPlugin)
             .add_plugin(SpawningPlugin)
-            .add_plugin(GrassPlugin);
+            .add_plugin(GrassPlugin)
+            .add_plugin(LevelTransitionPlugin);
     }
 }
@@ -0,0 +1,299 @@
+use crate::file_system_interaction::asset_loading::{ConfigAssets, LevelAssets};
+use crate::file_system_interaction::autosave::AutosaveRequest;
+use crate::file_system_interaction::config::GameConfig;
+use crate::file_system_interaction::level_serialization::{
+    get_level_path, SerializedLevel, WorldLoadRequest,
+};
+use crate::level_instantiation::spawning::{DelayedSpawnEvent, GameObject, SpawnEvent};
+use crate::player_control::actions::ActionsFrozen;
+use crate::player_control::camera::{CameraForceSnap, IngameCamera, IngameCameraKind};
+use crate::player_control::player_embodiment::Player;
+use crate::util::log_error::log_errors;
+use crate::world_interaction::checkpoint::Respawning;
+use crate::world_interaction::dialog::CurrentDialog;
+use crate::GameState;
+use anyhow::{Context, Result};
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContext};
+use bevy_rapier3d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Moves the player to another level when they enter a [`LevelPortal`].
+/// The screen fades to black, the current level is replaced by the target level through a [`WorldLoadRequest`]
+/// and the player is placed at the portal's target [`GameObject::SpawnPoint`], keeping the camera's kind and distance.
+/// The player's progress, i.e. flags, fired triggers and inventory, is kept, while objects spawned at runtime stay behind with their level.
+/// Spawn points should not overlap a portal, as the player would be sent right back.
+pub struct LevelTransitionPlugin;
+
+impl Plugin for LevelTransitionPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<LevelPortal>()
+            .init_resource::<ScreenFade>()
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(enter_portals.pipe(log_errors))
+                    .with_system(
+                        advance_level_transition
+                            .pipe(log_errors)
+                            .after(enter_portals),
+                    ),
+            )
+            .add_system(show_screen_fade);
+    }
+}
+
+/// Sends the player to a spawn point in another level.
+/// Set through [`SpawnEvent::portal`] on a [`GameObject::LevelPortal`].
+#[derive(
+    Debug, Clone, PartialEq, Eq, Default, Component, Reflect, FromReflect, Serialize, Deserialize,
+)]
+#[reflect(Component, Serialize, Deserialize)]
+pub struct LevelPortal {
+    /// Name of the level file without extension, e.g. "old_town"
+    pub target_level: String,
+    /// [`SpawnEvent::name`] of the [`GameObject::SpawnPoint`] in the target level
+    pub target_spawn_point: String,
+}
+
+/// How opaque the black overlay over the whole screen is, from 0 to 1
+#[derive(Debug, Clone, Copy, PartialEq, Resource, Default)]
+pub struct ScreenFade {
+    pub opacity: f32,
+}
+
+/// A level transition in progress. Saving is refused while this exists.
+#[derive(Debug, Clone, PartialEq, Resource)]
+pub struct LevelTransition {
+    pub portal: LevelPortal,
+    phase: TransitionPhase,
+    /// Kind of the camera in the old level, applied to the camera of the new level
+    camera_kind: Option<IngameCameraKind>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TransitionPhase {
+    FadingOut {
+        elapsed: f32,
+    },
+    /// Waiting for the new player to be spawned
+    Loading {
+        old_player: Option<Entity>,
+    },
+    FadingIn {
+        elapsed: f32,
+    },
+}
+
+fn enter_portals(
+    mut commands: Commands,
+    mut collision_events: EventReader<CollisionEvent>,
+    portal_query: Query<&LevelPortal>,
+    player_query: Query<(), With<Player>>,
+    current_dialog: Option<Res<CurrentDialog>>,
+    level_transition: Option<Res<LevelTransition>>,
+    respawning: Option<Res<Respawning>>,
+    mut actions_frozen: ResMut<ActionsFrozen>,
+    levels: Res<Assets<SerializedLevel>>,
+    level_handles: Res<LevelAssets>,
+) -> Result<()> {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("enter_portals").entered();
+    for event in collision_events.iter() {
+        let (entity_a, entity_b) = match event {
+            CollisionEvent::Started(entity_a, entity_b, _) => (*entity_a, *entity_b),
+            CollisionEvent::Stopped(..) => continue,
+        };
+        let portal = match (portal_query.get(entity_a), portal_query.get(entity_b)) {
+            (Ok(portal), _) if player_query.contains(entity_b) => portal,
+            (_, Ok(portal)) if player_query.contains(entity_a) => portal,
+            _ => continue,
+        };
+        if level_transition.is_some() || respawning.is_some() {
+            return Ok(());
+        }
+        if current_dialog.is_some() {
+            info!(
+                "Ignoring level portal to \"{}\" during dialog",
+                portal.target_level
+            );
+            continue;
+        }
+        // Checked up front so that a broken portal does not leave the player in an empty world
+        let level = level_handles
+            .levels
+            .get(&get_level_path(&portal.target_level)?)
+            .and_then(|handle| levels.get(handle))
+            .with_context(|| {
+                format!(
+                    "Failed to use level portal: No such level \"{}\"",
+                    portal.target_level
+                )
+            })?;
+        find_spawn_point(level, &portal.target_spawn_point).with_context(|| {
+            format!(
+                "Failed to use level portal: No spawn point \"{}\" in level \"{}\"",
+                portal.target_spawn_point, portal.target_level
+            )
+        })?;
+        actions_frozen.freeze();
+        commands.insert_resource(LevelTransition {
+            portal: portal.clone(),
+            phase: TransitionPhase::FadingOut { elapsed: 0. },
+            camera_kind: None,
+        });
+        return Ok(());
+    }
+    Ok(())
+}
+
+fn advance_level_transition(
+    mut commands: Commands,
+    time: Res<Time>,
+    level_transition: Option<ResMut<LevelTransition>>,
+    mut screen_fade: ResMut<ScreenFade>,
+    mut actions_frozen: ResMut<ActionsFrozen>,
+    mut world_load_requests: EventWriter<WorldLoadRequest>,
+    mut delayed_spawner: EventWriter<DelayedSpawnEvent>,
+    mut autosave_requests: EventWriter<AutosaveRequest>,
+    mut snap_events: EventWriter<CameraForceSnap>,
+    player_query: Query<(Entity, &Transform), (With<Player>, Without<IngameCamera>)>,
+    mut camera_query: Query<(&mut IngameCamera, &mut Transform), Without<Player>>,
+    levels: Res<Assets<SerializedLevel>>,
+    level_handles: Res<LevelAssets>,
+    config_handles: Res<ConfigAssets>,
+    config: Res<Assets<GameConfig>>,
+) -> Result<()> {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("advance_level_transition").entered();
+    let mut transition = match level_transition {
+        Some(transition) => transition,
+        None => return Ok(()),
+    };
+    let config = config
+        .get(&config_handles.game)
+        .context("Failed to get game config from handle")?;
+    let fade_duration = config.world.fade_duration.max(1e-5);
+    let dt = time.delta_seconds();
+    match transition.phase {
+        TransitionPhase::FadingOut { elapsed } => {
+            let elapsed = elapsed + dt;
+            screen_fade.opacity = (elapsed / fade_duration).min(1.);
+            if elapsed < fade_duration {
+                transition.phase = TransitionPhase::FadingOut { elapsed };
+                return Ok(());
+            }
+            let level = level_handles
+                .levels
+                .get(&get_level_path(&transition.portal.target_level)?)
+                .and_then(|handle| levels.get(handle))
+                .context("Failed to get target level of level transition")?;
+            let spawn_point = find_spawn_point(level, &transition.portal.target_spawn_point)
+                .context("Failed to get target spawn point of level transition")?;
+            transition.camera_kind = camera_query
+                .iter()
+                .next()
+                .map(|(camera, _)| camera.kind.clone());
+            world_load_requests.send(WorldLoadRequest {
+                filename: transition.portal.target_level.clone(),
+                keep_progress: true,
+                ..default()
+            });
+            // Make sure the player is spawned after the level
+            delayed_spawner.send(DelayedSpawnEvent {
+                tick_delay: 2,
+                event: SpawnEvent {
+                    object: GameObject::Player,
+                    transform: spawn_point,
+                    ..default()
+                },
+            });
+            transition.phase = TransitionPhase::Loading {
+                old_player: player_query.iter().next().map(|(entity, _)| entity),
+            };
+        }
+        TransitionPhase::Loading { old_player } => {
+            let player_transform = match player_query
+                .iter()
+                .find(|(entity, _)| Some(*entity) != old_player)
+            {
+                Some((_, transform)) => *transform,
+                None => return Ok(()),
+            };
+            if let Some(camera_kind) = transition.camera_kind.take() {
+                for (mut camera, mut camera_transform) in camera_query.iter_mut() {
+                    camera.kind = camera_kind.clone();
+                    *camera_transform = camera.snap_to(player_transform);
+                }
+            }
+            snap_events.send(CameraForceSnap);
+            transition.phase = TransitionPhase::FadingIn { elapsed: 0. };
+        }
+        TransitionPhase::FadingIn { elapsed } => {
+            let elapsed = elapsed + dt;
+            screen_fade.opacity = 1. - (elapsed / fade_duration).min(1.);
+            if elapsed < fade_duration {
+                transition.phase = TransitionPhase::FadingIn { elapsed };
+                return Ok(());
+            }
+            actions_frozen.unfreeze();
+            commands.remove_resource::<LevelTransition>();
+            autosave_requests.send(AutosaveRequest);
+        }
+    }
+    Ok(())
+}
+
+/// Transform of the [`GameObject::SpawnPoint`] with the given name
+fn find_spawn_point(level: &SerializedLevel, name: &str) -> Option<Transform> {
+    level
+        .0
+        .iter()
+        .find(|event| event.object == GameObject::SpawnPoint && event.name.as_deref() == Some(name))
+        .map(|event| event.transform)
+}
+
+fn show_screen_fade(screen_fade: Res<ScreenFade>, mut egui_context: ResMut<EguiContext>) {
+    if screen_fade.opacity <= 0. {
+        return;
+    }
+    let ctx = egui_context.ctx_mut();
+    let alpha = (screen_fade.opacity.min(1.) * 255.) as u8;
+    egui::Area::new("screen_fade")
+        .order(egui::Order::Foreground)
+        .interactable(false)
+        .fixed_pos(egui::Pos2::ZERO)
+        .show(ctx, |ui| {
+            let screen = ui.ctx().screen_rect();
+            ui.painter()
+                .rect_filled(screen, 0., egui::Color32::from_black_alpha(alpha));
+        });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn finds_spawn_point_by_name() {
+        let spawn_point = |name: &str, x: f32| SpawnEvent {
+            object: GameObject::SpawnPoint,
+            transform: Transform::from_xyz(x, 0., 0.),
+            name: Some(name.to_string()),
+            ..default()
+        };
+        let level = SerializedLevel(vec![
+            SpawnEvent {
+                object: GameObject::Box,
+                name: Some("gate".to_string()),
+                ..default()
+            },
+            spawn_point("entrance", 1.),
+            spawn_point("gate", 2.),
+        ]);
+        assert_eq!(
+            find_spawn_point(&level, "gate"),
+            Some(Transform::from_xyz(2., 0., 0.))
+        );
+        assert_eq!(find_spawn_point(&level, "tower"), None);
+    }
+}
@@ -1,40 +1,80 @@
-use crate::file_system_interaction::level_serialization::{CurrentLevel, WorldLoadRequest};
-use crate::level_instantiation::spawning::{DelayedSpawnEvent, GameObject, SpawnEvent};
+use crate::file_system_interaction::asset_loading::ConfigAssets;
+use crate::file_system_interaction::game_state_serialization::{GameLoadRequest, PlayTime};
+use crate::file_system_interaction::level_serialization::{
+    CurrentLevel, LevelManifest, SelectedLevel, WorldLoadRequest,
+};
+use crate::level_instantiation::level_transition::LevelTransition;
+use crate::level_instantiation::spawning::spawn::{
+    DelayedSpawnEvents, DespawnedObjects, NextRuntimeSpawnId, SpawnQueue,
+};
+use crate::level_instantiation::spawning::{
+    DelayedSpawnEvent, GameObject, SpawnEvent, SpawnTracker,
+};
+use crate::player_control::actions::ActionsFrozen;
+use crate::player_control::camera::RestoredCamera;
+#[cfg(feature = "wasm")]
 use crate::player_control::player_embodiment::Player;
+use crate::util::log_error::log_errors;
+use crate::world_interaction::checkpoint::{LastCheckpoint, Respawning};
+use crate::world_interaction::condition::ActiveConditions;
+use crate::world_interaction::dialog::{CurrentDialog, DialogVariables};
+use crate::world_interaction::health::{Dying, RestoredHealth};
+use crate::world_interaction::interactions_ui::InteractionOpportunities;
+use crate::world_interaction::inventory::Inventory;
+use crate::world_interaction::speedrun::{BestRun, RestoredSpeedrun, SpeedrunTimer};
+use crate::world_interaction::trigger::FiredTriggers;
 use crate::GameState;
+use anyhow::{Context, Result};
 use bevy::prelude::*;
+#[cfg(feature = "wasm")]
 use bevy_egui::{egui, EguiContext};
 
 pub struct MapPlugin;
 
 impl Plugin for MapPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system_set(SystemSet::on_enter(GameState::Playing).with_system(setup))
-            .add_system_set(
-                SystemSet::on_update(GameState::Playing).with_system(show_loading_screen),
-            );
+        app.add_system_set(
+            SystemSet::on_enter(GameState::Playing).with_system(setup.pipe(log_errors)),
+        )
+        .add_system_set(SystemSet::on_exit(GameState::Playing).with_system(teardown));
         #[cfg(feature = "wasm")]
         app.add_system_set(SystemSet::on_update(GameState::Playing).with_system(show_wasm_loader));
     }
 }
 
 fn setup(
-    mut commands: Commands,
     mut loader: EventWriter<WorldLoadRequest>,
     mut delayed_spawner: EventWriter<DelayedSpawnEvent>,
     current_level: Option<Res<CurrentLevel>>,
-) {
+    selected_level: Option<Res<SelectedLevel>>,
+    game_load_requests: EventReader<GameLoadRequest>,
+    config_handles: Res<ConfigAssets>,
+    manifests: Res<Assets<LevelManifest>>,
+) -> Result<()> {
     if current_level.is_some() {
-        return;
+        return Ok(());
     }
 
-    commands.insert_resource(AmbientLight {
-        color: Color::WHITE,
-        brightness: 0.3,
-    });
+    // A save loaded from the main menu brings its own level
+    if !game_load_requests.is_empty() {
+        return Ok(());
+    }
 
+    let manifest = manifests
+        .get(&config_handles.levels)
+        .context("Failed to get level manifest from handle")?;
+    let level = match selected_level {
+        Some(selected_level) => manifest.get(&selected_level.0).with_context(|| {
+            format!("No level with id \"{}\" in the manifest", selected_level.0)
+        })?,
+        None => manifest
+            .levels
+            .first()
+            .context("The level manifest is empty")?,
+    };
     loader.send(WorldLoadRequest {
-        filename: "old_town".to_string(),
+        filename: level.level.clone(),
+        ..default()
     });
 
     // Make sure the player is spawned after the level
@@ -42,26 +82,49 @@ fn setup(
         tick_delay: 2,
         event: SpawnEvent {
             object: GameObject::Player,
-            transform: Transform::from_xyz(0., 1.5, 0.),
+            transform: Transform::from_translation(level.spawn_point),
+            ..default()
         },
     });
+    Ok(())
 }
 
-fn show_loading_screen(player_query: Query<&Player>, mut egui_context: ResMut<EguiContext>) {
-    if player_query.iter().next().is_none() {
-        egui::CentralPanel::default().show(egui_context.ctx_mut(), |ui| {
-            ui.vertical_centered(|ui| {
-                ui.add_space(100.0);
-                ui.heading("Loading");
-                ui.label("Spawning level...");
-                ui.add_space(10.0);
-                #[cfg(feature = "wasm")]
-                ui.add_space(40.0); // Spinner from CSS (build/web/styles.css) goes here.
-                #[cfg(feature = "wasm")]
-                ui.label("This may take a while. Don't worry, your browser did not crash!");
-            });
-        });
+/// Despawns the level and the player when quitting to the main menu and forgets all progress,
+/// so that the next game starts from scratch
+fn teardown(
+    mut commands: Commands,
+    spawned_query: Query<Entity, With<SpawnTracker>>,
+    mut delayed_spawns: ResMut<DelayedSpawnEvents>,
+    mut spawn_queue: ResMut<SpawnQueue>,
+    mut actions_frozen: ResMut<ActionsFrozen>,
+) {
+    for entity in &spawned_query {
+        commands.entity(entity).despawn_recursive();
     }
+    delayed_spawns.clear();
+    spawn_queue.clear();
+    // Dialogs, level transitions, deaths and respawns hold freezes that they will never release now
+    *actions_frozen = default();
+    commands.remove_resource::<CurrentLevel>();
+    commands.remove_resource::<SelectedLevel>();
+    commands.remove_resource::<CurrentDialog>();
+    commands.remove_resource::<RestoredCamera>();
+    commands.remove_resource::<RestoredHealth>();
+    commands.remove_resource::<LevelTransition>();
+    commands.remove_resource::<Respawning>();
+    commands.remove_resource::<Dying>();
+    commands.remove_resource::<RestoredSpeedrun>();
+    commands.insert_resource(DespawnedObjects::default());
+    commands.insert_resource(NextRuntimeSpawnId::default());
+    commands.insert_resource(InteractionOpportunities::default());
+    commands.insert_resource(ActiveConditions::default());
+    commands.insert_resource(FiredTriggers::default());
+    commands.insert_resource(Inventory::default());
+    commands.insert_resource(DialogVariables::default());
+    commands.insert_resource(LastCheckpoint::default());
+    commands.insert_resource(PlayTime::default());
+    commands.insert_resource(SpeedrunTimer::default());
+    commands.insert_resource(BestRun::default());
 }
 
 #[cfg(feature = "wasm")]
@@ -1,24 +1,35 @@
 use crate::file_system_interaction::asset_loading::{AnimationAssets, SceneAssets};
+use crate::level_instantiation::level_transition::LevelPortal;
 use crate::level_instantiation::spawning::animation_link::link_animations;
 use crate::level_instantiation::spawning::objects::camera::CameraSpawner;
+use crate::level_instantiation::spawning::objects::checkpoint::CheckpointSpawner;
 use crate::level_instantiation::spawning::objects::level::LevelSpawner;
 use crate::level_instantiation::spawning::objects::npc::NpcSpawner;
 use crate::level_instantiation::spawning::objects::orb::OrbSpawner;
+use crate::level_instantiation::spawning::objects::platform::MovingPlatformSpawner;
 use crate::level_instantiation::spawning::objects::player::PlayerSpawner;
 use crate::level_instantiation::spawning::objects::point_light::PointLightSpawner;
+use crate::level_instantiation::spawning::objects::portal::{
+    LevelPortalSpawner, SpawnPointSpawner,
+};
 use crate::level_instantiation::spawning::objects::primitives::{
     BoxSpawner, CapsuleSpawner, EmptySpawner, SphereSpawner, TriangleSpawner,
 };
 use crate::level_instantiation::spawning::objects::skydome::SkydomeSpawner;
-use crate::level_instantiation::spawning::objects::sunlight::SunlightSpawner;
+use crate::level_instantiation::spawning::objects::sunlight::{Sun, SunlightSpawner};
+use crate::level_instantiation::spawning::objects::trigger::TriggerSpawner;
 use crate::level_instantiation::spawning::post_spawn_modification::{
     despawn_removed, set_color, set_hidden, set_shadows,
 };
 use crate::level_instantiation::spawning::spawn::{
-    despawn, spawn_delayed, spawn_requested, DelayedSpawnEvents, Despawn,
+    despawn, handle_despawn_requests, handle_spawn_requests, spawn_delayed, spawn_requested,
+    DelayedSpawnEvents, Despawn, DespawnedObjects, LevelOwned, NextRuntimeSpawnId, SpawnQueue,
 };
 use crate::shader::Materials;
 use crate::util::log_error::log_errors;
+use crate::world_interaction::animated_prop::AnimatedProp;
+use crate::world_interaction::inventory::Item;
+use crate::world_interaction::trigger::Trigger;
 use crate::GameState;
 pub use animation_link::AnimationEntityLink;
 use anyhow::Result;
@@ -43,21 +54,37 @@ impl Plugin for SpawningPlugin {
     fn build(&self, app: &mut App) {
         app.add_event::<SpawnEvent>();
         app.add_event::<DelayedSpawnEvent>()
+            .add_event::<SpawnRequest>()
+            .add_event::<DespawnRequest>()
             .init_resource::<DelayedSpawnEvents>()
+            .init_resource::<NextRuntimeSpawnId>()
+            .init_resource::<DespawnedObjects>()
+            .init_resource::<SpawnQueue>()
             .register_type::<DelayedSpawnEvent>()
             .register_type::<SpawnEvent>()
             .register_type::<SpawnTracker>()
+            .register_type::<LevelOwned>()
+            .register_type::<WaypointPath>()
+            .register_type::<SpawnId>()
+            .register_type::<SpawnRequest>()
+            .register_type::<NextRuntimeSpawnId>()
+            .register_type::<DespawnedObjects>()
+            .register_type::<PatrolRoute>()
+            .register_type::<PatrolMode>()
             .register_type::<Despawn>()
             .register_type::<DelayedSpawnEvents>()
             .register_type::<AnimationEntityLink>()
+            .register_type::<Sun>()
             .add_system_set(
                 SystemSet::on_exit(GameState::Loading).with_system(load_assets_for_spawner),
             )
             .add_system_set(
                 SystemSet::on_update(GameState::Playing)
                     .with_system(spawn_requested.pipe(log_errors).label(SpawnRequestedLabel))
+                    .with_system(handle_spawn_requests.before(SpawnRequestedLabel))
                     .with_system(spawn_delayed)
                     .with_system(despawn)
+                    .with_system(handle_despawn_requests)
                     .with_system(link_animations.after(SpawnRequestedLabel)),
             )
             .add_system_set(
@@ -99,6 +126,11 @@ fn load_assets_for_spawner(mut commands: Commands, mut mesh_assets: ResMut<Asset
                 GameObject::Camera => Box::new(CameraSpawner),
                 GameObject::Level => Box::new(LevelSpawner),
                 GameObject::Skydome => Box::new(SkydomeSpawner),
+                GameObject::MovingPlatform => Box::new(MovingPlatformSpawner),
+                GameObject::Trigger => Box::new(TriggerSpawner),
+                GameObject::LevelPortal => Box::new(LevelPortalSpawner),
+                GameObject::SpawnPoint => Box::new(SpawnPointSpawner),
+                GameObject::Checkpoint => Box::new(CheckpointSpawner),
             };
         implementors.insert(game_object, implementor);
     }
@@ -124,12 +156,44 @@ fn load_assets_for_spawner(mut commands: Commands, mut mesh_assets: ResMut<Asset
 #[reflect(Component, Serialize, Deserialize)]
 pub struct SpawnTracker {
     pub object: GameObject,
+    pub path: Option<WaypointPath>,
+    pub patrol: Option<PatrolRoute>,
+    pub item: Option<Item>,
+    pub trigger: Option<Trigger>,
+    pub name: Option<String>,
+    pub portal: Option<LevelPortal>,
+    pub prop: Option<AnimatedProp>,
+}
+
+impl SpawnTracker {
+    /// The event that spawns this object again
+    pub fn to_spawn_event(&self, id: Option<SpawnId>, transform: Transform) -> SpawnEvent {
+        SpawnEvent {
+            object: self.object,
+            transform,
+            path: self.path.clone(),
+            patrol: self.patrol.clone(),
+            id,
+            item: self.item.clone(),
+            trigger: self.trigger.clone(),
+            name: self.name.clone(),
+            portal: self.portal.clone(),
+            prop: self.prop.clone(),
+        }
+    }
 }
 
 impl From<SpawnEvent> for SpawnTracker {
     fn from(value: SpawnEvent) -> Self {
         Self {
             object: value.object,
+            path: value.path,
+            patrol: value.patrol,
+            item: value.item,
+            trigger: value.trigger,
+            name: value.name,
+            portal: value.portal,
+            prop: value.prop,
         }
     }
 }
@@ -163,6 +227,11 @@ pub enum GameObject {
     Orb,
     Camera,
     Skydome,
+    MovingPlatform,
+    Trigger,
+    LevelPortal,
+    SpawnPoint,
+    Checkpoint,
 }
 
 impl Default for GameObject {
@@ -175,6 +244,8 @@ pub trait PrimedGameObjectSpawnerImplementor {
     fn create_mesh(&self, _mesh_assets: &mut ResMut<Assets<Mesh>>) -> Option<Handle<Mesh>> {
         None
     }
+    /// Spawns the object with the whole transform, including its scale. Colliders are defined in unscaled local space,
+    /// rapier scales them by their global transform.
     fn spawn<'a, 'b: 'a>(
         &self,
         spawner: &'b mut PrimedGameObjectSpawner<'_, '_, 'a>,
@@ -189,6 +260,22 @@ pub struct GameObjectSpawner {
     implementors: HashMap<GameObject, Box<dyn PrimedGameObjectSpawnerImplementor + Send + Sync>>,
 }
 
+impl GameObjectSpawner {
+    /// Spawner without any meshes, for apps that don't load assets
+    #[cfg(test)]
+    pub(crate) fn from_implementors(
+        implementors: HashMap<
+            GameObject,
+            Box<dyn PrimedGameObjectSpawnerImplementor + Send + Sync>,
+        >,
+    ) -> Self {
+        Self {
+            meshes: HashMap::new(),
+            implementors,
+        }
+    }
+}
+
 #[derive(Resource)]
 pub struct PrimedGameObjectSpawner<'w, 's, 'a> {
     pub outer_spawner: &'a GameObjectSpawner,
@@ -1,4 +1,8 @@
+use crate::level_instantiation::level_transition::LevelPortal;
 use crate::level_instantiation::spawning::GameObject;
+use crate::world_interaction::animated_prop::AnimatedProp;
+use crate::world_interaction::inventory::Item;
+use crate::world_interaction::trigger::Trigger;
 use bevy::prelude::*;
 use serde::{Deserialize, Serialize};
 
@@ -9,6 +13,121 @@ use serde::{Deserialize, Serialize};
 pub struct SpawnEvent {
     pub object: GameObject,
     pub transform: Transform,
+    /// Path the spawned object follows, e.g. for a [`GameObject::MovingPlatform`].
+    /// Inserted into the spawned entity as a component.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<WaypointPath>,
+    /// Route an NPC patrols along, e.g. for a [`GameObject::Npc`].
+    /// Inserted into the spawned entity as a component.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub patrol: Option<PatrolRoute>,
+    /// Stable identifier of the spawned object. Assigned when the level is loaded or a [`SpawnRequest`] is handled.
+    /// Inserted into the spawned entity as a component.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<SpawnId>,
+    /// Makes the spawned object an item that the player can pick up
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub item: Option<Item>,
+    /// What happens when the player enters the spawned object, e.g. for a [`GameObject::Trigger`].
+    /// Inserted into the spawned entity as a component.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trigger: Option<Trigger>,
+    /// Replaces the default name of the spawned object. Used to refer to a [`GameObject::SpawnPoint`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Where the player is sent when entering the spawned object, e.g. for a [`GameObject::LevelPortal`].
+    /// Inserted into the spawned entity as a component.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub portal: Option<LevelPortal>,
+    /// Makes the spawned object open and close, e.g. a door made of a [`GameObject::Box`].
+    /// Inserted into the spawned entity as a component.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prop: Option<AnimatedProp>,
+}
+
+/// Identifies a spawned object across saving and loading
+#[derive(
+    Debug, Component, Clone, Copy, PartialEq, Eq, Hash, Reflect, FromReflect, Serialize, Deserialize,
+)]
+#[reflect(Component, Serialize, Deserialize)]
+pub enum SpawnId {
+    /// Object spawned by the level. Holds the index of its [`SpawnEvent`] in the level file.
+    Level(usize),
+    /// Object spawned at runtime through a [`SpawnRequest`]
+    Runtime(u64),
+}
+
+impl Default for SpawnId {
+    fn default() -> Self {
+        Self::Level(0)
+    }
+}
+
+impl SpawnId {
+    pub fn is_runtime(&self) -> bool {
+        matches!(self, SpawnId::Runtime(_))
+    }
+}
+
+/// Spawns an object during gameplay, e.g. from a trigger, a dialog or the dev editor.
+/// Unlike the objects of the level, these are stored in save files.
+#[derive(Debug, Clone, PartialEq, Default, Reflect, FromReflect, Serialize, Deserialize)]
+#[reflect(Serialize, Deserialize)]
+pub struct SpawnRequest {
+    pub object: GameObject,
+    pub transform: Transform,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub item: Option<Item>,
+}
+
+/// Despawns a spawned object. Despawned level objects are remembered in save files so that they stay gone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DespawnRequest {
+    Entity(Entity),
+    Id(SpawnId),
+}
+
+/// A looping path through a list of points.
+#[derive(
+    Debug, Component, Clone, PartialEq, Default, Reflect, FromReflect, Serialize, Deserialize,
+)]
+#[reflect(Component, Serialize, Deserialize)]
+pub struct WaypointPath {
+    /// Points in world space, visited in order. After the last one, the path starts again at the first one.
+    pub waypoints: Vec<Vec3>,
+    /// Speed in m/s
+    pub speed: f32,
+}
+
+/// A route an NPC walks along while it is not following the player.
+#[derive(
+    Debug, Component, Clone, PartialEq, Default, Reflect, FromReflect, Serialize, Deserialize,
+)]
+#[reflect(Component, Serialize, Deserialize)]
+pub struct PatrolRoute {
+    /// Name of the route. Unless [`PatrolRoute::waypoints`] are given, they are collected from the
+    /// empty nodes in the level scene named `Patrol.<name>.<index>`, e.g. `Patrol.guard.0`, `Patrol.guard.1`.
+    pub name: String,
+    /// Points in world space, visited in order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub waypoints: Vec<Vec3>,
+    #[serde(default)]
+    pub mode: PatrolMode,
+    /// Time in seconds spent waiting at each waypoint
+    #[serde(default)]
+    pub pause: f32,
+}
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Default, Reflect, FromReflect, Serialize, Deserialize,
+)]
+#[reflect(Serialize, Deserialize)]
+pub enum PatrolMode {
+    /// After the last waypoint, the route starts again at the first one
+    #[default]
+    Loop,
+    /// After the last waypoint, the route is walked backwards to the first one and so on
+    PingPong,
 }
 
 #[derive(
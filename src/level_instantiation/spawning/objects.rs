@@ -2,14 +2,18 @@ use bevy_rapier3d::prelude::*;
 use bitflags::bitflags;
 
 pub mod camera;
+pub mod checkpoint;
 pub mod level;
 pub mod npc;
 pub mod orb;
+pub mod platform;
 pub mod player;
 pub mod point_light;
+pub mod portal;
 pub mod primitives;
 pub mod skydome;
 pub mod sunlight;
+pub mod trigger;
 
 bitflags! {
     pub struct GameCollisionGroup: u32 {
@@ -2,7 +2,7 @@ use crate::level_instantiation::spawning::{
     GameObject, PrimedGameObjectSpawner, PrimedGameObjectSpawnerImplementor,
 };
 use crate::player_control::actions::create_camera_action_input_manager_bundle;
-use crate::player_control::camera::IngameCamera;
+use crate::player_control::camera::{DynamicFov, FixedStepCamera, IngameCamera, LandingDip};
 use anyhow::Result;
 use bevy::prelude::*;
 
@@ -19,6 +19,9 @@ impl PrimedGameObjectSpawnerImplementor for CameraSpawner {
             .commands
             .spawn((
                 IngameCamera::default(),
+                LandingDip::default(),
+                DynamicFov::default(),
+                FixedStepCamera::default(),
                 Camera3dBundle {
                     transform,
                     ..default()
@@ -0,0 +1,37 @@
+use crate::level_instantiation::spawning::objects::GameCollisionGroup;
+use crate::level_instantiation::spawning::{
+    GameObject, PrimedGameObjectSpawner, PrimedGameObjectSpawnerImplementor,
+};
+use crate::world_interaction::checkpoint::Checkpoint;
+use anyhow::Result;
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+/// Spawns the volume of a [`Checkpoint`].
+/// The volume is a box with half extents of 1 m, scaled by the checkpoint's transform.
+pub struct CheckpointSpawner;
+
+impl PrimedGameObjectSpawnerImplementor for CheckpointSpawner {
+    fn spawn<'a, 'b: 'a>(
+        &self,
+        spawner: &'b mut PrimedGameObjectSpawner<'_, '_, 'a>,
+        _object: GameObject,
+        transform: Transform,
+    ) -> Result<Entity> {
+        Ok(spawner
+            .commands
+            .spawn((
+                TransformBundle::from_transform(transform),
+                Name::new("Checkpoint"),
+                Checkpoint,
+                Collider::cuboid(1., 1., 1.),
+                Sensor,
+                ActiveEvents::COLLISION_EVENTS,
+                CollisionGroups::new(
+                    GameCollisionGroup::OTHER.into(),
+                    GameCollisionGroup::PLAYER.into(),
+                ),
+            ))
+            .id())
+    }
+}
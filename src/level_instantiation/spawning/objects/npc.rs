@@ -1,10 +1,13 @@
+use crate::file_system_interaction::audio::AudioEmitter;
 use crate::level_instantiation::spawning::objects::GameCollisionGroup;
 use crate::level_instantiation::spawning::{
     GameObject, PrimedGameObjectSpawner, PrimedGameObjectSpawnerImplementor,
 };
+use crate::movement::activity::Activity;
 use crate::movement::general_movement::{CharacterAnimations, CharacterControllerBundle, Model};
-use crate::movement::navigation::Follower;
+use crate::movement::navigation::{Follower, NavigationPath, PathTarget};
 use crate::world_interaction::dialog::{DialogId, DialogTarget};
+use crate::world_interaction::look_at::LookAtTarget;
 use anyhow::Result;
 use bevy::prelude::*;
 use bevy_rapier3d::prelude::*;
@@ -32,14 +35,25 @@ impl PrimedGameObjectSpawnerImplementor for NpcSpawner {
                 Name::new("NPC"),
                 CharacterControllerBundle::capsule(HEIGHT, RADIUS),
                 Follower,
+                PathTarget::default(),
+                NavigationPath::default(),
+                // The fox has no dedicated clips for jumping, landing and crouching
                 CharacterAnimations {
                     idle: spawner.animations.character_idle.clone(),
                     walk: spawner.animations.character_walking.clone(),
+                    run: spawner.animations.character_running.clone(),
+                    jump: spawner.animations.character_running.clone(),
                     aerial: spawner.animations.character_running.clone(),
+                    land: spawner.animations.character_idle.clone(),
+                    crouch_idle: spawner.animations.character_idle.clone(),
+                    crouch_walk: spawner.animations.character_walking.clone(),
                 },
                 DialogTarget {
                     dialog_id: DialogId::new("follower"),
                 },
+                AudioEmitter::default(),
+                Activity::default(),
+                LookAtTarget::default(),
             ))
             .with_children(|parent| {
                 parent.spawn((
@@ -1,3 +1,4 @@
+use crate::graphics_quality::QualityLight;
 use crate::level_instantiation::spawning::{
     GameObject, PrimedGameObjectSpawner, PrimedGameObjectSpawnerImplementor,
 };
@@ -34,16 +35,19 @@ impl PrimedGameObjectSpawnerImplementor for OrbSpawner {
                 NotShadowReceiver,
             ))
             .with_children(|parent| {
-                parent.spawn((PointLightBundle {
-                    point_light: PointLight {
-                        intensity: 10_000.,
-                        radius: 1.,
-                        color: Color::rgb(0.5, 0.1, 0.),
-                        shadows_enabled: true,
+                parent.spawn((
+                    PointLightBundle {
+                        point_light: PointLight {
+                            intensity: 10_000.,
+                            radius: 1.,
+                            color: Color::rgb(0.5, 0.1, 0.),
+                            shadows_enabled: true,
+                            ..default()
+                        },
                         ..default()
                     },
-                    ..default()
-                },));
+                    QualityLight,
+                ));
             })
             .id())
     }
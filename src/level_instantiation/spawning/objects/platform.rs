@@ -0,0 +1,45 @@
+use crate::level_instantiation::spawning::{
+    GameObject, PrimedGameObjectSpawner, PrimedGameObjectSpawnerImplementor,
+};
+use crate::movement::platform::MovingPlatform;
+use anyhow::Result;
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+const HALF_EXTENTS: Vec3 = Vec3::new(1.5, 0.15, 1.5);
+
+pub struct MovingPlatformSpawner;
+
+impl PrimedGameObjectSpawnerImplementor for MovingPlatformSpawner {
+    fn create_mesh(&self, mesh_assets: &mut ResMut<Assets<Mesh>>) -> Option<Handle<Mesh>> {
+        Some(mesh_assets.add(Mesh::from(shape::Box::new(
+            HALF_EXTENTS.x * 2.,
+            HALF_EXTENTS.y * 2.,
+            HALF_EXTENTS.z * 2.,
+        ))))
+    }
+
+    fn spawn<'a, 'b: 'a>(
+        &self,
+        spawner: &'b mut PrimedGameObjectSpawner<'_, '_, 'a>,
+        object: GameObject,
+        transform: Transform,
+    ) -> Result<Entity> {
+        Ok(spawner
+            .commands
+            .spawn((
+                PbrBundle {
+                    mesh: spawner.outer_spawner.meshes[&object].clone(),
+                    material: spawner.materials.platform.clone(),
+                    transform,
+                    ..default()
+                },
+                Name::new("Moving Platform"),
+                RigidBody::KinematicVelocityBased,
+                Collider::cuboid(HALF_EXTENTS.x, HALF_EXTENTS.y, HALF_EXTENTS.z),
+                Velocity::default(),
+                MovingPlatform::default(),
+            ))
+            .id())
+    }
+}
@@ -2,11 +2,19 @@ use crate::level_instantiation::spawning::objects::GameCollisionGroup;
 use crate::level_instantiation::spawning::{
     GameObject, PrimedGameObjectSpawner, PrimedGameObjectSpawnerImplementor,
 };
-use crate::movement::general_movement::{CharacterAnimations, CharacterControllerBundle, Model};
+use crate::movement::climbing::Climbing;
+use crate::movement::dash::Dashing;
+use crate::movement::footsteps::FootstepTracker;
+use crate::movement::general_movement::{
+    AirJumps, CharacterAnimations, CharacterControllerBundle, Model,
+};
+use crate::movement::water::Swimming;
 use crate::player_control::actions::{
     create_player_action_input_manager_bundle, create_ui_action_input_manager_bundle,
 };
-use crate::player_control::player_embodiment::Player;
+use crate::player_control::grapple::Grapple;
+use crate::player_control::player_embodiment::{Player, WallContact};
+use crate::world_interaction::health::Health;
 use anyhow::Result;
 use bevy::prelude::*;
 use bevy_rapier3d::prelude::*;
@@ -34,10 +42,27 @@ impl PrimedGameObjectSpawnerImplementor for PlayerSpawner {
                 Player,
                 Name::new("Player"),
                 CharacterControllerBundle::capsule(HEIGHT, RADIUS),
+                // Nested, as bundles are limited to 15 elements
+                (
+                    AirJumps::default(),
+                    WallContact::default(),
+                    Swimming::default(),
+                    Climbing::default(),
+                    Dashing::default(),
+                    Grapple::default(),
+                ),
+                Health::default(),
+                FootstepTracker::default(),
+                // The fox has no dedicated clips for jumping, landing and crouching
                 CharacterAnimations {
                     idle: spawner.animations.character_idle.clone(),
                     walk: spawner.animations.character_walking.clone(),
+                    run: spawner.animations.character_running.clone(),
+                    jump: spawner.animations.character_running.clone(),
                     aerial: spawner.animations.character_running.clone(),
+                    land: spawner.animations.character_idle.clone(),
+                    crouch_idle: spawner.animations.character_idle.clone(),
+                    crouch_walk: spawner.animations.character_walking.clone(),
                 },
                 CollisionGroups::new(
                     GameCollisionGroup::PLAYER.into(),
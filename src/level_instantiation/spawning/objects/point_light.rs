@@ -1,3 +1,4 @@
+use crate::graphics_quality::QualityLight;
 use crate::level_instantiation::spawning::{
     GameObject, PrimedGameObjectSpawner, PrimedGameObjectSpawnerImplementor,
 };
@@ -28,6 +29,7 @@ impl PrimedGameObjectSpawnerImplementor for PointLightSpawner {
                     transform,
                     ..default()
                 },
+                QualityLight,
                 Name::new("Light"),
             ))
             .id())
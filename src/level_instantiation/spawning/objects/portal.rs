@@ -0,0 +1,56 @@
+use crate::level_instantiation::spawning::objects::GameCollisionGroup;
+use crate::level_instantiation::spawning::{
+    GameObject, PrimedGameObjectSpawner, PrimedGameObjectSpawnerImplementor,
+};
+use anyhow::Result;
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+/// Spawns the volume of a [`LevelPortal`](crate::level_instantiation::level_transition::LevelPortal).
+/// The target is set through [`SpawnEvent::portal`](crate::level_instantiation::spawning::SpawnEvent::portal).
+pub struct LevelPortalSpawner;
+
+impl PrimedGameObjectSpawnerImplementor for LevelPortalSpawner {
+    fn spawn<'a, 'b: 'a>(
+        &self,
+        spawner: &'b mut PrimedGameObjectSpawner<'_, '_, 'a>,
+        _object: GameObject,
+        transform: Transform,
+    ) -> Result<Entity> {
+        Ok(spawner
+            .commands
+            .spawn((
+                TransformBundle::from_transform(transform),
+                Name::new("Level Portal"),
+                Collider::cuboid(1., 1., 1.),
+                Sensor,
+                ActiveEvents::COLLISION_EVENTS,
+                CollisionGroups::new(
+                    GameCollisionGroup::OTHER.into(),
+                    GameCollisionGroup::PLAYER.into(),
+                ),
+            ))
+            .id())
+    }
+}
+
+/// Spawns a point where the player arrives when coming through a level portal.
+/// It is found through its [`SpawnEvent::name`](crate::level_instantiation::spawning::SpawnEvent::name).
+pub struct SpawnPointSpawner;
+
+impl PrimedGameObjectSpawnerImplementor for SpawnPointSpawner {
+    fn spawn<'a, 'b: 'a>(
+        &self,
+        spawner: &'b mut PrimedGameObjectSpawner<'_, '_, 'a>,
+        _object: GameObject,
+        transform: Transform,
+    ) -> Result<Entity> {
+        Ok(spawner
+            .commands
+            .spawn((
+                TransformBundle::from_transform(transform),
+                Name::new("Spawn Point"),
+            ))
+            .id())
+    }
+}
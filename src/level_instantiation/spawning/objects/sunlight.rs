@@ -1,8 +1,15 @@
+use crate::graphics_quality::QualityLight;
 use crate::level_instantiation::spawning::{
     GameObject, PrimedGameObjectSpawner, PrimedGameObjectSpawnerImplementor,
 };
 use anyhow::Result;
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// The light that the [`TimeOfDay`](crate::world_interaction::time_of_day::TimeOfDay) moves across the sky
+#[derive(Debug, Component, Clone, Copy, Serialize, Deserialize, Reflect, FromReflect, Default)]
+#[reflect(Component, Serialize, Deserialize)]
+pub struct Sun;
 
 pub struct SunlightSpawner;
 
@@ -36,6 +43,8 @@ impl PrimedGameObjectSpawnerImplementor for SunlightSpawner {
                     transform,
                     ..default()
                 },
+                QualityLight,
+                Sun,
                 Name::new("Light"),
             ))
             .id())
@@ -0,0 +1,26 @@
+use crate::level_instantiation::spawning::{
+    GameObject, PrimedGameObjectSpawner, PrimedGameObjectSpawnerImplementor,
+};
+use anyhow::Result;
+use bevy::prelude::*;
+
+/// Spawns the volume of a [`Trigger`](crate::world_interaction::trigger::Trigger).
+/// The trigger itself is set through [`SpawnEvent::trigger`](crate::level_instantiation::spawning::SpawnEvent::trigger).
+pub struct TriggerSpawner;
+
+impl PrimedGameObjectSpawnerImplementor for TriggerSpawner {
+    fn spawn<'a, 'b: 'a>(
+        &self,
+        spawner: &'b mut PrimedGameObjectSpawner<'_, '_, 'a>,
+        _object: GameObject,
+        transform: Transform,
+    ) -> Result<Entity> {
+        Ok(spawner
+            .commands
+            .spawn((
+                TransformBundle::from_transform(transform),
+                Name::new("Trigger"),
+            ))
+            .id())
+    }
+}
@@ -1,11 +1,23 @@
-use crate::file_system_interaction::asset_loading::{AnimationAssets, SceneAssets};
-use crate::level_instantiation::spawning::event::SpawnEvent;
-use crate::level_instantiation::spawning::{DelayedSpawnEvent, GameObjectSpawner, SpawnTracker};
+use crate::file_system_interaction::asset_loading::{AnimationAssets, ConfigAssets, SceneAssets};
+use crate::file_system_interaction::config::GameConfig;
+use crate::level_instantiation::spawning::event::{
+    DespawnRequest, SpawnEvent, SpawnId, SpawnRequest,
+};
+use crate::level_instantiation::spawning::{
+    DelayedSpawnEvent, GameObject, GameObjectSpawner, SpawnTracker,
+};
+use crate::movement::patrol::PatrolProgress;
 use crate::shader::Materials;
-use anyhow::Result;
+use crate::world_interaction::animated_prop::{insert_prop, PropState};
+use crate::world_interaction::inventory::insert_item;
+use anyhow::{Context, Result};
 use bevy::prelude::*;
+use bevy::utils::Instant;
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
 
+/// Spawns the objects of the [`SpawnQueue`] in order of their [`SpawnPriority`] until `streaming.frame_budget_ms` is used up.
+/// Note that this only bounds the time spent here; scenes are instantiated by bevy in a later stage.
 pub fn spawn_requested(
     mut commands: Commands,
     scenes: Res<Assets<Scene>>,
@@ -14,8 +26,23 @@ pub fn spawn_requested(
     spawner: Res<GameObjectSpawner>,
     animations: Res<AnimationAssets>,
     scene_handles: Res<SceneAssets>,
+    mut spawn_queue: ResMut<SpawnQueue>,
+    config_handles: Res<ConfigAssets>,
+    config: Res<Assets<GameConfig>>,
 ) -> Result<()> {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("spawn_requested").entered();
+    let config = config
+        .get(&config_handles.game)
+        .context("Failed to get game config from handle")?;
     for spawn in spawn_requests.iter() {
+        spawn_queue.push(spawn.clone());
+    }
+    spawn_queue.playable_radius = config.streaming.playable_radius;
+    let budget = Duration::from_secs_f32(config.streaming.frame_budget_ms.max(0.) / 1000.);
+    let frame_start = Instant::now();
+    let mut spawned = 0;
+    while let Some(spawn) = spawn_queue.pop() {
         let entity = spawner
             .attach(
                 &mut commands,
@@ -28,10 +55,289 @@ pub fn spawn_requested(
         commands
             .entity(entity)
             .insert(SpawnTracker::from(spawn.clone()));
+        if let Some(path) = &spawn.path {
+            commands.entity(entity).insert(path.clone());
+        }
+        if let Some(id) = spawn.id {
+            commands.entity(entity).insert(id);
+            if !id.is_runtime() {
+                commands.entity(entity).insert(LevelOwned);
+            }
+        }
+        if let Some(patrol) = &spawn.patrol {
+            commands
+                .entity(entity)
+                .insert((patrol.clone(), PatrolProgress::default()));
+        }
+        if let Some(item) = &spawn.item {
+            insert_item(&mut commands.entity(entity), item.clone());
+        }
+        if let Some(trigger) = &spawn.trigger {
+            commands.entity(entity).insert(trigger.clone());
+        }
+        if let Some(name) = &spawn.name {
+            commands.entity(entity).insert(Name::new(name.clone()));
+        }
+        if let Some(portal) = &spawn.portal {
+            commands.entity(entity).insert(portal.clone());
+        }
+        if let Some(prop) = &spawn.prop {
+            insert_prop(&mut commands.entity(entity), prop.clone());
+        }
+        spawned += 1;
+        if frame_start.elapsed() >= budget {
+            break;
+        }
     }
+    spawn_queue.record_frame(spawned, frame_start.elapsed());
     Ok(())
 }
 
+/// Order in which queued objects are spawned, highest first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SpawnPriority {
+    /// Spawned after the level has become playable
+    Distant,
+    /// Within `streaming.playable_radius` of the player's spawn point
+    Nearby,
+    /// The player, the camera, lights and the level's geometry
+    Essential,
+}
+
+impl SpawnPriority {
+    pub fn of(spawn: &SpawnEvent, focus: Option<Vec3>, playable_radius: f32) -> Self {
+        match spawn.object {
+            GameObject::Player
+            | GameObject::Camera
+            | GameObject::Sunlight
+            | GameObject::PointLight
+            | GameObject::Skydome
+            | GameObject::Level => Self::Essential,
+            _ => match focus {
+                Some(focus) if spawn.transform.translation.distance(focus) <= playable_radius => {
+                    Self::Nearby
+                }
+                _ => Self::Distant,
+            },
+        }
+    }
+}
+
+/// Spawn events that have not been spawned yet, see [`spawn_requested`].
+/// The level is playable once everything but the [`SpawnPriority::Distant`] objects is spawned,
+/// the loading screen waits for that while the rest keeps streaming in.
+#[derive(Debug, Resource, Default)]
+pub struct SpawnQueue {
+    pending: Vec<SpawnEvent>,
+    /// Where the player is spawned, known as soon as its spawn event is queued
+    focus: Option<Vec3>,
+    pub playable_radius: f32,
+    /// Whether `pending` has to be sorted again before popping
+    unsorted: bool,
+    /// `None` outside of loading a level
+    stats: Option<StreamingStats>,
+}
+
+/// Timings of spawning a level, logged when it becomes playable and when it is fully spawned
+#[derive(Debug, Clone, Copy)]
+struct StreamingStats {
+    started: Instant,
+    spawned: usize,
+    longest_frame: Duration,
+    became_playable: bool,
+}
+
+impl SpawnQueue {
+    pub fn push(&mut self, spawn: SpawnEvent) {
+        if spawn.object == GameObject::Player {
+            self.focus = Some(spawn.transform.translation);
+        }
+        self.pending.push(spawn);
+        self.unsorted = true;
+    }
+
+    /// Takes the queued object with the highest priority, the closest one to the player's spawn point among equals
+    pub fn pop(&mut self) -> Option<SpawnEvent> {
+        if self.unsorted {
+            let (focus, playable_radius) = (self.focus, self.playable_radius);
+            let key = |spawn: &SpawnEvent| {
+                let distance = focus
+                    .map(|focus| spawn.transform.translation.distance(focus))
+                    .unwrap_or_default();
+                (SpawnPriority::of(spawn, focus, playable_radius), -distance)
+            };
+            // Popped from the back
+            self.pending.sort_by(|a, b| {
+                let (a_priority, a_distance) = key(a);
+                let (b_priority, b_distance) = key(b);
+                a_priority
+                    .cmp(&b_priority)
+                    .then(a_distance.total_cmp(&b_distance))
+            });
+            self.unsorted = false;
+        }
+        self.pending.pop()
+    }
+
+    pub fn is_playable(&self) -> bool {
+        self.focus.is_some()
+            && self.pending.iter().all(|spawn| {
+                SpawnPriority::of(spawn, self.focus, self.playable_radius) == SpawnPriority::Distant
+            })
+    }
+
+    pub fn clear(&mut self) {
+        self.pending.clear();
+        self.focus = None;
+        self.stats = None;
+    }
+
+    /// Forgets the previous level and starts timing the spawning of the next one
+    pub fn start_level(&mut self) {
+        self.clear();
+        self.stats = Some(StreamingStats {
+            started: Instant::now(),
+            spawned: 0,
+            longest_frame: Duration::ZERO,
+            became_playable: false,
+        });
+    }
+
+    fn record_frame(&mut self, spawned: usize, frame_time: Duration) {
+        let is_playable = self.is_playable();
+        let is_done = self.pending.is_empty();
+        let stats = match self.stats.as_mut() {
+            Some(stats) if spawned > 0 => stats,
+            _ => return,
+        };
+        stats.spawned += spawned;
+        stats.longest_frame = stats.longest_frame.max(frame_time);
+        if is_playable && !stats.became_playable {
+            stats.became_playable = true;
+            info!(
+                "Level became playable after {:.1} ms with {} objects spawned",
+                stats.started.elapsed().as_secs_f32() * 1000.,
+                stats.spawned
+            );
+        }
+        if is_playable && is_done {
+            info!(
+                "Level was fully spawned after {:.1} ms with {} objects, the longest frame spent {:.1} ms spawning",
+                stats.started.elapsed().as_secs_f32() * 1000.,
+                stats.spawned,
+                stats.longest_frame.as_secs_f32() * 1000.
+            );
+            self.stats = None;
+        }
+    }
+}
+
+/// Marks the objects spawned from the level file, i.e. those with a [`SpawnId::Level`].
+/// Runtime spawns and the player are not level owned.
+#[derive(
+    Debug, Component, Clone, Copy, PartialEq, Eq, Default, Reflect, Serialize, Deserialize,
+)]
+#[reflect(Component, Serialize, Deserialize)]
+pub struct LevelOwned;
+
+/// Counter for the [`SpawnId::Runtime`] of the next object spawned through a [`SpawnRequest`]
+#[derive(Debug, Resource, Clone, PartialEq, Eq, Default, Reflect, Serialize, Deserialize)]
+#[reflect(Resource, Serialize, Deserialize)]
+pub struct NextRuntimeSpawnId(pub u64);
+
+impl NextRuntimeSpawnId {
+    pub fn next(&mut self) -> SpawnId {
+        let id = SpawnId::Runtime(self.0);
+        self.0 += 1;
+        id
+    }
+}
+
+/// Level objects that were despawned through a [`DespawnRequest`] since the level was loaded
+#[derive(Debug, Resource, Clone, PartialEq, Eq, Default, Reflect, Serialize, Deserialize)]
+#[reflect(Resource, Serialize, Deserialize)]
+pub struct DespawnedObjects(pub Vec<SpawnId>);
+
+pub fn handle_spawn_requests(
+    mut requests: EventReader<SpawnRequest>,
+    mut next_id: ResMut<NextRuntimeSpawnId>,
+    mut spawn_events: EventWriter<SpawnEvent>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("handle_spawn_requests").entered();
+    for request in requests.iter() {
+        spawn_events.send(SpawnEvent {
+            object: request.object,
+            transform: request.transform,
+            id: Some(next_id.next()),
+            item: request.item.clone(),
+            ..default()
+        });
+    }
+}
+
+pub fn handle_despawn_requests(
+    mut commands: Commands,
+    mut requests: EventReader<DespawnRequest>,
+    spawn_id_query: Query<(Entity, &SpawnId)>,
+    mut despawned_objects: ResMut<DespawnedObjects>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("handle_despawn_requests").entered();
+    for request in requests.iter() {
+        let (entity, id) = match *request {
+            DespawnRequest::Entity(entity) => (
+                entity,
+                spawn_id_query.get(entity).ok().map(|(_entity, id)| *id),
+            ),
+            DespawnRequest::Id(id) => {
+                match spawn_id_query
+                    .iter()
+                    .find(|(_entity, spawn_id)| **spawn_id == id)
+                {
+                    Some((entity, _id)) => (entity, Some(id)),
+                    None => {
+                        warn!("Failed to despawn object {id:?}: No such object");
+                        continue;
+                    }
+                }
+            }
+        };
+        match commands.get_entity(entity) {
+            Some(entity_commands) => entity_commands.despawn_recursive(),
+            None => {
+                warn!("Failed to despawn entity {entity:?}: No such entity");
+                continue;
+            }
+        }
+        if let Some(id) = id.filter(|id| !id.is_runtime()) {
+            despawned_objects.0.push(id);
+        }
+    }
+}
+
+/// Objects spawned through a [`SpawnRequest`] in a form that can be stored in a save file.
+/// Animated props are stored closed, how far they are open is saved separately, see [`PropState`].
+pub fn get_runtime_spawns<'a>(
+    objects: impl IntoIterator<
+        Item = (
+            &'a SpawnTracker,
+            &'a SpawnId,
+            &'a Transform,
+            Option<&'a PropState>,
+        ),
+    >,
+) -> Vec<SpawnEvent> {
+    objects
+        .into_iter()
+        .filter(|(_spawn_tracker, id, _transform, _prop_state)| id.is_runtime())
+        .map(|(spawn_tracker, id, transform, prop_state)| {
+            let transform = prop_state.map(PropState::closed).unwrap_or(*transform);
+            spawn_tracker.to_spawn_event(Some(*id), transform)
+        })
+        .collect()
+}
+
 #[derive(Debug, Component, Clone, PartialEq, Default, Reflect, Serialize, Deserialize)]
 #[reflect(Component, Serialize, Deserialize)]
 pub struct Despawn {
@@ -54,6 +360,12 @@ pub fn despawn(mut commands: Commands, despawn_query: Query<(Entity, &Despawn, &
 #[reflect(Resource, Serialize, Deserialize)]
 pub struct DelayedSpawnEvents(Vec<DelayedSpawnEvent>);
 
+impl DelayedSpawnEvents {
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+}
+
 pub fn spawn_delayed(
     mut incoming_delayed_events: EventReader<DelayedSpawnEvent>,
     mut existing_delayed_events: ResMut<DelayedSpawnEvents>,
@@ -74,3 +386,135 @@ pub fn spawn_delayed(
         existing_delayed_events.0.remove(*index);
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::util::headless::build_headless_app;
+
+    #[test]
+    fn runtime_spawn_survives_save_and_load() {
+        let mut app = build_headless_app();
+        let transform = Transform::from_xyz(1., 2., 3.);
+        app.world.send_event(SpawnRequest {
+            object: GameObject::Box,
+            transform,
+            ..default()
+        });
+        app.update();
+        let id = SpawnId::Runtime(0);
+        assert!(
+            find_object(&mut app, id).is_some(),
+            "object was not spawned"
+        );
+
+        let saved = get_runtime_spawns(
+            app.world
+                .query::<(&SpawnTracker, &SpawnId, &Transform, Option<&PropState>)>()
+                .iter(&app.world),
+        );
+        let serialized = ron::to_string(&saved).unwrap();
+
+        app.world.send_event(DespawnRequest::Id(id));
+        app.update();
+        assert!(
+            find_object(&mut app, id).is_none(),
+            "object was not despawned"
+        );
+
+        let loaded: Vec<SpawnEvent> = ron::from_str(&serialized).unwrap();
+        for event in loaded {
+            app.world.send_event(DelayedSpawnEvent {
+                tick_delay: 2,
+                event,
+            });
+        }
+        for _ in 0..3 {
+            app.update();
+        }
+        let (object, loaded_transform) =
+            find_object(&mut app, id).expect("object was not spawned again after loading");
+        assert_eq!(object, GameObject::Box);
+        assert_eq!(loaded_transform, transform);
+    }
+
+    #[test]
+    fn despawned_level_object_is_remembered() {
+        let mut app = build_headless_app();
+        let id = SpawnId::Level(3);
+        app.world.send_event(SpawnEvent {
+            object: GameObject::Box,
+            id: Some(id),
+            ..default()
+        });
+        app.update();
+        let (entity, _) = app
+            .world
+            .query::<(Entity, &SpawnId)>()
+            .iter(&app.world)
+            .find(|(_entity, spawn_id)| **spawn_id == id)
+            .expect("level object was not spawned");
+
+        app.world.send_event(DespawnRequest::Entity(entity));
+        app.update();
+        assert!(
+            find_object(&mut app, id).is_none(),
+            "object was not despawned"
+        );
+        assert_eq!(app.world.resource::<DespawnedObjects>().0, vec![id]);
+        let saved = get_runtime_spawns(
+            app.world
+                .query::<(&SpawnTracker, &SpawnId, &Transform, Option<&PropState>)>()
+                .iter(&app.world),
+        );
+        assert!(
+            saved.is_empty(),
+            "level objects must not be saved as runtime spawns"
+        );
+    }
+
+    #[test]
+    fn queue_spawns_playable_set_first() {
+        let spawn = |object, x| SpawnEvent {
+            object,
+            transform: Transform::from_xyz(x, 0., 0.),
+            ..default()
+        };
+        let mut queue = SpawnQueue {
+            playable_radius: 10.,
+            ..default()
+        };
+        queue.push(spawn(GameObject::Box, 50.));
+        queue.push(spawn(GameObject::Box, 8.));
+        queue.push(spawn(GameObject::Sunlight, 100.));
+        queue.push(spawn(GameObject::Box, 3.));
+        assert!(!queue.is_playable(), "the spawn point is not known yet");
+        queue.push(spawn(GameObject::Player, 0.));
+
+        let order: Vec<_> = std::iter::from_fn(|| {
+            let is_playable = queue.is_playable();
+            queue
+                .pop()
+                .map(|spawn| (spawn.object, spawn.transform.translation.x, is_playable))
+        })
+        .collect();
+        assert_eq!(
+            order,
+            vec![
+                (GameObject::Player, 0., false),
+                (GameObject::Sunlight, 100., false),
+                (GameObject::Box, 3., false),
+                (GameObject::Box, 8., false),
+                (GameObject::Box, 50., true),
+            ]
+        );
+    }
+
+    fn find_object(app: &mut App, id: SpawnId) -> Option<(GameObject, Transform)> {
+        app.world
+            .query::<(&SpawnTracker, &SpawnId, &Transform)>()
+            .iter(&app.world)
+            .find(|(_spawn_tracker, spawn_id, _transform)| **spawn_id == id)
+            .map(|(spawn_tracker, _spawn_id, transform)| (spawn_tracker.object, *transform))
+    }
+}
@@ -19,14 +19,20 @@ pub mod bevy_config;
 #[cfg(feature = "dev")]
 pub mod dev;
 pub mod file_system_interaction;
+pub mod graphics_quality;
 pub mod ingame_menu;
+pub mod inventory_menu;
 pub mod level_instantiation;
+pub mod loading_screen;
 pub mod menu;
 pub mod movement;
 #[cfg(feature = "native")]
 pub mod particles;
 pub mod player_control;
+pub mod save_menu;
+pub mod settings_menu;
 pub mod shader;
+pub mod toast;
 pub mod util;
 pub mod world_interaction;
 
@@ -34,14 +40,19 @@ pub use crate::bevy_config::BevyConfigPlugin;
 #[cfg(feature = "dev")]
 use crate::dev::DevPlugin;
 use crate::file_system_interaction::FileSystemInteractionPlugin;
+use crate::graphics_quality::GraphicsQualityPlugin;
 use crate::ingame_menu::IngameMenuPlugin;
+use crate::inventory_menu::InventoryMenuPlugin;
 use crate::level_instantiation::LevelInstantiationPlugin;
+use crate::loading_screen::LoadingScreenPlugin;
 use crate::menu::MenuPlugin;
 use crate::movement::MovementPlugin;
 #[cfg(feature = "native")]
 use crate::particles::ParticlePlugin;
 use crate::player_control::PlayerControlPlugin;
+use crate::settings_menu::SettingsMenuPlugin;
 use crate::shader::ShaderPlugin;
+use crate::toast::ToastPlugin;
 use crate::world_interaction::WorldInteractionPlugin;
 use bevy::prelude::*;
 
@@ -49,8 +60,13 @@ use bevy::prelude::*;
 enum GameState {
     /// During the loading State the LoadingPlugin will load our assets
     Loading,
+    /// Entered when an asset failed to load. The player can retry loading from here.
+    LoadingFailed,
     /// During this State the actual game logic is executed
     Playing,
+    /// Pushed on top of [`GameState::Playing`] while the pause menu is open.
+    /// Systems that only run `on_update(GameState::Playing)` are paused along with the game.
+    Paused,
     /// Here the menu is drawn and waiting for player interaction
     Menu,
 }
@@ -60,6 +76,7 @@ enum GameState {
 /// The top-level plugins are:
 /// - [`BevyConfigPlugin`]: Sets up the bevy configuration.
 /// - [`MenuPlugin`]: Handles the menu.
+/// - [`LoadingScreenPlugin`]: Handles the loading screen.
 /// - [`MovementPlugin`]: Handles the movement of entities.
 /// - [`PlayerControlPlugin`]: Handles the player's control.
 /// - [`WorldInteractionPlugin`]: Handles the interaction of entities with the world.
@@ -68,6 +85,10 @@ enum GameState {
 /// - [`ShaderPlugin`]: Handles the shaders.
 /// - [`DevPlugin`]: Handles the dev tools.
 /// - [`IngameMenuPlugin`]: Handles the ingame menu accessed via ESC.
+/// - [`InventoryMenuPlugin`]: Handles the inventory accessed via I.
+/// - [`ToastPlugin`]: Handles short notifications, e.g. after a quick save.
+/// - [`SettingsMenuPlugin`]: Handles applying and persisting the settings chosen in the menus.
+/// - [`GraphicsQualityPlugin`]: Handles applying the graphics quality, e.g. shadows and anti-aliasing.
 /// - [`ParticlePlugin`]: Handles the particle system. Since [bevy_hanabi](https://github.com/djeedai/bevy_hanabi) does not support wasm, this plugin is only available on native.
 pub struct GamePlugin;
 
@@ -85,13 +106,18 @@ impl Plugin for GamePlugin {
         app.add_state(GameState::Loading)
             .add_plugin(BevyConfigPlugin)
             .add_plugin(MenuPlugin)
+            .add_plugin(LoadingScreenPlugin)
             .add_plugin(MovementPlugin)
             .add_plugin(PlayerControlPlugin)
             .add_plugin(WorldInteractionPlugin)
             .add_plugin(LevelInstantiationPlugin)
             .add_plugin(FileSystemInteractionPlugin)
             .add_plugin(ShaderPlugin)
-            .add_plugin(IngameMenuPlugin);
+            .add_plugin(IngameMenuPlugin)
+            .add_plugin(InventoryMenuPlugin)
+            .add_plugin(ToastPlugin)
+            .add_plugin(SettingsMenuPlugin)
+            .add_plugin(GraphicsQualityPlugin);
         #[cfg(feature = "dev")]
         app.add_plugin(DevPlugin);
         #[cfg(feature = "native")]
@@ -0,0 +1,313 @@
+use crate::file_system_interaction::asset_loading::{
+    AnimationAssets, AudioAssets, ConfigAssets, DialogAssets, LevelAssets, LocaleAssets,
+    ParticleEffects, PreloadedAudio, SceneAssets, TextureAssets,
+};
+use crate::file_system_interaction::level_serialization::WorldLoadRequest;
+use crate::level_instantiation::spawning::spawn::SpawnQueue;
+use crate::level_instantiation::spawning::SpawnTracker;
+use crate::movement::physics::ColliderTask;
+use crate::player_control::player_embodiment::Player;
+use crate::util::log_error::log_errors;
+use crate::GameState;
+use anyhow::Result;
+use bevy::app::AppExit;
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContext};
+use iyes_progress::prelude::*;
+use oxidized_navigation::{NavMesh, NavMeshAffector};
+use std::marker::PhantomData;
+
+/// Shows the loading screen while the assets are loaded in [`GameState::Loading`] and while a level is set up after a [`WorldLoadRequest`].
+/// The screen names the current phase next to a progress bar and a spinner, so that it is obvious the game is not stuck.
+/// Setting up a level is tracked in coarse steps, see [`LevelSetupStep`].
+/// When an asset fails to load, the game moves to [`GameState::LoadingFailed`], where the player can retry or quit.
+pub struct LoadingScreenPlugin;
+
+impl Plugin for LoadingScreenPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LevelSetup>()
+            .add_system_set(
+                SystemSet::on_update(GameState::Loading).with_system(show_asset_progress),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::LoadingFailed)
+                    .with_system(show_loading_failure.pipe(log_errors)),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(track_level_setup)
+                    .with_system(show_level_setup_progress.after(track_level_setup)),
+            );
+    }
+}
+
+/// Time in seconds after which a level setup step that a level might not need is skipped,
+/// e.g. generating the navmesh of a level without colliders
+const MAX_OPTIONAL_STEP_WAIT: f32 = 10.;
+
+/// The asset collections, grouped into the phases shown on the loading screen
+#[derive(SystemParam)]
+struct AssetCollections<'w, 's> {
+    audio: Option<Res<'w, AudioAssets>>,
+    preloaded_audio: Option<Res<'w, PreloadedAudio>>,
+    scenes: Option<Res<'w, SceneAssets>>,
+    animations: Option<Res<'w, AnimationAssets>>,
+    levels: Option<Res<'w, LevelAssets>>,
+    dialogs: Option<Res<'w, DialogAssets>>,
+    locales: Option<Res<'w, LocaleAssets>>,
+    textures: Option<Res<'w, TextureAssets>>,
+    particles: Option<Res<'w, ParticleEffects>>,
+    config: Option<Res<'w, ConfigAssets>>,
+    #[system_param(ignore)]
+    _marker: PhantomData<&'s ()>,
+}
+
+impl<'w, 's> AssetCollections<'w, 's> {
+    /// Names of the phases in the order they are shown, and whether they are done
+    fn phases(&self) -> [(&'static str, bool); 5] {
+        let audio_done = self.audio.is_some()
+            && self
+                .preloaded_audio
+                .as_ref()
+                .map(|preloaded_audio| preloaded_audio.is_done())
+                .unwrap_or_default();
+        [
+            (
+                "Configuration",
+                self.config.is_some() && self.locales.is_some(),
+            ),
+            (
+                "Textures",
+                self.textures.is_some() && self.particles.is_some(),
+            ),
+            ("Models", self.scenes.is_some() && self.animations.is_some()),
+            ("Levels", self.levels.is_some() && self.dialogs.is_some()),
+            ("Audio", audio_done),
+        ]
+    }
+}
+
+fn show_asset_progress(
+    progress: Option<Res<ProgressCounter>>,
+    collections: AssetCollections,
+    mut egui_context: ResMut<EguiContext>,
+    mut shown_progress: Local<f32>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("show_asset_progress").entered();
+    let progress = match progress {
+        Some(progress) => progress.progress(),
+        None => return,
+    };
+    // More assets are discovered while loading, which would make the bar jump back
+    *shown_progress = shown_progress.max(progress.done as f32 / progress.total.max(1) as f32);
+    let phases = collections.phases();
+    let phase = phases
+        .iter()
+        .find(|(_, done)| !done)
+        .map(|(name, _)| format!("Loading {}...", name.to_lowercase()))
+        .unwrap_or_else(|| "Finishing up...".to_string());
+
+    show_loading_panel(egui_context.ctx_mut(), &phase, *shown_progress, |ui| {
+        ui.add_enabled_ui(false, |ui| {
+            for (name, mut done) in phases {
+                ui.checkbox(&mut done, name);
+            }
+        });
+    });
+}
+
+fn show_loading_failure(
+    collections: AssetCollections,
+    mut egui_context: ResMut<EguiContext>,
+    mut state: ResMut<State<GameState>>,
+    mut app_exit: EventWriter<AppExit>,
+) -> Result<()> {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("show_loading_failure").entered();
+    let failed_phases: Vec<_> = collections
+        .phases()
+        .into_iter()
+        .filter(|(_, done)| !done)
+        .map(|(name, _)| name)
+        .collect();
+    let mut retry = false;
+    egui::CentralPanel::default().show(egui_context.ctx_mut(), |ui| {
+        ui.vertical_centered(|ui| {
+            ui.add_space(100.0);
+            ui.heading("Loading failed");
+            ui.label(format!(
+                "Some assets could not be loaded: {}",
+                failed_phases.join(", ").to_lowercase()
+            ));
+            ui.label("The log names the files that failed.");
+            ui.add_space(30.0);
+            retry = ui.button("Retry").clicked();
+            if ui.button("Quit").clicked() {
+                app_exit.send(AppExit);
+            }
+        });
+    });
+    if retry {
+        state.set(GameState::Loading)?;
+    }
+    Ok(())
+}
+
+/// Coarse steps of setting up a level after a [`WorldLoadRequest`], in order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LevelSetupStep {
+    /// Waiting for the objects around the player's spawn point to be spawned, see [`SpawnQueue::is_playable`].
+    /// Distant objects keep being spawned after the loading screen is gone.
+    Instantiating,
+    /// Waiting for the colliders of the level's scene to be created, including those built asynchronously
+    BuildingPhysics,
+    /// Waiting for the first tiles of the navmesh
+    GeneratingNavmesh,
+    /// Waiting for the player to be spawned
+    SpawningPlayer,
+}
+
+impl LevelSetupStep {
+    const ALL: [LevelSetupStep; 4] = [
+        LevelSetupStep::Instantiating,
+        LevelSetupStep::BuildingPhysics,
+        LevelSetupStep::GeneratingNavmesh,
+        LevelSetupStep::SpawningPlayer,
+    ];
+
+    fn index(self) -> usize {
+        Self::ALL
+            .iter()
+            .position(|step| *step == self)
+            .unwrap_or_default()
+    }
+
+    fn next(self) -> Option<Self> {
+        Self::ALL.get(self.index() + 1).copied()
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            LevelSetupStep::Instantiating => "Spawning level...",
+            LevelSetupStep::BuildingPhysics => "Building physics...",
+            LevelSetupStep::GeneratingNavmesh => "Generating navmesh...",
+            LevelSetupStep::SpawningPlayer => "Spawning player...",
+        }
+    }
+
+    /// Whether a level might never finish this step, e.g. because it has no colliders
+    fn is_optional(self) -> bool {
+        matches!(
+            self,
+            LevelSetupStep::BuildingPhysics | LevelSetupStep::GeneratingNavmesh
+        )
+    }
+}
+
+#[derive(Debug, Clone, Resource, Default)]
+pub struct LevelSetup {
+    /// `None` when no level is being set up
+    step: Option<LevelSetupStep>,
+    /// Time in seconds spent in the current step
+    waited: f32,
+}
+
+impl LevelSetup {
+    pub fn is_done(&self) -> bool {
+        self.step.is_none()
+    }
+}
+
+fn track_level_setup(
+    time: Res<Time>,
+    mut world_load_requests: EventReader<WorldLoadRequest>,
+    mut level_setup: ResMut<LevelSetup>,
+    spawned_objects: Query<(), With<SpawnTracker>>,
+    spawn_queue: Res<SpawnQueue>,
+    level_colliders: Query<(), With<NavMeshAffector>>,
+    collider_tasks: Query<(), With<ColliderTask>>,
+    nav_mesh: Res<NavMesh>,
+    player_query: Query<(), With<Player>>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("track_level_setup").entered();
+    if world_load_requests.iter().count() > 0 {
+        // The objects of the previous level are still around until the request is handled
+        *level_setup = LevelSetup {
+            step: Some(LevelSetupStep::Instantiating),
+            waited: 0.,
+        };
+        return;
+    }
+    let step = match level_setup.step {
+        Some(step) => step,
+        None => return,
+    };
+    level_setup.waited += time.raw_delta_seconds();
+    let is_done = match step {
+        LevelSetupStep::Instantiating => !spawned_objects.is_empty() && spawn_queue.is_playable(),
+        LevelSetupStep::BuildingPhysics => !level_colliders.is_empty() && collider_tasks.is_empty(),
+        LevelSetupStep::GeneratingNavmesh => nav_mesh
+            .get()
+            .read()
+            .map(|nav_mesh| !nav_mesh.get_tiles().is_empty())
+            .unwrap_or_default(),
+        LevelSetupStep::SpawningPlayer => !player_query.is_empty(),
+    };
+    let timed_out = step.is_optional() && level_setup.waited > MAX_OPTIONAL_STEP_WAIT;
+    if timed_out {
+        warn!("Level setup step {step:?} took longer than {MAX_OPTIONAL_STEP_WAIT} seconds, skipping it");
+    }
+    if is_done || timed_out {
+        *level_setup = LevelSetup {
+            step: step.next(),
+            waited: 0.,
+        };
+    }
+}
+
+fn show_level_setup_progress(level_setup: Res<LevelSetup>, mut egui_context: ResMut<EguiContext>) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("show_level_setup_progress").entered();
+    let step = match level_setup.step {
+        Some(step) => step,
+        None => return,
+    };
+    let progress = step.index() as f32 / LevelSetupStep::ALL.len() as f32;
+    show_loading_panel(
+        egui_context.ctx_mut(),
+        step.description(),
+        progress,
+        |_ui| {
+            #[cfg(feature = "wasm")]
+            _ui.label("This may take a while. Don't worry, your browser did not crash!");
+        },
+    );
+}
+
+fn show_loading_panel(
+    ctx: &egui::Context,
+    phase: &str,
+    progress: f32,
+    add_contents: impl FnOnce(&mut egui::Ui),
+) {
+    egui::CentralPanel::default().show(ctx, |ui| {
+        ui.vertical_centered(|ui| {
+            ui.add_space(100.0);
+            ui.heading("Loading");
+            ui.horizontal(|ui| {
+                ui.add(egui::Spinner::new());
+                ui.label(phase);
+            });
+            ui.add(
+                egui::ProgressBar::new(progress)
+                    .show_percentage()
+                    .animate(true),
+            );
+            ui.add_space(100.0);
+            add_contents(ui);
+        });
+    });
+}
@@ -1,7 +1,16 @@
+use crate::bevy_config::AvailableResolutions;
+use crate::file_system_interaction::asset_loading::{ConfigAssets, LevelAssets};
+use crate::file_system_interaction::config::GameConfig;
+use crate::file_system_interaction::game_state_serialization::GameLoadRequest;
+use crate::file_system_interaction::level_serialization::{LevelManifest, SelectedLevel};
+use crate::file_system_interaction::localization::Localization;
+use crate::file_system_interaction::user_settings::UserSettings;
+use crate::save_menu::{SaveMenu, SaveMenuAction};
+use crate::settings_menu::{SettingsChangeRequest, SettingsMenu};
 use crate::util::log_error::log_errors;
 use crate::GameState;
 use anyhow::Ok;
-use anyhow::Result;
+use anyhow::{Context, Result};
 use bevy::prelude::*;
 use bevy_egui::egui::FontFamily::Proportional;
 use bevy_egui::egui::FontId;
@@ -20,29 +29,144 @@ impl Plugin for MenuPlugin {
     }
 }
 
+/// Screen of the main menu that is currently shown
+#[derive(Debug, Clone, Default)]
+enum MenuScreen {
+    #[default]
+    Main,
+    Load(SaveMenu),
+    LevelSelect,
+    Settings(SettingsMenu),
+}
+
 fn setup_menu(
+    mut commands: Commands,
     mut egui_context: ResMut<EguiContext>,
     mut state: ResMut<State<GameState>>,
+    localization: Option<ResMut<Localization>>,
+    mut load_requests: EventWriter<GameLoadRequest>,
+    mut settings_requests: EventWriter<SettingsChangeRequest>,
+    settings: Res<UserSettings>,
+    resolutions: Res<AvailableResolutions>,
+    config_handles: Res<ConfigAssets>,
+    config: Res<Assets<GameConfig>>,
+    manifests: Res<Assets<LevelManifest>>,
+    level_handles: Res<LevelAssets>,
+    mut screen: Local<MenuScreen>,
 ) -> Result<()> {
-    get_menu_panel()
-        .show(egui_context.ctx_mut(), |ui| {
-            set_menu_style(ui.style_mut());
-            ui.vertical_centered_justified(|ui| {
-                ui.add_space(50.);
-                ui.heading("Foxtrot");
-                ui.separator();
-                ui.add_space(50.);
-                if ui.button("Play").clicked() {
-                    state.set(GameState::Playing)?;
+    // Inserted when loading is done, which might not have been applied yet on the first frame
+    let mut localization = match localization {
+        Some(localization) => localization,
+        None => return Ok(()),
+    };
+    let config = config
+        .get(&config_handles.game)
+        .context("Failed to get game config from handle")?;
+    let manifest = manifests.get(&config_handles.levels);
+    let mut next_screen = None;
+    let mut start_level = None;
+    let mut load_slot = None;
+    let mut new_settings = None;
+    get_menu_panel().show(egui_context.ctx_mut(), |ui| {
+        set_menu_style(ui.style_mut());
+        ui.vertical_centered_justified(|ui| {
+            ui.add_space(50.);
+            ui.heading(localization.get("menu.title"));
+            ui.separator();
+            ui.add_space(50.);
+            match &mut *screen {
+                MenuScreen::Main => {
+                    // Play starts the first level of the manifest
+                    let first_level = manifest.and_then(|manifest| manifest.levels.first());
+                    let error = match first_level {
+                        Some(level) => level.get_error(&level_handles),
+                        None => Some(localization.get("menu.no_levels").to_string()),
+                    };
+                    let play = ui.add_enabled(
+                        error.is_none(),
+                        egui::Button::new(localization.get("menu.play")),
+                    );
+                    if play.clicked() {
+                        start_level = first_level.map(|level| level.id.clone());
+                    }
+                    if ui.button(localization.get("menu.select_level")).clicked() {
+                        next_screen = Some(MenuScreen::LevelSelect);
+                    }
+                    if ui.button(localization.get("menu.load")).clicked() {
+                        next_screen = Some(MenuScreen::Load(default()));
+                    }
+                    if ui.button(localization.get("menu.settings")).clicked() {
+                        next_screen = Some(MenuScreen::Settings(default()));
+                    }
+                    if let Some(error) = error {
+                        show_error(ui, &error);
+                    }
+                }
+                MenuScreen::Load(save_menu) => {
+                    if let Some(SaveMenuAction::Load(slot)) =
+                        save_menu.show(ui, &localization, false)
+                    {
+                        load_slot = Some(slot);
+                    }
+                    ui.add_space(20.);
+                    if ui.button(localization.get("menu.back")).clicked() {
+                        next_screen = Some(MenuScreen::Main);
+                    }
+                }
+                MenuScreen::Settings(settings_menu) => {
+                    new_settings =
+                        settings_menu.show(ui, &mut localization, &settings, config, &resolutions);
+                    ui.add_space(20.);
+                    if ui.button(localization.get("menu.back")).clicked() {
+                        next_screen = Some(MenuScreen::Main);
+                    }
+                }
+                MenuScreen::LevelSelect => {
+                    let levels = manifest
+                        .map(|manifest| manifest.levels.as_slice())
+                        .unwrap_or_default();
+                    if levels.is_empty() {
+                        show_error(ui, localization.get("menu.no_levels"));
+                    }
+                    for level in levels {
+                        let error = level.get_error(&level_handles);
+                        let button =
+                            ui.add_enabled(error.is_none(), egui::Button::new(&level.name));
+                        if button.clicked() {
+                            start_level = Some(level.id.clone());
+                        }
+                        if let Some(error) = error {
+                            show_error(ui, &error);
+                        }
+                    }
+                    ui.add_space(20.);
+                    if ui.button(localization.get("menu.back")).clicked() {
+                        next_screen = Some(MenuScreen::Main);
+                    }
                 }
-                Ok(())
-            })
-            .inner
-        })
-        .inner?;
+            }
+        });
+    });
+
+    if let Some(new_settings) = new_settings {
+        settings_requests.send(SettingsChangeRequest(new_settings));
+    }
+    if let Some(slot) = load_slot {
+        load_requests.send(GameLoadRequest { slot });
+        state.set(GameState::Playing)?;
+    } else if let Some(id) = start_level {
+        commands.insert_resource(SelectedLevel(id));
+        state.set(GameState::Playing)?;
+    } else if let Some(next_screen) = next_screen {
+        *screen = next_screen;
+    }
     Ok(())
 }
 
+fn show_error(ui: &mut egui::Ui, error: &str) {
+    ui.colored_label(egui::Color32::from_rgb(230, 80, 80), error);
+}
+
 fn get_menu_panel() -> egui::CentralPanel {
     egui::CentralPanel::default().frame(egui::Frame {
         inner_margin: egui::style::Margin::same(60.),
@@ -51,10 +175,12 @@ fn get_menu_panel() -> egui::CentralPanel {
 }
 
 fn set_menu_style(style: &mut egui::Style) {
-    style.text_styles = [
-        (Heading, FontId::new(30.0, Proportional)),
-        (Button, FontId::new(20.0, Proportional)),
-    ]
-    .into();
+    // Keeps the other text styles, which the save and settings screens use for their labels
+    style
+        .text_styles
+        .insert(Heading, FontId::new(30.0, Proportional));
+    style
+        .text_styles
+        .insert(Button, FontId::new(20.0, Proportional));
     style.visuals.widgets.noninteractive.fg_stroke.color = egui::Color32::from_gray(250);
 }
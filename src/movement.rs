@@ -1,10 +1,26 @@
+pub mod activity;
+pub mod animation;
+pub mod climbing;
+pub mod dash;
+pub mod footsteps;
 pub mod general_movement;
 pub mod navigation;
+pub mod patrol;
 pub mod physics;
+pub mod platform;
+pub mod water;
 
+use crate::movement::activity::ActivityPlugin;
+use crate::movement::animation::CharacterAnimationPlugin;
+use crate::movement::climbing::ClimbingPlugin;
+use crate::movement::dash::DashPlugin;
+use crate::movement::footsteps::FootstepPlugin;
 use crate::movement::general_movement::GeneralMovementPlugin;
 use crate::movement::navigation::NavigationPlugin;
+use crate::movement::patrol::PatrolPlugin;
 use crate::movement::physics::PhysicsPlugin;
+use crate::movement::platform::PlatformPlugin;
+use crate::movement::water::WaterPlugin;
 use bevy::prelude::*;
 
 /// This plugin handles all physical movement that is not exclusive to the player.
@@ -14,12 +30,28 @@ use bevy::prelude::*;
 /// this sense is anything that behaves in a not-quite completely physical way, like a player, an npc, an elevator, a moving platform, etc.
 /// Contrast this with pure rigidbodies like a ball, a crate, etc.
 /// - [`NavigationPlugin`]: Handles npc pathfinding via bevy_pathmesh integration.
+/// - [`PatrolPlugin`]: Handles NPCs walking along patrol routes.
+/// - [`WaterPlugin`]: Handles water volumes and swimming.
+/// - [`ClimbingPlugin`]: Handles ladders and climbing.
+/// - [`PlatformPlugin`]: Handles moving platforms and carrying characters standing on them.
+/// - [`DashPlugin`]: Handles dashing.
+/// - [`FootstepPlugin`]: Handles footstep events and the surface types of the ground.
+/// - [`ActivityPlugin`]: Handles lowering the activity of characters far away from the camera.
+/// - [`CharacterAnimationPlugin`]: Handles which animations characters play.
 pub struct MovementPlugin;
 
 impl Plugin for MovementPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugin(PhysicsPlugin)
             .add_plugin(GeneralMovementPlugin)
-            .add_plugin(NavigationPlugin);
+            .add_plugin(NavigationPlugin)
+            .add_plugin(PatrolPlugin)
+            .add_plugin(WaterPlugin)
+            .add_plugin(ClimbingPlugin)
+            .add_plugin(PlatformPlugin)
+            .add_plugin(DashPlugin)
+            .add_plugin(FootstepPlugin)
+            .add_plugin(ActivityPlugin)
+            .add_plugin(CharacterAnimationPlugin);
     }
 }
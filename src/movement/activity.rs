@@ -0,0 +1,226 @@
+use crate::file_system_interaction::asset_loading::ConfigAssets;
+use crate::file_system_interaction::config::GameConfig;
+use crate::level_instantiation::spawning::AnimationEntityLink;
+use crate::player_control::camera::IngameCamera;
+use crate::player_control::player_embodiment::Player;
+use crate::util::log_error::log_errors;
+use crate::world_interaction::dialog::CurrentDialog;
+use crate::world_interaction::interactions_ui::InteractionOpportunities;
+use crate::GameState;
+use anyhow::{Context, Result};
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Lowers the [`ActivityLevel`] of characters with an [`Activity`] the farther they are from the active camera.
+/// Levels are reevaluated a few times per second. Characters wake up immediately when the player comes close,
+/// e.g. by teleporting, or when a dialog or interaction targets them.
+/// Systems that are not needed for inactive characters skip entities with the [`ReducedActivity`] or [`Dormant`] markers.
+/// The activity level is derived state and is not part of save files.
+pub struct ActivityPlugin;
+
+impl Plugin for ActivityPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Activity>()
+            .register_type::<ActivityLevel>()
+            .register_type::<ReducedActivity>()
+            .register_type::<Dormant>()
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(update_activity.pipe(log_errors)),
+            );
+    }
+}
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Reflect,
+    FromReflect,
+    Serialize,
+    Deserialize,
+    Default,
+)]
+#[reflect(Serialize, Deserialize)]
+pub enum ActivityLevel {
+    /// Everything runs
+    #[default]
+    Full,
+    /// Paths are planned less often and animations are paused
+    Reduced,
+    /// The rigid body is fixed and movement and navigation skip the character
+    Dormant,
+}
+
+impl ActivityLevel {
+    fn from_distance(distance: f32, config: &GameConfig) -> Self {
+        if distance >= config.activity.dormant_distance {
+            ActivityLevel::Dormant
+        } else if distance >= config.activity.reduced_distance {
+            ActivityLevel::Reduced
+        } else {
+            ActivityLevel::Full
+        }
+    }
+
+    /// Level a character at `distance` from the camera should switch to.
+    /// Characters only become less active once they are clearly past a threshold so that they don't flicker between levels.
+    fn next(self, distance: f32, config: &GameConfig) -> Self {
+        let level = Self::from_distance(distance, config);
+        if level > self {
+            Self::from_distance(distance - config.activity.hysteresis, config).max(self)
+        } else {
+            level
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Component, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Component, Serialize, Deserialize)]
+pub struct Activity {
+    pub level: ActivityLevel,
+    /// Distance in m to the active camera when the level was last evaluated
+    pub distance: f32,
+}
+
+/// Marks characters whose [`ActivityLevel`] is [`ActivityLevel::Reduced`] or lower
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Component, Reflect, Serialize, Deserialize, Default,
+)]
+#[reflect(Component, Serialize, Deserialize)]
+pub struct ReducedActivity;
+
+/// Marks characters whose [`ActivityLevel`] is [`ActivityLevel::Dormant`]
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Component, Reflect, Serialize, Deserialize, Default,
+)]
+#[reflect(Component, Serialize, Deserialize)]
+pub struct Dormant;
+
+fn update_activity(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut time_since_update: Local<f32>,
+    mut character_query: Query<(
+        Entity,
+        &Transform,
+        &mut Activity,
+        &mut RigidBody,
+        &mut Velocity,
+        Option<&AnimationEntityLink>,
+    )>,
+    camera_query: Query<&Transform, (With<IngameCamera>, Without<Activity>)>,
+    player_query: Query<&Transform, (With<Player>, Without<Activity>)>,
+    mut animation_player_query: Query<&mut AnimationPlayer>,
+    current_dialog: Option<Res<CurrentDialog>>,
+    interaction_opportunities: Res<InteractionOpportunities>,
+    config_handles: Res<ConfigAssets>,
+    config: Res<Assets<GameConfig>>,
+) -> Result<()> {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("update_activity").entered();
+    let config = config
+        .get(&config_handles.game)
+        .context("Failed to get game config from handle")?;
+    *time_since_update += time.delta_seconds();
+    let is_update_due = *time_since_update >= config.activity.update_interval;
+    if is_update_due {
+        *time_since_update = 0.;
+    }
+    let camera_translation = camera_query
+        .iter()
+        .next()
+        .map(|transform| transform.translation);
+    let player_translation = player_query
+        .iter()
+        .next()
+        .map(|transform| transform.translation);
+
+    for (entity, transform, mut activity, mut rigid_body, mut velocity, animation_link) in
+        &mut character_query
+    {
+        let is_targeted = current_dialog
+            .as_ref()
+            .map(|dialog| dialog.source == entity)
+            .unwrap_or_default()
+            || interaction_opportunities.0.contains(&entity);
+        let is_near_player = player_translation
+            .map(|player_translation| {
+                player_translation.distance(transform.translation)
+                    < config.activity.reduced_distance
+            })
+            .unwrap_or_default();
+        let level = if activity.level != ActivityLevel::Full && (is_targeted || is_near_player) {
+            ActivityLevel::Full
+        } else if is_update_due {
+            let camera_translation = match camera_translation.or(player_translation) {
+                Some(translation) => translation,
+                None => continue,
+            };
+            activity.distance = camera_translation.distance(transform.translation);
+            if is_targeted || is_near_player {
+                ActivityLevel::Full
+            } else {
+                activity.level.next(activity.distance, config)
+            }
+        } else {
+            continue;
+        };
+        if level == activity.level {
+            continue;
+        }
+        let previous_level = activity.level;
+        activity.level = level;
+
+        let mut entity_commands = commands.entity(entity);
+        match level {
+            ActivityLevel::Full => entity_commands.remove::<(ReducedActivity, Dormant)>(),
+            ActivityLevel::Reduced => entity_commands.insert(ReducedActivity).remove::<Dormant>(),
+            ActivityLevel::Dormant => entity_commands.insert((ReducedActivity, Dormant)),
+        };
+        if level == ActivityLevel::Dormant {
+            *rigid_body = RigidBody::Fixed;
+            *velocity = default();
+        } else if previous_level == ActivityLevel::Dormant {
+            *rigid_body = RigidBody::Dynamic;
+        }
+        if let Some(mut animation_player) =
+            animation_link.and_then(|link| animation_player_query.get_mut(link.0).ok())
+        {
+            if level == ActivityLevel::Full {
+                animation_player.resume();
+            } else {
+                animation_player.pause();
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn switches_level_with_hysteresis() {
+        let mut config = GameConfig::default();
+        config.activity.reduced_distance = 30.;
+        config.activity.dormant_distance = 60.;
+        config.activity.hysteresis = 5.;
+
+        let full = ActivityLevel::Full;
+        assert_eq!(full.next(32., &config), ActivityLevel::Full);
+        assert_eq!(full.next(36., &config), ActivityLevel::Reduced);
+        assert_eq!(full.next(70., &config), ActivityLevel::Dormant);
+
+        let dormant = ActivityLevel::Dormant;
+        assert_eq!(dormant.next(58., &config), ActivityLevel::Reduced);
+        assert_eq!(dormant.next(10., &config), ActivityLevel::Full);
+    }
+}
@@ -0,0 +1,502 @@
+use crate::file_system_interaction::asset_loading::ConfigAssets;
+use crate::file_system_interaction::config::{self, GameConfig};
+use crate::level_instantiation::spawning::AnimationEntityLink;
+use crate::movement::activity::ReducedActivity;
+use crate::movement::general_movement::{
+    apply_jumping, update_landing, CharacterAnimations, Grounded, Landing,
+};
+use crate::player_control::player_embodiment::{Player, PlayerJumped, PlayerLanded};
+use crate::util::log_error::log_errors;
+use crate::util::trait_extension::Vec3Ext;
+use crate::GameState;
+use anyhow::{Context, Result};
+use bevy::animation::animation_player;
+use bevy::prelude::*;
+use bevy::transform::TransformSystem;
+use bevy_rapier3d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Plays the [`CharacterAnimations`] of every character according to its [`CharacterAnimationState`].
+/// The state follows whether the character is [`Grounded`] and its horizontal speed, with the thresholds from the `animation` section of the config.
+/// The player's jumps and landings come from [`PlayerJumped`] and [`PlayerLanded`], other characters land whenever their [`Landing`] reports an impact.
+/// Walking and running switch with some margin around the threshold and their clips play faster or slower with the speed, so the gait follows the speed smoothly.
+/// On every change of clip, the last pose fades into the new clip over the configured blend time.
+/// Characters with a [`ReducedActivity`] keep their state and clip.
+pub struct CharacterAnimationPlugin;
+
+impl Plugin for CharacterAnimationPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<CharacterAnimationState>()
+            .register_type::<Crouching>()
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(add_animation_states)
+                    .with_system(
+                        update_animation_states
+                            .pipe(log_errors)
+                            .after(add_animation_states)
+                            .after(update_landing)
+                            .after(apply_jumping),
+                    ),
+            )
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                blend_poses
+                    .after(animation_player)
+                    .before(TransformSystem::TransformPropagate),
+            );
+    }
+}
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, Reflect, FromReflect, Serialize, Deserialize, Default,
+)]
+#[reflect(Serialize, Deserialize)]
+pub enum AnimationState {
+    #[default]
+    Idle,
+    Walk,
+    Run,
+    /// Taking off the ground, followed by [`AnimationState::Airborne`]
+    JumpStart,
+    Airborne,
+    /// Touching the ground after a fall, followed by one of the grounded states
+    Land,
+    CrouchIdle,
+    CrouchWalk,
+}
+
+impl AnimationState {
+    /// Lowercase name used for the keys of the configured blend times
+    pub fn name(self) -> &'static str {
+        match self {
+            AnimationState::Idle => "idle",
+            AnimationState::Walk => "walk",
+            AnimationState::Run => "run",
+            AnimationState::JumpStart => "jump_start",
+            AnimationState::Airborne => "airborne",
+            AnimationState::Land => "land",
+            AnimationState::CrouchIdle => "crouch_idle",
+            AnimationState::CrouchWalk => "crouch_walk",
+        }
+    }
+
+    /// Whether the character moves along the ground in this state, e.g. for deciding whether to kick up dust
+    pub fn is_moving_on_ground(self) -> bool {
+        matches!(
+            self,
+            AnimationState::Walk | AnimationState::Run | AnimationState::CrouchWalk
+        )
+    }
+
+    fn clip(self, animations: &CharacterAnimations) -> &Handle<AnimationClip> {
+        match self {
+            AnimationState::Idle => &animations.idle,
+            AnimationState::Walk => &animations.walk,
+            AnimationState::Run => &animations.run,
+            AnimationState::JumpStart => &animations.jump,
+            AnimationState::Airborne => &animations.aerial,
+            AnimationState::Land => &animations.land,
+            AnimationState::CrouchIdle => &animations.crouch_idle,
+            AnimationState::CrouchWalk => &animations.crouch_walk,
+        }
+    }
+
+    fn repeats(self) -> bool {
+        !matches!(self, AnimationState::JumpStart | AnimationState::Land)
+    }
+}
+
+/// Which animation a character is playing. Added to every character with [`CharacterAnimations`].
+#[derive(Debug, Clone, Copy, PartialEq, Component, Reflect, Default)]
+#[reflect(Component)]
+pub struct CharacterAnimationState {
+    pub current: AnimationState,
+    pub previous: AnimationState,
+    /// Time in seconds since entering [`CharacterAnimationState::current`]
+    pub time_in_state: f32,
+}
+
+/// Marks a character as crouching, which makes it play its crouching animations instead of idling, walking or running
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Component, Reflect, Default)]
+#[reflect(Component)]
+pub struct Crouching;
+
+/// What the [`AnimationState`] of a character depends on during a single frame
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct AnimationInput {
+    pub grounded: bool,
+    /// Speed in m/s along the ground
+    pub horizontal_speed: f32,
+    /// Whether the character jumped this frame
+    pub jumped: bool,
+    /// Whether the character landed hard enough to show it this frame
+    pub landed: bool,
+    pub crouching: bool,
+}
+
+/// The state following `state` after a frame with the given `input`
+pub fn get_next_animation_state(
+    state: &CharacterAnimationState,
+    input: &AnimationInput,
+    config: &config::Animation,
+) -> AnimationState {
+    if input.jumped {
+        return AnimationState::JumpStart;
+    }
+    if input.landed {
+        return AnimationState::Land;
+    }
+    match state.current {
+        AnimationState::JumpStart if state.time_in_state < config.jump_start_duration => {
+            AnimationState::JumpStart
+        }
+        AnimationState::Land if input.grounded && state.time_in_state < config.land_duration => {
+            AnimationState::Land
+        }
+        _ if !input.grounded => AnimationState::Airborne,
+        current => get_grounded_state(current, input, config),
+    }
+}
+
+fn get_grounded_state(
+    current: AnimationState,
+    input: &AnimationInput,
+    config: &config::Animation,
+) -> AnimationState {
+    let margin = config.speed_threshold_margin;
+    let exceeds = |threshold: f32, already_above: bool| {
+        if already_above {
+            input.horizontal_speed > threshold - margin
+        } else {
+            input.horizontal_speed > threshold + margin
+        }
+    };
+    let is_moving = exceeds(config.walk_speed, current.is_moving_on_ground());
+    if input.crouching {
+        return if is_moving {
+            AnimationState::CrouchWalk
+        } else {
+            AnimationState::CrouchIdle
+        };
+    }
+    if !is_moving {
+        AnimationState::Idle
+    } else if exceeds(config.run_speed, current == AnimationState::Run) {
+        AnimationState::Run
+    } else {
+        AnimationState::Walk
+    }
+}
+
+/// Time in seconds over which the pose of `from` fades into `to`
+pub fn get_blend_time(from: AnimationState, to: AnimationState, config: &config::Animation) -> f32 {
+    let key = format!("{}->{}", from.name(), to.name());
+    config
+        .blend_times
+        .get(&key)
+        .copied()
+        .unwrap_or(config.default_blend_time)
+}
+
+/// Factor at which the clip of `state` plays, so that walking and running follow the speed
+fn get_playback_speed(
+    state: AnimationState,
+    horizontal_speed: f32,
+    config: &config::Animation,
+) -> f32 {
+    let clip_speed = match state {
+        AnimationState::Walk | AnimationState::CrouchWalk => config.walk_clip_speed,
+        AnimationState::Run => config.run_clip_speed,
+        _ => return 1.,
+    };
+    (horizontal_speed / clip_speed).clamp(config.min_playback_speed, config.max_playback_speed)
+}
+
+/// The pose the character had when its clip changed, fading out over [`PoseBlend::duration`].
+/// Bevy's [`AnimationPlayer`] can only play a single clip, so the fade starts from a snapshot of the bones instead of the previous clip.
+#[derive(Debug, Clone, PartialEq, Component)]
+struct PoseBlend {
+    bones: Vec<(Entity, Transform)>,
+    elapsed: f32,
+    duration: f32,
+}
+
+impl PoseBlend {
+    /// How much of the snapshot is left, from 1 right after the change to 0 at the end of the fade
+    fn weight(&self) -> f32 {
+        let progress = (self.elapsed / self.duration).clamp(0., 1.);
+        let eased = progress * progress * (3. - 2. * progress);
+        1. - eased
+    }
+}
+
+fn add_animation_states(
+    mut commands: Commands,
+    characters: Query<Entity, (Added<CharacterAnimations>, Without<CharacterAnimationState>)>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("add_animation_states").entered();
+    for entity in characters.iter() {
+        commands
+            .entity(entity)
+            .insert(CharacterAnimationState::default());
+    }
+}
+
+fn update_animation_states(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut player_jumped_events: EventReader<PlayerJumped>,
+    mut player_landed_events: EventReader<PlayerLanded>,
+    mut characters: Query<
+        (
+            &Velocity,
+            &Transform,
+            &Grounded,
+            Option<&Landing>,
+            &AnimationEntityLink,
+            &CharacterAnimations,
+            &mut CharacterAnimationState,
+            Option<&Player>,
+            Option<&Crouching>,
+        ),
+        Without<ReducedActivity>,
+    >,
+    mut animation_players: Query<&mut AnimationPlayer>,
+    children: Query<&Children>,
+    bones: Query<&Transform>,
+    config_handles: Res<ConfigAssets>,
+    config: Res<Assets<GameConfig>>,
+) -> Result<()> {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("update_animation_states").entered();
+    let config = config
+        .get(&config_handles.game)
+        .context("Failed to get game config from handle")?;
+    let player_jumped = player_jumped_events.iter().count() > 0;
+    let player_landed = player_landed_events.iter().count() > 0;
+    let dt = time.delta_seconds();
+    for (
+        velocity,
+        transform,
+        grounded,
+        landing,
+        animation_entity_link,
+        animations,
+        mut state,
+        player,
+        crouching,
+    ) in characters.iter_mut()
+    {
+        let mut animation_player = animation_players
+            .get_mut(animation_entity_link.0)
+            .context("animation_entity_link held entity without animation player")?;
+
+        let horizontal_speed = velocity.linvel.split(transform.up()).horizontal.length();
+        let landed = if player.is_some() {
+            player_landed
+        } else {
+            landing
+                .and_then(|landing| landing.impact_speed)
+                .map(|speed| speed >= config.player.soft_landing_threshold)
+                .unwrap_or_default()
+        };
+        let input = AnimationInput {
+            grounded: grounded.0,
+            horizontal_speed,
+            jumped: player.is_some() && player_jumped,
+            landed,
+            crouching: crouching.is_some(),
+        };
+
+        let next = get_next_animation_state(&state, &input, &config.animation);
+        if next == state.current {
+            state.time_in_state += dt;
+        } else {
+            let from_clip = state.current.clip(animations);
+            let to_clip = next.clip(animations);
+            let duration = get_blend_time(state.current, next, &config.animation);
+            if from_clip != to_clip && duration > 0. {
+                let mut snapshot = Vec::new();
+                collect_bones(animation_entity_link.0, &children, &bones, &mut snapshot);
+                commands.entity(animation_entity_link.0).insert(PoseBlend {
+                    bones: snapshot,
+                    elapsed: 0.,
+                    duration,
+                });
+            }
+            *state = CharacterAnimationState {
+                current: next,
+                previous: state.current,
+                time_in_state: 0.,
+            };
+        }
+
+        let clip = state.current.clip(animations).clone_weak();
+        // Restarts the clip only if it is not already playing
+        if state.current.repeats() {
+            animation_player.play(clip).repeat();
+        } else {
+            animation_player.play(clip);
+        }
+        animation_player.set_speed(get_playback_speed(
+            state.current,
+            horizontal_speed,
+            &config.animation,
+        ));
+    }
+    Ok(())
+}
+
+fn collect_bones(
+    entity: Entity,
+    children: &Query<&Children>,
+    bones: &Query<&Transform>,
+    snapshot: &mut Vec<(Entity, Transform)>,
+) {
+    let entity_children = match children.get(entity) {
+        Ok(entity_children) => entity_children,
+        Err(_) => return,
+    };
+    for &child in entity_children.iter() {
+        if let Ok(transform) = bones.get(child) {
+            snapshot.push((child, *transform));
+        }
+        collect_bones(child, children, bones, snapshot);
+    }
+}
+
+pub fn blend_poses(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut blends: Query<(Entity, &mut PoseBlend)>,
+    mut transforms: Query<&mut Transform>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("blend_poses").entered();
+    for (entity, mut blend) in blends.iter_mut() {
+        blend.elapsed += time.delta_seconds();
+        let weight = blend.weight();
+        if weight <= 0. {
+            commands.entity(entity).remove::<PoseBlend>();
+            continue;
+        }
+        for (bone, from) in blend.bones.iter() {
+            if let Ok(mut transform) = transforms.get_mut(*bone) {
+                transform.translation = transform.translation.lerp(from.translation, weight);
+                transform.rotation = transform.rotation.slerp(from.rotation, weight);
+                transform.scale = transform.scale.lerp(from.scale, weight);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn step(
+        state: &mut CharacterAnimationState,
+        input: AnimationInput,
+        dt: f32,
+        config: &config::Animation,
+    ) {
+        let next = get_next_animation_state(state, &input, config);
+        if next == state.current {
+            state.time_in_state += dt;
+        } else {
+            *state = CharacterAnimationState {
+                current: next,
+                previous: state.current,
+                time_in_state: 0.,
+            };
+        }
+    }
+
+    #[test]
+    fn jump_goes_through_start_airborne_and_land() {
+        let config = config::Animation::default();
+        let mut state = CharacterAnimationState::default();
+        let grounded = AnimationInput {
+            grounded: true,
+            ..default()
+        };
+        step(
+            &mut state,
+            AnimationInput {
+                jumped: true,
+                ..grounded
+            },
+            0.01,
+            &config,
+        );
+        assert_eq!(state.current, AnimationState::JumpStart);
+        // Still touching the ground in the first frames of the jump
+        step(&mut state, grounded, 0.01, &config);
+        assert_eq!(state.current, AnimationState::JumpStart);
+        step(&mut state, AnimationInput::default(), 1., &config);
+        step(&mut state, AnimationInput::default(), 0.01, &config);
+        assert_eq!(state.current, AnimationState::Airborne);
+        step(
+            &mut state,
+            AnimationInput {
+                landed: true,
+                ..grounded
+            },
+            0.01,
+            &config,
+        );
+        assert_eq!(state.current, AnimationState::Land);
+        step(&mut state, grounded, 1., &config);
+        step(&mut state, grounded, 0.01, &config);
+        assert_eq!(state.current, AnimationState::Idle);
+    }
+
+    #[test]
+    fn walk_and_run_do_not_flicker_around_thresholds() {
+        let config = config::Animation::default();
+        let mut state = CharacterAnimationState::default();
+        let at_speed = |horizontal_speed| AnimationInput {
+            grounded: true,
+            horizontal_speed,
+            ..default()
+        };
+        step(&mut state, at_speed(config.walk_speed), 0.01, &config);
+        assert_eq!(state.current, AnimationState::Idle);
+        step(&mut state, at_speed(config.walk_speed + 0.5), 0.01, &config);
+        assert_eq!(state.current, AnimationState::Walk);
+        step(&mut state, at_speed(config.run_speed), 0.01, &config);
+        assert_eq!(state.current, AnimationState::Walk);
+        step(&mut state, at_speed(config.run_speed + 0.5), 0.01, &config);
+        assert_eq!(state.current, AnimationState::Run);
+        step(&mut state, at_speed(config.run_speed), 0.01, &config);
+        assert_eq!(state.current, AnimationState::Run);
+        step(&mut state, at_speed(config.run_speed - 0.5), 0.01, &config);
+        assert_eq!(state.current, AnimationState::Walk);
+        step(
+            &mut state,
+            AnimationInput {
+                crouching: true,
+                ..at_speed(config.walk_speed)
+            },
+            0.01,
+            &config,
+        );
+        assert_eq!(state.current, AnimationState::CrouchWalk);
+    }
+
+    #[test]
+    fn blend_time_falls_back_to_default() {
+        let mut config = config::Animation::default();
+        config.blend_times.insert("walk->run".to_string(), 0.5);
+        assert_eq!(
+            get_blend_time(AnimationState::Walk, AnimationState::Run, &config),
+            0.5
+        );
+        assert_eq!(
+            get_blend_time(AnimationState::Run, AnimationState::Walk, &config),
+            config.default_blend_time
+        );
+    }
+}
@@ -0,0 +1,223 @@
+use crate::movement::general_movement::{apply_jumping, apply_walking, Grounded, Jumping, Walking};
+use crate::movement::water::apply_swimming;
+use crate::util::log_error::log_errors;
+use crate::util::trait_extension::{MeshExt, Vec3Ext};
+use crate::GameState;
+use anyhow::{Context, Result};
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Handles ladders and climbing characters.
+/// Ladders are loaded from any entity whose name contains `"[ladder]"`. They become sensors shaped like the bounding box of their mesh.
+/// Characters with a [`Climbing`] component start climbing when they overlap a ladder and walk towards it.
+pub struct ClimbingPlugin;
+
+impl Plugin for ClimbingPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Ladder>()
+            .register_type::<Climbing>()
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(read_ladders.pipe(log_errors))
+                    .with_system(
+                        apply_climbing
+                            .before(apply_swimming)
+                            .before(apply_walking)
+                            .before(apply_jumping),
+                    ),
+            );
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Component, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Component, Serialize, Deserialize)]
+pub struct Ladder {
+    /// Center of the ladder in local space
+    pub center: Vec3,
+}
+
+#[derive(Debug, Clone, PartialEq, Component, Reflect, Serialize, Deserialize)]
+#[reflect(Component, Serialize, Deserialize)]
+pub struct Climbing {
+    /// Ladder that is currently being climbed
+    pub ladder: Option<Entity>,
+    /// Requested movement along the ladder this tick, where `1.0` is full speed upwards and `-1.0` full speed downwards
+    pub requested: f32,
+    /// Speed in m/s when climbing
+    pub speed: f32,
+    /// Distance in m the character is moved forward when dismounting at the top
+    pub dismount_step: f32,
+    /// Speed in m/s when jumping off the ladder
+    pub jump_off_speed: f32,
+    /// Time in seconds after dismounting during which the character cannot climb again
+    pub remount_cooldown: f32,
+    /// Remaining time in seconds until the character can climb again
+    pub time_until_remount: f32,
+}
+
+impl Climbing {
+    pub fn is_climbing(&self) -> bool {
+        self.ladder.is_some()
+    }
+}
+
+impl Default for Climbing {
+    fn default() -> Self {
+        Self {
+            ladder: None,
+            requested: 0.,
+            speed: 2.,
+            dismount_step: 0.5,
+            jump_off_speed: 4.,
+            remount_cooldown: 0.5,
+            time_until_remount: 0.,
+        }
+    }
+}
+
+pub fn read_ladders(
+    mut commands: Commands,
+    added_name: Query<(Entity, &Name), Added<Name>>,
+    children: Query<&Children>,
+    meshes: Res<Assets<Mesh>>,
+    mesh_handles: Query<&Handle<Mesh>>,
+) -> Result<()> {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("read_ladders").entered();
+    for (entity, name) in &added_name {
+        if name.to_lowercase().contains("[ladder]") {
+            for (ladder_entity, ladder_mesh) in
+                Mesh::search_in_children(entity, &children, &meshes, &mesh_handles)
+            {
+                let aabb = ladder_mesh
+                    .compute_aabb()
+                    .context("Failed to compute bounding box of ladder mesh")?;
+                let center = Vec3::from(aabb.center);
+                let half_extents = Vec3::from(aabb.half_extents);
+                commands.entity(ladder_entity).insert((
+                    Ladder { center },
+                    Collider::compound(vec![(
+                        center,
+                        Quat::IDENTITY,
+                        Collider::cuboid(half_extents.x, half_extents.y, half_extents.z),
+                    )]),
+                    Sensor,
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn apply_climbing(
+    time: Res<Time>,
+    mut character_query: Query<(
+        Entity,
+        &mut Climbing,
+        &mut Walking,
+        &mut Jumping,
+        &mut Transform,
+        &mut Velocity,
+        &mut ExternalImpulse,
+        &ReadMassProperties,
+        &Grounded,
+    )>,
+    ladders: Query<(&Ladder, &GlobalTransform)>,
+    rapier_context: Res<RapierContext>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("apply_climbing").entered();
+    let dt = time.delta_seconds();
+    for (
+        entity,
+        mut climbing,
+        mut walking,
+        mut jumping,
+        mut transform,
+        mut velocity,
+        mut impulse,
+        mass,
+        grounded,
+    ) in &mut character_query
+    {
+        climbing.time_until_remount = (climbing.time_until_remount - dt).max(0.);
+        let up = transform.up();
+        let overlapping_ladder = rapier_context
+            .intersections_with(entity)
+            .filter(|(_, _, intersecting)| *intersecting)
+            .map(|(a, b, _)| if a == entity { b } else { a })
+            .find_map(|other| ladders.get(other).ok().map(|ladder| (other, ladder)));
+
+        let ladder = match (climbing.ladder, overlapping_ladder) {
+            (None, Some((ladder_entity, (ladder, ladder_transform)))) => {
+                let to_ladder = (ladder_transform.transform_point(ladder.center)
+                    - transform.translation)
+                    .split(up)
+                    .horizontal
+                    .normalize_or_zero();
+                let is_walking_towards_ladder = walking
+                    .direction
+                    .and_then(|direction| direction.try_normalize())
+                    .map(|direction| direction.dot(to_ladder) > 0.5)
+                    .unwrap_or_default();
+                if !is_walking_towards_ladder || climbing.time_until_remount > 0. {
+                    continue;
+                }
+                climbing.ladder = Some(ladder_entity);
+                velocity.linvel = Vec3::ZERO;
+                (ladder, ladder_transform)
+            }
+            (Some(_), Some((_ladder_entity, ladder))) => ladder,
+            (Some(climbed_ladder), None) => {
+                // Left the ladder. If we were climbing up, step onto whatever the ladder leads to
+                if climbing.requested > 0. {
+                    if let Ok((ladder, ladder_transform)) = ladders.get(climbed_ladder) {
+                        let to_ladder = (ladder_transform.transform_point(ladder.center)
+                            - transform.translation)
+                            .split(up)
+                            .horizontal
+                            .normalize_or_zero();
+                        transform.translation += to_ladder * climbing.dismount_step;
+                    }
+                }
+                dismount(&mut climbing, &mut velocity);
+                continue;
+            }
+            (None, None) => continue,
+        };
+        let (ladder, ladder_transform) = ladder;
+        let ladder_up = ladder_transform.up();
+        let to_ladder = (ladder_transform.transform_point(ladder.center) - transform.translation)
+            .split(up)
+            .horizontal
+            .normalize_or_zero();
+
+        // Horizontal input is ignored while climbing
+        walking.direction = None;
+
+        if jumping.requested {
+            // Jumping off a ladder never consumes an air jump
+            jumping.requested = false;
+            if !jumping.previously_requested {
+                dismount(&mut climbing, &mut velocity);
+                let jump_direction = (-to_ladder + up * 0.5).normalize_or_zero();
+                impulse.impulse += jump_direction * climbing.jump_off_speed * mass.0.mass;
+                continue;
+            }
+        }
+
+        if grounded.0 && climbing.requested < 0. {
+            dismount(&mut climbing, &mut velocity);
+            continue;
+        }
+
+        velocity.linvel = ladder_up * climbing.requested * climbing.speed;
+    }
+}
+
+fn dismount(climbing: &mut Climbing, velocity: &mut Velocity) {
+    climbing.ladder = None;
+    climbing.time_until_remount = climbing.remount_cooldown;
+    velocity.linvel = Vec3::ZERO;
+}
@@ -0,0 +1,139 @@
+use crate::movement::general_movement::{apply_jumping, apply_walking, Jumping, Walking};
+use crate::player_control::player_embodiment::{Player, PlayerDashed};
+use crate::util::trait_extension::Vec3Ext;
+use crate::GameState;
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Handles short bursts of horizontal movement for characters with a [`Dashing`] component.
+/// While dashing, gravity is reduced and [`Walking`] is ignored. Jumping cancels a dash.
+pub struct DashPlugin;
+
+impl Plugin for DashPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Dashing>().add_system_set(
+            SystemSet::on_update(GameState::Playing)
+                .with_system(apply_dashing.before(apply_walking).before(apply_jumping)),
+        );
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Component, Reflect, Serialize, Deserialize)]
+#[reflect(Component, Serialize, Deserialize)]
+pub struct Dashing {
+    /// Whether a dash was requested this tick
+    pub requested: bool,
+    /// Speed in m/s while dashing
+    pub speed: f32,
+    /// Duration of a dash in seconds
+    pub duration: f32,
+    /// Time in seconds after a dash ends during which no new dash can be started
+    pub cooldown: f32,
+    /// Multiplier for gravity while dashing
+    pub gravity_scale: f32,
+    /// Direction of the current dash
+    pub direction: Vec3,
+    /// Remaining time in seconds of the current dash
+    pub time_remaining: f32,
+    /// Remaining time in seconds until the next dash can be started
+    pub cooldown_remaining: f32,
+}
+
+impl Default for Dashing {
+    fn default() -> Self {
+        Self {
+            requested: false,
+            speed: 15.,
+            duration: 0.2,
+            cooldown: 0.8,
+            gravity_scale: 0.2,
+            direction: Vec3::ZERO,
+            time_remaining: 0.,
+            cooldown_remaining: 0.,
+        }
+    }
+}
+
+impl Dashing {
+    pub fn is_dashing(&self) -> bool {
+        self.time_remaining > 0.
+    }
+
+    pub fn end(&mut self) {
+        self.time_remaining = 0.;
+        self.cooldown_remaining = self.cooldown;
+    }
+}
+
+pub fn apply_dashing(
+    time: Res<Time>,
+    rapier_configuration: Res<RapierConfiguration>,
+    mut character_query: Query<(
+        &mut Dashing,
+        &Walking,
+        &Jumping,
+        &Transform,
+        &mut Velocity,
+        &mut ExternalForce,
+        &ReadMassProperties,
+        &GravityScale,
+        Option<&Player>,
+    )>,
+    mut player_dashed_events: EventWriter<PlayerDashed>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("apply_dashing").entered();
+    let dt = time.delta_seconds();
+    for (
+        mut dashing,
+        walking,
+        jumping,
+        transform,
+        mut velocity,
+        mut force,
+        mass,
+        gravity_scale,
+        player,
+    ) in &mut character_query
+    {
+        let up = transform.up();
+        if dashing.is_dashing() {
+            if jumping.requested && !jumping.previously_requested {
+                // Cancel into the jump, which is then handled by the regular jumping logic
+                dashing.end();
+                continue;
+            }
+            dashing.time_remaining -= dt;
+            if !dashing.is_dashing() {
+                dashing.end();
+                continue;
+            }
+        } else {
+            dashing.cooldown_remaining = (dashing.cooldown_remaining - dt).max(0.);
+            if !dashing.requested || dashing.cooldown_remaining > 0. {
+                continue;
+            }
+            let direction = walking
+                .direction
+                .map(|direction| direction.split(up).horizontal)
+                .filter(|direction| !direction.is_approx_zero())
+                .unwrap_or_else(|| transform.forward().split(up).horizontal);
+            dashing.direction = direction.normalize_or_zero();
+            dashing.time_remaining = dashing.duration;
+            // Start the dash at the same height regardless of whether we were falling before
+            velocity.linvel = velocity.linvel.split(up).horizontal;
+            if player.is_some() {
+                player_dashed_events.send(PlayerDashed {
+                    direction: dashing.direction,
+                });
+            }
+        }
+
+        // Keep the full dash speed even against damping. Walls simply stop us through the regular collision response.
+        let vertical_velocity = velocity.linvel.split(up).vertical;
+        velocity.linvel = dashing.direction * dashing.speed + vertical_velocity;
+        let gravity = rapier_configuration.gravity * gravity_scale.0;
+        force.force -= gravity * (1. - dashing.gravity_scale) * mass.0.mass;
+    }
+}
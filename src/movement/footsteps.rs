@@ -0,0 +1,210 @@
+use crate::movement::general_movement::{
+    apply_walking, update_landing, GroundContact, Grounded, Landing,
+};
+use crate::util::trait_extension::Vec3Ext;
+use crate::GameState;
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Emits a [`Footstep`] event whenever a character with a [`FootstepTracker`] puts a foot on the ground.
+/// The cadence follows the character's horizontal speed, so running produces more steps than walking.
+/// Landing after a fall is reported as a footstep with a [`Footstep::landing_speed`].
+/// The [`SurfaceType`] of level geometry is read from the glTF node names at spawn time.
+pub struct FootstepPlugin;
+
+impl Plugin for FootstepPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<SurfaceType>()
+            .register_type::<FootstepTracker>()
+            .add_event::<Footstep>()
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(read_surface_types)
+                    .with_system(emit_footsteps.after(update_landing).after(apply_walking)),
+            );
+    }
+}
+
+/// What a piece of level geometry is made of. Determines which sounds are played and which dust is kicked up when walking on it.
+/// Colliders without a surface type inherit the one of their closest ancestor that has one.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, Component, Reflect, Serialize, Deserialize, Default,
+)]
+#[reflect(Component, Serialize, Deserialize)]
+pub enum SurfaceType {
+    #[default]
+    Unknown,
+    Grass,
+    Stone,
+    Wood,
+    Dirt,
+}
+
+impl SurfaceType {
+    /// Surfaces that can be assigned in the level, in the order in which they are matched against node names
+    pub const ALL: [SurfaceType; 4] = [
+        SurfaceType::Grass,
+        SurfaceType::Stone,
+        SurfaceType::Wood,
+        SurfaceType::Dirt,
+    ];
+
+    /// Lowercase name used for node names and sound file names
+    pub fn name(self) -> &'static str {
+        match self {
+            SurfaceType::Unknown => "default",
+            SurfaceType::Grass => "grass",
+            SurfaceType::Stone => "stone",
+            SurfaceType::Wood => "wood",
+            SurfaceType::Dirt => "dirt",
+        }
+    }
+
+    /// Finds the surface type mentioned in a node or material name, e.g. "Cobblestone Path" or "Floor [wood]"
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.to_lowercase();
+        Self::ALL
+            .into_iter()
+            .find(|surface| name.contains(surface.name()))
+    }
+}
+
+/// Sent whenever a character's foot touches the ground
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Footstep {
+    /// The character that made the step
+    pub entity: Entity,
+    /// Point on the ground where the step happened
+    pub position: Vec3,
+    pub surface: SurfaceType,
+    /// Downwards speed in m/s if this step is the landing after a fall
+    pub landing_speed: Option<f32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Component, Reflect, Serialize, Deserialize)]
+#[reflect(Component, Serialize, Deserialize)]
+pub struct FootstepTracker {
+    /// Horizontal distance in m between two steps
+    pub stride_length: f32,
+    /// Landings slower than this in m/s are not reported, e.g. when walking down a small step
+    pub min_landing_speed: f32,
+    /// Horizontal distance in m walked since the last step
+    pub distance_since_last_step: f32,
+}
+
+impl Default for FootstepTracker {
+    fn default() -> Self {
+        Self {
+            stride_length: 0.8,
+            min_landing_speed: 2.,
+            distance_since_last_step: 0.,
+        }
+    }
+}
+
+fn read_surface_types(mut commands: Commands, added_name: Query<(Entity, &Name), Added<Name>>) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("read_surface_types").entered();
+    for (entity, name) in &added_name {
+        if let Some(surface) = SurfaceType::from_name(name) {
+            commands.entity(entity).insert(surface);
+        }
+    }
+}
+
+fn emit_footsteps(
+    time: Res<Time>,
+    mut character_query: Query<(
+        Entity,
+        &Transform,
+        &Velocity,
+        &Grounded,
+        &GroundContact,
+        &Landing,
+        &mut FootstepTracker,
+    )>,
+    surface_query: Query<&SurfaceType>,
+    parent_query: Query<&Parent>,
+    mut footstep_events: EventWriter<Footstep>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("emit_footsteps").entered();
+    let dt = time.delta_seconds();
+    for (entity, transform, velocity, grounded, ground_contact, landing, mut tracker) in
+        &mut character_query
+    {
+        if !grounded.0 {
+            continue;
+        }
+        let surface = || {
+            ground_contact
+                .entity
+                .map(|ground| get_surface_type(ground, &surface_query, &parent_query))
+                .unwrap_or_default()
+        };
+
+        if let Some(landing_speed) = landing.impact_speed {
+            tracker.distance_since_last_step = 0.;
+            if landing_speed >= tracker.min_landing_speed {
+                footstep_events.send(Footstep {
+                    entity,
+                    position: ground_contact.point,
+                    surface: surface(),
+                    landing_speed: Some(landing_speed),
+                });
+            }
+            continue;
+        }
+
+        // Standing still on a moving platform should not produce steps
+        let own_velocity = velocity.linvel - ground_contact.platform_velocity;
+        let horizontal_speed = own_velocity.split(transform.up()).horizontal.length();
+        tracker.distance_since_last_step += horizontal_speed * dt;
+        if tracker.distance_since_last_step >= tracker.stride_length {
+            tracker.distance_since_last_step %= tracker.stride_length;
+            footstep_events.send(Footstep {
+                entity,
+                position: ground_contact.point,
+                surface: surface(),
+                landing_speed: None,
+            });
+        }
+    }
+}
+
+/// The surface type of a collider, inherited from its closest ancestor that has one
+pub fn get_surface_type(
+    entity: Entity,
+    surface_query: &Query<&SurfaceType>,
+    parent_query: &Query<&Parent>,
+) -> SurfaceType {
+    let mut current = entity;
+    loop {
+        if let Ok(surface) = surface_query.get(current) {
+            return *surface;
+        }
+        match parent_query.get(current) {
+            Ok(parent) => current = parent.get(),
+            Err(_) => return SurfaceType::Unknown,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn reads_surface_type_from_name() {
+        assert_eq!(
+            SurfaceType::from_name("Cobblestone Path"),
+            Some(SurfaceType::Stone)
+        );
+        assert_eq!(
+            SurfaceType::from_name("Floor [collider][wood]"),
+            Some(SurfaceType::Wood)
+        );
+        assert_eq!(SurfaceType::from_name("Fountain"), None);
+    }
+}
@@ -1,10 +1,13 @@
-use anyhow::{Context, Result};
 use bevy::prelude::*;
 
 use bevy_rapier3d::prelude::*;
 mod components;
-use crate::level_instantiation::spawning::AnimationEntityLink;
-use crate::util::log_error::log_errors;
+use crate::movement::activity::Dormant;
+use crate::movement::climbing::{apply_climbing, Climbing};
+use crate::movement::dash::Dashing;
+use crate::movement::physics::{get_damping_factor, get_physics_timestep};
+use crate::movement::water::{update_swimming, Swimming};
+use crate::player_control::player_embodiment::{Player, PlayerJumped};
 use crate::util::trait_extension::Vec3Ext;
 use crate::GameState;
 pub use components::*;
@@ -31,47 +34,290 @@ pub use components::*;
 /// - An instantaneous force (i.e. an impulse) like jumping: `external_impulse.impulse += velocity * read_mass_properties.0.mass`, with `external_impulse`: [`ExternalImpulse`], `read_mass_properties`: [`ReadMassProperties`], and a user-defined `velocity`: [`Vec3`]
 ///
 /// Note: you might notice that the normal force is not included in the above diagram. This is because rapier emulates it by moving penetrating colliders out of each other.
+///
+/// Characters with a [`KinematicMovement`] and a [`KinematicCharacterController`] are not simulated as dynamic rigid bodies.
+/// Instead, the forces and impulses described above are integrated by hand and the result is passed to the controller,
+/// so the above still applies to them.
+///
+/// The [`GravityScale`] of a character is derived from its [`BaseGravityScale`] and whether it is swimming or climbing, see [`update_gravity_scale`].
+/// Likewise, its [`Damping`] is derived from its [`BaseDamping`] and whether it is swimming, see [`update_damping`].
+///
+/// Characters with a [`Dormant`] marker are skipped.
 pub struct GeneralMovementPlugin;
 
 impl Plugin for GeneralMovementPlugin {
     fn build(&self, app: &mut App) {
         app.register_type::<Model>()
             .register_type::<Grounded>()
+            .register_type::<GroundContact>()
+            .register_type::<Landing>()
+            .register_type::<BaseGravityScale>()
+            .register_type::<BaseDamping>()
+            .register_type::<KinematicMovement>()
             .register_type::<Jumping>()
+            .register_type::<AirJumps>()
             .register_type::<Velocity>()
             .register_type::<Walking>()
             .register_type::<CharacterAnimations>()
             .add_system_set(
                 SystemSet::on_update(GameState::Playing)
                     .with_system(reset_movement_components)
-                    .with_system(update_grounded.after(reset_movement_components))
-                    .with_system(apply_walking.after(update_grounded))
-                    .with_system(apply_jumping.after(update_grounded))
-                    .with_system(rotate_characters.after(update_grounded))
-                    .with_system(play_animations.pipe(log_errors).after(update_grounded)),
+                    .with_system(read_kinematic_movement.after(reset_movement_components))
+                    .with_system(
+                        update_grounded
+                            .after(reset_movement_components)
+                            .after(read_kinematic_movement),
+                    )
+                    .with_system(update_landing.after(update_grounded))
+                    .with_system(
+                        update_gravity_scale
+                            .after(update_swimming)
+                            .after(apply_climbing),
+                    )
+                    .with_system(update_damping.after(update_swimming))
+                    .with_system(
+                        apply_kinematic_movement
+                            .after(apply_walking)
+                            .after(apply_jumping),
+                    )
+                    .with_system(
+                        apply_walking
+                            .after(update_grounded)
+                            .after(update_gravity_scale)
+                            .after(update_damping),
+                    )
+                    .with_system(
+                        apply_jumping
+                            .after(update_grounded)
+                            .after(update_gravity_scale)
+                            .after(update_damping),
+                    )
+                    .with_system(rotate_characters.after(update_grounded)),
             );
     }
 }
 
-fn update_grounded(
-    mut query: Query<(Entity, &Transform, &Collider, &mut Grounded)>,
+pub fn update_grounded(
+    mut query: Query<
+        (
+            Entity,
+            &Transform,
+            &Collider,
+            &mut Grounded,
+            Option<&mut GroundContact>,
+            Option<&KinematicCharacterControllerOutput>,
+        ),
+        Without<Dormant>,
+    >,
     rapier_context: Res<RapierContext>,
 ) {
     #[cfg(feature = "tracing")]
     let _span = info_span!("update_grounded").entered();
-    for (entity, transform, collider, mut grounded) in &mut query {
-        let height = collider.raw.compute_local_aabb().maxs.y;
-        grounded.0 = rapier_context
-            .cast_ray(
+    for (entity, transform, collider, mut grounded, ground_contact, kinematic_output) in &mut query
+    {
+        let probe = probe_ground(&rapier_context, entity, transform, collider);
+        let ground = probe.ray_hit;
+        grounded.0 = match kinematic_output {
+            Some(kinematic_output) => kinematic_output.grounded,
+            None => ground.is_some(),
+        };
+        if let Some(mut ground_contact) = ground_contact {
+            let up = transform.up();
+            ground_contact.entity = ground.map(|(ground_entity, _intersection)| ground_entity);
+            ground_contact.point = ground
+                .map(|(_ground_entity, intersection)| intersection.point)
+                .unwrap_or(transform.translation);
+            ground_contact.normal = match ground {
+                Some((_ground_entity, intersection)) => probe
+                    .sphere_hit
+                    .map(|(_ground_entity, toi)| toi.normal1)
+                    .filter(|normal| normal.dot(up) > intersection.normal.dot(up))
+                    .unwrap_or(intersection.normal),
+                None => up,
+            };
+        }
+    }
+}
+
+/// What is below a character, see [`probe_ground`]
+#[derive(Debug, Clone, Copy)]
+pub struct GroundProbe {
+    /// Length of the ray cast down from the character's center
+    pub ray_length: f32,
+    pub ray_hit: Option<(Entity, RayIntersection)>,
+    /// Radius of the sphere swept down from the character's center, only if the ray hit something
+    pub sphere_radius: f32,
+    pub sphere_distance: f32,
+    /// Ground hit by the sphere, unless the sphere started inside of it
+    pub sphere_hit: Option<(Entity, Toi)>,
+}
+
+/// Casts a ray below the character's center to find the ground.
+/// The ray only samples the ground right below the center. When standing across an edge between
+/// a walkable and a too steep surface, the rest of the footprint might be supported by the walkable one,
+/// so a sphere is swept down as well, which lets [`update_grounded`] keep whichever normal is the most upright.
+pub fn probe_ground(
+    rapier_context: &RapierContext,
+    entity: Entity,
+    transform: &Transform,
+    collider: &Collider,
+) -> GroundProbe {
+    let aabb = collider.raw.compute_local_aabb();
+    let height = aabb.maxs.y;
+    let filter = QueryFilter::new()
+        .exclude_collider(entity)
+        .exclude_sensors();
+    let ray_length = height + 0.1;
+    let ray_hit = rapier_context.cast_ray_and_get_normal(
+        transform.translation,
+        transform.down(),
+        ray_length,
+        true,
+        filter,
+    );
+    let sphere_radius = aabb.maxs.x.min(aabb.maxs.z) * 0.9;
+    let sphere_distance = ray_length - sphere_radius;
+    let sphere_hit = ray_hit
+        .and_then(|_| {
+            rapier_context.cast_shape(
                 transform.translation,
+                Quat::IDENTITY,
                 transform.down(),
-                height + 0.1,
-                true,
-                QueryFilter::new()
-                    .exclude_collider(entity)
-                    .exclude_sensors(),
+                &Collider::ball(sphere_radius),
+                sphere_distance,
+                filter,
             )
-            .is_some();
+        })
+        .filter(|(_ground_entity, toi)| toi.status != TOIStatus::Penetrating);
+    GroundProbe {
+        ray_length,
+        ray_hit,
+        sphere_radius,
+        sphere_distance,
+        sphere_hit,
+    }
+}
+
+/// Turns the movement a [`KinematicCharacterController`] actually performed last tick back into a velocity
+fn read_kinematic_movement(
+    mut character_query: Query<(
+        &KinematicMovement,
+        &KinematicCharacterControllerOutput,
+        &mut Velocity,
+    )>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("read_kinematic_movement").entered();
+    for (kinematic_movement, output, mut velocity) in &mut character_query {
+        if kinematic_movement.last_dt > 0. {
+            velocity.linvel = output.effective_translation / kinematic_movement.last_dt;
+        }
+    }
+}
+
+/// The only system that writes [`GravityScale`], so that overlapping states like climbing a ladder out of the water
+/// cannot leave a character with the gravity of a state it has already left.
+pub fn update_gravity_scale(
+    mut character_query: Query<(
+        &BaseGravityScale,
+        &mut GravityScale,
+        Option<&Swimming>,
+        Option<&Climbing>,
+    )>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("update_gravity_scale").entered();
+    for (base_gravity_scale, mut gravity_scale, swimming, climbing) in &mut character_query {
+        gravity_scale.0 = get_gravity_scale(base_gravity_scale.0, swimming, climbing);
+    }
+}
+
+fn get_gravity_scale(base: f32, swimming: Option<&Swimming>, climbing: Option<&Climbing>) -> f32 {
+    if climbing.map(Climbing::is_climbing).unwrap_or_default() {
+        return 0.;
+    }
+    match swimming.filter(|swimming| swimming.is_swimming()) {
+        Some(swimming) => base * swimming.gravity_scale,
+        None => base,
+    }
+}
+
+/// The only system that writes the linear [`Damping`] of characters, see [`update_gravity_scale`] for why.
+pub fn update_damping(mut character_query: Query<(&BaseDamping, &mut Damping, Option<&Swimming>)>) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("update_damping").entered();
+    for (base_damping, mut damping, swimming) in &mut character_query {
+        let linear_damping = match swimming.filter(|swimming| swimming.is_swimming()) {
+            Some(swimming) => swimming.linear_damping,
+            None => base_damping.0,
+        };
+        // Don't trigger change detection every frame
+        if damping.linear_damping != linear_damping {
+            damping.linear_damping = linear_damping;
+        }
+    }
+}
+
+/// Integrates the forces and impulses applied to kinematic characters the same way rapier does for dynamic bodies
+/// and hands the result to their [`KinematicCharacterController`].
+pub fn apply_kinematic_movement(
+    time: Res<Time>,
+    rapier_configuration: Res<RapierConfiguration>,
+    mut character_query: Query<(
+        &mut KinematicMovement,
+        &mut KinematicCharacterController,
+        &mut Velocity,
+        &ExternalForce,
+        &ExternalImpulse,
+        &ReadMassProperties,
+        &GravityScale,
+        &Damping,
+    )>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("apply_kinematic_movement").entered();
+    let (dt, substeps) = get_physics_timestep(&rapier_configuration, &time);
+    for (
+        mut kinematic_movement,
+        mut controller,
+        mut velocity,
+        force,
+        impulse,
+        mass,
+        gravity_scale,
+        damping,
+    ) in &mut character_query
+    {
+        let inverse_mass = if mass.0.mass > 0. {
+            1. / mass.0.mass
+        } else {
+            0.
+        };
+        let acceleration =
+            force.force * inverse_mass + rapier_configuration.gravity * gravity_scale.0;
+        let linvel = (velocity.linvel + impulse.impulse * inverse_mass + acceleration * dt)
+            * get_damping_factor(dt, substeps, damping.linear_damping);
+        velocity.linvel = linvel;
+        controller.translation = Some(linvel * dt);
+        kinematic_movement.last_dt = dt;
+    }
+}
+
+pub fn update_landing(mut query: Query<(&Transform, &Velocity, &Grounded, &mut Landing)>) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("update_landing").entered();
+    for (transform, velocity, grounded, mut landing) in &mut query {
+        landing.impact_speed = None;
+        if !grounded.0 {
+            landing.was_grounded = false;
+            landing.last_airborne_vertical_speed = velocity.linvel.dot(transform.up());
+            continue;
+        }
+        if !landing.was_grounded {
+            landing.was_grounded = true;
+            // Rapier already stopped us this tick, so use the speed from right before the impact
+            landing.impact_speed = Some((-landing.last_airborne_vertical_speed).max(0.));
+        }
     }
 }
 
@@ -80,6 +326,7 @@ pub fn reset_movement_components(
     mut impulses: Query<&mut ExternalImpulse>,
     mut walking: Query<&mut Walking>,
     mut jumpers: Query<&mut Jumping>,
+    mut dashers: Query<&mut Dashing>,
 ) {
     #[cfg(feature = "tracing")]
     let _span = info_span!("reset_movement_components").entered();
@@ -93,36 +340,96 @@ pub fn reset_movement_components(
         walk.direction = None;
     }
     for mut jumper in &mut jumpers {
+        jumper.previously_requested = jumper.requested;
         jumper.requested = false;
     }
+    for mut dasher in &mut dashers {
+        dasher.requested = false;
+    }
 }
 
 pub fn apply_jumping(
-    mut character_query: Query<(
-        &Grounded,
-        &mut ExternalImpulse,
-        &mut Velocity,
-        &ReadMassProperties,
-        &Jumping,
-        &Transform,
-    )>,
+    time: Res<Time>,
+    mut character_query: Query<
+        (
+            &Grounded,
+            &mut ExternalImpulse,
+            &mut Velocity,
+            &ReadMassProperties,
+            &mut Jumping,
+            Option<&mut AirJumps>,
+            &Transform,
+            Option<&Player>,
+            Option<&Dashing>,
+        ),
+        Without<Dormant>,
+    >,
+    mut player_jumped_events: EventWriter<PlayerJumped>,
 ) {
     #[cfg(feature = "tracing")]
     let _span = info_span!("apply_jumping").entered();
-    for (grounded, mut impulse, mut velocity, mass, jump, transform) in &mut character_query {
-        if jump.requested && grounded.0 {
-            let up = transform.up();
-            impulse.impulse += up * mass.0.mass * jump.speed;
+    let dt = time.delta_seconds();
+    for (
+        grounded,
+        mut impulse,
+        mut velocity,
+        mass,
+        mut jump,
+        mut air_jumps,
+        transform,
+        player,
+        dashing,
+    ) in &mut character_query
+    {
+        let is_dashing = dashing.map(Dashing::is_dashing).unwrap_or_default();
+        if grounded.0 {
+            jump.time_since_grounded = 0.;
+            if let Some(ref mut air_jumps) = air_jumps {
+                air_jumps.remaining = air_jumps.max;
+            }
+        } else if !is_dashing {
+            // Dashing off a ledge should still leave the full coyote time afterwards
+            jump.time_since_grounded += dt;
+        }
+        if !jump.requested {
+            continue;
+        }
+
+        let jump_speed = if jump.can_ground_jump(grounded.0) {
+            // Using up the coyote time must not consume an air jump
+            jump.time_since_grounded = f32::INFINITY;
+            Some((jump.speed, false))
+        } else {
+            match air_jumps {
+                Some(ref mut air_jumps)
+                    if air_jumps.remaining > 0 && !jump.previously_requested =>
+                {
+                    air_jumps.remaining -= 1;
+                    Some((jump.air_speed, true))
+                }
+                _ => None,
+            }
+        };
 
+        if let Some((speed, air)) = jump_speed {
+            let up = transform.up();
             // Kill any downward velocity. This ensures that repeated jumps are always the same height.
             // Otherwise the falling velocity from the last tick would dampen the jump velocity.
             let velocity_components = velocity.linvel.split(up);
             velocity.linvel = velocity_components.horizontal;
+            impulse.impulse += up * mass.0.mass * speed;
+
+            if player.is_some() {
+                player_jumped_events.send(PlayerJumped { air });
+            }
         }
     }
 }
 
-fn rotate_characters(time: Res<Time>, mut player_query: Query<(&Velocity, &mut Transform)>) {
+fn rotate_characters(
+    time: Res<Time>,
+    mut player_query: Query<(&Velocity, &mut Transform), Without<Dormant>>,
+) {
     #[cfg(feature = "tracing")]
     let _span = info_span!("rotate_characters").entered();
     let dt = time.delta_seconds();
@@ -142,65 +449,78 @@ fn rotate_characters(time: Res<Time>, mut player_query: Query<(&Velocity, &mut T
     }
 }
 
-fn play_animations(
-    mut animation_player: Query<&mut AnimationPlayer>,
-    characters: Query<(
-        &Velocity,
-        &Transform,
-        &Grounded,
-        &AnimationEntityLink,
-        &CharacterAnimations,
-    )>,
-) -> Result<()> {
-    #[cfg(feature = "tracing")]
-    let _span = info_span!("play_animations").entered();
-    for (velocity, transform, grounded, animation_entity_link, animations) in characters.iter() {
-        let mut animation_player = animation_player
-            .get_mut(animation_entity_link.0)
-            .context("animation_entity_link held entity without animation player")?;
-
-        let has_horizontal_movement = !velocity
-            .linvel
-            .split(transform.up())
-            .horizontal
-            .is_approx_zero();
-
-        if !grounded.0 {
-            animation_player
-                .play(animations.aerial.clone_weak())
-                .repeat();
-        } else if has_horizontal_movement {
-            animation_player.play(animations.walk.clone_weak()).repeat();
-        } else {
-            animation_player.play(animations.idle.clone_weak()).repeat();
-        }
-    }
-    Ok(())
-}
-
 pub fn apply_walking(
-    mut character_query: Query<(
-        &mut ExternalForce,
-        &Walking,
-        &mut Velocity,
-        &Grounded,
-        &ReadMassProperties,
-        &Transform,
-    )>,
+    mut character_query: Query<
+        (
+            &mut ExternalForce,
+            &Walking,
+            &mut Velocity,
+            &Grounded,
+            &ReadMassProperties,
+            &Transform,
+            Option<&GroundContact>,
+            &GravityScale,
+            Option<&Dashing>,
+        ),
+        Without<Dormant>,
+    >,
+    rapier_configuration: Res<RapierConfiguration>,
 ) {
     #[cfg(feature = "tracing")]
     let _span = info_span!("apply_walking").entered();
-    for (mut force, walking, mut velocity, grounded, mass, transform) in &mut character_query {
+    for (
+        mut force,
+        walking,
+        mut velocity,
+        grounded,
+        mass,
+        transform,
+        ground_contact,
+        gravity_scale,
+        dashing,
+    ) in &mut character_query
+    {
+        if dashing.map(Dashing::is_dashing).unwrap_or_default() {
+            continue;
+        }
         let mass = mass.0.mass;
+        let up = transform.up();
+        let ground_normal = ground_contact
+            .filter(|_| grounded.0)
+            .map(|ground_contact| ground_contact.normal);
+        match ground_normal {
+            Some(normal) if !walking.is_walkable(normal, up) => {
+                // Too steep: slide down and don't allow walking up, but still allow steering sideways and jumping
+                let fall_line = get_fall_line(normal, up);
+                let acceleration = walking
+                    .get_acceleration(true)
+                    .map(|acceleration| restrict_to_slope(acceleration, normal, fall_line))
+                    .unwrap_or_default();
+                force.force += (acceleration + fall_line * walking.slide_acceleration) * mass;
+                continue;
+            }
+            Some(normal) => {
+                // Cancel the part of gravity that would make us creep down the slope
+                let gravity = rapier_configuration.gravity * gravity_scale.0;
+                let gravity_along_slope = gravity - normal * gravity.dot(normal);
+                force.force -= gravity_along_slope * mass;
+            }
+            None => {}
+        }
+
         if let Some(acceleration) = walking.get_acceleration(grounded.0) {
             let walking_force = acceleration * mass;
             force.force += walking_force;
         } else if grounded.0 {
-            let velocity_components = velocity.linvel.split(transform.up());
+            // Brake relative to the ground so that we don't slide off moving platforms
+            let platform_velocity = ground_contact
+                .map(|ground_contact| ground_contact.platform_velocity)
+                .unwrap_or_default();
+            let velocity_components = (velocity.linvel - platform_velocity).split(transform.up());
             if velocity_components.horizontal.length_squared()
                 < walking.stopping_speed * walking.stopping_speed
             {
-                velocity.linvel = velocity_components.vertical;
+                velocity.linvel = velocity_components.vertical + platform_velocity;
             } else if let Some(braking_direction) =
                 velocity_components.horizontal.try_normalize().map(|v| -v)
             {
@@ -210,3 +530,126 @@ pub fn apply_walking(
         }
     }
 }
+
+/// Direction in which something would slide down a surface with the given normal
+fn get_fall_line(normal: Vec3, up: Vec3) -> Vec3 {
+    let down = -up;
+    (down - normal * down.dot(normal)).normalize_or_zero()
+}
+
+/// Projects an acceleration onto a slope and removes the part of it that points uphill
+fn restrict_to_slope(acceleration: Vec3, normal: Vec3, fall_line: Vec3) -> Vec3 {
+    let on_slope = acceleration - normal * acceleration.dot(normal);
+    let uphill = -on_slope.dot(fall_line);
+    if uphill > 0. {
+        on_slope + fall_line * uphill
+    } else {
+        on_slope
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::util::test_util::assert_nearly_eq;
+
+    #[test]
+    fn fall_line_on_flat_ground_is_zero() {
+        assert_eq!(get_fall_line(Vec3::Y, Vec3::Y), Vec3::ZERO);
+    }
+
+    #[test]
+    fn fall_line_points_down_the_slope() {
+        let normal = Vec3::new(1., 1., 0.).normalize();
+        let fall_line = get_fall_line(normal, Vec3::Y);
+        assert_nearly_eq(fall_line, Vec3::new(1., -1., 0.).normalize());
+    }
+
+    #[test]
+    fn cannot_walk_up_steep_slope() {
+        let normal = Vec3::new(1., 1., 0.).normalize();
+        let fall_line = get_fall_line(normal, Vec3::Y);
+        let uphill = Vec3::new(-1., 0., 0.);
+        assert_nearly_eq(restrict_to_slope(uphill, normal, fall_line), Vec3::ZERO);
+    }
+
+    #[test]
+    fn can_walk_sideways_and_down_steep_slope() {
+        let normal = Vec3::new(1., 1., 0.).normalize();
+        let fall_line = get_fall_line(normal, Vec3::Y);
+        let sideways = Vec3::new(0., 0., 1.);
+        assert_nearly_eq(restrict_to_slope(sideways, normal, fall_line), sideways);
+        let downhill = Vec3::new(1., 0., 0.);
+        let restricted = restrict_to_slope(downhill, normal, fall_line);
+        assert!(restricted.dot(fall_line) > 0.);
+        assert!(restricted.dot(normal).abs() < 1e-5);
+    }
+
+    #[test]
+    fn overlapping_water_and_ladder_restore_base_gravity() {
+        let mut app = App::new();
+        app.add_system(update_gravity_scale);
+        let character = app
+            .world
+            .spawn((
+                BaseGravityScale(2.),
+                GravityScale(2.),
+                Swimming::default(),
+                Climbing::default(),
+            ))
+            .id();
+        let set_state = |app: &mut App, in_water: bool, on_ladder: bool| {
+            let mut entity = app.world.entity_mut(character);
+            entity.get_mut::<Swimming>().unwrap().water_surface = in_water.then_some(Vec3::ZERO);
+            entity.get_mut::<Climbing>().unwrap().ladder = on_ladder.then_some(character);
+            app.update();
+            app.world.get::<GravityScale>(character).unwrap().0
+        };
+
+        assert_eq!(
+            set_state(&mut app, true, false),
+            2. * Swimming::default().gravity_scale
+        );
+        assert_eq!(set_state(&mut app, true, true), 0.);
+        // Leaving the water while climbing, then dismounting on dry land
+        assert_eq!(set_state(&mut app, false, true), 0.);
+        assert_eq!(set_state(&mut app, false, false), 2.);
+        // Dismounting into the water
+        set_state(&mut app, false, true);
+        assert_eq!(
+            set_state(&mut app, true, false),
+            2. * Swimming::default().gravity_scale
+        );
+    }
+
+    #[test]
+    fn leaving_water_restores_current_base_damping() {
+        let mut app = App::new();
+        app.add_system(update_damping);
+        let character = app
+            .world
+            .spawn((BaseDamping(1.5), Damping::default(), Swimming::default()))
+            .id();
+        let set_in_water = |app: &mut App, in_water: bool| {
+            app.world
+                .get_mut::<Swimming>(character)
+                .unwrap()
+                .water_surface = in_water.then_some(Vec3::ZERO);
+            app.update();
+            app.world.get::<Damping>(character).unwrap().linear_damping
+        };
+
+        assert_eq!(set_in_water(&mut app, false), 1.5);
+        assert_eq!(
+            set_in_water(&mut app, true),
+            Swimming::default().linear_damping
+        );
+        // Changed while swimming
+        app.world.get_mut::<BaseDamping>(character).unwrap().0 = 0.5;
+        assert_eq!(
+            set_in_water(&mut app, true),
+            Swimming::default().linear_damping
+        );
+        assert_eq!(set_in_water(&mut app, false), 0.5);
+    }
+}
@@ -4,12 +4,16 @@ use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Bundle)]
 pub struct CharacterControllerBundle {
+    pub base_gravity_scale: BaseGravityScale,
     pub gravity_scale: GravityScale,
     pub mass: ColliderMassProperties,
     pub read_mass: ReadMassProperties,
     pub walking: Walking,
     pub jumping: Jumping,
     pub grounded: Grounded,
+    pub ground_contact: GroundContact,
+    pub landing: Landing,
+    pub base_damping: BaseDamping,
     pub damping: Damping,
     pub rigid_body: RigidBody,
     pub locked_axes: LockedAxes,
@@ -24,12 +28,16 @@ impl Default for CharacterControllerBundle {
     fn default() -> Self {
         Self {
             read_mass: default(),
+            base_gravity_scale: default(),
             gravity_scale: GravityScale(1.0),
             force: default(),
             mass: ColliderMassProperties::Mass(3.0),
             walking: default(),
             jumping: default(),
             grounded: default(),
+            ground_contact: default(),
+            landing: default(),
+            base_damping: default(),
             damping: Damping {
                 linear_damping: 1.5,
                 ..default()
@@ -53,6 +61,28 @@ impl CharacterControllerBundle {
     }
 }
 
+/// Gravity scale of a character on dry land. Its [`GravityScale`] is derived from this by [`update_gravity_scale`](crate::movement::general_movement::update_gravity_scale).
+#[derive(Debug, Clone, Copy, PartialEq, Component, Reflect, Serialize, Deserialize)]
+#[reflect(Component, Serialize, Deserialize)]
+pub struct BaseGravityScale(pub f32);
+
+impl Default for BaseGravityScale {
+    fn default() -> Self {
+        Self(1.)
+    }
+}
+
+/// Linear damping of a character on dry land. Its [`Damping`] is derived from this by [`update_damping`](crate::movement::general_movement::update_damping).
+#[derive(Debug, Clone, Copy, PartialEq, Component, Reflect, Serialize, Deserialize)]
+#[reflect(Component, Serialize, Deserialize)]
+pub struct BaseDamping(pub f32);
+
+impl Default for BaseDamping {
+    fn default() -> Self {
+        Self(1.5)
+    }
+}
+
 #[derive(Debug, Clone, Eq, PartialEq, Component, Reflect, Serialize, Deserialize, Default)]
 #[reflect(Component, Serialize, Deserialize)]
 pub struct Model;
@@ -74,6 +104,10 @@ pub struct Walking {
     pub direction: Option<Vec3>,
     /// Whether we are sprinting this tick
     pub sprinting: bool,
+    /// Steepest slope in radians that can be walked up. Steeper slopes make the character slide down.
+    pub max_walkable_slope: f32,
+    /// Acceleration down the fall line when standing on a slope steeper than [`Walking::max_walkable_slope`]
+    pub slide_acceleration: f32,
 }
 
 impl Walking {
@@ -89,6 +123,10 @@ impl Walking {
         };
         self.direction.map(|dir| dir * acceleration)
     }
+
+    pub fn is_walkable(&self, ground_normal: Vec3, up: Vec3) -> bool {
+        ground_normal.angle_between(up) <= self.max_walkable_slope
+    }
 }
 
 impl Default for Walking {
@@ -101,6 +139,8 @@ impl Default for Walking {
             stopping_speed: 0.1,
             direction: None,
             sprinting: false,
+            max_walkable_slope: 45_f32.to_radians(),
+            slide_acceleration: 6.,
         }
     }
 }
@@ -109,28 +149,127 @@ impl Default for Walking {
 #[reflect(Component, Serialize, Deserialize)]
 pub struct Grounded(pub bool);
 
+/// What a character is standing on. Updated together with [`Grounded`].
+#[derive(Debug, Clone, PartialEq, Component, Reflect, Serialize, Deserialize)]
+#[reflect(Component, Serialize, Deserialize)]
+pub struct GroundContact {
+    /// The collider below the character, if it is [`Grounded`]
+    pub entity: Option<Entity>,
+    /// Point where the character touches the ground
+    pub point: Vec3,
+    /// Normal of the ground below the character. Points up when not [`Grounded`].
+    pub normal: Vec3,
+    /// Velocity of the ground at [`GroundContact::point`] in m/s, e.g. when standing on a moving platform
+    pub platform_velocity: Vec3,
+    /// The part of the character's velocity that was inherited from the ground last tick, after rapier applied damping to it
+    pub inherited_velocity: Vec3,
+}
+
+impl Default for GroundContact {
+    fn default() -> Self {
+        Self {
+            entity: None,
+            point: Vec3::ZERO,
+            normal: Vec3::Y,
+            platform_velocity: Vec3::ZERO,
+            inherited_velocity: Vec3::ZERO,
+        }
+    }
+}
+
+/// Tracks the transition from being airborne to being [`Grounded`].
+#[derive(Debug, Clone, PartialEq, Component, Reflect, Serialize, Deserialize)]
+#[reflect(Component, Serialize, Deserialize)]
+pub struct Landing {
+    /// Downwards speed in m/s right before touching the ground. Only set during the tick in which the character landed.
+    pub impact_speed: Option<f32>,
+    pub was_grounded: bool,
+    /// Vertical speed in m/s during the last tick spent in the air
+    pub last_airborne_vertical_speed: f32,
+}
+
+impl Default for Landing {
+    fn default() -> Self {
+        Self {
+            impact_speed: None,
+            was_grounded: true,
+            last_airborne_vertical_speed: 0.,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Component, Reflect, Serialize, Deserialize)]
 #[reflect(Component, Serialize, Deserialize)]
 pub struct Jumping {
     /// Speed of the jump in m/s
     pub speed: f32,
+    /// Speed of a jump performed while airborne in m/s. Only relevant when the character has [`AirJumps`].
+    pub air_speed: f32,
+    /// Time in seconds after leaving the ground during which a jump still counts as a ground jump
+    pub coyote_time: f32,
+    /// Time in seconds since the character was last grounded
+    pub time_since_grounded: f32,
     /// Was jump requested?
     pub requested: bool,
+    /// Was jump requested last tick? Used to require a fresh press for air jumps.
+    pub previously_requested: bool,
+    /// Whether the held jump button was already used up by something other than a regular jump, e.g. a wall jump.
+    /// Stays set until the button is released so that holding it does not also trigger a regular jump.
+    pub consumed: bool,
+}
+
+impl Jumping {
+    pub fn can_ground_jump(&self, grounded: bool) -> bool {
+        grounded || self.time_since_grounded < self.coyote_time
+    }
 }
 
 impl Default for Jumping {
     fn default() -> Self {
         Self {
             speed: 3.5,
+            air_speed: 2.8,
+            coyote_time: 0.,
+            time_since_grounded: 0.,
             requested: false,
+            previously_requested: false,
+            consumed: false,
         }
     }
 }
 
+/// Allows a character to jump while airborne. Resets whenever the character is [`Grounded`].
+#[derive(Debug, Clone, PartialEq, Eq, Component, Reflect, Default, Serialize, Deserialize)]
+#[reflect(Component, Serialize, Deserialize)]
+pub struct AirJumps {
+    /// How many jumps can be performed before touching the ground again
+    pub max: usize,
+    /// How many jumps are left until touching the ground again
+    pub remaining: usize,
+}
+
+/// The clips a character plays for each [`AnimationState`](crate::movement::animation::AnimationState).
+/// Characters without a dedicated clip for a state can reuse a similar one, e.g. `idle` for `land`.
 #[derive(Debug, Clone, PartialEq, Component, Reflect, Default)]
 #[reflect(Component)]
 pub struct CharacterAnimations {
     pub idle: Handle<AnimationClip>,
     pub walk: Handle<AnimationClip>,
+    pub run: Handle<AnimationClip>,
+    /// Played once when jumping off the ground
+    pub jump: Handle<AnimationClip>,
     pub aerial: Handle<AnimationClip>,
+    /// Played once when touching the ground after a fall
+    pub land: Handle<AnimationClip>,
+    pub crouch_idle: Handle<AnimationClip>,
+    pub crouch_walk: Handle<AnimationClip>,
+}
+
+/// Marks a character that is moved by rapier's [`KinematicCharacterController`] instead of by a dynamic rigid body.
+/// The forces and impulses applied to it are integrated by hand, so other systems can treat it like any other character.
+#[derive(Debug, Clone, PartialEq, Component, Reflect, Default, Serialize, Deserialize)]
+#[reflect(Component, Serialize, Deserialize)]
+pub struct KinematicMovement {
+    /// Duration of the last physics step in seconds, used to turn the controller's output back into a velocity
+    pub last_dt: f32,
 }
@@ -1,14 +1,15 @@
 #[cfg(feature = "dev")]
 use crate::dev::dev_editor::DevEditorWindow;
+use crate::file_system_interaction::asset_loading::ConfigAssets;
+use crate::file_system_interaction::config::GameConfig;
 use crate::level_instantiation::spawning::objects::npc;
+use crate::movement::activity::{Dormant, ReducedActivity};
 use crate::movement::general_movement::{apply_walking, reset_movement_components, Walking};
 use crate::player_control::player_embodiment::Player;
 use crate::util::log_error::log_errors;
 use crate::util::trait_extension::{F32Ext, Vec3Ext};
 use crate::GameState;
-#[cfg(feature = "dev")]
-use anyhow::Context;
-use anyhow::Result;
+use anyhow::{Context, Result};
 use bevy::prelude::*;
 #[cfg(feature = "dev")]
 use bevy_prototype_debug_lines::DebugLines;
@@ -17,9 +18,14 @@ use oxidized_navigation::{
     NavMesh, NavMeshGenerationState, NavMeshSettings, OxidizedNavigationPlugin,
 };
 use serde::{Deserialize, Serialize};
+use std::f32::consts::TAU;
 
-/// Handles NPC pathfinding. Currently, all entities with the [`Follower`] component will follow the [`Player`].
-/// Currently only one navmesh is supported. It is loaded automagically from any entity whose name contains `"[navmesh]"`.
+/// Handles NPC pathfinding. Characters with a [`PathTarget`] and a [`NavigationPath`] walk to their target along a path on the navmesh.
+/// The path is planned again when the target moves too far, the character gets stuck or the path gets old, so new obstacles are noticed.
+/// All entities with the [`Follower`] component will follow the [`Player`], stopping on a ring around them.
+/// Characters steer away from each other when they come too close. Dormant characters are skipped
+/// and characters with a reduced activity plan their paths less often.
+/// The navmesh is generated from all colliders with a `NavMeshAffector`.
 pub struct NavigationPlugin;
 
 const CELL_WIDTH: f32 = 0.5 * npc::RADIUS;
@@ -44,13 +50,20 @@ impl Plugin for NavigationPlugin {
             max_contour_simplification_error: 1.1,
             max_edge_length: 70,
         })
+        .register_type::<Follower>()
+        .register_type::<PathTarget>()
+        .register_type::<NavigationPath>()
         .add_system_set(
-            SystemSet::on_update(GameState::Playing).with_system(
-                query_mesh
-                    .pipe(log_errors)
-                    .after(reset_movement_components)
-                    .before(apply_walking),
-            ),
+            SystemSet::on_update(GameState::Playing)
+                .with_system(set_follower_targets.pipe(log_errors))
+                .with_system(plan_paths.pipe(log_errors).after(set_follower_targets))
+                .with_system(
+                    follow_paths
+                        .pipe(log_errors)
+                        .after(plan_paths)
+                        .after(reset_movement_components)
+                        .before(apply_walking),
+                ),
         );
     }
 }
@@ -59,55 +72,331 @@ impl Plugin for NavigationPlugin {
 #[reflect(Component, Serialize, Deserialize)]
 pub struct Follower;
 
-fn query_mesh(
-    mut with_follower: Query<(&Transform, &mut Walking), (With<Follower>, Without<Player>)>,
+/// Where a character wants to walk to. Resolved into a [`NavigationPath`] on the navmesh.
+#[derive(Debug, Component, Clone, PartialEq, Default, Reflect, Serialize, Deserialize)]
+#[reflect(Component, Serialize, Deserialize)]
+pub struct PathTarget {
+    pub target: Option<Vec3>,
+    /// The character stops walking when it is closer than this to the target
+    pub stop_distance: f32,
+}
+
+/// The path a character with a [`PathTarget`] is currently walking along
+#[derive(Debug, Component, Clone, PartialEq, Default, Reflect, Serialize, Deserialize)]
+#[reflect(Component, Serialize, Deserialize)]
+pub struct NavigationPath {
+    pub waypoints: Vec<Vec3>,
+    /// Index into [`NavigationPath::waypoints`] of the waypoint we are currently walking towards
+    pub next_waypoint: usize,
+    /// The target the path was planned for
+    pub planned_target: Option<Vec3>,
+    /// Last position from which a path was found. We return here when no path can be found.
+    pub last_valid_point: Option<Vec3>,
+    /// Time in seconds since the path was planned
+    pub age: f32,
+    /// Time in seconds during which we did not get closer to the next waypoint
+    pub time_without_progress: f32,
+    /// Distance to the next waypoint last tick
+    pub last_distance_to_waypoint: f32,
+    /// Smoothed push away from other characters that are too close
+    pub avoidance: Vec3,
+}
+
+impl NavigationPath {
+    pub fn clear(&mut self) {
+        self.waypoints.clear();
+        self.next_waypoint = 0;
+        self.time_without_progress = 0.;
+        self.last_distance_to_waypoint = f32::INFINITY;
+    }
+
+    fn next_waypoint(&self) -> Option<Vec3> {
+        self.waypoints.get(self.next_waypoint).copied()
+    }
+}
+
+/// Followers don't walk to the player directly but to a spot on a ring around them.
+/// The spots are spread along an arc facing the followers, so that they don't crowd the same point.
+pub fn set_follower_targets(
+    mut with_follower: Query<
+        (&Transform, &mut PathTarget),
+        (With<Follower>, Without<Player>, Without<Dormant>),
+    >,
     with_player: Query<&Transform, (With<Player>, Without<Follower>)>,
+    config_handles: Res<ConfigAssets>,
+    config: Res<Assets<GameConfig>>,
+) -> Result<()> {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("set_follower_targets").entered();
+    let player_transform = match with_player.iter().next() {
+        Some(player_transform) => player_transform,
+        None => return Ok(()),
+    };
+    let config = config
+        .get(&config_handles.game)
+        .context("Failed to get game config from handle")?;
+    let player_translation = player_transform.translation;
+    let up = player_transform.up();
+    let ring_radius = config.navigation.follow_stop_distance;
+
+    let mut followers: Vec<_> = with_follower
+        .iter_mut()
+        .map(|(transform, path_target)| {
+            let offset = (transform.translation - player_translation)
+                .split(up)
+                .horizontal;
+            (offset, path_target)
+        })
+        .collect();
+    if followers.is_empty() {
+        return Ok(());
+    }
+    let center = followers
+        .iter()
+        .map(|(offset, _)| offset.normalize_or_zero())
+        .sum::<Vec3>()
+        .try_normalize()
+        .unwrap_or_else(|| {
+            player_transform
+                .back()
+                .split(up)
+                .horizontal
+                .normalize_or_zero()
+        });
+    let right = center.cross(up);
+    // Assign the spots in the order in which the followers already stand around the player so that their paths don't cross
+    let get_angle = |offset: Vec3| offset.dot(right).atan2(offset.dot(center));
+    followers.sort_by(|(a, _), (b, _)| get_angle(*a).total_cmp(&get_angle(*b)));
+
+    let count = followers.len();
+    let spacing = (config.navigation.separation_radius / ring_radius).min(TAU / count as f32);
+    for (index, (_, mut path_target)) in followers.into_iter().enumerate() {
+        let angle = (index as f32 - (count - 1) as f32 / 2.) * spacing;
+        let direction = Quat::from_axis_angle(up, -angle) * center;
+        path_target.target = Some(player_translation + direction * ring_radius);
+        path_target.stop_distance = config.navigation.waypoint_reach_distance;
+    }
+    Ok(())
+}
+
+pub fn plan_paths(
+    time: Res<Time>,
+    mut with_path: Query<
+        (
+            &Transform,
+            &PathTarget,
+            &mut NavigationPath,
+            Option<&ReducedActivity>,
+        ),
+        Without<Dormant>,
+    >,
     nav_mesh_settings: Res<NavMeshSettings>,
     nav_mesh: Res<NavMesh>,
-    #[cfg(feature = "dev")] mut lines: ResMut<DebugLines>,
-    #[cfg(feature = "dev")] editor_state: Res<bevy_editor_pls::Editor>,
+    config_handles: Res<ConfigAssets>,
+    config: Res<Assets<GameConfig>>,
 ) -> Result<()> {
     #[cfg(feature = "tracing")]
-    let _span = info_span!("query_mesh").entered();
-    if let Ok(nav_mesh) = nav_mesh.get().read() {
-        for (follower_transform, mut walking) in &mut with_follower {
-            for player_transform in &with_player {
-                let from = follower_transform.translation;
-                let to = player_transform.translation;
-                if (to - from).length_squared() < 3.0f32.squared() {
-                    continue;
-                }
+    let _span = info_span!("plan_paths").entered();
+    let config = config
+        .get(&config_handles.game)
+        .context("Failed to get game config from handle")?;
+    let nav_mesh = match nav_mesh.get().read() {
+        Ok(nav_mesh) => nav_mesh,
+        Err(_) => return Ok(()),
+    };
+    let dt = time.delta_seconds();
+    for (transform, path_target, mut path, reduced_activity) in &mut with_path {
+        path.age += dt;
+        if reduced_activity.is_some()
+            && path.planned_target.is_some()
+            && path.age < config.activity.reduced_navigation_interval
+        {
+            continue;
+        }
+        let target = match path_target.target {
+            Some(target) => target,
+            None => {
+                path.clear();
+                path.planned_target = None;
+                continue;
+            }
+        };
+        let target_moved = path
+            .planned_target
+            .map(|planned_target| {
+                planned_target.distance_squared(target)
+                    > config.navigation.replan_distance.squared()
+            })
+            .unwrap_or(true);
+        let is_stuck = path.time_without_progress > config.navigation.stuck_time;
+        // Also catches obstacles that appeared on the path since it was planned
+        let is_outdated = path.age > config.navigation.replan_interval;
+        if !target_moved && !is_stuck && !is_outdated {
+            continue;
+        }
 
-                if let Ok(path) = find_path(&nav_mesh, &nav_mesh_settings, from, to, None, None) {
-                    let path = perform_string_pulling_on_path(&nav_mesh, from, to, &path)
-                        .map_err(|e| anyhow::Error::msg(format!("{e:?}")))?;
-                    #[cfg(feature = "dev")]
-                    if editor_state
-                        .window_state::<DevEditorWindow>()
-                        .context("Failed to get dev window state")?
-                        .navmesh_render_enabled
-                    {
-                        draw_path(&path, &mut lines, Color::RED);
-                    }
-                    let dir = path
-                        .into_iter()
-                        .map(|next_point| {
-                            (next_point - from)
-                                .split(follower_transform.up())
-                                .horizontal
-                        })
-                        .filter(|dir| dir.length_squared() > 1e-3f32.squared())
-                        .filter_map(|dir| dir.try_normalize())
-                        .next();
-                    walking.direction = dir;
+        let from = transform.translation;
+        let waypoints = find_path(&nav_mesh, &nav_mesh_settings, from, target, None, None)
+            .ok()
+            .and_then(|polygons| {
+                perform_string_pulling_on_path(&nav_mesh, from, target, &polygons).ok()
+            });
+        path.clear();
+        path.planned_target = Some(target);
+        path.age = 0.;
+        match waypoints {
+            Some(waypoints) => {
+                path.waypoints = waypoints;
+                path.last_valid_point = Some(from);
+            }
+            None => {
+                // Idle at the last point we could navigate from instead of walking into a wall
+                if let Some(last_valid_point) = path.last_valid_point {
+                    path.waypoints = vec![last_valid_point];
                 }
             }
         }
     }
+    Ok(())
+}
+
+fn follow_paths(
+    time: Res<Time>,
+    mut with_path: Query<
+        (
+            Entity,
+            &Transform,
+            &PathTarget,
+            &mut NavigationPath,
+            &mut Walking,
+        ),
+        Without<Dormant>,
+    >,
+    config_handles: Res<ConfigAssets>,
+    config: Res<Assets<GameConfig>>,
+    #[cfg(feature = "dev")] mut lines: ResMut<DebugLines>,
+    #[cfg(feature = "dev")] editor_state: Res<bevy_editor_pls::Editor>,
+) -> Result<()> {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("follow_paths").entered();
+    let config = config
+        .get(&config_handles.game)
+        .context("Failed to get game config from handle")?;
+    #[cfg(feature = "dev")]
+    let draw_paths = editor_state
+        .window_state::<DevEditorWindow>()
+        .context("Failed to get dev window state")?
+        .navigation_path_render_enabled;
+    let dt = time.delta_seconds();
+    let positions: Vec<_> = with_path
+        .iter()
+        .map(|(entity, transform, ..)| (entity, transform.translation))
+        .collect();
+    for (entity, transform, path_target, mut path, mut walking) in &mut with_path {
+        let from = transform.translation;
+        let up = transform.up();
+        let path_direction = get_path_direction(from, up, path_target, &mut path, dt, config);
 
+        let separation = get_separation(
+            entity,
+            from,
+            up,
+            &positions,
+            config.navigation.separation_radius,
+        );
+        // Smoothing the avoidance keeps two characters that push each other apart from oscillating
+        let smoothing = (config.navigation.avoidance_smoothing * dt).min(1.);
+        path.avoidance = path.avoidance.lerp(separation, smoothing);
+        let avoidance = path.avoidance * config.navigation.separation_strength;
+        walking.direction = match path_direction {
+            Some(direction) => {
+                // Someone blocking the way head-on cancels out the path direction.
+                // Always sidestepping to the right lets two characters meeting in a corridor pass each other.
+                let blocking = (-avoidance.dot(direction)).max(0.);
+                let right = direction.cross(up);
+                (direction + avoidance + right * blocking).try_normalize()
+            }
+            // Idle characters only get out of the way when someone comes really close, so that they don't drift around
+            None => (avoidance.length() > 1.).then(|| avoidance.normalize()),
+        };
+
+        #[cfg(feature = "dev")]
+        if draw_paths && path_direction.is_some() {
+            let remaining_path: Vec<_> = std::iter::once(from)
+                .chain(path.waypoints[path.next_waypoint..].iter().copied())
+                .collect();
+            draw_path(&remaining_path, &mut lines, Color::RED);
+        }
+    }
     Ok(())
 }
 
+/// Horizontal direction towards the next waypoint, or `None` if there is nowhere to walk to
+fn get_path_direction(
+    from: Vec3,
+    up: Vec3,
+    path_target: &PathTarget,
+    path: &mut NavigationPath,
+    dt: f32,
+    config: &GameConfig,
+) -> Option<Vec3> {
+    let is_at_target = path_target
+        .target
+        .map(|target| (target - from).length_squared() < path_target.stop_distance.squared())
+        .unwrap_or(true);
+    if is_at_target {
+        path.time_without_progress = 0.;
+        return None;
+    }
+
+    // Skip all waypoints we already reached
+    while let Some(waypoint) = path.next_waypoint() {
+        let distance = (waypoint - from).split(up).horizontal.length();
+        if distance > config.navigation.waypoint_reach_distance {
+            break;
+        }
+        path.next_waypoint += 1;
+        path.last_distance_to_waypoint = f32::INFINITY;
+    }
+    let waypoint = match path.next_waypoint() {
+        Some(waypoint) => waypoint,
+        None => {
+            path.time_without_progress = 0.;
+            return None;
+        }
+    };
+
+    let to_waypoint = (waypoint - from).split(up).horizontal;
+    let distance = to_waypoint.length();
+    if distance < path.last_distance_to_waypoint - 1e-2 {
+        path.time_without_progress = 0.;
+    } else {
+        path.time_without_progress += dt;
+    }
+    path.last_distance_to_waypoint = distance;
+    to_waypoint.try_normalize()
+}
+
+/// Pushes away from all other characters closer than `radius`, the stronger the closer they are
+fn get_separation(
+    entity: Entity,
+    position: Vec3,
+    up: Vec3,
+    others: &[(Entity, Vec3)],
+    radius: f32,
+) -> Vec3 {
+    others
+        .iter()
+        .filter(|(other, _)| *other != entity)
+        .filter_map(|(_, other_position)| {
+            let away = (position - *other_position).split(up).horizontal;
+            let distance = away.length();
+            (distance > 1e-3 && distance < radius)
+                .then(|| away / distance * (radius / distance - 1.))
+        })
+        .sum()
+}
+
 #[cfg(feature = "dev")]
 fn draw_path(path: &[Vec3], lines: &mut DebugLines, color: Color) {
     for (a, b) in path.iter().zip(path.iter().skip(1)) {
@@ -0,0 +1,279 @@
+use crate::file_system_interaction::asset_loading::ConfigAssets;
+use crate::file_system_interaction::config::GameConfig;
+use crate::level_instantiation::spawning::{PatrolMode, PatrolRoute};
+use crate::movement::activity::Dormant;
+use crate::movement::navigation::{plan_paths, set_follower_targets, Follower, PathTarget};
+use crate::player_control::player_embodiment::Player;
+use crate::util::log_error::log_errors;
+use crate::util::trait_extension::Vec3Ext;
+use crate::GameState;
+use anyhow::{Context, Result};
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::sync::LazyLock;
+
+/// Lets NPCs with a [`PatrolRoute`] walk from waypoint to waypoint, waiting at each one for the route's pause.
+/// A [`Follower`] stops patrolling while the player is within the configured follow radius and
+/// resumes at the nearest waypoint once the player is gone.
+/// Waypoints that are not given in the level file are collected from empty nodes named `Patrol.<route>.<index>` in the level scene.
+pub struct PatrolPlugin;
+
+impl Plugin for PatrolPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<PatrolProgress>()
+            .register_type::<PatrolWaypoint>()
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(read_patrol_waypoints)
+                    .with_system(collect_patrol_waypoints)
+                    .with_system(restore_patrol_progress)
+                    .with_system(
+                        patrol
+                            .pipe(log_errors)
+                            .after(collect_patrol_waypoints)
+                            .after(restore_patrol_progress)
+                            .after(set_follower_targets)
+                            .before(plan_paths),
+                    ),
+            );
+    }
+}
+
+/// How far along its [`PatrolRoute`] an NPC is. Part of the save file.
+#[derive(Debug, Clone, PartialEq, Component, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Component, Serialize, Deserialize)]
+pub struct PatrolProgress {
+    /// Index of the waypoint the NPC is walking towards or waiting at
+    pub next_waypoint: usize,
+    /// Whether a [`PatrolMode::PingPong`] route is currently walked backwards
+    pub backwards: bool,
+    /// Time in seconds the NPC already waited at the current waypoint
+    pub pause_elapsed: f32,
+    /// Whether the patrol was interrupted to follow the player
+    pub interrupted: bool,
+}
+
+impl PatrolProgress {
+    fn advance(&mut self, mode: PatrolMode, waypoint_count: usize) {
+        self.pause_elapsed = 0.;
+        if waypoint_count < 2 {
+            self.next_waypoint = 0;
+            return;
+        }
+        match mode {
+            PatrolMode::Loop => {
+                self.next_waypoint = (self.next_waypoint + 1) % waypoint_count;
+            }
+            PatrolMode::PingPong => {
+                if self.backwards && self.next_waypoint == 0 {
+                    self.backwards = false;
+                } else if !self.backwards && self.next_waypoint + 1 >= waypoint_count {
+                    self.backwards = true;
+                }
+                self.next_waypoint = if self.backwards {
+                    self.next_waypoint - 1
+                } else {
+                    self.next_waypoint + 1
+                };
+            }
+        }
+    }
+}
+
+/// Marks an empty node in the level scene as a waypoint of a [`PatrolRoute`]
+#[derive(Debug, Clone, PartialEq, Eq, Component, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Component, Serialize, Deserialize)]
+pub struct PatrolWaypoint {
+    pub route: String,
+    pub index: usize,
+}
+
+/// Patrol progress read from a save file, keyed by [`PatrolRoute::name`].
+/// Applied to the NPCs walking these routes as soon as they are spawned.
+#[derive(Debug, Clone, PartialEq, Resource, Default)]
+pub struct PendingPatrolProgress(pub HashMap<String, PatrolProgress>);
+
+static PATROL_WAYPOINT_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^patrol\.([^.\s]+)\.(\d+)").expect("Failed to compile patrol waypoint regex")
+});
+
+fn read_patrol_waypoints(mut commands: Commands, added_name: Query<(Entity, &Name), Added<Name>>) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("read_patrol_waypoints").entered();
+    for (entity, name) in &added_name {
+        let captures = match PATROL_WAYPOINT_REGEX.captures(&name.to_lowercase()) {
+            Some(captures) => captures,
+            None => continue,
+        };
+        let index = match captures[2].parse() {
+            Ok(index) => index,
+            Err(e) => {
+                warn!("Failed to parse index of patrol waypoint \"{name}\": {e}");
+                continue;
+            }
+        };
+        commands.entity(entity).insert(PatrolWaypoint {
+            route: captures[1].to_string(),
+            index,
+        });
+    }
+}
+
+fn collect_patrol_waypoints(
+    mut route_query: Query<&mut PatrolRoute>,
+    waypoint_query: Query<(&PatrolWaypoint, &GlobalTransform)>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("collect_patrol_waypoints").entered();
+    for mut route in route_query
+        .iter_mut()
+        .filter(|route| route.waypoints.is_empty())
+    {
+        let route_name = route.name.to_lowercase();
+        let mut waypoints: Vec<_> = waypoint_query
+            .iter()
+            .filter(|(waypoint, _)| waypoint.route == route_name)
+            .map(|(waypoint, transform)| (waypoint.index, transform.translation()))
+            .collect();
+        if waypoints.is_empty() {
+            // The level scene might not be spawned yet
+            continue;
+        }
+        waypoints.sort_by_key(|(index, _)| *index);
+        route.waypoints = waypoints
+            .into_iter()
+            .map(|(_, translation)| translation)
+            .collect();
+    }
+}
+
+fn restore_patrol_progress(
+    mut commands: Commands,
+    pending: Option<ResMut<PendingPatrolProgress>>,
+    mut patrol_query: Query<(&PatrolRoute, &mut PatrolProgress)>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("restore_patrol_progress").entered();
+    let mut pending = match pending {
+        Some(pending) => pending,
+        None => return,
+    };
+    for (route, mut progress) in &mut patrol_query {
+        if let Some(saved_progress) = pending.0.remove(&route.name) {
+            *progress = saved_progress;
+        }
+    }
+    if pending.0.is_empty() {
+        commands.remove_resource::<PendingPatrolProgress>();
+    }
+}
+
+fn patrol(
+    time: Res<Time>,
+    mut patrol_query: Query<
+        (
+            &Transform,
+            &PatrolRoute,
+            &mut PatrolProgress,
+            &mut PathTarget,
+            Option<&Follower>,
+        ),
+        (Without<Player>, Without<Dormant>),
+    >,
+    player_query: Query<&Transform, With<Player>>,
+    config_handles: Res<ConfigAssets>,
+    config: Res<Assets<GameConfig>>,
+) -> Result<()> {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("patrol").entered();
+    let config = config
+        .get(&config_handles.game)
+        .context("Failed to get game config from handle")?;
+    let player_translation = player_query
+        .iter()
+        .next()
+        .map(|transform| transform.translation);
+    let dt = time.delta_seconds();
+    for (transform, route, mut progress, mut path_target, follower) in &mut patrol_query {
+        if route.waypoints.is_empty() {
+            continue;
+        }
+        let translation = transform.translation;
+        let is_following_player = follower.is_some()
+            && player_translation
+                .map(|player_translation| {
+                    player_translation.distance(translation) < config.navigation.follow_radius
+                })
+                .unwrap_or_default();
+        if is_following_player {
+            // The follow behavior already set the target to the player
+            progress.interrupted = true;
+            continue;
+        }
+        if progress.interrupted {
+            progress.interrupted = false;
+            progress.pause_elapsed = 0.;
+            progress.next_waypoint = get_nearest_waypoint(&route.waypoints, translation);
+        }
+        progress.next_waypoint %= route.waypoints.len();
+
+        let waypoint = route.waypoints[progress.next_waypoint];
+        let stop_distance = config.navigation.patrol_stop_distance;
+        let distance = (waypoint - translation)
+            .split(transform.up())
+            .horizontal
+            .length();
+        if distance <= stop_distance {
+            progress.pause_elapsed += dt;
+            if progress.pause_elapsed >= route.pause {
+                progress.advance(route.mode, route.waypoints.len());
+            }
+        }
+        path_target.target = Some(route.waypoints[progress.next_waypoint]);
+        path_target.stop_distance = stop_distance;
+    }
+    Ok(())
+}
+
+fn get_nearest_waypoint(waypoints: &[Vec3], translation: Vec3) -> usize {
+    waypoints
+        .iter()
+        .enumerate()
+        .min_by(|(_, a), (_, b)| {
+            a.distance_squared(translation)
+                .total_cmp(&b.distance_squared(translation))
+        })
+        .map(|(index, _)| index)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ping_pong_reverses_at_both_ends() {
+        let mut progress = PatrolProgress::default();
+        let visited: Vec<_> = (0..6)
+            .map(|_| {
+                progress.advance(PatrolMode::PingPong, 3);
+                progress.next_waypoint
+            })
+            .collect();
+        assert_eq!(visited, vec![1, 2, 1, 0, 1, 2]);
+    }
+
+    #[test]
+    fn loop_wraps_around() {
+        let mut progress = PatrolProgress::default();
+        let visited: Vec<_> = (0..4)
+            .map(|_| {
+                progress.advance(PatrolMode::Loop, 3);
+                progress.next_waypoint
+            })
+            .collect();
+        assert_eq!(visited, vec![1, 2, 0, 1]);
+    }
+}
@@ -1,17 +1,28 @@
+use crate::file_system_interaction::asset_loading::ConfigAssets;
+use crate::file_system_interaction::config::GameConfig;
+use crate::level_instantiation::spawning::spawn::SpawnQueue;
+use crate::loading_screen::LevelSetup;
 use crate::util::log_error::log_errors;
 use crate::util::trait_extension::MeshExt;
 use crate::GameState;
 use anyhow::{Context, Result};
 use bevy::prelude::*;
+use bevy::tasks::{AsyncComputeTaskPool, Task};
+use bevy::utils::Instant;
 use bevy_rapier3d::prelude::*;
+use futures_lite::future;
 use oxidized_navigation::NavMeshAffector;
+use serde::{Deserialize, Serialize};
 
 /// Sets up the [`RapierPhysicsPlugin`] and [`RapierConfiguration`].
+/// Colliders of large meshes are built on the [`AsyncComputeTaskPool`], see [`ColliderTask`].
+/// Dynamic bodies spawned while the level is still being built, e.g. the player, are held in place until there is ground under them, see [`GroundHold`].
 pub struct PhysicsPlugin;
 
 impl Plugin for PhysicsPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugin(RapierPhysicsPlugin::<NoUserData>::default())
+            .register_type::<GroundHold>()
             .insert_resource(RapierConfiguration {
                 timestep_mode: TimestepMode::Variable {
                     max_dt: 1.0 / 20.0,
@@ -22,25 +33,53 @@ impl Plugin for PhysicsPlugin {
             })
             .add_system_set(
                 SystemSet::on_update(GameState::Playing)
-                    .with_system(read_colliders.pipe(log_errors)),
+                    .with_system(read_colliders.pipe(log_errors))
+                    .with_system(apply_collider_tasks)
+                    .with_system(hold_new_bodies)
+                    .with_system(hold_until_grounded.pipe(log_errors).after(hold_new_bodies)),
             );
     }
 }
 
+/// A collider that is still being built on the [`AsyncComputeTaskPool`]
+#[derive(Component)]
+pub struct ColliderTask {
+    task: Task<Option<Collider>>,
+    started: Instant,
+}
+
 pub fn read_colliders(
     mut commands: Commands,
     added_name: Query<(Entity, &Name), Added<Name>>,
     children: Query<&Children>,
     meshes: Res<Assets<Mesh>>,
     mesh_handles: Query<&Handle<Mesh>>,
+    config_handles: Res<ConfigAssets>,
+    config: Res<Assets<GameConfig>>,
 ) -> Result<()> {
     #[cfg(feature = "tracing")]
     let _span = info_span!("read_colliders").entered();
+    let config = config
+        .get(&config_handles.game)
+        .context("Failed to get game config from handle")?;
     for (entity, name) in &added_name {
         if name.to_lowercase().contains("[collider]") {
             for (collider_entity, collider_mesh) in
                 Mesh::search_in_children(entity, &children, &meshes, &mesh_handles)
             {
+                if collider_mesh.count_vertices()
+                    > config.streaming.async_collider_vertex_count as usize
+                {
+                    let mesh = collider_mesh.clone();
+                    let task = AsyncComputeTaskPool::get().spawn(async move {
+                        Collider::from_bevy_mesh(&mesh, &ComputedColliderShape::TriMesh)
+                    });
+                    commands.entity(collider_entity).insert(ColliderTask {
+                        task,
+                        started: Instant::now(),
+                    });
+                    continue;
+                }
                 let rapier_collider =
                     Collider::from_bevy_mesh(collider_mesh, &ComputedColliderShape::TriMesh)
                         .context("Failed to create collider from mesh")?;
@@ -53,3 +92,235 @@ pub fn read_colliders(
     }
     Ok(())
 }
+
+fn apply_collider_tasks(
+    mut commands: Commands,
+    mut collider_tasks: Query<(Entity, &mut ColliderTask)>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("apply_collider_tasks").entered();
+    for (entity, mut collider_task) in &mut collider_tasks {
+        let collider = match future::block_on(future::poll_once(&mut collider_task.task)) {
+            Some(collider) => collider,
+            None => continue,
+        };
+        commands.entity(entity).remove::<ColliderTask>();
+        match collider {
+            Some(collider) => {
+                debug!(
+                    "Built collider of {entity:?} in {:.1} ms",
+                    collider_task.started.elapsed().as_secs_f32() * 1000.
+                );
+                commands
+                    .entity(entity)
+                    .insert((collider, NavMeshAffector::default()));
+            }
+            None => warn!("Failed to create collider from mesh of {entity:?}"),
+        }
+    }
+}
+
+/// Holds a body in place as [`RigidBody::Fixed`] until there is ground under it,
+/// so that it does not fall through a floor whose collider is still being built.
+/// Added to every dynamic body that is spawned while the level is being built, see [`is_building`].
+/// It is let go right away when nothing is being built anymore, and after `streaming.max_ground_wait` at the latest.
+#[derive(Debug, Clone, Copy, PartialEq, Component, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Component, Serialize, Deserialize)]
+pub struct GroundHold {
+    /// Time in seconds the body has been held
+    pub held: f32,
+    /// Whether the body has been made [`RigidBody::Fixed`] and has to be made dynamic again
+    pub frozen: bool,
+}
+
+/// Whether colliders that bodies might stand on could still be missing, i.e. the level is still being set up,
+/// objects near the player have yet to be spawned, or colliders are still being built
+fn is_building(
+    collider_tasks: &Query<(), With<ColliderTask>>,
+    spawn_queue: &SpawnQueue,
+    level_setup: Option<&LevelSetup>,
+) -> bool {
+    !collider_tasks.is_empty()
+        || !spawn_queue.is_playable()
+        || level_setup
+            .map(|level_setup| !level_setup.is_done())
+            .unwrap_or_default()
+}
+
+fn hold_new_bodies(
+    mut commands: Commands,
+    mut added_bodies: Query<(Entity, &mut RigidBody), (Added<RigidBody>, Without<GroundHold>)>,
+    collider_tasks: Query<(), With<ColliderTask>>,
+    spawn_queue: Res<SpawnQueue>,
+    level_setup: Option<Res<LevelSetup>>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("hold_new_bodies").entered();
+    if added_bodies.is_empty()
+        || !is_building(&collider_tasks, &spawn_queue, level_setup.as_deref())
+    {
+        return;
+    }
+    for (entity, mut rigid_body) in &mut added_bodies {
+        if *rigid_body == RigidBody::Dynamic {
+            // Frozen right away so that it does not fall for the frame until the hold is checked
+            *rigid_body = RigidBody::Fixed;
+            commands.entity(entity).insert(GroundHold {
+                frozen: true,
+                ..default()
+            });
+        }
+    }
+}
+
+fn hold_until_grounded(
+    mut commands: Commands,
+    time: Res<Time>,
+    rapier_context: Res<RapierContext>,
+    mut held_query: Query<(
+        Entity,
+        &Transform,
+        &mut GroundHold,
+        &mut RigidBody,
+        Option<&mut Velocity>,
+    )>,
+    collider_tasks: Query<(), With<ColliderTask>>,
+    spawn_queue: Res<SpawnQueue>,
+    level_setup: Option<Res<LevelSetup>>,
+    config_handles: Res<ConfigAssets>,
+    config: Res<Assets<GameConfig>>,
+) -> Result<()> {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("hold_until_grounded").entered();
+    let config = config
+        .get(&config_handles.game)
+        .context("Failed to get game config from handle")?;
+    let is_building = is_building(&collider_tasks, &spawn_queue, level_setup.as_deref());
+    for (entity, transform, mut hold, mut rigid_body, velocity) in &mut held_query {
+        hold.held += time.delta_seconds();
+        let filter = QueryFilter::only_fixed()
+            .exclude_sensors()
+            .exclude_rigid_body(entity);
+        let has_ground = rapier_context
+            .cast_ray(
+                transform.translation,
+                Vec3::NEG_Y,
+                config.streaming.max_ground_distance,
+                true,
+                filter,
+            )
+            .is_some();
+        let timed_out = hold.held > config.streaming.max_ground_wait;
+        if has_ground || !is_building || timed_out {
+            if timed_out && !has_ground {
+                warn!(
+                    "Found no ground under {entity:?} after {} seconds, letting it go",
+                    config.streaming.max_ground_wait
+                );
+            }
+            // Unless something else changed the body in the meantime, e.g. the player's controller backend
+            if hold.frozen && *rigid_body == RigidBody::Fixed {
+                *rigid_body = RigidBody::Dynamic;
+                debug!("Let go of {entity:?} after {:.2} seconds", hold.held);
+            }
+            commands.entity(entity).remove::<GroundHold>();
+        } else if !hold.frozen {
+            hold.frozen = true;
+            *rigid_body = RigidBody::Fixed;
+            if let Some(mut velocity) = velocity {
+                *velocity = default();
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Returns the duration of the next physics step and the number of substeps it is split into.
+pub fn get_physics_timestep(
+    rapier_configuration: &RapierConfiguration,
+    time: &Time,
+) -> (f32, usize) {
+    match rapier_configuration.timestep_mode {
+        TimestepMode::Fixed { dt, substeps } | TimestepMode::Interpolated { dt, substeps, .. } => {
+            (dt, substeps.max(1))
+        }
+        TimestepMode::Variable {
+            max_dt,
+            time_scale,
+            substeps,
+        } => (
+            (time.delta_seconds() * time_scale).min(max_dt),
+            substeps.max(1),
+        ),
+    }
+}
+
+/// Factor by which rapier scales a velocity because of linear damping over one physics step.
+pub fn get_damping_factor(dt: f32, substeps: usize, linear_damping: f32) -> f32 {
+    let substep_dt = dt / substeps as f32;
+    (1. + substep_dt * linear_damping).powi(-(substeps as i32))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::util::headless::{build_headless_app, HeadlessApp};
+
+    const DT: f32 = 1. / 60.;
+
+    #[test]
+    fn body_waits_for_floor_that_is_being_built() {
+        let mut app = build_headless_app();
+        app.spawn_player_on_floor();
+        // Far away from the floor under the player
+        let floor_translation = Vec3::new(200., -0.5, 0.);
+        let collider_task = app
+            .world
+            .spawn(ColliderTask {
+                task: AsyncComputeTaskPool::get().spawn(future::pending()),
+                started: Instant::now(),
+            })
+            .id();
+        let body = app
+            .world
+            .spawn((
+                TransformBundle::from_transform(Transform::from_xyz(200., 2., 0.)),
+                RigidBody::Dynamic,
+                Collider::ball(0.5),
+                Velocity::default(),
+            ))
+            .id();
+        app.step(30);
+        assert!(
+            app.world.get::<GroundHold>(body).unwrap().frozen,
+            "body was not held"
+        );
+        assert_eq!(app.translation(body).y, 2., "held body moved");
+
+        // The collider is done
+        app.world.despawn(collider_task);
+        app.spawn_fixed_box(floor_translation, Vec3::new(5., 0.5, 5.));
+        app.step(60);
+        assert!(
+            app.world.get::<GroundHold>(body).is_none(),
+            "body was not let go"
+        );
+        assert_eq!(app.world.get::<RigidBody>(body), Some(&RigidBody::Dynamic));
+        let landed = app.translation(body).y;
+        assert!(
+            (landed - 0.5).abs() < 0.05,
+            "body did not land on the floor: {landed}"
+        );
+    }
+
+    #[test]
+    fn damping_factor_matches_single_step() {
+        let factor = get_damping_factor(DT, 1, 1.5);
+        assert!((factor - 1. / (1. + DT * 1.5)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn damping_factor_without_damping_is_one() {
+        assert_eq!(get_damping_factor(DT, 4, 0.), 1.);
+    }
+}
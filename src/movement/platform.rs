@@ -0,0 +1,159 @@
+use crate::level_instantiation::spawning::WaypointPath;
+use crate::movement::climbing::apply_climbing;
+use crate::movement::general_movement::{
+    apply_jumping, apply_walking, update_grounded, GroundContact, Grounded,
+};
+use crate::movement::physics::{get_damping_factor, get_physics_timestep};
+use crate::movement::water::apply_swimming;
+use crate::GameState;
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Handles moving platforms and characters standing on them.
+/// A [`MovingPlatform`] with a [`WaypointPath`] moves along the path's waypoints. Its path is defined in the level data.
+/// Characters with a [`GroundContact`] inherit the velocity of whatever kinematic or dynamic body they stand on,
+/// including the rotational part, so they are carried along by platforms and turntables and keep that momentum when jumping off.
+pub struct PlatformPlugin;
+
+impl Plugin for PlatformPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<MovingPlatform>().add_system_set(
+            SystemSet::on_update(GameState::Playing)
+                .with_system(move_platforms)
+                .with_system(
+                    apply_platform_velocity
+                        .after(update_grounded)
+                        .before(apply_climbing)
+                        .before(apply_swimming)
+                        .before(apply_walking)
+                        .before(apply_jumping),
+                ),
+        );
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Component, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Component, Serialize, Deserialize)]
+pub struct MovingPlatform {
+    /// Index of the waypoint in the platform's [`WaypointPath`] that it is currently moving towards
+    pub next_waypoint: usize,
+}
+
+fn move_platforms(
+    time: Res<Time>,
+    rapier_configuration: Res<RapierConfiguration>,
+    mut platforms: Query<(
+        &mut MovingPlatform,
+        &WaypointPath,
+        &Transform,
+        &mut Velocity,
+    )>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("move_platforms").entered();
+    let (dt, _substeps) = get_physics_timestep(&rapier_configuration, &time);
+    for (mut platform, path, transform, mut velocity) in platforms.iter_mut() {
+        if path.waypoints.is_empty() {
+            velocity.linvel = Vec3::ZERO;
+            continue;
+        }
+        platform.next_waypoint %= path.waypoints.len();
+        let mut to_waypoint = path.waypoints[platform.next_waypoint] - transform.translation;
+        // Advance as soon as the waypoint would be reached this tick so that we don't overshoot and oscillate around it
+        if to_waypoint.length() <= path.speed * dt {
+            platform.next_waypoint = (platform.next_waypoint + 1) % path.waypoints.len();
+            to_waypoint = path.waypoints[platform.next_waypoint] - transform.translation;
+        }
+        velocity.linvel = to_waypoint.normalize_or_zero() * path.speed;
+    }
+}
+
+pub fn apply_platform_velocity(
+    time: Res<Time>,
+    rapier_configuration: Res<RapierConfiguration>,
+    rapier_context: Res<RapierContext>,
+    mut characters: Query<(&Grounded, &mut GroundContact, &mut Velocity, &Damping)>,
+    platforms: Query<(&Velocity, &GlobalTransform), Without<GroundContact>>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("apply_platform_velocity").entered();
+    let (dt, substeps) = get_physics_timestep(&rapier_configuration, &time);
+    for (grounded, mut ground_contact, mut velocity, damping) in characters.iter_mut() {
+        if !grounded.0 {
+            // Keep the inherited velocity as regular momentum, e.g. when jumping off a platform
+            ground_contact.platform_velocity = Vec3::ZERO;
+            ground_contact.inherited_velocity = Vec3::ZERO;
+            continue;
+        }
+        let platform_velocity = ground_contact
+            .entity
+            .map(|entity| rapier_context.collider_parent(entity).unwrap_or(entity))
+            .and_then(|body| platforms.get(body).ok())
+            .map(|(platform_velocity, platform_transform)| {
+                let lever = ground_contact.point - platform_transform.translation();
+                platform_velocity.linvel + platform_velocity.angvel.cross(lever)
+            })
+            .unwrap_or_default();
+
+        // Rapier damps the whole velocity, including the part inherited from the platform.
+        // Replacing exactly what is left of last tick's inheritance makes the character move
+        // with the platform without lagging behind while leaving its own movement untouched.
+        velocity.linvel += platform_velocity - ground_contact.inherited_velocity;
+        ground_contact.platform_velocity = platform_velocity;
+        ground_contact.inherited_velocity =
+            platform_velocity * get_damping_factor(dt, substeps, damping.linear_damping);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::level_instantiation::spawning::GameObject;
+    use crate::util::headless::{build_headless_app, HeadlessApp};
+
+    #[test]
+    fn player_is_carried_by_platform_moving_sideways() {
+        let mut app = build_headless_app();
+        let platform = app
+            .world
+            .spawn((
+                TransformBundle::from_transform(Transform::from_xyz(0., 0., 0.)),
+                RigidBody::KinematicVelocityBased,
+                Collider::cuboid(2., 0.1, 2.),
+                Velocity::default(),
+                MovingPlatform::default(),
+                WaypointPath {
+                    waypoints: vec![Vec3::new(20., 0., 0.)],
+                    speed: 2.,
+                },
+            ))
+            .id();
+        // Capsule bottom rests on the top of the platform at y = 0.1
+        let player = app.spawn_object(GameObject::Player, Transform::from_xyz(0., 0.6, 0.));
+        app.spawn_object(
+            GameObject::Camera,
+            Transform::from_xyz(0., 2., 5.).looking_at(Vec3::ZERO, Vec3::Y),
+        );
+
+        // Let the player settle on the platform before measuring
+        app.step(30);
+        let offset = app.translation(player) - app.translation(platform);
+
+        app.step(120);
+        let platform_translation = app.translation(platform);
+        assert!(
+            platform_translation.x > 3.,
+            "platform did not move: {platform_translation:?}"
+        );
+        let drift = app.translation(player) - platform_translation - offset;
+        assert!(
+            drift.x.abs() < 0.1 && drift.z.abs() < 0.1,
+            "player drifted {drift:?} relative to platform"
+        );
+        assert!(
+            app.world.get::<Grounded>(player).unwrap().0,
+            "player fell off the platform"
+        );
+    }
+}
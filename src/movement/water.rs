@@ -0,0 +1,214 @@
+use crate::movement::general_movement::{
+    apply_jumping, apply_walking, reset_movement_components, Jumping, Walking,
+};
+use crate::util::log_error::log_errors;
+use crate::util::trait_extension::{MeshExt, Vec3Ext};
+use crate::GameState;
+use anyhow::{Context, Result};
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::f32::consts::TAU;
+
+/// Handles water volumes and swimming characters.
+/// Water volumes are loaded from any entity whose name contains `"[water]"`. They become sensors shaped like the bounding box of their mesh.
+/// Characters with a [`Swimming`] component switch to swimming as soon as their collider overlaps a water volume.
+pub struct WaterPlugin;
+
+impl Plugin for WaterPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<WaterVolume>()
+            .register_type::<Swimming>()
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(read_water_volumes.pipe(log_errors))
+                    .with_system(update_swimming.after(reset_movement_components))
+                    .with_system(
+                        apply_swimming
+                            .after(update_swimming)
+                            .before(apply_walking)
+                            .before(apply_jumping),
+                    ),
+            );
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Component, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Component, Serialize, Deserialize)]
+pub struct WaterVolume {
+    /// Center of the volume in local space
+    pub center: Vec3,
+    /// Half extents of the volume in local space
+    pub half_extents: Vec3,
+}
+
+impl WaterVolume {
+    pub fn surface(&self, global_transform: &GlobalTransform) -> Vec3 {
+        global_transform.transform_point(self.center + Vec3::Y * self.half_extents.y)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Component, Reflect, Serialize, Deserialize)]
+#[reflect(Component, Serialize, Deserialize)]
+pub struct Swimming {
+    /// Point on the surface of the water the character is currently in, if any
+    pub water_surface: Option<Vec3>,
+    /// Acceleration while swimming
+    pub acceleration: f32,
+    /// Multiplier for the character's [`BaseGravityScale`](crate::movement::general_movement::BaseGravityScale) while swimming
+    pub gravity_scale: f32,
+    /// Linear damping while swimming
+    pub linear_damping: f32,
+    /// Depth below which the character is no longer considered to be at the surface
+    pub surface_depth: f32,
+    /// Depth at which the character floats when at the surface
+    pub floating_depth: f32,
+    /// Acceleration per meter of depth that pushes the character back to [`Swimming::floating_depth`]
+    pub buoyancy: f32,
+    /// Amplitude in m of the bobbing at the surface
+    pub bobbing_amplitude: f32,
+    /// Frequency in Hz of the bobbing at the surface
+    pub bobbing_frequency: f32,
+    /// Speed in m/s of a jump out of the water at the surface
+    pub surface_jump_speed: f32,
+}
+
+impl Swimming {
+    pub fn is_swimming(&self) -> bool {
+        self.water_surface.is_some()
+    }
+}
+
+impl Default for Swimming {
+    fn default() -> Self {
+        Self {
+            water_surface: None,
+            acceleration: 7.,
+            gravity_scale: 0.1,
+            linear_damping: 3.,
+            surface_depth: 0.5,
+            floating_depth: 0.3,
+            buoyancy: 20.,
+            bobbing_amplitude: 0.05,
+            bobbing_frequency: 0.5,
+            surface_jump_speed: 5.,
+        }
+    }
+}
+
+pub fn read_water_volumes(
+    mut commands: Commands,
+    added_name: Query<(Entity, &Name), Added<Name>>,
+    children: Query<&Children>,
+    meshes: Res<Assets<Mesh>>,
+    mesh_handles: Query<&Handle<Mesh>>,
+) -> Result<()> {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("read_water_volumes").entered();
+    for (entity, name) in &added_name {
+        if name.to_lowercase().contains("[water]") {
+            for (water_entity, water_mesh) in
+                Mesh::search_in_children(entity, &children, &meshes, &mesh_handles)
+            {
+                let aabb = water_mesh
+                    .compute_aabb()
+                    .context("Failed to compute bounding box of water mesh")?;
+                let center = Vec3::from(aabb.center);
+                let half_extents = Vec3::from(aabb.half_extents);
+                commands.entity(water_entity).insert((
+                    WaterVolume {
+                        center,
+                        half_extents,
+                    },
+                    Collider::compound(vec![(
+                        center,
+                        Quat::IDENTITY,
+                        Collider::cuboid(half_extents.x, half_extents.y, half_extents.z),
+                    )]),
+                    Sensor,
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Recomputes every tick whether a character is in water, so leaving the water is handled in the same tick it happens.
+/// Gravity and damping are handled by [`update_gravity_scale`](crate::movement::general_movement::update_gravity_scale)
+/// and [`update_damping`](crate::movement::general_movement::update_damping).
+pub fn update_swimming(
+    mut swimmers: Query<(Entity, &mut Swimming)>,
+    water_volumes: Query<(&WaterVolume, &GlobalTransform)>,
+    rapier_context: Res<RapierContext>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("update_swimming").entered();
+    for (entity, mut swimming) in &mut swimmers {
+        let water_surface = rapier_context
+            .intersections_with(entity)
+            .filter(|(_, _, intersecting)| *intersecting)
+            .map(|(a, b, _)| if a == entity { b } else { a })
+            .filter_map(|other| water_volumes.get(other).ok())
+            .map(|(water_volume, global_transform)| water_volume.surface(global_transform))
+            .max_by(|a, b| a.y.total_cmp(&b.y));
+        swimming.water_surface = water_surface;
+    }
+}
+
+pub fn apply_swimming(
+    time: Res<Time>,
+    mut character_query: Query<(
+        &mut ExternalForce,
+        &mut ExternalImpulse,
+        &mut Walking,
+        &mut Jumping,
+        &mut Velocity,
+        &ReadMassProperties,
+        &Transform,
+        &Swimming,
+    )>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("apply_swimming").entered();
+    for (
+        mut force,
+        mut impulse,
+        mut walking,
+        mut jumping,
+        mut velocity,
+        mass,
+        transform,
+        swimming,
+    ) in &mut character_query
+    {
+        let water_surface = match swimming.water_surface {
+            Some(water_surface) => water_surface,
+            None => continue,
+        };
+        let mass = mass.0.mass;
+        let up = transform.up();
+
+        // Taking the direction prevents the regular walking logic from acting on it
+        if let Some(direction) = walking.direction.take() {
+            force.force += direction * swimming.acceleration * mass;
+        }
+
+        let depth = (water_surface - transform.translation).dot(up);
+        let is_at_surface = depth < swimming.surface_depth;
+        if is_at_surface {
+            let bobbing = (time.elapsed_seconds() * swimming.bobbing_frequency * TAU).sin()
+                * swimming.bobbing_amplitude;
+            let target_depth = swimming.floating_depth + bobbing;
+            force.force += up * (depth - target_depth) * swimming.buoyancy * mass;
+        }
+
+        if jumping.requested {
+            // Swimming never consumes air jumps
+            jumping.requested = false;
+            if is_at_surface && !jumping.previously_requested {
+                velocity.linvel = velocity.linvel.split(up).horizontal;
+                impulse.impulse += up * swimming.surface_jump_speed * mass;
+            }
+        }
+    }
+}
@@ -1,52 +1,267 @@
-use crate::level_instantiation::spawning::objects::player;
-use crate::movement::general_movement::Grounded;
-use crate::particles::init::init_effects;
-use crate::util::trait_extension::{F32Ext, Vec3Ext};
+use crate::file_system_interaction::asset_loading::ConfigAssets;
+use crate::file_system_interaction::config::{DustColors, GameConfig};
+use crate::movement::footsteps::{get_surface_type, SurfaceType};
+use crate::movement::general_movement::{GroundContact, Grounded, Walking};
+use crate::movement::water::Swimming;
+use crate::particles::registry::{
+    despawn_finished_effects, forget_changed_effects, spawn_particle_effects, BuiltEffects,
+    FiniteParticleEffect, ParticleEffectRegistry, SpawnParticleEffect,
+};
+use crate::player_control::player_embodiment::{Player, PlayerLanded};
+use crate::util::log_error::log_errors;
+use crate::util::trait_extension::Vec3Ext;
 use crate::GameState;
+use anyhow::{Context, Result};
+use bevy::pbr::NotShadowReceiver;
 use bevy::prelude::*;
 use bevy_hanabi::prelude::*;
 use bevy_rapier3d::prelude::*;
 
-mod init;
+pub mod registry;
 
 /// Handles particle effects instantiation and playing.
+/// Effects are defined in `assets/particles/*.particle.ron`, see [`ParticleEffectDefinition`](crate::file_system_interaction::particle_definition::ParticleEffectDefinition).
+/// Send a [`SpawnParticleEffect`] to play one of them somewhere in the world.
+///
+/// The player kicks up dust tinted by the [`SurfaceType`] below them while sprinting, sliding down steep slopes and landing.
+/// Each of these can be turned off in the [`Particles`](crate::file_system_interaction::config::Particles) config.
 pub struct ParticlePlugin;
 
 impl Plugin for ParticlePlugin {
     fn build(&self, app: &mut App) {
-        app.register_type::<SprintingParticle>()
+        app.register_type::<DustEmitter>()
+            .register_type::<FiniteParticleEffect>()
             .add_plugin(HanabiPlugin)
-            .add_system_set(SystemSet::on_exit(GameState::Loading).with_system(init_effects))
+            .init_resource::<BuiltEffects>()
+            .add_event::<SpawnParticleEffect>()
+            .add_system(forget_changed_effects)
+            .add_system(spawn_particle_effects.after(forget_changed_effects))
+            .add_system(despawn_finished_effects)
             .add_system_set(
-                SystemSet::on_update(GameState::Playing).with_system(play_sprinting_effect),
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(play_movement_dust.pipe(log_errors))
+                    .with_system(apply_pending_dust_bursts.after(play_movement_dust)),
             );
     }
 }
 
-#[derive(Debug, Clone, Eq, PartialEq, Component, Reflect, Default)]
+/// Emits the dust of one kind of movement on one surface.
+/// The emitters are created on demand and then moved to wherever the dust is kicked up, so that the sprinting trail
+/// does not need a new entity every frame. They are not attached to the player, so the particles stay behind.
+#[derive(Debug, Clone, PartialEq, Component, Reflect, Default)]
 #[reflect(Component)]
-struct SprintingParticle;
-
-fn play_sprinting_effect(
-    with_player: Query<(&Transform, &Grounded, &Velocity), Without<SprintingParticle>>,
-    mut with_particle: Query<(&mut Transform, &mut ParticleEffect), With<SprintingParticle>>,
-) {
-    const SPRINT_EFFECT_SPEED_THRESHOLD: f32 = 7.;
-    for (player_transform, grounded, velocity) in with_player.iter() {
-        let horizontal_speed_squared = velocity
-            .linvel
-            .split(player_transform.up())
-            .horizontal
-            .length_squared();
-        for (mut particle_transform, mut effect) in with_particle.iter_mut() {
-            if grounded.0 && horizontal_speed_squared > SPRINT_EFFECT_SPEED_THRESHOLD.squared() {
-                let translation = player_transform.translation
-                    - player_transform.up() * (player::HEIGHT / 2. + player::RADIUS);
-                *particle_transform = player_transform.with_translation(translation);
-                effect.maybe_spawner().unwrap().set_active(true);
+struct DustEmitter {
+    kind: DustKind,
+    surface: SurfaceType,
+    /// Particles per second of a continuous emitter
+    rate: f32,
+    /// Burst that is emitted as soon as the spawner of a new emitter is ready
+    pending_burst: Option<f32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Reflect, FromReflect, Default)]
+enum DustKind {
+    #[default]
+    Sprint,
+    Slide,
+    Landing,
+    HardLanding,
+}
+
+impl DustKind {
+    fn effect_name(self) -> &'static str {
+        match self {
+            DustKind::Sprint => "sprint",
+            DustKind::Slide => "slide",
+            DustKind::Landing => "landing",
+            DustKind::HardLanding => "hard_landing",
+        }
+    }
+}
+
+fn play_movement_dust(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut player_landed_events: EventReader<PlayerLanded>,
+    player_query: Query<
+        (
+            &Transform,
+            &Grounded,
+            &GroundContact,
+            &Velocity,
+            &Walking,
+            &Swimming,
+        ),
+        With<Player>,
+    >,
+    surface_query: Query<&SurfaceType>,
+    parent_query: Query<&Parent>,
+    mut emitters: Query<(&mut DustEmitter, &mut Transform, &mut ParticleEffect), Without<Player>>,
+    mut registry: ParticleEffectRegistry,
+    mut slide_cooldown: Local<f32>,
+    config_handles: Res<ConfigAssets>,
+    config: Res<Assets<GameConfig>>,
+) -> Result<()> {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("play_movement_dust").entered();
+    let config = config
+        .get(&config_handles.game)
+        .context("Failed to get game config from handle")?;
+    let particles = &config.particles;
+    let impact_speed = player_landed_events
+        .iter()
+        .map(|event| event.impact_speed)
+        .reduce(f32::max);
+    *slide_cooldown -= time.delta_seconds();
+
+    let mut sprint_trail = None;
+    let mut bursts = Vec::new();
+    for (transform, grounded, ground_contact, velocity, walking, swimming) in player_query.iter() {
+        // No dust in the air or in the water
+        if !grounded.0 || swimming.is_swimming() {
+            continue;
+        }
+        let surface = ground_contact
+            .entity
+            .map(|ground| get_surface_type(ground, &surface_query, &parent_query))
+            .unwrap_or_default();
+        let up = transform.up();
+        let at_feet =
+            Transform::from_translation(ground_contact.point).with_rotation(transform.rotation);
+        let sliding = !walking.is_walkable(ground_contact.normal, up);
+
+        if let Some(impact_speed) = impact_speed.filter(|_| particles.landing_dust) {
+            let count = (impact_speed * particles.landing_dust_per_speed)
+                .min(particles.max_landing_dust)
+                .floor();
+            if impact_speed >= config.player.hard_landing_threshold {
+                bursts.push((
+                    DustKind::HardLanding,
+                    surface,
+                    at_feet,
+                    count * particles.hard_landing_dust_factor,
+                ));
             } else {
-                effect.maybe_spawner().unwrap().set_active(false);
+                bursts.push((DustKind::Landing, surface, at_feet, count));
+            }
+        }
+
+        if sliding && particles.slide_dust {
+            if *slide_cooldown <= 0. {
+                *slide_cooldown = particles.slide_dust_interval;
+                bursts.push((
+                    DustKind::Slide,
+                    surface,
+                    at_feet,
+                    particles.slide_dust_count,
+                ));
+            }
+        } else {
+            // Starting to slide kicks up dust right away
+            *slide_cooldown = 0.;
+        }
+
+        // Standing still on a moving platform should not kick up dust
+        let own_velocity = velocity.linvel - ground_contact.platform_velocity;
+        let horizontal_speed = own_velocity.split(up).horizontal.length();
+        if particles.sprint_dust && !sliding && horizontal_speed > particles.sprint_dust_min_speed {
+            // Changing the rate restarts the spawner, so only do it in whole steps
+            let rate = (horizontal_speed * particles.sprint_dust_per_speed).round();
+            sprint_trail = Some((surface, at_feet, rate));
+        }
+    }
+
+    let mut missing_emitters = Vec::new();
+    let mut found_sprint_emitter = false;
+    for (mut emitter, mut transform, mut effect) in emitters.iter_mut() {
+        if emitter.kind != DustKind::Sprint {
+            continue;
+        }
+        let trail = sprint_trail.filter(|(surface, ..)| *surface == emitter.surface);
+        found_sprint_emitter |= trail.is_some();
+        // The spawner of a new emitter is initialized one frame later
+        let spawner = match effect.maybe_spawner() {
+            Some(spawner) => spawner,
+            None => continue,
+        };
+        match trail {
+            Some((_surface, at_feet, rate)) => {
+                *transform = at_feet;
+                if emitter.rate != rate {
+                    emitter.rate = rate;
+                    *spawner = Spawner::rate(rate.into());
+                }
+                spawner.set_active(true);
+            }
+            None => spawner.set_active(false),
+        }
+    }
+    if let Some((surface, at_feet, _rate)) = sprint_trail {
+        if !found_sprint_emitter {
+            missing_emitters.push((DustKind::Sprint, surface, at_feet, None));
+        }
+    }
+
+    for (kind, surface, at_feet, count) in bursts {
+        if count < 1. {
+            continue;
+        }
+        let emitter = emitters
+            .iter_mut()
+            .find(|(emitter, ..)| emitter.kind == kind && emitter.surface == surface);
+        match emitter {
+            Some((mut emitter, mut transform, _effect)) => {
+                *transform = at_feet;
+                emitter.pending_burst = Some(count);
             }
+            None => missing_emitters.push((kind, surface, at_feet, Some(count))),
         }
     }
+
+    for (kind, surface, transform, pending_burst) in missing_emitters {
+        let tint = get_dust_color(&particles.dust_colors, surface);
+        let instance = match registry.instantiate_tinted(kind.effect_name(), Some(tint)) {
+            Some(instance) => instance,
+            None => continue,
+        };
+        commands.spawn((
+            Name::new(format!("{kind:?} dust on {}", surface.name())),
+            DustEmitter {
+                kind,
+                surface,
+                rate: 0.,
+                pending_burst,
+            },
+            ParticleEffectBundle {
+                transform,
+                ..instance.bundle
+            },
+            NotShadowReceiver,
+        ));
+    }
+    Ok(())
+}
+
+/// Bursts can only be emitted once the spawner of an emitter is initialized
+fn apply_pending_dust_bursts(mut emitters: Query<(&mut DustEmitter, &mut ParticleEffect)>) {
+    for (mut emitter, mut effect) in emitters.iter_mut() {
+        let count = match emitter.pending_burst {
+            Some(count) => count,
+            None => continue,
+        };
+        if let Some(spawner) = effect.maybe_spawner() {
+            *spawner = Spawner::once(count.into(), true);
+            emitter.pending_burst = None;
+        }
+    }
+}
+
+fn get_dust_color(dust_colors: &DustColors, surface: SurfaceType) -> Vec3 {
+    Vec3::from(match surface {
+        SurfaceType::Unknown => dust_colors.default,
+        SurfaceType::Dirt => dust_colors.dirt,
+        SurfaceType::Grass => dust_colors.grass,
+        SurfaceType::Stone => dust_colors.stone,
+        SurfaceType::Wood => dust_colors.wood,
+    })
 }
@@ -0,0 +1,261 @@
+use crate::file_system_interaction::asset_loading::ParticleEffects;
+use crate::file_system_interaction::particle_definition::{
+    ParticleEffectDefinition, ParticleShape, ParticleSpawn, ParticleValue,
+};
+use bevy::ecs::system::SystemParam;
+use bevy::pbr::NotShadowReceiver;
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use bevy_hanabi::prelude::*;
+
+/// Requests an instance of the effect defined in `assets/particles/<name>.particle.ron`.
+/// When attached to an entity, the effect is placed relative to it and despawned along with it.
+/// Effects that only emit [`ParticleSpawn::Once`] despawn by themselves when their particles are gone.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpawnParticleEffect {
+    pub name: String,
+    pub transform: Transform,
+    pub attach_to: Option<Entity>,
+}
+
+/// Effects built from their [`ParticleEffectDefinition`] so far, by definition and the bits of their tint.
+/// Entries are dropped when their definition changes, so that hot reloading affects subsequently spawned effects.
+#[derive(Debug, Clone, Resource, Default)]
+pub struct BuiltEffects(
+    HashMap<(Handle<ParticleEffectDefinition>, Option<[u32; 3]>), Handle<EffectAsset>>,
+);
+
+/// Despawns an effect once all of its particles are gone
+#[derive(Debug, Clone, PartialEq, Component, Reflect, Default)]
+#[reflect(Component)]
+pub struct FiniteParticleEffect {
+    /// Seconds until the last particle died
+    pub remaining: f32,
+}
+
+/// Looks up particle effects by name and builds them on demand
+#[derive(SystemParam)]
+pub struct ParticleEffectRegistry<'w, 's> {
+    particle_effects: Option<Res<'w, ParticleEffects>>,
+    definitions: Res<'w, Assets<ParticleEffectDefinition>>,
+    built_effects: ResMut<'w, BuiltEffects>,
+    effect_assets: ResMut<'w, Assets<EffectAsset>>,
+    asset_server: Res<'w, AssetServer>,
+    #[system_param(ignore)]
+    _marker: std::marker::PhantomData<&'s ()>,
+}
+
+impl<'w, 's> ParticleEffectRegistry<'w, 's> {
+    /// Builds a fresh bundle of the named effect. Logs a warning and returns `None` for unknown effects.
+    pub fn instantiate(&mut self, name: &str) -> Option<ParticleEffectInstance> {
+        self.instantiate_tinted(name, None)
+    }
+
+    /// Like [`ParticleEffectRegistry::instantiate`], but multiplies the RGB of the effect's colors with the tint
+    pub fn instantiate_tinted(
+        &mut self,
+        name: &str,
+        tint: Option<Vec3>,
+    ) -> Option<ParticleEffectInstance> {
+        let particle_effects = self.particle_effects.as_ref()?;
+        let handle = match particle_effects.get(name) {
+            Some(handle) => handle,
+            None => {
+                warn!(
+                    "Failed to spawn particle effect \"{name}\": No such effect. Available effects: {:?}",
+                    particle_effects.names().collect::<Vec<_>>()
+                );
+                return None;
+            }
+        };
+        let definition = match self.definitions.get(handle) {
+            Some(definition) => definition,
+            None => {
+                warn!("Failed to spawn particle effect \"{name}\": Its definition is not loaded");
+                return None;
+            }
+        };
+        let effect_asset = self
+            .built_effects
+            .0
+            .entry((
+                handle.clone(),
+                tint.map(|tint| tint.to_array().map(f32::to_bits)),
+            ))
+            .or_insert_with(|| {
+                self.effect_assets.add(build_effect_asset(
+                    name,
+                    definition,
+                    tint.unwrap_or(Vec3::ONE),
+                    &self.asset_server,
+                ))
+            })
+            .clone();
+        Some(ParticleEffectInstance {
+            bundle: ParticleEffectBundle {
+                effect: ParticleEffect::new(effect_asset),
+                ..default()
+            },
+            finite: definition.spawn.is_finite().then(|| FiniteParticleEffect {
+                remaining: definition.lifetime,
+            }),
+        })
+    }
+}
+
+/// A newly built effect, ready to be spawned
+pub struct ParticleEffectInstance {
+    pub bundle: ParticleEffectBundle,
+    pub finite: Option<FiniteParticleEffect>,
+}
+
+pub fn spawn_particle_effects(
+    mut commands: Commands,
+    mut spawn_events: EventReader<SpawnParticleEffect>,
+    mut registry: ParticleEffectRegistry,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("spawn_particle_effects").entered();
+    for event in spawn_events.iter() {
+        let instance = match registry.instantiate(&event.name) {
+            Some(instance) => instance,
+            None => continue,
+        };
+        let bundle = (
+            Name::new(format!("Particle effect \"{}\"", event.name)),
+            ParticleEffectBundle {
+                transform: event.transform,
+                ..instance.bundle
+            },
+            NotShadowReceiver,
+        );
+        if let Some(parent) = event.attach_to {
+            if commands.get_entity(parent).is_none() {
+                warn!(
+                    "Failed to attach particle effect \"{}\" to {parent:?}: No such entity",
+                    event.name
+                );
+                continue;
+            }
+        }
+        let mut entity_commands = commands.spawn(bundle);
+        if let Some(finite) = instance.finite {
+            entity_commands.insert(finite);
+        }
+        let effect = entity_commands.id();
+        // As a child, the effect is despawned recursively along with its parent
+        if let Some(parent) = event.attach_to {
+            commands.entity(parent).add_child(effect);
+        }
+    }
+}
+
+pub fn forget_changed_effects(
+    mut asset_events: EventReader<AssetEvent<ParticleEffectDefinition>>,
+    mut built_effects: ResMut<BuiltEffects>,
+) {
+    for event in asset_events.iter() {
+        match event {
+            AssetEvent::Modified { handle } | AssetEvent::Removed { handle } => {
+                built_effects
+                    .0
+                    .retain(|(definition, _tint), _effect| definition != handle);
+            }
+            AssetEvent::Created { .. } => {}
+        }
+    }
+}
+
+pub fn despawn_finished_effects(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut effects: Query<(Entity, &mut FiniteParticleEffect)>,
+) {
+    for (entity, mut effect) in effects.iter_mut() {
+        effect.remaining -= time.delta_seconds();
+        if effect.remaining <= 0. {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+fn build_effect_asset(
+    name: &str,
+    definition: &ParticleEffectDefinition,
+    tint: Vec3,
+    asset_server: &AssetServer,
+) -> EffectAsset {
+    let mut color_gradient = Gradient::new();
+    for (key, color) in definition.color_over_lifetime.iter() {
+        color_gradient.add_key(*key, *color * tint.extend(1.));
+    }
+    let mut size_gradient = Gradient::new();
+    for (key, size) in definition.size_over_lifetime.iter() {
+        size_gradient.add_key(*key, Vec2::splat(*size));
+    }
+    let spawner = match definition.spawn {
+        ParticleSpawn::Rate(rate) => Spawner::rate(to_value(rate)),
+        ParticleSpawn::Once(count) => Spawner::once(to_value(count), true),
+        ParticleSpawn::Burst { count, period } => Spawner::burst(to_value(count), to_value(period)),
+    }
+    .with_active(definition.active);
+
+    let asset = EffectAsset {
+        name: name.to_string(),
+        capacity: definition.capacity,
+        spawner,
+        ..default()
+    };
+    let asset = match definition.shape {
+        ParticleShape::Circle { radius, filled } => asset.init(PositionCircleModifier {
+            dimension: to_dimension(filled),
+            radius,
+            speed: to_value(definition.speed),
+            center: Vec3::ZERO,
+            axis: Vec3::Y,
+        }),
+        ParticleShape::Sphere { radius, filled } => asset.init(PositionSphereModifier {
+            dimension: to_dimension(filled),
+            radius,
+            speed: to_value(definition.speed),
+            center: Vec3::ZERO,
+        }),
+    }
+    .init(ParticleLifetimeModifier {
+        lifetime: definition.lifetime,
+    })
+    .update(LinearDragModifier {
+        drag: definition.drag,
+    })
+    .update(AccelModifier {
+        accel: definition.acceleration,
+    })
+    .render(BillboardModifier {})
+    .render(ColorOverLifetimeModifier {
+        gradient: color_gradient,
+    })
+    .render(SizeOverLifetimeModifier {
+        gradient: size_gradient,
+    });
+    match definition.texture.as_ref() {
+        Some(texture) => asset.render(ParticleTextureModifier {
+            texture: asset_server.load(texture.as_str()),
+        }),
+        None => asset,
+    }
+}
+
+fn to_value(value: ParticleValue) -> Value<f32> {
+    match value {
+        ParticleValue::Constant(value) => Value::Single(value),
+        ParticleValue::Random(min, max) => Value::Uniform((min, max)),
+    }
+}
+
+fn to_dimension(filled: bool) -> ShapeDimension {
+    if filled {
+        ShapeDimension::Volume
+    } else {
+        ShapeDimension::Surface
+    }
+}
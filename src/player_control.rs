@@ -1,10 +1,16 @@
+pub mod accessibility;
 pub mod actions;
 pub mod camera;
+pub mod grapple;
 pub mod player_embodiment;
+pub mod rumble;
 
+pub use crate::player_control::accessibility::AccessibilityPlugin;
 pub use crate::player_control::actions::ActionsPlugin;
 pub use crate::player_control::camera::CameraPlugin;
+pub use crate::player_control::grapple::GrapplePlugin;
 pub use crate::player_control::player_embodiment::PlayerEmbodimentPlugin;
+pub use crate::player_control::rumble::RumblePlugin;
 use bevy::prelude::*;
 
 /// Handles systems exclusive to the player's control. Is split into the following sub-plugins:
@@ -12,12 +18,18 @@ use bevy::prelude::*;
 /// - [`CameraPlugin`]: Handles camera movement.
 /// - [`PlayerEmbodimentPlugin`]: Tells the components from [`super::MovementPlugin`] about the desired player [`actions::Actions`].
 /// Also handles other systems that change how the player is physically represented in the world.
+/// - [`GrapplePlugin`]: Handles the grappling hook.
+/// - [`RumblePlugin`]: Handles gamepad rumble.
+/// - [`AccessibilityPlugin`]: Handles the accessibility options that change how the player controls the game.
 pub struct PlayerControlPlugin;
 
 impl Plugin for PlayerControlPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugin(ActionsPlugin)
             .add_plugin(CameraPlugin)
-            .add_plugin(PlayerEmbodimentPlugin);
+            .add_plugin(PlayerEmbodimentPlugin)
+            .add_plugin(GrapplePlugin)
+            .add_plugin(RumblePlugin)
+            .add_plugin(AccessibilityPlugin);
     }
 }
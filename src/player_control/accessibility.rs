@@ -0,0 +1,197 @@
+use crate::file_system_interaction::asset_loading::ConfigAssets;
+use crate::file_system_interaction::config::{Accessibility, GameConfig};
+use crate::file_system_interaction::user_settings::UserSettings;
+use crate::player_control::actions::{remove_actions_when_frozen, ActionsFrozen, PlayerAction};
+use crate::player_control::player_embodiment::Player;
+use bevy::prelude::*;
+use bevy::utils::HashSet;
+use leafwing_input_manager::plugin::InputManagerSystem;
+use leafwing_input_manager::prelude::*;
+
+/// Keeps the [`AccessibilityOptions`] in effect in sync with the game config and the player's [`UserSettings`],
+/// so that changes from the settings menu apply right away.
+/// Systems that move the camera on their own check [`AccessibilityOptions::reduce_camera_motion`] there,
+/// and the camera scales its smoothing with [`AccessibilityOptions::scale_camera_smoothing`].
+/// When "hold to toggle" is on, pressing one of the [`TOGGLEABLE_ACTIONS`] keeps it pressed until it is pressed again.
+/// A toggled action is let go whenever the actions are frozen, e.g. by a menu or a dialog, and when a new player is spawned,
+/// e.g. after loading a save, so that it is never stuck on.
+pub struct AccessibilityPlugin;
+
+impl Plugin for AccessibilityPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<AccessibilityOptions>()
+            .init_resource::<ToggledActions>()
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                update_accessibility_options.before(apply_hold_to_toggle),
+            )
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                apply_hold_to_toggle
+                    .after(InputManagerSystem::ManualControl)
+                    .before(remove_actions_when_frozen),
+            );
+    }
+}
+
+/// Actions that are held down by default, but are toggled instead when [`Accessibility::hold_to_toggle`] is on
+pub const TOGGLEABLE_ACTIONS: [PlayerAction; 1] = [PlayerAction::Sprint];
+
+/// The accessibility options in effect, which are the player's own from the [`UserSettings`] or else the game config's.
+#[derive(Debug, Clone, PartialEq, Resource, Default, Deref)]
+pub struct AccessibilityOptions(pub Accessibility);
+
+impl AccessibilityOptions {
+    /// Returns a copy of `config` with every camera smoothing constant multiplied by
+    /// [`Accessibility::camera_smoothing_multiplier`]
+    pub fn scale_camera_smoothing(&self, config: &GameConfig) -> GameConfig {
+        // A smoothing of zero would freeze the camera in place
+        let multiplier = self.camera_smoothing_multiplier.max(0.01);
+        let mut config = config.clone();
+        let camera = &mut config.camera;
+        camera.dynamic_fov_smoothing *= multiplier;
+        camera.fixed_angle.rotation_smoothing *= multiplier;
+        camera.fixed_angle.translation_smoothing *= multiplier;
+        camera.first_person.rotation_smoothing *= multiplier;
+        camera.first_person.translation_smoothing *= multiplier;
+        camera.third_person.translation_smoothing_going_closer *= multiplier;
+        camera.third_person.translation_smoothing_going_further *= multiplier;
+        camera.third_person.rotation_smoothing *= multiplier;
+        camera.third_person.climbing_target_smoothing *= multiplier;
+        camera.third_person.alignment_smoothing *= multiplier;
+        config
+    }
+}
+
+/// Which of the [`TOGGLEABLE_ACTIONS`] are held down and which are toggled on, by their [`Actionlike::index`]
+#[derive(Debug, Clone, PartialEq, Eq, Resource, Default)]
+pub struct ToggledActions {
+    held: HashSet<usize>,
+    toggled: HashSet<usize>,
+}
+
+impl ToggledActions {
+    /// Toggles the action when it was just pressed and returns whether it is toggled on.
+    /// While `frozen`, the action is let go instead. It is still tracked whether it is held down,
+    /// so that keeping it held while a menu closes does not toggle it on.
+    pub fn update(&mut self, action: PlayerAction, is_held: bool, frozen: bool) -> bool {
+        let index = action.index();
+        let was_held = if is_held {
+            !self.held.insert(index)
+        } else {
+            self.held.remove(&index)
+        };
+        if frozen {
+            self.toggled.remove(&index);
+        } else if is_held && !was_held && !self.toggled.remove(&index) {
+            self.toggled.insert(index);
+        }
+        self.toggled.contains(&index)
+    }
+
+    pub fn release_all(&mut self) {
+        self.toggled.clear();
+    }
+}
+
+fn update_accessibility_options(
+    settings: Res<UserSettings>,
+    mut options: ResMut<AccessibilityOptions>,
+    config_handles: Option<Res<ConfigAssets>>,
+    config: Res<Assets<GameConfig>>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("update_accessibility_options").entered();
+    // The config is only available after loading
+    let config = match config_handles.and_then(|handles| config.get(&handles.game)) {
+        Some(config) => config,
+        None => return,
+    };
+    let accessibility = settings.accessibility_or(&config.accessibility);
+    if options.0 != *accessibility {
+        options.0 = accessibility.clone();
+    }
+}
+
+fn apply_hold_to_toggle(
+    options: Res<AccessibilityOptions>,
+    actions_frozen: Res<ActionsFrozen>,
+    mut toggled_actions: ResMut<ToggledActions>,
+    mut player_actions_query: Query<&mut ActionState<PlayerAction>>,
+    added_players: Query<(), Added<Player>>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("apply_hold_to_toggle").entered();
+    if !options.hold_to_toggle || !added_players.is_empty() {
+        toggled_actions.release_all();
+    }
+    for mut actions in player_actions_query.iter_mut() {
+        for action in TOGGLEABLE_ACTIONS {
+            // The input manager just set the action from the raw input, so this is whether it is held down
+            let is_held = actions.pressed(action.clone());
+            let is_on = toggled_actions.update(action.clone(), is_held, actions_frozen.is_frozen());
+            if !options.hold_to_toggle {
+                continue;
+            }
+            if is_on {
+                actions.press(action);
+            } else {
+                actions.release(action);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn pressing_toggles_and_freezing_lets_go() {
+        let mut toggled = ToggledActions::default();
+        let sprint = PlayerAction::Sprint;
+        assert!(toggled.update(sprint.clone(), true, false));
+        // Holding or letting go does not change anything
+        assert!(toggled.update(sprint.clone(), true, false));
+        assert!(toggled.update(sprint.clone(), false, false));
+        // Pressing again toggles it off
+        assert!(!toggled.update(sprint.clone(), true, false));
+        assert!(!toggled.update(sprint.clone(), false, false));
+
+        assert!(toggled.update(sprint.clone(), true, false));
+        assert!(toggled.update(sprint.clone(), false, false));
+        // A menu opens while sprinting
+        assert!(!toggled.update(sprint.clone(), false, true));
+        assert!(!toggled.update(sprint.clone(), true, true));
+        // The key is still held when the menu closes, which is not a new press
+        assert!(!toggled.update(sprint.clone(), true, false));
+        assert!(!toggled.update(sprint.clone(), false, false));
+        assert!(toggled.update(sprint.clone(), true, false));
+
+        toggled.release_all();
+        assert!(!toggled.update(sprint, true, false));
+    }
+
+    #[test]
+    fn smoothing_multiplier_scales_every_camera_smoothing() {
+        let options = AccessibilityOptions(Accessibility {
+            camera_smoothing_multiplier: 0.5,
+            ..default()
+        });
+        let config = GameConfig::default();
+        let scaled = options.scale_camera_smoothing(&config);
+        assert_eq!(
+            scaled.camera.third_person.rotation_smoothing,
+            config.camera.third_person.rotation_smoothing * 0.5
+        );
+        assert_eq!(
+            scaled.camera.first_person.translation_smoothing,
+            config.camera.first_person.translation_smoothing * 0.5
+        );
+        assert_eq!(
+            scaled.camera.dynamic_fov_smoothing,
+            config.camera.dynamic_fov_smoothing * 0.5
+        );
+        assert_eq!(scaled.camera.fov_degrees, config.camera.fov_degrees);
+    }
+}
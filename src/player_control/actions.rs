@@ -1,3 +1,6 @@
+use crate::file_system_interaction::asset_loading::ConfigAssets;
+use crate::file_system_interaction::config::GameConfig;
+use crate::world_interaction::dialog::CurrentDialog;
 use bevy::prelude::*;
 use leafwing_input_manager::axislike::DualAxisData;
 use leafwing_input_manager::plugin::InputManagerSystem;
@@ -50,9 +53,19 @@ pub enum PlayerAction {
     Move,
     Sprint,
     Jump,
+    Dash,
+    Grapple,
     Interact,
     SpeedUpDialog,
+    /// Reveals the rest of the current dialog page or advances to the next one. Held down, it fast-forwards.
+    ConfirmDialog,
+    /// Fast-forwards through dialog pages without choices
+    SkipDialog,
     NumberedChoice(u16),
+    /// Saves into the newest quicksave slot without opening a menu
+    QuickSave,
+    /// Loads the newest readable quicksave
+    QuickLoad,
 }
 
 #[derive(Debug, Clone, Actionlike, Reflect, FromReflect, Default)]
@@ -66,6 +79,9 @@ pub enum CameraAction {
 pub enum UiAction {
     #[default]
     TogglePause,
+    ToggleInventory,
+    /// Shows or hides the speedrun timer
+    ToggleSpeedrunTimer,
 }
 
 pub fn create_player_action_input_manager_bundle() -> InputManagerBundle<PlayerAction> {
@@ -73,8 +89,12 @@ pub fn create_player_action_input_manager_bundle() -> InputManagerBundle<PlayerA
         input_map: InputMap::new([
             (QwertyScanCode::Space, PlayerAction::Jump),
             (QwertyScanCode::LShift, PlayerAction::Sprint),
+            (QwertyScanCode::Q, PlayerAction::Dash),
             (QwertyScanCode::E, PlayerAction::Interact),
             (QwertyScanCode::Space, PlayerAction::SpeedUpDialog),
+            (QwertyScanCode::E, PlayerAction::ConfirmDialog),
+            (QwertyScanCode::Return, PlayerAction::ConfirmDialog),
+            (QwertyScanCode::Tab, PlayerAction::SkipDialog),
             (QwertyScanCode::Key1, PlayerAction::NumberedChoice(1)),
             (QwertyScanCode::Key2, PlayerAction::NumberedChoice(2)),
             (QwertyScanCode::Key3, PlayerAction::NumberedChoice(3)),
@@ -85,8 +105,11 @@ pub fn create_player_action_input_manager_bundle() -> InputManagerBundle<PlayerA
             (QwertyScanCode::Key8, PlayerAction::NumberedChoice(8)),
             (QwertyScanCode::Key9, PlayerAction::NumberedChoice(9)),
             (QwertyScanCode::Key0, PlayerAction::NumberedChoice(0)),
+            (QwertyScanCode::F5, PlayerAction::QuickSave),
+            (QwertyScanCode::F9, PlayerAction::QuickLoad),
         ])
         .insert(VirtualDPad::wasd(), PlayerAction::Move)
+        .insert(MouseButton::Right, PlayerAction::Grapple)
         .build(),
         ..default()
     }
@@ -104,7 +127,11 @@ pub fn create_camera_action_input_manager_bundle() -> InputManagerBundle<CameraA
 
 pub fn create_ui_action_input_manager_bundle() -> InputManagerBundle<UiAction> {
     InputManagerBundle {
-        input_map: InputMap::new([(QwertyScanCode::Escape, UiAction::TogglePause)]),
+        input_map: InputMap::new([
+            (QwertyScanCode::Escape, UiAction::TogglePause),
+            (QwertyScanCode::I, UiAction::ToggleInventory),
+            (QwertyScanCode::F11, UiAction::ToggleSpeedrunTimer),
+        ]),
         ..default()
     }
 }
@@ -113,16 +140,31 @@ pub fn remove_actions_when_frozen(
     actions_frozen: Res<ActionsFrozen>,
     mut player_actions_query: Query<&mut ActionState<PlayerAction>>,
     mut camera_actions_query: Query<&mut ActionState<CameraAction>>,
+    current_dialog: Option<Res<CurrentDialog>>,
+    time: Res<Time>,
+    config_handles: Option<Res<ConfigAssets>>,
+    config: Res<Assets<GameConfig>>,
 ) {
     if actions_frozen.is_frozen() {
+        let allow_camera_pan = config_handles
+            .and_then(|handles| config.get(&handles.game))
+            .map(|config| config.dialog.allow_camera_pan)
+            .unwrap_or_default()
+            && current_dialog.is_some()
+            // Pausing freezes the actions as well
+            && !time.is_paused();
         for mut player_actions in player_actions_query.iter_mut() {
             player_actions.action_data_mut(PlayerAction::Move).axis_pair = Some(default());
             player_actions.release(PlayerAction::Jump);
+            player_actions.release(PlayerAction::Dash);
+            player_actions.release(PlayerAction::Grapple);
             player_actions.release(PlayerAction::Interact);
             player_actions.release(PlayerAction::Sprint);
         }
         for mut camera_actions in camera_actions_query.iter_mut() {
-            camera_actions.action_data_mut(CameraAction::Pan).axis_pair = Some(default());
+            if !allow_camera_pan {
+                camera_actions.action_data_mut(CameraAction::Pan).axis_pair = Some(default());
+            }
             camera_actions.action_data_mut(CameraAction::Zoom).value = default();
         }
     }
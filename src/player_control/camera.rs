@@ -1,9 +1,12 @@
 use crate::file_system_interaction::asset_loading::ConfigAssets;
 use crate::file_system_interaction::config::GameConfig;
 use crate::level_instantiation::spawning::objects::skydome::Skydome;
+use crate::player_control::accessibility::AccessibilityOptions;
 use crate::player_control::actions::{ActionsFrozen, CameraAction};
 use crate::player_control::camera::focus::{set_camera_focus, switch_kind};
+use crate::player_control::player_embodiment::PlayerLanded;
 use crate::util::log_error::log_errors;
+use crate::util::trait_extension::TransformExt;
 use crate::GameState;
 use anyhow::{Context, Result};
 use bevy::prelude::*;
@@ -11,14 +14,20 @@ use bevy::window::CursorGrabMode;
 use bevy_rapier3d::prelude::*;
 pub use first_person::FirstPersonCamera;
 pub use fixed_angle::FixedAngleCamera;
+pub use fixed_step::FixedStepCamera;
+use fov::apply_fov;
+pub use fov::DynamicFov;
 use leafwing_input_manager::prelude::ActionState;
 use serde::{Deserialize, Serialize};
-pub use third_person::ThirdPersonCamera;
+use std::f32::consts::PI;
+pub use third_person::{CameraRaycaster, LineOfSightCorrection, ThirdPersonCamera};
 use ui::*;
 
 mod first_person;
 mod fixed_angle;
+mod fixed_step;
 pub mod focus;
+mod fov;
 mod third_person;
 mod ui;
 mod util;
@@ -32,6 +41,15 @@ pub struct IngameCamera {
 }
 
 impl IngameCamera {
+    /// The point the camera follows, as last set by [`IngameCamera::set_primary_target`]
+    pub fn primary_target(&self) -> Vec3 {
+        match &self.kind {
+            IngameCameraKind::ThirdPerson(camera) => camera.target,
+            IngameCameraKind::FirstPerson(camera) => camera.transform.translation,
+            IngameCameraKind::FixedAngle(camera) => camera.target,
+        }
+    }
+
     pub fn set_primary_target(&mut self, target: Vec3) {
         match &mut self.kind {
             IngameCameraKind::ThirdPerson(camera) => {
@@ -46,6 +64,54 @@ impl IngameCamera {
         }
     }
 
+    /// Like [`IngameCamera::set_primary_target`], but the third person camera eases towards the target
+    /// instead of snapping to it.
+    pub fn follow_primary_target_smoothly(&mut self, target: Vec3, dt: f32) {
+        if let IngameCameraKind::ThirdPerson(camera) = &mut self.kind {
+            let smoothing = camera.config.camera.third_person.climbing_target_smoothing;
+            let scale = (smoothing * dt).min(1.);
+            camera.target = camera.target.lerp(target, scale);
+        } else {
+            self.set_primary_target(target);
+        }
+    }
+
+    /// Moves the camera to the given target without easing, e.g. after the player was teleported.
+    /// The third person camera is placed behind the target at its current distance.
+    /// Returns the new transform of the camera entity.
+    pub fn snap_to(&mut self, target: Transform) -> Transform {
+        match &mut self.kind {
+            IngameCameraKind::ThirdPerson(camera) => {
+                let height_offset = camera.config.camera.third_person.target_height_offset;
+                camera.up = target.up();
+                camera.target = target.translation + camera.up * height_offset;
+                let eye = camera.target - target.forward() * camera.distance;
+                camera.transform = Transform::from_translation(eye)
+                    .try_looking_at(camera.target, camera.up)
+                    .unwrap_or_else(|| target.with_translation(eye).with_scale(Vec3::ONE));
+                camera.transform
+            }
+            IngameCameraKind::FirstPerson(camera) => {
+                camera.up = target.up();
+                camera.transform = target.with_scale(Vec3::ONE);
+                camera.transform
+            }
+            IngameCameraKind::FixedAngle(camera) => {
+                let offset = camera.transform.translation - camera.target;
+                camera.up = target.up();
+                camera.target = target.translation;
+                camera.transform.translation = target.translation + offset;
+                camera.transform
+            }
+        }
+    }
+
+    pub fn set_target_underwater(&mut self, underwater: bool) {
+        if let IngameCameraKind::ThirdPerson(camera) = &mut self.kind {
+            camera.underwater = underwater;
+        }
+    }
+
     pub fn up(&self) -> Vec3 {
         match &self.kind {
             IngameCameraKind::ThirdPerson(camera) => camera.up,
@@ -93,9 +159,105 @@ impl Default for IngameCameraKind {
     }
 }
 
+impl IngameCameraKind {
+    pub fn transform(&self) -> Transform {
+        match self {
+            IngameCameraKind::ThirdPerson(camera) => camera.transform,
+            IngameCameraKind::FirstPerson(camera) => camera.transform,
+            IngameCameraKind::FixedAngle(camera) => camera.transform,
+        }
+    }
+
+    /// The game config with the camera smoothing scaled by the [`AccessibilityOptions`]
+    pub fn config(&self) -> &GameConfig {
+        match self {
+            IngameCameraKind::ThirdPerson(camera) => &camera.config,
+            IngameCameraKind::FirstPerson(camera) => &camera.config,
+            IngameCameraKind::FixedAngle(camera) => &camera.config,
+        }
+    }
+
+    fn update_transform(
+        &mut self,
+        dt: f32,
+        input: CameraInput,
+        raycaster: &impl CameraRaycaster,
+        transform: Transform,
+        snap: bool,
+    ) -> Transform {
+        match self {
+            IngameCameraKind::ThirdPerson(camera) => {
+                camera.update_transform(dt, input, raycaster, transform, snap)
+            }
+            IngameCameraKind::FirstPerson(camera) => {
+                camera.update_transform(dt, input, transform, snap)
+            }
+            IngameCameraKind::FixedAngle(camera) => {
+                camera.update_transform(dt, input, transform, snap)
+            }
+        }
+    }
+
+    fn transform_mut(&mut self) -> &mut Transform {
+        match self {
+            IngameCameraKind::ThirdPerson(camera) => &mut camera.transform,
+            IngameCameraKind::FirstPerson(camera) => &mut camera.transform,
+            IngameCameraKind::FixedAngle(camera) => &mut camera.transform,
+        }
+    }
+
+    fn config_mut(&mut self) -> &mut GameConfig {
+        match self {
+            IngameCameraKind::ThirdPerson(camera) => &mut camera.config,
+            IngameCameraKind::FirstPerson(camera) => &mut camera.config,
+            IngameCameraKind::FixedAngle(camera) => &mut camera.config,
+        }
+    }
+}
+
+/// The camera controls of a frame, or of several frames when the camera runs in fixed steps
+#[derive(Debug, Clone, Copy, PartialEq, Reflect, FromReflect, Serialize, Deserialize, Default)]
+#[reflect(Serialize, Deserialize)]
+pub struct CameraInput {
+    pub pan: Vec2,
+    pub zoom: f32,
+}
+
+impl CameraInput {
+    pub fn from_actions(actions: &ActionState<CameraAction>) -> Result<Self> {
+        let pan = actions
+            .axis_pair(CameraAction::Pan)
+            .context("Camera movement is not an axis pair")?
+            .xy();
+        let zoom = actions.clamped_value(CameraAction::Zoom);
+        Ok(Self { pan, zoom })
+    }
+
+    /// Adds the controls of another frame
+    pub fn add(&mut self, other: CameraInput) {
+        self.pan += other.pan;
+        self.zoom += other.zoom;
+    }
+
+    /// Returns the collected controls and starts collecting anew
+    pub fn take(&mut self) -> CameraInput {
+        std::mem::take(self)
+    }
+}
+
+/// Makes the next camera update skip its smoothing, e.g. after the player was teleported,
+/// so that the camera does not fly across the level. The line of sight is checked from the new position right away.
+/// Also sent by [`set_camera_focus`] when the target moved further than `camera.teleport_snap_distance` in one frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CameraForceSnap;
+
+/// Camera from a loaded save. Replaces the kind of the next [`IngameCamera`] that is spawned.
+#[derive(Debug, Clone, PartialEq, Resource)]
+pub struct RestoredCamera(pub IngameCameraKind);
+
 /// Handles the main ingame camera, i.e. not the UI camera in the menu.
 /// Cameras are controlled with [`CameraActions`]. Depending on the distance, a first person,
-/// third person or fixed angle camera is used.
+/// third person or fixed angle camera is used. The field of view widens at high speeds, see [`DynamicFov`].
 pub struct CameraPlugin;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, SystemLabel)]
@@ -109,15 +271,31 @@ impl Plugin for CameraPlugin {
             .register_type::<IngameCameraKind>()
             .register_type::<FirstPersonCamera>()
             .register_type::<FixedAngleCamera>()
+            .register_type::<LandingDip>()
+            .register_type::<DynamicFov>()
+            .register_type::<FixedStepCamera>()
+            .register_type::<CameraInput>()
             .init_resource::<ForceCursorGrabMode>()
+            .add_event::<CameraForceSnap>()
             .add_startup_system(spawn_ui_camera)
             .add_system_set(SystemSet::on_enter(GameState::Playing).with_system(despawn_ui_camera))
+            .add_system_set(SystemSet::on_exit(GameState::Playing).with_system(spawn_ui_camera))
+            // Frees the cursor for the pause menu
+            .add_system_set(
+                SystemSet::on_in_stack_update(GameState::Playing)
+                    .with_system(cursor_grab_system.pipe(log_errors)),
+            )
             .add_system_set(
                 SystemSet::on_update(GameState::Playing)
-                    .with_system(cursor_grab_system.pipe(log_errors))
-                    .with_system(init_camera.pipe(log_errors))
+                    .with_system(
+                        init_camera
+                            .pipe(log_errors)
+                            .before(SetCameraFocusLabel)
+                            .before(UpdateCameraTransformLabel),
+                    )
                     .with_system(set_camera_focus.pipe(log_errors).label(SetCameraFocusLabel))
                     .with_system(switch_kind.after(SetCameraFocusLabel))
+                    .with_system(start_landing_dip.pipe(log_errors).before(update_transform))
                     .with_system(
                         update_transform
                             .pipe(log_errors)
@@ -125,6 +303,8 @@ impl Plugin for CameraPlugin {
                             .after(switch_kind),
                     )
                     .with_system(update_config.pipe(log_errors))
+                    .with_system(update_camera_smoothing.pipe(log_errors))
+                    .with_system(apply_fov.after(init_camera))
                     .with_system(move_skydome.after(UpdateCameraTransformLabel)),
             );
     }
@@ -133,31 +313,90 @@ impl Plugin for CameraPlugin {
 #[derive(SystemLabel)]
 pub struct UpdateCameraTransformLabel;
 
+/// Briefly lowers the camera's eye, e.g. when the player lands after a fall.
+#[derive(Debug, Clone, PartialEq, Component, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Component, Serialize, Deserialize)]
+pub struct LandingDip {
+    /// Maximum depth of the dip in m
+    pub depth: f32,
+    /// Duration of the whole dip in seconds
+    pub duration: f32,
+    /// Time in seconds since the dip started
+    pub elapsed: f32,
+    /// Offset that was added to the camera's translation last tick
+    pub applied_offset: Vec3,
+}
+
+impl LandingDip {
+    fn advance(&mut self, dt: f32) -> f32 {
+        if self.elapsed >= self.duration {
+            return 0.;
+        }
+        self.elapsed = (self.elapsed + dt).min(self.duration);
+        self.depth * (PI * self.elapsed / self.duration).sin()
+    }
+}
+
+fn start_landing_dip(
+    mut player_landed_events: EventReader<PlayerLanded>,
+    mut camera_query: Query<&mut LandingDip>,
+    accessibility: Res<AccessibilityOptions>,
+    config_handles: Res<ConfigAssets>,
+    config: Res<Assets<GameConfig>>,
+) -> Result<()> {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("start_landing_dip").entered();
+    if accessibility.reduce_camera_motion {
+        player_landed_events.clear();
+        return Ok(());
+    }
+    let impact_speed = match player_landed_events
+        .iter()
+        .map(|event| event.impact_speed)
+        .reduce(f32::max)
+    {
+        Some(impact_speed) => impact_speed,
+        None => return Ok(()),
+    };
+    let config = config
+        .get(&config_handles.game)
+        .context("Failed to get game config from handle")?;
+    for mut dip in camera_query.iter_mut() {
+        dip.depth =
+            (impact_speed * config.camera.landing_dip_per_speed).min(config.camera.max_landing_dip);
+        dip.duration = config.camera.landing_dip_duration;
+        dip.elapsed = 0.;
+    }
+    Ok(())
+}
+
 fn init_camera(
-    mut camera: Query<(&Transform, &mut IngameCamera), Added<IngameCamera>>,
+    mut commands: Commands,
+    mut camera: Query<(&mut Transform, &mut IngameCamera), Added<IngameCamera>>,
+    restored_camera: Option<Res<RestoredCamera>>,
+    accessibility: Res<AccessibilityOptions>,
     config_handles: Res<ConfigAssets>,
     config: Res<Assets<GameConfig>>,
 ) -> Result<()> {
     #[cfg(feature = "tracing")]
     let _span = info_span!("init_camera").entered();
-    for (transform, mut camera) in camera.iter_mut() {
+    for (mut transform, mut camera) in camera.iter_mut() {
         let game_config = config
             .get(&config_handles.game)
             .context("Failed to get game config from handle")?;
-        match &mut camera.kind {
-            IngameCameraKind::ThirdPerson(camera) => {
-                camera.transform = *transform;
-                camera.config = game_config.clone();
-            }
-            IngameCameraKind::FirstPerson(camera) => {
-                camera.transform = *transform;
-                camera.config = game_config.clone();
-            }
-            IngameCameraKind::FixedAngle(camera) => {
-                camera.transform = *transform;
-                camera.config = game_config.clone();
+        if let Some(restored_camera) = &restored_camera {
+            // Applied before the camera is first updated, so that it doesn't visibly jump
+            camera.kind = restored_camera.0.clone();
+            *transform = camera.kind.transform();
+            commands.remove_resource::<RestoredCamera>();
+        } else {
+            match &mut camera.kind {
+                IngameCameraKind::ThirdPerson(camera) => camera.transform = *transform,
+                IngameCameraKind::FirstPerson(camera) => camera.transform = *transform,
+                IngameCameraKind::FixedAngle(camera) => camera.transform = *transform,
             }
         }
+        *camera.kind.config_mut() = accessibility.scale_camera_smoothing(game_config);
     }
     Ok(())
 }
@@ -165,36 +404,83 @@ fn init_camera(
 pub fn update_transform(
     time: Res<Time>,
     rapier_context: Res<RapierContext>,
+    mut snap_events: EventReader<CameraForceSnap>,
     mut camera: Query<(
         &ActionState<CameraAction>,
         &mut IngameCamera,
         &mut Transform,
+        Option<&mut LandingDip>,
+        Option<&mut FixedStepCamera>,
     )>,
 ) -> Result<()> {
     #[cfg(feature = "tracing")]
     let _span = info_span!("update_transform").entered();
-    for (actions, mut camera, mut transform) in camera.iter_mut() {
+    let snap = snap_events.iter().count() > 0;
+    for (actions, mut camera, mut transform, dip, mut fixed_step) in camera.iter_mut() {
         let dt = time.delta_seconds();
-        let new_transform = {
-            match &mut camera.kind {
-                IngameCameraKind::ThirdPerson(camera) => {
-                    camera.update_transform(dt, actions, &rapier_context, *transform)
-                }
-                IngameCameraKind::FirstPerson(camera) => {
-                    camera.update_transform(dt, actions, *transform)
-                }
-                IngameCameraKind::FixedAngle(camera) => {
-                    camera.update_transform(dt, actions, *transform)
+        // Remove last tick's dip so that it does not feed back into the smoothing
+        if let Some(dip) = &dip {
+            transform.translation -= dip.applied_offset;
+        }
+        let input = CameraInput::from_actions(actions)?;
+        let camera_config = &camera.kind.config().camera;
+        let fixed_timestep = camera_config
+            .fixed_timestep
+            .then(|| 1. / camera_config.fixed_timestep_rate.max(1.));
+        let new_transform = match (fixed_step.as_mut(), fixed_timestep) {
+            (Some(fixed_step), Some(step)) => {
+                let kind = &mut camera.kind;
+                fixed_step.advance(
+                    dt,
+                    step,
+                    input,
+                    *transform,
+                    snap,
+                    |dt, input, transform, snap| {
+                        kind.update_transform(dt, input, &*rapier_context, transform, snap)
+                    },
+                )
+            }
+            (fixed_step, _) => {
+                if let Some(fixed_step) = fixed_step {
+                    if fixed_step.initialized {
+                        fixed_step.reset();
+                    }
                 }
+                camera
+                    .kind
+                    .update_transform(dt, input, &*rapier_context, *transform, snap)
+            }
+        };
+        // A NaN transform would propagate to everything rendered and never recover,
+        // so the camera starts over from its last valid transform instead
+        let new_transform = if new_transform.is_finite() {
+            new_transform
+        } else {
+            warn!("Camera produced an invalid transform, keeping the last valid one");
+            let last_valid = if transform.is_finite() {
+                *transform
+            } else {
+                Transform::default()
+            };
+            *camera.kind.transform_mut() = last_valid;
+            if let Some(mut fixed_step) = fixed_step {
+                fixed_step.reset();
             }
-        }?;
+            last_valid
+        };
         *transform = new_transform;
+        if let Some(mut dip) = dip {
+            dip.applied_offset = -camera.up() * dip.advance(dt);
+            transform.translation += dip.applied_offset;
+        }
     }
     Ok(())
 }
 
 fn update_config(
     config: Res<Assets<GameConfig>>,
+    accessibility: Res<AccessibilityOptions>,
     mut config_asset_events: EventReader<AssetEvent<GameConfig>>,
     mut camera_query: Query<&mut IngameCamera>,
 ) -> Result<()> {
@@ -208,11 +494,7 @@ fn update_config(
                     .get(handle)
                     .context("Failed to get config even though it was just created")?;
                 for mut camera in camera_query.iter_mut() {
-                    *match camera.kind {
-                        IngameCameraKind::ThirdPerson(ref mut camera) => &mut camera.config,
-                        IngameCameraKind::FirstPerson(ref mut camera) => &mut camera.config,
-                        IngameCameraKind::FixedAngle(ref mut camera) => &mut camera.config,
-                    } = config.clone();
+                    *camera.kind.config_mut() = accessibility.scale_camera_smoothing(config);
                 }
             }
             AssetEvent::Removed { .. } => {}
@@ -221,6 +503,26 @@ fn update_config(
     Ok(())
 }
 
+fn update_camera_smoothing(
+    accessibility: Res<AccessibilityOptions>,
+    config_handles: Res<ConfigAssets>,
+    config: Res<Assets<GameConfig>>,
+    mut camera_query: Query<&mut IngameCamera>,
+) -> Result<()> {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("update_camera_smoothing").entered();
+    if !accessibility.is_changed() {
+        return Ok(());
+    }
+    let config = config
+        .get(&config_handles.game)
+        .context("Failed to get game config from handle")?;
+    for mut camera in camera_query.iter_mut() {
+        *camera.kind.config_mut() = accessibility.scale_camera_smoothing(config);
+    }
+    Ok(())
+}
+
 fn move_skydome(
     camera_query: Query<&Transform, (With<IngameCamera>, Without<Skydome>)>,
     mut skydome_query: Query<&mut Transform, (Without<IngameCamera>, With<Skydome>)>,
@@ -238,12 +540,17 @@ fn move_skydome(
 pub struct ForceCursorGrabMode(pub Option<CursorGrabMode>);
 
 fn cursor_grab_system(
-    mut windows: ResMut<Windows>,
+    windows: Option<ResMut<Windows>>,
     actions_frozen: Res<ActionsFrozen>,
     force_cursor_grab: Res<ForceCursorGrabMode>,
 ) -> Result<()> {
     #[cfg(feature = "tracing")]
     let _span = info_span!("cursor_grab_system").entered();
+    // Headless apps, e.g. in tests, have no windows at all
+    let mut windows = match windows {
+        Some(windows) => windows,
+        None => return Ok(()),
+    };
     let window = windows
         .get_primary_mut()
         .context("Failed to get primary window")?;
@@ -1,10 +1,8 @@
 use crate::file_system_interaction::config::GameConfig;
-use crate::player_control::actions::CameraAction;
 use crate::player_control::camera::util::clamp_pitch;
-use crate::player_control::camera::ThirdPersonCamera;
-use anyhow::{Context, Result};
+use crate::player_control::camera::{CameraInput, ThirdPersonCamera};
+use crate::util::trait_extension::TransformExt;
 use bevy::prelude::*;
-use leafwing_input_manager::prelude::ActionState;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, PartialEq, Reflect, FromReflect, Serialize, Deserialize)]
@@ -13,6 +11,8 @@ pub struct FirstPersonCamera {
     pub transform: Transform,
     pub look_target: Option<Vec3>,
     pub up: Vec3,
+    /// Not saved, the live config is applied when the camera is restored
+    #[serde(skip)]
     pub config: GameConfig,
 }
 
@@ -47,19 +47,39 @@ impl FirstPersonCamera {
     pub fn update_transform(
         &mut self,
         dt: f32,
-        camera_actions: &ActionState<CameraAction>,
+        input: CameraInput,
         transform: Transform,
-    ) -> Result<Transform> {
-        if let Some(look_target) = self.look_target {
-            self.look_at(look_target);
-        } else {
-            let camera_movement = camera_actions
-                .axis_pair(CameraAction::Pan)
-                .context("Camera movement is not an axis pair")?
-                .xy();
-            self.handle_camera_controls(camera_movement);
+        snap: bool,
+    ) -> Transform {
+        match self.look_target {
+            Some(look_target) => {
+                // The player can still glance around, but the target pulls the view back
+                let weight = self
+                    .config
+                    .camera
+                    .first_person
+                    .look_target_weight
+                    .clamp(0., 1.);
+                self.handle_camera_controls_near_look_target(input.pan * (1. - weight));
+                let max_angle = if snap {
+                    f32::INFINITY
+                } else {
+                    self.config
+                        .camera
+                        .first_person
+                        .look_target_max_angular_speed
+                        * weight
+                        * dt
+                };
+                self.turn_towards(look_target, max_angle);
+            }
+            // Also pulls the view back within the pitch limits right after a look target that was past them is cleared
+            None => self.handle_camera_controls(input.pan),
+        }
+        if snap {
+            return self.transform;
         }
-        Ok(self.get_camera_transform(dt, transform))
+        self.get_camera_transform(dt, transform)
     }
 
     fn get_camera_transform(&self, dt: f32, mut transform: Transform) -> Transform {
@@ -83,9 +103,35 @@ impl FirstPersonCamera {
         self.rotate(yaw, pitch);
     }
 
-    fn look_at(&mut self, target: Vec3) {
-        let up = self.up;
-        self.transform.look_at(target, up);
+    /// Like [`FirstPersonCamera::handle_camera_controls`], but the pitch is not pulled back within its limits,
+    /// as that would fight a look target that is past them. Instead, the player cannot pitch any further there.
+    fn handle_camera_controls_near_look_target(&mut self, camera_movement: Vec2) {
+        let yaw = -camera_movement.x * self.config.camera.mouse_sensitivity_x;
+        let pitch = -camera_movement.y * self.config.camera.mouse_sensitivity_y;
+        let is_within_pitch_limits = self.clamp_pitch(0.) == 0.;
+        let pitch = if is_within_pitch_limits {
+            self.clamp_pitch(pitch)
+        } else {
+            0.
+        };
+        self.rotate(yaw, pitch);
+    }
+
+    /// Turns towards `target` by at most `max_angle` radians.
+    /// Keeps the last rotation when the target is straight above or below.
+    fn turn_towards(&mut self, target: Vec3, max_angle: f32) {
+        let looking_at_target = match self.transform.try_looking_at(target, self.up) {
+            Some(transform) => transform.rotation,
+            None => return,
+        };
+        let angle = self.transform.rotation.angle_between(looking_at_target);
+        self.transform.rotation = if angle <= max_angle {
+            looking_at_target
+        } else {
+            self.transform
+                .rotation
+                .slerp(looking_at_target, max_angle / angle)
+        };
     }
 
     fn rotate(&mut self, yaw: f32, pitch: f32) {
@@ -106,3 +152,66 @@ impl FirstPersonCamera {
         )
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::f32::consts::FRAC_PI_2;
+
+    fn build_camera() -> FirstPersonCamera {
+        let mut camera = FirstPersonCamera::default();
+        camera.config.camera.first_person.look_target_weight = 1.;
+        camera
+            .config
+            .camera
+            .first_person
+            .look_target_max_angular_speed = FRAC_PI_2;
+        camera
+    }
+
+    #[test]
+    fn turns_towards_look_target_at_capped_speed() {
+        let mut camera = build_camera();
+        camera.look_target = Some(Vec3::X);
+        let input = CameraInput {
+            pan: Vec2::new(100., 100.),
+            zoom: 0.,
+        };
+
+        camera.update_transform(0.5, input, camera.transform, false);
+        let angle_to_target = camera.forward().angle_between(Vec3::X);
+        assert!((angle_to_target - FRAC_PI_2 / 2.).abs() < 1e-4);
+
+        camera.update_transform(0.5, input, camera.transform, false);
+        assert!(camera.forward().angle_between(Vec3::X) < 1e-3);
+        camera.update_transform(0.5, input, camera.transform, false);
+        assert!(camera.forward().angle_between(Vec3::X) < 1e-3);
+    }
+
+    #[test]
+    fn pitch_is_clamped_again_once_look_target_clears() {
+        let mut camera = build_camera();
+        camera
+            .config
+            .camera
+            .first_person
+            .look_target_max_angular_speed = 100.;
+        // Far steeper than the camera may look down on its own
+        camera.look_target = Some(Vec3::new(0.1, -1., 0.));
+        let input = CameraInput::default();
+        let most_acute_from_above = camera.config.camera.first_person.most_acute_from_above;
+
+        camera.update_transform(0.1, input, camera.transform, false);
+        assert!(camera.forward().angle_between(Vec3::NEG_Y) < most_acute_from_above / 2.);
+        // The look target keeps the camera there
+        camera.update_transform(0.1, input, camera.transform, false);
+        assert!(camera.forward().angle_between(Vec3::NEG_Y) < most_acute_from_above / 2.);
+
+        camera.look_target = None;
+        let rotation = camera.transform.rotation;
+        camera.update_transform(0.1, input, camera.transform, false);
+        assert_ne!(camera.transform.rotation, rotation);
+        assert!(camera.forward().angle_between(Vec3::NEG_Y) > most_acute_from_above - 1e-3);
+        assert!(camera.forward().y < 0.);
+    }
+}
@@ -1,9 +1,6 @@
 use crate::file_system_interaction::config::GameConfig;
-use crate::player_control::actions::CameraAction;
-use crate::player_control::camera::ThirdPersonCamera;
-use anyhow::Result;
+use crate::player_control::camera::{CameraInput, ThirdPersonCamera};
 use bevy::prelude::*;
-use leafwing_input_manager::prelude::ActionState;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, PartialEq, Reflect, FromReflect, Serialize, Deserialize)]
@@ -14,6 +11,8 @@ pub struct FixedAngleCamera {
     pub up: Vec3,
     pub secondary_target: Option<Vec3>,
     pub distance: f32,
+    /// Not saved, the live config is applied when the camera is restored
+    #[serde(skip)]
     pub config: GameConfig,
 }
 
@@ -53,13 +52,16 @@ impl FixedAngleCamera {
     pub fn update_transform(
         &mut self,
         dt: f32,
-        camera_actions: &ActionState<CameraAction>,
+        input: CameraInput,
         transform: Transform,
-    ) -> Result<Transform> {
-        let zoom = camera_actions.clamped_value(CameraAction::Zoom);
-        self.zoom(zoom);
+        snap: bool,
+    ) -> Transform {
+        self.zoom(input.zoom);
         self.follow_target();
-        Ok(self.get_camera_transform(dt, transform))
+        if snap {
+            return self.transform;
+        }
+        self.get_camera_transform(dt, transform)
     }
 
     fn follow_target(&mut self) {
@@ -69,7 +71,9 @@ impl FixedAngleCamera {
             self.target
         };
         self.transform.translation = target + self.up * self.distance;
-        self.transform.look_at(target, self.transform.up());
+        if let Some(transform) = self.transform.try_looking_at(target, self.transform.up()) {
+            self.transform = transform;
+        }
     }
 
     fn zoom(&mut self, zoom: f32) {
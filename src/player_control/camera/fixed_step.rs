@@ -0,0 +1,169 @@
+use crate::player_control::camera::CameraInput;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Advances the camera in fixed steps of `camera.fixed_timestep_rate` per second when `camera.fixed_timestep` is on.
+/// The smoothing constants are rates per second either way, but in fixed steps they behave the same at every frame rate.
+/// The rendered transform is interpolated between the last two steps, so it lags behind by at most one step.
+/// Camera input is collected every frame and applied in the next step, so that no mouse movement is lost or applied twice.
+#[derive(Debug, Clone, PartialEq, Component, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Component, Serialize, Deserialize)]
+pub struct FixedStepCamera {
+    /// Transform after the step before the last one
+    pub previous: Transform,
+    /// Transform after the last step
+    pub current: Transform,
+    /// Time in seconds since the last step
+    pub accumulator: f32,
+    pub pending_input: CameraInput,
+    /// Whether the steps have started from the rendered transform yet
+    pub initialized: bool,
+}
+
+impl FixedStepCamera {
+    /// Steps taken in a single frame at most, so that a long frame does not make the camera fall further and further behind
+    pub const MAX_STEPS_PER_FRAME: usize = 5;
+
+    /// Takes as many steps of `step` seconds as fit into the time since the last frame and returns the interpolated transform.
+    /// `update` advances the camera by one step from the given transform.
+    /// A `snap` takes a single step right away and does not interpolate from the old transform.
+    pub fn advance(
+        &mut self,
+        dt: f32,
+        step: f32,
+        input: CameraInput,
+        rendered: Transform,
+        snap: bool,
+        mut update: impl FnMut(f32, CameraInput, Transform, bool) -> Transform,
+    ) -> Transform {
+        self.pending_input.add(input);
+        if !self.initialized {
+            self.previous = rendered;
+            self.current = rendered;
+            self.accumulator = 0.;
+            self.initialized = true;
+        }
+        if snap {
+            let snapped = update(step, self.pending_input.take(), self.current, true);
+            self.previous = snapped;
+            self.current = snapped;
+            self.accumulator = 0.;
+            return snapped;
+        }
+
+        self.accumulator += dt;
+        let mut steps = 0;
+        while self.accumulator >= step {
+            if steps == Self::MAX_STEPS_PER_FRAME {
+                self.accumulator = 0.;
+                break;
+            }
+            self.accumulator -= step;
+            steps += 1;
+            self.previous = self.current;
+            self.current = update(step, self.pending_input.take(), self.current, false);
+        }
+        let overstep = (self.accumulator / step).clamp(0., 1.);
+        Transform {
+            translation: self
+                .previous
+                .translation
+                .lerp(self.current.translation, overstep),
+            rotation: self
+                .previous
+                .rotation
+                .slerp(self.current.rotation, overstep),
+            scale: self.previous.scale.lerp(self.current.scale, overstep),
+        }
+    }
+
+    /// Makes the next frame start over from the rendered transform, e.g. after switching back from variable steps
+    pub fn reset(&mut self) {
+        self.initialized = false;
+        self.pending_input = default();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const STEP: f32 = 0.1;
+
+    /// Moves one m along x per step and turns by the pan input
+    fn step_camera(dt: f32, input: CameraInput, transform: Transform, _snap: bool) -> Transform {
+        assert_eq!(dt, STEP);
+        Transform {
+            translation: transform.translation + Vec3::X,
+            rotation: Quat::from_rotation_y(input.pan.x) * transform.rotation,
+            ..transform
+        }
+    }
+
+    #[test]
+    fn interpolates_between_steps() {
+        let mut camera = FixedStepCamera::default();
+        let input = CameraInput::default();
+        let rendered = camera.advance(0.05, STEP, input, Transform::IDENTITY, false, step_camera);
+        assert_eq!(rendered.translation, Vec3::ZERO);
+
+        let rendered = camera.advance(0.1, STEP, input, rendered, false, step_camera);
+        assert!((rendered.translation - Vec3::new(0.5, 0., 0.)).length() < 1e-5);
+        assert_eq!(camera.current.translation, Vec3::X);
+    }
+
+    #[test]
+    fn input_between_steps_is_not_lost() {
+        let mut camera = FixedStepCamera::default();
+        let pan = |x| CameraInput {
+            pan: Vec2::new(x, 0.),
+            zoom: 0.,
+        };
+        // No step is taken in these frames
+        camera.advance(
+            0.03,
+            STEP,
+            pan(0.1),
+            Transform::IDENTITY,
+            false,
+            step_camera,
+        );
+        camera.advance(
+            0.03,
+            STEP,
+            pan(0.2),
+            Transform::IDENTITY,
+            false,
+            step_camera,
+        );
+        // Two steps are taken in this frame, only the first one gets the input
+        camera.advance(
+            0.15,
+            STEP,
+            pan(0.3),
+            Transform::IDENTITY,
+            false,
+            step_camera,
+        );
+        let (_, angle) = camera.current.rotation.to_axis_angle();
+        assert!((angle - 0.6).abs() < 1e-5);
+        assert_eq!(camera.current.translation, Vec3::X * 2.);
+        assert_eq!(camera.pending_input, CameraInput::default());
+    }
+
+    #[test]
+    fn long_frames_are_capped_and_snaps_do_not_interpolate() {
+        let mut camera = FixedStepCamera::default();
+        let input = CameraInput::default();
+        camera.advance(10., STEP, input, Transform::IDENTITY, false, step_camera);
+        assert_eq!(
+            camera.current.translation,
+            Vec3::X * FixedStepCamera::MAX_STEPS_PER_FRAME as f32
+        );
+        assert_eq!(camera.accumulator, 0.);
+
+        let rendered = camera.advance(0.05, STEP, input, Transform::IDENTITY, true, step_camera);
+        assert_eq!(rendered, camera.current);
+        assert_eq!(camera.previous, camera.current);
+    }
+}
@@ -1,29 +1,76 @@
+use crate::file_system_interaction::asset_loading::ConfigAssets;
+use crate::file_system_interaction::config::GameConfig;
+use crate::movement::climbing::Climbing;
+use crate::movement::water::Swimming;
 use crate::player_control::actions::CameraAction;
-use crate::player_control::camera::{IngameCamera, IngameCameraKind};
+use crate::player_control::camera::{CameraForceSnap, IngameCamera, IngameCameraKind};
+use crate::player_control::grapple::Grapple;
 use crate::player_control::player_embodiment::Player;
 use crate::world_interaction::dialog::CurrentDialog;
-use anyhow::Result;
+use crate::world_interaction::health::{get_death_camera_focus, Dying};
+use anyhow::{Context, Result};
 use bevy::prelude::*;
 use leafwing_input_manager::prelude::ActionState;
 
 pub fn set_camera_focus(
+    time: Res<Time>,
     mut camera_query: Query<&mut IngameCamera>,
     current_dialog: Option<Res<CurrentDialog>>,
-    player_query: Query<&Transform, With<Player>>,
-    non_player_query: Query<&GlobalTransform, Without<Player>>,
+    dying: Option<Res<Dying>>,
+    player_query: Query<(&Transform, &Swimming, &Climbing, &Grapple), With<Player>>,
+    speaker_query: Query<&GlobalTransform>,
+    mut snap_events: EventWriter<CameraForceSnap>,
+    config_handles: Res<ConfigAssets>,
+    config: Res<Assets<GameConfig>>,
 ) -> Result<()> {
+    let config = config
+        .get(&config_handles.game)
+        .context("Failed to get game config from handle")?;
     for mut camera in camera_query.iter_mut() {
         if let Some(ref active_dialogue) = current_dialog {
-            let global_translation = non_player_query.get(active_dialogue.source)?;
-            let translation = global_translation.translation();
+            // Frame the speaker's head. When the speaker changes, the camera eases over to the new one.
+            let speaker_transform = speaker_query.get(active_dialogue.speaker)?;
+            let translation = speaker_transform.translation()
+                + speaker_transform.up() * config.dialog.speaker_head_offset;
             *camera.secondary_target_mut() = Some(translation);
         } else {
             *camera.secondary_target_mut() = None;
         }
-        for transform in player_query.iter() {
-            let translation = transform.translation;
-            camera.set_primary_target(translation);
+        for (transform, swimming, climbing, grapple) in player_query.iter() {
+            let is_third_person = matches!(camera.kind, IngameCameraKind::ThirdPerson(_));
+            if current_dialog.is_none() && is_third_person {
+                // Keep the anchor in frame while swinging
+                *camera.secondary_target_mut() = grapple.get_anchor_point();
+            }
+            if dying.is_some() && is_third_person {
+                // Orbit the body until the player respawns
+                *camera.secondary_target_mut() = get_death_camera_focus(
+                    transform.translation,
+                    transform.up(),
+                    camera.forward(),
+                    config.player.death_camera_orbit_speed,
+                    config.camera.third_person.alignment_smoothing,
+                );
+            }
+            let translation = if is_third_person {
+                transform.translation
+                    + transform.up() * config.camera.third_person.target_height_offset
+            } else {
+                transform.translation
+            };
+            // The player was teleported without telling us, so don't let the camera fly after them
+            let teleported = camera.primary_target().distance(translation)
+                > config.camera.teleport_snap_distance;
+            if teleported {
+                snap_events.send(CameraForceSnap);
+            }
+            if climbing.is_climbing() && !teleported {
+                camera.follow_primary_target_smoothly(translation, time.delta_seconds());
+            } else {
+                camera.set_primary_target(translation);
+            }
             *camera.up_mut() = transform.up();
+            camera.set_target_underwater(swimming.is_swimming());
         }
     }
     Ok(())
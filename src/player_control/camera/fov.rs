@@ -0,0 +1,134 @@
+use crate::file_system_interaction::config;
+use crate::file_system_interaction::user_settings::UserSettings;
+use crate::player_control::accessibility::AccessibilityOptions;
+use crate::player_control::camera::{IngameCamera, IngameCameraKind};
+use crate::player_control::player_embodiment::Player;
+use crate::util::trait_extension::Vec3Ext;
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+const MIN_FOV_DEGREES: f32 = 1.;
+const MAX_FOV_DEGREES: f32 = 170.;
+
+/// Widens the field of view of the [`IngameCamera`] while the player moves fast.
+/// The base field of view is `camera.fov_degrees`, unless the player chose their own in the settings menu.
+/// The camera shows `(base + boost) * zoom` degrees, so zooming in narrows a widened view by the same factor as a calm one.
+/// Only the base is ever written to the settings, never the boost.
+/// The boost is turned off by the dynamic FOV setting and by [`AccessibilityOptions::reduce_camera_motion`].
+/// The boost is turned off by the dynamic FOV setting and by [`AccessibilityOptions::reduce_camera_motion`].
+#[derive(Debug, Clone, Copy, PartialEq, Component, Reflect, Serialize, Deserialize)]
+#[reflect(Component, Serialize, Deserialize)]
+pub struct DynamicFov {
+    /// Degrees added on top of the base, eased towards [`get_fov_boost_target`]
+    pub boost: f32,
+    /// Factor for the whole field of view, e.g. for aiming. Below 1 zooms in.
+    pub zoom: f32,
+}
+
+impl Default for DynamicFov {
+    fn default() -> Self {
+        Self {
+            boost: 0.,
+            zoom: 1.,
+        }
+    }
+}
+
+/// Degrees to widen the field of view by when moving horizontally at `horizontal_speed` m/s.
+/// Grows linearly up to `dynamic_fov_max_boost` at `dynamic_fov_max_speed`.
+pub fn get_fov_boost_target(horizontal_speed: f32, camera: &config::Camera) -> f32 {
+    if camera.dynamic_fov_max_speed <= 0. {
+        return 0.;
+    }
+    let scale = (horizontal_speed / camera.dynamic_fov_max_speed).clamp(0., 1.);
+    scale * camera.dynamic_fov_max_boost
+}
+
+/// Vertical field of view in radians, see [`DynamicFov`]
+pub fn get_fov(base_degrees: f32, boost_degrees: f32, zoom: f32) -> f32 {
+    ((base_degrees + boost_degrees) * zoom)
+        .clamp(MIN_FOV_DEGREES, MAX_FOV_DEGREES)
+        .to_radians()
+}
+
+pub(super) fn apply_fov(
+    time: Res<Time>,
+    settings: Res<UserSettings>,
+    accessibility: Res<AccessibilityOptions>,
+    player_query: Query<&Velocity, With<Player>>,
+    mut camera_query: Query<(&IngameCamera, &mut Projection, &mut DynamicFov)>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("apply_fov").entered();
+    let dt = time.delta_seconds();
+    for (camera, mut projection, mut fov) in camera_query.iter_mut() {
+        // Has its smoothing scaled by the accessibility options
+        let camera_config = &camera.kind.config().camera;
+        let base = settings.graphics.fov_degrees_or(camera_config.fov_degrees);
+        let dynamic = settings.graphics.dynamic_fov_or(camera_config.dynamic_fov)
+            && !accessibility.reduce_camera_motion;
+        // An orthographic projection has no field of view
+        let current_fov = match &*projection {
+            Projection::Perspective(perspective) => perspective.fov,
+            Projection::Orthographic(_) => continue,
+        };
+        if dynamic {
+            // The fixed angle camera is far away from the player, so speeding up should not change its view
+            let target = if matches!(camera.kind, IngameCameraKind::FixedAngle(_)) {
+                0.
+            } else {
+                let speed = player_query
+                    .iter()
+                    .map(|velocity| velocity.linvel.split(camera.up()).horizontal.length())
+                    .fold(0., f32::max);
+                get_fov_boost_target(speed, camera_config)
+            };
+            let scale = (camera_config.dynamic_fov_smoothing * dt).min(1.);
+            fov.boost += (target - fov.boost) * scale;
+        } else {
+            // Players who turned it off should not see the view ease back
+            fov.boost = 0.;
+        }
+        let new_fov = get_fov(base, fov.boost, fov.zoom);
+        if (new_fov - current_fov).abs() > 1e-6 {
+            if let Projection::Perspective(perspective) = &mut *projection {
+                perspective.fov = new_fov;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn boost_grows_with_speed_up_to_the_max() {
+        let camera = config::Camera {
+            dynamic_fov_max_boost: 10.,
+            dynamic_fov_max_speed: 8.,
+            ..default()
+        };
+        assert_eq!(get_fov_boost_target(0., &camera), 0.);
+        assert_eq!(get_fov_boost_target(4., &camera), 5.);
+        assert_eq!(get_fov_boost_target(8., &camera), 10.);
+        assert_eq!(get_fov_boost_target(100., &camera), 10.);
+
+        let without_max_speed = config::Camera {
+            dynamic_fov_max_speed: 0.,
+            ..camera
+        };
+        assert_eq!(get_fov_boost_target(4., &without_max_speed), 0.);
+    }
+
+    #[test]
+    fn zoom_scales_the_boosted_fov() {
+        assert!((get_fov(60., 0., 1.) - 60_f32.to_radians()).abs() < 1e-6);
+        assert!((get_fov(60., 20., 1.) - 80_f32.to_radians()).abs() < 1e-6);
+        assert!((get_fov(60., 20., 0.5) - 40_f32.to_radians()).abs() < 1e-6);
+        assert!((get_fov(60., 0., 0.5) - 30_f32.to_radians()).abs() < 1e-6);
+        assert_eq!(get_fov(160., 20., 1.), MAX_FOV_DEGREES.to_radians());
+        assert_eq!(get_fov(60., 0., 0.), MIN_FOV_DEGREES.to_radians());
+    }
+}
@@ -1,12 +1,9 @@
 use crate::file_system_interaction::config::GameConfig;
-use crate::player_control::actions::CameraAction;
 use crate::player_control::camera::util::clamp_pitch;
-use crate::player_control::camera::{FirstPersonCamera, FixedAngleCamera};
-use crate::util::trait_extension::{Vec2Ext, Vec3Ext};
-use anyhow::{Context, Result};
+use crate::player_control::camera::{CameraInput, FirstPersonCamera, FixedAngleCamera};
+use crate::util::trait_extension::{TransformExt, Vec2Ext, Vec3Ext};
 use bevy::prelude::*;
 use bevy_rapier3d::prelude::*;
-use leafwing_input_manager::prelude::ActionState;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, PartialEq, Reflect, FromReflect, Serialize, Deserialize)]
@@ -17,7 +14,15 @@ pub struct ThirdPersonCamera {
     pub up: Vec3,
     pub secondary_target: Option<Vec3>,
     pub distance: f32,
+    /// Whether the target is currently underwater
+    pub underwater: bool,
+    /// Not saved, the live config is applied when the camera is restored
+    #[serde(skip)]
     pub config: GameConfig,
+    /// Result of the last line of sight check, kept for debugging
+    #[serde(skip)]
+    #[reflect(ignore)]
+    pub line_of_sight: Option<LineOfSightResult>,
 }
 
 impl Default for ThirdPersonCamera {
@@ -28,7 +33,9 @@ impl Default for ThirdPersonCamera {
             distance: 5.,
             target: default(),
             secondary_target: default(),
+            underwater: default(),
             config: default(),
+            line_of_sight: None,
         }
     }
 }
@@ -39,14 +46,19 @@ impl From<&FirstPersonCamera> for ThirdPersonCamera {
         let distance = first_person_camera.config.camera.third_person.min_distance;
         let eye = target - first_person_camera.forward() * distance;
         let up = first_person_camera.up;
-        let eye = Transform::from_translation(eye).looking_at(target, up);
+        let eye = Transform::from_translation(eye)
+            .try_looking_at(target, up)
+            // Looking straight up or down, where there is no rotation that keeps `up` up
+            .unwrap_or_else(|| first_person_camera.transform.with_translation(eye));
         Self {
             transform: eye,
             target,
             up,
             distance,
             secondary_target: first_person_camera.look_target,
+            underwater: default(),
             config: first_person_camera.config.clone(),
+            line_of_sight: None,
         }
     }
 }
@@ -65,7 +77,9 @@ impl From<&FixedAngleCamera> for ThirdPersonCamera {
             up: fixed_angle_camera.up,
             distance: fixed_angle_camera.distance,
             secondary_target: fixed_angle_camera.secondary_target,
+            underwater: default(),
             config: fixed_angle_camera.config.clone(),
+            line_of_sight: None,
         }
     }
 }
@@ -87,26 +101,35 @@ impl ThirdPersonCamera {
     pub fn update_transform(
         &mut self,
         dt: f32,
-        camera_actions: &ActionState<CameraAction>,
-        rapier_context: &RapierContext,
+        input: CameraInput,
+        raycaster: &impl CameraRaycaster,
         transform: Transform,
-    ) -> Result<Transform> {
+        snap: bool,
+    ) -> Transform {
+        if snap {
+            // The eye is still behind the old target, which would throw off the alignment that pivots around the new one
+            self.transform.translation = self.target - self.forward() * self.distance;
+        }
         if let Some(secondary_target) = self.secondary_target {
-            self.move_eye_to_align_target_with(secondary_target);
+            let smoothing = self.config.camera.third_person.alignment_smoothing;
+            let scale = (smoothing * dt).min(1.);
+            self.ease_eye_to_align_target_with(secondary_target, scale);
         }
 
-        let camera_movement = camera_actions
-            .axis_pair(CameraAction::Pan)
-            .context("Camera movement is not an axis pair")?
-            .xy();
-        if !camera_movement.is_approx_zero() {
-            self.handle_camera_controls(camera_movement);
+        if !input.pan.is_approx_zero() {
+            self.handle_camera_controls(input.pan);
         }
 
-        let zoom = camera_actions.clamped_value(CameraAction::Zoom);
-        self.zoom(zoom);
-        let los_correction = self.place_eye_in_valid_position(rapier_context);
-        Ok(self.get_camera_transform(dt, transform, los_correction))
+        self.zoom(input.zoom);
+        let los_correction = self.place_eye_in_valid_position(raycaster);
+        if snap {
+            return Transform {
+                translation: self.transform.translation,
+                rotation: self.get_framed_rotation(),
+                ..transform
+            };
+        }
+        self.get_camera_transform(dt, transform, los_correction)
     }
 
     fn handle_camera_controls(&mut self, camera_movement: Vec2) {
@@ -134,27 +157,42 @@ impl ThirdPersonCamera {
         self.distance = (self.distance - zoom).clamp(min_distance, max_distance);
     }
 
+    #[cfg(test)]
     fn move_eye_to_align_target_with(&mut self, secondary_target: Vec3) {
-        let target_to_secondary_target = (secondary_target - self.target).split(self.up).horizontal;
-        if target_to_secondary_target.is_approx_zero() {
-            return;
-        }
-        let target_to_secondary_target = target_to_secondary_target.normalize();
-        let eye_to_target = (self.target - self.transform.translation)
+        self.ease_eye_to_align_target_with(secondary_target, 1.);
+    }
+
+    /// Rotates the eye around the target so that the secondary target is behind it.
+    /// A `scale` of 1 aligns it completely, smaller values only part of the way, so that switching targets is smooth.
+    fn ease_eye_to_align_target_with(&mut self, secondary_target: Vec3, scale: f32) {
+        // Nothing to align with when either of them is straight above or below the target
+        let target_to_secondary_target = match (secondary_target - self.target)
+            .split(self.up)
+            .horizontal_direction()
+        {
+            Some(direction) => direction,
+            None => return,
+        };
+        let eye_to_target = match (self.target - self.transform.translation)
             .split(self.up)
-            .horizontal
-            .normalize();
+            .horizontal_direction()
+        {
+            Some(direction) => direction,
+            None => return,
+        };
         let rotation = Quat::from_rotation_arc(eye_to_target, target_to_secondary_target);
+        let rotation = Quat::IDENTITY.slerp(rotation, scale);
         let pivot = self.target;
         self.transform.rotate_around(pivot, rotation);
     }
 
     fn place_eye_in_valid_position(
         &mut self,
-        rapier_context: &RapierContext,
+        raycaster: &impl CameraRaycaster,
     ) -> LineOfSightCorrection {
-        let line_of_sight_result = self.keep_line_of_sight(rapier_context);
+        let line_of_sight_result = self.keep_line_of_sight(raycaster);
         self.transform.translation = line_of_sight_result.location;
+        self.line_of_sight = Some(line_of_sight_result);
         line_of_sight_result.correction
     }
 
@@ -183,52 +221,100 @@ impl ThirdPersonCamera {
 
         let rotation_smoothing = self.config.camera.first_person.rotation_smoothing;
         let scale = (rotation_smoothing * dt).min(1.);
-        transform.rotation = transform.rotation.slerp(self.transform.rotation, scale);
+        transform.rotation = transform.rotation.slerp(self.get_framed_rotation(), scale);
 
         transform
     }
 
-    pub fn keep_line_of_sight(&self, rapier_context: &RapierContext) -> LineOfSightResult {
-        let origin = self.target;
+    /// The rotation of the eye, tilted up so that the target appears `frame_offset_y` m lower on the screen.
+    /// Only the rendered camera is tilted, the eye keeps orbiting the target itself.
+    fn get_framed_rotation(&self) -> Quat {
+        let frame_offset = self.config.camera.third_person.frame_offset_y;
+        if frame_offset == 0. {
+            return self.transform.rotation;
+        }
+        // Closer than 1 m the camera is about to switch to first person, where a steep tilt would be disorienting
+        let angle = (frame_offset / self.distance.max(1.)).atan();
+        Quat::from_axis_angle(self.transform.local_x(), angle) * self.transform.rotation
+    }
+
+    pub fn keep_line_of_sight(&self, raycaster: &impl CameraRaycaster) -> LineOfSightResult {
+        let origin = self.get_line_of_sight_origin(raycaster);
         let direction = -self.forward();
 
-        let distance = self.get_raycast_distance(origin, direction, rapier_context);
+        let hit_distance = raycaster.cast(origin, direction, self.distance);
+        let distance = self.get_distance_before_hit(hit_distance);
         let location = origin + direction * distance;
 
-        let original_distance = self.target - self.transform.translation;
+        let original_distance = origin - self.transform.translation;
         let correction = if distance * distance < original_distance.length_squared() - 1e-3 {
             LineOfSightCorrection::Closer
         } else {
             LineOfSightCorrection::Further
         };
         LineOfSightResult {
+            origin,
             location,
+            hit: hit_distance.map(|distance| origin + direction * distance),
             correction,
         }
     }
 
-    pub fn get_raycast_distance(
-        &self,
-        origin: Vec3,
-        direction: Vec3,
-        rapier_context: &RapierContext,
-    ) -> f32 {
-        let max_toi = self.distance;
+    /// The target, which is raised by `target_height_offset` above the player.
+    /// Under a ceiling that is lower than that, it is lowered again so that the line of sight does not start inside the ceiling.
+    fn get_line_of_sight_origin(&self, raycaster: &impl CameraRaycaster) -> Vec3 {
+        let height_offset = self.config.camera.third_person.target_height_offset;
+        if height_offset <= 0. {
+            return self.target;
+        }
+        let player = self.target - self.up * height_offset;
+        match raycaster.cast(player, self.up, height_offset) {
+            Some(toi) => player + self.up * self.get_distance_before_hit(Some(toi)),
+            None => self.target,
+        }
+    }
+
+    fn get_distance_before_hit(&self, hit_distance: Option<f32>) -> f32 {
+        let min_distance_to_objects = if self.underwater {
+            self.config
+                .camera
+                .third_person
+                .min_distance_to_objects_underwater
+        } else {
+            self.config.camera.third_person.min_distance_to_objects
+        };
+        // Never go below zero, otherwise a wall right behind the target (e.g. one the player is sliding on)
+        // would flip the camera through the target to the other side.
+        hit_distance
+            .map(|toi| (toi - min_distance_to_objects).max(0.))
+            .unwrap_or(self.distance)
+    }
+}
+
+/// Finds what blocks the line of sight of the camera, so that the camera logic does not depend on the physics directly
+pub trait CameraRaycaster {
+    /// Distance along `direction` to the first obstacle that is at most `max_toi` away from `origin`
+    fn cast(&self, origin: Vec3, direction: Vec3, max_toi: f32) -> Option<f32>;
+}
+
+impl CameraRaycaster for RapierContext {
+    /// Only level geometry blocks the camera, moving objects and sensors such as triggers are ignored
+    fn cast(&self, origin: Vec3, direction: Vec3, max_toi: f32) -> Option<f32> {
         let solid = true;
         let mut filter = QueryFilter::only_fixed();
         filter.flags |= QueryFilterFlags::EXCLUDE_SENSORS;
-
-        let min_distance_to_objects = self.config.camera.third_person.min_distance_to_objects;
-        rapier_context
-            .cast_ray(origin, direction, max_toi, solid, filter)
-            .map(|(_entity, toi)| toi - min_distance_to_objects)
-            .unwrap_or(max_toi)
+        self.cast_ray(origin, direction, max_toi, solid, filter)
+            .map(|(_entity, toi)| toi)
     }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct LineOfSightResult {
+    /// Where the line of sight starts, i.e. the target unless it is below a low ceiling
+    pub origin: Vec3,
     pub location: Vec3,
+    /// Where the line of sight hit the level, if it did
+    pub hit: Option<Vec3>,
     pub correction: LineOfSightCorrection,
 }
 
@@ -241,6 +327,9 @@ pub enum LineOfSightCorrection {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::player_control::camera::{CameraForceSnap, IngameCamera};
+    use crate::util::headless::{build_headless_app, HeadlessApp};
+    use crate::util::test_util::assert_nearly_eq;
 
     #[test]
     fn facing_secondary_target_that_is_primary_changes_nothing() {
@@ -292,6 +381,251 @@ mod test {
         assert_nearly_eq(camera.transform.translation, expected_position);
     }
 
+    #[test]
+    fn eases_towards_secondary_target() {
+        let camera_translation = Vec3::new(2., 0., 0.);
+        let primary_target = Vec3::new(-2., 0., 0.);
+        let secondary_target = Vec3::new(-2., 0., -2.);
+
+        let mut camera = build_camera(camera_translation, primary_target);
+        camera.ease_eye_to_align_target_with(secondary_target, 0.5);
+
+        let offset = 4. * std::f32::consts::FRAC_1_SQRT_2;
+        let expected_position = primary_target + Vec3::new(offset, 0., offset);
+        assert_nearly_eq(camera.transform.translation, expected_position);
+    }
+
+    #[test]
+    fn camera_moves_in_front_of_wall_between_it_and_player() {
+        let mut app = build_headless_app();
+        let (_player, camera) = app.spawn_player_on_floor();
+        let eye = app.translation(camera);
+        assert!(eye.z > 4., "camera did not stay behind the player: {eye}");
+
+        // The side of the wall facing the player is at z = 2.4
+        app.spawn_fixed_box(Vec3::new(0., 1., 2.5), Vec3::new(5., 5., 0.1));
+        app.step(60);
+
+        let eye = app.translation(camera);
+        assert!(eye.z < 2.4, "camera is stuck behind the wall: {eye}");
+        assert!(eye.z > 0., "camera moved past the player: {eye}");
+    }
+
+    #[test]
+    fn camera_snaps_to_target_teleported_far_away() {
+        let mut app = build_headless_app();
+        let (player, camera) = app.spawn_player_on_floor();
+        app.spawn_fixed_box(Vec3::new(500., -0.5, 0.), Vec3::new(50., 0.5, 50.));
+
+        app.world.get_mut::<Transform>(player).unwrap().translation += Vec3::X * 500.;
+        app.step(1);
+
+        assert_camera_at_ideal_pose(&app, camera);
+        let eye = app.translation(camera);
+        assert!(
+            eye.distance(app.translation(player)) < 10.,
+            "camera did not follow the player: {eye}"
+        );
+    }
+
+    #[test]
+    fn forced_snap_skips_smoothing() {
+        let mut app = build_headless_app();
+        let (player, camera) = app.spawn_player_on_floor();
+
+        // Too close to be detected as a teleport
+        app.world.get_mut::<Transform>(player).unwrap().translation += Vec3::X * 5.;
+        app.world.send_event(CameraForceSnap);
+        app.step(1);
+
+        assert_camera_at_ideal_pose(&app, camera);
+    }
+
+    /// Within what the camera moves in one frame while following a player that walks normally
+    fn assert_camera_at_ideal_pose(app: &App, camera: Entity) {
+        let rendered = app.world.get::<Transform>(camera).unwrap();
+        let ideal = app
+            .world
+            .get::<IngameCamera>(camera)
+            .unwrap()
+            .kind
+            .transform();
+        let offset = rendered.translation.distance(ideal.translation);
+        assert!(
+            offset < 0.1,
+            "camera is {offset} m away from its ideal pose"
+        );
+        let angle = rendered.rotation.angle_between(ideal.rotation);
+        assert!(
+            angle < 1e-2,
+            "camera is turned {angle} rad away from its ideal pose"
+        );
+    }
+
+    #[test]
+    fn line_of_sight_blocked_right_behind_target_keeps_eye_at_target() {
+        let camera_translation = Vec3::new(0., 0., 5.);
+        let primary_target = Vec3::ZERO;
+        let camera = build_camera(camera_translation, primary_target);
+
+        // Closer to the target than the minimum distance to objects
+        let result = camera.keep_line_of_sight(&ScriptedRaycaster(Some(0.2)));
+
+        assert_nearly_eq(result.location, primary_target);
+        assert_eq!(result.correction, LineOfSightCorrection::Closer);
+    }
+
+    #[test]
+    fn unobstructed_line_of_sight_is_further() {
+        let camera = build_camera(Vec3::new(0., 0., 5.), Vec3::ZERO);
+
+        let result = camera.keep_line_of_sight(&ScriptedRaycaster(None));
+
+        assert_nearly_eq(result.location, Vec3::new(0., 0., 5.));
+        assert_eq!(result.hit, None);
+        assert_eq!(result.correction, LineOfSightCorrection::Further);
+    }
+
+    #[test]
+    fn line_of_sight_keeping_current_distance_is_further() {
+        let mut camera = build_camera(Vec3::new(0., 0., 5.), Vec3::ZERO);
+        // Zoomed out further than the eye currently is, so that the hit is in range
+        camera.distance = 6.;
+        let min_distance_to_objects = camera.config.camera.third_person.min_distance_to_objects;
+
+        let result =
+            camera.keep_line_of_sight(&ScriptedRaycaster(Some(5. + min_distance_to_objects)));
+
+        assert_nearly_eq(result.location, Vec3::new(0., 0., 5.));
+        assert_eq!(result.correction, LineOfSightCorrection::Further);
+    }
+
+    #[test]
+    fn line_of_sight_slightly_shorter_than_current_distance_is_closer() {
+        let mut camera = build_camera(Vec3::new(0., 0., 5.), Vec3::ZERO);
+        // Zoomed out further than the eye currently is, so that the hit is in range
+        camera.distance = 6.;
+        let min_distance_to_objects = camera.config.camera.third_person.min_distance_to_objects;
+
+        let result =
+            camera.keep_line_of_sight(&ScriptedRaycaster(Some(4.99 + min_distance_to_objects)));
+
+        assert_nearly_eq(result.location, Vec3::new(0., 0., 4.99));
+        assert_eq!(result.correction, LineOfSightCorrection::Closer);
+    }
+
+    #[test]
+    fn line_of_sight_from_eye_closer_than_desired_distance_is_further() {
+        let mut camera = build_camera(Vec3::new(0., 0., 2.), Vec3::ZERO);
+        camera.distance = 5.;
+
+        let result = camera.keep_line_of_sight(&ScriptedRaycaster(None));
+
+        assert_nearly_eq(result.location, Vec3::new(0., 0., 5.));
+        assert_eq!(result.correction, LineOfSightCorrection::Further);
+    }
+
+    #[test]
+    fn placing_eye_never_moves_it_past_target() {
+        let camera_translation = Vec3::new(3., 2., 4.);
+        let primary_target = Vec3::new(0., 1., 0.);
+        let behind_target = (camera_translation - primary_target).normalize();
+        for underwater in [false, true] {
+            for hit in [None, Some(0.), Some(0.05), Some(0.3), Some(1.), Some(10.)] {
+                let mut camera = build_camera(camera_translation, primary_target);
+                camera.underwater = underwater;
+                camera.place_eye_in_valid_position(&ScriptedRaycaster(hit));
+
+                let target_to_eye = camera.transform.translation - primary_target;
+                assert!(
+                    target_to_eye.dot(behind_target) >= -1e-5,
+                    "eye moved past the target with hit {hit:?}: {}",
+                    camera.transform.translation
+                );
+                assert!(target_to_eye.length() <= camera.distance + 1e-5);
+            }
+        }
+    }
+
+    #[test]
+    fn line_of_sight_starts_below_low_ceiling() {
+        let mut camera = build_camera(Vec3::new(0., 1.5, 5.), Vec3::new(0., 1.5, 0.));
+        camera.config.camera.third_person.target_height_offset = 1.5;
+        camera.distance = 6.;
+        let min_distance_to_objects = camera.config.camera.third_person.min_distance_to_objects;
+
+        // The ceiling is 1 m above the player, who stands at the origin
+        let result = camera.keep_line_of_sight(&ScriptedRaycaster(Some(1.)));
+
+        assert_nearly_eq(
+            result.origin,
+            Vec3::new(0., 1. - min_distance_to_objects, 0.),
+        );
+        let unobstructed = camera.keep_line_of_sight(&ScriptedRaycaster(None));
+        assert_nearly_eq(unobstructed.origin, camera.target);
+    }
+
+    #[test]
+    fn aligning_with_secondary_target_ignores_height_offset() {
+        let camera_translation = Vec3::new(2., 1.5, 0.);
+        let secondary_target = Vec3::new(-2., 0., -2.);
+        let mut camera = build_camera(camera_translation, Vec3::new(-2., 0., 0.));
+        let mut raised_camera = build_camera(camera_translation, Vec3::new(-2., 1.5, 0.));
+        raised_camera
+            .config
+            .camera
+            .third_person
+            .target_height_offset = 1.5;
+
+        camera.move_eye_to_align_target_with(secondary_target);
+        raised_camera.move_eye_to_align_target_with(secondary_target);
+
+        let heading = (camera.target - camera.transform.translation).split(Vec3::Y);
+        let raised_heading =
+            (raised_camera.target - raised_camera.transform.translation).split(Vec3::Y);
+        assert_nearly_eq(
+            heading.horizontal.normalize(),
+            raised_heading.horizontal.normalize(),
+        );
+    }
+
+    #[test]
+    fn aligning_from_straight_above_keeps_transform_finite() {
+        let mut camera = ThirdPersonCamera::default();
+        camera.transform = Transform::from_xyz(0., 5., 0.)
+            .with_rotation(Quat::from_rotation_x(-std::f32::consts::FRAC_PI_2));
+        camera.distance = 5.;
+        let transform = camera.transform;
+
+        camera.move_eye_to_align_target_with(Vec3::new(1., 0., 1.));
+        assert_eq!(camera.transform, transform);
+        // The secondary target is straight below the target
+        camera.transform = Transform::from_xyz(0., 1., 5.).looking_at(Vec3::ZERO, Vec3::Y);
+        camera.move_eye_to_align_target_with(Vec3::new(0., -3., 0.));
+        assert!(camera.transform.translation.is_finite());
+        assert!(camera.transform.rotation.is_finite());
+    }
+
+    #[test]
+    fn frame_offset_tilts_camera_up() {
+        let mut camera = build_camera(Vec3::new(0., 0., 5.), Vec3::ZERO);
+        assert_eq!(camera.get_framed_rotation(), camera.transform.rotation);
+
+        camera.config.camera.third_person.frame_offset_y = 1.;
+        let forward = camera.get_framed_rotation() * Vec3::NEG_Z;
+        assert!((forward.angle_between(Vec3::NEG_Z) - 0.2_f32.atan()).abs() < 1e-5);
+        assert!(forward.y > 0.);
+    }
+
+    /// Reports the same hit for every ray, as long as it is in range
+    struct ScriptedRaycaster(Option<f32>);
+
+    impl CameraRaycaster for ScriptedRaycaster {
+        fn cast(&self, _origin: Vec3, _direction: Vec3, max_toi: f32) -> Option<f32> {
+            self.0.filter(|toi| *toi <= max_toi)
+        }
+    }
+
     fn build_camera(camera_translation: Vec3, primary_target: Vec3) -> ThirdPersonCamera {
         let mut camera = ThirdPersonCamera::default();
         let camera_transform = Transform::from_translation(camera_translation);
@@ -302,13 +636,4 @@ mod test {
 
         camera
     }
-
-    fn assert_nearly_eq(actual: Vec3, expected: Vec3) {
-        assert!(
-            (actual - expected).length_squared() < 1e-5,
-            "expected: {:?}, actual: {:?}",
-            expected,
-            actual
-        );
-    }
 }
@@ -0,0 +1,268 @@
+use crate::file_system_interaction::asset_loading::ConfigAssets;
+use crate::file_system_interaction::config::GameConfig;
+use crate::file_system_interaction::level_serialization::WorldLoadRequest;
+use crate::movement::general_movement::{
+    apply_kinematic_movement, apply_walking, reset_movement_components,
+};
+use crate::player_control::actions::PlayerAction;
+use crate::player_control::camera::{IngameCamera, UpdateCameraTransformLabel};
+use crate::player_control::player_embodiment::Player;
+use crate::shader::Materials;
+use crate::util::log_error::log_errors;
+use crate::GameState;
+use anyhow::{Context, Result};
+use bevy::pbr::NotShadowCaster;
+use bevy::prelude::*;
+use bevy::utils::HashSet;
+use bevy_rapier3d::prelude::*;
+use leafwing_input_manager::prelude::ActionState;
+use serde::{Deserialize, Serialize};
+
+/// Handles the player's grappling hook. While [`PlayerAction::Grapple`] is held, the player is pulled towards
+/// the fixed collider in the center of the screen and swings around it like on a rope.
+/// A beam is rendered between the player and the anchor for as long as the grapple is attached.
+pub struct GrapplePlugin;
+
+impl Plugin for GrapplePlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Grapple>()
+            .register_type::<GrappleBeam>()
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(
+                        handle_grapple
+                            .pipe(log_errors)
+                            .after(UpdateCameraTransformLabel)
+                            .after(reset_movement_components)
+                            .before(apply_walking)
+                            .before(apply_kinematic_movement),
+                    )
+                    .with_system(update_grapple_beams.after(handle_grapple))
+                    .with_system(release_grapples_on_level_change.before(handle_grapple)),
+            );
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Component, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Component, Serialize, Deserialize)]
+pub struct Grapple {
+    /// Entity the grapple is attached to
+    pub anchor: Option<Entity>,
+    /// Attachment point in the local space of [`Grapple::anchor`]
+    pub local_anchor_point: Vec3,
+    /// Attachment point in world space, updated every tick
+    pub anchor_point: Vec3,
+    /// Current length of the rope in m. The rope only ever gets shorter while attached.
+    pub rope_length: f32,
+}
+
+impl Grapple {
+    /// Attachment point in world space, if the grapple is attached
+    pub fn get_anchor_point(&self) -> Option<Vec3> {
+        self.anchor.map(|_| self.anchor_point)
+    }
+
+    pub fn release(&mut self) {
+        self.anchor = None;
+    }
+}
+
+/// The visual connection between a grappling player and their anchor.
+#[derive(Debug, Clone, PartialEq, Eq, Component, Reflect, Serialize, Deserialize)]
+#[reflect(Component, Serialize, Deserialize)]
+pub struct GrappleBeam {
+    pub owner: Entity,
+}
+
+impl Default for GrappleBeam {
+    fn default() -> Self {
+        Self {
+            owner: Entity::from_raw(u32::MAX),
+        }
+    }
+}
+
+fn handle_grapple(
+    mut player_query: Query<
+        (
+            &ActionState<PlayerAction>,
+            &Transform,
+            &mut Grapple,
+            &mut ExternalForce,
+            &mut Velocity,
+            &ReadMassProperties,
+        ),
+        With<Player>,
+    >,
+    camera_query: Query<&Transform, (With<IngameCamera>, Without<Player>)>,
+    anchor_query: Query<&GlobalTransform>,
+    rapier_context: Res<RapierContext>,
+    config_handles: Res<ConfigAssets>,
+    config: Res<Assets<GameConfig>>,
+) -> Result<()> {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("handle_grapple").entered();
+    let camera_transform = match camera_query.iter().next() {
+        Some(camera_transform) => camera_transform,
+        None => return Ok(()),
+    };
+    let config = config
+        .get(&config_handles.game)
+        .context("Failed to get game config from handle")?;
+    for (actions, transform, mut grapple, mut force, mut velocity, mass) in &mut player_query {
+        if !actions.pressed(PlayerAction::Grapple) {
+            grapple.release();
+            continue;
+        }
+        if grapple.anchor.is_none() && actions.just_pressed(PlayerAction::Grapple) {
+            if let Some((anchor, point)) = find_grapple_anchor(
+                camera_transform,
+                transform.translation,
+                config.player.grapple_range,
+                &rapier_context,
+            ) {
+                let anchor_transform = anchor_query
+                    .get(anchor)
+                    .context("Failed to get global transform of grapple anchor")?;
+                grapple.anchor = Some(anchor);
+                grapple.local_anchor_point =
+                    anchor_transform.affine().inverse().transform_point3(point);
+                grapple.rope_length = transform.translation.distance(point);
+            }
+        }
+        let anchor_transform = match grapple
+            .anchor
+            .and_then(|anchor| anchor_query.get(anchor).ok())
+        {
+            Some(anchor_transform) => anchor_transform,
+            None => {
+                // Also covers the anchor being despawned
+                grapple.release();
+                continue;
+            }
+        };
+        grapple.anchor_point = anchor_transform.transform_point(grapple.local_anchor_point);
+
+        let to_anchor = grapple.anchor_point - transform.translation;
+        let distance = to_anchor.length();
+        let direction = to_anchor.normalize_or_zero();
+        grapple.rope_length = grapple.rope_length.min(distance);
+        force.force += direction * config.player.grapple_pull_strength * mass.0.mass;
+
+        // Act like a rope: we can swing around the anchor but not move away from it
+        let radial_speed = velocity.linvel.dot(direction);
+        if distance >= grapple.rope_length - 1e-2 && radial_speed < 0. {
+            velocity.linvel -= direction * radial_speed;
+        }
+        velocity.linvel = velocity
+            .linvel
+            .clamp_length_max(config.player.grapple_max_swing_speed);
+    }
+    Ok(())
+}
+
+/// Shoots a ray through the center of the screen. The ray starts at the point closest to the player
+/// so that nothing between the camera and the player can be grappled, e.g. in third person.
+fn find_grapple_anchor(
+    camera_transform: &Transform,
+    player_translation: Vec3,
+    range: f32,
+    rapier_context: &RapierContext,
+) -> Option<(Entity, Vec3)> {
+    let direction = camera_transform.forward();
+    let distance_to_player = (player_translation - camera_transform.translation)
+        .dot(direction)
+        .max(0.);
+    let origin = camera_transform.translation + direction * distance_to_player;
+    let filter = QueryFilter::only_fixed().exclude_sensors();
+    rapier_context
+        .cast_ray(origin, direction, range, true, filter)
+        .map(|(entity, toi)| (entity, origin + direction * toi))
+        .filter(|(_entity, point)| point.distance(player_translation) <= range)
+}
+
+fn update_grapple_beams(
+    mut commands: Commands,
+    player_query: Query<(Entity, &Transform, &Grapple), With<Player>>,
+    mut beam_query: Query<(Entity, &GrappleBeam, &mut Transform), Without<Player>>,
+    materials: Res<Materials>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("update_grapple_beams").entered();
+    let mut owners_with_beam = HashSet::new();
+    for (beam_entity, beam, mut beam_transform) in &mut beam_query {
+        let anchored_player =
+            player_query
+                .get(beam.owner)
+                .ok()
+                .and_then(|(_entity, transform, grapple)| {
+                    grapple
+                        .get_anchor_point()
+                        .map(|anchor_point| (transform, anchor_point))
+                });
+        match anchored_player {
+            Some((player_transform, anchor_point)) => {
+                *beam_transform = get_beam_transform(player_transform, anchor_point);
+                owners_with_beam.insert(beam.owner);
+            }
+            None => {
+                commands.entity(beam_entity).despawn_recursive();
+            }
+        }
+    }
+
+    for (player_entity, player_transform, grapple) in &player_query {
+        let anchor_point = match grapple.get_anchor_point() {
+            Some(anchor_point) => anchor_point,
+            None => continue,
+        };
+        if owners_with_beam.contains(&player_entity) {
+            continue;
+        }
+        commands.spawn((
+            MaterialMeshBundle {
+                mesh: meshes.add(Mesh::from(shape::Box::new(0.03, 0.03, 1.))),
+                material: materials.glowy.clone(),
+                transform: get_beam_transform(player_transform, anchor_point),
+                ..default()
+            },
+            GrappleBeam {
+                owner: player_entity,
+            },
+            NotShadowCaster,
+            Name::new("Grapple Beam"),
+        ));
+    }
+}
+
+/// Transform that stretches a box of length 1 along its z axis from the player's hand to the anchor
+fn get_beam_transform(player_transform: &Transform, anchor_point: Vec3) -> Transform {
+    const HAND_HEIGHT: f32 = 0.3;
+    let hand = player_transform.translation + player_transform.up() * HAND_HEIGHT;
+    let length = hand.distance(anchor_point);
+    Transform::from_translation(hand.lerp(anchor_point, 0.5))
+        .looking_at(anchor_point, player_transform.up())
+        .with_scale(Vec3::new(1., 1., length))
+}
+
+/// The beam is not part of the level or the save files, so it has to be cleaned up by hand when the world is swapped out
+fn release_grapples_on_level_change(
+    mut commands: Commands,
+    mut load_requests: EventReader<WorldLoadRequest>,
+    mut grapple_query: Query<&mut Grapple>,
+    beam_query: Query<Entity, With<GrappleBeam>>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("release_grapples_on_level_change").entered();
+    if load_requests.is_empty() {
+        return;
+    }
+    load_requests.clear();
+    for mut grapple in &mut grapple_query {
+        grapple.release();
+    }
+    for beam in &beam_query {
+        commands.entity(beam).despawn_recursive();
+    }
+}
@@ -1,14 +1,21 @@
+use crate::file_system_interaction::asset_loading::{AudioAssets, ConfigAssets};
 use crate::file_system_interaction::audio::AudioHandles;
+use crate::file_system_interaction::config::{CharacterControllerBackend, GameConfig};
+use crate::movement::climbing::{apply_climbing, Climbing};
+use crate::movement::dash::{apply_dashing, Dashing};
 use crate::movement::general_movement::{
-    apply_jumping, apply_walking, reset_movement_components, Grounded, Jumping, Walking,
+    apply_jumping, apply_kinematic_movement, apply_walking, reset_movement_components,
+    update_landing, AirJumps, BaseGravityScale, Grounded, Jumping, KinematicMovement, Landing,
+    Walking,
 };
+use crate::movement::water::{apply_swimming, update_swimming, Swimming};
 use crate::player_control::actions::{DualAxisDataExt, PlayerAction};
 use crate::player_control::camera::{
     focus::switch_kind as switch_camera_kind, IngameCamera, IngameCameraKind,
     UpdateCameraTransformLabel,
 };
 use crate::util::log_error::log_errors;
-use crate::util::trait_extension::{F32Ext, TransformExt, Vec3Ext};
+use crate::util::trait_extension::{TransformExt, Vec3Ext};
 use crate::world_interaction::dialog::CurrentDialog;
 use crate::GameState;
 use anyhow::{Context, Result};
@@ -17,7 +24,6 @@ use bevy_kira_audio::AudioInstance;
 use bevy_rapier3d::prelude::*;
 use leafwing_input_manager::prelude::ActionState;
 use serde::{Deserialize, Serialize};
-use std::ops::DerefMut;
 
 pub struct PlayerEmbodimentPlugin;
 
@@ -27,11 +33,17 @@ impl Plugin for PlayerEmbodimentPlugin {
     fn build(&self, app: &mut App) {
         app.register_type::<Timer>()
             .register_type::<Player>()
+            .register_type::<WallContact>()
+            .add_event::<PlayerJumped>()
+            .add_event::<PlayerDashed>()
+            .add_event::<PlayerLanded>()
             .add_system_set(
                 SystemSet::on_update(GameState::Playing)
                     .with_system(
                         handle_jump
+                            .pipe(log_errors)
                             .after(reset_movement_components)
+                            .before(apply_swimming)
                             .before(apply_jumping),
                     )
                     .with_system(
@@ -39,14 +51,50 @@ impl Plugin for PlayerEmbodimentPlugin {
                             .pipe(log_errors)
                             .after(UpdateCameraTransformLabel)
                             .after(reset_movement_components)
+                            .after(update_swimming)
+                            .before(apply_swimming)
                             .before(apply_walking),
                     )
+                    .with_system(
+                        handle_dash
+                            .pipe(log_errors)
+                            .after(reset_movement_components)
+                            .after(handle_horizontal_movement)
+                            .before(apply_dashing),
+                    )
+                    .with_system(
+                        handle_climbing
+                            .pipe(log_errors)
+                            .after(handle_jump)
+                            .after(handle_horizontal_movement)
+                            .before(apply_climbing),
+                    )
+                    .with_system(
+                        handle_wall_interaction
+                            .pipe(log_errors)
+                            .after(handle_jump)
+                            .after(handle_horizontal_movement)
+                            .after(apply_climbing)
+                            .before(apply_swimming)
+                            .before(apply_jumping)
+                            .before(apply_walking),
+                    )
+                    .with_system(
+                        handle_controller_backend
+                            .pipe(log_errors)
+                            .before(apply_kinematic_movement),
+                    )
                     .with_system(
                         handle_camera_kind
                             .after(switch_camera_kind)
                             .before(apply_walking),
                     )
-                    .with_system(handle_speed_effects)
+                    .with_system(
+                        handle_landing
+                            .pipe(log_errors)
+                            .after(update_landing)
+                            .before(apply_walking),
+                    )
                     .with_system(rotate_to_speaker)
                     .with_system(control_walking_sound.pipe(log_errors)),
             );
@@ -57,17 +105,87 @@ impl Plugin for PlayerEmbodimentPlugin {
 #[reflect(Component, Serialize, Deserialize)]
 pub struct Player;
 
-fn handle_jump(mut player_query: Query<(&ActionState<PlayerAction>, &mut Jumping), With<Player>>) {
+/// Tracks the wall the player is currently sliding on, if any.
+#[derive(Debug, Clone, PartialEq, Component, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Component, Serialize, Deserialize)]
+pub struct WallContact {
+    /// Normal of the wall, pointing away from it
+    pub normal: Option<Vec3>,
+    /// Remaining time in seconds during which horizontal input is ignored after a wall jump
+    pub input_lock: f32,
+}
+
+/// Sent whenever the player starts a dash. Useful for hooking up sounds or particles.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PlayerDashed {
+    /// Horizontal direction of the dash
+    pub direction: Vec3,
+}
+
+/// Sent whenever the player lands hard enough to warrant feedback, i.e. faster than the configured soft landing threshold.
+/// Useful for hooking up camera effects, sounds or particles.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PlayerLanded {
+    /// Downwards speed in m/s right before touching the ground
+    pub impact_speed: f32,
+}
+
+/// Sent whenever the player jumps. Useful for hooking up sounds or particles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlayerJumped {
+    /// Whether the jump was performed while airborne, i.e. consumed one of the [`AirJumps`]
+    pub air: bool,
+}
+
+fn handle_jump(
+    mut player_query: Query<
+        (
+            &ActionState<PlayerAction>,
+            &mut Jumping,
+            &mut AirJumps,
+            &BaseGravityScale,
+        ),
+        With<Player>,
+    >,
+    config_handles: Res<ConfigAssets>,
+    config: Res<Assets<GameConfig>>,
+    rapier_configuration: Res<RapierConfiguration>,
+) -> Result<()> {
     #[cfg(feature = "tracing")]
     let _span = info_span!("handle_jump").entered();
-    for (actions, mut jump) in &mut player_query {
-        jump.requested |= actions.pressed(PlayerAction::Jump);
+    let config = config
+        .get(&config_handles.game)
+        .context("Failed to get game config from handle")?;
+    for (actions, mut jump, mut air_jumps, base_gravity_scale) in &mut player_query {
+        // Not the current gravity scale, which is zero on a ladder
+        let gravity = rapier_configuration.gravity.length() * base_gravity_scale.0;
+        jump.air_speed = (2. * gravity * config.player.air_jump_height).sqrt();
+        jump.coyote_time = config.player.coyote_time;
+        if air_jumps.max != config.player.extra_jumps {
+            air_jumps.max = config.player.extra_jumps;
+            air_jumps.remaining = air_jumps.remaining.min(air_jumps.max);
+        }
+        let pressed = actions.pressed(PlayerAction::Jump);
+        jump.consumed &= pressed;
+        jump.requested |= pressed && !jump.consumed;
     }
+    Ok(())
 }
 
 fn handle_horizontal_movement(
-    mut player_query: Query<(&ActionState<PlayerAction>, &mut Walking, &Transform), With<Player>>,
+    mut player_query: Query<
+        (
+            &ActionState<PlayerAction>,
+            &mut Walking,
+            &Transform,
+            &WallContact,
+            &Swimming,
+        ),
+        With<Player>,
+    >,
     camera_query: Query<&IngameCamera>,
+    config_handles: Res<ConfigAssets>,
+    config: Res<Assets<GameConfig>>,
 ) -> Result<()> {
     #[cfg(feature = "tracing")]
     let _span = info_span!("handle_horizontal_movement").entered();
@@ -75,19 +193,34 @@ fn handle_horizontal_movement(
         Some(camera) => camera,
         None => return Ok(()),
     };
+    let config = config
+        .get(&config_handles.game)
+        .context("Failed to get game config from handle")?;
 
-    for (actions, mut walk, transform) in &mut player_query {
+    for (actions, mut walk, transform, wall_contact, swimming) in &mut player_query {
+        walk.max_walkable_slope = config.player.max_walkable_slope_degrees.to_radians();
+        walk.slide_acceleration = config.player.slope_slide_acceleration;
+        if wall_contact.input_lock > 0. {
+            continue;
+        }
         if let Some(movement) = actions
             .axis_pair(PlayerAction::Move)
             .context("Player movement is not an axis pair")?
             .max_normalized()
         {
-            let forward = camera
-                .forward()
-                .split(transform.up())
-                .horizontal
-                .normalize();
-            let sideways = forward.cross(transform.up());
+            let up = transform.up();
+            let forward = if swimming.is_swimming() {
+                // Swimmers can move freely in all directions, so include the camera's pitch
+                camera.forward()
+            } else {
+                // Looking straight down has no horizontal direction, so walk where the player is facing
+                camera
+                    .forward()
+                    .split(up)
+                    .horizontal_direction()
+                    .unwrap_or_else(|| transform.forward())
+            };
+            let sideways = forward.cross(up).normalize_or_zero();
             let forward_action = forward * movement.y;
             let sideways_action = sideways * movement.x;
 
@@ -107,6 +240,248 @@ fn handle_horizontal_movement(
     Ok(())
 }
 
+fn handle_dash(
+    mut player_query: Query<
+        (
+            &ActionState<PlayerAction>,
+            &mut Dashing,
+            &Swimming,
+            &Climbing,
+        ),
+        With<Player>,
+    >,
+    config_handles: Res<ConfigAssets>,
+    config: Res<Assets<GameConfig>>,
+) -> Result<()> {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("handle_dash").entered();
+    let config = config
+        .get(&config_handles.game)
+        .context("Failed to get game config from handle")?;
+    for (actions, mut dashing, swimming, climbing) in &mut player_query {
+        dashing.speed = config.player.dash_speed;
+        dashing.duration = config.player.dash_duration;
+        dashing.cooldown = config.player.dash_cooldown;
+        dashing.gravity_scale = config.player.dash_gravity_scale;
+        dashing.requested = actions.just_pressed(PlayerAction::Dash)
+            && !swimming.is_swimming()
+            && !climbing.is_climbing();
+    }
+    Ok(())
+}
+
+fn handle_controller_backend(
+    mut commands: Commands,
+    mut player_query: Query<
+        (
+            Entity,
+            &Transform,
+            &Walking,
+            Option<&mut KinematicCharacterController>,
+        ),
+        With<Player>,
+    >,
+    config_handles: Res<ConfigAssets>,
+    config: Res<Assets<GameConfig>>,
+) -> Result<()> {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("handle_controller_backend").entered();
+    let config = config
+        .get(&config_handles.game)
+        .context("Failed to get game config from handle")?;
+    for (entity, transform, walking, controller) in &mut player_query {
+        match (config.player.controller, controller) {
+            (CharacterControllerBackend::Kinematic, Some(mut controller)) => {
+                configure_kinematic_controller(&mut controller, config, walking, transform.up());
+            }
+            (CharacterControllerBackend::Kinematic, None) => {
+                let mut controller = KinematicCharacterController::default();
+                configure_kinematic_controller(&mut controller, config, walking, transform.up());
+                commands.entity(entity).insert((
+                    controller,
+                    KinematicMovement::default(),
+                    RigidBody::KinematicPositionBased,
+                    // Kinematic bodies don't interact with fixed sensors like water volumes by default
+                    ActiveCollisionTypes::default() | ActiveCollisionTypes::KINEMATIC_FIXED,
+                ));
+            }
+            (CharacterControllerBackend::Dynamic, Some(_)) => {
+                commands
+                    .entity(entity)
+                    .remove::<(
+                        KinematicCharacterController,
+                        KinematicCharacterControllerOutput,
+                        KinematicMovement,
+                        ActiveCollisionTypes,
+                    )>()
+                    .insert(RigidBody::Dynamic);
+            }
+            (CharacterControllerBackend::Dynamic, None) => {}
+        }
+    }
+    Ok(())
+}
+
+fn configure_kinematic_controller(
+    controller: &mut KinematicCharacterController,
+    config: &GameConfig,
+    walking: &Walking,
+    up: Vec3,
+) {
+    controller.up = up;
+    controller.autostep = Some(CharacterAutostep {
+        max_height: CharacterLength::Absolute(config.player.autostep_max_height),
+        min_width: CharacterLength::Absolute(config.player.autostep_min_width),
+        include_dynamic_bodies: true,
+    });
+    controller.snap_to_ground = Some(CharacterLength::Absolute(
+        config.player.snap_to_ground_distance,
+    ));
+    // Sliding down steep slopes is handled by the general movement, just like for dynamic characters
+    controller.max_slope_climb_angle = walking.max_walkable_slope;
+    controller.min_slope_slide_angle = walking.max_walkable_slope;
+}
+
+fn handle_climbing(
+    mut player_query: Query<(&ActionState<PlayerAction>, &mut Climbing), With<Player>>,
+    config_handles: Res<ConfigAssets>,
+    config: Res<Assets<GameConfig>>,
+) -> Result<()> {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("handle_climbing").entered();
+    let config = config
+        .get(&config_handles.game)
+        .context("Failed to get game config from handle")?;
+    for (actions, mut climbing) in &mut player_query {
+        climbing.speed = config.player.climb_speed;
+        climbing.requested = actions
+            .axis_pair(PlayerAction::Move)
+            .context("Player movement is not an axis pair")?
+            .max_normalized()
+            .map(|movement| movement.y)
+            .unwrap_or_default();
+    }
+    Ok(())
+}
+
+fn handle_wall_interaction(
+    time: Res<Time>,
+    mut player_query: Query<
+        (
+            &ActionState<PlayerAction>,
+            &Transform,
+            &Collider,
+            &Grounded,
+            &Walking,
+            &ReadMassProperties,
+            &mut Jumping,
+            &mut Velocity,
+            &mut ExternalImpulse,
+            &mut WallContact,
+            &Swimming,
+        ),
+        With<Player>,
+    >,
+    rapier_context: Res<RapierContext>,
+    config_handles: Res<ConfigAssets>,
+    config: Res<Assets<GameConfig>>,
+) -> Result<()> {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("handle_wall_interaction").entered();
+    let config = config
+        .get(&config_handles.game)
+        .context("Failed to get game config from handle")?;
+    let dt = time.delta_seconds();
+    for (
+        actions,
+        transform,
+        collider,
+        grounded,
+        walking,
+        mass,
+        mut jump,
+        mut velocity,
+        mut impulse,
+        mut wall_contact,
+        swimming,
+    ) in &mut player_query
+    {
+        wall_contact.input_lock = (wall_contact.input_lock - dt).max(0.);
+        let up = transform.up();
+        wall_contact.normal = if grounded.0 || swimming.is_swimming() {
+            None
+        } else {
+            walking
+                .direction
+                .and_then(|direction| find_wall(&rapier_context, transform, collider, direction))
+        };
+        let normal = match wall_contact.normal {
+            Some(normal) => normal,
+            None => continue,
+        };
+
+        if actions.just_pressed(PlayerAction::Jump) {
+            // Don't let the regular jump logic spend an air jump on this, neither now nor while jump is held
+            jump.requested = false;
+            jump.consumed = true;
+            velocity.linvel = Vec3::ZERO;
+            let launch_velocity = get_wall_jump_velocity(
+                normal,
+                up,
+                config.player.wall_jump_speed,
+                config.player.wall_jump_angle,
+            );
+            impulse.impulse += launch_velocity * mass.0.mass;
+            wall_contact.input_lock = config.player.wall_jump_input_lock;
+            wall_contact.normal = None;
+        } else {
+            let velocity_components = velocity.linvel.split(up);
+            let max_slide_speed = config.player.wall_slide_speed;
+            if velocity_components.vertical.dot(up) < -max_slide_speed {
+                velocity.linvel = velocity_components.horizontal - up * max_slide_speed;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn find_wall(
+    rapier_context: &RapierContext,
+    transform: &Transform,
+    collider: &Collider,
+    direction: Vec3,
+) -> Option<Vec3> {
+    const MAX_WALL_DISTANCE: f32 = 0.1;
+    const MAX_WALL_STEEPNESS: f32 = 0.3;
+    const MIN_PUSH_INTO_WALL: f32 = 0.5;
+    let up = transform.up();
+    let direction = direction.split(up).horizontal.try_normalize()?;
+    let filter = QueryFilter::only_fixed().exclude_sensors();
+    let (_entity, toi) = rapier_context.cast_shape(
+        transform.translation,
+        transform.rotation,
+        direction,
+        collider,
+        MAX_WALL_DISTANCE,
+        filter,
+    )?;
+    let normal = if toi.normal1.dot(direction) > 0. {
+        -toi.normal1
+    } else {
+        toi.normal1
+    };
+    let is_wall = normal.dot(up).abs() < MAX_WALL_STEEPNESS;
+    let is_pushing_into_wall = direction.dot(normal) < -MIN_PUSH_INTO_WALL;
+    (is_wall && is_pushing_into_wall).then_some(normal)
+}
+
+/// Launch velocity for jumping off a wall with the given `wall_normal`.
+/// `angle` is measured in radians upwards from the horizontal plane.
+pub fn get_wall_jump_velocity(wall_normal: Vec3, up: Vec3, speed: f32, angle: f32) -> Vec3 {
+    let away_from_wall = wall_normal.split(up).horizontal.normalize_or_zero();
+    (away_from_wall * angle.cos() + up * angle.sin()) * speed
+}
+
 fn handle_camera_kind(
     mut with_player: Query<(&mut Transform, &mut Visibility), With<Player>>,
     camera_query: Query<(&Transform, &IngameCamera), Without<Player>>,
@@ -131,26 +506,33 @@ fn handle_camera_kind(
     }
 }
 
-fn handle_speed_effects(
-    velocities: Query<&Velocity, With<Player>>,
-    mut projections: Query<&mut Projection, With<IngameCamera>>,
-) {
+fn handle_landing(
+    mut player_query: Query<(&Landing, &Transform, &mut Velocity), With<Player>>,
+    config_handles: Res<ConfigAssets>,
+    config: Res<Assets<GameConfig>>,
+    mut player_landed_events: EventWriter<PlayerLanded>,
+) -> Result<()> {
     #[cfg(feature = "tracing")]
-    let _span = info_span!("handle_speed_effects").entered();
-    for velocity in velocities.iter() {
-        let speed_squared = velocity.linvel.length_squared();
-        for mut projection in projections.iter_mut() {
-            if let Projection::Perspective(ref mut perspective) = projection.deref_mut() {
-                const MAX_SPEED_FOR_FOV: f32 = 12.;
-                const MIN_FOV: f32 = 0.75;
-                const MAX_FOV: f32 = 1.5;
-                let scale = (speed_squared / MAX_SPEED_FOR_FOV.squared())
-                    .min(1.0)
-                    .squared();
-                perspective.fov = MIN_FOV + (MAX_FOV - MIN_FOV) * scale;
+    let _span = info_span!("handle_landing").entered();
+    let config = config
+        .get(&config_handles.game)
+        .context("Failed to get game config from handle")?;
+    for (landing, transform, mut velocity) in &mut player_query {
+        let impact_speed = match landing.impact_speed {
+            Some(impact_speed) if impact_speed >= config.player.soft_landing_threshold => {
+                impact_speed
             }
+            _ => continue,
+        };
+        player_landed_events.send(PlayerLanded { impact_speed });
+        if impact_speed >= config.player.hard_landing_threshold {
+            let velocity_components = velocity.linvel.split(transform.up());
+            velocity.linvel = velocity_components.horizontal
+                * config.player.hard_landing_speed_factor
+                + velocity_components.vertical;
         }
     }
+    Ok(())
 }
 
 fn rotate_to_speaker(
@@ -188,15 +570,28 @@ fn rotate_to_speaker(
 fn control_walking_sound(
     time: Res<Time>,
     character_query: Query<(&Velocity, &Transform, &Grounded), With<Player>>,
-    audio: Res<AudioHandles>,
-    mut audio_instances: ResMut<Assets<AudioInstance>>,
+    audio: Option<Res<AudioHandles>>,
+    audio_assets: Option<Res<AudioAssets>>,
+    audio_instances: Option<ResMut<Assets<AudioInstance>>>,
 ) -> Result<()> {
     #[cfg(feature = "tracing")]
     let _span = info_span!("control_walking_sound").entered();
+    // Headless apps, e.g. in tests, run without audio
+    let (audio, audio_assets, mut audio_instances) = match (audio, audio_assets, audio_instances) {
+        (Some(audio), Some(audio_assets), Some(audio_instances)) => {
+            (audio, audio_assets, audio_instances)
+        }
+        _ => return Ok(()),
+    };
     for (velocity, transform, grounded) in character_query.iter() {
         let audio_instance = audio_instances
             .get_mut(&audio.walking)
             .context("Failed to get audio instance from handle")?;
+        // The looped sound is only a stand-in for when no individual footstep sounds are available
+        if !audio_assets.footsteps.is_empty() {
+            audio_instance.pause(default());
+            continue;
+        }
         let has_horizontal_movement = !velocity
             .linvel
             .split(transform.up())
@@ -211,3 +606,149 @@ fn control_walking_sound(
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::util::headless::{build_headless_app, HeadlessApp};
+    use crate::util::test_util::assert_nearly_eq;
+    use std::f32::consts::TAU;
+
+    #[test]
+    fn wall_jump_at_zero_angle_pushes_straight_away_from_wall() {
+        let velocity = get_wall_jump_velocity(Vec3::X, Vec3::Y, 5., 0.);
+
+        assert_nearly_eq(velocity, Vec3::new(5., 0., 0.));
+    }
+
+    #[test]
+    fn wall_jump_at_right_angle_goes_straight_up() {
+        let velocity = get_wall_jump_velocity(Vec3::X, Vec3::Y, 5., TAU / 4.);
+
+        assert_nearly_eq(velocity, Vec3::new(0., 5., 0.));
+    }
+
+    #[test]
+    fn wall_jump_ignores_vertical_part_of_wall_normal() {
+        let wall_normal = Vec3::new(-1., 1., 0.).normalize();
+        let velocity = get_wall_jump_velocity(wall_normal, Vec3::Y, 2., TAU / 8.);
+
+        let expected = Vec3::new(-1., 1., 0.).normalize() * 2.;
+        assert_nearly_eq(velocity, expected);
+    }
+
+    #[test]
+    fn wall_jump_respects_custom_up() {
+        let velocity = get_wall_jump_velocity(Vec3::Y, Vec3::X, 3., TAU / 4.);
+
+        assert_nearly_eq(velocity, Vec3::new(3., 0., 0.));
+    }
+
+    #[test]
+    fn player_walks_away_from_camera() {
+        let mut app = build_headless_app();
+        let (player, _camera) = app.spawn_player_on_floor();
+        let start = app.translation(player);
+
+        app.set_movement(Vec2::Y);
+        app.step(60);
+
+        let end = app.translation(player);
+        // The camera looks along -z
+        assert!(
+            end.z < start.z - 1.,
+            "player did not walk: {start} -> {end}"
+        );
+        assert!(
+            (end.x - start.x).abs() < 0.1,
+            "player walked sideways: {start} -> {end}"
+        );
+    }
+
+    #[test]
+    fn jump_leaves_the_ground_and_lands() {
+        let mut app = build_headless_app();
+        let (player, _camera) = app.spawn_player_on_floor();
+        let start = app.translation(player);
+        assert!(
+            is_grounded(&app, player),
+            "player did not land after spawning"
+        );
+
+        app.hold(PlayerAction::Jump);
+        app.step(3);
+        app.release(PlayerAction::Jump);
+        let mut highest = start.y;
+        let mut left_ground = false;
+        for _ in 0..30 {
+            app.step(1);
+            highest = highest.max(app.translation(player).y);
+            left_ground |= !is_grounded(&app, player);
+        }
+        assert!(left_ground, "player never left the ground");
+        assert!(
+            highest > start.y + 0.3,
+            "player only jumped to {highest}, starting at {}",
+            start.y
+        );
+
+        app.step(120);
+        let end = app.translation(player);
+        assert!(is_grounded(&app, player), "player did not land");
+        assert!(
+            (end.y - start.y).abs() < 0.1,
+            "player landed at {end}, started at {start}"
+        );
+    }
+
+    #[test]
+    fn holding_jump_after_wall_jump_keeps_air_jumps() {
+        let mut app = build_headless_app();
+        let (player, _camera) = app.spawn_player_on_floor();
+        let config = app.world.resource::<ConfigAssets>().game.clone();
+        app.world
+            .resource_mut::<Assets<GameConfig>>()
+            .get_mut(&config)
+            .unwrap()
+            .player
+            .extra_jumps = 1;
+        // Air jumps are only refilled on the ground
+        app.step(1);
+        // Wall to the left of the player, high enough to slide on while falling
+        app.spawn_fixed_box(Vec3::new(-1., 5., 0.), Vec3::new(0.5, 5., 5.));
+        app.world.get_mut::<Transform>(player).unwrap().translation = Vec3::new(0., 6., 0.);
+        app.set_movement(Vec2::NEG_X);
+
+        let mut frames = 0;
+        while app
+            .world
+            .get::<WallContact>(player)
+            .unwrap()
+            .normal
+            .is_none()
+        {
+            assert!(frames < 60, "player never reached the wall");
+            app.step(1);
+            frames += 1;
+        }
+        assert_eq!(app.world.get::<AirJumps>(player).unwrap().remaining, 1);
+
+        app.hold(PlayerAction::Jump);
+        app.step(1);
+        let velocity = app.world.get::<Velocity>(player).unwrap().linvel;
+        assert!(
+            velocity.x > 0.,
+            "player did not jump off the wall: {velocity}"
+        );
+        app.step(10);
+        assert_eq!(
+            app.world.get::<AirJumps>(player).unwrap().remaining,
+            1,
+            "holding jump after a wall jump spent an air jump"
+        );
+    }
+
+    fn is_grounded(app: &App, player: Entity) -> bool {
+        app.world.get::<Grounded>(player).unwrap().0
+    }
+}
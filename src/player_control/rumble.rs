@@ -0,0 +1,346 @@
+use crate::file_system_interaction::asset_loading::ConfigAssets;
+use crate::file_system_interaction::config::GameConfig;
+use crate::player_control::player_embodiment::{Player, PlayerLanded};
+use crate::util::log_error::log_errors;
+use crate::world_interaction::dialog::{CurrentDialog, PageId};
+use crate::world_interaction::health::Health;
+use crate::GameState;
+use anyhow::{Context, Result};
+use bevy::input::gamepad::{GamepadEvent, GamepadEventType};
+use bevy::prelude::*;
+
+/// Shakes the gamepad the player last used.
+/// Send a [`RumbleRequest`] to rumble. Hard landings, taking damage and, if enabled, new dialog pages do so on their own,
+/// each of them can be turned off in the `rumble` section of the config, which also scales every rumble.
+/// Requests that overlap are not queued, instead each motor runs at the strongest strength requested for it.
+/// The rumble stops as soon as the game is paused or a menu opens.
+/// Only native platforms with a gamepad that supports force feedback rumble, everywhere else requests are ignored.
+pub struct RumblePlugin;
+
+impl Plugin for RumblePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<RumbleRequest>()
+            .init_resource::<RumbleMix>()
+            .init_resource::<ActiveGamepad>()
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .label(RumbleRequestLabel)
+                    .with_system(rumble_on_landing.pipe(log_errors))
+                    .with_system(rumble_on_damage.pipe(log_errors))
+                    .with_system(rumble_on_dialog_page.pipe(log_errors)),
+            )
+            .add_system(track_active_gamepad)
+            .add_system(mix_rumble.after(RumbleRequestLabel));
+        #[cfg(feature = "native")]
+        app.add_system(native::play_rumble.pipe(log_errors).after(mix_rumble));
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, SystemLabel)]
+struct RumbleRequestLabel;
+
+/// Rumbles the [`ActiveGamepad`]. Strengths go from 0 to 1.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct RumbleRequest {
+    /// Strength of the heavy motor, which feels like a deep shake
+    pub low_frequency: f32,
+    /// Strength of the light motor, which feels like a buzz
+    pub high_frequency: f32,
+    /// Time in seconds
+    pub duration: f32,
+}
+
+/// The gamepad the player pressed something on last
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Resource, Default)]
+pub struct ActiveGamepad(pub Option<Gamepad>);
+
+/// The requests that are currently rumbling, with their remaining duration
+#[derive(Debug, Clone, PartialEq, Resource, Default)]
+pub struct RumbleMix {
+    active: Vec<RumbleRequest>,
+}
+
+impl RumbleMix {
+    pub fn add(&mut self, request: RumbleRequest) {
+        if request.duration > 0. {
+            self.active.push(request);
+        }
+    }
+
+    pub fn advance(&mut self, dt: f32) {
+        for request in self.active.iter_mut() {
+            request.duration -= dt;
+        }
+        self.active.retain(|request| request.duration > 0.);
+    }
+
+    pub fn clear(&mut self) {
+        self.active.clear();
+    }
+
+    /// Strength of each motor, i.e. the strongest of all active requests
+    pub fn strength(&self) -> (f32, f32) {
+        self.active.iter().fold((0., 0.), |(low, high), request| {
+            (
+                low.max(request.low_frequency.clamp(0., 1.)),
+                high.max(request.high_frequency.clamp(0., 1.)),
+            )
+        })
+    }
+
+    /// Time in seconds until the last request ends
+    pub fn remaining(&self) -> f32 {
+        self.active
+            .iter()
+            .map(|request| request.duration)
+            .fold(0., f32::max)
+    }
+}
+
+fn track_active_gamepad(
+    mut gamepad_events: EventReader<GamepadEvent>,
+    mut active_gamepad: ResMut<ActiveGamepad>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("track_active_gamepad").entered();
+    for event in gamepad_events.iter() {
+        match event.event_type {
+            GamepadEventType::Disconnected => {
+                if active_gamepad.0 == Some(event.gamepad) {
+                    active_gamepad.0 = None;
+                }
+            }
+            GamepadEventType::Connected => {
+                active_gamepad.0.get_or_insert(event.gamepad);
+            }
+            GamepadEventType::ButtonChanged(..) | GamepadEventType::AxisChanged(..) => {
+                active_gamepad.0 = Some(event.gamepad);
+            }
+        }
+    }
+}
+
+fn mix_rumble(
+    time: Res<Time>,
+    state: Res<State<GameState>>,
+    mut rumble_requests: EventReader<RumbleRequest>,
+    mut mix: ResMut<RumbleMix>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("mix_rumble").entered();
+    if *state.current() != GameState::Playing {
+        rumble_requests.clear();
+        if !mix.active.is_empty() {
+            mix.clear();
+        }
+        return;
+    }
+    mix.advance(time.delta_seconds());
+    for request in rumble_requests.iter() {
+        mix.add(*request);
+    }
+}
+
+fn rumble_on_landing(
+    mut player_landed_events: EventReader<PlayerLanded>,
+    mut rumble_requests: EventWriter<RumbleRequest>,
+    config_handles: Res<ConfigAssets>,
+    config: Res<Assets<GameConfig>>,
+) -> Result<()> {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("rumble_on_landing").entered();
+    let config = config
+        .get(&config_handles.game)
+        .context("Failed to get game config from handle")?;
+    for landing in player_landed_events.iter() {
+        if !config.rumble.landings || landing.impact_speed < config.player.hard_landing_threshold {
+            continue;
+        }
+        let strength = landing.impact_speed * config.rumble.landing_strength_per_speed;
+        rumble_requests.send(RumbleRequest {
+            low_frequency: strength,
+            high_frequency: strength / 2.,
+            duration: config.rumble.landing_duration,
+        });
+    }
+    Ok(())
+}
+
+fn rumble_on_damage(
+    health_query: Query<(Entity, &Health), (With<Player>, Changed<Health>)>,
+    mut rumble_requests: EventWriter<RumbleRequest>,
+    mut last_health: Local<Option<(Entity, f32)>>,
+    config_handles: Res<ConfigAssets>,
+    config: Res<Assets<GameConfig>>,
+) -> Result<()> {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("rumble_on_damage").entered();
+    let config = config
+        .get(&config_handles.game)
+        .context("Failed to get game config from handle")?;
+    for (entity, health) in health_query.iter() {
+        let was_hurt = matches!(*last_health, Some((last_entity, last_current)) if last_entity == entity && health.current < last_current);
+        *last_health = Some((entity, health.current));
+        if was_hurt && config.rumble.damage {
+            rumble_requests.send(RumbleRequest {
+                low_frequency: config.rumble.damage_strength,
+                high_frequency: config.rumble.damage_strength,
+                duration: config.rumble.damage_duration,
+            });
+        }
+    }
+    Ok(())
+}
+
+fn rumble_on_dialog_page(
+    current_dialog: Option<Res<CurrentDialog>>,
+    mut rumble_requests: EventWriter<RumbleRequest>,
+    mut last_page: Local<Option<PageId>>,
+    config_handles: Res<ConfigAssets>,
+    config: Res<Assets<GameConfig>>,
+) -> Result<()> {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("rumble_on_dialog_page").entered();
+    let page = current_dialog.map(|dialog| dialog.current_page.clone());
+    if page == *last_page {
+        return Ok(());
+    }
+    let config = config
+        .get(&config_handles.game)
+        .context("Failed to get game config from handle")?;
+    if page.is_some() && config.rumble.dialog {
+        rumble_requests.send(RumbleRequest {
+            low_frequency: 0.,
+            high_frequency: config.rumble.dialog_tick_strength,
+            duration: config.rumble.dialog_tick_duration,
+        });
+    }
+    *last_page = page;
+    Ok(())
+}
+
+#[cfg(feature = "native")]
+mod native {
+    use super::*;
+    use gilrs::ff::{BaseEffect, BaseEffectType, Effect, EffectBuilder, Replay, Ticks};
+    use gilrs::Gilrs;
+
+    /// The effect that is currently playing and the strengths it was built with
+    #[derive(Default)]
+    pub(super) struct PlayingRumble {
+        effect: Option<Effect>,
+        strength: (f32, f32),
+    }
+
+    pub(super) fn play_rumble(
+        gilrs: Option<NonSendMut<Gilrs>>,
+        mix: Res<RumbleMix>,
+        active_gamepad: Res<ActiveGamepad>,
+        mut playing: Local<PlayingRumble>,
+        config_handles: Res<ConfigAssets>,
+        config: Res<Assets<GameConfig>>,
+    ) -> Result<()> {
+        #[cfg(feature = "tracing")]
+        let _span = info_span!("play_rumble").entered();
+        let mut gilrs = match gilrs {
+            Some(gilrs) => gilrs,
+            None => return Ok(()),
+        };
+        let config = config
+            .get(&config_handles.game)
+            .context("Failed to get game config from handle")?;
+        let intensity = config.rumble.intensity.clamp(0., 1.);
+        let (low, high) = mix.strength();
+        let strength = (low * intensity, high * intensity);
+        if strength == playing.strength {
+            return Ok(());
+        }
+        if let Some(effect) = playing.effect.take() {
+            if let Err(e) = effect.stop() {
+                warn!("Failed to stop rumble: {e}");
+            }
+        }
+        playing.strength = strength;
+        if strength == (0., 0.) {
+            return Ok(());
+        }
+        let gamepad = active_gamepad.0.and_then(|gamepad| {
+            gilrs
+                .gamepads()
+                .find(|(id, pad)| usize::from(*id) == gamepad.id && pad.is_ff_supported())
+                .map(|(id, _)| id)
+        });
+        let gamepad = match gamepad {
+            Some(gamepad) => gamepad,
+            None => return Ok(()),
+        };
+        // Stops on its own even if the game hangs
+        let scheduling = Replay {
+            play_for: Ticks::from_ms((mix.remaining() * 1000.) as u32),
+            ..default()
+        };
+        let effect = match EffectBuilder::new()
+            .add_effect(BaseEffect {
+                kind: BaseEffectType::Strong {
+                    magnitude: (strength.0 * u16::MAX as f32) as u16,
+                },
+                scheduling,
+                ..default()
+            })
+            .add_effect(BaseEffect {
+                kind: BaseEffectType::Weak {
+                    magnitude: (strength.1 * u16::MAX as f32) as u16,
+                },
+                scheduling,
+                ..default()
+            })
+            .gamepads(&[gamepad])
+            .finish(&mut gilrs)
+        {
+            Ok(effect) => effect,
+            Err(e) => {
+                warn!("Failed to create rumble: {e}");
+                return Ok(());
+            }
+        };
+        if let Err(e) = effect.play() {
+            warn!("Failed to play rumble: {e}");
+            return Ok(());
+        }
+        playing.effect = Some(effect);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn overlapping_requests_take_the_strongest_motor() {
+        let mut mix = RumbleMix::default();
+        mix.add(RumbleRequest {
+            low_frequency: 0.8,
+            high_frequency: 0.1,
+            duration: 0.2,
+        });
+        mix.add(RumbleRequest {
+            low_frequency: 0.3,
+            high_frequency: 0.5,
+            duration: 1.,
+        });
+        mix.add(RumbleRequest {
+            low_frequency: 1.,
+            high_frequency: 1.,
+            duration: 0.,
+        });
+        assert_eq!(mix.strength(), (0.8, 0.5));
+        assert_eq!(mix.remaining(), 1.);
+
+        mix.advance(0.5);
+        assert_eq!(mix.strength(), (0.3, 0.5));
+        mix.advance(0.5);
+        assert_eq!(mix.strength(), (0., 0.));
+        assert_eq!(mix.remaining(), 0.);
+    }
+}
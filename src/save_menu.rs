@@ -0,0 +1,261 @@
+use crate::file_system_interaction::game_state_serialization::{
+    delete_save_slot, list_save_slots, SaveSlot, SaveSlotInfo,
+};
+use crate::file_system_interaction::localization::Localization;
+use crate::file_system_interaction::thumbnail::{
+    read_thumbnail, THUMBNAIL_HEIGHT, THUMBNAIL_WIDTH,
+};
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use bevy_egui::egui;
+use chrono::{Local, TimeZone};
+use std::fmt::{Debug, Formatter};
+
+/// Lists the save slots for loading, overwriting and deleting them.
+/// Shared by the main menu and the pause menu, which keep one of these around while they are open.
+#[derive(Debug, Clone, Default)]
+pub struct SaveMenu {
+    /// Cached, as listing the slots touches the disk
+    slots: Option<Vec<SaveSlotInfo>>,
+    thumbnails: ThumbnailTextures,
+    new_slot_name: String,
+    confirmation: Option<Confirmation>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Confirmation {
+    Overwrite(SaveSlot),
+    Delete(SaveSlot),
+}
+
+/// What the player chose in the [`SaveMenu`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SaveMenuAction {
+    Save(SaveSlot),
+    Load(SaveSlot),
+}
+
+impl SaveMenu {
+    /// Makes the menu read the slots from disk again the next time it is shown
+    pub fn refresh(&mut self) {
+        self.slots = None;
+        self.thumbnails = default();
+        self.confirmation = None;
+    }
+
+    /// Saving is only offered while playing
+    pub fn show(
+        &mut self,
+        ui: &mut egui::Ui,
+        localization: &Localization,
+        allow_saving: bool,
+    ) -> Option<SaveMenuAction> {
+        let slots = self.slots.get_or_insert_with(list_save_slots).clone();
+        let mut action = None;
+
+        if let Some(confirmation) = self.confirmation.clone() {
+            let (question, slot) = match &confirmation {
+                Confirmation::Overwrite(slot) => (localization.get("save.confirm_overwrite"), slot),
+                Confirmation::Delete(slot) => (localization.get("save.confirm_delete"), slot),
+            };
+            ui.label(question.replace("{slot}", &get_slot_label(slot, localization)));
+            ui.horizontal(|ui| {
+                if ui.button(localization.get("save.yes")).clicked() {
+                    match &confirmation {
+                        Confirmation::Overwrite(slot) => {
+                            action = Some(SaveMenuAction::Save(slot.clone()))
+                        }
+                        Confirmation::Delete(slot) => {
+                            if let Err(e) = delete_save_slot(slot) {
+                                error!("{e:?}");
+                            }
+                        }
+                    }
+                    self.refresh();
+                }
+                if ui.button(localization.get("save.no")).clicked() {
+                    self.confirmation = None;
+                }
+            });
+            return action;
+        }
+
+        if allow_saving {
+            ui.horizontal(|ui| {
+                ui.text_edit_singleline(&mut self.new_slot_name);
+                if ui.button(localization.get("save.new")).clicked() {
+                    let slot = if self.new_slot_name.trim().is_empty() {
+                        get_free_slot(&slots)
+                    } else {
+                        SaveSlot::from_name(&self.new_slot_name)
+                    };
+                    if slots.iter().any(|info| info.slot == slot) {
+                        self.confirmation = Some(Confirmation::Overwrite(slot));
+                    } else {
+                        action = Some(SaveMenuAction::Save(slot));
+                        self.new_slot_name.clear();
+                        self.refresh();
+                    }
+                }
+            });
+            ui.separator();
+        }
+
+        if slots.is_empty() {
+            ui.label(localization.get("save.no_saves"));
+            return action;
+        }
+        egui::ScrollArea::vertical()
+            .max_height(300.)
+            .show(ui, |ui| {
+                egui::Grid::new("save_slots")
+                    .striped(true)
+                    .spacing(egui::Vec2::new(12., 6.))
+                    .show(ui, |ui| {
+                        for info in slots.iter() {
+                            let thumbnail = self.thumbnails.get(ui.ctx(), &info.slot);
+                            ui.image(thumbnail, THUMBNAIL_DISPLAY_SIZE);
+                            ui.label(get_slot_label(&info.slot, localization));
+                            match &info.metadata {
+                                Ok(metadata) => {
+                                    ui.label(format_timestamp(metadata.timestamp));
+                                    ui.label(format_play_time(metadata.play_time));
+                                    let position = metadata.player_position;
+                                    ui.label(format!(
+                                        "{} ({:.0}, {:.0}, {:.0})",
+                                        metadata.level, position.x, position.y, position.z
+                                    ));
+                                    if ui.button(localization.get("save.load")).clicked() {
+                                        action = Some(SaveMenuAction::Load(info.slot.clone()));
+                                    }
+                                }
+                                Err(reason) => {
+                                    ui.label(localization.get("save.unreadable"))
+                                        .on_hover_text(reason.as_str());
+                                    // Keep the buttons in their columns
+                                    for _ in 0..4 {
+                                        ui.label("");
+                                    }
+                                }
+                            }
+                            // Autosaves and quicksaves are rotated by the game and cannot be overwritten by hand
+                            if allow_saving && !info.slot.is_rotated() {
+                                if ui.button(localization.get("save.overwrite")).clicked() {
+                                    self.confirmation =
+                                        Some(Confirmation::Overwrite(info.slot.clone()));
+                                }
+                            } else {
+                                ui.label("");
+                            }
+                            if ui.button(localization.get("save.delete")).clicked() {
+                                self.confirmation = Some(Confirmation::Delete(info.slot.clone()));
+                            }
+                            ui.end_row();
+                        }
+                    });
+            });
+        action
+    }
+}
+
+/// Half the size of the stored thumbnails
+const THUMBNAIL_DISPLAY_SIZE: egui::Vec2 =
+    egui::Vec2::new(THUMBNAIL_WIDTH as f32 / 2., THUMBNAIL_HEIGHT as f32 / 2.);
+
+/// Textures of the thumbnails of the listed slots, loaded the first time a slot is shown
+#[derive(Clone, Default)]
+struct ThumbnailTextures {
+    /// `None` for slots without a readable thumbnail
+    textures: HashMap<SaveSlot, Option<egui::TextureHandle>>,
+    placeholder: Option<egui::TextureHandle>,
+}
+
+impl Debug for ThumbnailTextures {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ThumbnailTextures")
+            .field("slots", &self.textures.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl ThumbnailTextures {
+    fn get(&mut self, ctx: &egui::Context, slot: &SaveSlot) -> egui::TextureId {
+        let texture =
+            self.textures
+                .entry(slot.clone())
+                .or_insert_with(|| match read_thumbnail(slot) {
+                    Ok(thumbnail) => thumbnail.map(|thumbnail| {
+                        let size = [thumbnail.width() as usize, thumbnail.height() as usize];
+                        let image =
+                            egui::ColorImage::from_rgba_unmultiplied(size, thumbnail.as_raw());
+                        ctx.load_texture(
+                            format!("thumbnail {slot}"),
+                            image,
+                            egui::TextureOptions::LINEAR,
+                        )
+                    }),
+                    Err(e) => {
+                        warn!("Failed to read thumbnail of save slot {slot}: {e:#}");
+                        None
+                    }
+                });
+        match texture {
+            Some(texture) => texture.id(),
+            None => self
+                .placeholder
+                .get_or_insert_with(|| {
+                    ctx.load_texture(
+                        "thumbnail placeholder",
+                        egui::ColorImage::new([16, 9], egui::Color32::from_gray(60)),
+                        egui::TextureOptions::LINEAR,
+                    )
+                })
+                .id(),
+        }
+    }
+}
+
+fn get_slot_label(slot: &SaveSlot, localization: &Localization) -> String {
+    match slot {
+        SaveSlot::Autosave(index) => localization
+            .get("save.autosave")
+            .replace("{index}", &(index + 1).to_string()),
+        SaveSlot::Quicksave(index) => localization
+            .get("save.quicksave")
+            .replace("{index}", &(index + 1).to_string()),
+        slot => slot.to_string(),
+    }
+}
+
+/// The first numbered slot that is not taken yet
+fn get_free_slot(slots: &[SaveSlotInfo]) -> SaveSlot {
+    (0..)
+        .map(SaveSlot::Index)
+        .find(|slot| slots.iter().all(|info| info.slot != *slot))
+        .unwrap_or_default()
+}
+
+fn format_timestamp(timestamp: i64) -> String {
+    Local
+        .timestamp_opt(timestamp, 0)
+        .single()
+        .map(|time| time.format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_default()
+}
+
+fn format_play_time(seconds: f32) -> String {
+    let minutes = (seconds / 60.) as u32;
+    format!("{}:{:02}", minutes / 60, minutes % 60)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn formats_play_time_as_hours_and_minutes() {
+        assert_eq!(format_play_time(0.), "0:00");
+        assert_eq!(format_play_time(59. * 60. + 59.), "0:59");
+        assert_eq!(format_play_time(2. * 3600. + 5. * 60.), "2:05");
+    }
+}
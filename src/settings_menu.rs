@@ -0,0 +1,409 @@
+use crate::bevy_config::AvailableResolutions;
+use crate::file_system_interaction::config::GameConfig;
+use crate::file_system_interaction::localization::Localization;
+use crate::file_system_interaction::user_settings::{
+    write_user_settings, DisplayMode, Graphics, UserSettings,
+};
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContext};
+
+/// Applies the settings chosen in the [`SettingsMenu`] and writes them to the [`UserSettings`] file.
+/// Changes to the display mode or resolution are reverted after a countdown unless the player keeps them,
+/// so that a resolution the monitor cannot show does not lock them out.
+pub struct SettingsMenuPlugin;
+
+impl Plugin for SettingsMenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<SettingsChangeRequest>()
+            .add_system(handle_settings_change_requests)
+            .add_system(confirm_display_change.after(handle_settings_change_requests));
+    }
+}
+
+/// Time in seconds after which display changes are reverted unless the player keeps them
+const DISPLAY_CHANGE_REVERT_SECONDS: f32 = 10.;
+
+const FRAME_RATE_CAPS: [Option<u32>; 5] = [None, Some(30), Some(60), Some(120), Some(144)];
+
+const SCALE_FACTOR_OVERRIDES: [Option<f64>; 6] =
+    [None, Some(1.), Some(1.25), Some(1.5), Some(2.), Some(3.)];
+
+const SHADOW_MAP_SIZES: [u32; 4] = [512, 1024, 2048, 4096];
+
+const MSAA_SAMPLES: [u32; 2] = [1, 4];
+
+const FOV_DEGREES: std::ops::RangeInclusive<f32> = 30.0..=90.0;
+
+const CAMERA_SMOOTHING_MULTIPLIERS: std::ops::RangeInclusive<f32> = 0.25..=4.0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SettingsChangeRequest(pub UserSettings);
+
+/// Shows the language, graphics and accessibility settings.
+/// Shared by the main menu and the pause menu, which keep one of these around while they are open.
+#[derive(Debug, Clone, Default)]
+pub struct SettingsMenu {
+    /// The settings as edited by the player, but not applied yet
+    settings: Option<UserSettings>,
+}
+
+impl SettingsMenu {
+    /// Returns the settings to apply when the player applies their changes.
+    /// Until the player chooses their own, the quality, field of view and accessibility options from the game `config` are shown.
+    pub fn show(
+        &mut self,
+        ui: &mut egui::Ui,
+        localization: &mut Localization,
+        settings: &UserSettings,
+        config: &GameConfig,
+        resolutions: &AvailableResolutions,
+    ) -> Option<UserSettings> {
+        let default_quality = &config.quality;
+        show_language_selection(ui, localization);
+        ui.add_space(30.0);
+        ui.label(localization.get("settings.graphics"));
+        let current_settings = UserSettings {
+            graphics: Graphics {
+                quality: Some(settings.graphics.quality_or(default_quality).clone()),
+                fov_degrees: Some(settings.graphics.fov_degrees_or(config.camera.fov_degrees)),
+                dynamic_fov: Some(settings.graphics.dynamic_fov_or(config.camera.dynamic_fov)),
+                ..settings.graphics.clone()
+            },
+            accessibility: Some(settings.accessibility_or(&config.accessibility).clone()),
+        };
+        let edited_settings = self
+            .settings
+            .get_or_insert_with(|| current_settings.clone());
+        let graphics = &mut edited_settings.graphics;
+        egui::Grid::new("graphics_settings")
+            .num_columns(2)
+            .show(ui, |ui| {
+                ui.label(localization.get("settings.display_mode"));
+                egui::ComboBox::from_id_source("display_mode")
+                    .selected_text(localization.get(graphics.display_mode.localization_key()))
+                    .show_ui(ui, |ui| {
+                        for display_mode in DisplayMode::ALL {
+                            ui.selectable_value(
+                                &mut graphics.display_mode,
+                                display_mode,
+                                localization.get(display_mode.localization_key()),
+                            );
+                        }
+                    });
+                ui.end_row();
+
+                ui.label(localization.get("settings.resolution"));
+                egui::ComboBox::from_id_source("resolution")
+                    .selected_text(format_resolution(graphics.resolution))
+                    .show_ui(ui, |ui| {
+                        for resolution in resolutions.0.iter() {
+                            ui.selectable_value(
+                                &mut graphics.resolution,
+                                *resolution,
+                                format_resolution(*resolution),
+                            );
+                        }
+                    });
+                ui.end_row();
+
+                ui.label(localization.get("settings.vsync"));
+                ui.checkbox(&mut graphics.vsync, "");
+                ui.end_row();
+
+                ui.label(localization.get("settings.frame_rate_cap"));
+                egui::ComboBox::from_id_source("frame_rate_cap")
+                    .selected_text(format_frame_rate_cap(graphics.frame_rate_cap, localization))
+                    .show_ui(ui, |ui| {
+                        for frame_rate_cap in FRAME_RATE_CAPS {
+                            ui.selectable_value(
+                                &mut graphics.frame_rate_cap,
+                                frame_rate_cap,
+                                format_frame_rate_cap(frame_rate_cap, localization),
+                            );
+                        }
+                    });
+                ui.end_row();
+
+                ui.label(localization.get("settings.ui_scale"));
+                egui::ComboBox::from_id_source("ui_scale")
+                    .selected_text(format_scale_factor(
+                        graphics.scale_factor_override,
+                        localization,
+                    ))
+                    .show_ui(ui, |ui| {
+                        for scale_factor in SCALE_FACTOR_OVERRIDES {
+                            ui.selectable_value(
+                                &mut graphics.scale_factor_override,
+                                scale_factor,
+                                format_scale_factor(scale_factor, localization),
+                            );
+                        }
+                    });
+                ui.end_row();
+
+                let quality = graphics
+                    .quality
+                    .get_or_insert_with(|| default_quality.clone());
+                ui.label(localization.get("settings.shadows"));
+                ui.checkbox(&mut quality.shadows, "");
+                ui.end_row();
+
+                ui.label(localization.get("settings.shadow_quality"));
+                egui::ComboBox::from_id_source("shadow_map_size")
+                    .selected_text(quality.shadow_map_size.to_string())
+                    .show_ui(ui, |ui| {
+                        for shadow_map_size in SHADOW_MAP_SIZES {
+                            ui.selectable_value(
+                                &mut quality.shadow_map_size,
+                                shadow_map_size,
+                                shadow_map_size.to_string(),
+                            );
+                        }
+                    });
+                ui.end_row();
+
+                ui.label(localization.get("settings.anti_aliasing"));
+                egui::ComboBox::from_id_source("msaa_samples")
+                    .selected_text(format_msaa_samples(quality.msaa_samples, localization))
+                    .show_ui(ui, |ui| {
+                        for msaa_samples in MSAA_SAMPLES {
+                            ui.selectable_value(
+                                &mut quality.msaa_samples,
+                                msaa_samples,
+                                format_msaa_samples(msaa_samples, localization),
+                            );
+                        }
+                    });
+                ui.end_row();
+
+                ui.label(localization.get("settings.ambient_brightness"));
+                ui.add(egui::Slider::new(
+                    &mut quality.ambient_brightness,
+                    0.0..=1.0,
+                ));
+                ui.end_row();
+
+                ui.label(localization.get("settings.draw_distance"));
+                ui.add(egui::Slider::new(&mut quality.draw_distance, 100.0..=2000.0).suffix(" m"));
+                ui.end_row();
+
+                let fov_degrees = graphics
+                    .fov_degrees
+                    .get_or_insert(config.camera.fov_degrees);
+                ui.label(localization.get("settings.field_of_view"));
+                ui.add(egui::Slider::new(fov_degrees, FOV_DEGREES).suffix("°"));
+                ui.end_row();
+
+                let dynamic_fov = graphics
+                    .dynamic_fov
+                    .get_or_insert(config.camera.dynamic_fov);
+                ui.label(localization.get("settings.dynamic_fov"));
+                ui.checkbox(dynamic_fov, "")
+                    .on_hover_text(localization.get("settings.dynamic_fov.hint"));
+                ui.end_row();
+            });
+
+        ui.add_space(30.0);
+        ui.label(localization.get("settings.accessibility"));
+        let accessibility = edited_settings
+            .accessibility
+            .get_or_insert_with(|| config.accessibility.clone());
+        egui::Grid::new("accessibility_settings")
+            .num_columns(2)
+            .show(ui, |ui| {
+                ui.label(localization.get("settings.reduce_camera_motion"));
+                ui.checkbox(&mut accessibility.reduce_camera_motion, "")
+                    .on_hover_text(localization.get("settings.reduce_camera_motion.hint"));
+                ui.end_row();
+
+                ui.label(localization.get("settings.hold_to_toggle"));
+                ui.checkbox(&mut accessibility.hold_to_toggle, "")
+                    .on_hover_text(localization.get("settings.hold_to_toggle.hint"));
+                ui.end_row();
+
+                ui.label(localization.get("settings.camera_smoothing"));
+                ui.add(
+                    egui::Slider::new(
+                        &mut accessibility.camera_smoothing_multiplier,
+                        CAMERA_SMOOTHING_MULTIPLIERS,
+                    )
+                    .logarithmic(true)
+                    .suffix("×"),
+                )
+                .on_hover_text(localization.get("settings.camera_smoothing.hint"));
+                ui.end_row();
+            });
+
+        let has_changes = *edited_settings != current_settings;
+        let mut applied_settings = None;
+        ui.add_space(10.0);
+        if ui
+            .add_enabled(
+                has_changes,
+                egui::Button::new(localization.get("settings.apply")),
+            )
+            .clicked()
+        {
+            applied_settings = Some(edited_settings.clone());
+        }
+        if ui
+            .add_enabled(
+                has_changes,
+                egui::Button::new(localization.get("settings.discard")),
+            )
+            .clicked()
+        {
+            self.settings = None;
+        }
+        applied_settings
+    }
+}
+
+fn format_resolution(resolution: UVec2) -> String {
+    format!("{} × {}", resolution.x, resolution.y)
+}
+
+fn format_frame_rate_cap(frame_rate_cap: Option<u32>, localization: &Localization) -> String {
+    match frame_rate_cap {
+        Some(frame_rate_cap) => format!("{frame_rate_cap} FPS"),
+        None => localization.get("settings.unlimited").to_string(),
+    }
+}
+
+fn format_msaa_samples(msaa_samples: u32, localization: &Localization) -> String {
+    match msaa_samples {
+        1 => localization.get("settings.off").to_string(),
+        msaa_samples => format!("{msaa_samples}× MSAA"),
+    }
+}
+
+fn format_scale_factor(scale_factor: Option<f64>, localization: &Localization) -> String {
+    match scale_factor {
+        Some(scale_factor) => format!("{}%", (scale_factor * 100.).round()),
+        None => localization.get("settings.automatic").to_string(),
+    }
+}
+
+pub fn show_language_selection(ui: &mut egui::Ui, localization: &mut Localization) {
+    ui.label(localization.get("pause.language"));
+    let languages: Vec<_> = localization
+        .languages()
+        .into_iter()
+        .map(|language| language.to_string())
+        .collect();
+    let current_language = localization.language().to_string();
+    let mut selected_language = None;
+    ui.horizontal(|ui| {
+        for language in languages {
+            // Each locale names its own language
+            let name = localization
+                .get_in(&language, "language.name")
+                .unwrap_or(&language)
+                .to_string();
+            if ui
+                .selectable_label(language == current_language, name)
+                .clicked()
+            {
+                selected_language = Some(language);
+            }
+        }
+    });
+    if let Some(language) = selected_language {
+        if language != current_language {
+            if let Err(e) = localization.set_language(language) {
+                error!("{e:?}");
+            }
+        }
+    }
+}
+
+/// Display changes that revert to `previous` unless the player keeps them in time
+#[derive(Debug, Clone, PartialEq, Resource)]
+struct PendingDisplayChange {
+    previous: Graphics,
+    remaining_seconds: f32,
+}
+
+fn handle_settings_change_requests(
+    mut commands: Commands,
+    mut requests: EventReader<SettingsChangeRequest>,
+    mut settings: ResMut<UserSettings>,
+    pending_display_change: Option<Res<PendingDisplayChange>>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("handle_settings_change_requests").entered();
+    for request in requests.iter() {
+        // While a display change is pending, the settings before it are the last ones known to work
+        let previous = pending_display_change
+            .as_ref()
+            .map(|pending| pending.previous.clone())
+            .unwrap_or_else(|| settings.graphics.clone());
+        *settings = request.0.clone();
+        if previous.changes_display(&settings.graphics) {
+            commands.insert_resource(PendingDisplayChange {
+                previous,
+                remaining_seconds: DISPLAY_CHANGE_REVERT_SECONDS,
+            });
+        } else {
+            commands.remove_resource::<PendingDisplayChange>();
+            save_user_settings(&settings);
+        }
+    }
+}
+
+fn confirm_display_change(
+    mut commands: Commands,
+    time: Res<Time>,
+    pending_display_change: Option<ResMut<PendingDisplayChange>>,
+    mut settings: ResMut<UserSettings>,
+    localization: Option<Res<Localization>>,
+    mut egui_context: ResMut<EguiContext>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("confirm_display_change").entered();
+    let (mut pending_display_change, localization) = match (pending_display_change, localization) {
+        (Some(pending_display_change), Some(localization)) => {
+            (pending_display_change, localization)
+        }
+        _ => return,
+    };
+    // The game's time stands still while the pause menu is open
+    pending_display_change.remaining_seconds -= time.raw_delta_seconds();
+    let mut keep = false;
+    let mut revert = pending_display_change.remaining_seconds <= 0.;
+    egui::Window::new(localization.get("settings.keep_display"))
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+        .show(egui_context.ctx_mut(), |ui| {
+            let remaining_seconds = pending_display_change.remaining_seconds.max(0.).ceil();
+            ui.label(
+                localization
+                    .get("settings.reverting_in")
+                    .replace("{seconds}", &remaining_seconds.to_string()),
+            );
+            ui.horizontal(|ui| {
+                keep = ui.button(localization.get("settings.keep")).clicked();
+                if ui.button(localization.get("settings.revert")).clicked() {
+                    revert = true;
+                }
+            });
+        });
+
+    if keep {
+        commands.remove_resource::<PendingDisplayChange>();
+        save_user_settings(&settings);
+    } else if revert {
+        // Other settings applied together with the display change are kept
+        settings.graphics = pending_display_change.previous.clone();
+        commands.remove_resource::<PendingDisplayChange>();
+        save_user_settings(&settings);
+    }
+}
+
+/// Failing to persist the settings is not worth interrupting the game for
+fn save_user_settings(settings: &UserSettings) {
+    if let Err(e) = write_user_settings(settings) {
+        error!("{e:?}");
+    }
+}
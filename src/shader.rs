@@ -1,6 +1,8 @@
 #![allow(clippy::extra_unused_type_parameters)]
-use crate::file_system_interaction::asset_loading::TextureAssets;
+use crate::file_system_interaction::asset_loading::{ConfigAssets, TextureAssets};
+use crate::file_system_interaction::config::GameConfig;
 use crate::util::log_error::log_errors;
+use crate::world_interaction::wind::Wind;
 use crate::GameState;
 use anyhow::{Context, Result};
 use bevy::asset::HandleId;
@@ -26,10 +28,15 @@ impl Plugin for ShaderPlugin {
         app.add_plugin(MaterialPlugin::<GlowyMaterial>::default())
             .add_plugin(MaterialPlugin::<RepeatedMaterial>::default())
             .add_plugin(MaterialPlugin::<SkydomeMaterial>::default())
+            .add_plugin(MaterialPlugin::<FoliageMaterial>::default())
+            .add_plugin(MaterialPlugin::<OutlineMaterial>::default())
             .add_system_set(SystemSet::on_exit(GameState::Loading).with_system(setup_shader))
             .add_system_set(
                 SystemSet::on_update(GameState::Playing)
-                    .with_system(set_texture_to_repeat.pipe(log_errors)),
+                    .with_system(set_texture_to_repeat.pipe(log_errors))
+                    .with_system(set_foliage_material.pipe(log_errors))
+                    .with_system(update_foliage_wind.after(set_foliage_material))
+                    .with_system(update_outline_material.pipe(log_errors)),
             );
     }
 }
@@ -39,26 +46,49 @@ pub struct Materials {
     pub glowy: Handle<GlowyMaterial>,
     /// (Texture asset ID, Repeats) -> RepeatedMaterial
     pub repeated: HashMap<(HandleId, Repeats), Handle<RepeatedMaterial>>,
+    /// Texture asset ID -> FoliageMaterial
+    pub foliage: HashMap<HandleId, Handle<FoliageMaterial>>,
     pub skydome: Handle<SkydomeMaterial>,
+    /// Shared by all [`OutlineHull`]s
+    pub outline: Handle<OutlineMaterial>,
+    pub platform: Handle<StandardMaterial>,
 }
 
 fn setup_shader(
     mut commands: Commands,
     mut glow_materials: ResMut<Assets<GlowyMaterial>>,
     mut skydome_materials: ResMut<Assets<SkydomeMaterial>>,
-    texture_assets: Res<TextureAssets>,
+    mut outline_materials: ResMut<Assets<OutlineMaterial>>,
+    mut standard_materials: ResMut<Assets<StandardMaterial>>,
+    texture_assets: Option<Res<TextureAssets>>,
 ) {
+    // Missing when loading failed
+    let texture_assets = match texture_assets {
+        Some(texture_assets) => texture_assets,
+        None => return,
+    };
     let glowy = glow_materials.add(GlowyMaterial {
         env_texture: texture_assets.glowy_interior.clone(),
     });
     let skydome = skydome_materials.add(SkydomeMaterial {
         env_texture: texture_assets.sky.clone(),
+        zenith_color: Color::WHITE,
+        horizon_color: Color::WHITE,
+    });
+    let outline = outline_materials.add(OutlineMaterial { outline: default() });
+    let platform = standard_materials.add(StandardMaterial {
+        base_color: Color::rgb(0.4, 0.35, 0.3),
+        perceptual_roughness: 0.9,
+        ..default()
     });
 
     commands.insert_resource(Materials {
         repeated: HashMap::new(),
+        foliage: HashMap::new(),
         glowy,
         skydome,
+        outline,
+        platform,
     });
 }
 
@@ -80,10 +110,15 @@ impl Material for GlowyMaterial {
 #[derive(AsBindGroup, Debug, Clone, TypeUuid)]
 #[uuid = "8ca95d76-91d6-44c0-a67b-8a4d22cd59b1"]
 /// Material for [`skydome.wgsl`](https://github.com/janhohenheim/foxtrot/blob/main/assets/shaders/skydome.wgsl).
+/// The texture is tinted with a gradient from the horizon color to the zenith color, which follow the time of day.
 pub struct SkydomeMaterial {
     #[texture(0)]
     #[sampler(1)]
     pub env_texture: Handle<Image>,
+    #[uniform(2)]
+    pub zenith_color: Color,
+    #[uniform(3)]
+    pub horizon_color: Color,
 }
 
 impl Material for SkydomeMaterial {
@@ -174,3 +209,195 @@ pub fn set_texture_to_repeat(
     }
     Ok(())
 }
+
+#[repr(C, align(16))] // All WebGPU uniforms must be aligned to 16 bytes
+#[derive(Clone, Copy, ShaderType, Debug, PartialEq, Default)]
+pub struct WindUniform {
+    /// Horizontal direction as (x, z)
+    pub direction: Vec2,
+    pub strength: f32,
+    pub frequency: f32,
+    /// Time in seconds that the sway is based on
+    pub time: f32,
+    pub _wasm_padding1: f32,
+    pub _wasm_padding2: f32,
+    pub _wasm_padding3: f32,
+}
+
+#[derive(AsBindGroup, Debug, Clone, TypeUuid)]
+#[uuid = "3f5b9c2e-8d41-4a7e-b6f0-1c9e2d7a4b58"]
+/// Material for [`foliage.wgsl`](https://github.com/janhohenheim/foxtrot/blob/main/assets/shaders/foliage.wgsl).
+/// Sways in the [`Wind`] the further its vertices are above the mesh's origin, so the origin should be at the ground.
+/// All foliage with the same texture shares one material, so that updating the wind every frame stays cheap.
+pub struct FoliageMaterial {
+    #[texture(0)]
+    #[sampler(1)]
+    pub texture: Handle<Image>,
+    #[uniform(2)]
+    pub wind: WindUniform,
+}
+
+impl Material for FoliageMaterial {
+    fn vertex_shader() -> ShaderRef {
+        "shaders/foliage.wgsl".into()
+    }
+
+    fn fragment_shader() -> ShaderRef {
+        "shaders/foliage.wgsl".into()
+    }
+
+    fn alpha_mode(&self) -> AlphaMode {
+        AlphaMode::Mask(0.5)
+    }
+
+    fn specialize(
+        _pipeline: &MaterialPipeline<Self>,
+        descriptor: &mut RenderPipelineDescriptor,
+        _layout: &MeshVertexBufferLayout,
+        _key: MaterialPipelineKey<Self>,
+    ) -> Result<(), SpecializedMeshPipelineError> {
+        // Leaves are usually single planes that are seen from both sides
+        descriptor.primitive.cull_mode = None;
+        Ok(())
+    }
+}
+
+/// Replaces the material of the meshes below nodes whose name contains `[foliage]` with a [`FoliageMaterial`]
+pub fn set_foliage_material(
+    mut commands: Commands,
+    added_name: Query<(&Name, &Children), Added<Name>>,
+    material_handles: Query<&Handle<StandardMaterial>>,
+    mut materials: ResMut<Materials>,
+    standard_materials: Res<Assets<StandardMaterial>>,
+    mut foliage_materials: ResMut<Assets<FoliageMaterial>>,
+) -> Result<()> {
+    for (name, children) in &added_name {
+        if !name.to_lowercase().contains("[foliage]") {
+            continue;
+        }
+        for child in children.iter() {
+            if let Ok(standard_material_handle) = material_handles.get(*child) {
+                let standard_material = standard_materials
+                    .get(standard_material_handle)
+                    .context("Failed to get standard material from handle")?;
+                let texture = standard_material.base_color_texture.as_ref().context(
+                    "Failed to get texture from standard material. Is the texture missing?",
+                )?;
+                let foliage_material = materials.foliage.entry(texture.id()).or_insert_with(|| {
+                    foliage_materials.add(FoliageMaterial {
+                        texture: texture.clone(),
+                        wind: default(),
+                    })
+                });
+
+                commands
+                    .entity(*child)
+                    .remove::<Handle<StandardMaterial>>()
+                    .insert(foliage_material.clone());
+            }
+        }
+    }
+    Ok(())
+}
+
+fn update_foliage_wind(
+    wind: Res<Wind>,
+    time: Res<Time>,
+    materials: Res<Materials>,
+    mut foliage_materials: ResMut<Assets<FoliageMaterial>>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("update_foliage_wind").entered();
+    let uniform = WindUniform {
+        direction: wind.direction,
+        strength: wind.strength,
+        frequency: wind.frequency,
+        time: time.elapsed_seconds(),
+        ..default()
+    };
+    for handle in materials.foliage.values() {
+        if let Some(material) = foliage_materials.get_mut(handle) {
+            material.wind = uniform;
+        }
+    }
+}
+
+#[repr(C, align(16))] // All WebGPU uniforms must be aligned to 16 bytes
+#[derive(Clone, Copy, ShaderType, Debug, PartialEq, Default)]
+pub struct OutlineUniform {
+    /// Linear RGBA
+    pub color: Vec4,
+    /// Width in pixels
+    pub thickness: f32,
+    /// Fraction by which the brightness pulses
+    pub pulse_amount: f32,
+    /// Pulses per second
+    pub pulse_frequency: f32,
+    /// Time in seconds that the pulse is based on
+    pub time: f32,
+}
+
+#[derive(AsBindGroup, Debug, Clone, TypeUuid)]
+#[uuid = "a1d7e3f4-52c8-4b9a-8e6d-0f3b7c2a9d15"]
+/// Material for [`outline.wgsl`](https://github.com/janhohenheim/foxtrot/blob/main/assets/shaders/outline.wgsl).
+/// Draws an unlit hull around a mesh when put on a copy of it, see [`OutlineHull`].
+pub struct OutlineMaterial {
+    #[uniform(0)]
+    pub outline: OutlineUniform,
+}
+
+impl Material for OutlineMaterial {
+    fn vertex_shader() -> ShaderRef {
+        "shaders/outline.wgsl".into()
+    }
+
+    fn fragment_shader() -> ShaderRef {
+        "shaders/outline.wgsl".into()
+    }
+
+    fn specialize(
+        _pipeline: &MaterialPipeline<Self>,
+        descriptor: &mut RenderPipelineDescriptor,
+        _layout: &MeshVertexBufferLayout,
+        _key: MaterialPipelineKey<Self>,
+    ) -> Result<(), SpecializedMeshPipelineError> {
+        // Only the back faces peek out around the mesh
+        descriptor.primitive.cull_mode = Some(Front);
+        Ok(())
+    }
+}
+
+/// A child of a mesh entity that renders its mesh again with the [`OutlineMaterial`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Component, Default)]
+pub struct OutlineHull;
+
+fn update_outline_material(
+    time: Res<Time>,
+    materials: Res<Materials>,
+    mut outline_materials: ResMut<Assets<OutlineMaterial>>,
+    hull_query: Query<(), With<OutlineHull>>,
+    config_handles: Res<ConfigAssets>,
+    config: Res<Assets<GameConfig>>,
+) -> Result<()> {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("update_outline_material").entered();
+    // Uploading the material is only worth it while it is visible
+    if hull_query.is_empty() {
+        return Ok(());
+    }
+    let config = config
+        .get(&config_handles.game)
+        .context("Failed to get game config from handle")?;
+    let [red, green, blue] = config.interaction.outline_color;
+    let outline = OutlineUniform {
+        color: Color::rgb(red, green, blue).as_linear_rgba_f32().into(),
+        thickness: config.interaction.outline_thickness,
+        pulse_amount: config.interaction.outline_pulse,
+        pulse_frequency: config.interaction.outline_pulse_frequency,
+        time: time.elapsed_seconds(),
+    };
+    if let Some(material) = outline_materials.get_mut(&materials.outline) {
+        material.outline = outline;
+    }
+    Ok(())
+}
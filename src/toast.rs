@@ -0,0 +1,67 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContext};
+
+/// Shows short notifications at the top of the screen, e.g. after a quick save.
+/// Send a [`Toast`] to show one. Toasts fade out after a few seconds.
+pub struct ToastPlugin;
+
+impl Plugin for ToastPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<Toast>()
+            .init_resource::<ActiveToasts>()
+            .add_system(show_toasts);
+    }
+}
+
+/// Time in seconds a toast is shown for
+const TOAST_DURATION: f32 = 2.5;
+/// Time in seconds at the end of [`TOAST_DURATION`] during which a toast fades out
+const TOAST_FADE_DURATION: f32 = 0.5;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Toast {
+    /// Already localized text
+    pub text: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Resource, Default)]
+struct ActiveToasts(Vec<(Toast, f32)>);
+
+fn show_toasts(
+    time: Res<Time>,
+    mut toast_events: EventReader<Toast>,
+    mut active_toasts: ResMut<ActiveToasts>,
+    mut egui_context: ResMut<EguiContext>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("show_toasts").entered();
+    // Real time, as toasts are also shown while the game is paused
+    let dt = time.raw_delta_seconds();
+    for (_, remaining) in active_toasts.0.iter_mut() {
+        *remaining -= dt;
+    }
+    active_toasts.0.retain(|(_, remaining)| *remaining > 0.);
+    active_toasts.0.extend(
+        toast_events
+            .iter()
+            .map(|toast| (toast.clone(), TOAST_DURATION)),
+    );
+    if active_toasts.0.is_empty() {
+        return;
+    }
+
+    egui::Area::new("toasts")
+        .anchor(egui::Align2::CENTER_TOP, egui::Vec2::new(0., 30.))
+        .interactable(false)
+        .show(egui_context.ctx_mut(), |ui| {
+            for (toast, remaining) in active_toasts.0.iter() {
+                let alpha = (remaining / TOAST_FADE_DURATION).min(1.);
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.label(
+                        egui::RichText::new(&toast.text)
+                            .color(ui.visuals().text_color().linear_multiply(alpha)),
+                    );
+                });
+            }
+        });
+}
@@ -1,2 +1,6 @@
+#[cfg(test)]
+pub(crate) mod headless;
 pub mod log_error;
+#[cfg(test)]
+pub(crate) mod test_util;
 pub mod trait_extension;
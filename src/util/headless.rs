@@ -0,0 +1,312 @@
+use crate::file_system_interaction::asset_loading::{AnimationAssets, ConfigAssets, SceneAssets};
+use crate::file_system_interaction::config::GameConfig;
+use crate::file_system_interaction::game_state_serialization::GameStateSerializationPlugin;
+use crate::file_system_interaction::level_serialization::{CurrentLevel, WorldLoadRequest};
+use crate::file_system_interaction::thumbnail::ThumbnailRequest;
+use crate::file_system_interaction::user_settings::UserSettings;
+use crate::level_instantiation::spawning::objects::camera::CameraSpawner;
+use crate::level_instantiation::spawning::objects::player::PlayerSpawner;
+use crate::level_instantiation::spawning::objects::primitives::BoxSpawner;
+use crate::level_instantiation::spawning::spawn::{
+    handle_despawn_requests, handle_spawn_requests, spawn_delayed, spawn_requested,
+    DelayedSpawnEvents, DespawnedObjects, NextRuntimeSpawnId, SpawnQueue,
+};
+use crate::level_instantiation::spawning::{
+    DelayedSpawnEvent, DespawnRequest, GameObject, GameObjectSpawner,
+    PrimedGameObjectSpawnerImplementor, SpawnEvent, SpawnRequest, SpawnTracker,
+};
+use crate::movement::climbing::ClimbingPlugin;
+use crate::movement::dash::DashPlugin;
+use crate::movement::footsteps::FootstepPlugin;
+use crate::movement::general_movement::GeneralMovementPlugin;
+use crate::movement::physics::PhysicsPlugin;
+use crate::movement::platform::PlatformPlugin;
+use crate::movement::water::WaterPlugin;
+use crate::player_control::actions::{remove_actions_when_frozen, PlayerAction};
+use crate::player_control::camera::IngameCamera;
+use crate::player_control::player_embodiment::Player;
+use crate::player_control::PlayerControlPlugin;
+use crate::shader::Materials;
+use crate::util::log_error::log_errors;
+use crate::world_interaction::animated_prop::AnimatedPropPlugin;
+use crate::world_interaction::checkpoint::LastCheckpoint;
+use crate::world_interaction::condition::ActiveConditions;
+use crate::world_interaction::dialog::DialogEvent;
+use crate::world_interaction::inventory::Inventory;
+use crate::world_interaction::speedrun::SpeedrunTimer;
+use crate::world_interaction::time_of_day::TimeOfDay;
+use crate::world_interaction::trigger::{FiredTriggers, TriggerEvent};
+use crate::GameState;
+use bevy::gltf::Gltf;
+use bevy::input::InputPlugin;
+use bevy::time::TimePlugin;
+use bevy::utils::{HashMap, Instant};
+use bevy_rapier3d::prelude::*;
+use leafwing_input_manager::axislike::DualAxisData;
+use leafwing_input_manager::plugin::InputManagerSystem;
+use leafwing_input_manager::prelude::*;
+use std::time::Duration;
+
+/// Duration of one update of the headless app in seconds. Both [`Time`] and the physics advance by exactly this much,
+/// so that tests behave the same no matter how fast they run.
+pub(crate) const FRAME_TIME: f32 = 1. / 60.;
+
+/// Builds an app that runs the movement, player control, animated prop and save game plugins without a window, rendering or audio.
+/// The game starts out in [`GameState::Playing`] with the config from `assets/config/config.game.toml`, but without a level.
+/// Things that are normally provided by the level and by plugins that need assets are stubbed out,
+/// so objects have to be spawned by the test, see [`HeadlessApp`]. Only the player, the camera and boxes can be spawned.
+/// Saves and settings are kept in memory, see [`storage`](crate::file_system_interaction::storage::storage).
+pub(crate) fn build_headless_app() -> App {
+    let mut app = App::new();
+    let mut implementors: HashMap<
+        GameObject,
+        Box<dyn PrimedGameObjectSpawnerImplementor + Send + Sync>,
+    > = HashMap::new();
+    implementors.insert(GameObject::Player, Box::new(PlayerSpawner));
+    implementors.insert(GameObject::Camera, Box::new(CameraSpawner));
+    implementors.insert(GameObject::Box, Box::new(BoxSpawner));
+    app.add_plugins(MinimalPlugins.build().disable::<TimePlugin>())
+        .init_resource::<Time>()
+        .add_system_to_stage(CoreStage::First, advance_time)
+        .add_plugin(TransformPlugin)
+        .add_plugin(HierarchyPlugin)
+        .add_plugin(InputPlugin)
+        .add_plugin(AssetPlugin::default())
+        .add_asset::<Mesh>()
+        .add_asset::<Scene>()
+        .add_asset::<GameConfig>()
+        .add_state(GameState::Playing)
+        .add_plugin(PhysicsPlugin)
+        .insert_resource(RapierConfiguration {
+            timestep_mode: TimestepMode::Fixed {
+                dt: FRAME_TIME,
+                substeps: 1,
+            },
+            ..default()
+        })
+        .add_plugin(GeneralMovementPlugin)
+        .add_plugin(WaterPlugin)
+        .add_plugin(ClimbingPlugin)
+        .add_plugin(PlatformPlugin)
+        .add_plugin(DashPlugin)
+        .add_plugin(FootstepPlugin)
+        .add_plugin(PlayerControlPlugin)
+        .add_plugin(GameStateSerializationPlugin)
+        .add_plugin(AnimatedPropPlugin)
+        .add_asset::<Gltf>()
+        .add_asset::<AnimationClip>()
+        // Spawning without the meshes and scenes of the real spawner
+        .add_event::<SpawnEvent>()
+        .add_event::<DelayedSpawnEvent>()
+        .add_event::<SpawnRequest>()
+        .add_event::<DespawnRequest>()
+        .init_resource::<DelayedSpawnEvents>()
+        .init_resource::<NextRuntimeSpawnId>()
+        .init_resource::<DespawnedObjects>()
+        .init_resource::<SpawnQueue>()
+        .insert_resource(GameObjectSpawner::from_implementors(implementors))
+        .insert_resource(Materials {
+            glowy: default(),
+            repeated: default(),
+            foliage: default(),
+            skydome: default(),
+            outline: default(),
+            platform: default(),
+        })
+        .insert_resource(AnimationAssets {
+            character_idle: default(),
+            character_walking: default(),
+            character_running: default(),
+        })
+        .insert_resource(SceneAssets {
+            character: default(),
+            level: default(),
+            level_gltf: default(),
+        })
+        .add_system(handle_spawn_requests.before(spawn_requested))
+        .add_system(spawn_delayed.before(spawn_requested))
+        .add_system(spawn_requested.pipe(log_errors))
+        .add_system(handle_despawn_requests)
+        // What the level and world interaction plugins would provide
+        .add_event::<WorldLoadRequest>()
+        .add_event::<ThumbnailRequest>()
+        .add_event::<DialogEvent>()
+        .add_event::<TriggerEvent>()
+        .insert_resource(CurrentLevel {
+            scene: "headless".to_string(),
+        })
+        .init_resource::<ActiveConditions>()
+        .init_resource::<Inventory>()
+        .init_resource::<FiredTriggers>()
+        .init_resource::<LastCheckpoint>()
+        .init_resource::<TimeOfDay>()
+        .init_resource::<SpeedrunTimer>()
+        .init_resource::<UserSettings>()
+        .init_resource::<ScriptedInput>()
+        .add_system_to_stage(CoreStage::PreUpdate, detach_player_input)
+        .add_system_to_stage(
+            CoreStage::PreUpdate,
+            apply_scripted_input
+                .after(InputManagerSystem::ManualControl)
+                .before(remove_actions_when_frozen),
+        );
+
+    let config: GameConfig = toml::from_str(include_str!("../../assets/config/config.game.toml"))
+        .expect("Failed to parse game config");
+    let game = app.world.resource_mut::<Assets<GameConfig>>().add(config);
+    app.insert_resource(ConfigAssets {
+        game,
+        levels: default(),
+    });
+    app
+}
+
+/// Player input for the next updates, see [`HeadlessApp::hold`] and [`HeadlessApp::set_movement`]
+#[derive(Debug, Clone, Resource, Default)]
+pub(crate) struct ScriptedInput {
+    held: Vec<PlayerAction>,
+    movement: Vec2,
+}
+
+/// Helpers for scripting a test in a [`build_headless_app`]
+pub(crate) trait HeadlessApp {
+    /// Spawns an object through the regular [`SpawnEvent`] and runs one update to do so
+    fn spawn_object(&mut self, object: GameObject, transform: Transform) -> Entity;
+    /// Spawns a fixed box collider, e.g. as ground or as a wall
+    fn spawn_fixed_box(&mut self, translation: Vec3, half_extents: Vec3) -> Entity;
+    /// Spawns a large floor at y = 0, the player at the origin and the camera behind the player, looking along -z.
+    /// Returns the player and the camera after the player has landed on the floor.
+    fn spawn_player_on_floor(&mut self) -> (Entity, Entity);
+    /// Keeps the action pressed until it is released
+    fn hold(&mut self, action: PlayerAction);
+    fn release(&mut self, action: PlayerAction);
+    /// Sets the movement input, where +y is forward as seen from the camera
+    fn set_movement(&mut self, movement: Vec2);
+    /// Runs `frames` updates of [`FRAME_TIME`] each
+    fn step(&mut self, frames: usize);
+    fn translation(&self, entity: Entity) -> Vec3;
+    fn player(&mut self) -> Option<Entity>;
+    fn camera(&mut self) -> Option<Entity>;
+}
+
+impl HeadlessApp for App {
+    fn spawn_object(&mut self, object: GameObject, transform: Transform) -> Entity {
+        let mut query = self.world.query_filtered::<Entity, With<SpawnTracker>>();
+        let before: Vec<_> = query.iter(&self.world).collect();
+        self.world.send_event(SpawnEvent {
+            object,
+            transform,
+            ..default()
+        });
+        self.update();
+        query
+            .iter(&self.world)
+            .find(|entity| !before.contains(entity))
+            .unwrap_or_else(|| panic!("{object:?} was not spawned"))
+    }
+
+    fn spawn_fixed_box(&mut self, translation: Vec3, half_extents: Vec3) -> Entity {
+        self.world
+            .spawn((
+                TransformBundle::from_transform(Transform::from_translation(translation)),
+                RigidBody::Fixed,
+                Collider::cuboid(half_extents.x, half_extents.y, half_extents.z),
+            ))
+            .id()
+    }
+
+    fn spawn_player_on_floor(&mut self) -> (Entity, Entity) {
+        self.spawn_fixed_box(Vec3::new(0., -0.5, 0.), Vec3::new(50., 0.5, 50.));
+        let player = self.spawn_object(GameObject::Player, Transform::from_xyz(0., 1., 0.));
+        let camera = self.spawn_object(
+            GameObject::Camera,
+            Transform::from_xyz(0., 1., 5.).looking_at(Vec3::new(0., 0.5, 0.), Vec3::Y),
+        );
+        self.step(60);
+        (player, camera)
+    }
+
+    fn hold(&mut self, action: PlayerAction) {
+        let mut input = self.world.resource_mut::<ScriptedInput>();
+        if !input.held.iter().any(|held| held.index() == action.index()) {
+            input.held.push(action);
+        }
+    }
+
+    fn release(&mut self, action: PlayerAction) {
+        self.world
+            .resource_mut::<ScriptedInput>()
+            .held
+            .retain(|held| held.index() != action.index());
+    }
+
+    fn set_movement(&mut self, movement: Vec2) {
+        self.world.resource_mut::<ScriptedInput>().movement = movement;
+    }
+
+    fn step(&mut self, frames: usize) {
+        for _ in 0..frames {
+            self.update();
+        }
+    }
+
+    fn translation(&self, entity: Entity) -> Vec3 {
+        self.world
+            .get::<Transform>(entity)
+            .expect("Entity has no transform")
+            .translation
+    }
+
+    fn player(&mut self) -> Option<Entity> {
+        self.world
+            .query_filtered::<Entity, With<Player>>()
+            .iter(&self.world)
+            .next()
+    }
+
+    fn camera(&mut self) -> Option<Entity> {
+        self.world
+            .query_filtered::<Entity, With<IngameCamera>>()
+            .iter(&self.world)
+            .next()
+    }
+}
+
+fn advance_time(mut time: ResMut<Time>, mut now: Local<Option<Instant>>) {
+    let next = match *now {
+        Some(now) => now + Duration::from_secs_f32(FRAME_TIME),
+        None => Instant::now(),
+    };
+    *now = Some(next);
+    time.update_with_instant(next);
+}
+
+/// Stops the keyboard and mouse from overwriting the scripted actions of the player
+fn detach_player_input(
+    mut commands: Commands,
+    players: Query<Entity, (With<Player>, With<InputMap<PlayerAction>>)>,
+) {
+    for player in players.iter() {
+        commands.entity(player).remove::<InputMap<PlayerAction>>();
+    }
+}
+
+fn apply_scripted_input(
+    input: Res<ScriptedInput>,
+    mut players: Query<&mut ActionState<PlayerAction>, With<Player>>,
+) {
+    let is_moving = input.movement != Vec2::ZERO;
+    for mut actions in players.iter_mut() {
+        for action in PlayerAction::variants() {
+            let is_held = input.held.iter().any(|held| held.index() == action.index())
+                || is_moving && action.index() == PlayerAction::Move.index();
+            if is_held {
+                actions.press(action);
+            } else {
+                actions.release(action);
+            }
+        }
+        actions.action_data_mut(PlayerAction::Move).axis_pair =
+            Some(DualAxisData::from_xy(input.movement));
+    }
+}
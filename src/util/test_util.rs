@@ -0,0 +1,10 @@
+use bevy::prelude::*;
+
+pub(crate) fn assert_nearly_eq(actual: Vec3, expected: Vec3) {
+    assert!(
+        (actual - expected).length_squared() < 1e-5,
+        "expected: {:?}, actual: {:?}",
+        expected,
+        actual
+    );
+}
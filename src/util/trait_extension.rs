@@ -1,26 +1,56 @@
 use bevy::prelude::*;
 use bevy::render::mesh::{MeshVertexAttributeId, PrimitiveTopology, VertexAttributeValues};
 
+/// Vectors whose squared length is below this count as zero for [`Vec3Ext::is_approx_zero`] and [`Vec2Ext::is_approx_zero`].
+/// Such vectors have no meaningful direction, so they are never normalized.
+pub const APPROX_ZERO_LENGTH_SQUARED: f32 = 1e-5;
+
+/// Numbers whose absolute value is below this count as zero for [`F32Ext::is_approx_zero`]
+pub const APPROX_ZERO: f32 = 1e-5;
+
 pub trait Vec3Ext {
     #[allow(clippy::wrong_self_convention)] // Because [`Vec3`] is [`Copy`]
     fn is_approx_zero(self) -> bool;
+    /// Splits the vector into its part along `up` and the part perpendicular to it.
+    /// `up` does not need to be normalized. When it is approximately zero, the whole vector counts as horizontal.
     #[allow(clippy::wrong_self_convention)] // Because [`Vec3`] is [`Copy`]
     fn split(self, up: Vec3) -> SplitVec3;
+    /// The normalized vector, or `None` when it is approximately zero or not finite, i.e. when normalizing it would produce NaN
+    fn direction(self) -> Option<Vec3>;
+    /// The normalized vector, or `fallback` when it has no [`Vec3Ext::direction`]
+    fn normalize_or(self, fallback: Vec3) -> Vec3;
 }
 impl Vec3Ext for Vec3 {
     #[inline]
     fn is_approx_zero(self) -> bool {
-        self.length_squared() < 1e-5
+        self.length_squared() < APPROX_ZERO_LENGTH_SQUARED
     }
 
     fn split(self, up: Vec3) -> SplitVec3 {
-        let vertical = up * self.dot(up);
+        let vertical = match up.direction() {
+            Some(up) => up * self.dot(up),
+            None => Vec3::ZERO,
+        };
         let horizontal = self - vertical;
         SplitVec3 {
             vertical,
             horizontal,
         }
     }
+
+    #[inline]
+    fn direction(self) -> Option<Vec3> {
+        if self.is_approx_zero() || !self.is_finite() {
+            None
+        } else {
+            Some(self.normalize())
+        }
+    }
+
+    #[inline]
+    fn normalize_or(self, fallback: Vec3) -> Vec3 {
+        self.direction().unwrap_or(fallback)
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -33,17 +63,40 @@ impl SplitVec3 {
     pub fn as_array(self) -> [Vec3; 2] {
         [self.vertical, self.horizontal]
     }
+
+    /// The normalized horizontal part, or `None` when the vector was (almost) parallel to `up` or zero
+    pub fn horizontal_direction(self) -> Option<Vec3> {
+        self.horizontal.direction()
+    }
 }
 
 pub trait Vec2Ext {
     #[allow(clippy::wrong_self_convention)] // Because [`Vec2`] is [`Copy`]
     fn is_approx_zero(self) -> bool;
     fn x0y(self) -> Vec3;
+    /// The normalized vector, or `None` when it is approximately zero or not finite, i.e. when normalizing it would produce NaN
+    fn direction(self) -> Option<Vec2>;
+    /// The normalized vector, or `fallback` when it has no [`Vec2Ext::direction`]
+    fn normalize_or(self, fallback: Vec2) -> Vec2;
 }
 impl Vec2Ext for Vec2 {
     #[inline]
     fn is_approx_zero(self) -> bool {
-        self.length_squared() < 1e-5
+        self.length_squared() < APPROX_ZERO_LENGTH_SQUARED
+    }
+
+    #[inline]
+    fn direction(self) -> Option<Vec2> {
+        if self.is_approx_zero() || !self.is_finite() {
+            None
+        } else {
+            Some(self.normalize())
+        }
+    }
+
+    #[inline]
+    fn normalize_or(self, fallback: Vec2) -> Vec2 {
+        self.direction().unwrap_or(fallback)
     }
 
     #[inline]
@@ -142,7 +195,7 @@ pub trait F32Ext {
 impl F32Ext for f32 {
     #[inline]
     fn is_approx_zero(self) -> bool {
-        self.abs() < 1e-5
+        self.abs() < APPROX_ZERO
     }
 
     #[inline]
@@ -152,14 +205,128 @@ impl F32Ext for f32 {
 }
 
 pub trait TransformExt {
+    /// Turns around `up` to face `target`, or keeps the rotation when `target` is straight above or below
     fn horizontally_looking_at(self, target: Vec3, up: Vec3) -> Transform;
+    /// Like [`Transform::looking_at`], but `None` instead of a NaN rotation when `target` is at the translation
+    /// or straight along `up` from it, or when `up` is zero
+    fn try_looking_at(self, target: Vec3, up: Vec3) -> Option<Transform>;
+    #[allow(clippy::wrong_self_convention)] // Because [`Transform`] is [`Copy`]
+    fn is_finite(self) -> bool;
 }
 
 impl TransformExt for Transform {
     fn horizontally_looking_at(self, target: Vec3, up: Vec3) -> Transform {
-        let direction = target - self.translation;
-        let horizontal_direction = direction - up * direction.dot(up);
+        let horizontal_direction = (target - self.translation).split(up).horizontal;
         let look_target = self.translation + horizontal_direction;
-        self.looking_at(look_target, up)
+        self.try_looking_at(look_target, up).unwrap_or(self)
+    }
+
+    fn try_looking_at(self, target: Vec3, up: Vec3) -> Option<Transform> {
+        let forward = (target - self.translation).direction()?;
+        let up = up.direction()?;
+        if forward.cross(up).is_approx_zero() {
+            return None;
+        }
+        Some(self.looking_at(target, up))
+    }
+
+    fn is_finite(self) -> bool {
+        self.translation.is_finite() && self.rotation.is_finite() && self.scale.is_finite()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn split_adds_up_to_original() {
+        let vector = Vec3::new(1., 2., 3.);
+        let split = vector.split(Vec3::Y);
+        assert_eq!(split.vertical, Vec3::new(0., 2., 0.));
+        assert_eq!(split.horizontal, Vec3::new(1., 0., 3.));
+        assert_eq!(split.vertical + split.horizontal, vector);
+    }
+
+    #[test]
+    fn split_normalizes_up() {
+        let vector = Vec3::new(1., 2., 3.);
+        assert_eq!(vector.split(Vec3::Y * 4.), vector.split(Vec3::Y));
+        let tilted = vector.split(Vec3::new(1., 1., 0.) * 0.1);
+        assert!((tilted.vertical - Vec3::new(1.5, 1.5, 0.)).length() < 1e-5);
+        assert!(tilted.horizontal.dot(Vec3::new(1., 1., 0.)).abs() < 1e-5);
+    }
+
+    #[test]
+    fn split_along_zero_up_is_horizontal() {
+        let vector = Vec3::new(1., 2., 3.);
+        let split = vector.split(Vec3::ZERO);
+        assert_eq!(split.vertical, Vec3::ZERO);
+        assert_eq!(split.horizontal, vector);
+        assert_eq!(vector.split(Vec3::splat(f32::NAN)).horizontal, vector);
+    }
+
+    #[test]
+    fn vector_parallel_to_up_has_no_horizontal_direction() {
+        let split = Vec3::new(0., -3., 0.).split(Vec3::Y);
+        assert_eq!(split.vertical, Vec3::new(0., -3., 0.));
+        assert_eq!(split.horizontal_direction(), None);
+        let almost_parallel = Vec3::new(1e-4, 5., 0.).split(Vec3::Y);
+        assert_eq!(almost_parallel.horizontal_direction(), None);
+        let sideways = Vec3::new(0.5, 5., 0.).split(Vec3::Y);
+        assert_eq!(sideways.horizontal_direction(), Some(Vec3::X));
+    }
+
+    #[test]
+    fn near_zero_vectors_have_no_direction() {
+        assert_eq!(Vec3::ZERO.direction(), None);
+        assert_eq!(Vec3::splat(1e-3).direction(), None);
+        assert_eq!(Vec3::new(f32::NAN, 1., 0.).direction(), None);
+        assert_eq!(Vec3::new(f32::INFINITY, 0., 0.).direction(), None);
+        assert_eq!(Vec3::new(0., 0., -0.1).direction(), Some(Vec3::NEG_Z));
+        assert_eq!(Vec3::ZERO.normalize_or(Vec3::Y), Vec3::Y);
+        assert_eq!(Vec3::X.normalize_or(Vec3::Y), Vec3::X);
+
+        assert_eq!(Vec2::ZERO.direction(), None);
+        assert_eq!(Vec2::new(0., 2.).direction(), Some(Vec2::Y));
+        assert_eq!(Vec2::splat(1e-3).normalize_or(Vec2::X), Vec2::X);
+    }
+
+    #[test]
+    fn approx_zero_uses_same_threshold_for_every_dimension() {
+        let below = APPROX_ZERO_LENGTH_SQUARED.sqrt() * 0.99;
+        let above = APPROX_ZERO_LENGTH_SQUARED.sqrt() * 1.01;
+        assert!(Vec3::new(below, 0., 0.).is_approx_zero());
+        assert!(Vec2::new(below, 0.).is_approx_zero());
+        assert!(!Vec3::new(above, 0., 0.).is_approx_zero());
+        assert!(!Vec2::new(above, 0.).is_approx_zero());
+        assert!((APPROX_ZERO * 0.99).is_approx_zero());
+        assert!(!(APPROX_ZERO * 1.01).is_approx_zero());
+    }
+
+    #[test]
+    fn looking_at_degenerate_targets_is_rejected() {
+        let transform = Transform::from_xyz(1., 2., 3.);
+        assert_eq!(
+            transform.try_looking_at(transform.translation, Vec3::Y),
+            None
+        );
+        assert_eq!(
+            transform.try_looking_at(Vec3::new(1., 5., 3.), Vec3::Y),
+            None
+        );
+        assert_eq!(
+            transform.try_looking_at(Vec3::new(1., 2., 0.), Vec3::ZERO),
+            None
+        );
+        let looking = transform
+            .try_looking_at(Vec3::new(1., 2., 0.), Vec3::Y * 2.)
+            .unwrap();
+        assert!(looking.is_finite());
+        assert!((looking.forward() - Vec3::NEG_Z).length() < 1e-5);
+
+        let straight_above = transform.horizontally_looking_at(Vec3::new(1., 5., 3.), Vec3::Y);
+        assert_eq!(straight_above, transform);
+        assert!(!Transform::from_xyz(f32::NAN, 0., 0.).is_finite());
     }
 }
@@ -1,22 +1,58 @@
+pub mod animated_prop;
+pub mod checkpoint;
 pub mod condition;
 pub mod dialog;
+pub mod health;
 pub mod interactions_ui;
+pub mod inventory;
+pub mod look_at;
+pub mod speedrun;
+pub mod time_of_day;
+pub mod trigger;
+pub mod wind;
 
+use crate::world_interaction::animated_prop::AnimatedPropPlugin;
+use crate::world_interaction::checkpoint::CheckpointPlugin;
 use crate::world_interaction::condition::ConditionPlugin;
 use crate::world_interaction::dialog::DialogPlugin;
+use crate::world_interaction::health::HealthPlugin;
 use crate::world_interaction::interactions_ui::InteractionsUiPlugin;
+use crate::world_interaction::inventory::InventoryPlugin;
+use crate::world_interaction::look_at::LookAtPlugin;
+use crate::world_interaction::speedrun::SpeedrunPlugin;
+use crate::world_interaction::time_of_day::TimeOfDayPlugin;
+use crate::world_interaction::trigger::TriggerPlugin;
+use crate::world_interaction::wind::WindPlugin;
 use bevy::prelude::*;
 
 /// Handles player to world interactions. Split in to the following sub-plugins:
+/// - [`AnimatedPropPlugin`] handles doors, chests, elevators and other level objects that open and close
+/// - [`CheckpointPlugin`] handles respawning the player at the last checkpoint after falling off the map
 /// - [`ConditionPlugin`] handles trackers of player actions such as chosen dialog options
 /// - [`DialogPlugin`] handles dialog trees
+/// - [`HealthPlugin`] handles the player taking damage, dying and respawning
 /// - [`InteractionsUiPlugin`] handles the UI for interacting with an object in front of the player.
+/// - [`InventoryPlugin`] handles the items the player carries
+/// - [`LookAtPlugin`] handles characters turning their heads towards the player
+/// - [`SpeedrunPlugin`] handles timing how fast the player gets through a level
+/// - [`TimeOfDayPlugin`] handles the day/night cycle
+/// - [`TriggerPlugin`] handles invisible volumes that fire events when the player enters them
+/// - [`WindPlugin`] handles the wind that sways the foliage
 pub struct WorldInteractionPlugin;
 
 impl Plugin for WorldInteractionPlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugin(ConditionPlugin)
+        app.add_plugin(AnimatedPropPlugin)
+            .add_plugin(CheckpointPlugin)
+            .add_plugin(ConditionPlugin)
             .add_plugin(DialogPlugin)
-            .add_plugin(InteractionsUiPlugin);
+            .add_plugin(HealthPlugin)
+            .add_plugin(InteractionsUiPlugin)
+            .add_plugin(InventoryPlugin)
+            .add_plugin(LookAtPlugin)
+            .add_plugin(SpeedrunPlugin)
+            .add_plugin(TimeOfDayPlugin)
+            .add_plugin(TriggerPlugin)
+            .add_plugin(WindPlugin);
     }
 }
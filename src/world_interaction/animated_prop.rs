@@ -0,0 +1,958 @@
+use crate::file_system_interaction::asset_loading::SceneAssets;
+use crate::file_system_interaction::level_serialization::CurrentLevel;
+use crate::level_instantiation::spawning::objects::GameCollisionGroup;
+use crate::level_instantiation::spawning::SpawnId;
+use crate::movement::physics::get_physics_timestep;
+use crate::movement::platform::apply_platform_velocity;
+use crate::world_interaction::condition::{ActiveConditions, Condition};
+use crate::world_interaction::inventory::Inventory;
+use crate::world_interaction::time_of_day::TimeOfDay;
+use crate::world_interaction::trigger::{TriggerEdge, TriggerEvent};
+use crate::GameState;
+use bevy::animation::{Keyframes, VariableCurve};
+use bevy::ecs::system::EntityCommands;
+use bevy::gltf::Gltf;
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use bevy_rapier3d::prelude::*;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::f32::consts::{PI, TAU};
+use std::iter;
+use std::sync::LazyLock;
+
+/// Level objects like doors, chests and elevators that move between a closed and an open pose.
+/// A prop is either spawned with [`SpawnEvent::prop`](crate::level_instantiation::spawning::SpawnEvent::prop) set, or read from a node in the level scene named
+/// `Prop.<kind>.<id>` or `Prop.<kind>.<id>.<value>`, where the kind is one of
+/// - `door`, which turns around its up axis by `value` degrees, 90 by default,
+/// - `chest`, which turns around its right axis by `value` degrees, -110 by default, and opens only once,
+/// - `elevator`, which rises by `value` m, 4 by default,
+/// - `clip`, which plays the animation named `value` from the level's glTF file.
+///
+/// Such nodes are opened through the interaction prompt. `[once]`, `[toggle]` or `[auto]` in their name changes their [`PropMode`].
+/// Add `[collider]` to the name so that the prop blocks the way, its colliders move along with it.
+/// Props carry characters standing on them like a [`MovingPlatform`](crate::movement::platform::MovingPlatform).
+/// How far every prop is open is part of the save file, and an [`ObjectAnimationFinished`] is sent whenever a prop is fully open or closed.
+pub struct AnimatedPropPlugin;
+
+impl Plugin for AnimatedPropPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<AnimatedProp>()
+            .register_type::<PropProgress>()
+            .add_event::<PropInteractEvent>()
+            .add_event::<ObjectAnimationFinished>()
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(read_prop_nodes)
+                    .with_system(init_props.after(read_prop_nodes))
+                    .with_system(restore_prop_progress.after(init_props))
+                    .with_system(activate_props.after(restore_prop_progress))
+                    .with_system(
+                        animate_props
+                            .after(activate_props)
+                            .before(apply_platform_velocity),
+                    ),
+            );
+    }
+}
+
+/// Radius in m of the area around a prop in which the player can use it
+const INTERACTION_RADIUS: f32 = 1.5;
+/// Time in seconds a [`PropMode::AutoClose`] prop from the level scene stays open
+const DEFAULT_AUTO_CLOSE_DELAY: f32 = 3.;
+
+#[derive(Debug, Clone, PartialEq, Component, Reflect, FromReflect, Serialize, Deserialize)]
+#[reflect(Component, Serialize, Deserialize)]
+pub struct AnimatedProp {
+    /// Identifies the prop in save files and in [`ObjectAnimationFinished`]. Defaults to the prop's [`SpawnId`] or, for scene nodes, its name.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub animation: PropAnimation,
+    #[serde(default)]
+    pub trigger: PropTrigger,
+    #[serde(default)]
+    pub mode: PropMode,
+    /// Time in seconds to open or close. Ignored by [`PropAnimation::Clip`], which takes as long as its clip.
+    #[serde(default = "get_default_duration")]
+    pub duration: f32,
+    #[serde(default)]
+    pub easing: Easing,
+}
+
+fn get_default_duration() -> f32 {
+    1.
+}
+
+impl Default for AnimatedProp {
+    fn default() -> Self {
+        Self {
+            id: default(),
+            animation: default(),
+            trigger: default(),
+            mode: default(),
+            duration: get_default_duration(),
+            easing: default(),
+        }
+    }
+}
+
+/// How a prop moves from closed to open
+#[derive(Debug, Clone, PartialEq, Reflect, FromReflect, Serialize, Deserialize)]
+#[reflect(Serialize, Deserialize)]
+pub enum PropAnimation {
+    /// Moves the prop by `offset`, given in its parent's space, and turns it by `rotation`, given relative to its closed rotation.
+    /// Props turn around their origin, so a door's origin should be at its hinges.
+    Move {
+        #[serde(default)]
+        offset: Vec3,
+        #[serde(default)]
+        rotation: Quat,
+    },
+    /// Plays the clip with this name from the level's glTF file. Closing plays it backwards.
+    Clip(String),
+}
+
+impl Default for PropAnimation {
+    fn default() -> Self {
+        Self::Move {
+            offset: Vec3::ZERO,
+            rotation: Quat::IDENTITY,
+        }
+    }
+}
+
+/// What activates a prop, see [`PropMode`] for what it does then
+#[derive(Debug, Clone, PartialEq, Default, Reflect, FromReflect, Serialize, Deserialize)]
+#[reflect(Serialize, Deserialize)]
+pub enum PropTrigger {
+    /// The player uses the prop through the interaction prompt
+    #[default]
+    Interact,
+    /// A character enters a trigger with a [`TriggerAction::Event`](crate::world_interaction::trigger::TriggerAction::Event) of this name
+    TriggerEvent(String),
+    /// The condition becomes met, e.g. [`Condition::FlagSet`]. A [`PropMode::Toggle`] prop is open exactly while the condition is met.
+    Condition(Condition),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Reflect, FromReflect, Serialize, Deserialize)]
+#[reflect(Serialize, Deserialize)]
+pub enum PropMode {
+    /// Every activation opens a closed or closing prop and closes an open or opening one
+    #[default]
+    Toggle,
+    /// The first activation opens the prop for good
+    OneShot,
+    /// Every activation opens the prop, which closes again after it was open for this many seconds
+    AutoClose(f32),
+}
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Default, Reflect, FromReflect, Serialize, Deserialize,
+)]
+#[reflect(Serialize, Deserialize)]
+pub enum Easing {
+    Linear,
+    /// Starts slowly
+    EaseIn,
+    /// Ends slowly
+    EaseOut,
+    /// Starts and ends slowly
+    #[default]
+    EaseInOut,
+}
+
+impl Easing {
+    /// Maps the linear progress `t` from 0 to 1 to the eased one
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.clamp(0., 1.);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t,
+            Easing::EaseOut => t * (2. - t),
+            Easing::EaseInOut => t * t * (3. - 2. * t),
+        }
+    }
+}
+
+/// How far a prop is open. Part of the save file.
+#[derive(Debug, Clone, PartialEq, Reflect, FromReflect, Serialize, Deserialize, Default)]
+#[reflect(Serialize, Deserialize)]
+pub struct PropProgress {
+    /// 0 when closed, 1 when open. Linear in time, see [`Easing`].
+    pub openness: f32,
+    /// Whether the prop is opening or open, as opposed to closing or closed
+    pub opening: bool,
+    /// Time in seconds the prop has been fully open, see [`PropMode::AutoClose`]
+    #[serde(default)]
+    pub open_elapsed: f32,
+    /// Whether a [`PropMode::OneShot`] prop was already activated
+    #[serde(default)]
+    pub used: bool,
+}
+
+impl PropProgress {
+    /// Reacts to the prop being activated. Returns `false` if it cannot be activated anymore.
+    pub fn activate(&mut self, mode: PropMode) -> bool {
+        match mode {
+            PropMode::Toggle => self.opening = !self.opening,
+            PropMode::OneShot => {
+                if self.used {
+                    return false;
+                }
+                self.used = true;
+                self.opening = true;
+            }
+            PropMode::AutoClose(_) => {
+                self.opening = true;
+                self.open_elapsed = 0.;
+            }
+        }
+        true
+    }
+
+    /// Moves the prop `dt` seconds further along an animation taking `duration` seconds.
+    /// Returns `Some(true)` if it finished opening and `Some(false)` if it finished closing.
+    pub fn advance(&mut self, dt: f32, duration: f32, mode: PropMode) -> Option<bool> {
+        if let PropMode::AutoClose(delay) = mode {
+            if self.opening && self.openness >= 1. {
+                self.open_elapsed += dt;
+                if self.open_elapsed >= delay {
+                    self.opening = false;
+                    self.open_elapsed = 0.;
+                }
+            }
+        }
+        let target = if self.opening { 1. } else { 0. };
+        if self.openness == target {
+            return None;
+        }
+        let step = if duration > 0. { dt / duration } else { 1. };
+        self.openness = if self.opening {
+            (self.openness + step).min(1.)
+        } else {
+            (self.openness - step).max(0.)
+        };
+        (self.openness == target).then_some(self.opening)
+    }
+}
+
+/// Marks the props that the interaction prompt offers to use. Removed once a [`PropMode::OneShot`] prop was used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Component, Default)]
+pub struct InteractableProp;
+
+#[derive(Debug, Clone, PartialEq, Component, Default)]
+pub struct PropState {
+    /// Key of the prop in save files
+    pub key: Option<String>,
+    pub progress: PropProgress,
+    /// Transform relative to the parent when closed
+    closed: Transform,
+    /// Root of the scene whose nodes the clip of a [`PropAnimation::Clip`] animates
+    animation_root: Option<Entity>,
+    /// Whether the condition of a [`PropTrigger::Condition`] was met the last time it was checked
+    condition_met: Option<bool>,
+    /// Whether the prop jumps to its progress instead of moving there, e.g. after loading
+    snap: bool,
+}
+
+impl PropState {
+    /// Transform relative to the parent when closed, which is what is saved for the prop instead of its current one
+    pub fn closed(&self) -> Transform {
+        self.closed
+    }
+}
+
+/// Sent by the interaction prompt when the player uses a prop
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PropInteractEvent(pub Entity);
+
+/// Sent when a prop is fully open or closed, for game code to react to, e.g. by starting a dialog once a door opened
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObjectAnimationFinished {
+    pub prop: Entity,
+    /// Key of the prop in save files, see [`AnimatedProp::id`]
+    pub id: Option<String>,
+    /// Whether the prop finished opening instead of closing
+    pub opened: bool,
+}
+
+/// Prop progress read from a save file, keyed by [`PropState::key`].
+/// Applied to the props as soon as they are spawned.
+#[derive(Debug, Clone, PartialEq, Resource, Default)]
+pub struct PendingPropProgress(pub HashMap<String, PropProgress>);
+
+static PROP_NODE_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)^prop\.(door|chest|elevator|clip)\.([^\s\[.]+)(?:\.([^\s\[]+))?")
+        .expect("Failed to compile prop node regex")
+});
+
+fn read_prop_nodes(
+    mut commands: Commands,
+    added_name: Query<(Entity, &Name), (Added<Name>, Without<AnimatedProp>)>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("read_prop_nodes").entered();
+    for (entity, name) in &added_name {
+        if let Some(prop) = parse_prop_node(name) {
+            insert_prop(&mut commands.entity(entity), prop);
+        }
+    }
+}
+
+fn parse_prop_node(name: &str) -> Option<AnimatedProp> {
+    let captures = PROP_NODE_REGEX.captures(name)?;
+    let value = captures.get(3).map(|value| value.as_str());
+    let value_or = |default: f32| match value {
+        Some(value) => value.parse::<f32>().ok(),
+        None => Some(default),
+    };
+    let kind = captures[1].to_lowercase();
+    let (animation, mode, duration) = match kind.as_str() {
+        "door" => (
+            PropAnimation::Move {
+                offset: Vec3::ZERO,
+                rotation: Quat::from_rotation_y(value_or(90.)?.to_radians()),
+            },
+            PropMode::Toggle,
+            1.,
+        ),
+        "chest" => (
+            PropAnimation::Move {
+                offset: Vec3::ZERO,
+                rotation: Quat::from_rotation_x(value_or(-110.)?.to_radians()),
+            },
+            PropMode::OneShot,
+            1.,
+        ),
+        "elevator" => (
+            PropAnimation::Move {
+                offset: Vec3::Y * value_or(4.)?,
+                rotation: Quat::IDENTITY,
+            },
+            PropMode::Toggle,
+            4.,
+        ),
+        _ => (
+            PropAnimation::Clip(value?.to_string()),
+            PropMode::Toggle,
+            get_default_duration(),
+        ),
+    };
+    let lowercase_name = name.to_lowercase();
+    let mode = if lowercase_name.contains("[once]") {
+        PropMode::OneShot
+    } else if lowercase_name.contains("[toggle]") {
+        PropMode::Toggle
+    } else if lowercase_name.contains("[auto]") {
+        PropMode::AutoClose(DEFAULT_AUTO_CLOSE_DELAY)
+    } else {
+        mode
+    };
+    Some(AnimatedProp {
+        id: Some(captures[2].to_string()),
+        animation,
+        mode,
+        duration,
+        ..default()
+    })
+}
+
+/// Makes a freshly spawned object an animated prop.
+/// The rigid body is inserted right away so that the colliders spawned along with the object are attached to it.
+pub fn insert_prop(entity_commands: &mut EntityCommands, prop: AnimatedProp) {
+    let is_interactable = prop.trigger == PropTrigger::Interact;
+    entity_commands.insert((
+        prop,
+        RigidBody::KinematicPositionBased,
+        // Set by hand so that characters standing on the prop are carried along, see `apply_platform_velocity`
+        Velocity::default(),
+    ));
+    if is_interactable {
+        entity_commands
+            .insert(InteractableProp)
+            .with_children(|parent| {
+                parent.spawn((
+                    Name::new("Prop Interaction Collider"),
+                    TransformBundle::default(),
+                    Collider::ball(INTERACTION_RADIUS),
+                    Sensor,
+                    ActiveEvents::COLLISION_EVENTS,
+                    CollisionGroups::new(
+                        GameCollisionGroup::OTHER.into(),
+                        GameCollisionGroup::PLAYER.into(),
+                    ),
+                ));
+            });
+    }
+}
+
+fn init_props(
+    mut commands: Commands,
+    added_props: Query<
+        (
+            Entity,
+            &AnimatedProp,
+            &Transform,
+            Option<&SpawnId>,
+            Option<&Name>,
+        ),
+        Added<AnimatedProp>,
+    >,
+    current_level: Option<Res<CurrentLevel>>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("init_props").entered();
+    for (entity, prop, transform, spawn_id, name) in &added_props {
+        // Fallback keys are only unique within their level
+        let level = current_level
+            .as_ref()
+            .map(|level| level.scene.as_str())
+            .unwrap_or_default();
+        let key = prop
+            .id
+            .clone()
+            .or_else(|| spawn_id.map(|spawn_id| format!("{level}.{spawn_id:?}")))
+            .or_else(|| name.map(|name| format!("{level}.{name}")));
+        if key.is_none() {
+            warn!("Animated prop {entity:?} has no id, so it will be closed again after loading");
+        }
+        commands.entity(entity).insert(PropState {
+            key,
+            closed: *transform,
+            snap: true,
+            ..default()
+        });
+    }
+}
+
+fn restore_prop_progress(
+    mut commands: Commands,
+    pending: Option<ResMut<PendingPropProgress>>,
+    mut prop_query: Query<(Entity, &AnimatedProp, &mut PropState)>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("restore_prop_progress").entered();
+    let mut pending = match pending {
+        Some(pending) => pending,
+        None => return,
+    };
+    for (entity, prop, mut state) in &mut prop_query {
+        let saved_progress = state.key.as_ref().and_then(|key| pending.0.remove(key));
+        if let Some(saved_progress) = saved_progress {
+            if prop.mode == PropMode::OneShot && saved_progress.used {
+                commands.entity(entity).remove::<InteractableProp>();
+            }
+            state.progress = saved_progress;
+            state.snap = true;
+        }
+    }
+    if pending.0.is_empty() {
+        commands.remove_resource::<PendingPropProgress>();
+    }
+}
+
+fn activate_props(
+    mut commands: Commands,
+    mut interact_events: EventReader<PropInteractEvent>,
+    mut trigger_events: EventReader<TriggerEvent>,
+    mut prop_query: Query<(Entity, &AnimatedProp, &mut PropState)>,
+    active_conditions: Res<ActiveConditions>,
+    inventory: Res<Inventory>,
+    time_of_day: Res<TimeOfDay>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("activate_props").entered();
+    for PropInteractEvent(entity) in interact_events.iter() {
+        let (entity, prop, mut state) = match prop_query.get_mut(*entity) {
+            Ok(prop) => prop,
+            Err(_) => continue,
+        };
+        if prop.trigger != PropTrigger::Interact {
+            continue;
+        }
+        state.progress.activate(prop.mode);
+        if prop.mode == PropMode::OneShot {
+            commands.entity(entity).remove::<InteractableProp>();
+        }
+    }
+    for event in trigger_events.iter() {
+        if event.edge != TriggerEdge::Enter {
+            continue;
+        }
+        for (_entity, prop, mut state) in &mut prop_query {
+            if matches!(&prop.trigger, PropTrigger::TriggerEvent(name) if *name == event.name) {
+                state.progress.activate(prop.mode);
+            }
+        }
+    }
+
+    for (_entity, prop, mut state) in &mut prop_query {
+        let condition = match &prop.trigger {
+            PropTrigger::Condition(condition) => condition,
+            _ => continue,
+        };
+        let is_met = condition.is_met(&active_conditions, &inventory, &time_of_day);
+        let was_met = state.condition_met.replace(is_met);
+        if was_met == Some(is_met) {
+            continue;
+        }
+        match prop.mode {
+            // A prop whose condition is already met when it is spawned is open right away instead of opening in front of the player
+            PropMode::Toggle | PropMode::OneShot if was_met.is_none() => {
+                if is_met && state.progress.activate(prop.mode) {
+                    state.progress.opening = true;
+                    state.progress.openness = 1.;
+                    state.snap = true;
+                }
+            }
+            PropMode::Toggle => state.progress.opening = is_met,
+            _ if is_met => {
+                state.progress.activate(prop.mode);
+            }
+            _ => {}
+        }
+    }
+}
+
+fn animate_props(
+    time: Res<Time>,
+    rapier_configuration: Res<RapierConfiguration>,
+    mut prop_query: Query<(
+        Entity,
+        &AnimatedProp,
+        &mut PropState,
+        &mut Velocity,
+        Option<&Parent>,
+    )>,
+    mut transforms: Query<&mut Transform>,
+    global_transforms: Query<&GlobalTransform>,
+    parent_query: Query<&Parent>,
+    children_query: Query<&Children>,
+    names: Query<&Name>,
+    animation_players: Query<(), With<AnimationPlayer>>,
+    animation_clips: Res<Assets<AnimationClip>>,
+    gltfs: Res<Assets<Gltf>>,
+    scene_handles: Res<SceneAssets>,
+    mut finished_events: EventWriter<ObjectAnimationFinished>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("animate_props").entered();
+    let (dt, _substeps) = get_physics_timestep(&rapier_configuration, &time);
+    for (entity, prop, mut state, mut velocity, parent) in &mut prop_query {
+        let clip = match &prop.animation {
+            PropAnimation::Move { .. } => None,
+            PropAnimation::Clip(name) => {
+                if state.animation_root.is_none() {
+                    state.animation_root = find_animation_root(
+                        entity,
+                        &parent_query,
+                        &children_query,
+                        &animation_players,
+                    );
+                }
+                let clip = gltfs
+                    .get(&scene_handles.level_gltf)
+                    .and_then(|gltf| gltf.named_animations.get(name));
+                match (clip, state.animation_root) {
+                    (Some(clip), Some(animation_root)) => Some((clip, animation_root)),
+                    // The clip or the scene holding the prop might not be loaded yet
+                    _ => continue,
+                }
+            }
+        };
+        let duration = match clip {
+            Some((clip, _)) => match animation_clips.get(clip) {
+                Some(clip) => clip.duration(),
+                None => continue,
+            },
+            None => prop.duration,
+        };
+
+        let openness = state.progress.openness;
+        if let Some(opened) = state.progress.advance(dt, duration, prop.mode) {
+            finished_events.send(ObjectAnimationFinished {
+                prop: entity,
+                id: state.key.clone(),
+                opened,
+            });
+        }
+        if state.progress.openness == openness && !state.snap {
+            if *velocity != Velocity::zero() {
+                *velocity = Velocity::zero();
+            }
+            continue;
+        }
+        let eased = prop.easing.apply(state.progress.openness);
+        match (&prop.animation, clip) {
+            (PropAnimation::Move { offset, rotation }, _) => {
+                let mut transform = match transforms.get_mut(entity) {
+                    Ok(transform) => transform,
+                    Err(_) => continue,
+                };
+                let pose = get_pose(state.closed, *offset, *rotation, eased);
+                *velocity = if state.snap {
+                    Velocity::zero()
+                } else {
+                    let parent_transform = parent
+                        .and_then(|parent| global_transforms.get(parent.get()).ok())
+                        .copied()
+                        .unwrap_or_default();
+                    get_velocity(
+                        parent_transform
+                            .mul_transform(*transform)
+                            .compute_transform(),
+                        parent_transform.mul_transform(pose).compute_transform(),
+                        dt,
+                    )
+                };
+                *transform = pose;
+            }
+            (PropAnimation::Clip(_), Some((clip, animation_root))) => {
+                if let Some(clip) = animation_clips.get(clip) {
+                    sample_clip(
+                        clip,
+                        animation_root,
+                        eased * duration,
+                        &children_query,
+                        &names,
+                        &mut transforms,
+                    );
+                }
+            }
+            (PropAnimation::Clip(_), None) => {}
+        }
+        state.snap = false;
+    }
+}
+
+/// Transform of a prop that was closed at `closed` and is `eased_openness` of the way towards its open pose
+fn get_pose(closed: Transform, offset: Vec3, rotation: Quat, eased_openness: f32) -> Transform {
+    Transform {
+        translation: closed.translation + offset * eased_openness,
+        rotation: closed.rotation * Quat::IDENTITY.slerp(rotation, eased_openness),
+        scale: closed.scale,
+    }
+}
+
+/// Velocity that moves a body from `from` to `to` within `dt` seconds
+fn get_velocity(from: Transform, to: Transform, dt: f32) -> Velocity {
+    if dt <= 0. {
+        return Velocity::zero();
+    }
+    let (axis, angle) = (to.rotation * from.rotation.inverse()).to_axis_angle();
+    // Turn the short way around
+    let angle = if angle > PI { angle - TAU } else { angle };
+    Velocity {
+        linvel: (to.translation - from.translation) / dt,
+        angvel: axis * angle / dt,
+    }
+}
+
+/// The entity with the animation player of the prop's scene, which is the root the paths of the scene's clips start at.
+/// That is the prop itself, its closest ancestor with a player, or its first descendant with one.
+fn find_animation_root(
+    entity: Entity,
+    parent_query: &Query<&Parent>,
+    children_query: &Query<&Children>,
+    animation_players: &Query<(), With<AnimationPlayer>>,
+) -> Option<Entity> {
+    iter::successors(Some(entity), |entity| {
+        parent_query.get(*entity).ok().map(|parent| parent.get())
+    })
+    .find(|entity| animation_players.contains(*entity))
+    .or_else(|| {
+        let mut entities = vec![entity];
+        while let Some(entity) = entities.pop() {
+            if animation_players.contains(entity) {
+                return Some(entity);
+            }
+            if let Ok(children) = children_query.get(entity) {
+                entities.extend(children.iter().rev());
+            }
+        }
+        None
+    })
+}
+
+/// Poses the nodes below `root` that `clip` animates as they are `elapsed` seconds into it.
+/// The scene's [`AnimationPlayer`] is not used for this since it plays only one clip at a time,
+/// but every clip prop of a scene has to hold its own pose.
+fn sample_clip(
+    clip: &AnimationClip,
+    root: Entity,
+    elapsed: f32,
+    children_query: &Query<&Children>,
+    names: &Query<&Name>,
+    transforms: &mut Query<&mut Transform>,
+) {
+    for (path, curves) in clip.curves() {
+        let target = match find_path_target(root, &path.parts, children_query, names) {
+            Some(target) => target,
+            None => continue,
+        };
+        if let Ok(mut transform) = transforms.get_mut(target) {
+            for curve in curves {
+                sample_curve(curve, elapsed, &mut transform);
+            }
+        }
+    }
+}
+
+/// Follows the names of an [`EntityPath`](bevy::animation::EntityPath) down from `root`, whose own name is the first part, the same way an [`AnimationPlayer`] does
+fn find_path_target(
+    root: Entity,
+    parts: &[Name],
+    children_query: &Query<&Children>,
+    names: &Query<&Name>,
+) -> Option<Entity> {
+    let (root_name, parts) = parts.split_first()?;
+    if names.get(root).ok()? != root_name {
+        return None;
+    }
+    parts.iter().try_fold(root, |entity, part| {
+        children_query
+            .get(entity)
+            .ok()?
+            .iter()
+            .find(|child| names.get(**child).ok() == Some(part))
+            .copied()
+    })
+}
+
+fn sample_curve(curve: &VariableCurve, elapsed: f32, transform: &mut Transform) {
+    let timestamps = &curve.keyframe_timestamps;
+    if timestamps.is_empty() {
+        return;
+    }
+    let next = timestamps.partition_point(|timestamp| *timestamp <= elapsed);
+    let (from, to, t) = if next == 0 {
+        (0, 0, 0.)
+    } else if next == timestamps.len() {
+        (next - 1, next - 1, 0.)
+    } else {
+        let from = next - 1;
+        let t = (elapsed - timestamps[from]) / (timestamps[next] - timestamps[from]);
+        (from, next, t)
+    };
+    match &curve.keyframes {
+        Keyframes::Rotation(keyframes) => {
+            transform.rotation = keyframes[from].slerp(keyframes[to], t);
+        }
+        Keyframes::Translation(keyframes) => {
+            transform.translation = keyframes[from].lerp(keyframes[to], t);
+        }
+        Keyframes::Scale(keyframes) => {
+            transform.scale = keyframes[from].lerp(keyframes[to], t);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::file_system_interaction::game_state_serialization::{
+        delete_save_slot, GameLoadRequest, GameSaveRequest, SaveSlot,
+    };
+    use crate::level_instantiation::spawning::{GameObject, SpawnEvent};
+    use crate::util::headless::{build_headless_app, HeadlessApp};
+    use bevy::animation::EntityPath;
+
+    #[test]
+    fn parses_prop_nodes() {
+        let prop = parse_prop_node("Prop.door.front_gate [collider]").unwrap();
+        assert_eq!(prop.id, Some("front_gate".to_string()));
+        assert_eq!(prop.mode, PropMode::Toggle);
+        assert_eq!(prop.trigger, PropTrigger::Interact);
+
+        let prop = parse_prop_node("prop.elevator.tower.2.5 [auto]").unwrap();
+        assert_eq!(
+            prop.animation,
+            PropAnimation::Move {
+                offset: Vec3::Y * 2.5,
+                rotation: Quat::IDENTITY,
+            }
+        );
+        assert_eq!(prop.mode, PropMode::AutoClose(DEFAULT_AUTO_CLOSE_DELAY));
+
+        let prop = parse_prop_node("Prop.chest.loot").unwrap();
+        assert_eq!(prop.mode, PropMode::OneShot);
+
+        let prop = parse_prop_node("Prop.clip.well.LowerBucket").unwrap();
+        assert_eq!(
+            prop.animation,
+            PropAnimation::Clip("LowerBucket".to_string())
+        );
+
+        assert!(parse_prop_node("Prop.clip.well").is_none());
+        assert!(parse_prop_node("Prop.door.front.wide").is_none());
+        assert!(parse_prop_node("Prop.window.kitchen").is_none());
+        assert!(parse_prop_node("Door [collider]").is_none());
+    }
+
+    #[test]
+    fn progress_follows_mode() {
+        let mut progress = PropProgress::default();
+        assert!(progress.activate(PropMode::Toggle));
+        assert_eq!(progress.advance(0.5, 1., PropMode::Toggle), None);
+        assert_eq!(progress.advance(0.5, 1., PropMode::Toggle), Some(true));
+        assert_eq!(progress.advance(0.5, 1., PropMode::Toggle), None);
+        // Toggling while it moves turns it around from where it is
+        progress.activate(PropMode::Toggle);
+        progress.advance(0.25, 1., PropMode::Toggle);
+        progress.activate(PropMode::Toggle);
+        assert_eq!(progress.advance(0.25, 1., PropMode::Toggle), Some(true));
+
+        let mut progress = PropProgress::default();
+        assert!(progress.activate(PropMode::OneShot));
+        assert!(!progress.activate(PropMode::OneShot));
+        assert!(progress.opening);
+
+        let mode = PropMode::AutoClose(2.);
+        let mut progress = PropProgress::default();
+        progress.activate(mode);
+        assert_eq!(progress.advance(1., 1., mode), Some(true));
+        assert_eq!(progress.advance(1.5, 1., mode), None);
+        // The delay passes, so it starts closing in the same frame
+        assert_eq!(progress.advance(1., 1., mode), Some(false));
+        assert!(!progress.opening);
+    }
+
+    #[test]
+    fn velocity_reaches_pose_in_one_step() {
+        let closed = Transform::from_xyz(1., 0., 0.);
+        let rotation = Quat::from_rotation_y(TAU / 4.);
+        let from = get_pose(closed, Vec3::Y, rotation, 0.25);
+        let to = get_pose(closed, Vec3::Y, rotation, 0.5);
+        let dt = 0.1;
+        let velocity = get_velocity(from, to, dt);
+        assert!((velocity.linvel - Vec3::Y * 2.5).length() < 1e-4);
+        assert!((velocity.angvel - Vec3::Y * TAU / 4. * 2.5).length() < 1e-3);
+
+        let turned = Quat::from_scaled_axis(velocity.angvel * dt) * from.rotation;
+        assert!(turned.angle_between(to.rotation) < 1e-3);
+
+        assert_eq!(Easing::EaseInOut.apply(0.5), 0.5);
+        assert_eq!(Easing::EaseIn.apply(2.), 1.);
+    }
+
+    #[test]
+    fn open_runtime_prop_is_still_open_after_loading() {
+        let mut app = build_headless_app();
+        let (player, _camera) = app.spawn_player_on_floor();
+        let closed = Transform::from_xyz(5., 1., 0.);
+        let rotation = Quat::from_rotation_y(TAU / 4.);
+        app.world.send_event(SpawnEvent {
+            object: GameObject::Box,
+            transform: closed,
+            id: Some(SpawnId::Runtime(0)),
+            prop: Some(AnimatedProp {
+                animation: PropAnimation::Move {
+                    offset: Vec3::ZERO,
+                    rotation,
+                },
+                ..default()
+            }),
+            ..default()
+        });
+        app.step(1);
+        let prop = find_prop(&mut app);
+        app.world.send_event(PropInteractEvent(prop));
+        app.step(90);
+        let open = closed.rotation * rotation;
+        assert!(
+            app.world
+                .get::<Transform>(prop)
+                .unwrap()
+                .rotation
+                .angle_between(open)
+                < 1e-3
+        );
+
+        // Tests share an in-memory storage, see `storage`
+        let slot = SaveSlot::Named("animated prop test".to_string());
+        app.world.send_event(GameSaveRequest { slot: slot.clone() });
+        app.step(1);
+        // Loading a save normally replaces the level and with it everything spawned in it
+        app.world.entity_mut(player).despawn_recursive();
+        app.world.entity_mut(prop).despawn_recursive();
+        app.world.send_event(GameLoadRequest { slot: slot.clone() });
+        app.step(10);
+        delete_save_slot(&slot).unwrap();
+
+        let prop = find_prop(&mut app);
+        let loaded = app.world.get::<Transform>(prop).unwrap();
+        assert!(
+            loaded.rotation.angle_between(open) < 1e-3,
+            "prop was saved open but loaded at {:?}",
+            loaded.rotation
+        );
+        assert_eq!(loaded.translation, closed.translation);
+    }
+
+    #[test]
+    fn clips_of_one_scene_hold_their_own_pose() {
+        let mut app = App::new();
+        let door = app
+            .world
+            .spawn((Name::new("Door"), Transform::default()))
+            .id();
+        let bucket = app
+            .world
+            .spawn((Name::new("Bucket"), Transform::default()))
+            .id();
+        let root = app
+            .world
+            .spawn((Name::new("Scene"), Transform::default()))
+            .push_children(&[door, bucket])
+            .id();
+        let get_clip = |node: &str| {
+            let mut clip = AnimationClip::default();
+            clip.add_curve_to_path(
+                EntityPath {
+                    parts: vec![Name::new("Scene"), Name::new(node.to_string())],
+                },
+                VariableCurve {
+                    keyframe_timestamps: vec![0., 2.],
+                    keyframes: Keyframes::Translation(vec![Vec3::ZERO, Vec3::Y * 2.]),
+                },
+            );
+            clip
+        };
+        let door_clip = get_clip("Door");
+        let bucket_clip = get_clip("Bucket");
+        app.add_system(
+            move |children_query: Query<&Children>,
+                  names: Query<&Name>,
+                  mut transforms: Query<&mut Transform>| {
+                for (clip, elapsed) in [(&door_clip, 0.5), (&bucket_clip, 3.)] {
+                    sample_clip(
+                        clip,
+                        root,
+                        elapsed,
+                        &children_query,
+                        &names,
+                        &mut transforms,
+                    );
+                }
+            },
+        );
+        app.update();
+
+        assert_eq!(
+            app.world.get::<Transform>(door).unwrap().translation,
+            Vec3::Y * 0.5
+        );
+        assert_eq!(
+            app.world.get::<Transform>(bucket).unwrap().translation,
+            Vec3::Y * 2.
+        );
+    }
+
+    fn find_prop(app: &mut App) -> Entity {
+        app.world
+            .query_filtered::<Entity, With<AnimatedProp>>()
+            .iter(&app.world)
+            .next()
+            .expect("prop was not spawned")
+    }
+}
@@ -0,0 +1,341 @@
+use crate::file_system_interaction::asset_loading::ConfigAssets;
+use crate::file_system_interaction::autosave::AutosaveRequest;
+use crate::file_system_interaction::config::GameConfig;
+use crate::file_system_interaction::level_serialization::CurrentLevel;
+use crate::level_instantiation::level_transition::{LevelTransition, ScreenFade};
+use crate::movement::activity::Dormant;
+use crate::movement::dash::Dashing;
+use crate::movement::navigation::{Follower, NavigationPath};
+use crate::player_control::actions::ActionsFrozen;
+use crate::player_control::camera::{CameraForceSnap, IngameCamera};
+use crate::player_control::grapple::Grapple;
+use crate::player_control::player_embodiment::Player;
+use crate::util::log_error::log_errors;
+use crate::GameState;
+use anyhow::{Context, Result};
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::f32::consts::TAU;
+
+/// Brings the player back when they fall off the map.
+/// When the player drops below `world.kill_y`, a [`TriggerAction::Respawn`](crate::world_interaction::trigger::TriggerAction::Respawn) fires
+/// or the player died (see [`HealthPlugin`](crate::world_interaction::health::HealthPlugin)), the screen fades to black and they are put back at the [`LastCheckpoint`] together with their followers.
+/// A checkpoint is recorded when the player enters a [`GameObject::Checkpoint`](crate::level_instantiation::spawning::GameObject::Checkpoint)
+/// or arrives in a level, and is part of the save file.
+pub struct CheckpointPlugin;
+
+impl Plugin for CheckpointPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Checkpoint>()
+            .register_type::<LastCheckpoint>()
+            .init_resource::<LastCheckpoint>()
+            .add_event::<CheckpointReached>()
+            .add_event::<PlayerRespawnRequest>()
+            .add_event::<PlayerRespawned>()
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(record_level_start)
+                    .with_system(activate_checkpoints.after(record_level_start))
+                    .with_system(detect_falls.pipe(log_errors))
+                    .with_system(
+                        respawn_player
+                            .pipe(log_errors)
+                            .after(detect_falls)
+                            .after(activate_checkpoints),
+                    ),
+            );
+    }
+}
+
+/// Records itself as the [`LastCheckpoint`] when the player enters it.
+/// The player respawns at the checkpoint's translation, facing along its forward direction.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Component, Reflect, Serialize, Deserialize, Default,
+)]
+#[reflect(Component, Serialize, Deserialize)]
+pub struct Checkpoint;
+
+/// Where the player respawns
+#[derive(Debug, Clone, PartialEq, Resource, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Resource, Serialize, Deserialize)]
+pub struct LastCheckpoint {
+    /// Level the checkpoint belongs to. Checkpoints of other levels are replaced by the player's arrival point.
+    pub level: String,
+    pub transform: Transform,
+}
+
+/// Sent when the player enters a checkpoint other than the [`LastCheckpoint`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CheckpointReached {
+    pub transform: Transform,
+}
+
+/// Sends the player back to the [`LastCheckpoint`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PlayerRespawnRequest;
+
+/// Sent after the player was put back at the [`LastCheckpoint`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PlayerRespawned {
+    pub player: Entity,
+    pub transform: Transform,
+}
+
+/// A respawn in progress, i.e. the screen fading out or back in
+#[derive(Debug, Clone, Copy, PartialEq, Resource)]
+pub struct Respawning {
+    elapsed: f32,
+    teleported: bool,
+}
+
+fn record_level_start(
+    player_query: Query<&Transform, Added<Player>>,
+    current_level: Option<Res<CurrentLevel>>,
+    mut last_checkpoint: ResMut<LastCheckpoint>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("record_level_start").entered();
+    let current_level = match current_level {
+        Some(current_level) => current_level,
+        None => return,
+    };
+    // A checkpoint restored from a save file belongs to the current level and is kept
+    if last_checkpoint.level == current_level.scene {
+        return;
+    }
+    for transform in player_query.iter() {
+        *last_checkpoint = LastCheckpoint {
+            level: current_level.scene.clone(),
+            transform: get_respawn_transform(transform),
+        };
+    }
+}
+
+fn activate_checkpoints(
+    mut collision_events: EventReader<CollisionEvent>,
+    checkpoint_query: Query<&GlobalTransform, With<Checkpoint>>,
+    player_query: Query<(), With<Player>>,
+    current_level: Option<Res<CurrentLevel>>,
+    mut last_checkpoint: ResMut<LastCheckpoint>,
+    mut autosave_requests: EventWriter<AutosaveRequest>,
+    mut checkpoint_events: EventWriter<CheckpointReached>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("activate_checkpoints").entered();
+    let current_level = match current_level {
+        Some(current_level) => current_level,
+        None => return,
+    };
+    for event in collision_events.iter() {
+        let (entity_a, entity_b) = match event {
+            CollisionEvent::Started(entity_a, entity_b, _) => (*entity_a, *entity_b),
+            CollisionEvent::Stopped(..) => continue,
+        };
+        let checkpoint = match (
+            checkpoint_query.get(entity_a),
+            checkpoint_query.get(entity_b),
+        ) {
+            (Ok(checkpoint), _) if player_query.contains(entity_b) => checkpoint,
+            (_, Ok(checkpoint)) if player_query.contains(entity_a) => checkpoint,
+            _ => continue,
+        };
+        let checkpoint = LastCheckpoint {
+            level: current_level.scene.clone(),
+            transform: get_respawn_transform(&checkpoint.compute_transform()),
+        };
+        if *last_checkpoint != checkpoint {
+            info!("Reached checkpoint at {}", checkpoint.transform.translation);
+            checkpoint_events.send(CheckpointReached {
+                transform: checkpoint.transform,
+            });
+            *last_checkpoint = checkpoint;
+            autosave_requests.send(AutosaveRequest);
+        }
+    }
+}
+
+fn detect_falls(
+    player_query: Query<&Transform, With<Player>>,
+    respawning: Option<Res<Respawning>>,
+    level_transition: Option<Res<LevelTransition>>,
+    mut respawn_requests: EventWriter<PlayerRespawnRequest>,
+    config_handles: Res<ConfigAssets>,
+    config: Res<Assets<GameConfig>>,
+) -> Result<()> {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("detect_falls").entered();
+    if respawning.is_some() || level_transition.is_some() {
+        return Ok(());
+    }
+    let config = config
+        .get(&config_handles.game)
+        .context("Failed to get game config from handle")?;
+    if player_query
+        .iter()
+        .any(|transform| transform.translation.y < config.world.kill_y)
+    {
+        respawn_requests.send(PlayerRespawnRequest);
+    }
+    Ok(())
+}
+
+fn respawn_player(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut respawn_requests: EventReader<PlayerRespawnRequest>,
+    respawning: Option<ResMut<Respawning>>,
+    level_transition: Option<Res<LevelTransition>>,
+    last_checkpoint: Res<LastCheckpoint>,
+    mut screen_fade: ResMut<ScreenFade>,
+    mut actions_frozen: ResMut<ActionsFrozen>,
+    mut respawned_events: EventWriter<PlayerRespawned>,
+    mut snap_events: EventWriter<CameraForceSnap>,
+    mut player_query: Query<
+        (
+            Entity,
+            &mut Transform,
+            &mut Velocity,
+            &mut Dashing,
+            &mut Grapple,
+        ),
+        (With<Player>, Without<Follower>, Without<IngameCamera>),
+    >,
+    mut follower_query: Query<
+        (&mut Transform, &mut Velocity, Option<&mut NavigationPath>),
+        (
+            With<Follower>,
+            Without<Player>,
+            Without<Dormant>,
+            Without<IngameCamera>,
+        ),
+    >,
+    mut camera_query: Query<
+        (&mut IngameCamera, &mut Transform),
+        (Without<Player>, Without<Follower>),
+    >,
+    config_handles: Res<ConfigAssets>,
+    config: Res<Assets<GameConfig>>,
+) -> Result<()> {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("respawn_player").entered();
+    let requested = respawn_requests.iter().count() > 0;
+    let mut respawning = match respawning {
+        Some(respawning) => respawning,
+        None => {
+            // A level transition already places the player anew
+            if requested && level_transition.is_none() {
+                actions_frozen.freeze();
+                commands.insert_resource(Respawning {
+                    elapsed: 0.,
+                    teleported: false,
+                });
+            }
+            return Ok(());
+        }
+    };
+    let config = config
+        .get(&config_handles.game)
+        .context("Failed to get game config from handle")?;
+    let fade_duration = config.world.fade_duration.max(1e-5);
+    respawning.elapsed += time.delta_seconds();
+
+    if !respawning.teleported {
+        screen_fade.opacity = (respawning.elapsed / fade_duration).min(1.);
+        if respawning.elapsed < fade_duration {
+            return Ok(());
+        }
+        let target = last_checkpoint.transform;
+        for (entity, mut transform, mut velocity, mut dashing, mut grapple) in &mut player_query {
+            *transform = target;
+            *velocity = default();
+            dashing.end();
+            grapple.release();
+            for (mut camera, mut camera_transform) in &mut camera_query {
+                *camera_transform = camera.snap_to(target);
+            }
+            snap_events.send(CameraForceSnap);
+            respawned_events.send(PlayerRespawned {
+                player: entity,
+                transform: target,
+            });
+        }
+        let followers = follower_query.iter().count();
+        let spots = get_follower_spots(
+            target,
+            followers,
+            config.navigation.follow_stop_distance,
+            config.navigation.separation_radius,
+        );
+        for ((mut transform, mut velocity, path), spot) in follower_query.iter_mut().zip(spots) {
+            transform.translation = spot;
+            transform.look_at(target.translation, target.up());
+            *velocity = default();
+            if let Some(mut path) = path {
+                path.clear();
+                path.last_valid_point = None;
+            }
+        }
+        respawning.teleported = true;
+        respawning.elapsed = 0.;
+        return Ok(());
+    }
+
+    screen_fade.opacity = 1. - (respawning.elapsed / fade_duration).min(1.);
+    if respawning.elapsed >= fade_duration {
+        actions_frozen.unfreeze();
+        commands.remove_resource::<Respawning>();
+    }
+    Ok(())
+}
+
+/// Keeps only the heading of the given transform, so that the player stands upright after respawning
+fn get_respawn_transform(transform: &Transform) -> Transform {
+    let forward = Vec3::new(transform.forward().x, 0., transform.forward().z);
+    let forward = forward.try_normalize().unwrap_or(Vec3::NEG_Z);
+    Transform::from_translation(transform.translation)
+        .looking_at(transform.translation + forward, Vec3::Y)
+}
+
+/// Spots behind the respawning player on an arc of the given radius, spaced so that followers don't overlap
+fn get_follower_spots(target: Transform, count: usize, radius: f32, spacing: f32) -> Vec<Vec3> {
+    if count == 0 {
+        return vec![];
+    }
+    let up = target.up();
+    let spacing = (spacing / radius).min(TAU / count as f32);
+    (0..count)
+        .map(|index| {
+            let angle = (index as f32 - (count - 1) as f32 / 2.) * spacing;
+            target.translation + Quat::from_axis_angle(up, angle) * target.back() * radius
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn places_followers_behind_the_player() {
+        let target = Transform::from_xyz(10., 0., 0.);
+        let spots = get_follower_spots(target, 3, 2., 1.);
+        assert_eq!(spots.len(), 3);
+        // Behind means along +Z, as the target faces -Z
+        assert!(spots[1].abs_diff_eq(Vec3::new(10., 0., 2.), 1e-5));
+        for spot in &spots {
+            assert!((spot.distance(target.translation) - 2.).abs() < 1e-5);
+        }
+        assert!(spots[0].distance(spots[2]) > 1.);
+        assert!(get_follower_spots(target, 0, 2., 1.).is_empty());
+    }
+
+    #[test]
+    fn respawns_upright() {
+        let tilted = Transform::from_xyz(1., 2., 3.).looking_at(Vec3::new(1., 0., 0.), Vec3::Y);
+        let transform = get_respawn_transform(&tilted);
+        assert_eq!(transform.translation, Vec3::new(1., 2., 3.));
+        assert!(transform.up().abs_diff_eq(Vec3::Y, 1e-5));
+        assert!(transform.forward().abs_diff_eq(Vec3::NEG_Z, 1e-5));
+    }
+}
@@ -1,15 +1,26 @@
+use crate::world_interaction::inventory::Inventory;
+use crate::world_interaction::time_of_day::TimeOfDay;
 use crate::GameState;
 use bevy::prelude::*;
 use bevy::utils::HashSet;
 use serde::{Deserialize, Serialize};
 
+/// Tracks the flags that describe what the player has done, e.g. which dialog choices they picked.
+/// Flags are set through [`ConditionAddEvent`]s and unset through [`ConditionRemoveEvent`]s.
+/// A [`Condition`] checks these flags, the player's [`Inventory`] and the [`TimeOfDay`].
 pub struct ConditionPlugin;
 
 impl Plugin for ConditionPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<ActiveConditions>()
+            .register_type::<Condition>()
             .add_event::<ConditionAddEvent>()
-            .add_system_set(SystemSet::on_update(GameState::Playing).with_system(add_conditions));
+            .add_event::<ConditionRemoveEvent>()
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(add_conditions)
+                    .with_system(remove_conditions.after(add_conditions)),
+            );
     }
 }
 
@@ -45,6 +56,56 @@ impl From<ConditionId> for String {
 #[reflect(Serialize, Deserialize)]
 pub struct ConditionAddEvent(pub ConditionId);
 
+#[derive(Debug, Clone, Eq, PartialEq, Default, Reflect, Hash, Serialize, Deserialize)]
+#[reflect(Serialize, Deserialize)]
+pub struct ConditionRemoveEvent(pub ConditionId);
+
+/// A check on the game state, e.g. `FlagSet("met_guard")` or `HasItem("key")`.
+/// Conditions are evaluated whenever they are needed, so they always reflect the current state.
+#[derive(Debug, Clone, Eq, PartialEq, Reflect, FromReflect, Serialize, Deserialize)]
+#[reflect(Serialize, Deserialize)]
+pub enum Condition {
+    /// The flag is in the [`ActiveConditions`]
+    FlagSet(ConditionId),
+    /// The flag is not in the [`ActiveConditions`]
+    FlagNotSet(ConditionId),
+    /// The [`Inventory`] holds at least one of the item
+    HasItem(String),
+    /// The [`Inventory`] holds none of the item
+    LacksItem(String),
+    /// The sun is above the horizon
+    Day,
+    /// The sun is below the horizon
+    Night,
+    /// The [`TimeOfDay`] is at or after the first hour and before the second one, e.g. `HourBetween(22, 4)` for late at night
+    HourBetween(u32, u32),
+}
+
+impl Default for Condition {
+    fn default() -> Self {
+        Self::FlagSet(default())
+    }
+}
+
+impl Condition {
+    pub fn is_met(
+        &self,
+        active_conditions: &ActiveConditions,
+        inventory: &Inventory,
+        time_of_day: &TimeOfDay,
+    ) -> bool {
+        match self {
+            Condition::FlagSet(flag) => active_conditions.0.contains(flag),
+            Condition::FlagNotSet(flag) => !active_conditions.0.contains(flag),
+            Condition::HasItem(item) => inventory.has_item(item),
+            Condition::LacksItem(item) => !inventory.has_item(item),
+            Condition::Day => time_of_day.is_day(),
+            Condition::Night => !time_of_day.is_day(),
+            Condition::HourBetween(from, to) => time_of_day.is_between(*from as f32, *to as f32),
+        }
+    }
+}
+
 fn add_conditions(
     mut conditions: ResMut<ActiveConditions>,
     mut incoming_conditions: EventReader<ConditionAddEvent>,
@@ -53,3 +114,12 @@ fn add_conditions(
         conditions.0.insert(incoming_condition.0.clone());
     }
 }
+
+fn remove_conditions(
+    mut conditions: ResMut<ActiveConditions>,
+    mut removed_conditions: EventReader<ConditionRemoveEvent>,
+) {
+    for removed_condition in removed_conditions.iter() {
+        conditions.0.remove(&removed_condition.0);
+    }
+}
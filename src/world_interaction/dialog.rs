@@ -1,11 +1,19 @@
-use crate::file_system_interaction::asset_loading::DialogAssets;
+use crate::file_system_interaction::asset_loading::{ConfigAssets, DialogAssets};
+use crate::file_system_interaction::config::GameConfig;
+use crate::file_system_interaction::localization::Localization;
 use crate::player_control::actions::{ActionsFrozen, PlayerAction};
+use crate::player_control::player_embodiment::Player;
 use crate::util::log_error::log_errors;
-use crate::world_interaction::condition::{ActiveConditions, ConditionAddEvent, ConditionId};
-use crate::world_interaction::dialog::resources::Page;
+use crate::world_interaction::condition::{
+    ActiveConditions, ConditionAddEvent, ConditionId, ConditionRemoveEvent,
+};
 pub use crate::world_interaction::dialog::resources::{
-    CurrentDialog, Dialog, DialogEvent, DialogId, InitialPage, NextPage,
+    Branch, CurrentDialog, Dialog, DialogEvent, DialogId, DialogLoader, DialogVariables,
+    InitialPage, NextPage, PageId, Speaker,
 };
+use crate::world_interaction::inventory::{Inventory, ItemEffect};
+use crate::world_interaction::look_at::LookAtTarget;
+use crate::world_interaction::time_of_day::TimeOfDay;
 use crate::GameState;
 use anyhow::{Context, Ok, Result};
 use bevy::prelude::*;
@@ -25,11 +33,15 @@ impl Plugin for DialogPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugin(EguiPlugin)
             .register_type::<DialogId>()
+            .register_type::<DialogVariables>()
+            .init_resource::<DialogVariables>()
             .add_event::<DialogEvent>()
             .add_system_set(
                 SystemSet::on_update(GameState::Playing)
                     .with_system(set_current_dialog.pipe(log_errors))
-                    .with_system(show_dialog.pipe(log_errors)),
+                    .with_system(show_dialog.pipe(log_errors))
+                    .with_system(set_speaker.pipe(log_errors).after(show_dialog))
+                    .with_system(look_at_player_during_dialog.after(show_dialog)),
             );
     }
 }
@@ -42,6 +54,8 @@ pub struct DialogTarget {
 fn set_current_dialog(
     mut commands: Commands,
     active_conditions: Res<ActiveConditions>,
+    inventory: Res<Inventory>,
+    time_of_day: Res<TimeOfDay>,
     mut dialog_events: EventReader<DialogEvent>,
     dialogs: Res<Assets<Dialog>>,
     dialog_handles: Res<DialogAssets>,
@@ -77,7 +91,7 @@ fn set_current_dialog(
             dialog
                 .initial_page
                 .iter()
-                .find(|page| page.is_available(&active_conditions))
+                .find(|page| page.is_available(&active_conditions, &inventory, &time_of_day))
                 ?
                 .id
                 .clone()
@@ -89,6 +103,7 @@ fn set_current_dialog(
         })?;
         commands.insert_resource(CurrentDialog {
             source: dialog_event.source,
+            speaker: dialog_event.source,
             id: dialog_event.dialog.clone(),
             dialog: dialog.clone(),
             current_page,
@@ -99,27 +114,130 @@ fn set_current_dialog(
     Ok(())
 }
 
+/// Makes the character the player talks to look at them until the dialog ends, however it ends
+fn look_at_player_during_dialog(
+    current_dialog: Option<Res<CurrentDialog>>,
+    players: Query<Entity, With<Player>>,
+    mut look_at_targets: Query<&mut LookAtTarget>,
+    mut last_source: Local<Option<Entity>>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("look_at_player_during_dialog").entered();
+    let source = current_dialog.map(|current_dialog| current_dialog.source);
+    if source == *last_source {
+        return;
+    }
+    if let Some(mut look_at_target) =
+        last_source.and_then(|last| look_at_targets.get_mut(last).ok())
+    {
+        look_at_target.0 = None;
+    }
+    if let Some(mut look_at_target) = source.and_then(|source| look_at_targets.get_mut(source).ok())
+    {
+        look_at_target.0 = players.iter().next();
+    }
+    *last_source = source;
+}
+
+/// Which page the dialog window is showing and for how long
+#[derive(Debug, Clone, PartialEq, Default)]
+struct PageProgress {
+    page: Option<PageId>,
+    elapsed_time: f32,
+}
+
+/// Input that moves the dialog along without picking a specific choice
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+struct PacingInput {
+    /// Advance past a page without choices or exit the dialog at its end
+    confirm: bool,
+    /// Advance past a page without choices, but never exit the dialog
+    fast_forward: bool,
+}
+
 fn show_dialog(
     mut commands: Commands,
     current_dialog: Option<ResMut<CurrentDialog>>,
     active_conditions: Res<ActiveConditions>,
+    inventory: Res<Inventory>,
+    time_of_day: Res<TimeOfDay>,
+    dialog_variables: Res<DialogVariables>,
+    localization: Res<Localization>,
     mut condition_writer: EventWriter<ConditionAddEvent>,
+    mut condition_remover: EventWriter<ConditionRemoveEvent>,
+    mut item_effect_writer: EventWriter<ItemEffect>,
     mut egui_context: ResMut<EguiContext>,
     mut actions_frozen: ResMut<ActionsFrozen>,
     actions: Query<&ActionState<PlayerAction>>,
     time: Res<Time>,
-    mut elapsed_time: Local<f32>,
+    mut progress: Local<PageProgress>,
+    config_handles: Res<ConfigAssets>,
+    config: Res<Assets<GameConfig>>,
 ) -> Result<()> {
     let mut current_dialog = match current_dialog {
         Some(current_dialog) => current_dialog,
         None => {
-            *elapsed_time = 0.0;
+            *progress = default();
             return Ok(());
         }
     };
+    let config = config
+        .get(&config_handles.game)
+        .context("Failed to get game config from handle")?;
+
+    // Pages are advanced at most once per frame, so this runs exactly once for every page that is shown, even when skipping
+    if progress.page.as_ref() != Some(&current_dialog.current_page) {
+        let page = current_dialog.fetch_current_page()?;
+        let (_, missing_keys) = dialog_variables.interpolate(localization.get(&page.text));
+        for key in missing_keys {
+            warn!(
+                "Dialog variable \"{key}\" used in page {} of dialog {} is not set",
+                current_dialog.current_page.0, current_dialog.id.0
+            );
+        }
+        for flag in page.set_flags {
+            condition_writer.send(ConditionAddEvent(flag));
+        }
+        for flag in page.unset_flags {
+            condition_remover.send(ConditionRemoveEvent(flag));
+        }
+        item_effect_writer.send_batch(page.item_effects);
+        progress.page = Some(current_dialog.current_page.clone());
+        progress.elapsed_time = 0.0;
+    }
 
     for actions in actions.iter() {
         let current_page = current_dialog.fetch_current_page()?;
+        // Interpolated every frame so that the text reflects the current values
+        let (page_text, _) = dialog_variables.interpolate(localization.get(&current_page.text));
+        let reveal_duration = get_reveal_duration(
+            &page_text,
+            current_page.talking_speed,
+            config.dialog.letters_per_second,
+        );
+        let was_revealed = progress.elapsed_time >= reveal_duration;
+        let is_fast_forwarding = config.dialog.allow_skipping
+            && (actions.pressed(PlayerAction::SkipDialog)
+                || (actions.pressed(PlayerAction::ConfirmDialog)
+                    && actions
+                        .current_duration(PlayerAction::ConfirmDialog)
+                        .as_secs_f32()
+                        > config.dialog.fast_forward_hold_time));
+        let pacing_input = if was_revealed {
+            PacingInput {
+                confirm: actions.just_pressed(PlayerAction::ConfirmDialog),
+                fast_forward: is_fast_forwarding
+                    && progress.elapsed_time - reveal_duration
+                        >= config.dialog.fast_forward_page_time,
+            }
+        } else {
+            // Skipping the reveal consumes the press, so that a single press never skips a whole page
+            if actions.just_pressed(PlayerAction::ConfirmDialog) || is_fast_forwarding {
+                progress.elapsed_time = reveal_duration;
+            }
+            default()
+        };
+
         get_dialog_window()
             .show(egui_context.ctx_mut(), |ui| {
                 // Get current context style
@@ -128,11 +246,16 @@ fn show_dialog(
                 ui.set_width(dialog_size.x);
                 ui.set_height(dialog_size.y);
 
-                let dialog_text = create_dialog_rich_text(&current_page, *elapsed_time);
+                let dialog_text = create_dialog_rich_text(
+                    &page_text,
+                    current_page.talking_speed,
+                    config.dialog.letters_per_second,
+                    progress.elapsed_time,
+                );
                 ui.vertical(|ui| {
                     ui.add_space(5.);
                     ui.label(&dialog_text);
-                    if dialog_text == current_page.text {
+                    if dialog_text == page_text {
                         ui.add_space(3.);
                         ui.separator();
                         ui.add_space(8.);
@@ -141,11 +264,15 @@ fn show_dialog(
                             &mut commands,
                             &mut current_dialog,
                             &active_conditions,
+                            &inventory,
+                            &time_of_day,
+                            &localization,
                             &mut condition_writer,
                             &mut actions_frozen,
                             actions,
+                            pacing_input,
                             current_page.next_page,
-                            &mut elapsed_time,
+                            &mut progress,
                         )
                         .context("Failed to present dialog choices")?;
                     }
@@ -161,7 +288,42 @@ fn show_dialog(
         } else {
             1.
         };
-        *elapsed_time += time.delta_seconds() * dt_speed_multiplier;
+        progress.elapsed_time += time.delta_seconds() * dt_speed_multiplier;
+    }
+    Ok(())
+}
+
+fn set_speaker(
+    current_dialog: Option<ResMut<CurrentDialog>>,
+    player_query: Query<Entity, With<Player>>,
+    name_query: Query<(Entity, &Name)>,
+    mut warned_page: Local<Option<PageId>>,
+) -> Result<()> {
+    let mut current_dialog = match current_dialog {
+        Some(current_dialog) => current_dialog,
+        None => return Ok(()),
+    };
+    let speaker = match current_dialog.fetch_current_page()?.speaker {
+        Speaker::Source => Some(current_dialog.source),
+        Speaker::Player => player_query.iter().next(),
+        Speaker::Named(name) => {
+            let speaker = name_query
+                .iter()
+                .find(|(_, entity_name)| entity_name.as_str() == name)
+                .map(|(entity, _)| entity);
+            if speaker.is_none() && warned_page.as_ref() != Some(&current_dialog.current_page) {
+                warn!(
+                    "Speaker \"{name}\" of page {} in dialog {} not found, using the dialog's source instead",
+                    current_dialog.current_page.0, current_dialog.id.0
+                );
+                *warned_page = Some(current_dialog.current_page.clone());
+            }
+            speaker
+        }
+    };
+    let speaker = speaker.unwrap_or(current_dialog.source);
+    if current_dialog.speaker != speaker {
+        current_dialog.speaker = speaker;
     }
     Ok(())
 }
@@ -171,42 +333,59 @@ fn present_choices(
     commands: &mut Commands,
     current_dialog: &mut CurrentDialog,
     active_conditions: &ActiveConditions,
+    inventory: &Inventory,
+    time_of_day: &TimeOfDay,
+    localization: &Localization,
     condition_writer: &mut EventWriter<ConditionAddEvent>,
     actions_frozen: &mut ActionsFrozen,
     actions: &ActionState<PlayerAction>,
+    pacing_input: PacingInput,
     next_page: NextPage,
-    elapsed_time: &mut f32,
+    progress: &mut PageProgress,
 ) -> Result<()> {
     match next_page {
         NextPage::Continue(next_page_id) => {
-            let text = create_choice_rich_text(0, "Continue");
-            if ui.button(text).clicked() || actions.just_pressed(PlayerAction::NumberedChoice(1)) {
+            let text = create_choice_rich_text(0, localization.get("dialog.continue"));
+            if ui.button(text).clicked()
+                || actions.just_pressed(PlayerAction::NumberedChoice(1))
+                || pacing_input.confirm
+                || pacing_input.fast_forward
+            {
                 current_dialog.current_page = next_page_id;
-                *elapsed_time = 0.0;
+                // Also restarts the page if it continues with itself
+                progress.page = None;
             }
         }
         NextPage::Choice(choices) => {
             let mut picked_choice = None;
-            for (index, (choice_id, choice)) in choices
+            let mut index = 0;
+            for (choice_id, choice) in choices
                 .iter()
-                .filter(|(choice_id, choice)| {
-                    choice.is_available(active_conditions)
-                        && !was_just_picked(current_dialog, choice_id)
-                })
-                .enumerate()
+                .filter(|(choice_id, _)| !was_just_picked(current_dialog, choice_id))
             {
-                let text = create_choice_rich_text(index, &choice.text);
+                // Conditions are evaluated every frame so that choices react to the game state while the page is shown
+                if !choice.is_available(active_conditions, inventory, time_of_day) {
+                    if choice.show_when_unavailable {
+                        ui.add_enabled(
+                            false,
+                            egui::Button::new(format!("-. {}", localization.get(&choice.text))),
+                        );
+                    }
+                    continue;
+                }
+                let text = create_choice_rich_text(index, localization.get(&choice.text));
                 if ui.button(text).clicked()
                     || actions.just_pressed(PlayerAction::NumberedChoice(index as u16 + 1))
                 {
                     picked_choice = Some((choice_id.clone(), choice.clone()));
                 }
+                index += 1;
             }
             if let Some((choice_id, choice)) = picked_choice {
                 condition_writer.send(ConditionAddEvent(choice_id.clone()));
                 current_dialog.last_choice = Some(choice_id);
                 current_dialog.current_page = choice.next_page_id;
-                *elapsed_time = 0.0;
+                progress.page = None;
             }
         }
         NextPage::SameAs(other_page_id) => {
@@ -216,16 +395,53 @@ fn present_choices(
                 commands,
                 current_dialog,
                 active_conditions,
+                inventory,
+                time_of_day,
+                localization,
+                condition_writer,
+                actions_frozen,
+                actions,
+                pacing_input,
+                next_page,
+                progress,
+            )?;
+        }
+        NextPage::Branch(branches) => {
+            let next_page = branches
+                .into_iter()
+                .find(|branch| {
+                    branch
+                        .condition
+                        .as_ref()
+                        .map(|condition| {
+                            condition.is_met(active_conditions, inventory, time_of_day)
+                        })
+                        .unwrap_or(true)
+                })
+                .map(|branch| NextPage::Continue(branch.next_page_id))
+                .unwrap_or(NextPage::Exit);
+            present_choices(
+                ui,
+                commands,
+                current_dialog,
+                active_conditions,
+                inventory,
+                time_of_day,
+                localization,
                 condition_writer,
                 actions_frozen,
                 actions,
+                pacing_input,
                 next_page,
-                elapsed_time,
+                progress,
             )?;
         }
         NextPage::Exit => {
-            let text = create_choice_rich_text(0, "Exit");
-            if ui.button(text).clicked() || actions.just_pressed(PlayerAction::NumberedChoice(1)) {
+            let text = create_choice_rich_text(0, localization.get("dialog.exit"));
+            if ui.button(text).clicked()
+                || actions.just_pressed(PlayerAction::NumberedChoice(1))
+                || pacing_input.confirm
+            {
                 commands.remove_resource::<CurrentDialog>();
                 actions_frozen.unfreeze();
             }
@@ -258,10 +474,26 @@ fn set_dialog_style(style: &mut egui::Style) {
     style.visuals.widgets.noninteractive.fg_stroke.color = egui::Color32::from_gray(250);
 }
 
-fn create_dialog_rich_text(page: &Page, elapsed_time: f32) -> String {
-    const BASE_LETTERS_PER_SECOND: f32 = 60.;
-    let letters_to_display = (BASE_LETTERS_PER_SECOND * page.talking_speed * elapsed_time) as usize;
-    page.text.graphemes(true).take(letters_to_display).collect()
+fn create_dialog_rich_text(
+    text: &str,
+    talking_speed: f32,
+    letters_per_second: f32,
+    elapsed_time: f32,
+) -> String {
+    let letters_to_display = (letters_per_second * talking_speed * elapsed_time) as usize;
+    text.graphemes(true).take(letters_to_display).collect()
+}
+
+/// Time in seconds until [`create_dialog_rich_text`] shows all of `text`
+fn get_reveal_duration(text: &str, talking_speed: f32, letters_per_second: f32) -> f32 {
+    let letters = text.graphemes(true).count() as f32;
+    let letters_per_second = letters_per_second * talking_speed;
+    if letters_per_second > 0. {
+        // Slightly more than needed so that rounding down never hides the last letter
+        (letters + 0.5) / letters_per_second
+    } else {
+        0.
+    }
 }
 
 fn create_choice_rich_text(index: usize, text: &str) -> String {
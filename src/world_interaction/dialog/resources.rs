@@ -1,10 +1,16 @@
-use crate::world_interaction::condition::{ActiveConditions, ConditionId};
+use crate::world_interaction::condition::{ActiveConditions, Condition, ConditionId};
+use crate::world_interaction::inventory::{Inventory, ItemEffect};
+use crate::world_interaction::time_of_day::TimeOfDay;
 use anyhow::{Context, Result};
+use bevy::asset::{AssetLoader, LoadContext, LoadedAsset};
 use bevy::prelude::*;
 use bevy::reflect::TypeUuid;
-use bevy::utils::{HashMap, HashSet};
+use bevy::utils::{BoxedFuture, HashMap, HashSet};
 use indexmap::IndexMap;
+use regex::Regex;
 use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::LazyLock;
 
 #[derive(Debug, Clone, Eq, PartialEq, Reflect, Serialize, Deserialize, FromReflect)]
 #[reflect(Serialize, Deserialize)]
@@ -17,6 +23,8 @@ pub struct DialogEvent {
 #[derive(Debug, Clone, PartialEq, Resource, Serialize, Deserialize)]
 pub struct CurrentDialog {
     pub source: Entity,
+    /// Entity that speaks the current page, see [`Page::speaker`]
+    pub speaker: Entity,
     pub id: DialogId,
     pub dialog: Dialog,
     pub current_page: PageId,
@@ -40,6 +48,9 @@ impl CurrentDialog {
 pub struct Dialog {
     pub initial_page: Vec<InitialPage>,
     pub pages: HashMap<PageId, Page>,
+    /// Whether the game is autosaved when the dialog ends
+    #[serde(default)]
+    pub autosave: bool,
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Reflect, Serialize, Deserialize, Default, FromReflect)]
@@ -50,21 +61,46 @@ pub struct InitialPage {
     pub positive_requirements: HashSet<ConditionId>,
     #[serde(default, skip_serializing_if = "HashSet::is_empty")]
     pub negative_requirements: HashSet<ConditionId>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub condition: Option<Condition>,
 }
 
 impl InitialPage {
-    pub fn is_available(&self, active_conditions: &ActiveConditions) -> bool {
+    pub fn is_available(
+        &self,
+        active_conditions: &ActiveConditions,
+        inventory: &Inventory,
+        time_of_day: &TimeOfDay,
+    ) -> bool {
         self.positive_requirements.is_subset(&active_conditions.0)
             && self.negative_requirements.is_disjoint(&active_conditions.0)
+            && is_met(&self.condition, active_conditions, inventory, time_of_day)
     }
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Page {
+    /// Localization key of the text. Text that is not a key is shown as is.
     pub text: String,
     #[serde(default = "get_default_talking_speed")]
     pub talking_speed: f32,
     pub next_page: NextPage,
+    /// Path to a voice clip that is played when the page is shown, e.g. "audio/voices/greeting.ogg".
+    /// It is played through the speaker's [`AudioEmitter`](crate::file_system_interaction::audio::AudioEmitter) if they have one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub voice: Option<String>,
+    /// Who speaks the page. The camera frames the speaker.
+    #[serde(default, skip_serializing_if = "Speaker::is_source")]
+    pub speaker: Speaker,
+    /// Flags that are set when the page is shown
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub set_flags: Vec<ConditionId>,
+    /// Flags that are unset when the page is shown
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub unset_flags: Vec<ConditionId>,
+    /// Items that are given to or taken from the player when the page is shown
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub item_effects: Vec<ItemEffect>,
 }
 
 fn get_default_talking_speed() -> f32 {
@@ -77,16 +113,39 @@ impl Default for Page {
             text: default(),
             talking_speed: get_default_talking_speed(),
             next_page: default(),
+            voice: default(),
+            speaker: default(),
+            set_flags: default(),
+            unset_flags: default(),
+            item_effects: default(),
         }
     }
 }
 
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize, Default)]
+pub enum Speaker {
+    /// The entity the dialog was started with
+    #[default]
+    Source,
+    Player,
+    /// The entity with this [`Name`]. Falls back to the source if there is none.
+    Named(String),
+}
+
+impl Speaker {
+    fn is_source(&self) -> bool {
+        *self == Speaker::Source
+    }
+}
+
 #[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
 pub enum NextPage {
     /// There is only one automatic option for the next page
     Continue(PageId),
     /// The user can choose between different answers that determine the next page
     Choice(IndexMap<ConditionId, DialogChoice>),
+    /// Continue with the first branch whose condition is met. Exits the dialog if none is.
+    Branch(Vec<Branch>),
     /// Use `next_page` of the specified `Page`
     SameAs(PageId),
     /// Exit dialog after this page
@@ -98,25 +157,110 @@ impl Default for NextPage {
     }
 }
 
+#[derive(Debug, Clone, Eq, PartialEq, Default, Reflect, Serialize, Deserialize, FromReflect)]
+#[reflect(Serialize, Deserialize)]
+pub struct Branch {
+    /// Branches without a condition are always taken
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub condition: Option<Condition>,
+    pub next_page_id: PageId,
+}
+
 #[derive(Debug, Clone, Eq, PartialEq, Default, Reflect, Serialize, Deserialize, FromReflect)]
 #[reflect(Serialize, Deserialize)]
 pub struct DialogChoice {
-    /// The player's answer
+    /// Localization key of the player's answer. Text that is not a key is shown as is.
     pub text: String,
     pub next_page_id: PageId,
     #[serde(default, skip_serializing_if = "HashSet::is_empty")]
     pub positive_requirements: HashSet<ConditionId>,
     #[serde(default, skip_serializing_if = "HashSet::is_empty")]
     pub negative_requirements: HashSet<ConditionId>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub condition: Option<Condition>,
+    /// Show the choice greyed out instead of hiding it while it is not available
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub show_when_unavailable: bool,
 }
 
 impl DialogChoice {
-    pub fn is_available(&self, active_conditions: &ActiveConditions) -> bool {
+    pub fn is_available(
+        &self,
+        active_conditions: &ActiveConditions,
+        inventory: &Inventory,
+        time_of_day: &TimeOfDay,
+    ) -> bool {
         self.positive_requirements.is_subset(&active_conditions.0)
             && self.negative_requirements.is_disjoint(&active_conditions.0)
+            && is_met(&self.condition, active_conditions, inventory, time_of_day)
     }
 }
 
+/// Values that are substituted for `{key}` placeholders in dialog text whenever it is shown.
+/// Write `{{` and `}}` for literal braces.
+#[derive(Debug, Clone, Eq, PartialEq, Resource, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Resource, Serialize, Deserialize)]
+pub struct DialogVariables(pub HashMap<String, String>);
+
+impl DialogVariables {
+    pub fn set(&mut self, key: impl Into<String>, value: impl ToString) {
+        self.0.insert(key.into(), value.to_string());
+    }
+
+    /// Replaces the placeholders in `text` and returns the keys that are not set.
+    /// Placeholders with a missing key are kept as they are. Substituted values are not interpolated again.
+    pub fn interpolate(&self, text: &str) -> (String, Vec<String>) {
+        let mut interpolated = String::with_capacity(text.len());
+        let mut missing_keys = Vec::new();
+        let mut rest = text;
+        while let Some(start) = rest.find(['{', '}']) {
+            interpolated.push_str(&rest[..start]);
+            let brace = &rest[start..];
+            if brace.starts_with("{{") || brace.starts_with("}}") {
+                interpolated.push_str(&brace[..1]);
+                rest = &brace[2..];
+                continue;
+            }
+            let placeholder_end = brace[1..]
+                .find(['{', '}'])
+                .map(|index| index + 1)
+                .filter(|&index| brace[index..].starts_with('}'));
+            match placeholder_end {
+                Some(end) if brace.starts_with('{') => {
+                    let key = &brace[1..end];
+                    match self.0.get(key) {
+                        Some(value) => interpolated.push_str(value),
+                        None => {
+                            interpolated.push_str(&brace[..=end]);
+                            missing_keys.push(key.to_string());
+                        }
+                    }
+                    rest = &brace[end + 1..];
+                }
+                // Unmatched or nested brace, which is shown as is
+                _ => {
+                    interpolated.push_str(&brace[..1]);
+                    rest = &brace[1..];
+                }
+            }
+        }
+        interpolated.push_str(rest);
+        (interpolated, missing_keys)
+    }
+}
+
+fn is_met(
+    condition: &Option<Condition>,
+    active_conditions: &ActiveConditions,
+    inventory: &Inventory,
+    time_of_day: &TimeOfDay,
+) -> bool {
+    condition
+        .as_ref()
+        .map(|condition| condition.is_met(active_conditions, inventory, time_of_day))
+        .unwrap_or(true)
+}
+
 #[derive(
     Debug,
     Clone,
@@ -169,3 +313,157 @@ impl From<PageId> for String {
         value.0
     }
 }
+
+/// Loads `.dlg.ron` files. Unlike a plain RON loader, errors name the dialog node they occurred in.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DialogLoader;
+
+impl AssetLoader for DialogLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let dialog = parse_dialog(bytes, load_context.path())?;
+            load_context.set_default_asset(LoadedAsset::new(dialog));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["dlg.ron"]
+    }
+}
+
+fn parse_dialog(bytes: &[u8], path: &Path) -> Result<Dialog> {
+    ron::de::from_bytes(bytes).map_err(|e| {
+        let text = String::from_utf8_lossy(bytes);
+        let node = find_enclosing_node(&text, e.position.line, e.position.col)
+            .map(|node| format!(" in node \"{node}\""))
+            .unwrap_or_default();
+        anyhow::anyhow!(
+            "Failed to parse dialog \"{}\"{node}: {e}",
+            path.to_string_lossy()
+        )
+    })
+}
+
+static NODE_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#""([^"]*)"\s*:\s*\("#).expect("Failed to compile dialog node regex")
+});
+
+/// Finds the id of the innermost page or choice that starts before the given 1-based position
+fn find_enclosing_node(text: &str, line: usize, column: usize) -> Option<String> {
+    let line_start: usize = text
+        .split_inclusive('\n')
+        .take(line.saturating_sub(1))
+        .map(str::len)
+        .sum();
+    let offset = text[line_start..]
+        .char_indices()
+        .nth(column.saturating_sub(1))
+        .map(|(index, _)| line_start + index)
+        .unwrap_or(text.len());
+    NODE_REGEX
+        .captures_iter(&text[..offset])
+        .last()
+        .map(|captures| captures[1].to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn get_variables() -> DialogVariables {
+        let mut variables = DialogVariables::default();
+        variables.set("player_name", "Fox");
+        variables.set("coins", 12);
+        variables.set("trap", "{coins}");
+        variables
+    }
+
+    #[test]
+    fn substitutes_variables() {
+        let (text, missing_keys) =
+            get_variables().interpolate("Hello, {player_name}! You have {coins} coins.");
+        assert_eq!(text, "Hello, Fox! You have 12 coins.");
+        assert!(missing_keys.is_empty());
+    }
+
+    #[test]
+    fn keeps_missing_placeholders() {
+        let (text, missing_keys) = get_variables().interpolate("{greeting}, {player_name}!");
+        assert_eq!(text, "{greeting}, Fox!");
+        assert_eq!(missing_keys, vec!["greeting".to_string()]);
+    }
+
+    #[test]
+    fn unescapes_braces() {
+        let (text, missing_keys) =
+            get_variables().interpolate("{{player_name}} is {player_name}}}");
+        assert_eq!(text, "{player_name} is Fox}");
+        assert!(missing_keys.is_empty());
+    }
+
+    #[test]
+    fn does_not_resolve_nested_placeholders() {
+        let variables = get_variables();
+        let (text, missing_keys) = variables.interpolate("{trap} {a{player_name}} {coins");
+        assert_eq!(text, "{coins} {aFox} {coins");
+        assert!(missing_keys.is_empty());
+    }
+
+    #[test]
+    fn parses_conditions() {
+        let dialog = parse_dialog(
+            br#"(
+                initial_page: [(id: "page:greet")],
+                pages: {
+                    "page:greet": (
+                        text: "Hello",
+                        set_flags: ["met_guard"],
+                        next_page: Choice({
+                            "choice:key": (
+                                text: "Open the door",
+                                next_page_id: "page:greet",
+                                condition: Some(HasItem("key")),
+                                show_when_unavailable: true,
+                            ),
+                        }),
+                    ),
+                },
+            )"#,
+            Path::new("dialogs/test.dlg.ron"),
+        )
+        .unwrap();
+        let page = &dialog.pages[&PageId("page:greet".to_string())];
+        assert_eq!(page.set_flags, vec![ConditionId("met_guard".to_string())]);
+    }
+
+    #[test]
+    fn names_node_of_malformed_condition() {
+        let error = parse_dialog(
+            br#"(
+                initial_page: [(id: "page:greet")],
+                pages: {
+                    "page:greet": (
+                        text: "Hello",
+                        next_page: Choice({
+                            "choice:key": (
+                                text: "Open the door",
+                                next_page_id: "page:greet",
+                                condition: Some(HasKey("key")),
+                            ),
+                        }),
+                    ),
+                },
+            )"#,
+            Path::new("dialogs/test.dlg.ron"),
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(error.contains("dialogs/test.dlg.ron"), "{error}");
+        assert!(error.contains("choice:key"), "{error}");
+    }
+}
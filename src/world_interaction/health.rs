@@ -0,0 +1,258 @@
+use crate::file_system_interaction::asset_loading::ConfigAssets;
+use crate::file_system_interaction::config::GameConfig;
+use crate::level_instantiation::level_transition::LevelTransition;
+use crate::player_control::actions::ActionsFrozen;
+use crate::player_control::player_embodiment::Player;
+use crate::util::log_error::log_errors;
+use crate::util::trait_extension::Vec3Ext;
+use crate::world_interaction::checkpoint::{PlayerRespawnRequest, PlayerRespawned, Respawning};
+use crate::world_interaction::dialog::CurrentDialog;
+use crate::GameState;
+use anyhow::{Context, Result};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::f32::consts::FRAC_PI_2;
+
+/// Lets the player die. Damage is dealt with a [`DamageEvent`], e.g. by a [`TriggerAction::Damage`](crate::world_interaction::trigger::TriggerAction::Damage).
+/// When the player's [`Health`] reaches zero, their input is disabled, an open dialog is closed and the third person camera
+/// orbits the body for `player.death_camera_duration` seconds, see [`Dying`]. Afterwards the player respawns at the
+/// [`LastCheckpoint`](crate::world_interaction::checkpoint::LastCheckpoint) like after a fall and gets their health back.
+/// The player cannot be hurt while dying, respawning or moving to another level.
+pub struct HealthPlugin;
+
+impl Plugin for HealthPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Health>()
+            .add_event::<DamageEvent>()
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(init_health.pipe(log_errors))
+                    .with_system(apply_damage.after(init_health))
+                    .with_system(progress_death.pipe(log_errors).after(apply_damage))
+                    .with_system(revive_player.after(progress_death)),
+            );
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Component, Reflect, Serialize, Deserialize)]
+#[reflect(Component, Serialize, Deserialize)]
+pub struct Health {
+    pub current: f32,
+    pub max: f32,
+}
+
+impl Default for Health {
+    fn default() -> Self {
+        Self::full(100.)
+    }
+}
+
+impl Health {
+    pub fn full(max: f32) -> Self {
+        Self { current: max, max }
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.current <= 0.
+    }
+
+    /// Returns whether the damage was lethal. The dead cannot be damaged any further.
+    pub fn take_damage(&mut self, amount: f32) -> bool {
+        if self.is_dead() {
+            return false;
+        }
+        self.current = (self.current - amount.max(0.)).max(0.);
+        self.is_dead()
+    }
+
+    pub fn restore(&mut self) {
+        self.current = self.max;
+    }
+}
+
+/// Hurts the target if it has [`Health`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DamageEvent {
+    pub target: Entity,
+    pub amount: f32,
+}
+
+/// Health from a loaded save. Replaces the health of the next [`Player`] that is spawned.
+#[derive(Debug, Clone, Copy, PartialEq, Resource)]
+pub struct RestoredHealth(pub Health);
+
+/// The player died and the camera is orbiting their body. Ends when the player was respawned.
+#[derive(Debug, Clone, Copy, PartialEq, Resource, Default)]
+pub struct Dying {
+    elapsed: f32,
+    respawn_requested: bool,
+}
+
+fn init_health(
+    mut commands: Commands,
+    mut player_query: Query<&mut Health, Added<Player>>,
+    restored_health: Option<Res<RestoredHealth>>,
+    config_handles: Res<ConfigAssets>,
+    config: Res<Assets<GameConfig>>,
+) -> Result<()> {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("init_health").entered();
+    for mut health in player_query.iter_mut() {
+        let config = config
+            .get(&config_handles.game)
+            .context("Failed to get game config from handle")?;
+        *health = Health::full(config.player.max_health);
+        if let Some(restored_health) = &restored_health {
+            // Saves are never written while dying, but a dead player would be stuck
+            if !restored_health.0.is_dead() {
+                *health = restored_health.0;
+            }
+            commands.remove_resource::<RestoredHealth>();
+        }
+    }
+    Ok(())
+}
+
+fn apply_damage(
+    mut commands: Commands,
+    mut damage_events: EventReader<DamageEvent>,
+    mut health_query: Query<(&mut Health, Option<&Player>)>,
+    dying: Option<Res<Dying>>,
+    respawning: Option<Res<Respawning>>,
+    level_transition: Option<Res<LevelTransition>>,
+    current_dialog: Option<Res<CurrentDialog>>,
+    mut actions_frozen: ResMut<ActionsFrozen>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("apply_damage").entered();
+    let player_is_invulnerable =
+        dying.is_some() || respawning.is_some() || level_transition.is_some();
+    let mut player_died = false;
+    for damage in damage_events.iter() {
+        let (mut health, player) = match health_query.get_mut(damage.target) {
+            Ok(target) => target,
+            Err(_) => continue,
+        };
+        if player.is_some() && (player_is_invulnerable || player_died) {
+            continue;
+        }
+        let died = health.take_damage(damage.amount);
+        player_died |= died && player.is_some();
+    }
+    if !player_died {
+        return;
+    }
+    info!("The player died");
+    // The dialog froze the actions when it started
+    if current_dialog.is_some() {
+        commands.remove_resource::<CurrentDialog>();
+        actions_frozen.unfreeze();
+    }
+    actions_frozen.freeze();
+    commands.insert_resource(Dying::default());
+}
+
+fn progress_death(
+    time: Res<Time>,
+    dying: Option<ResMut<Dying>>,
+    mut respawn_requests: EventWriter<PlayerRespawnRequest>,
+    config_handles: Res<ConfigAssets>,
+    config: Res<Assets<GameConfig>>,
+) -> Result<()> {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("progress_death").entered();
+    let mut dying = match dying {
+        Some(dying) => dying,
+        None => return Ok(()),
+    };
+    if dying.respawn_requested {
+        return Ok(());
+    }
+    let config = config
+        .get(&config_handles.game)
+        .context("Failed to get game config from handle")?;
+    dying.elapsed += time.delta_seconds();
+    if dying.elapsed >= config.player.death_camera_duration {
+        // Fades the screen out, teleports the player and fades back in
+        respawn_requests.send(PlayerRespawnRequest);
+        dying.respawn_requested = true;
+    }
+    Ok(())
+}
+
+fn revive_player(
+    mut commands: Commands,
+    mut respawned_events: EventReader<PlayerRespawned>,
+    mut health_query: Query<&mut Health>,
+    dying: Option<Res<Dying>>,
+    mut actions_frozen: ResMut<ActionsFrozen>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("revive_player").entered();
+    for respawned in respawned_events.iter() {
+        if let Ok(mut health) = health_query.get_mut(respawned.player) {
+            if health.is_dead() {
+                health.restore();
+            }
+        }
+        if dying.is_some() {
+            commands.remove_resource::<Dying>();
+            actions_frozen.unfreeze();
+        }
+    }
+}
+
+/// A point next to the player's body for the third person camera to align with while the player is dying, see
+/// [`ThirdPersonCamera::secondary_target`](crate::player_control::camera::ThirdPersonCamera::secondary_target).
+/// It is always a bit ahead of the camera's current heading, so that the camera keeps easing around the body at
+/// roughly `orbit_speed` radians per second. The camera still keeps its line of sight, so it does not clip into walls.
+pub fn get_death_camera_focus(
+    body: Vec3,
+    up: Vec3,
+    camera_forward: Vec3,
+    orbit_speed: f32,
+    alignment_smoothing: f32,
+) -> Option<Vec3> {
+    let heading = camera_forward.split(up).horizontal;
+    if heading.is_approx_zero() {
+        return None;
+    }
+    // The camera turns by this angle times the smoothing every second
+    let lead_angle = (orbit_speed / alignment_smoothing.max(1e-5)).min(FRAC_PI_2);
+    Some(body + Quat::from_axis_angle(up, lead_angle) * heading.normalize())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn damage_kills_once() {
+        let mut health = Health::full(10.);
+        assert!(!health.take_damage(4.));
+        assert!(!health.take_damage(-100.));
+        assert_eq!(health.current, 6.);
+        assert!(health.take_damage(100.));
+        assert_eq!(health.current, 0.);
+        assert!(!health.take_damage(1.));
+        health.restore();
+        assert_eq!(health, Health::full(10.));
+    }
+
+    #[test]
+    fn death_camera_focus_leads_the_camera_around_the_body() {
+        let body = Vec3::new(1., 2., 3.);
+        let focus = get_death_camera_focus(body, Vec3::Y, Vec3::new(0., -1., -1.), 1., 4.).unwrap();
+        let heading = focus - body;
+        assert!((heading.length() - 1.).abs() < 1e-5);
+        assert!(heading.y.abs() < 1e-5);
+        let angle = Vec3::NEG_Z.angle_between(heading);
+        assert!((angle - 0.25).abs() < 1e-4, "angle: {angle}");
+        // Turning around the up axis counterclockwise
+        assert!(heading.x < 0.);
+
+        assert!(get_death_camera_focus(body, Vec3::Y, Vec3::NEG_Y, 1., 4.).is_none());
+        let fast = get_death_camera_focus(body, Vec3::Y, Vec3::NEG_Z, 100., 1.).unwrap();
+        assert!(fast.abs_diff_eq(body + Vec3::NEG_X, 1e-5));
+    }
+}
@@ -1,24 +1,42 @@
+use crate::file_system_interaction::asset_loading::ConfigAssets;
+use crate::file_system_interaction::config::GameConfig;
+use crate::file_system_interaction::localization::Localization;
 use crate::player_control::actions::{ActionsFrozen, PlayerAction};
-use crate::player_control::camera::{IngameCamera, IngameCameraKind};
+use crate::player_control::camera::{IngameCamera, IngameCameraKind, UpdateCameraTransformLabel};
 use crate::player_control::player_embodiment::Player;
+use crate::shader::{Materials, OutlineHull};
 use crate::util::log_error::log_errors;
+use crate::world_interaction::animated_prop::{InteractableProp, PropInteractEvent, PropState};
 use crate::world_interaction::dialog::{DialogEvent, DialogTarget};
+use crate::world_interaction::inventory::{Item, ItemPickupEvent};
 use crate::GameState;
 use anyhow::{Context, Result};
+use bevy::pbr::{NotShadowCaster, NotShadowReceiver};
 use bevy::prelude::*;
+use bevy::render::mesh::skinning::SkinnedMesh;
 use bevy::utils::HashSet;
-use bevy_egui::{egui, EguiContext};
+use bevy_egui::{egui, EguiContext, EguiSettings};
 use bevy_rapier3d::prelude::*;
 use leafwing_input_manager::prelude::ActionState;
 use serde::{Deserialize, Serialize};
 use std::f32::consts::TAU;
+use std::iter;
 
+/// Shows a prompt for the interactable the player can currently use and, on [`PlayerAction::Interact`],
+/// starts its dialog, picks it up if it is an [`Item`] or opens or closes it if it is an [`AnimatedProp`](crate::world_interaction::animated_prop::AnimatedProp).
+/// Interactables are found with the sensor around them that the player is touching, or, in first person,
+/// with a ray from the crosshair so that only the one the player looks at is picked.
+/// The interactable with the prompt is outlined, see [`OutlineMaterial`](crate::shader::OutlineMaterial).
+/// The prompt floats above the interactable, see [`PromptOffset`], and fades out as the player nears the edge of the interaction range.
+/// It is hidden while the interactable is off-screen, unless `interaction.prompt_clamp_to_screen` keeps it at the edge of the screen.
 pub struct InteractionsUiPlugin;
 
 impl Plugin for InteractionsUiPlugin {
     fn build(&self, app: &mut App) {
         app.register_type::<InteractionOpportunities>()
+            .register_type::<PromptOffset>()
             .init_resource::<InteractionOpportunities>()
+            .init_resource::<InteractionUi>()
             .add_system_set(
                 SystemSet::on_update(GameState::Playing)
                     .with_system(update_interaction_opportunities)
@@ -27,26 +45,121 @@ impl Plugin for InteractionsUiPlugin {
                             .pipe(log_errors)
                             .after(update_interaction_opportunities),
                     )
-                    .with_system(display_interaction_prompt.pipe(log_errors)),
+                    .with_system(
+                        display_interaction_prompt
+                            .pipe(log_errors)
+                            .after(update_interaction_ui)
+                            // Anchors the prompt with this frame's camera, even while it is still moving towards its target
+                            .after(UpdateCameraTransformLabel),
+                    )
+                    .with_system(highlight_interaction_target.after(update_interaction_ui)),
             );
     }
 }
 
-#[derive(Resource, Debug)]
+/// The interactable whose prompt is shown
+#[derive(Resource, Debug, Default)]
 pub struct InteractionUi {
-    source: Entity,
+    source: Option<Entity>,
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Resource, Reflect, Serialize, Deserialize, Default)]
 #[reflect(Resource, Serialize, Deserialize)]
 pub struct InteractionOpportunities(pub HashSet<Entity>);
 
+/// Where the prompt of an interactable is shown relative to its origin, in its local space.
+/// Interactables without one show their prompt `interaction.prompt_offset` above their origin.
+#[derive(Debug, Clone, Copy, PartialEq, Component, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Component, Serialize, Deserialize)]
+pub struct PromptOffset(pub Vec3);
+
+/// Where a prompt is shown on the screen, in egui's coordinates
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct PromptPlacement {
+    position: Vec2,
+    /// Direction towards the interactable when the prompt sticks to the edge of the screen
+    edge_direction: Option<Vec2>,
+}
+
+/// Places the prompt at `viewport_position`, the projection of its anchor that is missing when the anchor is behind the camera.
+/// Off-screen prompts are hidden or, with `clamp_to_screen`, moved to the edge of the screen in the direction of the anchor.
+/// `view_position` is the anchor in the camera's local space, which gives that direction when it is behind the camera.
+fn get_prompt_placement(
+    viewport_position: Option<Vec2>,
+    view_position: Vec3,
+    screen_size: Vec2,
+    margin: f32,
+    clamp_to_screen: bool,
+) -> Option<PromptPlacement> {
+    let is_on_screen =
+        |position: Vec2| position.cmpge(Vec2::ZERO).all() && position.cmple(screen_size).all();
+    if let Some(position) = viewport_position.filter(|position| is_on_screen(*position)) {
+        return Some(PromptPlacement {
+            position,
+            edge_direction: None,
+        });
+    }
+    if !clamp_to_screen {
+        return None;
+    }
+    let center = screen_size / 2.;
+    let direction = viewport_position
+        .map(|position| position - center)
+        // Behind the camera. The camera looks along -z and egui's y axis points down.
+        .unwrap_or_else(|| Vec2::new(view_position.x, -view_position.y))
+        .try_normalize()
+        // Straight behind the camera
+        .unwrap_or(Vec2::Y);
+    let half_extent = (center - Vec2::splat(margin)).max(Vec2::ZERO);
+    let scale = |extent: f32, direction: f32| {
+        if direction.abs() > 1e-5 {
+            extent / direction.abs()
+        } else {
+            f32::INFINITY
+        }
+    };
+    let distance = scale(half_extent.x, direction.x).min(scale(half_extent.y, direction.y));
+    Some(PromptPlacement {
+        position: center + direction * distance,
+        edge_direction: Some(direction),
+    })
+}
+
+/// Fully opaque up to `fade_distance` m before `max_distance`, then fading to `min_opacity`
+fn get_prompt_opacity(
+    distance: f32,
+    max_distance: f32,
+    fade_distance: f32,
+    min_opacity: f32,
+) -> f32 {
+    let min_opacity = min_opacity.clamp(0., 1.);
+    if fade_distance <= 0. {
+        return if distance <= max_distance {
+            1.
+        } else {
+            min_opacity
+        };
+    }
+    ((max_distance - distance) / fade_distance).clamp(min_opacity, 1.)
+}
+
 fn update_interaction_opportunities(
     mut collision_events: EventReader<CollisionEvent>,
     player_query: Query<Entity, With<Player>>,
     parent_query: Query<&Parent>,
     mut interaction_opportunities: ResMut<InteractionOpportunities>,
+    entities: &Entities,
 ) {
+    // Despawned entities don't always get a collision event
+    if interaction_opportunities
+        .0
+        .iter()
+        .any(|entity| !entities.contains(*entity))
+    {
+        interaction_opportunities
+            .0
+            .retain(|entity| entities.contains(*entity));
+    }
     for event in collision_events.iter() {
         let (entity_a, entity_b, ongoing) = unpack_event(event);
 
@@ -64,42 +177,150 @@ fn update_interaction_opportunities(
 }
 
 fn update_interaction_ui(
-    mut commands: Commands,
-    interaction_ui: Option<ResMut<InteractionUi>>,
+    mut interaction_ui: ResMut<InteractionUi>,
     non_player_query: Query<&Transform, Without<Player>>,
-    player_query: Query<&Transform, With<Player>>,
+    player_query: Query<(Entity, &Transform), With<Player>>,
     interaction_opportunities: Res<InteractionOpportunities>,
     camera_query: Query<&IngameCamera>,
+    interactable_query: Query<(), Or<(With<DialogTarget>, With<Item>, With<InteractableProp>)>>,
+    parent_query: Query<&Parent>,
+    rapier_context: Res<RapierContext>,
+    config_handles: Res<ConfigAssets>,
+    config: Res<Assets<GameConfig>>,
 ) -> Result<()> {
+    let config = config
+        .get(&config_handles.game)
+        .context("Failed to get game config from handle")?;
     let mut valid_target = None;
-    for entity in interaction_opportunities.0.iter() {
-        let target_transform = non_player_query
-            .get(*entity)
-            .context("Failed to get transform of interaction target")?;
-        for player_transform in player_query.iter() {
-            for camera in camera_query.iter() {
-                let is_facing_target =
-                    is_facing_target(*player_transform, *target_transform, camera);
-                if is_facing_target {
-                    valid_target = Some(*entity);
-                    break;
+    for (player_entity, player_transform) in player_query.iter() {
+        for camera in camera_query.iter() {
+            let ray = match &camera.kind {
+                IngameCameraKind::FirstPerson(first_person) => {
+                    Some((first_person.transform.translation, first_person.forward()))
+                }
+                IngameCameraKind::ThirdPerson(_) if config.interaction.third_person_raycast => {
+                    Some((player_transform.translation, player_transform.forward()))
                 }
+                _ => None,
+            };
+            if let Some((origin, direction)) = ray {
+                valid_target = get_interactable_under_ray(
+                    origin,
+                    direction,
+                    config.interaction.max_distance,
+                    player_entity,
+                    &rapier_context,
+                    &interactable_query,
+                    &parent_query,
+                );
+                continue;
             }
+            let candidates: Vec<_> = interaction_opportunities
+                .0
+                .iter()
+                // Entities despawned this frame are skipped so that we fall back to the next candidate right away
+                .filter_map(|entity| Some((*entity, non_player_query.get(*entity).ok()?)))
+                // E.g. a chest that only opens once
+                .filter(|(entity, _)| interactable_query.contains(*entity))
+                .filter(|(_, target_transform)| {
+                    is_facing_target(*player_transform, **target_transform, camera)
+                })
+                .map(|(entity, target_transform)| {
+                    InteractionCandidate::new(entity, player_transform, target_transform)
+                })
+                .collect();
+            let current_target = interaction_ui.source;
+            valid_target = select_interaction_target(
+                &candidates,
+                current_target,
+                config.interaction.selection_margin,
+            );
         }
     }
-    if let Some(mut interaction_ui) = interaction_ui {
-        if let Some(valid_target) = valid_target {
-            interaction_ui.source = valid_target;
+    // Set right away instead of through commands so that the prompt is never a frame behind
+    if interaction_ui.source != valid_target {
+        interaction_ui.source = valid_target;
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct InteractionCandidate {
+    entity: Entity,
+    /// Distance in m to the player
+    distance: f32,
+    /// Angle in radians between the player's facing and the direction to the candidate
+    angle: f32,
+}
+
+impl InteractionCandidate {
+    fn new(entity: Entity, player_transform: &Transform, target_transform: &Transform) -> Self {
+        let player_to_target = target_transform.translation - player_transform.translation;
+        Self {
+            entity,
+            distance: player_to_target.length(),
+            angle: player_transform.forward().angle_between(player_to_target),
+        }
+    }
+
+    /// Nearer candidates are better. Candidates at about the same distance are compared by their angle.
+    fn is_better_than(&self, other: &Self) -> bool {
+        const SAME_DISTANCE: f32 = 1e-2;
+        if (self.distance - other.distance).abs() < SAME_DISTANCE {
+            self.angle < other.angle
         } else {
-            commands.remove_resource::<InteractionUi>();
+            self.distance < other.distance
         }
-    } else if let Some(valid_target) = valid_target {
-        commands.insert_resource(InteractionUi {
-            source: valid_target,
-        });
     }
+}
 
-    Ok(())
+/// Picks the best candidate, but keeps the current target until another one is closer by more than `margin` m,
+/// so that the selection does not flicker while standing between two interactables.
+fn select_interaction_target(
+    candidates: &[InteractionCandidate],
+    current_target: Option<Entity>,
+    margin: f32,
+) -> Option<Entity> {
+    let best = candidates.iter().copied().reduce(|best, candidate| {
+        if candidate.is_better_than(&best) {
+            candidate
+        } else {
+            best
+        }
+    })?;
+    let current = current_target
+        .and_then(|current_target| {
+            candidates
+                .iter()
+                .find(|candidate| candidate.entity == current_target)
+        })
+        .copied();
+    match current {
+        Some(current) if best.distance + margin >= current.distance => Some(current.entity),
+        _ => Some(best.entity),
+    }
+}
+
+/// The interactable whose collider, or one of whose children's colliders, is hit first by the ray.
+/// Sensors are ignored so that the ray hits the interactable itself and not the area around it.
+fn get_interactable_under_ray(
+    origin: Vec3,
+    direction: Vec3,
+    max_distance: f32,
+    player: Entity,
+    rapier_context: &RapierContext,
+    interactable_query: &Query<(), Or<(With<DialogTarget>, With<Item>, With<InteractableProp>)>>,
+    parent_query: &Query<&Parent>,
+) -> Option<Entity> {
+    let mut filter = QueryFilter::default().exclude_rigid_body(player);
+    filter.flags |= QueryFilterFlags::EXCLUDE_SENSORS;
+    let solid = true;
+    let (hit, _toi) = rapier_context.cast_ray(origin, direction, max_distance, solid, filter)?;
+    iter::successors(Some(hit), |entity| {
+        parent_query.get(*entity).ok().map(|parent| parent.get())
+    })
+    .find(|entity| interactable_query.contains(*entity))
 }
 
 fn unpack_event(event: &CollisionEvent) -> (Entity, Entity, bool) {
@@ -149,38 +370,166 @@ fn is_facing_target(
 }
 
 fn display_interaction_prompt(
-    interaction_ui: Option<Res<InteractionUi>>,
+    interaction_ui: Res<InteractionUi>,
     mut dialog_event_writer: EventWriter<DialogEvent>,
+    mut pickup_event_writer: EventWriter<ItemPickupEvent>,
+    mut prop_event_writer: EventWriter<PropInteractEvent>,
     mut egui_context: ResMut<EguiContext>,
+    egui_settings: Res<EguiSettings>,
     actions: Query<&ActionState<PlayerAction>>,
     windows: Res<Windows>,
     actions_frozen: Res<ActionsFrozen>,
-    dialog_target_query: Query<&DialogTarget>,
+    interactable_query: Query<(
+        Option<&DialogTarget>,
+        Option<&Item>,
+        Option<&PropState>,
+        Option<&PromptOffset>,
+        &GlobalTransform,
+    )>,
+    player_query: Query<&GlobalTransform, With<Player>>,
+    camera_query: Query<(&Camera, &Transform), With<IngameCamera>>,
+    localization: Res<Localization>,
+    config_handles: Res<ConfigAssets>,
+    config: Res<Assets<GameConfig>>,
 ) -> Result<()> {
     if actions_frozen.is_frozen() {
         return Ok(());
     }
-    let interaction_ui = match interaction_ui {
-        Some(interaction_ui) => interaction_ui,
+    let source = match interaction_ui.source {
+        Some(source) => source,
         None => return Ok(()),
     };
+    let (dialog_target, item, prop, prompt_offset, target_transform) =
+        match interactable_query.get(source) {
+            Ok(target) => target,
+            // Despawned since the selection was made
+            Err(_) => return Ok(()),
+        };
+    let config = config
+        .get(&config_handles.game)
+        .context("Failed to get game config from handle")?;
 
     for actions in actions.iter() {
         let window = windows
             .get_primary()
             .context("Failed to get primary window")?;
-        egui::Window::new("Interaction")
-            .collapsible(false)
-            .title_bar(false)
-            .auto_sized()
-            .fixed_pos(egui::Pos2::new(window.width() / 2., window.height() / 2.))
-            .show(egui_context.ctx_mut(), |ui| {
-                ui.label("E: Talk");
-            });
+        let prompt_translation = match prompt_offset {
+            Some(offset) => target_transform.transform_point(offset.0),
+            None => {
+                target_transform.translation()
+                    + target_transform.up() * config.interaction.prompt_offset
+            }
+        };
+        // egui's points are the window's logical pixels divided by egui's own scale
+        let points_per_pixel = 1. / egui_settings.scale_factor as f32;
+        let screen_size = Vec2::new(window.width(), window.height()) * points_per_pixel;
+        let placement = match camera_query.iter().next() {
+            Some((camera, camera_transform)) => {
+                // The camera is a root entity, so its transform from this frame is already its global transform
+                let camera_transform = GlobalTransform::from(*camera_transform);
+                let viewport_position = camera
+                    .world_to_viewport(&camera_transform, prompt_translation)
+                    // The viewport's origin is at the bottom, egui's at the top
+                    .map(|position| {
+                        Vec2::new(position.x, window.height() - position.y) * points_per_pixel
+                    });
+                let view_position = camera_transform
+                    .compute_matrix()
+                    .inverse()
+                    .transform_point3(prompt_translation);
+                get_prompt_placement(
+                    viewport_position,
+                    view_position,
+                    screen_size,
+                    config.interaction.prompt_screen_margin,
+                    config.interaction.prompt_clamp_to_screen,
+                )
+            }
+            None => Some(PromptPlacement {
+                position: screen_size / 2.,
+                edge_direction: None,
+            }),
+        };
+        let opacity = player_query
+            .iter()
+            .next()
+            .map(|player_transform| {
+                get_prompt_opacity(
+                    player_transform
+                        .translation()
+                        .distance(target_transform.translation()),
+                    config.interaction.max_distance,
+                    config.interaction.prompt_fade_distance,
+                    config.interaction.prompt_min_opacity,
+                )
+            })
+            .unwrap_or(1.);
+
+        if let Some(placement) = placement {
+            let pivot = if placement.edge_direction.is_some() {
+                egui::Align2::CENTER_CENTER
+            } else {
+                egui::Align2::CENTER_BOTTOM
+            };
+            egui::Area::new("Interaction")
+                .interactable(false)
+                .pivot(pivot)
+                .fixed_pos(egui::Pos2::new(placement.position.x, placement.position.y))
+                .show(egui_context.ctx_mut(), |ui| {
+                    let mut frame = egui::Frame::popup(ui.style());
+                    frame.fill = frame.fill.linear_multiply(opacity);
+                    frame.stroke.color = frame.stroke.color.linear_multiply(opacity);
+                    frame.shadow.color = frame.shadow.color.linear_multiply(opacity);
+                    let text_color = ui.visuals().text_color().linear_multiply(opacity);
+                    frame.show(ui, |ui| {
+                        ui.horizontal(|ui| {
+                            if let Some(direction) = placement.edge_direction {
+                                let (rect, _) = ui.allocate_exact_size(
+                                    egui::Vec2::splat(ui.spacing().interact_size.y),
+                                    egui::Sense::hover(),
+                                );
+                                let direction =
+                                    egui::Vec2::new(direction.x, direction.y) * rect.width() / 2.;
+                                ui.painter().arrow(
+                                    rect.center() - direction,
+                                    direction * 2.,
+                                    egui::Stroke::new(2., text_color),
+                                );
+                            }
+                            egui::Frame::none()
+                                .stroke(egui::Stroke::new(1., text_color))
+                                .rounding(3.)
+                                .inner_margin(egui::style::Margin::symmetric(4., 0.))
+                                .show(ui, |ui| {
+                                    ui.label(
+                                        egui::RichText::new(localization.get("interaction.key"))
+                                            .strong()
+                                            .color(text_color),
+                                    );
+                                });
+                            let action = match (item, prop) {
+                                (Some(item), _) => localization
+                                    .get("interaction.pick_up")
+                                    .replace("{item}", localization.get(&item.name)),
+                                (None, Some(prop)) if prop.progress.opening => {
+                                    localization.get("interaction.close").to_string()
+                                }
+                                (None, Some(_)) => localization.get("interaction.open").to_string(),
+                                (None, None) => localization.get("interaction.talk").to_string(),
+                            };
+                            ui.label(egui::RichText::new(action).color(text_color));
+                        });
+                    });
+                });
+        }
         if actions.just_pressed(PlayerAction::Interact) {
-            if let Ok(dialog_target) = dialog_target_query.get(interaction_ui.source) {
+            if item.is_some() {
+                pickup_event_writer.send(ItemPickupEvent(source));
+            } else if prop.is_some() {
+                prop_event_writer.send(PropInteractEvent(source));
+            } else if let Some(dialog_target) = dialog_target {
                 dialog_event_writer.send(DialogEvent {
-                    source: interaction_ui.source,
+                    source,
                     dialog: dialog_target.dialog_id.clone(),
                     page: None,
                 });
@@ -189,3 +538,143 @@ fn display_interaction_prompt(
     }
     Ok(())
 }
+
+/// Puts an [`OutlineHull`] on every mesh of the interactable whose prompt is shown.
+/// The hulls are children of the meshes, so they are despawned along with the interactable.
+fn highlight_interaction_target(
+    mut commands: Commands,
+    interaction_ui: Res<InteractionUi>,
+    actions_frozen: Res<ActionsFrozen>,
+    children_query: Query<&Children>,
+    mesh_query: Query<(&Handle<Mesh>, Option<&SkinnedMesh>), Without<OutlineHull>>,
+    hull_query: Query<Entity, With<OutlineHull>>,
+    materials: Res<Materials>,
+    mut highlighted: Local<Option<Entity>>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("highlight_interaction_target").entered();
+    // Same as the prompt, which is hidden while e.g. a dialog is open
+    let target = interaction_ui
+        .source
+        .filter(|_| !actions_frozen.is_frozen());
+    if *highlighted == target {
+        return;
+    }
+    *highlighted = target;
+    for hull in &hull_query {
+        commands.entity(hull).despawn_recursive();
+    }
+    let mut entities: Vec<_> = target.into_iter().collect();
+    while let Some(entity) = entities.pop() {
+        if let Ok((mesh, skinned_mesh)) = mesh_query.get(entity) {
+            let mut hull = commands.spawn((
+                MaterialMeshBundle {
+                    mesh: mesh.clone(),
+                    material: materials.outline.clone(),
+                    ..default()
+                },
+                OutlineHull,
+                NotShadowCaster,
+                NotShadowReceiver,
+                Name::new("Outline"),
+            ));
+            // Skinned meshes are posed by their joints, so the hull follows the animation
+            if let Some(skinned_mesh) = skinned_mesh {
+                hull.insert(skinned_mesh.clone());
+            }
+            let hull = hull.id();
+            commands.entity(entity).add_child(hull);
+        }
+        if let Ok(children) = children_query.get(entity) {
+            entities.extend(children.iter());
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn prompts_off_screen_are_hidden_or_clamped() {
+        let screen = Vec2::new(800., 600.);
+        let on_screen =
+            get_prompt_placement(Some(Vec2::new(100., 200.)), Vec3::NEG_Z, screen, 40., false);
+        assert_eq!(
+            on_screen.map(|placement| placement.position),
+            Some(Vec2::new(100., 200.))
+        );
+        assert_eq!(
+            on_screen.and_then(|placement| placement.edge_direction),
+            None
+        );
+
+        let off_to_the_right = Some(Vec2::new(1200., 300.));
+        assert_eq!(
+            get_prompt_placement(off_to_the_right, Vec3::NEG_Z, screen, 40., false),
+            None
+        );
+        let clamped =
+            get_prompt_placement(off_to_the_right, Vec3::NEG_Z, screen, 40., true).unwrap();
+        assert_eq!(clamped.position, Vec2::new(760., 300.));
+        assert_eq!(clamped.edge_direction, Some(Vec2::X));
+
+        // Behind the camera and to its left
+        let behind = Vec3::new(-1., 0., 1.);
+        assert_eq!(get_prompt_placement(None, behind, screen, 40., false), None);
+        let clamped = get_prompt_placement(None, behind, screen, 40., true).unwrap();
+        assert_eq!(clamped.position, Vec2::new(40., 300.));
+    }
+
+    #[test]
+    fn prompt_fades_near_edge_of_range() {
+        assert_eq!(get_prompt_opacity(1., 3., 1., 0.25), 1.);
+        assert_eq!(get_prompt_opacity(2.5, 3., 1., 0.25), 0.5);
+        assert_eq!(get_prompt_opacity(4., 3., 1., 0.25), 0.25);
+        assert_eq!(get_prompt_opacity(4., 3., 0., 0.25), 0.25);
+    }
+
+    fn candidate(index: u32, distance: f32, angle: f32) -> InteractionCandidate {
+        InteractionCandidate {
+            entity: Entity::from_raw(index),
+            distance,
+            angle,
+        }
+    }
+
+    #[test]
+    fn selects_nearest_then_smallest_angle() {
+        let candidates = [
+            candidate(0, 2., 0.1),
+            candidate(1, 1., 0.5),
+            candidate(2, 1., 0.2),
+        ];
+        assert_eq!(
+            select_interaction_target(&candidates, None, 0.5),
+            Some(Entity::from_raw(2))
+        );
+    }
+
+    #[test]
+    fn keeps_current_target_within_margin() {
+        let candidates = [candidate(0, 1.2, 0.), candidate(1, 1., 0.)];
+        let current = Some(Entity::from_raw(0));
+        assert_eq!(
+            select_interaction_target(&candidates, current, 0.5),
+            Some(Entity::from_raw(0))
+        );
+        assert_eq!(
+            select_interaction_target(&candidates, current, 0.1),
+            Some(Entity::from_raw(1))
+        );
+    }
+
+    #[test]
+    fn falls_back_when_current_target_is_gone() {
+        let candidates = [candidate(1, 3., 0.)];
+        assert_eq!(
+            select_interaction_target(&candidates, Some(Entity::from_raw(0)), 0.5),
+            Some(Entity::from_raw(1))
+        );
+    }
+}
@@ -0,0 +1,307 @@
+use crate::level_instantiation::spawning::objects::GameCollisionGroup;
+use crate::level_instantiation::spawning::{DespawnRequest, GameObject, SpawnRequest};
+use crate::player_control::player_embodiment::Player;
+use crate::GameState;
+use bevy::ecs::system::EntityCommands;
+use bevy::prelude::*;
+use bevy::utils::{HashMap, HashSet};
+use bevy_rapier3d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Holds the items the player carries. Part of the save file.
+/// Objects spawned with an [`Item`] can be picked up through the interaction prompt, which despawns them.
+/// Dialogs and triggers change the inventory through [`ItemEffect`]s.
+pub struct InventoryPlugin;
+
+impl Plugin for InventoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Inventory>()
+            .register_type::<Item>()
+            .init_resource::<Inventory>()
+            .add_event::<ItemPickupEvent>()
+            .add_event::<ItemDropEvent>()
+            .add_event::<ItemEffect>()
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(pick_up_items)
+                    .with_system(drop_items)
+                    .with_system(apply_item_effects),
+            );
+    }
+}
+
+/// Radius in m of the area around an item in which the player can pick it up
+const PICKUP_RADIUS: f32 = 1.;
+
+/// An object that can be picked up and carried in the [`Inventory`].
+/// Set through [`SpawnEvent::item`](crate::level_instantiation::spawning::SpawnEvent::item).
+#[derive(Debug, Component, Clone, PartialEq, Eq, Reflect, FromReflect, Serialize, Deserialize)]
+#[reflect(Component, Serialize, Deserialize)]
+pub struct Item {
+    pub id: String,
+    /// Localization key of the name shown to the player
+    pub name: String,
+    /// Path to the image shown in the inventory, e.g. "textures/items/key.png"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub icon: Option<String>,
+    /// How many of the item fit into one inventory slot
+    #[serde(default = "get_default_stack_size")]
+    pub stack_size: u32,
+    /// Object that is spawned when the item is dropped
+    #[serde(default = "get_default_object")]
+    pub object: GameObject,
+}
+
+fn get_default_stack_size() -> u32 {
+    1
+}
+
+fn get_default_object() -> GameObject {
+    GameObject::Box
+}
+
+impl Default for Item {
+    fn default() -> Self {
+        Self {
+            id: default(),
+            name: default(),
+            icon: default(),
+            stack_size: get_default_stack_size(),
+            object: get_default_object(),
+        }
+    }
+}
+
+/// Makes a freshly spawned object a physical item that the player can pick up
+pub fn insert_item(entity_commands: &mut EntityCommands, item: Item) {
+    entity_commands
+        .insert((item, RigidBody::Dynamic))
+        .with_children(|parent| {
+            parent.spawn((
+                Name::new("Item Pickup Collider"),
+                Collider::ball(PICKUP_RADIUS),
+                Sensor,
+                ActiveEvents::COLLISION_EVENTS,
+                ActiveCollisionTypes::DYNAMIC_DYNAMIC,
+                CollisionGroups::new(
+                    GameCollisionGroup::OTHER.into(),
+                    GameCollisionGroup::PLAYER.into(),
+                ),
+            ));
+        });
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Reflect, FromReflect, Serialize, Deserialize)]
+#[reflect(Serialize, Deserialize)]
+pub struct InventoryItem {
+    pub item: Item,
+    pub count: u32,
+}
+
+/// Items the player carries, by item id
+#[derive(Debug, Clone, Eq, PartialEq, Resource, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Resource, Serialize, Deserialize)]
+pub struct Inventory(pub HashMap<String, InventoryItem>);
+
+impl Inventory {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn count(&self, item: &str) -> u32 {
+        self.0
+            .get(item)
+            .map(|inventory_item| inventory_item.count)
+            .unwrap_or_default()
+    }
+
+    pub fn has_item(&self, item: &str) -> bool {
+        self.count(item) > 0
+    }
+
+    pub fn add(&mut self, item: Item, count: u32) {
+        if count == 0 {
+            return;
+        }
+        self.0
+            .entry(item.id.clone())
+            .or_insert(InventoryItem { item, count: 0 })
+            .count += count;
+    }
+
+    /// Removes up to `count` items and returns how many were actually removed
+    pub fn remove(&mut self, item: &str, count: u32) -> u32 {
+        let available = self.count(item);
+        let removed = available.min(count);
+        if removed == available {
+            self.0.remove(item);
+        } else if let Some(held) = self.0.get_mut(item) {
+            held.count -= removed;
+        }
+        removed
+    }
+
+    /// Slots as shown in the inventory, sorted by item id. Items that exceed their [`Item::stack_size`] take up several slots.
+    pub fn slots(&self) -> Vec<(&Item, u32)> {
+        let mut items: Vec<_> = self.0.values().collect();
+        items.sort_unstable_by(|a, b| a.item.id.cmp(&b.item.id));
+        items
+            .into_iter()
+            .flat_map(|inventory_item| {
+                let stack_size = inventory_item.item.stack_size.max(1);
+                let full_stacks = inventory_item.count / stack_size;
+                let rest = inventory_item.count % stack_size;
+                std::iter::repeat(stack_size)
+                    .take(full_stacks as usize)
+                    .chain((rest > 0).then_some(rest))
+                    .map(|count| (&inventory_item.item, count))
+            })
+            .collect()
+    }
+}
+
+/// Picks up the item on the given entity
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ItemPickupEvent(pub Entity);
+
+/// Drops one of the item with the given id in front of the player
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ItemDropEvent(pub String);
+
+/// Changes the [`Inventory`] without picking up or dropping anything. Used by dialogs and triggers.
+#[derive(Debug, Clone, PartialEq, Eq, Reflect, FromReflect, Serialize, Deserialize)]
+#[reflect(Serialize, Deserialize)]
+pub enum ItemEffect {
+    GiveItem {
+        item: Item,
+        #[serde(default = "get_default_count")]
+        count: u32,
+    },
+    RemoveItem {
+        id: String,
+        #[serde(default = "get_default_count")]
+        count: u32,
+    },
+}
+
+fn get_default_count() -> u32 {
+    1
+}
+
+fn pick_up_items(
+    mut pickup_events: EventReader<ItemPickupEvent>,
+    mut despawn_requests: EventWriter<DespawnRequest>,
+    mut inventory: ResMut<Inventory>,
+    item_query: Query<&Item>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("pick_up_items").entered();
+    // The entity is only despawned at the end of the frame, so make sure it is not picked up twice
+    let mut picked_up = HashSet::new();
+    for ItemPickupEvent(entity) in pickup_events.iter() {
+        if !picked_up.insert(*entity) {
+            continue;
+        }
+        let item = match item_query.get(*entity) {
+            Ok(item) => item,
+            Err(_) => {
+                warn!("Failed to pick up entity {entity:?}: It is not an item");
+                continue;
+            }
+        };
+        inventory.add(item.clone(), 1);
+        // Despawned level objects are remembered, so the item does not come back when the game is loaded
+        despawn_requests.send(DespawnRequest::Entity(*entity));
+    }
+}
+
+fn drop_items(
+    mut drop_events: EventReader<ItemDropEvent>,
+    mut spawn_requests: EventWriter<SpawnRequest>,
+    mut inventory: ResMut<Inventory>,
+    player_query: Query<&Transform, With<Player>>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("drop_items").entered();
+    for ItemDropEvent(id) in drop_events.iter() {
+        let player_transform = match player_query.iter().next() {
+            Some(transform) => transform,
+            None => continue,
+        };
+        let item = match inventory.0.get(id) {
+            Some(inventory_item) => inventory_item.item.clone(),
+            None => {
+                warn!("Failed to drop item \"{id}\": The inventory does not hold it");
+                continue;
+            }
+        };
+        inventory.remove(id, 1);
+        const DROP_DISTANCE: f32 = 1.5;
+        let translation = player_transform.translation
+            + player_transform.forward() * DROP_DISTANCE
+            + player_transform.up() * 0.5;
+        spawn_requests.send(SpawnRequest {
+            object: item.object,
+            transform: Transform::from_translation(translation)
+                .with_rotation(player_transform.rotation)
+                .with_scale(Vec3::splat(0.2)),
+            item: Some(item),
+        });
+    }
+}
+
+fn apply_item_effects(mut item_effects: EventReader<ItemEffect>, mut inventory: ResMut<Inventory>) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("apply_item_effects").entered();
+    for effect in item_effects.iter() {
+        match effect {
+            ItemEffect::GiveItem { item, count } => inventory.add(item.clone(), *count),
+            ItemEffect::RemoveItem { id, count } => {
+                let removed = inventory.remove(id, *count);
+                if removed < *count {
+                    warn!("Tried to remove {count} of item \"{id}\", but the inventory only held {removed}");
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn item(id: &str, stack_size: u32) -> Item {
+        Item {
+            id: id.to_string(),
+            stack_size,
+            ..default()
+        }
+    }
+
+    #[test]
+    fn splits_items_into_stacks() {
+        let mut inventory = Inventory::default();
+        inventory.add(item("coin", 10), 23);
+        inventory.add(item("apple", 1), 1);
+        let slots: Vec<_> = inventory
+            .slots()
+            .into_iter()
+            .map(|(item, count)| (item.id.as_str(), count))
+            .collect();
+        assert_eq!(
+            slots,
+            vec![("apple", 1), ("coin", 10), ("coin", 10), ("coin", 3)]
+        );
+    }
+
+    #[test]
+    fn removes_at_most_what_is_held() {
+        let mut inventory = Inventory::default();
+        inventory.add(item("coin", 10), 3);
+        assert_eq!(inventory.remove("coin", 2), 2);
+        assert_eq!(inventory.count("coin"), 1);
+        assert_eq!(inventory.remove("coin", 5), 1);
+        assert!(!inventory.has_item("coin"));
+        assert!(inventory.is_empty());
+    }
+}
@@ -0,0 +1,320 @@
+use crate::file_system_interaction::asset_loading::ConfigAssets;
+use crate::file_system_interaction::config::GameConfig;
+use crate::movement::activity::Dormant;
+use crate::movement::animation::blend_poses;
+use crate::movement::general_movement::Walking;
+use crate::player_control::player_embodiment::Player;
+use crate::util::log_error::log_errors;
+use crate::world_interaction::dialog::CurrentDialog;
+use crate::GameState;
+use anyhow::{Context, Result};
+use bevy::animation::animation_player;
+use bevy::prelude::*;
+use bevy::transform::TransformSystem;
+
+/// Turns the heads of characters with a [`LookAtTarget`] towards it. The head bone is found by name in the character's rig,
+/// see `npc.head_bone`. It turns within `npc.max_head_yaw` and `npc.max_head_pitch`, and when the target is further to the side,
+/// the upper body turns as well by up to `npc.max_upper_body_yaw`. The head eases towards new targets and back when the target is cleared.
+/// The turn is added to the bones after the animations were sampled, so that the animations do not overwrite it.
+/// The dialog makes the character the player talks to look at them, and idle NPCs glance at the player
+/// while they are closer than `npc.glance_distance`.
+pub struct LookAtPlugin;
+
+impl Plugin for LookAtPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<LookAtTarget>()
+            .register_type::<LookAngles>()
+            .register_type::<LookAtBone>()
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(find_look_at_bones.pipe(log_errors))
+                    .with_system(glance_at_player.pipe(log_errors))
+                    .with_system(
+                        update_look_angles
+                            .pipe(log_errors)
+                            .after(find_look_at_bones)
+                            .after(glance_at_player),
+                    ),
+            )
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                apply_look_angles
+                    .after(animation_player)
+                    .after(blend_poses)
+                    .before(TransformSystem::TransformPropagate),
+            );
+    }
+}
+
+/// The entity whose head turns towards this one, if any
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Component, Reflect, Default)]
+#[reflect(Component)]
+pub struct LookAtTarget(pub Option<Entity>);
+
+/// How far the head is currently turned relative to the body, in radians. Eases towards the [`LookAtTarget`].
+#[derive(Debug, Clone, Copy, PartialEq, Component, Reflect, Default)]
+#[reflect(Component)]
+pub struct LookAngles {
+    /// Positive turns to the left
+    pub yaw: f32,
+    /// Positive looks up
+    pub pitch: f32,
+}
+
+/// The bones of a character with a [`LookAtTarget`] that turn
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Component)]
+struct LookAtBones {
+    head: Entity,
+    upper_body: Option<Entity>,
+}
+
+/// A bone turned by [`LookAngles`]
+#[derive(Debug, Clone, Copy, PartialEq, Component, Reflect, Default)]
+#[reflect(Component)]
+pub struct LookAtBone {
+    /// Rotation added to the animated local rotation of the bone
+    offset: Quat,
+    /// Local rotation of the bone including the offset, as last written
+    written: Quat,
+}
+
+/// Yaw and pitch in radians of the direction from `eye` to `target`, relative to the facing of `body`.
+/// Positive yaw is to the left, positive pitch is up.
+pub fn get_look_angles(body: &Transform, eye: Vec3, target: Vec3) -> Option<(f32, f32)> {
+    let direction = body.rotation.inverse() * (target - eye);
+    let direction = direction.try_normalize()?;
+    let yaw = (-direction.x).atan2(-direction.z);
+    let pitch = direction.y.clamp(-1., 1.).asin();
+    Some((yaw, pitch))
+}
+
+/// Splits a yaw in radians into the part the head turns and the part the upper body turns.
+/// The head turns first, and the upper body only takes what is beyond the head's limit.
+pub fn split_yaw(yaw: f32, max_head_yaw: f32, max_upper_body_yaw: f32) -> (f32, f32) {
+    let head = yaw.clamp(-max_head_yaw, max_head_yaw);
+    let upper_body = (yaw - head).clamp(-max_upper_body_yaw, max_upper_body_yaw);
+    (head, upper_body)
+}
+
+fn find_look_at_bones(
+    mut commands: Commands,
+    characters: Query<Entity, (With<LookAtTarget>, Without<LookAtBones>)>,
+    children: Query<&Children>,
+    names: Query<&Name>,
+    config_handles: Res<ConfigAssets>,
+    config: Res<Assets<GameConfig>>,
+) -> Result<()> {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("find_look_at_bones").entered();
+    if characters.is_empty() {
+        return Ok(());
+    }
+    let config = config
+        .get(&config_handles.game)
+        .context("Failed to get game config from handle")?;
+    for character in characters.iter() {
+        // The model's scene might not be spawned yet
+        let head = match find_descendant(character, &config.npc.head_bone, &children, &names) {
+            Some(head) => head,
+            None => continue,
+        };
+        let upper_body = find_descendant(character, &config.npc.upper_body_bone, &children, &names);
+        commands
+            .entity(character)
+            .insert((LookAtBones { head, upper_body }, LookAngles::default()));
+        for bone in std::iter::once(head).chain(upper_body) {
+            commands.entity(bone).insert(LookAtBone {
+                offset: Quat::IDENTITY,
+                written: Quat::NAN,
+            });
+        }
+    }
+    Ok(())
+}
+
+fn find_descendant(
+    entity: Entity,
+    name: &str,
+    children: &Query<&Children>,
+    names: &Query<&Name>,
+) -> Option<Entity> {
+    children.get(entity).ok()?.iter().find_map(|child| {
+        let is_match = names
+            .get(*child)
+            .map(|child_name| child_name.as_str() == name)
+            .unwrap_or_default();
+        if is_match {
+            Some(*child)
+        } else {
+            find_descendant(*child, name, children, names)
+        }
+    })
+}
+
+fn glance_at_player(
+    mut characters: Query<
+        (Entity, &GlobalTransform, &Walking, &mut LookAtTarget),
+        (Without<Player>, Without<Dormant>),
+    >,
+    players: Query<(Entity, &GlobalTransform), With<Player>>,
+    current_dialog: Option<Res<CurrentDialog>>,
+    config_handles: Res<ConfigAssets>,
+    config: Res<Assets<GameConfig>>,
+) -> Result<()> {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("glance_at_player").entered();
+    let config = config
+        .get(&config_handles.game)
+        .context("Failed to get game config from handle")?;
+    let player = players.iter().next();
+    for (entity, transform, walking, mut look_at_target) in characters.iter_mut() {
+        // The dialog decides where the character the player talks to looks
+        if current_dialog
+            .as_ref()
+            .map(|dialog| dialog.source == entity)
+            .unwrap_or_default()
+        {
+            continue;
+        }
+        let is_idle = walking.direction.is_none();
+        let target = player.and_then(|(player, player_transform)| {
+            let distance = player_transform
+                .translation()
+                .distance(transform.translation());
+            (is_idle && distance < config.npc.glance_distance).then_some(player)
+        });
+        if look_at_target.0 != target {
+            look_at_target.0 = target;
+        }
+    }
+    Ok(())
+}
+
+fn update_look_angles(
+    time: Res<Time>,
+    mut characters: Query<(
+        &GlobalTransform,
+        &LookAtTarget,
+        &LookAtBones,
+        &mut LookAngles,
+    )>,
+    targets: Query<&GlobalTransform>,
+    config_handles: Res<ConfigAssets>,
+    config: Res<Assets<GameConfig>>,
+) -> Result<()> {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("update_look_angles").entered();
+    let config = config
+        .get(&config_handles.game)
+        .context("Failed to get game config from handle")?;
+    let npc = &config.npc;
+    let dt = time.delta_seconds();
+    for (transform, look_at_target, bones, mut angles) in characters.iter_mut() {
+        let body = transform.compute_transform();
+        let eye = targets
+            .get(bones.head)
+            .map(|head| head.translation())
+            .unwrap_or(body.translation);
+        let target_angles = look_at_target
+            .0
+            .and_then(|target| targets.get(target).ok())
+            .and_then(|target| get_look_angles(&body, eye, target.translation()))
+            .map(|(yaw, pitch)| {
+                let max_yaw = npc.max_head_yaw + npc.max_upper_body_yaw;
+                (
+                    yaw.clamp(-max_yaw, max_yaw),
+                    pitch.clamp(-npc.max_head_pitch, npc.max_head_pitch),
+                )
+            })
+            .unwrap_or_default();
+        let scale = (npc.look_at_smoothing * dt).min(1.);
+        angles.yaw += (target_angles.0 - angles.yaw) * scale;
+        angles.pitch += (target_angles.1 - angles.pitch) * scale;
+    }
+    Ok(())
+}
+
+fn apply_look_angles(
+    characters: Query<(&GlobalTransform, &LookAtBones, &LookAngles)>,
+    mut bones: Query<(&mut Transform, &GlobalTransform, &mut LookAtBone)>,
+    config_handles: Option<Res<ConfigAssets>>,
+    config: Res<Assets<GameConfig>>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("apply_look_angles").entered();
+    // The config is only available after loading
+    let config = match config_handles.and_then(|handles| config.get(&handles.game)) {
+        Some(config) => config,
+        None => return,
+    };
+    let npc = &config.npc;
+    for (transform, look_at_bones, angles) in characters.iter() {
+        let body_rotation = transform.compute_transform().rotation;
+        let up = body_rotation * Vec3::Y;
+        let (head_yaw, upper_body_yaw) =
+            split_yaw(angles.yaw, npc.max_head_yaw, npc.max_upper_body_yaw);
+        let right = Quat::from_axis_angle(up, angles.yaw) * body_rotation * Vec3::X;
+        let head_turn =
+            Quat::from_axis_angle(right, angles.pitch) * Quat::from_axis_angle(up, head_yaw);
+        let upper_body_turn = Quat::from_axis_angle(up, upper_body_yaw);
+        let turns = std::iter::once((look_at_bones.head, head_turn)).chain(
+            look_at_bones
+                .upper_body
+                .map(|upper_body| (upper_body, upper_body_turn)),
+        );
+        for (bone, turn) in turns {
+            let (mut bone_transform, bone_global_transform, mut look_at_bone) =
+                match bones.get_mut(bone) {
+                    Ok(bone) => bone,
+                    Err(_) => continue,
+                };
+            // The animation overwrites the rotation every frame, but a bone it does not animate still has last frame's offset
+            let animated_rotation = if bone_transform.rotation == look_at_bone.written {
+                look_at_bone.written * look_at_bone.offset.inverse()
+            } else {
+                bone_transform.rotation
+            };
+            // The global rotation is from last frame, which included last frame's offset
+            let global_rotation =
+                bone_global_transform.compute_transform().rotation * look_at_bone.offset.inverse();
+            let offset = global_rotation.inverse() * turn * global_rotation;
+            bone_transform.rotation = animated_rotation * offset;
+            look_at_bone.offset = offset;
+            look_at_bone.written = bone_transform.rotation;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::f32::consts::FRAC_PI_4;
+
+    #[test]
+    fn look_angles_are_relative_to_the_body() {
+        let body = Transform::from_rotation(Quat::from_rotation_y(FRAC_PI_4));
+        let eye = Vec3::ZERO;
+        let (yaw, pitch) = get_look_angles(&body, eye, body.forward()).unwrap();
+        assert!(yaw.abs() < 1e-5);
+        assert!(pitch.abs() < 1e-5);
+
+        let (yaw, pitch) = get_look_angles(&body, eye, body.left() + body.forward()).unwrap();
+        assert!((yaw - FRAC_PI_4).abs() < 1e-5, "yaw: {yaw}");
+        assert!(pitch.abs() < 1e-5);
+
+        let (yaw, pitch) = get_look_angles(&body, eye, body.right() + Vec3::Y).unwrap();
+        assert!((yaw + FRAC_PI_4 * 2.).abs() < 1e-5, "yaw: {yaw}");
+        assert!((pitch - FRAC_PI_4).abs() < 1e-5, "pitch: {pitch}");
+
+        assert!(get_look_angles(&body, eye, eye).is_none());
+    }
+
+    #[test]
+    fn upper_body_takes_what_the_head_cannot() {
+        assert_eq!(split_yaw(0.5, 1., 0.4), (0.5, 0.));
+        let (head, upper_body) = split_yaw(-1.2, 1., 0.4);
+        assert_eq!(head, -1.);
+        assert!((upper_body + 0.2).abs() < 1e-5);
+        assert_eq!(split_yaw(3., 1., 0.4), (1., 0.4));
+    }
+}
@@ -0,0 +1,453 @@
+use crate::file_system_interaction::asset_loading::ConfigAssets;
+use crate::file_system_interaction::config::GameConfig;
+use crate::file_system_interaction::level_serialization::CurrentLevel;
+use crate::file_system_interaction::localization::Localization;
+use crate::file_system_interaction::storage::{get_data_dir, storage};
+use crate::level_instantiation::level_transition::LevelTransition;
+use crate::player_control::actions::{ActionsFrozen, UiAction};
+use crate::player_control::player_embodiment::Player;
+use crate::util::log_error::log_errors;
+use crate::world_interaction::checkpoint::CheckpointReached;
+use crate::GameState;
+use anyhow::{Context, Result};
+use bevy::prelude::*;
+use bevy::time::FixedTimestep;
+use bevy_egui::{egui, EguiContext};
+use leafwing_input_manager::prelude::ActionState;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// Times how fast the player gets through a level.
+/// The [`SpeedrunTimer`] starts when the player first gains control after the level was loaded and records a [`Split`]
+/// whenever they reach a checkpoint or enter a level portal. A [`TriggerAction::FinishLevel`](crate::world_interaction::trigger::TriggerAction::FinishLevel)
+/// stops it and stores the run as the new personal best of the level if it was faster, see [`get_best_run_path`].
+/// The timer counts fixed ticks of game time, so it stands exactly still while the game is paused or in a menu.
+/// Its overlay compares the splits against the personal best. It is shown if `speedrun.show_timer` is set and toggled with F11.
+/// Depending on `speedrun.restore_from_saves`, loading a save either continues the run stored in it or invalidates the current run.
+pub struct SpeedrunPlugin;
+
+impl Plugin for SpeedrunPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<LevelFinished>()
+            .init_resource::<SpeedrunTimer>()
+            .init_resource::<BestRun>()
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(restore_speedrun.pipe(log_errors))
+                    .with_system(start_speedrun.after(restore_speedrun))
+                    .with_system(record_splits.after(start_speedrun))
+                    .with_system(load_best_run.after(record_splits))
+                    .with_system(finish_speedrun.after(load_best_run))
+                    .with_system(show_speedrun_timer.pipe(log_errors)),
+            )
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(FixedTimestep::step(1. / TICKS_PER_SECOND as f64))
+                    .with_system(tick_speedrun),
+            );
+    }
+}
+
+/// Resolution of the [`SpeedrunTimer`]
+pub const TICKS_PER_SECOND: u32 = 100;
+
+/// Sent when the player reached the end of the level, e.g. by a [`TriggerAction::FinishLevel`](crate::world_interaction::trigger::TriggerAction::FinishLevel)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LevelFinished;
+
+/// The current run. Part of the save file.
+#[derive(Debug, Clone, PartialEq, Eq, Resource, Serialize, Deserialize, Default)]
+pub struct SpeedrunTimer {
+    /// Level the run started in, whose [`BestRun`] it is compared against
+    pub level: String,
+    pub state: RunState,
+    /// Time since the run started in ticks of 1 / [`TICKS_PER_SECOND`] seconds
+    pub ticks: u64,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub splits: Vec<Split>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum RunState {
+    /// Waiting for the player to gain control
+    #[default]
+    Waiting,
+    Running,
+    Finished,
+    /// A save was loaded during the run, so it does not count
+    Invalid,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Split {
+    pub kind: SplitKind,
+    /// Time of the run in ticks when the split was recorded
+    pub ticks: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SplitKind {
+    Checkpoint,
+    /// Entered a portal to the level with this name
+    LevelTransition(String),
+    Finish,
+}
+
+impl SpeedrunTimer {
+    pub fn is_running(&self) -> bool {
+        self.state == RunState::Running
+    }
+
+    fn split(&mut self, kind: SplitKind) {
+        self.splits.push(Split {
+            kind,
+            ticks: self.ticks,
+        });
+    }
+
+    /// Ticks by which the split at `index` was slower than the same split of the `best` run.
+    /// `None` if the best run has no such split, e.g. because it went through the levels in another order.
+    pub fn get_delta(&self, index: usize, best: &SpeedrunRecord) -> Option<i64> {
+        let split = self.splits.get(index)?;
+        let best_split = best.splits.get(index)?;
+        (split.kind == best_split.kind).then(|| split.ticks as i64 - best_split.ticks as i64)
+    }
+}
+
+/// A finished run as stored per level on disk
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub struct SpeedrunRecord {
+    /// The last split is always [`SplitKind::Finish`]
+    pub splits: Vec<Split>,
+}
+
+impl SpeedrunRecord {
+    pub fn total_ticks(&self) -> Option<u64> {
+        self.splits.last().map(|split| split.ticks)
+    }
+
+    pub fn is_beaten_by(&self, ticks: u64) -> bool {
+        self.total_ticks().map(|best| ticks < best).unwrap_or(true)
+    }
+}
+
+/// The personal best of the [`SpeedrunTimer::level`]
+#[derive(Debug, Clone, PartialEq, Eq, Resource, Default)]
+pub struct BestRun {
+    level: String,
+    pub record: Option<SpeedrunRecord>,
+    /// Whether the current run just replaced the record
+    pub is_new: bool,
+}
+
+/// The run from a loaded save, or `None` if the save was written before speedruns were timed.
+/// Replaces the current run if `speedrun.restore_from_saves` is set, otherwise it invalidates the current run.
+#[derive(Debug, Clone, PartialEq, Eq, Resource)]
+pub struct RestoredSpeedrun(pub Option<SpeedrunTimer>);
+
+/// Where the personal best of a level is stored. Relative on platforms without a data directory, e.g. the web.
+pub fn get_best_run_path(level: &str) -> PathBuf {
+    get_data_dir()
+        .join("speedruns")
+        .join(format!("{level}.ron"))
+}
+
+fn read_best_run(level: &str) -> Result<Option<SpeedrunRecord>> {
+    let path = get_best_run_path(level);
+    let storage = storage();
+    if !storage.is_file(&path) {
+        return Ok(None);
+    }
+    let serialized = storage.read(&path).context("Failed to read best run")?;
+    let serialized = String::from_utf8(serialized).context("Best run is not UTF-8")?;
+    let record = ron::from_str(&serialized).context("Failed to parse best run")?;
+    Ok(Some(record))
+}
+
+fn write_best_run(level: &str, record: &SpeedrunRecord) -> Result<()> {
+    let serialized =
+        ron::ser::to_string_pretty(record, default()).context("Failed to serialize best run")?;
+    storage()
+        .write(&get_best_run_path(level), serialized.as_bytes())
+        .context("Failed to write best run")
+}
+
+fn restore_speedrun(
+    mut commands: Commands,
+    restored_speedrun: Option<Res<RestoredSpeedrun>>,
+    mut timer: ResMut<SpeedrunTimer>,
+    config_handles: Res<ConfigAssets>,
+    config: Res<Assets<GameConfig>>,
+) -> Result<()> {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("restore_speedrun").entered();
+    let restored_speedrun = match restored_speedrun {
+        Some(restored_speedrun) => restored_speedrun,
+        None => return Ok(()),
+    };
+    let config = config
+        .get(&config_handles.game)
+        .context("Failed to get game config from handle")?;
+    match &restored_speedrun.0 {
+        Some(restored) if config.speedrun.restore_from_saves => *timer = restored.clone(),
+        _ => timer.state = RunState::Invalid,
+    }
+    commands.remove_resource::<RestoredSpeedrun>();
+    Ok(())
+}
+
+fn start_speedrun(
+    mut timer: ResMut<SpeedrunTimer>,
+    player_query: Query<(), With<Player>>,
+    current_level: Option<Res<CurrentLevel>>,
+    level_transition: Option<Res<LevelTransition>>,
+    actions_frozen: Res<ActionsFrozen>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("start_speedrun").entered();
+    if timer.state != RunState::Waiting
+        || player_query.is_empty()
+        || level_transition.is_some()
+        || actions_frozen.is_frozen()
+    {
+        return;
+    }
+    let current_level = match current_level {
+        Some(current_level) => current_level,
+        None => return,
+    };
+    *timer = SpeedrunTimer {
+        level: current_level.scene.clone(),
+        state: RunState::Running,
+        ..default()
+    };
+}
+
+/// Runs once per tick of game time, including while not playing, as the [`FixedTimestep`] keeps accumulating time then
+fn tick_speedrun(state: Res<State<GameState>>, mut timer: ResMut<SpeedrunTimer>) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("tick_speedrun").entered();
+    if *state.current() == GameState::Playing && timer.is_running() {
+        timer.ticks += 1;
+    }
+}
+
+fn record_splits(
+    mut timer: ResMut<SpeedrunTimer>,
+    mut checkpoint_events: EventReader<CheckpointReached>,
+    level_transition: Option<Res<LevelTransition>>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("record_splits").entered();
+    let checkpoints = checkpoint_events.iter().count();
+    if !timer.is_running() {
+        return;
+    }
+    for _ in 0..checkpoints {
+        timer.split(SplitKind::Checkpoint);
+    }
+    if let Some(level_transition) = level_transition {
+        if level_transition.is_added() {
+            timer.split(SplitKind::LevelTransition(
+                level_transition.portal.target_level.clone(),
+            ));
+        }
+    }
+}
+
+fn load_best_run(timer: Res<SpeedrunTimer>, mut best_run: ResMut<BestRun>) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("load_best_run").entered();
+    if timer.level.is_empty() || timer.level == best_run.level {
+        return;
+    }
+    let record = match read_best_run(&timer.level) {
+        Ok(record) => record,
+        Err(e) => {
+            warn!("Failed to read the best run of {}: {e:#}", timer.level);
+            None
+        }
+    };
+    *best_run = BestRun {
+        level: timer.level.clone(),
+        record,
+        is_new: false,
+    };
+}
+
+fn finish_speedrun(
+    mut finished_events: EventReader<LevelFinished>,
+    mut timer: ResMut<SpeedrunTimer>,
+    mut best_run: ResMut<BestRun>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("finish_speedrun").entered();
+    if finished_events.iter().count() == 0 || !timer.is_running() {
+        return;
+    }
+    timer.split(SplitKind::Finish);
+    timer.state = RunState::Finished;
+    let is_personal_best = best_run
+        .record
+        .as_ref()
+        .map(|record| record.is_beaten_by(timer.ticks))
+        .unwrap_or(true);
+    info!("Finished {} in {}", timer.level, format_ticks(timer.ticks));
+    if !is_personal_best {
+        return;
+    }
+    let record = SpeedrunRecord {
+        splits: timer.splits.clone(),
+    };
+    if let Err(e) = write_best_run(&timer.level, &record) {
+        error!(
+            "Failed to store the personal best of {}: {e:#}",
+            timer.level
+        );
+    }
+    best_run.record = Some(record);
+    best_run.is_new = true;
+}
+
+fn show_speedrun_timer(
+    timer: Res<SpeedrunTimer>,
+    best_run: Res<BestRun>,
+    actions: Query<&ActionState<UiAction>>,
+    mut egui_context: ResMut<EguiContext>,
+    localization: Res<Localization>,
+    config_handles: Res<ConfigAssets>,
+    config: Res<Assets<GameConfig>>,
+    mut visible: Local<Option<bool>>,
+) -> Result<()> {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("show_speedrun_timer").entered();
+    let config = config
+        .get(&config_handles.game)
+        .context("Failed to get game config from handle")?;
+    let visible = visible.get_or_insert(config.speedrun.show_timer);
+    if actions
+        .iter()
+        .any(|action| action.just_pressed(UiAction::ToggleSpeedrunTimer))
+    {
+        *visible = !*visible;
+    }
+    if !*visible {
+        return Ok(());
+    }
+
+    egui::Area::new("speedrun_timer")
+        .anchor(egui::Align2::RIGHT_TOP, egui::Vec2::new(-10., 10.))
+        .interactable(false)
+        .show(egui_context.ctx_mut(), |ui| {
+            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                ui.heading(egui::RichText::new(format_ticks(timer.ticks)).monospace());
+                match timer.state {
+                    RunState::Invalid => {
+                        ui.label(localization.get("speedrun.invalid"));
+                    }
+                    RunState::Finished if best_run.is_new => {
+                        ui.label(localization.get("speedrun.personal_best"));
+                    }
+                    _ => {}
+                }
+                egui::Grid::new("speedrun_splits").show(ui, |ui| {
+                    for (index, split) in timer.splits.iter().enumerate() {
+                        let name = match &split.kind {
+                            SplitKind::Checkpoint => localization.get("speedrun.checkpoint"),
+                            SplitKind::LevelTransition(level) => level.as_str(),
+                            SplitKind::Finish => localization.get("speedrun.finish"),
+                        };
+                        ui.label(name);
+                        ui.monospace(format_ticks(split.ticks));
+                        match best_run
+                            .record
+                            .as_ref()
+                            .and_then(|best| timer.get_delta(index, best))
+                        {
+                            Some(delta) => {
+                                let color = if delta <= 0 {
+                                    egui::Color32::from_rgb(80, 220, 80)
+                                } else {
+                                    egui::Color32::from_rgb(230, 80, 80)
+                                };
+                                ui.label(
+                                    egui::RichText::new(format_delta(delta))
+                                        .monospace()
+                                        .color(color),
+                                );
+                            }
+                            None => {
+                                ui.label("");
+                            }
+                        }
+                        ui.end_row();
+                    }
+                });
+            });
+        });
+    Ok(())
+}
+
+/// E.g. "1:02.35"
+pub fn format_ticks(ticks: u64) -> String {
+    let ticks_per_second = TICKS_PER_SECOND as u64;
+    let hundredths = ticks % ticks_per_second * 100 / ticks_per_second;
+    let seconds = ticks / ticks_per_second;
+    format!("{}:{:02}.{hundredths:02}", seconds / 60, seconds % 60)
+}
+
+/// E.g. "+0.42" or "-1:02.35"
+pub fn format_delta(ticks: i64) -> String {
+    let sign = if ticks <= 0 { '-' } else { '+' };
+    let formatted = format_ticks(ticks.unsigned_abs());
+    let formatted = formatted.strip_prefix("0:").unwrap_or(&formatted);
+    let formatted = formatted.strip_prefix('0').unwrap_or(formatted);
+    format!("{sign}{formatted}")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn formats_times() {
+        let second = TICKS_PER_SECOND as u64;
+        assert_eq!(format_ticks(0), "0:00.00");
+        assert_eq!(format_ticks(62 * second + second * 35 / 100), "1:02.35");
+        assert_eq!(format_delta(second * 42 / 100), "+0.42");
+        assert_eq!(format_delta(-12 * (second as i64)), "-12.00");
+        assert_eq!(format_delta(-62 * (second as i64)), "-1:02.00");
+    }
+
+    #[test]
+    fn compares_splits_with_the_best_run() {
+        let best = SpeedrunRecord {
+            splits: vec![
+                Split {
+                    kind: SplitKind::Checkpoint,
+                    ticks: 100,
+                },
+                Split {
+                    kind: SplitKind::Finish,
+                    ticks: 300,
+                },
+            ],
+        };
+        let mut timer = SpeedrunTimer {
+            state: RunState::Running,
+            ticks: 90,
+            ..default()
+        };
+        timer.split(SplitKind::Checkpoint);
+        timer.ticks = 250;
+        timer.split(SplitKind::LevelTransition("old_town".to_string()));
+        assert_eq!(timer.get_delta(0, &best), Some(-10));
+        // Different route
+        assert_eq!(timer.get_delta(1, &best), None);
+        assert_eq!(timer.get_delta(2, &best), None);
+
+        assert!(best.is_beaten_by(299));
+        assert!(!best.is_beaten_by(300));
+        assert!(SpeedrunRecord::default().is_beaten_by(u64::MAX));
+    }
+}
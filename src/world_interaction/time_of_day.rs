@@ -0,0 +1,281 @@
+use crate::file_system_interaction::asset_loading::ConfigAssets;
+use crate::file_system_interaction::config::{DayNight, GameConfig, LightingKeyframe};
+use crate::file_system_interaction::user_settings::UserSettings;
+use crate::level_instantiation::spawning::objects::sunlight::Sun;
+use crate::shader::{Materials, SkydomeMaterial};
+use crate::GameState;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::f32::consts::TAU;
+
+/// Advances the [`TimeOfDay`] while playing and lights the level accordingly:
+/// the [`Sun`] follows the sun or moon across the sky, and its color, the ambient light and the sky
+/// are interpolated between the [`DayNight::keyframes`] of the config.
+/// [`Condition::Day`](crate::world_interaction::condition::Condition::Day) and friends let dialogs and triggers depend on the time.
+pub struct TimeOfDayPlugin;
+
+impl Plugin for TimeOfDayPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<TimeOfDay>()
+            .init_resource::<TimeOfDay>()
+            .add_system_set(SystemSet::on_enter(GameState::Playing).with_system(reset_time_of_day))
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing).with_system(advance_time_of_day),
+            )
+            // Also runs while paused, so that changing the ambient brightness in the settings shows right away
+            .add_system(update_lighting.after(advance_time_of_day));
+    }
+}
+
+/// Hour at which the sun rises. It sets 12 hours later.
+pub const SUNRISE_HOUR: f32 = 6.;
+
+/// The in-game time, which is part of the save file
+#[derive(Debug, Clone, Copy, PartialEq, Resource, Reflect, Serialize, Deserialize)]
+#[reflect(Resource, Serialize, Deserialize)]
+pub struct TimeOfDay {
+    /// Hour in [0, 24)
+    pub hour: f32,
+    /// Whether the time stands still
+    #[serde(default)]
+    pub frozen: bool,
+}
+
+impl Default for TimeOfDay {
+    fn default() -> Self {
+        Self {
+            hour: 12.,
+            frozen: false,
+        }
+    }
+}
+
+impl TimeOfDay {
+    pub fn advance(&mut self, hours: f32) {
+        if !self.frozen {
+            self.hour = (self.hour + hours).rem_euclid(24.);
+        }
+    }
+
+    /// Whether the sun is above the horizon
+    pub fn is_day(&self) -> bool {
+        self.is_between(SUNRISE_HOUR, SUNRISE_HOUR + 12.)
+    }
+
+    /// Whether the hour is in [`from`, `to`), wrapping around midnight when `from` is later than `to`
+    pub fn is_between(&self, from: f32, to: f32) -> bool {
+        let hour = self.hour.rem_euclid(24.);
+        let (from, to) = (from.rem_euclid(24.), to.rem_euclid(24.));
+        if from <= to {
+            (from..to).contains(&hour)
+        } else {
+            hour >= from || hour < to
+        }
+    }
+}
+
+fn reset_time_of_day(
+    mut time_of_day: ResMut<TimeOfDay>,
+    config_handles: Res<ConfigAssets>,
+    config: Res<Assets<GameConfig>>,
+) {
+    // A save loaded afterwards restores its own time
+    if let Some(config) = config.get(&config_handles.game) {
+        *time_of_day = TimeOfDay {
+            hour: config.day_night.start_hour.rem_euclid(24.),
+            frozen: config.day_night.frozen,
+        };
+    }
+}
+
+fn advance_time_of_day(
+    time: Res<Time>,
+    mut time_of_day: ResMut<TimeOfDay>,
+    config_handles: Res<ConfigAssets>,
+    config: Res<Assets<GameConfig>>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("advance_time_of_day").entered();
+    if time_of_day.frozen {
+        return;
+    }
+    if let Some(config) = config.get(&config_handles.game) {
+        time_of_day.advance(time.delta_seconds() * config.day_night.hours_per_second);
+    }
+}
+
+fn update_lighting(
+    time_of_day: Res<TimeOfDay>,
+    settings: Res<UserSettings>,
+    config_handles: Option<Res<ConfigAssets>>,
+    config: Res<Assets<GameConfig>>,
+    materials: Option<Res<Materials>>,
+    mut skydome_materials: ResMut<Assets<SkydomeMaterial>>,
+    mut ambient_light: ResMut<AmbientLight>,
+    mut sun_query: Query<(&mut DirectionalLight, &mut Transform), With<Sun>>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("update_lighting").entered();
+    // The config is only available after loading
+    let config = match config_handles.and_then(|handles| config.get(&handles.game)) {
+        Some(config) => config,
+        None => return,
+    };
+    let day_night = &config.day_night;
+    let keyframe = match sample_keyframes(&day_night.keyframes, time_of_day.hour) {
+        Some(keyframe) => keyframe,
+        None => return,
+    };
+
+    let light_direction = get_light_direction(day_night, time_of_day.hour);
+    let elevation = light_direction.y.asin().to_degrees();
+    let horizon_factor = smoothstep(elevation / day_night.horizon_fade.max(f32::EPSILON));
+    for (mut light, mut transform) in sun_query.iter_mut() {
+        light.color = to_color(keyframe.light_color);
+        light.illuminance = keyframe.light_illuminance * horizon_factor;
+        transform.rotation = Transform::IDENTITY
+            .looking_at(-light_direction, Vec3::Y)
+            .rotation;
+    }
+
+    let quality = settings.graphics.quality_or(&config.quality);
+    ambient_light.color = to_color(keyframe.ambient_color);
+    ambient_light.brightness = quality.ambient_brightness * keyframe.ambient_brightness;
+
+    let zenith_color = to_color(keyframe.sky_zenith_color);
+    let horizon_color = to_color(keyframe.sky_horizon_color);
+    if let Some(materials) = materials {
+        let needs_update = skydome_materials
+            .get(&materials.skydome)
+            .map(|material| {
+                material.zenith_color != zenith_color || material.horizon_color != horizon_color
+            })
+            .unwrap_or_default();
+        // Getting the material mutably uploads it to the GPU again
+        if needs_update {
+            if let Some(material) = skydome_materials.get_mut(&materials.skydome) {
+                material.zenith_color = zenith_color;
+                material.horizon_color = horizon_color;
+            }
+        }
+    }
+}
+
+/// Direction towards the sun or, while the sun is below the horizon, towards the moon opposite to it
+fn get_light_direction(day_night: &DayNight, hour: f32) -> Vec3 {
+    // Zero at sunrise in the east, a quarter turn at noon
+    let angle = (hour - SUNRISE_HOUR) / 24. * TAU;
+    // Straight up would make the light's orientation undefined
+    let max_elevation = day_night.max_sun_elevation.clamp(0., 89.).to_radians();
+    let sun_direction = Quat::from_rotation_y(day_night.sun_azimuth.to_radians())
+        * Vec3::new(
+            angle.cos(),
+            angle.sin() * max_elevation.sin(),
+            angle.sin() * max_elevation.cos(),
+        );
+    if sun_direction.y >= 0. {
+        sun_direction
+    } else {
+        -sun_direction
+    }
+}
+
+/// The lighting at the hour, interpolated between the keyframes before and after it
+fn sample_keyframes(keyframes: &[LightingKeyframe], hour: f32) -> Option<LightingKeyframe> {
+    let hour = hour.rem_euclid(24.);
+    let since = |keyframe: &LightingKeyframe| (hour - keyframe.hour).rem_euclid(24.);
+    let until = |keyframe: &LightingKeyframe| (keyframe.hour - hour).rem_euclid(24.);
+    let previous = keyframes
+        .iter()
+        .min_by(|a, b| since(a).total_cmp(&since(b)))?;
+    let next = keyframes
+        .iter()
+        .filter(|keyframe| until(keyframe) > 0.)
+        .min_by(|a, b| until(a).total_cmp(&until(b)))
+        .unwrap_or(previous);
+    let span = since(previous) + until(next);
+    let t = if span > 0. {
+        since(previous) / span
+    } else {
+        0.
+    };
+    Some(LightingKeyframe {
+        hour,
+        light_color: lerp_color(previous.light_color, next.light_color, t),
+        light_illuminance: lerp(previous.light_illuminance, next.light_illuminance, t),
+        ambient_color: lerp_color(previous.ambient_color, next.ambient_color, t),
+        ambient_brightness: lerp(previous.ambient_brightness, next.ambient_brightness, t),
+        sky_zenith_color: lerp_color(previous.sky_zenith_color, next.sky_zenith_color, t),
+        sky_horizon_color: lerp_color(previous.sky_horizon_color, next.sky_horizon_color, t),
+    })
+}
+
+fn lerp(from: f32, to: f32, t: f32) -> f32 {
+    from + (to - from) * t
+}
+
+fn lerp_color(from: [f32; 3], to: [f32; 3], t: f32) -> [f32; 3] {
+    Vec3::from(from).lerp(Vec3::from(to), t).into()
+}
+
+fn smoothstep(x: f32) -> f32 {
+    let x = x.clamp(0., 1.);
+    x * x * (3. - 2. * x)
+}
+
+fn to_color([red, green, blue]: [f32; 3]) -> Color {
+    Color::rgb(red, green, blue)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn keyframe(hour: f32, light_illuminance: f32) -> LightingKeyframe {
+        LightingKeyframe {
+            hour,
+            light_illuminance,
+            ..default()
+        }
+    }
+
+    #[test]
+    fn keyframes_are_interpolated_over_midnight() {
+        let keyframes = [keyframe(6., 100.), keyframe(22., 0.)];
+        let sample = |hour| {
+            sample_keyframes(&keyframes, hour)
+                .unwrap()
+                .light_illuminance
+        };
+        assert_eq!(sample(6.), 100.);
+        assert_eq!(sample(14.), 50.);
+        assert_eq!(sample(22.), 0.);
+        assert_eq!(sample(2.), 50.);
+        assert_eq!(sample(26.), 50.);
+    }
+
+    #[test]
+    fn light_stays_above_horizon() {
+        let day_night = DayNight::default();
+        for hour in 0..24 {
+            let direction = get_light_direction(&day_night, hour as f32);
+            assert!(direction.y >= 0., "Light below horizon at {hour}:00");
+        }
+        let sunrise = get_light_direction(&day_night, SUNRISE_HOUR - 0.01);
+        let after_sunrise = get_light_direction(&day_night, SUNRISE_HOUR + 0.01);
+        assert!(sunrise.y < 0.01 && after_sunrise.y < 0.01);
+    }
+
+    #[test]
+    fn time_ranges_wrap_around_midnight() {
+        let time_of_day = |hour| TimeOfDay {
+            hour,
+            frozen: false,
+        };
+        assert!(time_of_day(23.).is_between(22., 4.));
+        assert!(time_of_day(1.).is_between(22., 4.));
+        assert!(!time_of_day(12.).is_between(22., 4.));
+        assert!(time_of_day(12.).is_day());
+        assert!(!time_of_day(3.).is_day());
+    }
+}
@@ -0,0 +1,442 @@
+use crate::file_system_interaction::audio::{AudioEmitter, SfxChannel};
+use crate::file_system_interaction::level_serialization::CurrentLevel;
+use crate::level_instantiation::spawning::objects::GameCollisionGroup;
+use crate::level_instantiation::spawning::{SpawnId, SpawnRequest};
+use crate::movement::general_movement::Walking;
+use crate::player_control::player_embodiment::Player;
+use crate::world_interaction::checkpoint::PlayerRespawnRequest;
+use crate::world_interaction::condition::{
+    ActiveConditions, Condition, ConditionAddEvent, ConditionId,
+};
+use crate::world_interaction::dialog::{DialogEvent, DialogId};
+use crate::world_interaction::health::DamageEvent;
+use crate::world_interaction::inventory::{Inventory, ItemEffect};
+use crate::world_interaction::speedrun::LevelFinished;
+use crate::world_interaction::time_of_day::TimeOfDay;
+use crate::GameState;
+use bevy::prelude::*;
+use bevy::utils::HashSet;
+use bevy_kira_audio::prelude::AudioChannel;
+use bevy_rapier3d::prelude::*;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::iter;
+use std::sync::LazyLock;
+
+/// Invisible volumes that run [`TriggerAction`]s when the player enters or leaves them.
+/// A trigger is either spawned as a [`GameObject::Trigger`](crate::level_instantiation::spawning::GameObject::Trigger)
+/// with [`SpawnEvent::trigger`](crate::level_instantiation::spawning::SpawnEvent::trigger) set, or read from a node in the level scene named
+/// `Trigger.<action>.<value>`, e.g. `Trigger.dialog.follower`, `Trigger.flag.entered_square`,
+/// `Trigger.sound.audio/bell.ogg`, `Trigger.event.open_gate` or `Trigger.damage.1000` for a kill volume.
+/// Such nodes fire once unless their name contains `[repeat]`.
+/// The volume is a box with half extents of 1 m, scaled by the trigger's transform.
+/// One-shot triggers that fired are remembered in [`FiredTriggers`], which is part of the save file.
+pub struct TriggerPlugin;
+
+impl Plugin for TriggerPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Trigger>()
+            .register_type::<FiredTriggers>()
+            .init_resource::<FiredTriggers>()
+            .add_event::<TriggerEvent>()
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(read_trigger_nodes)
+                    .with_system(init_triggers.after(read_trigger_nodes))
+                    .with_system(detect_trigger_activations)
+                    .with_system(run_trigger_actions.after(detect_trigger_activations)),
+            );
+    }
+}
+
+#[derive(
+    Debug, Clone, PartialEq, Default, Component, Reflect, FromReflect, Serialize, Deserialize,
+)]
+#[reflect(Component, Serialize, Deserialize)]
+pub struct Trigger {
+    /// Identifies the trigger in [`FiredTriggers`]. Defaults to the trigger's [`SpawnId`] or, for scene nodes, its name.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub on_enter: Vec<TriggerAction>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub on_exit: Vec<TriggerAction>,
+    /// Whether the trigger fires every time it is entered instead of only the first time
+    #[serde(default)]
+    pub repeatable: bool,
+    /// Time in seconds between entering or leaving the trigger and its actions being run
+    #[serde(default)]
+    pub delay: f32,
+    #[serde(default)]
+    pub activated_by: TriggerActivator,
+    /// Entering the trigger only fires it while this is met, e.g. [`Condition::Night`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub condition: Option<Condition>,
+}
+
+/// What a trigger does when it fires
+#[derive(Debug, Clone, PartialEq, Reflect, FromReflect, Serialize, Deserialize)]
+#[reflect(Serialize, Deserialize)]
+pub enum TriggerAction {
+    /// Starts the dialog with the trigger as its source
+    StartDialog(DialogId),
+    SetFlag(ConditionId),
+    /// Plays the sound at the given path at the trigger's position
+    PlaySound(String),
+    Spawn(SpawnRequest),
+    Item(ItemEffect),
+    /// Sends a [`TriggerEvent`] with this name
+    Event(String),
+    /// Sends the player back to the last checkpoint, e.g. for a kill volume below a bridge
+    Respawn,
+    /// Hurts whoever entered or left the trigger by this amount, see [`DamageEvent`]
+    Damage(f32),
+    /// Sends [`LevelFinished`], which stops the speedrun timer
+    FinishLevel,
+}
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Default, Reflect, FromReflect, Serialize, Deserialize,
+)]
+#[reflect(Serialize, Deserialize)]
+pub enum TriggerActivator {
+    #[default]
+    Player,
+    /// The player and NPCs
+    Character,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TriggerEdge {
+    Enter,
+    Exit,
+}
+
+/// Sent by [`TriggerAction::Event`] for game code to react to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TriggerEvent {
+    pub name: String,
+    pub trigger: Entity,
+    /// The character that entered or left the trigger
+    pub activator: Entity,
+    pub edge: TriggerEdge,
+}
+
+/// Ids of the one-shot triggers that already fired
+#[derive(Debug, Clone, Eq, PartialEq, Resource, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Resource, Serialize, Deserialize)]
+pub struct FiredTriggers(pub HashSet<String>);
+
+impl FiredTriggers {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Component, Default)]
+pub struct TriggerState {
+    /// Key of the trigger in [`FiredTriggers`]
+    pub key: Option<String>,
+    /// Characters currently inside the trigger
+    pub occupants: HashSet<Entity>,
+    /// Activations that wait for [`Trigger::delay`] to pass
+    pending: Vec<PendingActivation>,
+    /// Whether this one-shot trigger fired since it was spawned
+    fired: bool,
+    /// Whether the current occupants fired this trigger, so that its exit actions run once they leave
+    exit_armed: bool,
+}
+
+impl TriggerState {
+    pub fn is_occupied(&self) -> bool {
+        !self.occupants.is_empty()
+    }
+
+    pub fn has_fired(&self, fired_triggers: &FiredTriggers) -> bool {
+        self.fired
+            || self
+                .key
+                .as_ref()
+                .map(|key| fired_triggers.0.contains(key))
+                .unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct PendingActivation {
+    edge: TriggerEdge,
+    activator: Entity,
+    remaining_delay: f32,
+}
+
+static TRIGGER_NODE_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)^trigger\.(dialog|flag|sound|event|damage)\.([^\s\[]+)")
+        .expect("Failed to compile trigger node regex")
+});
+
+fn read_trigger_nodes(
+    mut commands: Commands,
+    added_name: Query<(Entity, &Name), (Added<Name>, Without<Trigger>)>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("read_trigger_nodes").entered();
+    for (entity, name) in &added_name {
+        if let Some(trigger) = parse_trigger_node(name) {
+            commands.entity(entity).insert(trigger);
+        }
+    }
+}
+
+fn parse_trigger_node(name: &str) -> Option<Trigger> {
+    let captures = TRIGGER_NODE_REGEX.captures(name)?;
+    let value = captures[2].to_string();
+    let action = match captures[1].to_lowercase().as_str() {
+        "dialog" => TriggerAction::StartDialog(DialogId(value)),
+        "flag" => TriggerAction::SetFlag(ConditionId(value)),
+        "sound" => TriggerAction::PlaySound(value),
+        "damage" => TriggerAction::Damage(value.parse().ok()?),
+        _ => TriggerAction::Event(value),
+    };
+    Some(Trigger {
+        on_enter: vec![action],
+        repeatable: name.to_lowercase().contains("[repeat]"),
+        ..default()
+    })
+}
+
+fn init_triggers(
+    mut commands: Commands,
+    added_triggers: Query<
+        (
+            Entity,
+            &Trigger,
+            Option<&SpawnId>,
+            Option<&Name>,
+            Option<&Collider>,
+        ),
+        Added<Trigger>,
+    >,
+    current_level: Option<Res<CurrentLevel>>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("init_triggers").entered();
+    for (entity, trigger, spawn_id, name, collider) in &added_triggers {
+        // Fallback keys are only unique within their level
+        let level = current_level
+            .as_ref()
+            .map(|level| level.scene.as_str())
+            .unwrap_or_default();
+        let key = trigger
+            .id
+            .clone()
+            .or_else(|| spawn_id.map(|spawn_id| format!("{level}.{spawn_id:?}")))
+            .or_else(|| name.map(|name| format!("{level}.{name}")));
+        if !trigger.repeatable && key.is_none() {
+            warn!("One-shot trigger {entity:?} has no id, so it will fire again after loading");
+        }
+        let filter = match trigger.activated_by {
+            TriggerActivator::Player => GameCollisionGroup::PLAYER,
+            TriggerActivator::Character => GameCollisionGroup::ALL,
+        };
+        let mut entity_commands = commands.entity(entity);
+        entity_commands.insert((
+            TriggerState { key, ..default() },
+            Sensor,
+            ActiveEvents::COLLISION_EVENTS,
+            CollisionGroups::new(GameCollisionGroup::OTHER.into(), filter.into()),
+            AudioEmitter::default(),
+        ));
+        if collider.is_none() {
+            entity_commands.insert(Collider::cuboid(1., 1., 1.));
+        }
+    }
+}
+
+fn detect_trigger_activations(
+    mut collision_events: EventReader<CollisionEvent>,
+    mut trigger_query: Query<(&Trigger, &mut TriggerState)>,
+    activator_query: Query<(Option<&Player>, Option<&Walking>)>,
+    parent_query: Query<&Parent>,
+    mut fired_triggers: ResMut<FiredTriggers>,
+    active_conditions: Res<ActiveConditions>,
+    inventory: Res<Inventory>,
+    time_of_day: Res<TimeOfDay>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("detect_trigger_activations").entered();
+    for event in collision_events.iter() {
+        let (entity_a, entity_b, entered) = match event {
+            CollisionEvent::Started(entity_a, entity_b, _) => (*entity_a, *entity_b, true),
+            CollisionEvent::Stopped(entity_a, entity_b, _) => (*entity_a, *entity_b, false),
+        };
+        let (trigger_entity, other) = if trigger_query.contains(entity_a) {
+            (entity_a, entity_b)
+        } else if trigger_query.contains(entity_b) {
+            (entity_b, entity_a)
+        } else {
+            continue;
+        };
+        let (trigger, mut state) = trigger_query
+            .get_mut(trigger_entity)
+            .expect("Trigger query changed while handling collision event");
+        let activator = iter::successors(Some(other), |entity| {
+            parent_query.get(*entity).ok().map(|parent| parent.get())
+        })
+        .find(|entity| match activator_query.get(*entity) {
+            Ok((player, walking)) => match trigger.activated_by {
+                TriggerActivator::Player => player.is_some(),
+                TriggerActivator::Character => player.is_some() || walking.is_some(),
+            },
+            Err(_) => false,
+        });
+        let activator = match activator {
+            Some(activator) => activator,
+            None => continue,
+        };
+
+        let was_occupied = state.is_occupied();
+        if entered {
+            state.occupants.insert(activator);
+        } else {
+            state.occupants.remove(&activator);
+        }
+        let edge = match (was_occupied, state.is_occupied()) {
+            (false, true) => TriggerEdge::Enter,
+            (true, false) => TriggerEdge::Exit,
+            _ => continue,
+        };
+        match edge {
+            TriggerEdge::Enter => {
+                if !trigger.repeatable && state.has_fired(&fired_triggers) {
+                    continue;
+                }
+                let condition_met = trigger
+                    .condition
+                    .as_ref()
+                    .map(|condition| condition.is_met(&active_conditions, &inventory, &time_of_day))
+                    .unwrap_or(true);
+                if !condition_met {
+                    continue;
+                }
+                if !trigger.repeatable {
+                    if let Some(key) = &state.key {
+                        fired_triggers.0.insert(key.clone());
+                    }
+                    state.fired = true;
+                }
+                state.exit_armed = true;
+            }
+            // Leaving only runs the exit actions if entering fired the trigger
+            TriggerEdge::Exit => {
+                if !state.exit_armed {
+                    continue;
+                }
+                state.exit_armed = false;
+            }
+        }
+        let remaining_delay = trigger.delay;
+        state.pending.push(PendingActivation {
+            edge,
+            activator,
+            remaining_delay,
+        });
+    }
+}
+
+fn run_trigger_actions(
+    time: Res<Time>,
+    mut trigger_query: Query<(Entity, &Trigger, &mut TriggerState, &mut AudioEmitter)>,
+    mut dialog_events: EventWriter<DialogEvent>,
+    mut condition_events: EventWriter<ConditionAddEvent>,
+    mut spawn_requests: EventWriter<SpawnRequest>,
+    mut item_effects: EventWriter<ItemEffect>,
+    mut trigger_events: EventWriter<TriggerEvent>,
+    mut respawn_requests: EventWriter<PlayerRespawnRequest>,
+    mut damage_events: EventWriter<DamageEvent>,
+    mut level_finished_events: EventWriter<LevelFinished>,
+    asset_server: Res<AssetServer>,
+    sfx: Res<AudioChannel<SfxChannel>>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("run_trigger_actions").entered();
+    let dt = time.delta_seconds();
+    for (entity, trigger, mut state, mut audio_emitter) in &mut trigger_query {
+        if state.pending.is_empty() {
+            continue;
+        }
+        for pending in state.pending.iter_mut() {
+            pending.remaining_delay -= dt;
+        }
+        let (due, waiting): (Vec<_>, Vec<_>) = state
+            .pending
+            .drain(..)
+            .partition(|pending| pending.remaining_delay <= 0.);
+        state.pending = waiting;
+        for activation in due {
+            let actions = match activation.edge {
+                TriggerEdge::Enter => &trigger.on_enter,
+                TriggerEdge::Exit => &trigger.on_exit,
+            };
+            for action in actions {
+                match action {
+                    TriggerAction::StartDialog(dialog) => dialog_events.send(DialogEvent {
+                        dialog: dialog.clone(),
+                        source: entity,
+                        page: None,
+                    }),
+                    TriggerAction::SetFlag(flag) => {
+                        condition_events.send(ConditionAddEvent(flag.clone()))
+                    }
+                    TriggerAction::PlaySound(path) => {
+                        audio_emitter.play(&sfx, asset_server.load(path.as_str()), 1.);
+                    }
+                    TriggerAction::Spawn(request) => spawn_requests.send(request.clone()),
+                    TriggerAction::Item(effect) => item_effects.send(effect.clone()),
+                    TriggerAction::Event(name) => trigger_events.send(TriggerEvent {
+                        name: name.clone(),
+                        trigger: entity,
+                        activator: activation.activator,
+                        edge: activation.edge,
+                    }),
+                    TriggerAction::Respawn => respawn_requests.send(PlayerRespawnRequest),
+                    TriggerAction::Damage(amount) => damage_events.send(DamageEvent {
+                        target: activation.activator,
+                        amount: *amount,
+                    }),
+                    TriggerAction::FinishLevel => level_finished_events.send(LevelFinished),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parses_trigger_nodes() {
+        let trigger = parse_trigger_node("Trigger.sound.audio/Bell.ogg [repeat]").unwrap();
+        assert_eq!(
+            trigger.on_enter,
+            vec![TriggerAction::PlaySound("audio/Bell.ogg".to_string())]
+        );
+        assert!(trigger.repeatable);
+
+        let trigger = parse_trigger_node("trigger.flag.entered_square").unwrap();
+        assert_eq!(
+            trigger.on_enter,
+            vec![TriggerAction::SetFlag(ConditionId(
+                "entered_square".to_string()
+            ))]
+        );
+        assert!(!trigger.repeatable);
+
+        let trigger = parse_trigger_node("Trigger.damage.1000 [repeat]").unwrap();
+        assert_eq!(trigger.on_enter, vec![TriggerAction::Damage(1000.)]);
+        assert!(parse_trigger_node("Trigger.damage.lots").is_none());
+
+        assert!(parse_trigger_node("Trigger.teleport.somewhere").is_none());
+        assert!(parse_trigger_node("Fountain [collider]").is_none());
+    }
+}
@@ -0,0 +1,165 @@
+use crate::file_system_interaction::asset_loading::ConfigAssets;
+use crate::file_system_interaction::config::{GameConfig, WindBaseline};
+use crate::GameState;
+use bevy::prelude::*;
+
+/// Keeps track of the [`Wind`] that sways the foliage, see [`FoliageMaterial`](crate::shader::FoliageMaterial).
+/// The wind varies smoothly around the [`WindBaseline`] of the config. Send a [`WindGust`] to blow on top of it for a while,
+/// e.g. when something explodes or drives by.
+pub struct WindPlugin;
+
+impl Plugin for WindPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Wind>()
+            .add_event::<WindGust>()
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(receive_wind_gusts)
+                    .with_system(update_wind.after(receive_wind_gusts)),
+            );
+    }
+}
+
+/// The wind blowing right now
+#[derive(Debug, Clone, PartialEq, Resource, Default)]
+pub struct Wind {
+    /// Horizontal direction the wind blows towards as (x, z), normalized
+    pub direction: Vec2,
+    /// How far in m the tip of a plant 1 m tall is pushed
+    pub strength: f32,
+    /// Sways per second
+    pub frequency: f32,
+    gusts: Vec<ActiveGust>,
+}
+
+/// A temporary blow of wind that adds to the [`Wind`] and fades out over its duration
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WindGust {
+    /// Direction the gust blows towards. Only the horizontal part is used.
+    pub direction: Vec3,
+    /// Strength at the start of the gust, like [`Wind::strength`]
+    pub strength: f32,
+    /// Time in seconds until the gust faded out
+    pub duration: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ActiveGust {
+    gust: WindGust,
+    remaining: f32,
+}
+
+impl ActiveGust {
+    /// The gust's horizontal contribution to the wind, fading out linearly
+    fn get_velocity(&self) -> Vec2 {
+        let direction = Vec2::new(self.gust.direction.x, self.gust.direction.z).normalize_or_zero();
+        let fade = if self.gust.duration > 0. {
+            (self.remaining / self.gust.duration).clamp(0., 1.)
+        } else {
+            0.
+        };
+        direction * self.gust.strength * fade
+    }
+}
+
+fn receive_wind_gusts(mut wind: ResMut<Wind>, mut gusts: EventReader<WindGust>) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("receive_wind_gusts").entered();
+    for gust in gusts.iter() {
+        wind.gusts.push(ActiveGust {
+            gust: *gust,
+            remaining: gust.duration,
+        });
+    }
+}
+
+fn update_wind(
+    time: Res<Time>,
+    mut wind: ResMut<Wind>,
+    config_handles: Res<ConfigAssets>,
+    config: Res<Assets<GameConfig>>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("update_wind").entered();
+    let config = match config.get(&config_handles.game) {
+        Some(config) => config,
+        None => return,
+    };
+    let dt = time.delta_seconds();
+    for gust in wind.gusts.iter_mut() {
+        gust.remaining -= dt;
+    }
+    wind.gusts.retain(|gust| gust.remaining > 0.);
+
+    let baseline = get_baseline_velocity(&config.wind, time.elapsed_seconds());
+    let velocity = wind
+        .gusts
+        .iter()
+        .fold(baseline, |velocity, gust| velocity + gust.get_velocity());
+    // Keep the direction when the gusts happen to cancel out the wind, so that the foliage does not jump
+    if let Some(direction) = velocity.try_normalize() {
+        wind.direction = direction;
+    }
+    wind.strength = velocity.length();
+    wind.frequency = config.wind.frequency;
+}
+
+/// The wind without gusts at the given time in seconds as horizontal velocity
+fn get_baseline_velocity(baseline: &WindBaseline, time: f32) -> Vec2 {
+    let t = time * baseline.variation_speed;
+    let strength = baseline.strength * (1. + baseline.strength_variation * noise(t)).max(0.);
+    // Far enough from the strength's samples to be unrelated to them
+    let angle = baseline.direction + baseline.direction_variation * noise(t + 1000.);
+    Vec2::from_angle(angle.to_radians()) * strength
+}
+
+/// Smooth value noise in [-1, 1] that changes about once per unit of `x`
+fn noise(x: f32) -> f32 {
+    let cell = x.floor();
+    let t = x - cell;
+    let t = t * t * (3. - 2. * t);
+    let cell = cell as i32;
+    let (a, b) = (hash(cell), hash(cell.wrapping_add(1)));
+    a + (b - a) * t
+}
+
+/// Pseudo-random number in [-1, 1] for an integer
+fn hash(n: i32) -> f32 {
+    let n = n as u32;
+    let n = (n << 13) ^ n;
+    let n = n
+        .wrapping_mul(n.wrapping_mul(n).wrapping_mul(15731).wrapping_add(789_221))
+        .wrapping_add(1_376_312_589);
+    1. - (n & 0x7fff_ffff) as f32 / 1_073_741_824.
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn noise_is_smooth_and_bounded() {
+        let mut previous = noise(0.);
+        for i in 1..10_000 {
+            let value = noise(i as f32 * 0.01);
+            assert!((-1. ..=1.).contains(&value));
+            assert!((value - previous).abs() < 0.1, "Noise jumps at {i}");
+            previous = value;
+        }
+    }
+
+    #[test]
+    fn gusts_fade_out() {
+        let gust = |remaining| ActiveGust {
+            gust: WindGust {
+                direction: Vec3::new(0., 5., 2.),
+                strength: 1.,
+                duration: 2.,
+            },
+            remaining,
+        };
+        assert_eq!(gust(2.).get_velocity(), Vec2::new(0., 1.));
+        assert_eq!(gust(1.).get_velocity(), Vec2::new(0., 0.5));
+        assert_eq!(gust(0.).get_velocity(), Vec2::ZERO);
+    }
+}